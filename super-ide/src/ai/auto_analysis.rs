@@ -0,0 +1,138 @@
+//! Gates on automatic analysis
+//!
+//! Analysis costs tokens, so analyses nobody asked for, such as the editor's
+//! background pass over a file, only run on files that pass the configured gates:
+//! a line limit, a language allowlist and ignore globs. Explicit analysis goes
+//! through `advanced_analyze_code` and is never gated.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::{AiEngine, AnalysisResult};
+use crate::config::AutoAnalysisSettings;
+
+/// Why a file was not analyzed automatically
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum SkipReason {
+    TooManyLines { lines: usize, limit: usize },
+    LanguageNotAllowed { language: String },
+    Ignored { pattern: String },
+}
+
+/// Outcome of an automatic analysis; exactly one of `analysis` and `skipped` is set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutoAnalysis {
+    pub analysis: Option<AnalysisResult>,
+    pub skipped: Option<SkipReason>,
+}
+
+/// The gates a file must pass to be analyzed automatically
+#[derive(Debug, Clone, Default)]
+pub struct AnalysisGates {
+    max_lines: Option<usize>,
+    /// Lowercased
+    languages: Vec<String>,
+    ignore: Vec<glob::Pattern>,
+    /// Root that ignore globs are relative to
+    workspace: Option<PathBuf>,
+}
+
+impl AnalysisGates {
+    /// Gates from the settings; ignore globs that do not parse are dropped, as
+    /// configuration validation has already reported them
+    pub fn new(settings: &AutoAnalysisSettings) -> Self {
+        Self {
+            max_lines: settings.max_lines,
+            languages: settings.languages.iter().map(|language| language.to_lowercase()).collect(),
+            ignore: settings.ignore.iter().filter_map(|pattern| glob::Pattern::new(pattern).ok()).collect(),
+            workspace: None,
+        }
+    }
+
+    /// Match ignore globs against paths relative to `workspace`, so `target/**`
+    /// also covers the absolute paths of open documents
+    pub fn with_workspace(mut self, workspace: PathBuf) -> Self {
+        self.workspace = Some(workspace);
+        self
+    }
+
+    /// Why `code` should not be analyzed automatically, if it should not. Ignore
+    /// globs are matched against the path within the workspace and against its
+    /// file name.
+    pub fn check(&self, code: &str, language: &str, file_path: Option<&str>) -> Option<SkipReason> {
+        if let Some(path) = file_path {
+            let path = self.workspace.as_deref()
+                .and_then(|workspace| Path::new(path).strip_prefix(workspace).ok())
+                .and_then(|relative| relative.to_str())
+                .unwrap_or(path);
+            let name = Path::new(path).file_name().and_then(|name| name.to_str()).unwrap_or(path);
+            if let Some(pattern) = self.ignore.iter().find(|pattern| pattern.matches(path) || pattern.matches(name)) {
+                return Some(SkipReason::Ignored { pattern: pattern.to_string() });
+            }
+        }
+        if !self.languages.is_empty() && !self.languages.contains(&language.to_lowercase()) {
+            return Some(SkipReason::LanguageNotAllowed { language: language.to_string() });
+        }
+        let lines = code.lines().count();
+        match self.max_lines {
+            Some(limit) if lines > limit => Some(SkipReason::TooManyLines { lines, limit }),
+            _ => None,
+        }
+    }
+}
+
+impl AiEngine {
+    /// Gate automatic analysis with `gates`
+    pub fn with_analysis_gates(mut self, gates: AnalysisGates) -> Self {
+        self.analysis_gates = gates;
+        self
+    }
+
+    /// Analyze `code` for a background caller, unless the gates rule it out
+    pub async fn auto_analyze(&self, code: &str, language: &str, file_path: Option<&str>) -> Result<AutoAnalysis> {
+        if let Some(reason) = self.analysis_gates.check(code, language, file_path) {
+            log::debug!("Skipping automatic analysis of {}: {:?}", file_path.unwrap_or("<buffer>"), reason);
+            return Ok(AutoAnalysis { analysis: None, skipped: Some(reason) });
+        }
+        let analysis = self.advanced_analyze_code(code, language, file_path).await?;
+        Ok(AutoAnalysis { analysis: Some(analysis), skipped: None })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::tests::test_config;
+
+    #[tokio::test]
+    async fn test_line_gate_skips_background_analysis_only() {
+        let engine = AiEngine::new_async(test_config("local", None)).await.unwrap().with_analysis_gates(AnalysisGates::new(&AutoAnalysisSettings {
+            max_lines: Some(3),
+            languages: vec!["Rust".to_string()],
+            ignore: vec!["*.min.js".to_string(), "target/**".to_string()],
+        }));
+        let code = "fn main() {\n    let value = input.unwrap();\n    let text = value.expect();\n    run(text);\n}";
+
+        let auto = engine.auto_analyze(code, "rust", Some("src/main.rs")).await.unwrap();
+        assert_eq!(auto.skipped, Some(SkipReason::TooManyLines { lines: 5, limit: 3 }));
+        assert!(auto.analysis.is_none());
+        let explicit = engine.advanced_analyze_code(code, "rust", Some("src/main.rs")).await.unwrap();
+        assert!(!explicit.bug_predictions.is_empty());
+
+        let short = "fn main() {\n    input.unwrap();\n}";
+        assert!(engine.auto_analyze(short, "rust", Some("src/main.rs")).await.unwrap().analysis.is_some());
+        let gates = &engine.analysis_gates;
+        assert_eq!(gates.check(short, "python", None), Some(SkipReason::LanguageNotAllowed { language: "python".to_string() }));
+        assert_eq!(gates.check(short, "rust", Some("web/app.min.js")), Some(SkipReason::Ignored { pattern: "*.min.js".to_string() }));
+        assert!(gates.check(short, "rust", Some("target/debug/build.rs")).is_some());
+
+        let gates = gates.clone().with_workspace(PathBuf::from("/home/dev/project"));
+        assert_eq!(
+            gates.check(short, "rust", Some("/home/dev/project/target/debug/build.rs")),
+            Some(SkipReason::Ignored { pattern: "target/**".to_string() })
+        );
+        assert!(gates.check(short, "rust", Some("/home/dev/project/src/target.rs")).is_none());
+    }
+}
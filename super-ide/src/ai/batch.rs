@@ -0,0 +1,147 @@
+//! Batch analysis
+//!
+//! Analyzes every file of a changeset in one call, as a pull request review needs.
+//! Files are analyzed concurrently, with the engine's analysis limiter bounding how
+//! many provider calls are in flight. A file that fails to analyze carries its error
+//! in the results instead of failing the others.
+
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+
+use super::{AiEngine, AnalysisResult, IssueSeverity};
+
+/// A file to analyze
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchFile {
+    pub path: String,
+    pub code: String,
+    pub language: String,
+}
+
+/// Outcome for one file; exactly one of `analysis` and `error` is set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileAnalysis {
+    pub path: String,
+    pub analysis: Option<AnalysisResult>,
+    pub error: Option<String>,
+}
+
+/// Issue counts across the batch
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SeverityCounts {
+    pub info: usize,
+    pub warning: usize,
+    pub error: usize,
+    pub critical: usize,
+}
+
+/// Totals over the files that were analyzed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BatchSummary {
+    pub files_analyzed: usize,
+    pub files_failed: usize,
+    pub total_issues: usize,
+    pub issues_by_severity: SeverityCounts,
+}
+
+/// Per-file results, in request order, with their summary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchAnalysis {
+    pub files: Vec<FileAnalysis>,
+    pub summary: BatchSummary,
+}
+
+impl BatchSummary {
+    fn from_results(files: &[FileAnalysis]) -> Self {
+        let mut summary = Self::default();
+        for file in files {
+            let Some(analysis) = &file.analysis else {
+                summary.files_failed += 1;
+                continue;
+            };
+            summary.files_analyzed += 1;
+            summary.total_issues += analysis.issues.len();
+            for issue in &analysis.issues {
+                let counts = &mut summary.issues_by_severity;
+                match issue.severity {
+                    IssueSeverity::Info => counts.info += 1,
+                    IssueSeverity::Warning => counts.warning += 1,
+                    IssueSeverity::Error => counts.error += 1,
+                    IssueSeverity::Critical => counts.critical += 1,
+                }
+            }
+        }
+        summary
+    }
+}
+
+impl AiEngine {
+    /// Run `advanced_analyze_code` over `files` concurrently
+    pub async fn analyze_batch(&self, files: &[BatchFile]) -> BatchAnalysis {
+        let results = join_all(files.iter().map(|file| async move {
+            let outcome = match self.analysis_limiter.acquire().await {
+                Ok(_permit) => self.advanced_analyze_code(&file.code, &file.language, Some(&file.path)).await,
+                Err(e) => Err(e.into()),
+            };
+            match outcome {
+                Ok(analysis) => FileAnalysis { path: file.path.clone(), analysis: Some(analysis), error: None },
+                Err(e) => FileAnalysis { path: file.path.clone(), analysis: None, error: Some(e.to_string()) },
+            }
+        }))
+        .await;
+
+        BatchAnalysis {
+            summary: BatchSummary::from_results(&results),
+            files: results,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::tests::{mock_http_server_with, openai_config};
+
+    /// Fail any request mentioning `broken.rs` and report two issues for the rest
+    fn fail_broken_file(request: &str) -> (&'static str, String) {
+        let (status, body) = if request.contains("broken.rs") {
+            ("500 Internal Server Error", serde_json::json!({
+                "error": { "message": "model crashed", "type": "server_error", "code": null }
+            }))
+        } else {
+            ("200 OK", serde_json::json!({
+                "choices": [{
+                    "message": { "role": "assistant", "content": "Warning: line 2 ignores the result\nError: line 3 panics on empty input" },
+                    "finish_reason": "stop"
+                }],
+                "usage": null
+            }))
+        };
+        (status, body.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_failed_file_does_not_fail_the_batch() {
+        let addr = mock_http_server_with(fail_broken_file).await;
+        let mut engine = AiEngine::new(openai_config(addr));
+        engine.initialize().await.unwrap();
+
+        let files = [
+            BatchFile { path: "src/ok.rs".to_string(), code: "fn first(v: &[u8]) -> u8 {\n    v.len();\n    v[0]\n}\n".to_string(), language: "rust".to_string() },
+            BatchFile { path: "src/broken.rs".to_string(), code: "fn broken() {}\n".to_string(), language: "rust".to_string() },
+        ];
+        let batch = engine.analyze_batch(&files).await;
+
+        assert_eq!(batch.files[0].path, "src/ok.rs");
+        assert_eq!(batch.files[0].analysis.as_ref().unwrap().issues.len(), 2);
+        assert_eq!(batch.files[1].path, "src/broken.rs");
+        assert!(batch.files[1].analysis.is_none());
+        assert!(batch.files[1].error.as_deref().unwrap().contains("model crashed"));
+        assert_eq!(batch.summary, BatchSummary {
+            files_analyzed: 1,
+            files_failed: 1,
+            total_issues: 2,
+            issues_by_severity: SeverityCounts { warning: 1, error: 1, ..Default::default() },
+        });
+    }
+}
@@ -0,0 +1,255 @@
+//! Changelogs for a range of commits
+//!
+//! The commits between two revisions are sorted by their conventional-commit
+//! prefix, where they have one, and handed to the model to write up as Breaking
+//! Changes, Features, Fixes and Other. A range too long for one prompt is split
+//! into chunks written up separately, and those partial changelogs are then
+//! merged by one more prompt.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::AiEngine;
+use crate::git::RangeCommit;
+
+/// Characters of commit text sent in one prompt
+const MAX_CHUNK_CHARS: usize = 24_000;
+
+/// Characters of each commit's diff included in the prompt
+const MAX_DIFF_CHARS: usize = 1500;
+
+/// Changelog categories, in the order they are written
+pub const CHANGELOG_CATEGORIES: &[&str] = &["Breaking Changes", "Features", "Fixes", "Other"];
+
+/// A changelog category and its entries
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangelogSection {
+    pub title: String,
+    pub entries: Vec<String>,
+}
+
+/// A changelog the model wrote for the commits from `from` to `to`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Changelog {
+    pub from: String,
+    pub to: String,
+    /// The changelog as Markdown
+    pub text: String,
+    /// The categories the text has entries under
+    pub sections: Vec<ChangelogSection>,
+    pub commit_count: usize,
+    /// Prompts the commits were split across
+    pub chunks: usize,
+}
+
+/// The category a commit's message puts it in: a `!` after its type or a
+/// `BREAKING CHANGE` footer makes it breaking, `feat` a feature and `fix` a fix
+fn commit_category(message: &str) -> &'static str {
+    if message.contains("BREAKING CHANGE") || message.contains("BREAKING-CHANGE") {
+        return "Breaking Changes";
+    }
+    let subject = message.lines().next().unwrap_or("");
+    let Some((prefix, _)) = subject.split_once(':') else {
+        return "Other";
+    };
+    if prefix.ends_with('!') {
+        return "Breaking Changes";
+    }
+    let kind = prefix.split('(').next().unwrap_or(prefix).trim().to_lowercase();
+    match kind.as_str() {
+        "feat" | "feature" => "Features",
+        "fix" | "bugfix" => "Fixes",
+        _ => "Other",
+    }
+}
+
+fn render_commit(commit: &RangeCommit) -> String {
+    let mut text = format!("- {} {}", &commit.hash[..commit.hash.len().min(12)], commit.message.trim());
+    if let Some(diff) = commit.diff.as_deref().map(str::trim).filter(|diff| !diff.is_empty()) {
+        let diff = match diff.char_indices().nth(MAX_DIFF_CHARS) {
+            Some((end, _)) => format!("{}\n[diff truncated]", &diff[..end]),
+            None => diff.to_string(),
+        };
+        text.push_str(&format!("\n```diff\n{}\n```", diff));
+    }
+    text
+}
+
+/// The commits as prompt text, split into chunks of at most [`MAX_CHUNK_CHARS`]
+/// unless a single commit is longer; each chunk groups its commits by category
+fn chunk_commits(commits: &[RangeCommit]) -> Vec<String> {
+    let mut chunks: Vec<Vec<(&'static str, String)>> = vec![Vec::new()];
+    let mut chars = 0;
+    for commit in commits {
+        let text = render_commit(commit);
+        if chars > 0 && chars + text.len() > MAX_CHUNK_CHARS {
+            chunks.push(Vec::new());
+            chars = 0;
+        }
+        chars += text.len() + 1;
+        chunks.last_mut().expect("there is always a chunk").push((commit_category(&commit.message), text));
+    }
+
+    chunks.into_iter()
+        .map(|chunk| {
+            CHANGELOG_CATEGORIES.iter()
+                .filter_map(|category| {
+                    let entries: Vec<&str> = chunk.iter()
+                        .filter(|(commit_category, _)| commit_category == category)
+                        .map(|(_, text)| text.as_str())
+                        .collect();
+                    (!entries.is_empty()).then(|| format!("Likely {}:\n{}", category, entries.join("\n")))
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n")
+        })
+        .collect()
+}
+
+fn format_instructions() -> String {
+    format!(
+        "Write the changelog in Markdown with a `## ` heading for each of these categories that has \
+         entries, in this order: {}. Under each heading, list one `- ` bullet per user-visible change, \
+         written for users rather than developers. Merge commits that make one change together, leave \
+         out purely internal ones, and move a commit to another category when its diff shows it belongs \
+         there. Output only the changelog.",
+        CHANGELOG_CATEGORIES.join(", ")
+    )
+}
+
+fn chunk_prompt(from: &str, to: &str, commits: &str, part: Option<(usize, usize)>) -> String {
+    let scope = match part {
+        Some((index, total)) => format!(" (part {} of {})", index + 1, total),
+        None => String::new(),
+    };
+    format!(
+        "Write a changelog for the commits from {} to {}{}, oldest first, grouped by the category their \
+         messages suggest:\n\n{}\n\n{}",
+        from, to, scope, commits, format_instructions()
+    )
+}
+
+fn combine_prompt(from: &str, to: &str, partials: &[String]) -> String {
+    format!(
+        "These changelogs each cover consecutive commits from {} to {}, oldest first:\n\n{}\n\n\
+         Combine them into a single changelog, removing duplicate entries. {}",
+        from, to, partials.join("\n\n---\n\n"), format_instructions()
+    )
+}
+
+/// The `## ` sections of a Markdown changelog and their bullet entries
+fn parse_sections(text: &str) -> Vec<ChangelogSection> {
+    let mut sections: Vec<ChangelogSection> = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if let Some(title) = line.strip_prefix('#').map(|title| title.trim_start_matches('#').trim()) {
+            sections.push(ChangelogSection { title: title.to_string(), entries: Vec::new() });
+        } else if let Some(entry) = line.strip_prefix("- ").or_else(|| line.strip_prefix("* ")) {
+            if let Some(section) = sections.last_mut() {
+                section.entries.push(entry.trim().to_string());
+            }
+        }
+    }
+    sections.retain(|section| !section.entries.is_empty());
+    sections
+}
+
+impl AiEngine {
+    /// Write a changelog for `commits`, the range from `from` to `to` oldest first
+    pub async fn changelog(&self, from: &str, to: &str, commits: &[RangeCommit]) -> Result<Changelog> {
+        if commits.is_empty() {
+            return Ok(Changelog {
+                from: from.to_string(),
+                to: to.to_string(),
+                text: format!("No changes between {} and {}.", from, to),
+                sections: Vec::new(),
+                commit_count: 0,
+                chunks: 0,
+            });
+        }
+
+        let chunks = chunk_commits(commits);
+        let text = if let [commits_text] = chunks.as_slice() {
+            self.run_prompt(&chunk_prompt(from, to, commits_text, None), "Markdown").await?
+        } else {
+            let mut partials = Vec::with_capacity(chunks.len());
+            for (index, commits_text) in chunks.iter().enumerate() {
+                let prompt = chunk_prompt(from, to, commits_text, Some((index, chunks.len())));
+                partials.push(self.run_prompt(&prompt, "Markdown").await?.trim().to_string());
+            }
+            self.run_prompt(&combine_prompt(from, to, &partials), "Markdown").await?
+        };
+
+        let text = text.trim().to_string();
+        Ok(Changelog {
+            from: from.to_string(),
+            to: to.to_string(),
+            sections: parse_sections(&text),
+            text,
+            commit_count: commits.len(),
+            chunks: chunks.len(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::tests::{mock_openai_server, openai_config};
+    use crate::git::tests::{git, temp_workspace};
+    use crate::git::GitManager;
+
+    #[tokio::test]
+    async fn test_changelog_of_commit_range_is_categorized() {
+        let root = temp_workspace();
+        git(&root, &["init", "-q"]);
+        std::fs::write(root.join("feed.py"), "def fetch():\n    return get(URL)\n").unwrap();
+        git(&root, &["add", "."]);
+        git(&root, &["commit", "-q", "-m", "Initial import"]);
+        git(&root, &["tag", "v1.0"]);
+        std::fs::write(root.join("feed.py"), "def fetch(retries=3):\n    return get(URL)\n").unwrap();
+        git(&root, &["commit", "-q", "-am", "feat: retry failed feed requests"]);
+        std::fs::write(root.join("feed.py"), "def fetch(retries=3):\n    return get(URL, timeout=5)\n").unwrap();
+        git(&root, &["commit", "-q", "-am", "fix(feed): time out hung requests"]);
+
+        let manager = GitManager::new(root.clone());
+        let missing = manager.commits_in_range("v0.9", "HEAD", false).await.unwrap_err();
+        assert!(matches!(missing.downcast_ref::<crate::git::GitError>(), Some(crate::git::GitError::RevisionNotFound(_))));
+        let commits = manager.commits_in_range("v1.0", "HEAD", true).await.unwrap();
+        assert_eq!(commits.len(), 2);
+        assert!(commits[0].message.starts_with("feat:"));
+
+        let (addr, server) = mock_openai_server(
+            "## Features\\n- Failed feed requests are retried\\n\\n## Fixes\\n- Hung feed requests time out",
+        ).await;
+        let engine = AiEngine::new_async(openai_config(addr)).await.unwrap();
+
+        let changelog = engine.changelog("v1.0", "HEAD", &commits).await.unwrap();
+        assert_eq!((changelog.commit_count, changelog.chunks), (2, 1));
+        let titles: Vec<_> = changelog.sections.iter().map(|section| section.title.as_str()).collect();
+        assert_eq!(titles, ["Features", "Fixes"]);
+        assert_eq!(changelog.sections[1].entries, ["Hung feed requests time out"]);
+
+        let request = server.await.unwrap();
+        assert!(request.contains("Likely Features:") && request.contains("Likely Fixes:"));
+        assert!(request.contains("+    return get(URL, timeout=5)"));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_long_ranges_are_split_into_chunks() {
+        let commit = |index: usize| RangeCommit {
+            hash: format!("{:040}", index),
+            author: "Test".to_string(),
+            timestamp: chrono::Utc::now(),
+            message: if index % 2 == 1 { format!("Tidy {}", index) } else { format!("feat!: change {}", index) },
+            diff: Some("+".repeat(MAX_DIFF_CHARS * 2)),
+        };
+        let commits: Vec<_> = (0..40).map(commit).collect();
+        let chunks = chunk_commits(&commits);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= MAX_CHUNK_CHARS + 200));
+        assert!(chunks[0].starts_with("Likely Breaking Changes:"));
+        assert!(chunks[0].contains("[diff truncated]"));
+    }
+}
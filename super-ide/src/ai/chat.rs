@@ -0,0 +1,166 @@
+//! Multi-turn chat
+//!
+//! Conversations are passed to the provider as they are, one message per turn, so
+//! a system persona and earlier replies shape the answer the way they would in the
+//! provider's own chat interface.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::{AiEngine, CompletionRequest, CompletionResponse, OpenAIMessage};
+
+/// Who a chat message is from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    System,
+    User,
+    Assistant,
+}
+
+impl ChatRole {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::System => "system",
+            Self::User => "user",
+            Self::Assistant => "assistant",
+        }
+    }
+}
+
+/// One turn of a conversation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: ChatRole,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn system(content: impl Into<String>) -> Self {
+        Self { role: ChatRole::System, content: content.into() }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self { role: ChatRole::User, content: content.into() }
+    }
+}
+
+impl AiEngine {
+    /// Continue a conversation, oldest message first. Without a system message the
+    /// model is told it is a `language` developer; `context`, such as the open file,
+    /// is passed as a system message ahead of the conversation.
+    pub async fn chat(
+        &self,
+        messages: &[ChatMessage],
+        language: &str,
+        context: Option<&str>,
+        max_tokens: Option<u32>,
+    ) -> Result<CompletionResponse> {
+        if !self.initialized {
+            return Err(anyhow::anyhow!("AI Engine not initialized"));
+        }
+        if !messages.iter().any(|message| message.role == ChatRole::User) {
+            return Err(anyhow::anyhow!("Chat needs at least one user message"));
+        }
+
+        let mut conversation = Vec::with_capacity(messages.len() + 2);
+        if !messages.iter().any(|message| message.role == ChatRole::System) {
+            conversation.push(ChatMessage::system(format!(
+                "You are an expert {} developer and a helpful pair programmer.",
+                language
+            )));
+        }
+        if let Some(context) = context.filter(|context| !context.trim().is_empty()) {
+            conversation.push(ChatMessage::system(context));
+        }
+        conversation.extend_from_slice(messages);
+
+        let conversation = &conversation;
+        self.with_provider_fallback(|provider| async move {
+            self.chat_with_provider(&provider, conversation, language, max_tokens).await
+        }).await
+    }
+
+    async fn chat_with_provider(
+        &self,
+        provider: &str,
+        conversation: &[ChatMessage],
+        language: &str,
+        max_tokens: Option<u32>,
+    ) -> Result<CompletionResponse> {
+        let mut response = match provider {
+            "openai" => {
+                let messages = conversation.iter()
+                    .map(|message| OpenAIMessage { role: message.role.as_str().to_string(), content: message.content.clone() })
+                    .collect();
+                let text = self.track("chat", self.send_openai_messages(messages, max_tokens)).await?
+                    .unwrap_or_default();
+                CompletionResponse { text, confidence: 0.8, suggestions: vec![], served_by: None, fallback: false }
+            }
+            // The local model completes code rather than holding a conversation
+            "local" => {
+                let latest = conversation.iter().rev()
+                    .find(|message| message.role == ChatRole::User)
+                    .map(|message| message.content.clone())
+                    .unwrap_or_default();
+                let request = CompletionRequest {
+                    language: language.to_string(),
+                    context: String::new(),
+                    position: None,
+                    prompt: latest.clone(),
+                    max_tokens,
+                    cursor_position: None,
+                    text_before_cursor: latest,
+                    document: None,
+                };
+                self.track("chat", self.generate_local_completion(&request)).await?
+            }
+            _ => CompletionResponse {
+                text: "AI provider not supported".to_string(),
+                confidence: 0.0,
+                suggestions: vec![],
+                served_by: None,
+                fallback: false,
+            },
+        };
+        response.served_by.get_or_insert_with(|| provider.to_string());
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::tests::{mock_openai_server, openai_config};
+
+    #[tokio::test]
+    async fn test_system_message_reaches_provider() {
+        let (addr, server) = mock_openai_server("Arr, use a match.").await;
+        let engine = AiEngine::new_async(openai_config(addr)).await.unwrap();
+
+        let messages = vec![
+            ChatMessage::system("You are a pirate who reviews Rust code."),
+            ChatMessage::user("How do I handle an Option?"),
+            ChatMessage { role: ChatRole::Assistant, content: "With care.".to_string() },
+            ChatMessage::user("More specifically?"),
+        ];
+        let reply = engine.chat(&messages, "rust", None, None).await.unwrap();
+        assert_eq!(reply.text, "Arr, use a match.");
+
+        let request = server.await.unwrap();
+        let sent = format!("\"messages\":{}", serde_json::to_string(&messages).unwrap());
+        assert!(request.contains(&sent), "{}", request);
+    }
+
+    #[tokio::test]
+    async fn test_default_persona_without_system_message() {
+        let (addr, server) = mock_openai_server("ok").await;
+        let engine = AiEngine::new_async(openai_config(addr)).await.unwrap();
+
+        engine.chat(&[ChatMessage::user("hello")], "python", Some("Open file: app.py"), None).await.unwrap();
+        let request = server.await.unwrap();
+        assert!(request.contains(r#"{"role":"system","content":"You are an expert python developer"#));
+        assert!(request.contains(r#"{"role":"system","content":"Open file: app.py"},{"role":"user","content":"hello"}"#));
+        assert!(engine.chat(&[ChatMessage::system("only a persona")], "python", None, None).await.is_err());
+    }
+}
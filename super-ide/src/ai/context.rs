@@ -0,0 +1,355 @@
+//! Code context for AI requests
+//!
+//! Sending a whole file as context overflows the provider's token limit on large
+//! files, and the provider then cuts it wherever it likes. A [`ContextBuilder`]
+//! instead picks lines by relevance until its token budget is spent: the line at
+//! the cursor, the function enclosing it, the file's imports, then the lines
+//! around the function. Omitted runs of lines are marked in the text, so the model
+//! knows the code is not contiguous. Tokens are estimated at four characters each.
+
+use serde::{Deserialize, Serialize};
+
+use crate::editor::symbols::{extract_symbols, SymbolKind};
+
+/// Context tokens when none are configured
+pub const DEFAULT_CONTEXT_TOKENS: usize = 3000;
+
+/// Lines above and below the enclosing function kept when the budget allows
+const DEFAULT_NEARBY_LINES: usize = 20;
+
+/// Characters set aside for each omitted-lines marker, newline included
+const MARKER_CHARS: usize = 32;
+
+/// Rough token count of `text`, for budgeting
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// The part of a file sent as context
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContextWindow {
+    pub text: String,
+    /// 0-based, end-exclusive ranges of the file's lines that were kept
+    pub ranges: Vec<(usize, usize)>,
+    /// Name of the function the cursor is in, when one was found
+    pub enclosing_function: Option<String>,
+    pub estimated_tokens: usize,
+    /// Whether lines were left out
+    pub truncated: bool,
+}
+
+impl ContextWindow {
+    /// The 1-based line of the file shown on 1-based line `window_line` of the text,
+    /// so line numbers a provider reports can be mapped back; `None` for a marker
+    /// line or a line past the end
+    pub fn file_line(&self, window_line: usize) -> Option<usize> {
+        let mut shown = 0;
+        let mut next = 0;
+        for &(start, end) in &self.ranges {
+            if start > next {
+                shown += 1;
+                if shown == window_line {
+                    return None;
+                }
+            }
+            if window_line <= shown + (end - start) {
+                return (window_line > shown).then(|| start + (window_line - shown));
+            }
+            shown += end - start;
+            next = end;
+        }
+        None
+    }
+}
+
+/// Selects the lines of a file most relevant to a cursor within a token budget
+#[derive(Debug, Clone)]
+pub struct ContextBuilder {
+    max_tokens: usize,
+    nearby_lines: usize,
+}
+
+impl Default for ContextBuilder {
+    fn default() -> Self {
+        Self::new(DEFAULT_CONTEXT_TOKENS)
+    }
+}
+
+impl ContextBuilder {
+    pub fn new(max_tokens: usize) -> Self {
+        Self { max_tokens, nearby_lines: DEFAULT_NEARBY_LINES }
+    }
+
+    /// Keep up to `lines` lines on either side of the enclosing function
+    pub fn with_nearby_lines(mut self, lines: usize) -> Self {
+        self.nearby_lines = lines;
+        self
+    }
+
+    /// Context for `code` in `language` around 0-based `cursor_line`. A file within
+    /// the budget is returned whole.
+    pub fn build(&self, code: &str, language: &str, cursor_line: usize) -> ContextWindow {
+        let lines: Vec<&str> = code.lines().collect();
+        let budget = self.max_tokens * 4;
+        if lines.is_empty() || code.chars().count() <= budget {
+            return ContextWindow {
+                text: code.to_string(),
+                ranges: if lines.is_empty() { Vec::new() } else { vec![(0, lines.len())] },
+                enclosing_function: None,
+                estimated_tokens: estimate_tokens(code),
+                truncated: false,
+            };
+        }
+
+        let cursor = cursor_line.min(lines.len() - 1);
+        let mut selection = Selection::new(&lines, budget);
+        selection.add(cursor);
+
+        let enclosing = enclosing_function(code, &lines, language, cursor);
+        let (start, end) = match &enclosing {
+            Some((_, start, end)) => {
+                // The signature says what the function is even when its body does not fit
+                selection.add(*start);
+                selection.expand(cursor, *start, *end);
+                (*start, *end)
+            }
+            None => (cursor, cursor),
+        };
+
+        for (index, line) in lines.iter().enumerate() {
+            if is_import(line) {
+                selection.add(index);
+            }
+        }
+
+        let above = start.saturating_sub(self.nearby_lines);
+        let below = (end + self.nearby_lines).min(lines.len() - 1);
+        selection.expand(cursor, above, below);
+
+        selection.into_window(enclosing.map(|(name, _, _)| name))
+    }
+}
+
+/// Lines chosen so far, and what rendering them would cost
+struct Selection<'a> {
+    lines: &'a [&'a str],
+    kept: Vec<bool>,
+    /// Characters of the rendered text, with [`MARKER_CHARS`] per omitted run
+    chars: usize,
+    budget: usize,
+}
+
+impl<'a> Selection<'a> {
+    fn new(lines: &'a [&'a str], budget: usize) -> Self {
+        Self { lines, kept: vec![false; lines.len()], chars: MARKER_CHARS, budget }
+    }
+
+    fn is_omitted(&self, index: Option<usize>) -> bool {
+        index.and_then(|index| self.kept.get(index)).is_some_and(|kept| !kept)
+    }
+
+    /// Keep line `index` if it fits; returns whether it is kept
+    fn add(&mut self, index: usize) -> bool {
+        if self.kept[index] {
+            return true;
+        }
+        // Keeping a line splits the omitted run it was in, shortens it, or removes it
+        let omitted_neighbours = [index.checked_sub(1), Some(index + 1)].into_iter()
+            .filter(|neighbour| self.is_omitted(*neighbour))
+            .count();
+        let markers = match omitted_neighbours {
+            2 => MARKER_CHARS as isize,
+            1 => 0,
+            _ => -(MARKER_CHARS as isize),
+        };
+        let chars = (self.chars + self.lines[index].chars().count() + 1).saturating_add_signed(markers);
+        if chars > self.budget {
+            return false;
+        }
+        self.kept[index] = true;
+        self.chars = chars;
+        true
+    }
+
+    /// Keep lines outward from `from`, alternating up and down, without leaving
+    /// `first..=last`; each direction stops at the first line that does not fit
+    fn expand(&mut self, from: usize, first: usize, last: usize) {
+        let (mut up, mut down) = (Some(from), Some(from));
+        while up.is_some() || down.is_some() {
+            up = up.and_then(|line| line.checked_sub(1)).filter(|line| *line >= first);
+            if let Some(line) = up {
+                if !self.add(line) {
+                    up = None;
+                }
+            }
+            down = down.map(|line| line + 1).filter(|line| *line <= last);
+            if let Some(line) = down {
+                if !self.add(line) {
+                    down = None;
+                }
+            }
+        }
+    }
+
+    fn into_window(self, enclosing_function: Option<String>) -> ContextWindow {
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for (index, _) in self.kept.iter().enumerate().filter(|(_, kept)| **kept) {
+            match ranges.last_mut() {
+                Some((_, end)) if *end == index => *end += 1,
+                _ => ranges.push((index, index + 1)),
+            }
+        }
+
+        let mut text = Vec::new();
+        let mut next = 0;
+        for &(start, end) in &ranges {
+            if start > next {
+                text.push(omitted(start - next));
+            }
+            text.extend(self.lines[start..end].iter().map(|line| line.to_string()));
+            next = end;
+        }
+        if next < self.lines.len() {
+            text.push(omitted(self.lines.len() - next));
+        }
+
+        let text = text.join("\n");
+        ContextWindow {
+            estimated_tokens: estimate_tokens(&text),
+            text,
+            ranges,
+            enclosing_function,
+            truncated: true,
+        }
+    }
+}
+
+fn omitted(count: usize) -> String {
+    format!("... {} lines omitted ...", count)
+}
+
+/// Whether `line` brings in another module
+fn is_import(line: &str) -> bool {
+    const IMPORT_PREFIXES: &[&str] = &[
+        "use ", "pub use ", "extern crate ", "import ", "from ", "#include", "package ", "require ",
+    ];
+    let line = line.trim_start();
+    IMPORT_PREFIXES.iter().any(|prefix| line.starts_with(prefix))
+        || (line.contains("require(") && (line.starts_with("const ") || line.starts_with("let ") || line.starts_with("var ")))
+}
+
+/// The innermost function containing `cursor`: its name and first and last lines
+fn enclosing_function(code: &str, lines: &[&str], language: &str, cursor: usize) -> Option<(String, usize, usize)> {
+    let extension = match language.to_lowercase().as_str() {
+        "rust" => "rs",
+        "python" => "py",
+        "javascript" => "js",
+        "typescript" => "ts",
+        "go" => "go",
+        _ => return None,
+    };
+    let indented = extension == "py";
+    extract_symbols(&format!("context.{}", extension), code)
+        .into_iter()
+        .filter(|symbol| symbol.kind == SymbolKind::Function && symbol.line - 1 <= cursor)
+        .rev()
+        .find_map(|symbol| {
+            let start = symbol.line - 1;
+            let end = if indented { indented_block_end(lines, start) } else { braced_block_end(lines, start) };
+            (end >= cursor).then_some((symbol.name, start, end))
+        })
+}
+
+/// Last line of the brace-delimited block starting on line `start`; a declaration
+/// ending in `;` before any brace is one line long
+fn braced_block_end(lines: &[&str], start: usize) -> usize {
+    let mut depth = 0usize;
+    let mut opened = false;
+    for (index, line) in lines.iter().enumerate().skip(start) {
+        for c in line.chars() {
+            match c {
+                '{' => {
+                    depth += 1;
+                    opened = true;
+                }
+                '}' => depth = depth.saturating_sub(1),
+                ';' if !opened => return index,
+                _ => {}
+            }
+        }
+        if opened && depth == 0 {
+            return index;
+        }
+    }
+    lines.len() - 1
+}
+
+/// Last line of the indented block whose header is line `start`
+fn indented_block_end(lines: &[&str], start: usize) -> usize {
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let header_indent = indent(lines[start]);
+    let mut end = start;
+    for (index, line) in lines.iter().enumerate().skip(start + 1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if indent(line) <= header_indent {
+            break;
+        }
+        end = index;
+    }
+    end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_large_file_context_keeps_the_enclosing_function_within_budget() {
+        let mut code = String::from("use std::collections::HashMap;\nuse std::fs;\n\n");
+        for index in 0..300 {
+            code.push_str(&format!("fn helper_{}(value: u32) -> u32 {{\n    value * {}\n}}\n\n", index, index));
+        }
+        code.push_str("pub fn load_settings(path: &str) -> HashMap<String, String> {\n");
+        code.push_str("    let text = fs::read_to_string(path).unwrap_or_default();\n");
+        let cursor = code.lines().count();
+        code.push_str("    let mut settings = HashMap::new();\n");
+        code.push_str("    settings.insert(\"path\".to_string(), text);\n    settings\n}\n\n");
+        for index in 300..600 {
+            code.push_str(&format!("fn helper_{}(value: u32) -> u32 {{\n    value * {}\n}}\n\n", index, index));
+        }
+        assert!(estimate_tokens(&code) > 3000);
+
+        let window = ContextBuilder::new(400).build(&code, "Rust", cursor);
+        assert!(window.truncated);
+        assert!(window.estimated_tokens <= 400, "{} tokens", window.estimated_tokens);
+        assert_eq!(estimate_tokens(&window.text), window.estimated_tokens);
+        assert_eq!(window.enclosing_function.as_deref(), Some("load_settings"));
+        let function = "pub fn load_settings(path: &str) -> HashMap<String, String> {\n    let text = fs::read_to_string(path).unwrap_or_default();\n    let mut settings = HashMap::new();\n    settings.insert(\"path\".to_string(), text);\n    settings\n}";
+        assert!(window.text.contains(function));
+        assert!(window.text.starts_with("use std::collections::HashMap;\nuse std::fs;\n"));
+        assert!(window.text.contains("lines omitted"));
+        assert!(!window.text.contains("helper_0("));
+        let text_lines: Vec<&str> = window.text.lines().collect();
+        let code_lines: Vec<&str> = code.lines().collect();
+        let signature = text_lines.iter().position(|line| line.starts_with("pub fn load_settings")).unwrap() + 1;
+        assert_eq!(code_lines[window.file_line(signature).unwrap() - 1], text_lines[signature - 1]);
+        assert_eq!(window.file_line(1), Some(1));
+        assert_eq!(window.file_line(3), None);
+        assert_eq!(window.file_line(text_lines.len() + 1), None);
+
+        let short = "def main():\n    print('hi')\n";
+        let whole = ContextBuilder::new(400).build(short, "Python", 1);
+        assert_eq!((whole.text.as_str(), whole.truncated), (short, false));
+    }
+
+    #[test]
+    fn test_python_function_ends_at_its_dedent() {
+        let lines = ["import os", "", "def run(path):", "    if path:", "        return os.stat(path)", "", "    return None", "", "def other():", "    pass"];
+        assert_eq!(indented_block_end(&lines, 2), 6);
+        let code = lines.join("\n");
+        assert_eq!(enclosing_function(&code, &lines, "python", 4), Some(("run".to_string(), 2, 6)));
+        assert_eq!(enclosing_function(&code, &lines, "python", 7), None);
+    }
+}
@@ -0,0 +1,238 @@
+//! Documentation comments for a single symbol
+//!
+//! The model is asked for the doc comment of one function only. Its reply is
+//! checked against the comment style of the language, so a reply that rewrites
+//! the function instead of documenting it is rejected rather than inserted.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::AiEngine;
+
+/// Doc comment conventions supported for generation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocStyle {
+    /// `///` line comments above the item
+    RustDoc,
+    /// Triple-quoted string as the first statement of the body
+    PythonDocstring,
+    /// `/** ... */` block above the function
+    JsDoc,
+}
+
+/// Where the generated comment goes relative to the symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocPlacement {
+    /// On the lines directly above the symbol
+    Above,
+    /// As the first line of the symbol's body
+    BodyStart,
+}
+
+impl DocStyle {
+    /// Style used for `language`, if documentation generation supports it
+    pub fn for_language(language: &str) -> Option<Self> {
+        match language.to_lowercase().as_str() {
+            "rust" | "rs" => Some(Self::RustDoc),
+            "python" | "py" => Some(Self::PythonDocstring),
+            "javascript" | "js" | "jsx" | "typescript" | "ts" | "tsx" => Some(Self::JsDoc),
+            _ => None,
+        }
+    }
+
+    /// Where a comment in this style is inserted
+    pub fn placement(self) -> DocPlacement {
+        match self {
+            Self::PythonDocstring => DocPlacement::BodyStart,
+            Self::RustDoc | Self::JsDoc => DocPlacement::Above,
+        }
+    }
+
+    fn instructions(self) -> &'static str {
+        match self {
+            Self::RustDoc => "Write a rustdoc comment using `///` lines: a one-line summary, then `# Arguments`, `# Returns` and `# Errors` or `# Panics` sections where they apply.",
+            Self::PythonDocstring => "Write a PEP 257 docstring in triple double quotes: a one-line summary, then Args, Returns and Raises sections where they apply.",
+            Self::JsDoc => "Write a JSDoc block starting with `/**` and ending with `*/`, with a summary and `@param`, `@returns` and `@throws` tags where they apply.",
+        }
+    }
+}
+
+/// Doc comment generation errors
+#[derive(Debug, thiserror::Error)]
+pub enum DocCommentError {
+    #[error("Documentation generation is not supported for {0}")]
+    UnsupportedLanguage(String),
+
+    #[error("Model reply is not a doc comment: {0}")]
+    NotAComment(String),
+}
+
+/// A doc comment ready to insert into the source
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeneratedDoc {
+    pub language: String,
+    pub style: DocStyle,
+    pub placement: DocPlacement,
+    /// Comment text, indented to match the symbol
+    pub comment: String,
+}
+
+/// Remove a surrounding Markdown code fence, if any
+fn strip_code_fence(reply: &str) -> &str {
+    let trimmed = reply.trim();
+    let Some(rest) = trimmed.strip_prefix("```") else {
+        return trimmed;
+    };
+    let body = rest.split_once('\n').map_or("", |(_, body)| body);
+    body.trim_end().strip_suffix("```").unwrap_or(body).trim()
+}
+
+fn leading_whitespace(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// Indentation the comment needs so it lines up with the documented code
+fn target_indent(style: DocStyle, code: &str) -> String {
+    let mut lines = code.lines().filter(|line| !line.trim().is_empty());
+    let header = lines.next().map(leading_whitespace).unwrap_or("");
+    match style.placement() {
+        DocPlacement::Above => header.to_string(),
+        DocPlacement::BodyStart => lines.next()
+            .map(leading_whitespace)
+            .filter(|body| body.len() > header.len())
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{}    ", header)),
+    }
+}
+
+/// Check that `reply` is only a doc comment in `style` and re-indent it for `code`
+pub fn extract_doc_comment(style: DocStyle, code: &str, reply: &str) -> Result<String, DocCommentError> {
+    let text = strip_code_fence(reply);
+    let lines: Vec<&str> = text.lines().map(str::trim).collect();
+    if lines.iter().all(|line| line.is_empty()) {
+        return Err(DocCommentError::NotAComment("reply is empty".to_string()));
+    }
+
+    let lines: Vec<String> = match style {
+        DocStyle::RustDoc => {
+            if let Some(line) = lines.iter().find(|line| !line.is_empty() && !line.starts_with("///")) {
+                return Err(DocCommentError::NotAComment(format!("line `{}` is not a `///` comment", line)));
+            }
+            lines.iter().filter(|line| !line.is_empty()).map(|line| line.to_string()).collect()
+        }
+        DocStyle::PythonDocstring => {
+            let quote = if text.starts_with("\"\"\"") {
+                "\"\"\""
+            } else if text.starts_with("'''") {
+                "'''"
+            } else {
+                return Err(DocCommentError::NotAComment("docstring must start with triple quotes".to_string()));
+            };
+            // The docstring has to close on the last line, otherwise code follows it
+            if text.len() < 2 * quote.len() || !text.ends_with(quote) || text[quote.len()..text.len() - quote.len()].contains(quote) {
+                return Err(DocCommentError::NotAComment("text continues after the docstring".to_string()));
+            }
+            // Keep the indentation of sections relative to the docstring itself
+            let raw: Vec<&str> = text.lines().collect();
+            let margin = raw.iter().skip(1)
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| leading_whitespace(line).len())
+                .min()
+                .unwrap_or(0);
+            raw.iter().enumerate()
+                .map(|(i, line)| if i == 0 || line.trim().is_empty() { line.trim().to_string() } else { line[margin..].trim_end().to_string() })
+                .collect()
+        }
+        DocStyle::JsDoc => {
+            // `/**/` starts and ends with the same `*`; it is a plain, empty block comment
+            if text.len() < "/***/".len() || !text.starts_with("/**") || !text.ends_with("*/") || text[3..].find("*/") != Some(text.len() - 5) {
+                return Err(DocCommentError::NotAComment("expected a single `/** ... */` block".to_string()));
+            }
+            lines.iter().enumerate()
+                .map(|(i, line)| if i == 0 || line.starts_with('*') { line.to_string() } else { format!("* {}", line) })
+                .map(|line| if line.starts_with('*') { format!(" {}", line) } else { line })
+                .collect()
+        }
+    };
+
+    let indent = target_indent(style, code);
+    Ok(lines.iter()
+        .map(|line| if line.is_empty() { String::new() } else { format!("{}{}", indent, line) })
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+impl AiEngine {
+    /// Generate the doc comment for one function without touching its code
+    pub async fn generate_doc_comment(&self, code: &str, language: &str) -> Result<GeneratedDoc> {
+        let style = DocStyle::for_language(language)
+            .ok_or_else(|| DocCommentError::UnsupportedLanguage(language.to_string()))?;
+
+        let prompt = format!(
+            "{}\nReply with only the documentation comment. Do not repeat or modify the code.\n\n{}",
+            style.instructions(),
+            code
+        );
+        let reply = self.run_prompt(&prompt, language).await?;
+        let comment = extract_doc_comment(style, code, &reply)?;
+
+        Ok(GeneratedDoc {
+            language: language.to_string(),
+            style,
+            placement: style.placement(),
+            comment,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rust_doc_comment_style() {
+        let code = "    pub fn add(a: i32, b: i32) -> i32 {\n        a + b\n    }";
+        let reply = "```rust\n/// Add two numbers.\n///\n/// # Returns\n/// The sum of `a` and `b`.\n```";
+        let comment = extract_doc_comment(DocStyle::RustDoc, code, reply).unwrap();
+        assert_eq!(comment, "    /// Add two numbers.\n    ///\n    /// # Returns\n    /// The sum of `a` and `b`.");
+
+        let rewritten = "/// Add two numbers.\npub fn add(a: i32, b: i32) -> i32 {\n    a + b\n}";
+        assert!(extract_doc_comment(DocStyle::RustDoc, code, rewritten).is_err());
+    }
+
+    #[test]
+    fn test_python_docstring_style() {
+        let code = "def add(a, b):\n    return a + b\n";
+        let reply = "\"\"\"Add two numbers.\n\nArgs:\n    a: First operand.\n    b: Second operand.\n\"\"\"";
+        let comment = extract_doc_comment(DocStyle::PythonDocstring, code, reply).unwrap();
+        assert_eq!(
+            comment,
+            "    \"\"\"Add two numbers.\n\n    Args:\n        a: First operand.\n        b: Second operand.\n    \"\"\""
+        );
+        assert_eq!(DocStyle::PythonDocstring.placement(), DocPlacement::BodyStart);
+
+        let rewritten = "def add(a, b):\n    \"\"\"Add two numbers.\"\"\"\n    return a + b";
+        assert!(extract_doc_comment(DocStyle::PythonDocstring, code, rewritten).is_err());
+        let trailing = "\"\"\"Add two numbers.\"\"\"\nreturn a + b";
+        assert!(extract_doc_comment(DocStyle::PythonDocstring, code, trailing).is_err());
+    }
+
+    #[test]
+    fn test_jsdoc_style() {
+        let code = "function add(a, b) {\n  return a + b;\n}";
+        let reply = "/**\n * Add two numbers.\n * @param {number} a\n * @param {number} b\n * @returns {number}\n */";
+        let comment = extract_doc_comment(DocStyle::JsDoc, code, reply).unwrap();
+        assert_eq!(
+            comment,
+            "/**\n * Add two numbers.\n * @param {number} a\n * @param {number} b\n * @returns {number}\n */"
+        );
+        assert_eq!(DocStyle::for_language("TypeScript"), Some(DocStyle::JsDoc));
+
+        let rewritten = "/** Add two numbers. */\nfunction add(a, b) {\n  return a + b;\n}";
+        assert!(extract_doc_comment(DocStyle::JsDoc, code, rewritten).is_err());
+        assert!(extract_doc_comment(DocStyle::JsDoc, code, "// Add two numbers.").is_err());
+        assert!(extract_doc_comment(DocStyle::JsDoc, code, "/**/").is_err());
+    }
+}
@@ -0,0 +1,215 @@
+//! Text embeddings
+//!
+//! With the OpenAI provider, texts go to its `/embeddings` endpoint. Other providers
+//! use a local embedding instead: hashed word counts, with identifiers split into
+//! their words, so `loadConfig` and "load the config" land near each other. It ranks
+//! by shared vocabulary rather than meaning, but needs no model download.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::{AiEngine, AiError, OpenAIError};
+
+/// Name recorded for vectors made by `local_embedding`
+pub const LOCAL_EMBEDDING_MODEL: &str = "local-hashed-words";
+
+const DEFAULT_OPENAI_EMBEDDING_MODEL: &str = "text-embedding-3-small";
+
+/// Length of local embedding vectors
+const LOCAL_DIMENSIONS: usize = 512;
+
+/// Inputs sent in one embeddings request
+const MAX_BATCH: usize = 64;
+
+/// Words too common in prose and code to tell texts apart
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "by", "for", "from", "in", "is", "it", "of", "on", "or",
+    "that", "the", "this", "to", "with", "fn", "let", "mut", "pub", "def", "var", "const", "return", "self",
+];
+
+#[derive(Serialize)]
+struct OpenAIEmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct OpenAIEmbeddingResponse {
+    data: Vec<OpenAIEmbedding>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIEmbedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+/// 64-bit FNV-1a; stable across runs and builds, unlike the std hasher
+pub(super) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Lowercase words of `text`, splitting `camelCase` and `snake_case` identifiers
+/// and dropping a plural or `-ing`/`-ed` ending
+fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for token in text.split(|c: char| !c.is_alphanumeric()) {
+        let chars: Vec<char> = token.chars().collect();
+        let mut start = 0;
+        for i in 1..=chars.len() {
+            let boundary = i == chars.len()
+                || (chars[i].is_uppercase() && chars[i - 1].is_lowercase())
+                || (chars[i].is_uppercase() && chars.get(i + 1).is_some_and(|next| next.is_lowercase()) && chars[i - 1].is_uppercase());
+            if boundary {
+                words.push(chars[start..i].iter().collect::<String>().to_lowercase());
+                start = i;
+            }
+        }
+    }
+
+    words.into_iter()
+        .filter(|word| word.len() > 1 && !STOP_WORDS.contains(&word.as_str()))
+        .map(|word| {
+            let stem = ["ing", "ed", "es", "s"].iter()
+                .find_map(|suffix| word.strip_suffix(suffix).filter(|stem| stem.len() >= 3));
+            stem.map(str::to_string).unwrap_or(word)
+        })
+        .collect()
+}
+
+/// Unit-length vector of hashed word counts
+pub fn local_embedding(text: &str) -> Vec<f32> {
+    let mut vector = vec![0.0f32; LOCAL_DIMENSIONS];
+    for word in words(text) {
+        let hash = fnv1a(word.as_bytes());
+        let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+        vector[(hash % LOCAL_DIMENSIONS as u64) as usize] += sign;
+    }
+    let norm = vector.iter().map(|value| value * value).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|value| *value /= norm);
+    }
+    vector
+}
+
+/// Cosine similarity, 0 when either vector is zero or their lengths differ
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norms = a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|y| y * y).sum::<f32>().sqrt();
+    if norms == 0.0 { 0.0 } else { dot / norms }
+}
+
+impl AiEngine {
+    /// Embedding model this engine's provider uses, `configured` overriding the
+    /// provider's default. Vectors from different models must not be compared.
+    pub fn embedding_model(&self, configured: Option<&str>) -> String {
+        match self.config.provider.as_str() {
+            "openai" => configured.unwrap_or(DEFAULT_OPENAI_EMBEDDING_MODEL).to_string(),
+            _ => LOCAL_EMBEDDING_MODEL.to_string(),
+        }
+    }
+
+    /// Embed each of `texts` with `model`, as named by `embedding_model`
+    pub async fn embed(&self, model: &str, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        if model == LOCAL_EMBEDDING_MODEL {
+            return Ok(texts.iter().map(|text| local_embedding(text)).collect());
+        }
+        self.track("embeddings", self.openai_embeddings(model, texts)).await
+    }
+
+    async fn openai_embeddings(&self, model: &str, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        // Self-hosted OpenAI-compatible servers usually run without a key
+        let api_key = self.config.api_key.as_ref();
+        if api_key.is_none() && self.config.base_url.is_none() {
+            return Err(AiError::Unavailable("OpenAI API key not configured".to_string()).into());
+        }
+        let client = self.http_client.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("HTTP client not initialized"))?;
+        let base_url = self.config.base_url.as_deref().unwrap_or("https://api.openai.com/v1");
+        let url = format!("{}/embeddings", base_url);
+
+        let mut vectors = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(MAX_BATCH) {
+            let mut request = client.post(&url).json(&OpenAIEmbeddingRequest { model, input: batch });
+            if let Some(api_key) = api_key {
+                request = request.bearer_auth(api_key);
+            }
+            let response = request
+                .send()
+                .await
+                .map_err(|e| self.request_error(e))?;
+
+            let status = response.status();
+            if !status.is_success() {
+                if let Some(message) = crate::utils::http::proxy_status_failure(&self.config.proxy, status) {
+                    return Err(AiError::Proxy(message).into());
+                }
+                return Err(match response.json::<OpenAIError>().await {
+                    Ok(error) => anyhow::anyhow!("OpenAI API error: {}", error.error.message),
+                    Err(_) => anyhow::anyhow!("OpenAI embeddings request failed with status: {}", status),
+                });
+            }
+
+            let mut body: OpenAIEmbeddingResponse = response.json().await.map_err(|e| self.request_error(e))?;
+            if body.data.len() != batch.len() {
+                anyhow::bail!("OpenAI returned {} embeddings for {} inputs", body.data.len(), batch.len());
+            }
+            body.data.sort_by_key(|embedding| embedding.index);
+            vectors.extend(body.data.into_iter().map(|embedding| embedding.embedding));
+        }
+        Ok(vectors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identifiers_share_words_with_prose() {
+        assert_eq!(words("parseHTTPHeaders load_config_file"), vec!["parse", "http", "header", "load", "config", "file"]);
+
+        let code = local_embedding("fn loadConfig(path: &Path) -> Config");
+        let related = cosine_similarity(&code, &local_embedding("load the config"));
+        let unrelated = cosine_similarity(&code, &local_embedding("render a button"));
+        assert!(related > 0.5 && unrelated < 0.1, "related {} unrelated {}", related, unrelated);
+    }
+
+    #[tokio::test]
+    async fn test_self_hosted_embeddings_need_no_key() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            while !String::from_utf8_lossy(&request).ends_with("]}") {
+                let n = socket.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+            let body = r#"{"data":[{"index":0,"embedding":[0.6,0.8]}]}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&request).to_string()
+        });
+
+        let config = crate::ai::AiConfig { api_key: None, ..crate::ai::tests::openai_config(addr) };
+        let engine = AiEngine::new_async(config).await.unwrap();
+        let vectors = engine.embed("nomic-embed-text", &["fn main() {}".to_string()]).await.unwrap();
+
+        assert_eq!(vectors, vec![vec![0.6, 0.8]]);
+        assert!(!server.await.unwrap().to_lowercase().contains("authorization"));
+    }
+}
@@ -0,0 +1,106 @@
+//! AI request lifecycle on the event bus
+//!
+//! Provider calls are reported as started, then finished or failed, so the UI can
+//! show that a request is in flight. Token counts come from the provider's usage
+//! report and are zero when it sends none (local analysis, for example).
+
+use anyhow::Result;
+use std::cell::Cell;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
+
+use super::{AiEngine, OpenAIUsage};
+use crate::utils::event_bus::{EventBus, IdeEvent};
+
+tokio::task_local! {
+    /// Tokens used so far by the tracked request running on this task
+    static REQUEST_TOKENS: Cell<u32>;
+}
+
+/// Add the provider's reported usage to the request being tracked, if any
+pub(super) fn record_usage(usage: Option<&OpenAIUsage>) {
+    if let Some(usage) = usage {
+        let _ = REQUEST_TOKENS.try_with(|tokens| tokens.set(tokens.get().saturating_add(usage.total_tokens)));
+    }
+}
+
+impl AiEngine {
+    /// Report this engine's provider calls on `event_bus`
+    pub fn with_event_bus(mut self, event_bus: Arc<EventBus>) -> Self {
+        self.event_bus = Some(event_bus);
+        self
+    }
+
+    /// Run one provider call for operation `op`, publishing its lifecycle events
+    pub(super) async fn track<T>(&self, op: &str, call: impl Future<Output = Result<T>>) -> Result<T> {
+        let Some(event_bus) = &self.event_bus else {
+            return call.await;
+        };
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let _ = event_bus.broadcast(IdeEvent::AiRequestStarted {
+            id: id.clone(),
+            op: op.to_string(),
+        });
+
+        let started = Instant::now();
+        let (result, tokens) = REQUEST_TOKENS.scope(Cell::new(0), async {
+            let result = call.await;
+            (result, REQUEST_TOKENS.with(Cell::get))
+        }).await;
+
+        let event = match &result {
+            Ok(_) => IdeEvent::AiRequestFinished {
+                id,
+                tokens,
+                ms: started.elapsed().as_millis() as u64,
+            },
+            Err(e) => IdeEvent::AiRequestFailed {
+                id,
+                error: e.to_string(),
+            },
+        };
+        let _ = event_bus.broadcast(event);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::tests::{mock_openai_server, openai_config};
+    use crate::ai::CompletionRequest;
+
+    #[tokio::test]
+    async fn test_completion_emits_started_and_finished() {
+        let event_bus = Arc::new(EventBus::new());
+        let mut subscriber = event_bus.subscribe("ai").unwrap();
+        let (addr, server) = mock_openai_server("let total = a + b;").await;
+        let engine = AiEngine::new_async(openai_config(addr)).await.unwrap().with_event_bus(event_bus.clone());
+
+        engine.generate_completion(CompletionRequest {
+            language: "rust".to_string(),
+            context: String::new(),
+            position: None,
+            prompt: "let total = ".to_string(),
+            max_tokens: None,
+            cursor_position: None,
+            text_before_cursor: "let total = ".to_string(),
+            document: None,
+        }).await.unwrap();
+        server.await.unwrap();
+
+        let started_id = match subscriber.recv().await {
+            Some(IdeEvent::AiRequestStarted { id, op }) => {
+                assert_eq!(op, "completion");
+                id
+            }
+            other => panic!("expected AiRequestStarted, got {:?}", other),
+        };
+        match subscriber.recv().await {
+            Some(IdeEvent::AiRequestFinished { id, .. }) => assert_eq!(id, started_id),
+            other => panic!("expected AiRequestFinished, got {:?}", other),
+        }
+    }
+}
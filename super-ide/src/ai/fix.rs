@@ -0,0 +1,196 @@
+//! One-click fixes for a single diagnostic
+//!
+//! The model gets the whole file with line numbers and one diagnostic, and is told
+//! to change only what that diagnostic needs. Where a tree-sitter grammar exists,
+//! the edited file must parse with fewer syntax errors than the original (none,
+//! unless the original already had unrelated ones) before it is returned.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::{AiEngine, CodeIssue};
+use crate::utils::language_tools::syntax_error_count;
+
+/// Edited file addressing one diagnostic
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticFix {
+    pub fixed_code: String,
+    /// What was changed, in the model's words
+    pub description: String,
+    /// 1-based lines of the original file that were changed or removed
+    pub changed_lines: Vec<usize>,
+    /// Whether the edited code was checked with a parser for the language
+    pub syntax_checked: bool,
+}
+
+/// Diagnostic fix errors
+#[derive(Debug, thiserror::Error)]
+pub enum FixDiagnosticError {
+    #[error("Diagnostic line {line} is outside the file ({lines} lines)")]
+    LineOutOfRange { line: usize, lines: usize },
+
+    #[error("Model reply does not contain the edited code")]
+    MissingCode,
+
+    #[error("Model reply leaves the code unchanged")]
+    Unchanged,
+
+    #[error("Edited code does not parse: {errors} syntax error(s)")]
+    InvalidSyntax { errors: usize },
+}
+
+fn fix_prompt(code: &str, language: &str, issue: &CodeIssue) -> String {
+    let numbered: Vec<String> = code.lines().enumerate()
+        .map(|(i, line)| format!("{:>4} | {}", i + 1, line))
+        .collect();
+    format!(
+        "Fix exactly one diagnostic in this {language} file and nothing else.\n\
+         Diagnostic at line {line}, column {column}: {message}\n\n\
+         Make the smallest edit that resolves it: do not reformat, rename, reorder or fix other problems.\n\
+         Reply with one line `DESCRIPTION: <what you changed>`, then the complete edited file in a single \
+         ```{language} code block, without line numbers.\n\n{code}",
+        language = language,
+        line = issue.line,
+        column = issue.column,
+        message = issue.message,
+        code = numbered.join("\n"),
+    )
+}
+
+/// Split a reply into its description and the code of its first fenced block
+fn parse_fix_reply(reply: &str) -> Result<(String, String), FixDiagnosticError> {
+    let description = reply.lines()
+        .find_map(|line| line.trim().strip_prefix("DESCRIPTION:"))
+        .map(|description| description.trim().to_string())
+        .unwrap_or_default();
+
+    let code = first_code_block(reply).ok_or(FixDiagnosticError::MissingCode)?;
+    Ok((description, code.to_string()))
+}
+
+/// Body of the first fenced code block in a reply, without its trailing newlines;
+/// `None` when there is no block or it is empty
+pub(super) fn first_code_block(reply: &str) -> Option<&str> {
+    let (_, after_fence) = reply.split_once("```")?;
+    let (_, body) = after_fence.split_once('\n')?;
+    let code = match body.rfind("```") {
+        Some(end) => &body[..end],
+        None => body,
+    };
+    if code.trim().is_empty() {
+        return None;
+    }
+    Some(code.trim_end_matches('\n'))
+}
+
+/// Lines of `original` that differ from `fixed`, matching from both ends
+fn changed_lines(original: &str, fixed: &str) -> Vec<usize> {
+    let before: Vec<&str> = original.lines().collect();
+    let after: Vec<&str> = fixed.lines().collect();
+    let prefix = before.iter().zip(&after).take_while(|(a, b)| a == b).count();
+    let suffix = before[prefix..].iter().rev()
+        .zip(after[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    (prefix..before.len() - suffix).map(|line| line + 1).collect()
+}
+
+/// Check the reply against the original file and the language grammar
+pub fn validate_fix(code: &str, language: &str, reply: &str) -> Result<DiagnosticFix, FixDiagnosticError> {
+    let (description, mut fixed_code) = parse_fix_reply(reply)?;
+    if code.ends_with('\n') {
+        fixed_code.push('\n');
+    }
+    if fixed_code == code {
+        return Err(FixDiagnosticError::Unchanged);
+    }
+
+    let syntax_checked = match (syntax_error_count(code, language), syntax_error_count(&fixed_code, language)) {
+        (Some(before), Some(after)) => {
+            if after > 0 && after >= before {
+                return Err(FixDiagnosticError::InvalidSyntax { errors: after });
+            }
+            true
+        }
+        _ => false,
+    };
+
+    Ok(DiagnosticFix {
+        changed_lines: changed_lines(code, &fixed_code),
+        fixed_code,
+        description,
+        syntax_checked,
+    })
+}
+
+impl AiEngine {
+    /// Ask the model for the smallest edit of `code` that resolves `issue`
+    pub async fn fix_diagnostic(&self, code: &str, language: &str, issue: &CodeIssue) -> Result<DiagnosticFix> {
+        let lines = code.lines().count();
+        if issue.line == 0 || issue.line > lines {
+            return Err(FixDiagnosticError::LineOutOfRange { line: issue.line, lines }.into());
+        }
+
+        let reply = self.run_prompt(&fix_prompt(code, language, issue), language).await?;
+        Ok(validate_fix(code, language, &reply)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::tests::{mock_openai_server, openai_config};
+    use crate::ai::IssueSeverity;
+
+    const MISSING_SEMICOLON: &str = "fn main() {\n    let total = 1 + 2\n    println!(\"{}\", total);\n}\n";
+
+    fn issue(line: usize, message: &str) -> CodeIssue {
+        CodeIssue {
+            id: "E0001".to_string(),
+            severity: IssueSeverity::Error,
+            message: message.to_string(),
+            line,
+            column: 22,
+            file_path: Some("src/main.rs".to_string()),
+            rule_id: None,
+            fix_suggestion: None,
+            documentation_url: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fix_missing_semicolon() {
+        let (addr, server) = mock_openai_server(
+            "DESCRIPTION: Added the missing semicolon after the `total` binding.\\n```rust\\nfn main() {\\n    let total = 1 + 2;\\n    println!(\\\"{}\\\", total);\\n}\\n```"
+        ).await;
+        let engine = AiEngine::new_async(openai_config(addr)).await.unwrap();
+
+        let fix = engine.fix_diagnostic(MISSING_SEMICOLON, "rust", &issue(2, "expected `;`")).await.unwrap();
+        assert_eq!(fix.fixed_code, MISSING_SEMICOLON.replace("1 + 2\n", "1 + 2;\n"));
+        assert_eq!(fix.description, "Added the missing semicolon after the `total` binding.");
+        assert_eq!(fix.changed_lines, vec![2]);
+        assert!(fix.syntax_checked);
+
+        let request = server.await.unwrap();
+        assert!(request.contains("line 2, column 22: expected `;`"));
+        assert!(request.contains("smallest edit"));
+    }
+
+    #[test]
+    fn test_fix_that_does_not_parse_is_rejected() {
+        let broken = "DESCRIPTION: Added a semicolon.\n```rust\nfn main() {\n    let total = 1 + 2;\n    println!(\"{}\", total);\n```";
+        assert!(matches!(
+            validate_fix(MISSING_SEMICOLON, "rust", broken),
+            Err(FixDiagnosticError::InvalidSyntax { .. })
+        ));
+        assert!(matches!(
+            validate_fix(MISSING_SEMICOLON, "rust", "The line needs a semicolon."),
+            Err(FixDiagnosticError::MissingCode)
+        ));
+
+        // Languages without a grammar are returned unchecked
+        let fix = validate_fix("x = 1 +\n", "ruby", "DESCRIPTION: Completed the sum.\n```ruby\nx = 1 + 2\n```").unwrap();
+        assert_eq!(fix.fixed_code, "x = 1 + 2\n");
+        assert!(!fix.syntax_checked);
+    }
+}
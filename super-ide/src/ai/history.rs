@@ -0,0 +1,110 @@
+//! Explanations of how code evolved
+//!
+//! The commits that touched a selection, as `git log -L` reports them, are given to
+//! the model oldest first with their diffs of those lines, so it can explain why
+//! the code changed the way it did. Lines without committed history are answered
+//! without asking the model.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use super::AiEngine;
+use crate::git::LineChange;
+
+/// Characters of each commit's diff included in the prompt
+const MAX_DIFF_CHARS: usize = 2000;
+
+/// The model's account of a selection's history, with the commits it was based on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryExplanation {
+    pub explanation: String,
+    /// Newest first, as git lists them
+    pub commits: Vec<LineChange>,
+}
+
+fn history_prompt(path: &str, code: &str, history: &[LineChange]) -> String {
+    let commits = history.iter()
+        .rev()
+        .map(|change| {
+            let diff = match change.diff.char_indices().nth(MAX_DIFF_CHARS) {
+                Some((end, _)) => format!("{}\n[diff truncated]", &change.diff[..end]),
+                None => change.diff.clone(),
+            };
+            format!(
+                "Commit {} by {} on {}\n{}\n\n{}",
+                &change.hash[..change.hash.len().min(12)],
+                change.author,
+                change.timestamp.format("%Y-%m-%d"),
+                change.message,
+                diff
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n---\n\n");
+
+    format!(
+        "These lines of {} are the result of the commits below, oldest first:\n```\n{}\n```\n\n{}\n\n\
+         Explain why the code evolved this way: what each change was for, going by the commit \
+         messages and diffs, and what that means for anyone changing it now. Do not restate the diffs.",
+        path, code, commits
+    )
+}
+
+impl AiEngine {
+    /// Explain the history of `code`, the selected lines of `path`, from the commits
+    /// that changed them
+    pub async fn explain_history(&self, path: &str, code: &str, history: Vec<LineChange>) -> Result<HistoryExplanation> {
+        if history.is_empty() {
+            return Ok(HistoryExplanation {
+                explanation: "These lines have no committed history yet; they were added after the last commit.".to_string(),
+                commits: history,
+            });
+        }
+
+        let language = crate::file_ops::language_for_path(Path::new(path));
+        let explanation = self.run_prompt(&history_prompt(path, code, &history), language).await?;
+        Ok(HistoryExplanation {
+            explanation: explanation.trim().to_string(),
+            commits: history,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::tests::{mock_openai_server, openai_config};
+    use crate::git::tests::{git, temp_workspace};
+    use crate::git::GitManager;
+
+    #[tokio::test]
+    async fn test_history_of_edited_lines_is_explained_oldest_first() {
+        let root = temp_workspace();
+        git(&root, &["init", "-q"]);
+        std::fs::write(root.join("retry.py"), "def fetch():\n    return get(URL)\n").unwrap();
+        git(&root, &["add", "."]);
+        git(&root, &["commit", "-q", "-m", "Fetch the feed"]);
+        std::fs::write(root.join("retry.py"), "def fetch():\n    return get(URL, timeout=5)\n").unwrap();
+        git(&root, &["commit", "-q", "-am", "Time out hung feed requests"]);
+
+        let (addr, server) = mock_openai_server("The timeout was added after requests hung.").await;
+        let engine = AiEngine::new_async(openai_config(addr)).await.unwrap();
+
+        let history = GitManager::new(root.clone()).line_history("retry.py", 2, 2).await.unwrap();
+        let explained = engine.explain_history("retry.py", "    return get(URL, timeout=5)", history).await.unwrap();
+        assert_eq!(explained.explanation, "The timeout was added after requests hung.");
+        assert_eq!(explained.commits.len(), 2);
+
+        let request = server.await.unwrap();
+        let (first, second) = (request.find("Fetch the feed").unwrap(), request.find("Time out hung feed requests").unwrap());
+        assert!(first < second, "commits should be listed oldest first");
+        assert!(request.contains("+    return get(URL, timeout=5)"));
+
+        // Uncommitted lines are answered without the provider, which would refuse a second call
+        let explained = engine.explain_history("retry.py", "    log(URL)", Vec::new()).await.unwrap();
+        assert!(explained.commits.is_empty() && explained.explanation.contains("no committed history"));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -3,16 +3,39 @@
 //! Provides AI-powered code analysis, completion, and assistance features.
 //! Supports both local AI models and cloud providers like OpenAI.
 
+pub mod auto_analysis;
+pub mod batch;
+pub mod docs;
+pub mod changelog;
+pub mod chat;
+pub mod context;
+pub mod embeddings;
+pub mod events;
+pub mod fix;
+pub mod history;
+pub mod patch;
+pub mod phased;
+pub mod regex_builder;
+pub mod review;
+pub mod rules;
+pub mod semantic;
+pub mod testgen;
+pub mod tools;
+pub mod typehints;
+
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use reqwest::{Client, header};
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use lru::LruCache;
+use once_cell::sync::Lazy;
+use regex::Regex;
 use std::num::NonZeroUsize;
 
 // Import Configuration types for conversion
-use crate::config::{Configuration, AIProvider};
+use crate::config::{Configuration, AIProvider, ProxySettings};
 
 /// AI configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,6 +46,65 @@ pub struct AiConfig {
     pub temperature: f32,
     pub max_tokens: u32,
     pub base_url: Option<String>,
+    pub model_path: Option<String>,
+    pub request_timeout_secs: u64,
+    pub connect_timeout_secs: u64,
+    #[serde(default)]
+    pub proxy: ProxySettings,
+}
+
+/// Per-request overrides of the configured provider settings
+#[derive(Debug, Clone, Default)]
+pub struct AiOverrides {
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    /// Base URL of an OpenAI-compatible server, e.g. `http://localhost:11434/v1`
+    pub base_url: Option<String>,
+    /// Key for `base_url`; the configured key is only sent to the configured server
+    pub api_key: Option<String>,
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+}
+
+/// Providers a request may switch to
+const OVERRIDABLE_PROVIDERS: &[&str] = &["openai", "local"];
+
+/// Upper bound for a requested completion length
+const MAX_COMPLETION_TOKENS: u32 = 32_768;
+
+/// AI engine errors that callers may want to handle distinctly
+#[derive(Debug, thiserror::Error)]
+pub enum AiError {
+    #[error("AI provider did not respond within {0} seconds")]
+    Timeout(u64),
+
+    #[error("AI provider request failed: {0}")]
+    Request(String),
+
+    #[error("AI provider unavailable: {0}")]
+    Unavailable(String),
+
+    #[error("AI provider unreachable, {0}")]
+    Proxy(String),
+
+    #[error("AI provider returned {status}: {message}")]
+    Status { status: u16, message: String },
+}
+
+impl AiError {
+    /// Whether the provider itself failed (unreachable, overloaded, out of quota or
+    /// misconfigured), as opposed to rejecting the request
+    pub fn is_provider_failure(&self) -> bool {
+        match self {
+            Self::Timeout(_) | Self::Request(_) | Self::Unavailable(_) | Self::Proxy(_) => true,
+            Self::Status { status, .. } => *status >= 500 || matches!(status, 401 | 403 | 429),
+        }
+    }
+}
+
+/// Whether another provider may succeed where this error occurred
+fn is_provider_failure(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<AiError>().is_some_and(AiError::is_provider_failure)
 }
 
 /// OpenAI API request structures
@@ -86,6 +168,11 @@ impl From<Configuration> for AiConfig {
             model_name: "default".to_string(), // Could be enhanced to use model_path
             temperature: config.ai.temperature,
             max_tokens: config.ai.max_tokens,
+            base_url: config.ai.base_url,
+            model_path: config.ai.model_path,
+            request_timeout_secs: config.ai.request_timeout_secs,
+            connect_timeout_secs: config.ai.connect_timeout_secs,
+            proxy: config.proxy,
         }
     }
 }
@@ -144,12 +231,16 @@ pub struct CodeComplexity {
     pub complexity_score: Option<f32>,
 }
 
-/// Issue severity levels
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// Issue severity levels, least severe first
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum IssueSeverity {
+    #[serde(alias = "info")]
     Info,
+    #[serde(alias = "warning")]
     Warning,
+    #[serde(alias = "error")]
     Error,
+    #[serde(alias = "critical")]
     Critical,
 }
 
@@ -231,6 +322,33 @@ pub struct SecurityVulnerability {
     pub cve_references: Vec<String>,
 }
 
+/// Location references in model prose: "line 12", "lines 10-14", "line 3, column 7"
+static LINE_REFERENCE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\blines?\s+(\d+)(?:\s*(?:-|\u{2013}|to)\s*\d+)?(?:\s*,?\s*(?:col|column)\s+(\d+))?").unwrap()
+});
+
+/// Compiler-style `line:column` references, e.g. "main.rs:42:5" or "at 42:5"
+static LINE_COLUMN: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b(\d+):(\d+)\b").unwrap());
+
+/// Extract the (line, column) a finding refers to; a range anchors at its first line
+fn parse_line_anchor(text: &str) -> Option<(usize, usize)> {
+    if let Some(caps) = LINE_REFERENCE.captures(text) {
+        let line = caps.get(1)?.as_str().parse().ok()?;
+        let column = caps.get(2).and_then(|c| c.as_str().parse().ok()).unwrap_or(1);
+        return Some((line, column));
+    }
+
+    let caps = LINE_COLUMN.captures(text)?;
+    Some((caps[1].parse().ok()?, caps[2].parse().ok()?))
+}
+
+/// Whether a line of Python is an `except:` clause naming no exception
+fn is_bare_except(line: &str) -> bool {
+    line.trim_start()
+        .strip_prefix("except")
+        .is_some_and(|rest| rest.trim_start().starts_with(':'))
+}
+
 /// Code analysis result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnalysisResult {
@@ -244,6 +362,17 @@ pub struct AnalysisResult {
     pub maintainability_score: f32,
 }
 
+impl AnalysisResult {
+    /// Drop issues, bug predictions, code smells and vulnerabilities less severe
+    /// than `min_severity`; scores and suggestions are kept
+    pub fn retain_min_severity(&mut self, min_severity: &IssueSeverity) {
+        self.issues.retain(|issue| issue.severity >= *min_severity);
+        self.bug_predictions.retain(|bug| bug.severity >= *min_severity);
+        self.code_smells.retain(|smell| smell.severity >= *min_severity);
+        self.security_vulnerabilities.retain(|vulnerability| vulnerability.severity >= *min_severity);
+    }
+}
+
 /// Debug session information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DebugSession {
@@ -303,10 +432,13 @@ pub struct CodeExplanationRequest {
     pub language: String,
     pub context: Option<String>,
     pub explanation_level: ExplanationLevel,
+    /// Ask the provider even when an explanation of the same code is cached
+    #[serde(default)]
+    pub no_cache: bool,
 }
 
 /// Levels of code explanation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ExplanationLevel {
     Basic,
     Detailed,
@@ -321,6 +453,49 @@ pub struct CodeExplanation {
     pub key_concepts: Vec<String>,
     pub complexity_analysis: String,
     pub suggestions: Vec<String>,
+    /// Notes anchored to specific lines; empty when the model gave none
+    #[serde(default)]
+    pub annotations: Vec<CodeAnnotation>,
+}
+
+/// Inclusive, 1-based line range
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// An explanation note attached to a range of lines
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CodeAnnotation {
+    pub line_range: LineRange,
+    pub note: String,
+}
+
+/// JSON shape the model is asked to return for explanations
+#[derive(Debug, Deserialize)]
+struct ExplanationPayload {
+    summary: Option<String>,
+    explanation: Option<String>,
+    #[serde(default)]
+    key_concepts: Vec<String>,
+    #[serde(default)]
+    annotations: Vec<RawAnnotation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawAnnotation {
+    line_range: RawLineRange,
+    note: String,
+}
+
+/// Line ranges as models tend to write them: `[3, 7]`, `5` or `{"start": 3, "end": 7}`
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawLineRange {
+    Pair([usize; 2]),
+    Single(usize),
+    Object { start: usize, end: usize },
 }
 
 /// Performance analysis result
@@ -375,6 +550,48 @@ pub struct CompletionSuggestion {
     pub text: String,
     pub confidence: f32,
     pub kind: String,
+    /// Text the suggestion replaces, usually the word under the cursor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replace_range: Option<TextRange>,
+    /// What to put in place of `replace_range`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub insert_text: Option<String>,
+}
+
+/// Half-open range of character offsets, `start..end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl TextRange {
+    /// The identifier around character offset `cursor` in `code`; empty, at the
+    /// cursor, when it is not touching one
+    pub fn word_at(code: &str, cursor: usize) -> Self {
+        let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+        let chars: Vec<char> = code.chars().collect();
+        let cursor = cursor.min(chars.len());
+        let before = chars[..cursor].iter().rev().take_while(|c| is_word(c)).count();
+        let after = chars[cursor..].iter().take_while(|c| is_word(c)).count();
+        Self { start: cursor - before, end: cursor + after }
+    }
+}
+
+impl CompletionSuggestion {
+    /// Anchor the suggestion at character offset `cursor` in `code`: it replaces the
+    /// word there, and `insert_text` is the whole new word whether `text` repeats the
+    /// part already typed or only continues it
+    pub fn anchored(self, code: &str, cursor: usize) -> Self {
+        let range = TextRange::word_at(code, cursor);
+        let typed: String = code.chars().skip(range.start).take(cursor.min(range.end) - range.start).collect();
+        let insert_text = if self.text.starts_with(&typed) {
+            self.text.clone()
+        } else {
+            format!("{}{}", typed, self.text)
+        };
+        Self { replace_range: Some(range), insert_text: Some(insert_text), ..self }
+    }
 }
 
 impl std::fmt::Display for CompletionSuggestion {
@@ -389,6 +606,14 @@ pub struct CompletionResponse {
     pub text: String,
     pub confidence: f32,
     pub suggestions: Vec<CompletionSuggestion>,
+    /// Provider that produced the completion, which differs from the configured
+    /// one when a fallback stepped in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub served_by: Option<String>,
+    /// Set when the local heuristics answered because the provider missed the
+    /// completion deadline
+    #[serde(default)]
+    pub fallback: bool,
 }
 
 /// Completion request
@@ -401,6 +626,9 @@ pub struct CompletionRequest {
     pub max_tokens: Option<u32>,
     pub cursor_position: Option<(usize, usize)>,
     pub text_before_cursor: String,
+    /// File or document being edited; a completion is only continued in the one it was made for
+    #[serde(default)]
+    pub document: Option<String>,
 }
 
 /// User feedback for AI learning
@@ -425,6 +653,109 @@ pub struct CodeSuggestion {
     pub confidence: f32,
 }
 
+/// Source file handed to the project overview
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectSourceFile {
+    pub path: String,
+    pub content: String,
+}
+
+/// Whole-project onboarding brief
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectOverview {
+    pub architecture_summary: String,
+    pub main_modules: Vec<String>,
+    pub where_to_start: Vec<String>,
+    pub files_included: Vec<String>,
+    pub content_hash: String,
+}
+
+/// Model offered by an AI provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub id: String,
+    pub owned_by: Option<String>,
+}
+
+/// Models available from the configured provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelList {
+    pub provider: String,
+    pub models: Vec<ModelInfo>,
+    /// Why the list is empty when the provider could not be queried
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIModelsResponse {
+    data: Vec<OpenAIModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIModel {
+    id: String,
+    owned_by: Option<String>,
+}
+
+/// How long a model listing is reused before querying the provider again
+const MODEL_LIST_TTL: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// Claude models offered when Anthropic is the provider
+const ANTHROPIC_MODELS: &[&str] = &[
+    "claude-3-5-sonnet-latest",
+    "claude-3-5-haiku-latest",
+    "claude-3-opus-latest",
+    "claude-3-haiku-20240307",
+];
+
+/// File extensions recognised as local model weights
+const LOCAL_MODEL_EXTENSIONS: &[&str] = &["gguf", "ggml", "bin", "safetensors", "onnx"];
+
+/// Upper bound on prompt characters sent per overview chunk
+const OVERVIEW_CHUNK_CHARS: usize = 12_000;
+
+/// Project overviews kept before the least recently used is dropped
+const OVERVIEW_CACHE_SIZE: usize = 8;
+
+/// Explanations kept before the least recently used is dropped
+const EXPLANATION_CACHE_SIZE: usize = 64;
+
+/// Provider analyses a batch runs at once
+const MAX_CONCURRENT_ANALYSES: usize = 4;
+
+/// Last completion fetched from the provider, reused while the user types into it
+#[derive(Debug, Clone)]
+struct CompletionAnchor {
+    text_before_cursor: String,
+    response: CompletionResponse,
+}
+
+impl CompletionAnchor {
+    /// What remains of the anchored completion once the user has typed on to
+    /// `text_before_cursor`; `None` if they edited before the anchor, typed something
+    /// the completion didn't predict, or typed all of it
+    fn continue_with(&self, text_before_cursor: &str) -> Option<CompletionResponse> {
+        let typed = text_before_cursor.strip_prefix(self.text_before_cursor.as_str())?;
+        let text = self.response.text.strip_prefix(typed)?;
+        if text.is_empty() {
+            return None;
+        }
+
+        Some(CompletionResponse {
+            text: text.to_string(),
+            confidence: self.response.confidence,
+            suggestions: self.response.suggestions.iter()
+                .filter_map(|suggestion| {
+                    let text = suggestion.text.strip_prefix(typed).filter(|rest| !rest.is_empty())?;
+                    Some(CompletionSuggestion { text: text.to_string(), ..suggestion.clone() })
+                })
+                .collect(),
+            served_by: self.response.served_by.clone(),
+            fallback: self.response.fallback,
+        })
+    }
+}
+
 /// Main AI Engine
 #[derive(Debug, Clone)]
 pub struct AiEngine {
@@ -432,14 +763,32 @@ pub struct AiEngine {
     initialized: bool,
     http_client: Option<Client>,
     request_cache: Arc<RwLock<lru::LruCache<String, CompletionResponse>>>,
+    /// Most recent provider completion per document and language, for prefix reuse
+    completion_anchors: Arc<RwLock<HashMap<(Option<String>, String), CompletionAnchor>>>,
     analysis_cache: Arc<RwLock<lru::LruCache<String, AnalysisResult>>>,
+    overview_cache: Arc<RwLock<lru::LruCache<String, ProjectOverview>>>,
+    explanation_cache: Arc<RwLock<lru::LruCache<String, CodeExplanation>>>,
+    /// Bounds the analyses one request runs side by side
+    analysis_limiter: Arc<tokio::sync::Semaphore>,
+    models_cache: Arc<RwLock<Option<(std::time::Instant, ModelList)>>>,
+    event_bus: Option<Arc<crate::utils::event_bus::EventBus>>,
+    /// Providers to retry completions with, in order, after a provider failure
+    fallback_providers: Vec<String>,
+    /// How long a completion may wait on the provider before the local one is used
+    completion_timeout: Option<std::time::Duration>,
+    /// What background analysis may spend tokens on
+    analysis_gates: auto_analysis::AnalysisGates,
+    /// Cuts file content sent as context down to the token budget
+    context_builder: context::ContextBuilder,
+    /// Which local analysis rules run, and at what severity
+    rules: rules::RuleSet,
 }
 
 impl AiEngine {
     /// Create a new AI Engine
     pub fn new(config: AiConfig) -> Self {
         let http_client = if config.provider == "openai" || config.provider == "anthropic" {
-            Some(Client::new())
+            Self::build_http_client(&config)
         } else {
             None
         };
@@ -448,9 +797,70 @@ impl AiEngine {
             config,
             initialized: false,
             http_client,
-            request_cache: Arc::new(RwLock::new(lru::LruCache::new(100))),
-            analysis_cache: Arc::new(RwLock::new(lru::LruCache::new(50))),
+            request_cache: Arc::new(RwLock::new(lru::LruCache::new(NonZeroUsize::new(100).unwrap()))),
+            completion_anchors: Arc::new(RwLock::new(HashMap::new())),
+            analysis_cache: Arc::new(RwLock::new(lru::LruCache::new(NonZeroUsize::new(50).unwrap()))),
+            overview_cache: Arc::new(RwLock::new(lru::LruCache::new(NonZeroUsize::new(OVERVIEW_CACHE_SIZE).unwrap()))),
+            explanation_cache: Arc::new(RwLock::new(lru::LruCache::new(NonZeroUsize::new(EXPLANATION_CACHE_SIZE).unwrap()))),
+            analysis_limiter: Arc::new(tokio::sync::Semaphore::new(MAX_CONCURRENT_ANALYSES)),
+            models_cache: Arc::new(RwLock::new(None)),
+            event_bus: None,
+            fallback_providers: Vec::new(),
+            completion_timeout: None,
+            analysis_gates: auto_analysis::AnalysisGates::default(),
+            context_builder: context::ContextBuilder::default(),
+            rules: rules::RuleSet::default(),
+        }
+    }
+
+    fn build_http_client(config: &AiConfig) -> Option<Client> {
+        let builder = match crate::utils::http::client_builder(&config.proxy) {
+            Ok(builder) => builder,
+            Err(e) => {
+                log::warn!("Invalid proxy configuration: {}", e);
+                return None;
+            }
+        };
+        builder
+            .connect_timeout(std::time::Duration::from_secs(config.connect_timeout_secs))
+            .timeout(std::time::Duration::from_secs(config.request_timeout_secs))
+            .build()
+            .map_err(|e| log::warn!("Failed to create HTTP client for {}: {}", config.provider, e))
+            .ok()
+    }
+
+    /// Retry completions with `providers`, in order, when the configured provider fails
+    /// for availability reasons. The configured provider is skipped if listed.
+    pub fn with_fallback_providers(mut self, providers: Vec<String>) -> Self {
+        self.fallback_providers = providers.into_iter()
+            .filter(|provider| *provider != self.config.provider)
+            .fold(Vec::new(), |mut unique, provider| {
+                if !unique.contains(&provider) {
+                    unique.push(provider);
+                }
+                unique
+            });
+        if self.http_client.is_none() && self.fallback_providers.iter().any(|p| p == "openai") {
+            self.http_client = Self::build_http_client(&self.config);
         }
+        self
+    }
+
+    /// Answer completions locally when the provider takes longer than `timeout_ms`
+    pub fn with_completion_timeout(mut self, timeout_ms: Option<u64>) -> Self {
+        self.completion_timeout = timeout_ms.map(std::time::Duration::from_millis);
+        self
+    }
+
+    /// Budget file content sent as context with `builder`
+    pub fn with_context_builder(mut self, builder: context::ContextBuilder) -> Self {
+        self.context_builder = builder;
+        self
+    }
+
+    /// The part of `code` to send as context for a request about 0-based `cursor_line`
+    pub fn context_window(&self, code: &str, language: &str, cursor_line: usize) -> context::ContextWindow {
+        self.context_builder.build(code, language, cursor_line)
     }
 
     /// Create a new AI Engine (async version for compatibility)
@@ -466,6 +876,65 @@ impl AiEngine {
         Ok(())
     }
 
+    /// Engine for a single request with overrides applied on top of the configured settings.
+    /// Unknown providers and invalid URLs are ignored in favour of the configured defaults.
+    pub async fn with_overrides(&self, overrides: &AiOverrides) -> Result<AiEngine> {
+        let mut engine = Self::new(self.overridden_config(overrides));
+        engine.event_bus = self.event_bus.clone();
+        engine = engine.with_fallback_providers(self.fallback_providers.clone());
+        engine.completion_timeout = self.completion_timeout;
+        engine.analysis_gates = self.analysis_gates.clone();
+        engine.context_builder = self.context_builder.clone();
+        engine.rules = self.rules.clone();
+        engine.initialize().await?;
+        Ok(engine)
+    }
+
+    fn overridden_config(&self, overrides: &AiOverrides) -> AiConfig {
+        let mut config = self.config.clone();
+
+        if let Some(base_url) = overrides.base_url.as_deref() {
+            match Self::validate_base_url(base_url) {
+                Some(url) => {
+                    if config.base_url.as_deref() != Some(url.as_str()) {
+                        config.api_key = overrides.api_key.clone();
+                    }
+                    config.base_url = Some(url);
+                    // A custom base URL always speaks the OpenAI protocol
+                    config.provider = "openai".to_string();
+                }
+                None => log::warn!("Ignoring invalid AI base URL override: {}", base_url),
+            }
+        }
+        if let Some(provider) = overrides.provider.as_deref() {
+            if OVERRIDABLE_PROVIDERS.contains(&provider) {
+                config.provider = provider.to_string();
+            } else {
+                log::warn!("Ignoring unsupported AI provider override: {}", provider);
+            }
+        }
+        if let Some(model) = overrides.model.as_deref().filter(|m| !m.trim().is_empty()) {
+            config.model_name = model.to_string();
+        }
+        if let Some(temperature) = overrides.temperature.filter(|t| t.is_finite()) {
+            config.temperature = temperature.clamp(0.0, 2.0);
+        }
+        if let Some(max_tokens) = overrides.max_tokens {
+            config.max_tokens = max_tokens.clamp(1, MAX_COMPLETION_TOKENS);
+        }
+
+        config
+    }
+
+    /// Accept only absolute http(s) URLs with a host, without a trailing slash
+    fn validate_base_url(base_url: &str) -> Option<String> {
+        let url = reqwest::Url::parse(base_url.trim()).ok()?;
+        if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+            return None;
+        }
+        Some(url.as_str().trim_end_matches('/').to_string())
+    }
+
     /// Generate code completion
     pub async fn generate_completion(&self, request: CompletionRequest) -> Result<CompletionResponse> {
         if !self.initialized {
@@ -481,38 +950,97 @@ impl AiEngine {
             }
         }
 
-        let result = match self.config.provider.as_str() {
-            "openai" => self.generate_openai_completion(&request).await,
-            "local" => self.generate_local_completion(&request).await,
-            _ => Ok(CompletionResponse {
-                text: "AI provider not supported".to_string(),
-                confidence: 0.0,
-                suggestions: vec![],
-            })
+        // Typing into the last completion reuses what is left of it
+        let anchor_key = (request.document.clone(), request.language.clone());
+        {
+            let mut anchors = self.completion_anchors.write().await;
+            if let Some(anchor) = anchors.get(&anchor_key) {
+                match anchor.continue_with(&request.text_before_cursor) {
+                    Some(response) => return Ok(response),
+                    None => {
+                        anchors.remove(&anchor_key);
+                    }
+                }
+            }
+        }
+
+        let result = match self.completion_timeout.filter(|_| self.config.provider != "local") {
+            Some(limit) => match tokio::time::timeout(limit, self.generate_chain_completion(&request)).await {
+                Ok(result) => result,
+                Err(_) => {
+                    // The timed-out future was dropped, aborting its request. A late
+                    // stand-in is not cached, so the next attempt asks the provider again.
+                    log::debug!("AI provider missed the {}ms completion deadline, completing locally", limit.as_millis());
+                    let mut response = self.generate_provider_completion("local", &request).await?;
+                    response.fallback = true;
+                    return Ok(response);
+                }
+            },
+            None => self.generate_chain_completion(&request).await,
         };
 
         // Cache the result
         if let Ok(ref response) = result {
             let mut cache = self.request_cache.write().await;
             cache.put(cache_key, response.clone());
+            self.completion_anchors.write().await.insert(anchor_key, CompletionAnchor {
+                text_before_cursor: request.text_before_cursor.clone(),
+                response: response.clone(),
+            });
         }
 
         result
     }
 
-    /// Generate completion using OpenAI API
-    async fn generate_openai_completion(&self, request: &CompletionRequest) -> Result<CompletionResponse> {
-        let api_key = self.config.api_key.as_ref()
-            .ok_or_else(|| anyhow::anyhow!("OpenAI API key not configured"))?;
+    /// Generate a completion with the configured provider or, failing that, the fallbacks
+    async fn generate_chain_completion(&self, request: &CompletionRequest) -> Result<CompletionResponse> {
+        self.with_provider_fallback(|provider| async move {
+            self.generate_provider_completion(&provider, request).await
+        }).await
+    }
 
-        let client = self.http_client.as_ref()
-            .ok_or_else(|| anyhow::anyhow!("HTTP client not initialized"))?;
+    /// Run `call` with the configured provider, then the fallbacks in turn while
+    /// providers are failing; rejected input is not retried elsewhere
+    async fn with_provider_fallback<T, F, Fut>(&self, mut call: F) -> Result<T>
+    where
+        F: FnMut(String) -> Fut,
+        Fut: std::future::Future<Output = Result<T>>,
+    {
+        let chain: Vec<&str> = std::iter::once(self.config.provider.as_str())
+            .chain(self.fallback_providers.iter().map(String::as_str))
+            .collect();
+        let mut result = Err(anyhow::anyhow!("No AI provider configured"));
+        for (index, provider) in chain.iter().enumerate() {
+            result = call(provider.to_string()).await;
+            match &result {
+                Err(e) if is_provider_failure(e) && index + 1 < chain.len() => {
+                    log::warn!("AI provider {} failed, falling back to {}: {}", provider, chain[index + 1], e);
+                }
+                _ => break,
+            }
+        }
+        result
+    }
 
-        let model = match self.config.model_name.as_str() {
-            "default" => "gpt-3.5-turbo",
-            other => other,
+    /// Generate a completion with one provider, tagging the response with its name
+    async fn generate_provider_completion(&self, provider: &str, request: &CompletionRequest) -> Result<CompletionResponse> {
+        let mut response = match provider {
+            "openai" => self.track("completion", self.generate_openai_completion(request)).await?,
+            "local" => self.track("completion", self.generate_local_completion(request)).await?,
+            _ => CompletionResponse {
+                text: "AI provider not supported".to_string(),
+                confidence: 0.0,
+                suggestions: vec![],
+                served_by: None,
+                fallback: false,
+            }
         };
+        response.served_by.get_or_insert_with(|| provider.to_string());
+        Ok(response)
+    }
 
+    /// Generate completion using OpenAI API
+    async fn generate_openai_completion(&self, request: &CompletionRequest) -> Result<CompletionResponse> {
         // Build the prompt for code completion
         let system_prompt = format!(
             "You are an expert {} developer. Provide helpful, accurate code completions and suggestions. Focus on clean, idiomatic code with proper error handling.",
@@ -526,20 +1054,49 @@ impl AiEngine {
             request.text_before_cursor
         );
 
+        let messages = vec![
+            OpenAIMessage {
+                role: "system".to_string(),
+                content: system_prompt,
+            },
+            OpenAIMessage {
+                role: "user".to_string(),
+                content: user_prompt,
+            },
+        ];
+        let completion_text = self.send_openai_messages(messages, request.max_tokens).await?
+            .unwrap_or_else(|| "No completion generated".to_string());
+
+        Ok(CompletionResponse {
+            text: completion_text,
+            confidence: 0.8, // Default confidence for OpenAI responses
+            suggestions: vec![],
+            served_by: None,
+            fallback: false,
+        })
+    }
+
+    /// Send `messages` to the OpenAI chat endpoint; `None` when the reply has no choices
+    async fn send_openai_messages(&self, messages: Vec<OpenAIMessage>, max_tokens: Option<u32>) -> Result<Option<String>> {
+        // Self-hosted OpenAI-compatible servers usually run without a key
+        let api_key = self.config.api_key.as_ref();
+        if api_key.is_none() && self.config.base_url.is_none() {
+            return Err(AiError::Unavailable("OpenAI API key not configured".to_string()).into());
+        }
+
+        let client = self.http_client.as_ref()
+            .ok_or_else(|| AiError::Unavailable("HTTP client not initialized".to_string()))?;
+
+        let model = match self.config.model_name.as_str() {
+            "default" => "gpt-3.5-turbo",
+            other => other,
+        };
+
         let openai_request = OpenAIRequest {
             model: model.to_string(),
-            messages: vec![
-                OpenAIMessage {
-                    role: "system".to_string(),
-                    content: system_prompt,
-                },
-                OpenAIMessage {
-                    role: "user".to_string(),
-                    content: user_prompt,
-                },
-            ],
+            messages,
             temperature: self.config.temperature,
-            max_tokens: Some(self.config.max_tokens),
+            max_tokens: Some(max_tokens.unwrap_or(self.config.max_tokens).clamp(1, MAX_COMPLETION_TOKENS)),
             stream: false,
         };
 
@@ -547,10 +1104,12 @@ impl AiEngine {
         let url = format!("{}/chat/completions", base_url);
 
         let mut headers = header::HeaderMap::new();
-        headers.insert(
-            header::AUTHORIZATION,
-            header::HeaderValue::from_str(&format!("Bearer {}", api_key))?
-        );
+        if let Some(api_key) = api_key {
+            headers.insert(
+                header::AUTHORIZATION,
+                header::HeaderValue::from_str(&format!("Bearer {}", api_key))?
+            );
+        }
         headers.insert(
             header::CONTENT_TYPE,
             header::HeaderValue::from_static("application/json")
@@ -561,29 +1120,23 @@ impl AiEngine {
             .headers(headers)
             .json(&openai_request)
             .send()
-            .await?;
+            .await
+            .map_err(|e| self.request_error(e))?;
 
         if response.status().is_success() {
-            let openai_response: OpenAIResponse = response.json().await?;
-
-            let completion_text = if let Some(choice) = openai_response.choices.first() {
-                choice.message.content.clone()
-            } else {
-                "No completion generated".to_string()
-            };
-
-            Ok(CompletionResponse {
-                text: completion_text,
-                confidence: 0.8, // Default confidence for OpenAI responses
-                suggestions: vec![],
-            })
+            let openai_response: OpenAIResponse = response.json().await.map_err(|e| self.request_error(e))?;
+            events::record_usage(openai_response.usage.as_ref());
+            Ok(openai_response.choices.first().map(|choice| choice.message.content.clone()))
+        } else if let Some(message) = crate::utils::http::proxy_status_failure(&self.config.proxy, response.status()) {
+            Err(AiError::Proxy(message).into())
         } else {
             let status = response.status();
             let error_response: Result<OpenAIError, _> = response.json().await;
-            match error_response {
-                Ok(error) => Err(anyhow::anyhow!("OpenAI API error: {}", error.error.message)),
-                Err(_) => Err(anyhow::anyhow!("OpenAI API request failed with status: {}", status)),
-            }
+            let message = match error_response {
+                Ok(error) => error.error.message,
+                Err(_) => status.canonical_reason().unwrap_or("no error details").to_string(),
+            };
+            Err(AiError::Status { status: status.as_u16(), message }.into())
         }
     }
 
@@ -618,7 +1171,11 @@ impl AiEngine {
                 text,
                 confidence: 0.5,
                 kind: "local".to_string(),
+                replace_range: None,
+                insert_text: None,
             }).collect(),
+            served_by: None,
+            fallback: false,
         })
     }
 
@@ -629,8 +1186,8 @@ impl AiEngine {
         }
 
         match self.config.provider.as_str() {
-            "openai" => self.analyze_code_with_openai(code, language).await,
-            "local" => self.analyze_code_locally(code, language).await,
+            "openai" => self.track("analysis", self.analyze_code_with_openai(code, language)).await,
+            "local" => self.track("analysis", self.analyze_code_locally(code, language)).await,
             _ => Ok(AnalysisResult {
                 issues: vec![],
                 suggestions: vec!["AI provider not supported".to_string()],
@@ -652,6 +1209,8 @@ impl AiEngine {
         let client = self.http_client.as_ref()
             .ok_or_else(|| anyhow::anyhow!("HTTP client not initialized"))?;
 
+        // Large files are cut down to the context budget; issue lines are mapped back
+        let window = self.context_window(code, language, 0);
         let prompt = format!(
             r#"Perform comprehensive code analysis for this {} file{}.
 
@@ -669,7 +1228,7 @@ Code:
 {}"#,
             language,
             if let Some(path) = file_path { format!(" at {}", path) } else { "".to_string() },
-            code
+            window.text
         );
 
         let openai_request = OpenAIRequest {
@@ -710,10 +1269,12 @@ Code:
             .headers(headers)
             .json(&openai_request)
             .send()
-            .await?;
+            .await
+            .map_err(|e| self.request_error(e))?;
 
         if response.status().is_success() {
-            let openai_response: OpenAIResponse = response.json().await?;
+            let openai_response: OpenAIResponse = response.json().await.map_err(|e| self.request_error(e))?;
+            events::record_usage(openai_response.usage.as_ref());
             
             let analysis_text = if let Some(choice) = openai_response.choices.first() {
                 choice.message.content.clone()
@@ -722,8 +1283,18 @@ Code:
             };
 
             // Parse the comprehensive analysis
-            let (issues, suggestions, bug_predictions, code_smells, security_vulns) = 
+            let (mut issues, suggestions, bug_predictions, code_smells, security_vulns) = 
                 self.parse_comprehensive_analysis(&analysis_text, language);
+            if window.truncated {
+                // Lines on an omitted-lines marker or past the window do not exist in the file.
+                issues.retain_mut(|issue| match window.file_line(issue.line) {
+                    Some(line) => {
+                        issue.line = line;
+                        true
+                    }
+                    None => false,
+                });
+            }
 
             Ok(AnalysisResult {
                 issues,
@@ -762,7 +1333,7 @@ Code:
                     let line_num = line_num + 1;
                     
                     // Bug prediction patterns
-                    if line.contains("unwrap()") {
+                    if line.contains("unwrap()") && self.rules.is_enabled("rust::unwrap_used") {
                         bug_predictions.push(BugPrediction {
                             line: line_num,
                             column: line.find("unwrap").unwrap_or(0) + 1,
@@ -770,30 +1341,30 @@ Code:
                             confidence: 0.8,
                             description: "Potential panic if unwrap() fails".to_string(),
                             fix_suggestion: "Use ? operator or proper error handling".to_string(),
-                            severity: IssueSeverity::Warning,
+                            severity: self.rules.severity("rust::unwrap_used"),
                         });
                     }
                     
-                    if line.contains("clone()") && line.contains("&") {
+                    if line.contains("clone()") && line.contains("&") && self.rules.is_enabled("rust::clone_on_ref") {
                         code_smells.push(CodeSmell {
                             id: format!("rust_clone_ref_{}", line_num),
                             name: "Unnecessary Clone".to_string(),
                             description: "Cloning a reference is usually unnecessary".to_string(),
                             line: line_num,
                             column: line.find("clone").unwrap_or(0) + 1,
-                            severity: IssueSeverity::Info,
+                            severity: self.rules.severity("rust::clone_on_ref"),
                             refactoring_suggestion: "Consider using references directly".to_string(),
                         });
                     }
                     
                     // Security patterns
-                    if line.contains("expect()") {
+                    if line.contains("expect()") && self.rules.is_enabled("rust::expect_used") {
                         security_vulnerabilities.push(SecurityVulnerability {
                             id: format!("rust_expect_{}", line_num),
                             cwe_id: Some("CWE-754".to_string()),
                             title: "Improper Check for Exceptional Conditions".to_string(),
                             description: "expect() can panic and should be used carefully".to_string(),
-                            severity: IssueSeverity::Warning,
+                            severity: self.rules.severity("rust::expect_used"),
                             line: line_num,
                             column: line.find("expect").unwrap_or(0) + 1,
                             recommendation: "Use proper error handling instead of expect()".to_string(),
@@ -809,10 +1380,10 @@ Code:
                 for (line_num, line) in lines.iter().enumerate() {
                     let line_num = line_num + 1;
                     
-                    if line.contains("except:") && !line.contains("except") {
+                    if is_bare_except(line) && self.rules.is_enabled("python::bare_except") {
                         issues.push(CodeIssue {
                             id: format!("python_bare_except_{}", line_num),
-                            severity: IssueSeverity::Warning,
+                            severity: self.rules.severity("python::bare_except"),
                             message: "Avoid bare except clauses".to_string(),
                             line: line_num,
                             column: line.find("except").unwrap_or(0) + 1,
@@ -823,13 +1394,13 @@ Code:
                         });
                     }
                     
-                    if line.contains("eval(") {
+                    if line.contains("eval(") && self.rules.is_enabled("python::eval") {
                         security_vulnerabilities.push(SecurityVulnerability {
                             id: format!("python_eval_{}", line_num),
                             cwe_id: Some("CWE-95".to_string()),
                             title: "Code Injection".to_string(),
                             description: "eval() can execute arbitrary code and is a security risk".to_string(),
-                            severity: IssueSeverity::Critical,
+                            severity: self.rules.severity("python::eval"),
                             line: line_num,
                             column: line.find("eval").unwrap_or(0) + 1,
                             recommendation: "Use ast.literal_eval() or avoid dynamic code execution".to_string(),
@@ -842,28 +1413,28 @@ Code:
                 for (line_num, line) in lines.iter().enumerate() {
                     let line_num = line_num + 1;
                     
-                    if line.contains("==") && !line.contains("===") {
+                    if line.contains("==") && !line.contains("===") && self.rules.is_enabled("js::eqeqeq") {
                         issues.push(CodeIssue {
                             id: format!("js_eqeq_{}", line_num),
-                            severity: IssueSeverity::Warning,
+                            severity: self.rules.severity("js::eqeqeq"),
                             message: "Use === instead of ==".to_string(),
                             line: line_num,
                             column: line.find("==").unwrap_or(0) + 1,
                             file_path: file_path.map(|s| s.to_string()),
                             rule_id: Some("eqeqeq".to_string()),
-                            fix_suggestion: Some("Use === for type-safe comparison".to_string()),
+                            fix_suggestion: Some("Replace `==` with `===` for type-safe comparison".to_string()),
                             documentation_url: Some("https://developer.mozilla.org/en-US/docs/Web/JavaScript/Equality_comparisons_and_sameness".to_string()),
                         });
                     }
                     
-                    if line.contains("var ") {
+                    if line.contains("var ") && self.rules.is_enabled("js::no_var") {
                         code_smells.push(CodeSmell {
                             id: format!("js_var_{}", line_num),
                             name: "Deprecated var Declaration".to_string(),
                             description: "var is deprecated, use let or const instead".to_string(),
                             line: line_num,
                             column: line.find("var").unwrap_or(0) + 1,
-                            severity: IssueSeverity::Info,
+                            severity: self.rules.severity("js::no_var"),
                             refactoring_suggestion: "Replace var with let or const".to_string(),
                         });
                     }
@@ -895,7 +1466,7 @@ Code:
         let prompt = format!(
             "Analyze this {} code for potential issues, bugs, and improvements. Provide specific suggestions with line numbers if possible.\n\nCode:\n{}",
             language,
-            code
+            self.context_window(code, language, 0).text
         );
 
         let openai_request = OpenAIRequest {
@@ -936,10 +1507,12 @@ Code:
             .headers(headers)
             .json(&openai_request)
             .send()
-            .await?;
+            .await
+            .map_err(|e| self.request_error(e))?;
 
         if response.status().is_success() {
-            let openai_response: OpenAIResponse = response.json().await?;
+            let openai_response: OpenAIResponse = response.json().await.map_err(|e| self.request_error(e))?;
+            events::record_usage(openai_response.usage.as_ref());
             
             let analysis_text = if let Some(choice) = openai_response.choices.first() {
                 choice.message.content.clone()
@@ -992,7 +1565,7 @@ Code:
                 }
             },
             "python" => {
-                if code.contains("except:") && !code.contains("except") {
+                if code.lines().any(is_bare_except) {
                     suggestions.push("Avoid bare except clauses, catch specific exceptions".to_string());
                 }
                 if code.contains("== None") {
@@ -1112,6 +1685,9 @@ Code:
                 continue;
             }
 
+            // Anchor the finding to the source location mentioned in the prose
+            let (anchor_line, anchor_column) = parse_line_anchor(trimmed).unwrap_or((1, 1));
+
             // Parse different types of findings
             if trimmed.starts_with("Issue:") || trimmed.starts_with("Warning:") || trimmed.starts_with("Error:") {
                 let severity = if trimmed.starts_with("Error:") {
@@ -1126,8 +1702,8 @@ Code:
                     id: format!("ai_issue_{}", line_num),
                     severity,
                     message: trimmed.to_string(),
-                    line: line_num,
-                    column: 1,
+                    line: anchor_line,
+                    column: anchor_column,
                     file_path: None,
                     rule_id: None,
                     fix_suggestion: None,
@@ -1137,8 +1713,8 @@ Code:
                 suggestions.push(trimmed.to_string());
             } else if trimmed.starts_with("Bug:") {
                 bug_predictions.push(BugPrediction {
-                    line: line_num,
-                    column: 1,
+                    line: anchor_line,
+                    column: anchor_column,
                     bug_type: BugType::LogicError,
                     confidence: 0.7,
                     description: trimmed.to_string(),
@@ -1150,8 +1726,8 @@ Code:
                     id: format!("ai_smell_{}", line_num),
                     name: "Code Smell".to_string(),
                     description: trimmed.to_string(),
-                    line: line_num,
-                    column: 1,
+                    line: anchor_line,
+                    column: anchor_column,
                     severity: IssueSeverity::Info,
                     refactoring_suggestion: "Consider refactoring".to_string(),
                 });
@@ -1162,8 +1738,8 @@ Code:
                     title: "Security Issue".to_string(),
                     description: trimmed.to_string(),
                     severity: IssueSeverity::Warning,
-                    line: line_num,
-                    column: 1,
+                    line: anchor_line,
+                    column: anchor_column,
                     recommendation: "Review security implications".to_string(),
                     cve_references: vec![],
                 });
@@ -1224,10 +1800,12 @@ Code:
             .headers(headers)
             .json(&openai_request)
             .send()
-            .await?;
+            .await
+            .map_err(|e| self.request_error(e))?;
 
         if response.status().is_success() {
-            let openai_response: OpenAIResponse = response.json().await?;
+            let openai_response: OpenAIResponse = response.json().await.map_err(|e| self.request_error(e))?;
+            events::record_usage(openai_response.usage.as_ref());
             
             let analysis_text = if let Some(choice) = openai_response.choices.first() {
                 choice.message.content.clone()
@@ -1335,11 +1913,14 @@ Code:
         };
 
         let prompt = format!(
-            "{} for this {} code. Explain what it does, how it works, and any important concepts.\n\nContext: {}\n\nCode:\n{}",
+            "{} for this {} code. Explain what it does, how it works, and any important concepts.\n\n\
+             Respond with only a JSON object of the form {{\"summary\": string, \"explanation\": string, \
+             \"key_concepts\": [string], \"annotations\": [{{\"line_range\": [start, end], \"note\": string}}]}}, \
+             where line numbers are 1-based and refer to the code below.\n\nContext: {}\n\nCode:\n{}",
             level_instruction,
             request.language,
             request.context.as_deref().unwrap_or(""),
-            request.code
+            Self::number_lines(&request.code)
         );
 
         let openai_request = OpenAIRequest {
@@ -1377,24 +1958,20 @@ Code:
             .headers(headers)
             .json(&openai_request)
             .send()
-            .await?;
+            .await
+            .map_err(|e| self.request_error(e))?;
 
         if response.status().is_success() {
-            let openai_response: OpenAIResponse = response.json().await?;
+            let openai_response: OpenAIResponse = response.json().await.map_err(|e| self.request_error(e))?;
+            events::record_usage(openai_response.usage.as_ref());
             
-            let explanation = if let Some(choice) = openai_response.choices.first() {
+            let content = if let Some(choice) = openai_response.choices.first() {
                 choice.message.content.clone()
             } else {
                 "No explanation generated".to_string()
             };
 
-            Ok(CodeExplanation {
-                summary: "Code explanation generated".to_string(),
-                explanation,
-                key_concepts: vec!["Functionality".to_string(), "Implementation".to_string()],
-                complexity_analysis: "Moderate complexity".to_string(),
-                suggestions: vec!["Consider adding documentation".to_string()],
-            })
+            Ok(Self::parse_explanation_response(&content, request.code.lines().count()))
         } else {
             Err(anyhow::anyhow!("Code explanation failed"))
         }
@@ -1417,20 +1994,25 @@ Code:
             key_concepts: vec!["Language Features".to_string(), "Logic Flow".to_string()],
             complexity_analysis: "Code complexity analysis based on structure".to_string(),
             suggestions: vec!["Add comments for clarity".to_string()],
+            annotations: Vec::new(),
         })
     }
 
     /// Generate unit tests using OpenAI
-    async fn generate_tests_with_openai(&self, code: &str, language: &str) -> Result<String> {
+    async fn generate_tests_with_openai(&self, code: &str, language: &str, framework: Option<testgen::TestFramework>) -> Result<String> {
         let api_key = self.config.api_key.as_ref()
             .ok_or_else(|| anyhow::anyhow!("OpenAI API key not configured"))?;
 
         let client = self.http_client.as_ref()
             .ok_or_else(|| anyhow::anyhow!("HTTP client not initialized"))?;
 
+        let framework_hint = framework
+            .map(|framework| format!(" Write them for {}.", framework.prompt_hint()))
+            .unwrap_or_default();
         let prompt = format!(
-            "Generate comprehensive unit tests for this {} code. Include edge cases and error scenarios.\n\nCode:\n{}",
+            "Generate comprehensive unit tests for this {} code. Include edge cases and error scenarios.{}\n\nCode:\n{}",
             language,
+            framework_hint,
             code
         );
 
@@ -1469,10 +2051,12 @@ Code:
             .headers(headers)
             .json(&openai_request)
             .send()
-            .await?;
+            .await
+            .map_err(|e| self.request_error(e))?;
 
         if response.status().is_success() {
-            let openai_response: OpenAIResponse = response.json().await?;
+            let openai_response: OpenAIResponse = response.json().await.map_err(|e| self.request_error(e))?;
+            events::record_usage(openai_response.usage.as_ref());
             
             let test_code = if let Some(choice) = openai_response.choices.first() {
                 choice.message.content.clone()
@@ -1487,55 +2071,121 @@ Code:
     }
 
     /// Generate unit tests using local patterns
-    async fn generate_tests_locally(&self, code: &str, language: &str) -> Result<String> {
-        let test_template = match language.to_lowercase().as_str() {
-            "rust" => r#"#[cfg(test)]
-mod tests {
-    use super::*;
+    async fn generate_tests_locally(&self, framework: Option<testgen::TestFramework>) -> Result<String> {
+        Ok(framework
+            .map(testgen::TestFramework::template)
+            .unwrap_or("// Tests not supported for this language")
+            .to_string())
+    }
 
-    #[test]
-    fn test_basic_functionality() {
-        // Add your test cases here
-        assert!(true);
+    /// Check if AI provider is available
+    pub async fn ai_provider(&self) -> Result<String> {
+        Ok(self.config.provider.clone())
     }
 
-    #[test]
-    fn test_edge_cases() {
-        // Test edge cases and error scenarios
-        assert!(true);
-    }
-}"#,
-            "python" => r#"import unittest
-
-class TestCode(unittest.TestCase):
-    def test_basic_functionality(self):
-        # Add your test cases here
-        self.assertTrue(True)
-
-    def test_edge_cases(self):
-        # Test edge cases and error scenarios
-        self.assertTrue(True)
-
-if __name__ == '__main__':
-    unittest.main()"#,
-            "javascript" => r#"describe('Test Suite', () => {
-    test('basic functionality', () => {
-        expect(true).toBe(true);
-    });
-
-    test('edge cases', () => {
-        expect(true).toBe(true);
-    });
-});"#,
-            _ => "// Tests not supported for this language"
+    /// List models the configured provider can serve, cached for a few minutes
+    pub async fn list_models(&self) -> ModelList {
+        {
+            let cache = self.models_cache.read().await;
+            if let Some((fetched_at, models)) = cache.as_ref() {
+                if fetched_at.elapsed() < MODEL_LIST_TTL {
+                    return models.clone();
+                }
+            }
+        }
+
+        let result = match self.config.provider.as_str() {
+            "openai" => self.list_openai_models().await,
+            "anthropic" => Ok(ANTHROPIC_MODELS.iter()
+                .map(|id| ModelInfo { id: id.to_string(), owned_by: Some("anthropic".to_string()) })
+                .collect()),
+            "local" => self.list_local_models(),
+            other => Err(format!("Model listing not supported for provider '{}'", other)),
+        };
+
+        let models = match result {
+            Ok(models) => ModelList { provider: self.config.provider.clone(), models, reason: None },
+            Err(reason) => ModelList { provider: self.config.provider.clone(), models: Vec::new(), reason: Some(reason) },
         };
 
-        Ok(test_template.to_string())
+        // Only cache successful listings so a fixed API key or network takes effect immediately
+        if models.reason.is_none() {
+            let mut cache = self.models_cache.write().await;
+            *cache = Some((std::time::Instant::now(), models.clone()));
+        }
+
+        models
     }
 
-    /// Check if AI provider is available
-    pub async fn ai_provider(&self) -> Result<String> {
-        Ok(self.config.provider.clone())
+    /// Query the OpenAI models endpoint and keep chat-capable models
+    async fn list_openai_models(&self) -> std::result::Result<Vec<ModelInfo>, String> {
+        let api_key = self.config.api_key.as_ref()
+            .ok_or_else(|| "no API key configured".to_string())?;
+        let client = self.http_client.as_ref()
+            .ok_or_else(|| "HTTP client not initialized".to_string())?;
+
+        let base_url = self.config.base_url.as_deref().unwrap_or("https://api.openai.com/v1");
+        let response = client
+            .get(format!("{}/models", base_url))
+            .bearer_auth(api_key)
+            .send()
+            .await
+            .map_err(|e| self.request_error(e).to_string())?;
+
+        if !response.status().is_success() {
+            return Err(format!("provider returned status {}", response.status()));
+        }
+
+        let body: OpenAIModelsResponse = response.json().await
+            .map_err(|e| format!("invalid models response: {}", e))?;
+
+        let mut models: Vec<ModelInfo> = body.data.into_iter()
+            .filter(|model| Self::is_chat_model(&model.id))
+            .map(|model| ModelInfo { id: model.id, owned_by: model.owned_by })
+            .collect();
+        models.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(models)
+    }
+
+    /// Whether an OpenAI model id can be used with the chat completions endpoint
+    fn is_chat_model(id: &str) -> bool {
+        const EXCLUDED: &[&str] = &["instruct", "embedding", "audio", "realtime", "tts", "transcribe", "search", "image"];
+        let is_chat_family = id.starts_with("gpt-") || id.starts_with("chatgpt-")
+            || id.starts_with("o1") || id.starts_with("o3") || id.starts_with("o4");
+        is_chat_family && !EXCLUDED.iter().any(|part| id.contains(part))
+    }
+
+    /// Discover model weight files under the configured local model path
+    fn list_local_models(&self) -> std::result::Result<Vec<ModelInfo>, String> {
+        let model_path = self.config.model_path.as_ref()
+            .ok_or_else(|| "no local model path configured".to_string())?;
+        let path = std::path::Path::new(model_path);
+
+        if path.is_file() {
+            return Ok(vec![ModelInfo { id: model_path.clone(), owned_by: Some("local".to_string()) }]);
+        }
+        if !path.is_dir() {
+            return Err(format!("model path {} does not exist", model_path));
+        }
+
+        let mut models: Vec<ModelInfo> = walkdir::WalkDir::new(path)
+            .max_depth(3)
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.file_type().is_file())
+            .filter(|entry| {
+                entry.path().extension()
+                    .and_then(|ext| ext.to_str())
+                    .map(|ext| LOCAL_MODEL_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+                    .unwrap_or(false)
+            })
+            .map(|entry| ModelInfo {
+                id: entry.path().strip_prefix(path).unwrap_or(entry.path()).display().to_string(),
+                owned_by: Some("local".to_string()),
+            })
+            .collect();
+        models.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(models)
     }
 
     /// Generate code completion (alias for generate_completion)
@@ -1576,8 +2226,8 @@ if __name__ == '__main__':
 
         // Perform comprehensive analysis
         let mut analysis = match self.config.provider.as_str() {
-            "openai" => self.comprehensive_analysis_with_openai(code, language, file_path).await?,
-            "local" => self.comprehensive_local_analysis(code, language, file_path).await?,
+            "openai" => self.track("advanced_analysis", self.comprehensive_analysis_with_openai(code, language, file_path)).await?,
+            "local" => self.track("advanced_analysis", self.comprehensive_local_analysis(code, language, file_path)).await?,
             _ => AnalysisResult {
                 issues: vec![],
                 suggestions: vec!["AI provider not supported".to_string()],
@@ -1614,26 +2264,43 @@ if __name__ == '__main__':
     /// Analyze code performance
     pub async fn analyze_performance(&self, code: &str, language: &str) -> Result<PerformanceAnalysis> {
         match self.config.provider.as_str() {
-            "openai" => self.performance_analysis_with_openai(code, language).await,
-            "local" => self.local_performance_analysis(code, language).await,
+            "openai" => self.track("performance_analysis", self.performance_analysis_with_openai(code, language)).await,
+            "local" => self.track("performance_analysis", self.local_performance_analysis(code, language)).await,
             _ => Err(anyhow::anyhow!("AI provider not supported"))
         }
     }
 
-    /// Explain code functionality
+    /// Explain code functionality. Explanations are cached by the code, language,
+    /// level and context; `no_cache` asks the provider again and refreshes the entry.
     pub async fn explain_code(&self, request: CodeExplanationRequest) -> Result<CodeExplanation> {
-        match self.config.provider.as_str() {
-            "openai" => self.explain_code_with_openai(request).await,
-            "local" => self.explain_code_locally(request).await,
-            _ => Err(anyhow::anyhow!("AI provider not supported"))
+        let cache_key = Self::hash_explanation_request(&request);
+        if !request.no_cache {
+            let mut cache = self.explanation_cache.write().await;
+            if let Some(cached) = cache.get(&cache_key) {
+                return Ok(cached.clone());
+            }
+        }
+
+        let explanation = match self.config.provider.as_str() {
+            "openai" => self.track("explanation", self.explain_code_with_openai(request)).await?,
+            "local" => self.track("explanation", self.explain_code_locally(request)).await?,
+            _ => return Err(anyhow::anyhow!("AI provider not supported"))
+        };
+
+        {
+            let mut cache = self.explanation_cache.write().await;
+            cache.put(cache_key, explanation.clone());
         }
+
+        Ok(explanation)
     }
 
-    /// Generate unit tests for code
-    pub async fn generate_tests(&self, code: &str, language: &str) -> Result<String> {
+    /// Generate unit tests for code with `test_framework`, or the language's default
+    pub async fn generate_tests(&self, code: &str, language: &str, test_framework: Option<&str>) -> Result<String> {
+        let framework = testgen::TestFramework::resolve(language, test_framework)?;
         match self.config.provider.as_str() {
-            "openai" => self.generate_tests_with_openai(code, language).await,
-            "local" => self.generate_tests_locally(code, language).await,
+            "openai" => self.track("test_generation", self.generate_tests_with_openai(code, language, framework)).await,
+            "local" => self.track("test_generation", self.generate_tests_locally(framework)).await,
             _ => Err(anyhow::anyhow!("AI provider not supported"))
         }
     }
@@ -1643,6 +2310,316 @@ if __name__ == '__main__':
         let analysis = self.advanced_analyze_code(code, language, None).await?;
         Ok(analysis.suggestions)
     }
+
+    /// Summarize a whole project: architecture, main modules and where to start reading
+    pub async fn project_overview(&self, layout: &[String], files: &[ProjectSourceFile]) -> Result<ProjectOverview> {
+        if !self.initialized {
+            return Err(anyhow::anyhow!("AI Engine not initialized"));
+        }
+
+        let content_hash = Self::hash_project_files(layout, files);
+        {
+            let mut cache = self.overview_cache.write().await;
+            if let Some(cached) = cache.get(&content_hash) {
+                return Ok(cached.clone());
+            }
+        }
+
+        let mut overview = match self.config.provider.as_str() {
+            "openai" => self.track("project_overview", self.project_overview_with_openai(layout, files)).await?,
+            _ => Self::project_overview_locally(layout, files),
+        };
+        overview.files_included = files.iter().map(|f| f.path.clone()).collect();
+        overview.content_hash = content_hash.clone();
+
+        {
+            let mut cache = self.overview_cache.write().await;
+            cache.put(content_hash, overview.clone());
+        }
+
+        Ok(overview)
+    }
+
+    /// Summarize the project with OpenAI, condensing chunk summaries until they fit one prompt
+    async fn project_overview_with_openai(&self, layout: &[String], files: &[ProjectSourceFile]) -> Result<ProjectOverview> {
+        let system_prompt = "You are a senior engineer writing an onboarding brief for a new contributor.";
+
+        let mut sections = vec![format!("Project layout:\n{}", layout.join("\n"))];
+        sections.extend(files.iter().map(|f| format!("File: {}\n{}", f.path, f.content)));
+
+        // Summarize each chunk, then summarize the summaries, until a single chunk remains
+        let mut chunks = Self::chunk_sections(&sections, OVERVIEW_CHUNK_CHARS);
+        while chunks.len() > 1 {
+            let mut summaries = Vec::with_capacity(chunks.len());
+            for chunk in &chunks {
+                let prompt = format!(
+                    "Summarize what this part of the project does, naming the modules and files involved. Be concise.\n\n{}",
+                    chunk
+                );
+                summaries.push(self.openai_chat(system_prompt, &prompt, 600).await?);
+            }
+            chunks = Self::chunk_sections(&summaries, OVERVIEW_CHUNK_CHARS);
+        }
+
+        let prompt = format!(
+            "Write an overview of this project. Answer with exactly these sections:\n\
+             Architecture: <one paragraph>\n\
+             Modules:\n- <module>: <purpose>\n\
+             Start:\n- <file or step a newcomer should read first>\n\n{}",
+            chunks.first().map(String::as_str).unwrap_or("")
+        );
+        let text = self.openai_chat(system_prompt, &prompt, 1200).await?;
+
+        Ok(Self::parse_project_overview(&text))
+    }
+
+    /// Build a project overview from file names and README text alone
+    fn project_overview_locally(layout: &[String], files: &[ProjectSourceFile]) -> ProjectOverview {
+        let readme_intro = files.iter()
+            .find(|f| f.path.to_lowercase().contains("readme"))
+            .and_then(|f| {
+                f.content.split("\n\n")
+                    .map(|p| p.trim())
+                    .find(|p| !p.is_empty() && !p.starts_with('#'))
+                    .map(|p| p.to_string())
+            });
+
+        let mut main_modules: Vec<String> = layout.iter()
+            .filter(|entry| entry.ends_with('/'))
+            .map(|entry| entry.trim_end_matches('/').to_string())
+            .filter(|dir| !dir.contains('/') || dir.starts_with("src/"))
+            .collect();
+        main_modules.truncate(20);
+
+        let where_to_start = files.iter()
+            .map(|f| f.path.clone())
+            .collect();
+
+        ProjectOverview {
+            architecture_summary: readme_intro.unwrap_or_else(|| {
+                format!("Project with {} entries and {} key files", layout.len(), files.len())
+            }),
+            main_modules,
+            where_to_start,
+            files_included: Vec::new(),
+            content_hash: String::new(),
+        }
+    }
+
+    /// Group sections into chunks no larger than `budget` characters, truncating oversized sections
+    fn chunk_sections(sections: &[String], budget: usize) -> Vec<String> {
+        let mut chunks = Vec::new();
+        let mut current = String::new();
+
+        for section in sections {
+            let section = if section.len() > budget {
+                let mut end = budget;
+                while !section.is_char_boundary(end) {
+                    end -= 1;
+                }
+                &section[..end]
+            } else {
+                section.as_str()
+            };
+
+            if !current.is_empty() && current.len() + section.len() + 2 > budget {
+                chunks.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push_str("\n\n");
+            }
+            current.push_str(section);
+        }
+
+        if !current.is_empty() {
+            chunks.push(current);
+        }
+        chunks
+    }
+
+    /// Prefix each line with its 1-based number so the model can cite lines reliably
+    fn number_lines(code: &str) -> String {
+        code.lines()
+            .enumerate()
+            .map(|(i, line)| format!("{:>4} | {}", i + 1, line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Read the model's JSON explanation; prose that is not valid JSON becomes the
+    /// explanation with no annotations. Ranges outside the code are dropped.
+    fn parse_explanation_response(content: &str, line_count: usize) -> CodeExplanation {
+        let payload = content.find('{')
+            .zip(content.rfind('}'))
+            .filter(|(start, end)| start < end)
+            .and_then(|(start, end)| serde_json::from_str::<ExplanationPayload>(&content[start..=end]).ok());
+
+        let Some(payload) = payload else {
+            return CodeExplanation {
+                summary: "Code explanation generated".to_string(),
+                explanation: content.to_string(),
+                key_concepts: vec!["Functionality".to_string(), "Implementation".to_string()],
+                complexity_analysis: "Moderate complexity".to_string(),
+                suggestions: vec!["Consider adding documentation".to_string()],
+                annotations: Vec::new(),
+            };
+        };
+
+        let annotations = payload.annotations.into_iter()
+            .filter_map(|raw| {
+                let (start, end) = match raw.line_range {
+                    RawLineRange::Pair([start, end]) => (start.min(end), start.max(end)),
+                    RawLineRange::Single(line) => (line, line),
+                    RawLineRange::Object { start, end } => (start.min(end), start.max(end)),
+                };
+                (start >= 1 && start <= line_count).then(|| CodeAnnotation {
+                    line_range: LineRange { start, end: end.min(line_count) },
+                    note: raw.note,
+                })
+            })
+            .collect();
+
+        let explanation = payload.explanation.unwrap_or_default();
+        CodeExplanation {
+            summary: payload.summary.unwrap_or_else(|| "Code explanation generated".to_string()),
+            explanation,
+            key_concepts: payload.key_concepts,
+            complexity_analysis: "Moderate complexity".to_string(),
+            suggestions: vec!["Consider adding documentation".to_string()],
+            annotations,
+        }
+    }
+
+    /// Parse the Architecture/Modules/Start sections of an overview response
+    fn parse_project_overview(text: &str) -> ProjectOverview {
+        let mut architecture = Vec::new();
+        let mut main_modules = Vec::new();
+        let mut where_to_start = Vec::new();
+        let mut section = "architecture";
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if let Some(rest) = trimmed.strip_prefix("Architecture:") {
+                section = "architecture";
+                if !rest.trim().is_empty() {
+                    architecture.push(rest.trim().to_string());
+                }
+            } else if trimmed.starts_with("Modules:") {
+                section = "modules";
+            } else if trimmed.starts_with("Start:") {
+                section = "start";
+            } else if !trimmed.is_empty() {
+                let item = trimmed.trim_start_matches(['-', '*']).trim().to_string();
+                match section {
+                    "modules" => main_modules.push(item),
+                    "start" => where_to_start.push(item),
+                    _ => architecture.push(trimmed.to_string()),
+                }
+            }
+        }
+
+        ProjectOverview {
+            architecture_summary: architecture.join(" "),
+            main_modules,
+            where_to_start,
+            files_included: Vec::new(),
+            content_hash: String::new(),
+        }
+    }
+
+    /// Stable hash over the layout and file contents used as the overview cache key
+    fn hash_project_files(layout: &[String], files: &[ProjectSourceFile]) -> String {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = DefaultHasher::new();
+        layout.hash(&mut hasher);
+        for file in files {
+            file.path.hash(&mut hasher);
+            file.content.hash(&mut hasher);
+        }
+        format!("{:x}", hasher.finish())
+    }
+
+    fn hash_explanation_request(request: &CodeExplanationRequest) -> String {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = DefaultHasher::new();
+        request.code.hash(&mut hasher);
+        request.language.hash(&mut hasher);
+        request.explanation_level.hash(&mut hasher);
+        request.context.hash(&mut hasher);
+        format!("{:x}", hasher.finish())
+    }
+
+    /// Convert a transport error, keeping timeouts distinguishable from other failures
+    fn request_error(&self, error: reqwest::Error) -> anyhow::Error {
+        if error.is_timeout() {
+            AiError::Timeout(self.config.request_timeout_secs).into()
+        } else if let Some(message) = crate::utils::http::proxy_failure(&self.config.proxy, &error) {
+            AiError::Proxy(message).into()
+        } else {
+            AiError::Request(error.to_string()).into()
+        }
+    }
+
+    /// Send a single system/user exchange to the OpenAI chat endpoint and return the reply text
+    async fn openai_chat(&self, system_prompt: &str, user_prompt: &str, max_tokens: u32) -> Result<String> {
+        let api_key = self.config.api_key.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("OpenAI API key not configured"))?;
+
+        let client = self.http_client.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("HTTP client not initialized"))?;
+
+        let model = match self.config.model_name.as_str() {
+            "default" => "gpt-3.5-turbo",
+            other => other,
+        };
+
+        let openai_request = OpenAIRequest {
+            model: model.to_string(),
+            messages: vec![
+                OpenAIMessage {
+                    role: "system".to_string(),
+                    content: system_prompt.to_string(),
+                },
+                OpenAIMessage {
+                    role: "user".to_string(),
+                    content: user_prompt.to_string(),
+                },
+            ],
+            temperature: self.config.temperature,
+            max_tokens: Some(max_tokens),
+            stream: false,
+        };
+
+        let base_url = self.config.base_url.as_deref().unwrap_or("https://api.openai.com/v1");
+        let url = format!("{}/chat/completions", base_url);
+
+        let response = client
+            .post(&url)
+            .bearer_auth(api_key)
+            .json(&openai_request)
+            .send()
+            .await
+            .map_err(|e| self.request_error(e))?;
+
+        if response.status().is_success() {
+            let openai_response: OpenAIResponse = response.json().await.map_err(|e| self.request_error(e))?;
+            events::record_usage(openai_response.usage.as_ref());
+            Ok(openai_response.choices.first()
+                .map(|choice| choice.message.content.clone())
+                .unwrap_or_default())
+        } else {
+            let status = response.status();
+            let error_response: Result<OpenAIError, _> = response.json().await;
+            match error_response {
+                Ok(error) => Err(anyhow::anyhow!("OpenAI API error: {}", error.error.message)),
+                Err(_) => Err(anyhow::anyhow!("OpenAI API request failed with status: {}", status)),
+            }
+        }
+    }
 }
 
 // Additional engines that some parts of the codebase expect
@@ -1711,3 +2688,529 @@ impl SecurityAnalyzer {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(path: &str, content: &str) -> ProjectSourceFile {
+        ProjectSourceFile { path: path.to_string(), content: content.to_string() }
+    }
+
+    #[test]
+    fn test_chunk_sections_respects_budget() {
+        let sections = vec!["a".repeat(40), "b".repeat(40), "c".repeat(200)];
+        let chunks = AiEngine::chunk_sections(&sections, 100);
+
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 100));
+    }
+
+    #[tokio::test]
+    async fn test_project_overview_is_cached_by_content() {
+        let mut engine = AiEngine::new(test_config("local", None));
+        engine.initialize().await.unwrap();
+
+        let layout = vec!["src/".to_string(), "src/main.rs".to_string()];
+        let files = vec![source("README.md", "# Demo\n\nA small demo tool.")];
+
+        let first = engine.project_overview(&layout, &files).await.unwrap();
+        let second = engine.project_overview(&layout, &files).await.unwrap();
+        assert_eq!(first.content_hash, second.content_hash);
+        assert_eq!(first.architecture_summary, "A small demo tool.");
+        assert_eq!(first.main_modules, vec!["src".to_string()]);
+
+        let changed = vec![source("README.md", "# Demo\n\nSomething else.")];
+        let third = engine.project_overview(&layout, &changed).await.unwrap();
+        assert_ne!(first.content_hash, third.content_hash);
+    }
+
+    #[tokio::test]
+    async fn test_slow_provider_surfaces_timeout() {
+        // Accept connections but never answer, simulating a hung provider
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+
+        let mut engine = AiEngine::new(AiConfig {
+            request_timeout_secs: 1,
+            connect_timeout_secs: 1,
+            ..test_config("openai", Some(format!("http://{}", addr)))
+        });
+        engine.initialize().await.unwrap();
+
+        let err = engine.openai_chat("system", "hello", 16).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<AiError>(), Some(AiError::Timeout(1))));
+    }
+
+    #[test]
+    fn test_parse_line_anchor_phrasings() {
+        assert_eq!(parse_line_anchor("Issue: unchecked unwrap on line 12"), Some((12, 1)));
+        assert_eq!(parse_line_anchor("Warning: Lines 10-14 duplicate the loop above"), Some((10, 1)));
+        assert_eq!(parse_line_anchor("Bug: lines 3 to 5 never release the lock"), Some((3, 1)));
+        assert_eq!(parse_line_anchor("Error: line 7, column 9: missing semicolon"), Some((7, 9)));
+        assert_eq!(parse_line_anchor("Error: src/main.rs:42:5 borrow of moved value"), Some((42, 5)));
+        assert_eq!(parse_line_anchor("Suggestion: add more tests"), None);
+    }
+
+    #[test]
+    fn test_comprehensive_analysis_anchors_findings() {
+        let engine = AiEngine::new(test_config("local", None));
+        let text = "Error: line 4 dereferences a null pointer\n\
+                    Security: lines 20-22 build SQL from user input\n\
+                    Smell: this function is too long";
+
+        let (issues, _, _, smells, vulnerabilities) = engine.parse_comprehensive_analysis(text, "rust");
+
+        assert_eq!((issues[0].line, issues[0].column), (4, 1));
+        assert_eq!(vulnerabilities[0].line, 20);
+        assert_eq!((smells[0].line, smells[0].column), (1, 1));
+    }
+
+    #[test]
+    fn test_explanation_annotations_parsed_from_json() {
+        let content = "```json\n{\"summary\": \"Adds numbers\", \"explanation\": \"Sums a slice.\", \
+            \"key_concepts\": [\"iterators\"], \"annotations\": [\
+            {\"line_range\": [1, 2], \"note\": \"Signature\"}, \
+            {\"line_range\": 3, \"note\": \"Fold\"}, \
+            {\"line_range\": {\"start\": 40, \"end\": 41}, \"note\": \"Out of range\"}]}\n```";
+
+        let explanation = AiEngine::parse_explanation_response(content, 4);
+        assert_eq!(explanation.summary, "Adds numbers");
+        assert_eq!(explanation.key_concepts, vec!["iterators"]);
+        assert_eq!(explanation.annotations, vec![
+            CodeAnnotation { line_range: LineRange { start: 1, end: 2 }, note: "Signature".to_string() },
+            CodeAnnotation { line_range: LineRange { start: 3, end: 3 }, note: "Fold".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn test_explanation_prose_falls_back_without_annotations() {
+        let content = "This function sums the values in a slice using fold.";
+
+        let explanation = AiEngine::parse_explanation_response(content, 4);
+        assert_eq!(explanation.explanation, content);
+        assert!(explanation.annotations.is_empty());
+    }
+
+    /// Minimal OpenAI-compatible server answering one chat request; resolves to the raw request it received
+    pub(super) async fn mock_openai_server(reply: &'static str) -> (std::net::SocketAddr, tokio::task::JoinHandle<String>) {
+        let body = format!(
+            r#"{{"choices":[{{"message":{{"role":"assistant","content":"{}"}},"finish_reason":"stop"}}],"usage":null}}"#,
+            reply
+        );
+        mock_http_server("200 OK", body).await
+    }
+
+    /// Serve one HTTP response with `status` and a JSON `body`, resolving to the raw request
+    async fn mock_http_server(status: &'static str, body: String) -> (std::net::SocketAddr, tokio::task::JoinHandle<String>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            answer_request(&mut socket, |_| (status, body)).await
+        });
+        (addr, server)
+    }
+
+    /// Serve every incoming request with the status and JSON body `respond` picks from the raw request
+    pub(super) async fn mock_http_server_with(respond: fn(&str) -> (&'static str, String)) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    answer_request(&mut socket, respond).await;
+                });
+            }
+        });
+        addr
+    }
+
+    /// Read one request up to its JSON body and write the response `respond` picks; returns the raw request
+    async fn answer_request(
+        socket: &mut tokio::net::TcpStream,
+        respond: impl FnOnce(&str) -> (&'static str, String),
+    ) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        while !String::from_utf8_lossy(&request).contains("\"stream\"") {
+            let n = socket.read(&mut buf).await.unwrap();
+            if n == 0 {
+                break;
+            }
+            request.extend_from_slice(&buf[..n]);
+        }
+        let request = String::from_utf8_lossy(&request).to_string();
+        let (status, body) = respond(&request);
+        let response = format!(
+            "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        socket.write_all(response.as_bytes()).await.unwrap();
+        request
+    }
+
+    /// Configuration of `provider` at `base_url` with a test key and short timeouts
+    pub(crate) fn test_config(provider: &str, base_url: Option<String>) -> AiConfig {
+        AiConfig {
+            provider: provider.to_string(),
+            api_key: Some("test-key".to_string()),
+            model_name: "default".to_string(),
+            temperature: 0.2,
+            max_tokens: 16,
+            base_url,
+            model_path: None,
+            request_timeout_secs: 5,
+            connect_timeout_secs: 5,
+            proxy: Default::default(),
+        }
+    }
+
+    /// Configuration of an OpenAI provider served by a mock server at `addr`
+    pub(crate) fn openai_config(addr: std::net::SocketAddr) -> AiConfig {
+        test_config("openai", Some(format!("http://{}/v1", addr)))
+    }
+
+    #[tokio::test]
+    async fn test_identical_explanations_call_provider_once() {
+        // The mock answers a single request, so a second provider call would fail
+        let (addr, server) = mock_openai_server("Adds two numbers.").await;
+        let mut engine = AiEngine::new(openai_config(addr));
+        engine.initialize().await.unwrap();
+        let request = CodeExplanationRequest {
+            code: "fn add(a: i32, b: i32) -> i32 { a + b }".to_string(),
+            language: "rust".to_string(),
+            context: None,
+            explanation_level: ExplanationLevel::Basic,
+            no_cache: false,
+        };
+
+        let first = engine.explain_code(request.clone()).await.unwrap();
+        let second = engine.explain_code(request.clone()).await.unwrap();
+        assert_eq!(first.explanation, "Adds two numbers.");
+        assert_eq!(second.explanation, first.explanation);
+        assert!(server.await.unwrap().contains("a + b"));
+
+        let err = engine.explain_code(CodeExplanationRequest { no_cache: true, ..request }).await;
+        assert!(err.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_local_fallback_serves_when_provider_fails() {
+        let (addr, server) = mock_http_server(
+            "503 Service Unavailable",
+            r#"{"error":{"message":"The server is overloaded","type":"server_error","code":null}}"#.to_string(),
+        ).await;
+        let engine = AiEngine::new_async(openai_config(addr)).await.unwrap()
+            .with_fallback_providers(vec!["openai".to_string(), "local".to_string()]);
+
+        let completion = engine.generate_completion(chat_request(None)).await.unwrap();
+        server.await.unwrap();
+        assert_eq!(completion.served_by.as_deref(), Some("local"));
+        assert!(!completion.suggestions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_request_does_not_fall_back() {
+        let (addr, server) = mock_http_server(
+            "400 Bad Request",
+            r#"{"error":{"message":"max_tokens is too large","type":"invalid_request_error","code":null}}"#.to_string(),
+        ).await;
+        let engine = AiEngine::new_async(openai_config(addr)).await.unwrap()
+            .with_fallback_providers(vec!["local".to_string()]);
+
+        let error = engine.generate_completion(chat_request(None)).await.unwrap_err();
+        server.await.unwrap();
+        assert!(matches!(error.downcast_ref::<AiError>(), Some(AiError::Status { status: 400, .. })));
+    }
+
+    #[tokio::test]
+    async fn test_slow_completion_falls_back_to_local() {
+        use tokio::io::AsyncReadExt;
+
+        // Read the request, never answer, and report once the client hangs up
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 4096];
+            while socket.read(&mut buf).await.unwrap_or(0) > 0 {}
+        });
+
+        let engine = AiEngine::new_async(openai_config(addr)).await.unwrap()
+            .with_completion_timeout(Some(100));
+        let started = std::time::Instant::now();
+        let completion = engine.generate_completion(chat_request(None)).await.unwrap();
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+        assert!(completion.fallback);
+        assert_eq!(completion.served_by.as_deref(), Some("local"));
+
+        // The abandoned provider request was closed rather than left to run to its own timeout
+        tokio::time::timeout(std::time::Duration::from_secs(2), server).await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_requests_go_through_configured_proxy() {
+        let (addr, proxy_server) = mock_http_server(
+            "407 Proxy Authentication Required",
+            r#"{"error":{"message":"denied","type":"proxy","code":null}}"#.to_string(),
+        ).await;
+        let config = AiConfig {
+            base_url: Some("http://ai.example.test/v1".to_string()),
+            proxy: ProxySettings { url: Some(format!("http://{}", addr)), ..ProxySettings::default() },
+            ..openai_config(addr)
+        };
+        let engine = AiEngine::new_async(config).await.unwrap();
+
+        let error = engine.generate_completion(chat_request(None)).await.unwrap_err();
+        let request = proxy_server.await.unwrap();
+        assert!(request.starts_with("POST http://ai.example.test/v1/chat/completions HTTP/1.1"), "{}", request);
+        assert!(matches!(error.downcast_ref::<AiError>(), Some(AiError::Proxy(_))), "{}", error);
+        assert!(error.to_string().contains(&format!("proxy http://{}/ rejected the connection", addr)), "{}", error);
+    }
+
+    fn chat_request(max_tokens: Option<u32>) -> CompletionRequest {
+        CompletionRequest {
+            prompt: "hi".to_string(),
+            context: String::new(),
+            language: "rust".to_string(),
+            max_tokens,
+            position: None,
+            cursor_position: None,
+            text_before_cursor: "hi".to_string(),
+            document: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chat_override_targets_compatible_server() {
+        let (addr, server) = mock_openai_server("hello from llama").await;
+
+        let mut engine = AiEngine::new(test_config("local", None));
+        engine.initialize().await.unwrap();
+
+        let overrides = AiOverrides {
+            model: Some("llama3".to_string()),
+            base_url: Some(format!("http://{}/v1/", addr)),
+            ..AiOverrides::default()
+        };
+        let transient = engine.with_overrides(&overrides).await.unwrap();
+        let completion = transient.generate_completion(chat_request(None)).await.unwrap();
+
+        assert_eq!(completion.text, "hello from llama");
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /v1/chat/completions"));
+        assert!(request.contains("\"model\":\"llama3\""));
+        assert_eq!(engine.config.provider, "local");
+    }
+
+    #[tokio::test]
+    async fn test_base_url_override_does_not_leak_configured_key() {
+        let (addr, server) = mock_openai_server("ok").await;
+        let engine = AiEngine::new_async(AiConfig {
+            api_key: Some("configured-secret".to_string()),
+            ..test_config("openai", Some("https://api.example.com/v1".to_string()))
+        }).await.unwrap();
+
+        let overrides = AiOverrides {
+            base_url: Some(format!("http://{}/v1", addr)),
+            ..AiOverrides::default()
+        };
+        let transient = engine.with_overrides(&overrides).await.unwrap();
+        transient.generate_completion(chat_request(None)).await.unwrap();
+
+        let request = server.await.unwrap();
+        assert!(!request.contains("configured-secret"));
+        assert!(engine.overridden_config(&AiOverrides {
+            base_url: Some("https://api.example.com/v1".to_string()),
+            ..AiOverrides::default()
+        }).api_key.is_some());
+    }
+
+    #[test]
+    fn test_invalid_base_url_override_falls_back() {
+        let engine = AiEngine::new(test_config("openai", Some("https://api.example.com/v1".to_string())));
+
+        let config = engine.overridden_config(&AiOverrides {
+            provider: Some("mystery".to_string()),
+            base_url: Some("file:///etc/passwd".to_string()),
+            ..AiOverrides::default()
+        });
+        assert_eq!(config.base_url.as_deref(), Some("https://api.example.com/v1"));
+        assert_eq!(config.provider, "openai");
+    }
+
+    #[tokio::test]
+    async fn test_analysis_issues_on_omitted_lines_are_dropped() {
+        let mut code = String::from("use std::fs;\n\n");
+        for index in 0..600 {
+            code.push_str(&format!("fn helper_{}(value: u32) -> u32 {{\n    value * {}\n}}\n\n", index, index));
+        }
+        let engine = AiEngine::new(test_config("local", None));
+        let window = engine.context_window(&code, "rust", 0);
+        assert!(window.truncated);
+        let marker = window.text.lines().position(|line| line.contains("lines omitted")).unwrap() + 1;
+        let reply = format!("Error: line 1 imports fs\\nError: line {} is not in the file", marker);
+
+        let (addr, server) = mock_openai_server(Box::leak(reply.into_boxed_str())).await;
+        let engine = AiEngine::new_async(openai_config(addr)).await.unwrap();
+        let result = engine.comprehensive_analysis_with_openai(&code, "rust", None).await.unwrap();
+        server.await.unwrap();
+
+        let lines: Vec<usize> = result.issues.iter().map(|issue| issue.line).collect();
+        assert_eq!(lines, vec![1]);
+    }
+
+    #[tokio::test]
+    async fn test_requested_max_tokens_reaches_provider() {
+        let (addr, server) = mock_openai_server("ok").await;
+        let engine = AiEngine::new_async(openai_config(addr)).await.unwrap();
+
+        let overrides = AiOverrides {
+            temperature: Some(9.0),
+            max_tokens: Some(42),
+            ..AiOverrides::default()
+        };
+        let transient = engine.with_overrides(&overrides).await.unwrap();
+        transient.generate_completion(chat_request(Some(42))).await.unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request.contains("\"max_tokens\":42"));
+        assert!(request.contains("\"temperature\":2.0"));
+    }
+
+    #[tokio::test]
+    async fn test_typing_into_completion_reuses_it() {
+        let (addr, server) = mock_openai_server("items.iter().sum();").await;
+        let engine = AiEngine::new_async(openai_config(addr)).await.unwrap();
+
+        let request = |text: &str| CompletionRequest {
+            text_before_cursor: text.to_string(),
+            cursor_position: Some((3, text.len())),
+            ..chat_request(None)
+        };
+        let first = engine.generate_completion(request("let total = ")).await.unwrap();
+        assert_eq!(first.text, "items.iter().sum();");
+        server.await.unwrap();
+
+        // The mock server is gone, so this only succeeds without a provider call
+        let next = engine.generate_completion(request("let total = item")).await.unwrap();
+        assert_eq!(next.text, "s.iter().sum();");
+
+        // Another document with the same text is not continued from this one's completion
+        let other = CompletionRequest { document: Some("src/other.rs".to_string()), ..request("let total = it") };
+        assert!(engine.generate_completion(other).await.is_err());
+    }
+
+    #[test]
+    fn test_completion_anchor_invalidated_by_edits() {
+        let anchor = CompletionAnchor {
+            text_before_cursor: "let total = ".to_string(),
+            response: CompletionResponse {
+                text: "items.len();".to_string(),
+                confidence: 0.8,
+                suggestions: vec![],
+                served_by: Some("openai".to_string()),
+                fallback: false,
+            },
+        };
+
+        assert_eq!(anchor.continue_with("let total = it").unwrap().text, "ems.len();");
+        // Edit before the cursor
+        assert!(anchor.continue_with("let count = it").is_none());
+        // Typed something the completion didn't predict
+        assert!(anchor.continue_with("let total = values").is_none());
+        // Typed the whole completion
+        assert!(anchor.continue_with("let total = items.len();").is_none());
+    }
+
+    #[test]
+    fn test_mid_word_completion_replaces_the_partial_word() {
+        let suggestion = |text: &str| CompletionSuggestion {
+            text: text.to_string(),
+            confidence: 0.8,
+            kind: "ai".to_string(),
+            replace_range: None,
+            insert_text: None,
+        };
+        // Cursor after `pri` in `pri|nt`
+        let code = "fn main() {\n    print\n}";
+        let cursor = "fn main() {\n    pri".chars().count();
+
+        let anchored = suggestion("println!").anchored(code, cursor);
+        assert_eq!(anchored.replace_range, Some(TextRange { start: 16, end: 21 }));
+        assert_eq!(anchored.insert_text.as_deref(), Some("println!"));
+
+        // A continuation of the typed prefix gets it put back in front
+        let anchored = suggestion("ntln!").anchored(code, cursor);
+        assert_eq!(anchored.insert_text.as_deref(), Some("println!"));
+
+        // Not touching a word: insert at the cursor
+        assert_eq!(TextRange::word_at("x = ", 4), TextRange { start: 4, end: 4 });
+        assert_eq!(TextRange::word_at("héllo wörld", 8), TextRange { start: 6, end: 11 });
+    }
+
+    #[test]
+    fn test_min_severity_error_drops_info_and_warnings() {
+        let issue = |severity: IssueSeverity| CodeIssue {
+            id: format!("{}", severity),
+            severity,
+            message: "finding".to_string(),
+            line: 1,
+            column: 1,
+            file_path: None,
+            rule_id: None,
+            fix_suggestion: None,
+            documentation_url: None,
+        };
+        let bug = |severity: IssueSeverity| BugPrediction {
+            line: 1,
+            column: 1,
+            bug_type: BugType::NullPointer,
+            confidence: 0.9,
+            description: "bug".to_string(),
+            fix_suggestion: String::new(),
+            severity,
+        };
+        let vulnerability = |severity: IssueSeverity| SecurityVulnerability {
+            id: "SEC".to_string(),
+            cwe_id: None,
+            title: "vulnerability".to_string(),
+            description: String::new(),
+            severity,
+            line: 1,
+            column: 1,
+            recommendation: String::new(),
+            cve_references: Vec::new(),
+        };
+        let mut analysis = AnalysisResult {
+            issues: vec![issue(IssueSeverity::Info), issue(IssueSeverity::Warning), issue(IssueSeverity::Error), issue(IssueSeverity::Critical)],
+            suggestions: vec!["Split the function".to_string()],
+            complexity_score: 0.7,
+            bug_predictions: vec![bug(IssueSeverity::Warning), bug(IssueSeverity::Error)],
+            code_smells: Vec::new(),
+            security_vulnerabilities: vec![vulnerability(IssueSeverity::Info)],
+            performance_insights: Vec::new(),
+            maintainability_score: 0.4,
+        };
+
+        let min_severity: IssueSeverity = serde_json::from_str("\"error\"").unwrap();
+        analysis.retain_min_severity(&min_severity);
+        let kept: Vec<_> = analysis.issues.iter().map(|issue| issue.severity.clone()).collect();
+        assert_eq!(kept, [IssueSeverity::Error, IssueSeverity::Critical]);
+        assert_eq!(analysis.bug_predictions.len(), 1);
+        assert!(analysis.security_vulnerabilities.is_empty());
+        assert_eq!((analysis.complexity_score, analysis.maintainability_score), (0.7, 0.4));
+        assert_eq!(analysis.suggestions.len(), 1);
+    }
+}
@@ -0,0 +1,123 @@
+//! Turning a prose suggestion into a reviewable change
+//!
+//! The model only rewrites the file. The diff shown to the user is computed here
+//! from the original and the rewritten text, so it always describes exactly what
+//! accepting the change would do, whatever the model claims to have changed.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::fix::first_code_block;
+use super::AiEngine;
+use crate::utils::diff::unified_diff;
+
+/// A suggestion applied to a file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuggestionPatch {
+    /// The whole file with the suggestion applied
+    pub new_content: String,
+    /// Unified diff from the original file to `new_content`
+    pub diff: String,
+}
+
+/// Suggestion patch errors
+#[derive(Debug, thiserror::Error)]
+pub enum SuggestionPatchError {
+    #[error("No suggestion to apply")]
+    EmptySuggestion,
+
+    #[error("Model reply does not contain the edited code")]
+    MissingCode,
+
+    #[error("Model reply leaves the code unchanged")]
+    Unchanged,
+}
+
+fn patch_prompt(code: &str, language: &str, suggestion: &str) -> String {
+    format!(
+        "Apply this suggestion to the {language} file below and change nothing else.\n\
+         Suggestion: {suggestion}\n\n\
+         Keep the existing formatting and comments. Reply with the complete edited file in a single \
+         ```{language} code block.\n\n```{language}\n{code}\n```",
+        language = language,
+        suggestion = suggestion.trim(),
+        code = code.trim_end_matches('\n'),
+    )
+}
+
+/// Build the patch for `code` from the model's reply. Labels such as `a/src/lib.rs`
+/// name the two sides in the diff headers.
+pub fn patch_from_reply(code: &str, reply: &str, old_label: &str, new_label: &str) -> Result<SuggestionPatch, SuggestionPatchError> {
+    let mut new_content = first_code_block(reply).ok_or(SuggestionPatchError::MissingCode)?.to_string();
+    if code.ends_with('\n') {
+        new_content.push('\n');
+    }
+    if new_content == code {
+        return Err(SuggestionPatchError::Unchanged);
+    }
+
+    Ok(SuggestionPatch {
+        diff: unified_diff(code, &new_content, old_label, new_label),
+        new_content,
+    })
+}
+
+impl AiEngine {
+    /// Ask the model to apply `suggestion` to `code` and diff the result against it.
+    /// `file_path` only names the file in the diff headers.
+    pub async fn suggestion_to_patch(
+        &self,
+        code: &str,
+        language: &str,
+        suggestion: &str,
+        file_path: Option<&str>,
+    ) -> Result<SuggestionPatch> {
+        if suggestion.trim().is_empty() {
+            return Err(SuggestionPatchError::EmptySuggestion.into());
+        }
+
+        let reply = self.run_prompt(&patch_prompt(code, language, suggestion), language).await?;
+        let path = file_path.unwrap_or("untitled");
+        Ok(patch_from_reply(code, &reply, &format!("a/{}", path), &format!("b/{}", path))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::tests::{mock_openai_server, openai_config};
+
+    const ORIGINAL: &str = "fn total(items: &[u32]) -> u32 {\n    let mut sum = 0;\n    for item in items {\n        sum += item;\n    }\n    sum\n}\n";
+
+    #[tokio::test]
+    async fn test_diff_matches_content_delta() {
+        let (addr, server) = mock_openai_server(
+            "Here is the updated file:\\n```rust\\nfn total(items: &[u32]) -> u32 {\\n    items.iter().sum()\\n}\\n```"
+        ).await;
+        let engine = AiEngine::new_async(openai_config(addr)).await.unwrap();
+
+        let patch = engine.suggestion_to_patch(ORIGINAL, "rust", "Use Iterator::sum instead of a manual loop", Some("src/lib.rs"))
+            .await
+            .unwrap();
+        assert_eq!(patch.new_content, "fn total(items: &[u32]) -> u32 {\n    items.iter().sum()\n}\n");
+        assert_eq!(patch.diff, unified_diff(ORIGINAL, &patch.new_content, "a/src/lib.rs", "b/src/lib.rs"));
+        assert_eq!(
+            patch.diff,
+            "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,7 +1,3 @@\n fn total(items: &[u32]) -> u32 {\n\
+             -    let mut sum = 0;\n-    for item in items {\n-        sum += item;\n-    }\n-    sum\n+    items.iter().sum()\n }\n"
+        );
+
+        let request = server.await.unwrap();
+        assert!(request.contains("Suggestion: Use Iterator::sum instead of a manual loop"));
+    }
+
+    #[test]
+    fn test_reply_without_a_change_is_rejected() {
+        let unchanged = format!("```rust\n{}```", ORIGINAL);
+        assert!(matches!(patch_from_reply(ORIGINAL, &unchanged, "a", "b"), Err(SuggestionPatchError::Unchanged)));
+        assert!(matches!(
+            patch_from_reply(ORIGINAL, "You could use `sum()` here.", "a", "b"),
+            Err(SuggestionPatchError::MissingCode)
+        ));
+    }
+}
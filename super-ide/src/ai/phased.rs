@@ -0,0 +1,191 @@
+//! Phase-by-phase code analysis with progress reporting
+//!
+//! Long analyses run as a sequence of phases (issues, bug prediction, security,
+//! performance). A callback receives each phase's findings as soon as it
+//! completes, and dropping the returned future cancels the phases not yet run.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::{AiEngine, AnalysisResult};
+
+/// One step of a phased analysis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalysisPhase {
+    Issues,
+    BugPrediction,
+    Security,
+    Performance,
+}
+
+impl AnalysisPhase {
+    /// Phases in the order they run
+    pub const ALL: [AnalysisPhase; 4] = [
+        AnalysisPhase::Issues,
+        AnalysisPhase::BugPrediction,
+        AnalysisPhase::Security,
+        AnalysisPhase::Performance,
+    ];
+
+    /// Prompt instructions for the model, asking for lines `parse_comprehensive_analysis` understands
+    fn instructions(self) -> &'static str {
+        match self {
+            AnalysisPhase::Issues => "code issues and code smells. Prefix errors with `Error:`, warnings with `Warning:`, other issues with `Issue:` and code smells with `Smell:`",
+            AnalysisPhase::BugPrediction => "likely bugs such as panics, off-by-one errors and unhandled cases. Prefix each with `Bug:`",
+            AnalysisPhase::Security => "security vulnerabilities. Prefix each with `Security:`",
+            AnalysisPhase::Performance => "performance problems. Prefix each with `Performance:`",
+        }
+    }
+
+    /// Keep only the findings this phase is responsible for
+    fn select(self, full: &AnalysisResult) -> AnalysisResult {
+        let mut partial = AnalysisResult {
+            issues: Vec::new(),
+            suggestions: Vec::new(),
+            complexity_score: full.complexity_score,
+            bug_predictions: Vec::new(),
+            code_smells: Vec::new(),
+            security_vulnerabilities: Vec::new(),
+            performance_insights: Vec::new(),
+            maintainability_score: full.maintainability_score,
+        };
+        match self {
+            AnalysisPhase::Issues => {
+                partial.issues = full.issues.clone();
+                partial.code_smells = full.code_smells.clone();
+                partial.suggestions = full.suggestions.clone();
+            }
+            AnalysisPhase::BugPrediction => partial.bug_predictions = full.bug_predictions.clone(),
+            AnalysisPhase::Security => partial.security_vulnerabilities = full.security_vulnerabilities.clone(),
+            AnalysisPhase::Performance => partial.performance_insights = full.performance_insights.clone(),
+        }
+        partial
+    }
+}
+
+/// Findings of one completed phase
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisProgress {
+    pub phase: AnalysisPhase,
+    /// Phases finished so far, including this one
+    pub completed: usize,
+    pub total: usize,
+    pub result: AnalysisResult,
+}
+
+impl AiEngine {
+    /// Analyze code phase by phase, reporting each phase's findings through `on_progress`,
+    /// and return the assembled result
+    pub async fn analyze_code_in_phases<F>(
+        &self,
+        code: &str,
+        language: &str,
+        file_path: Option<&str>,
+        mut on_progress: F,
+    ) -> Result<AnalysisResult>
+    where
+        F: FnMut(AnalysisProgress),
+    {
+        if !self.initialized {
+            return Err(anyhow::anyhow!("AI Engine not initialized"));
+        }
+
+        // Local analysis is a single fast pass; only remote providers are queried per phase
+        let local = match self.config.provider.as_str() {
+            "openai" => None,
+            _ => Some(self.advanced_analyze_code(code, language, file_path).await?),
+        };
+
+        let mut assembled = AnalysisResult {
+            issues: Vec::new(),
+            suggestions: Vec::new(),
+            complexity_score: self.calculate_complexity_score(code),
+            bug_predictions: Vec::new(),
+            code_smells: Vec::new(),
+            security_vulnerabilities: Vec::new(),
+            performance_insights: Vec::new(),
+            maintainability_score: self.calculate_maintainability_score(code),
+        };
+
+        for (index, phase) in AnalysisPhase::ALL.into_iter().enumerate() {
+            let partial = match &local {
+                Some(full) => phase.select(full),
+                None => self.track("advanced_analysis", self.openai_analysis_phase(phase, code, language)).await?,
+            };
+
+            assembled.issues.extend(partial.issues.iter().cloned());
+            assembled.suggestions.extend(partial.suggestions.iter().cloned());
+            assembled.bug_predictions.extend(partial.bug_predictions.iter().cloned());
+            assembled.code_smells.extend(partial.code_smells.iter().cloned());
+            assembled.security_vulnerabilities.extend(partial.security_vulnerabilities.iter().cloned());
+            assembled.performance_insights.extend(partial.performance_insights.iter().cloned());
+
+            on_progress(AnalysisProgress {
+                phase,
+                completed: index + 1,
+                total: AnalysisPhase::ALL.len(),
+                result: partial,
+            });
+        }
+
+        Ok(assembled)
+    }
+
+    /// Ask the model for one phase's findings only
+    async fn openai_analysis_phase(&self, phase: AnalysisPhase, code: &str, language: &str) -> Result<AnalysisResult> {
+        let system_prompt = "You are a senior software engineer performing a focused code review. Cite line numbers as `line N`.";
+        let user_prompt = format!(
+            "Review this {} code for {}. Put each finding on its own line.\n\nCode:\n{}",
+            language,
+            phase.instructions(),
+            code
+        );
+
+        let text = self.openai_chat(system_prompt, &user_prompt, 1000).await?;
+        let (issues, suggestions, bug_predictions, code_smells, security_vulnerabilities) =
+            self.parse_comprehensive_analysis(&text, language);
+        let performance_insights = text.lines()
+            .filter_map(|line| line.trim().strip_prefix("Performance:"))
+            .map(|insight| insight.trim().to_string())
+            .collect();
+
+        Ok(phase.select(&AnalysisResult {
+            issues,
+            suggestions,
+            complexity_score: self.calculate_complexity_score(code),
+            bug_predictions,
+            code_smells,
+            security_vulnerabilities,
+            performance_insights,
+            maintainability_score: self.calculate_maintainability_score(code),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::tests::test_config;
+
+    #[tokio::test]
+    async fn test_phases_report_progress_and_assemble_result() {
+        let engine = AiEngine::new_async(test_config("local", None)).await.unwrap();
+        let code = "fn main() {\n    let value = input.unwrap();\n    let text = value.expect();\n}";
+
+        let mut phases = Vec::new();
+        let result = engine.analyze_code_in_phases(code, "rust", None, |progress| {
+            phases.push((progress.phase, progress.completed, progress.total));
+        }).await.unwrap();
+
+        assert_eq!(phases, vec![
+            (AnalysisPhase::Issues, 1, 4),
+            (AnalysisPhase::BugPrediction, 2, 4),
+            (AnalysisPhase::Security, 3, 4),
+            (AnalysisPhase::Performance, 4, 4),
+        ]);
+        let full = engine.advanced_analyze_code(code, "rust", None).await.unwrap();
+        assert_eq!(result.bug_predictions.len(), full.bug_predictions.len());
+        assert_eq!(result.security_vulnerabilities.len(), full.security_vulnerabilities.len());
+    }
+}
@@ -0,0 +1,232 @@
+//! Regular expressions from a description
+//!
+//! The model proposes a pattern and it is compiled and run against the user's
+//! samples here, with the `regex` crate, before anything is returned. When the
+//! pattern misbehaves the model gets one more try with the failures spelled out.
+
+use anyhow::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::fix::first_code_block;
+use super::AiEngine;
+
+/// Model replies tried before giving up, including the first
+const MAX_ATTEMPTS: usize = 2;
+
+/// An input the pattern is checked against
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegexSample {
+    pub input: String,
+    /// Whether the pattern should find a match in `input`
+    #[serde(default = "default_should_match")]
+    pub should_match: bool,
+    /// The text the first match should cover, when it matters
+    pub expected_match: Option<String>,
+}
+
+fn default_should_match() -> bool {
+    true
+}
+
+/// How the pattern did on one sample
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegexSampleResult {
+    pub input: String,
+    /// Text of the first match, if any
+    pub matched: Option<String>,
+    pub passed: bool,
+}
+
+/// A pattern that passed every sample
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuiltRegex {
+    pub pattern: String,
+    pub samples: Vec<RegexSampleResult>,
+    /// Model replies it took, 1 unless the first pattern failed
+    pub attempts: usize,
+}
+
+/// Why a proposed pattern was rejected
+#[derive(Debug, Clone, PartialEq)]
+pub struct RejectedPattern {
+    /// `None` when the reply had no pattern in it
+    pub pattern: Option<String>,
+    pub problems: Vec<String>,
+}
+
+/// Regex builder errors
+#[derive(Debug, thiserror::Error)]
+pub enum RegexBuildError {
+    #[error("No description of what the regex should match")]
+    EmptyDescription,
+
+    #[error("No working regex after {attempts} attempts: {}", .problems.join("; "))]
+    NoWorkingPattern { attempts: usize, problems: Vec<String> },
+}
+
+fn describe_samples(samples: &[RegexSample]) -> String {
+    samples.iter()
+        .map(|sample| match (&sample.expected_match, sample.should_match) {
+            (Some(expected), _) => format!("- {:?} should match exactly {:?}", sample.input, expected),
+            (None, true) => format!("- {:?} should match", sample.input),
+            (None, false) => format!("- {:?} should not match", sample.input),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn regex_prompt(description: &str, samples: &[RegexSample]) -> String {
+    let mut prompt = format!(
+        "Write a regular expression for the Rust `regex` crate that matches: {}\n\
+         No look-around or backreferences; put any flags inline, e.g. (?i).\n\
+         Reply with the pattern alone in a single ```regex code block.",
+        description.trim()
+    );
+    if !samples.is_empty() {
+        prompt.push_str(&format!("\n\nIt is tested on these inputs:\n{}", describe_samples(samples)));
+    }
+    prompt
+}
+
+fn retry_prompt(description: &str, samples: &[RegexSample], rejected: &RejectedPattern) -> String {
+    let previous = match &rejected.pattern {
+        Some(pattern) => format!("Your previous pattern `{}` failed:", pattern),
+        None => "Your previous reply failed:".to_string(),
+    };
+    format!(
+        "{}\n\n{}\n{}\n\nFix these problems and reply with the corrected pattern alone in a single ```regex code block.",
+        regex_prompt(description, samples),
+        previous,
+        rejected.problems.iter().map(|problem| format!("- {}", problem)).collect::<Vec<_>>().join("\n")
+    )
+}
+
+/// Compile the pattern in `reply` and run it on `samples`, returning the pattern
+/// and its per-sample results when every sample passes
+pub fn check_reply(reply: &str, samples: &[RegexSample]) -> Result<(String, Vec<RegexSampleResult>), RejectedPattern> {
+    let Some(pattern) = first_code_block(reply).map(str::trim) else {
+        return Err(RejectedPattern {
+            pattern: None,
+            problems: vec!["the reply did not contain a ```regex code block".to_string()],
+        });
+    };
+    let regex = Regex::new(pattern).map_err(|e| RejectedPattern {
+        pattern: Some(pattern.to_string()),
+        problems: vec![format!("the pattern does not compile: {}", e)],
+    })?;
+
+    let mut problems = Vec::new();
+    let results = samples.iter()
+        .map(|sample| {
+            let matched = regex.find(&sample.input).map(|m| m.as_str().to_string());
+            let problem = match (&sample.expected_match, &matched) {
+                (Some(expected), Some(found)) if expected != found => {
+                    Some(format!("{:?} should match {:?} but matched {:?}", sample.input, expected, found))
+                }
+                (Some(expected), None) => Some(format!("{:?} should match {:?} but did not match", sample.input, expected)),
+                (None, None) if sample.should_match => Some(format!("{:?} should match but did not", sample.input)),
+                (None, Some(found)) if !sample.should_match => {
+                    Some(format!("{:?} should not match but matched {:?}", sample.input, found))
+                }
+                _ => None,
+            };
+            let passed = problem.is_none();
+            problems.extend(problem);
+            RegexSampleResult { input: sample.input.clone(), matched, passed }
+        })
+        .collect();
+
+    if problems.is_empty() {
+        Ok((pattern.to_string(), results))
+    } else {
+        Err(RejectedPattern { pattern: Some(pattern.to_string()), problems })
+    }
+}
+
+impl AiEngine {
+    /// Ask the model for a regex matching `description` that behaves as `samples`
+    /// expect, retrying once with the failures when it does not
+    pub async fn build_regex(&self, description: &str, samples: &[RegexSample]) -> Result<BuiltRegex> {
+        if description.trim().is_empty() {
+            return Err(RegexBuildError::EmptyDescription.into());
+        }
+
+        let mut prompt = regex_prompt(description, samples);
+        let mut problems = Vec::new();
+        for attempt in 1..=MAX_ATTEMPTS {
+            let reply = self.run_prompt(&prompt, "regex").await?;
+            match check_reply(&reply, samples) {
+                Ok((pattern, samples)) => return Ok(BuiltRegex { pattern, samples, attempts: attempt }),
+                Err(rejected) => {
+                    log::debug!("Regex attempt {} failed: {}", attempt, rejected.problems.join("; "));
+                    prompt = retry_prompt(description, samples, &rejected);
+                    problems = rejected.problems;
+                }
+            }
+        }
+        Err(RegexBuildError::NoWorkingPattern { attempts: MAX_ATTEMPTS, problems }.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::tests::{mock_openai_server, openai_config};
+
+    fn sample(input: &str, should_match: bool) -> RegexSample {
+        RegexSample { input: input.to_string(), should_match, expected_match: None }
+    }
+
+    fn phone_samples() -> Vec<RegexSample> {
+        vec![
+            sample("(555) 123-4567", true),
+            sample("555-123-4567", true),
+            RegexSample {
+                input: "Call 555.123.4567 today".to_string(),
+                should_match: true,
+                expected_match: Some("555.123.4567".to_string()),
+            },
+            sample("123-45-6789", false),
+            sample("555-1234", false),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_us_phone_number_regex_is_validated_against_samples() {
+        let (addr, server) = mock_openai_server(
+            "```regex\\n\\\\(?\\\\d{3}\\\\)?[-. ]?\\\\d{3}[-.]\\\\d{4}\\n```"
+        ).await;
+        let engine = AiEngine::new_async(openai_config(addr)).await.unwrap();
+
+        let built = engine.build_regex("US phone numbers", &phone_samples()).await.unwrap();
+        assert_eq!(built.pattern, r"\(?\d{3}\)?[-. ]?\d{3}[-.]\d{4}");
+        assert_eq!(built.attempts, 1);
+        assert!(built.samples.iter().all(|result| result.passed));
+        assert_eq!(built.samples[2].matched.as_deref(), Some("555.123.4567"));
+        assert!(built.samples[3].matched.is_none());
+
+        let request = server.await.unwrap();
+        assert!(request.contains("US phone numbers"));
+        assert!(request.contains("should not match"));
+    }
+
+    #[test]
+    fn test_failing_pattern_reports_each_sample() {
+        let samples = phone_samples();
+        let rejected = check_reply("```regex\n\\d{3}-\\d{4}\n```", &samples).unwrap_err();
+        assert_eq!(rejected.pattern.as_deref(), Some(r"\d{3}-\d{4}"));
+        assert_eq!(rejected.problems, vec![
+            r#""Call 555.123.4567 today" should match "555.123.4567" but did not match"#.to_string(),
+            r#""555-1234" should not match but matched "555-1234""#.to_string(),
+        ]);
+
+        let retry = retry_prompt("US phone numbers", &samples, &rejected);
+        assert!(retry.contains(r"Your previous pattern `\d{3}-\d{4}` failed:"));
+        assert!(retry.contains(r#"- "555-1234" should not match but matched "555-1234""#));
+
+        let rejected = check_reply("```regex\n(\\d{3}\n```", &samples).unwrap_err();
+        assert!(rejected.problems[0].starts_with("the pattern does not compile"));
+        assert!(check_reply("Try \\d+", &samples).unwrap_err().pattern.is_none());
+    }
+}
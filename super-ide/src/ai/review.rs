@@ -0,0 +1,249 @@
+//! Structured code review built from analysis results
+//!
+//! Each finding is assigned a category from its rule id, source and wording.
+//! A finding is auto-fixable only when its fix suggestion names a concrete
+//! replacement that applies to the flagged line; those items carry a preview
+//! of the fixed line.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::{AnalysisResult, BugPrediction, BugType, CodeIssue, CodeSmell, IssueSeverity, SecurityVulnerability};
+
+/// Review category of a finding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewCategory {
+    Style,
+    Correctness,
+    Performance,
+    Security,
+}
+
+/// Before/after view of an automatic fix on one line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FixPreview {
+    pub line: usize,
+    pub original: String,
+    pub fixed: String,
+}
+
+/// A single code review finding
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewItem {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub severity: String,
+    pub line: usize,
+    pub category: ReviewCategory,
+    pub suggestion: String,
+    pub can_auto_fix: bool,
+    pub fix_preview: Option<FixPreview>,
+}
+
+/// Fix suggestions of the form "Replace `old` with `new`"
+static REPLACEMENT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\b(?:replace|change)\s+`([^`]+)`\s+(?:with|to|by)\s+`([^`]*)`").unwrap()
+});
+
+const SECURITY_HINTS: &[&str] = &["security", "injection", "unsafe", "secret", "password", "xss", "csrf", "cwe-"];
+const PERFORMANCE_HINTS: &[&str] = &["performance", "perf", "clone", "allocation", "complexity", "slow", "inefficient"];
+const STYLE_HINTS: &[&str] = &["style", "naming", "format", "whitespace", "indent", "line too long", "deprecated"];
+
+/// Pick a category from the rule id, finding id and message, falling back on severity
+fn categorize(rule_id: Option<&str>, id: &str, message: &str, severity: &IssueSeverity) -> ReviewCategory {
+    let haystack = format!("{} {} {}", rule_id.unwrap_or(""), id, message).to_lowercase();
+    let mentions = |hints: &[&str]| hints.iter().any(|hint| haystack.contains(hint));
+
+    if mentions(SECURITY_HINTS) {
+        ReviewCategory::Security
+    } else if mentions(PERFORMANCE_HINTS) {
+        ReviewCategory::Performance
+    } else if mentions(STYLE_HINTS) {
+        ReviewCategory::Style
+    } else if *severity == IssueSeverity::Info {
+        ReviewCategory::Style
+    } else {
+        ReviewCategory::Correctness
+    }
+}
+
+/// Preview a fix suggestion applied to the flagged line, if it names a concrete replacement
+fn fix_preview(code: &str, line: usize, suggestion: Option<&str>) -> Option<FixPreview> {
+    let caps = REPLACEMENT.captures(suggestion?)?;
+    let original = code.lines().nth(line.checked_sub(1)?)?;
+    if !original.contains(&caps[1]) {
+        return None;
+    }
+
+    Some(FixPreview {
+        line,
+        original: original.to_string(),
+        fixed: original.replacen(&caps[1], &caps[2], 1),
+    })
+}
+
+fn severity_label(severity: &IssueSeverity) -> String {
+    format!("{:?}", severity).to_lowercase()
+}
+
+impl ReviewItem {
+    /// Review item for a code analysis issue
+    pub fn from_issue(issue: &CodeIssue, code: &str) -> Self {
+        let category = categorize(issue.rule_id.as_deref(), &issue.id, &issue.message, &issue.severity);
+        // Security fixes always need a human to confirm them
+        let fix_preview = match category {
+            ReviewCategory::Security => None,
+            _ => fix_preview(code, issue.line, issue.fix_suggestion.as_deref()),
+        };
+
+        Self {
+            id: issue.id.clone(),
+            title: format!("{} Issue", issue.severity),
+            description: issue.message.clone(),
+            severity: severity_label(&issue.severity),
+            line: issue.line,
+            category,
+            suggestion: issue.fix_suggestion.clone().unwrap_or_else(|| "Review this code".to_string()),
+            can_auto_fix: fix_preview.is_some(),
+            fix_preview,
+        }
+    }
+
+    /// Review item for a security finding; never auto-fixable
+    pub fn from_vulnerability(vulnerability: &SecurityVulnerability) -> Self {
+        Self {
+            id: vulnerability.id.clone(),
+            title: vulnerability.title.clone(),
+            description: vulnerability.description.clone(),
+            severity: severity_label(&vulnerability.severity),
+            line: vulnerability.line,
+            category: ReviewCategory::Security,
+            suggestion: vulnerability.recommendation.clone(),
+            can_auto_fix: false,
+            fix_preview: None,
+        }
+    }
+
+    /// Review item for a predicted bug
+    pub fn from_bug_prediction(prediction: &BugPrediction, code: &str) -> Self {
+        let category = match prediction.bug_type {
+            BugType::SecurityVulnerability => ReviewCategory::Security,
+            BugType::PerformanceIssue => ReviewCategory::Performance,
+            _ => ReviewCategory::Correctness,
+        };
+        let fix_preview = match category {
+            ReviewCategory::Security => None,
+            _ => fix_preview(code, prediction.line, Some(&prediction.fix_suggestion)),
+        };
+
+        Self {
+            id: format!("bug_{}_{}", prediction.line, prediction.column),
+            title: format!("Possible {:?}", prediction.bug_type),
+            description: prediction.description.clone(),
+            severity: severity_label(&prediction.severity),
+            line: prediction.line,
+            category,
+            suggestion: prediction.fix_suggestion.clone(),
+            can_auto_fix: fix_preview.is_some(),
+            fix_preview,
+        }
+    }
+
+    /// Review item for a code smell
+    pub fn from_code_smell(smell: &CodeSmell, code: &str) -> Self {
+        let category = categorize(None, &smell.id, &smell.description, &smell.severity);
+        let fix_preview = fix_preview(code, smell.line, Some(&smell.refactoring_suggestion));
+
+        Self {
+            id: smell.id.clone(),
+            title: smell.name.clone(),
+            description: smell.description.clone(),
+            severity: severity_label(&smell.severity),
+            line: smell.line,
+            category,
+            suggestion: smell.refactoring_suggestion.clone(),
+            can_auto_fix: fix_preview.is_some(),
+            fix_preview,
+        }
+    }
+}
+
+/// Build review items for every finding of an analysis, ordered by line
+pub fn review_items(analysis: &AnalysisResult, code: &str) -> Vec<ReviewItem> {
+    let mut items: Vec<ReviewItem> = analysis.issues.iter()
+        .map(|issue| ReviewItem::from_issue(issue, code))
+        .chain(analysis.security_vulnerabilities.iter().map(ReviewItem::from_vulnerability))
+        .chain(analysis.bug_predictions.iter().map(|prediction| ReviewItem::from_bug_prediction(prediction, code)))
+        .chain(analysis.code_smells.iter().map(|smell| ReviewItem::from_code_smell(smell, code)))
+        .collect();
+    items.sort_by_key(|item| item.line);
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issue(id: &str, rule_id: Option<&str>, message: &str, fix: Option<&str>) -> CodeIssue {
+        CodeIssue {
+            id: id.to_string(),
+            severity: IssueSeverity::Warning,
+            message: message.to_string(),
+            line: 2,
+            column: 1,
+            file_path: None,
+            rule_id: rule_id.map(str::to_string),
+            fix_suggestion: fix.map(str::to_string),
+            documentation_url: None,
+        }
+    }
+
+    #[test]
+    fn test_security_finding_is_not_auto_fixable() {
+        let code = "let q = input;\nlet sql = format!(\"SELECT * FROM t WHERE id = {}\", q);";
+        let finding = issue(
+            "ai_security_2",
+            Some("CWE-89"),
+            "SQL built from user input",
+            Some("Replace `format!` with `sqlx::query!`"),
+        );
+
+        let item = ReviewItem::from_issue(&finding, code);
+        assert_eq!(item.category, ReviewCategory::Security);
+        assert!(!item.can_auto_fix);
+        assert!(item.fix_preview.is_none());
+
+        let vulnerability = SecurityVulnerability {
+            id: "python_eval_1".to_string(),
+            cwe_id: Some("CWE-95".to_string()),
+            title: "Code Injection".to_string(),
+            description: "eval() can execute arbitrary code".to_string(),
+            severity: IssueSeverity::Critical,
+            line: 1,
+            column: 1,
+            recommendation: "Use ast.literal_eval()".to_string(),
+            cve_references: vec![],
+        };
+        let item = ReviewItem::from_vulnerability(&vulnerability);
+        assert_eq!(item.category, ReviewCategory::Security);
+        assert!(!item.can_auto_fix);
+    }
+
+    #[test]
+    fn test_concrete_fix_is_auto_fixable_with_preview() {
+        let code = "let a = 1;\nif (a == b) { run(); }";
+        let finding = issue("js_eqeq_2", Some("eqeqeq"), "Use === instead of ==", Some("Replace `==` with `===`"));
+
+        let item = ReviewItem::from_issue(&finding, code);
+        assert_eq!(item.category, ReviewCategory::Correctness);
+        assert!(item.can_auto_fix);
+        assert_eq!(item.fix_preview.unwrap().fixed, "if (a === b) { run(); }");
+
+        let vague = issue("rust_unwrap_2", None, "Avoid unwrap()", Some("Use proper error handling"));
+        assert!(!ReviewItem::from_issue(&vague, code).can_auto_fix);
+    }
+}
@@ -0,0 +1,178 @@
+//! Local analysis rules
+//!
+//! Every check the local analyzer runs has a rule id such as `rust::unwrap_used`.
+//! The `rules` configuration section turns rules off or changes their severity,
+//! and a project can override that section in `.super-ide/rules.toml`, so one
+//! team's lints need not be everyone's.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use super::{AiEngine, IssueSeverity};
+use crate::config::RuleSetting;
+
+/// Per-project rule overrides, relative to the project root
+pub const PROJECT_RULES_FILE: &str = ".super-ide/rules.toml";
+
+/// A rule the local analyzer implements
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleInfo {
+    pub id: &'static str,
+    pub language: &'static str,
+    pub description: &'static str,
+    pub default_severity: IssueSeverity,
+}
+
+/// Every local analysis rule, by language
+pub const LOCAL_RULES: &[RuleInfo] = &[
+    RuleInfo { id: "rust::unwrap_used", language: "rust", description: "unwrap() panics on None or Err", default_severity: IssueSeverity::Warning },
+    RuleInfo { id: "rust::clone_on_ref", language: "rust", description: "Cloning where a reference would do", default_severity: IssueSeverity::Info },
+    RuleInfo { id: "rust::expect_used", language: "rust", description: "expect() panics on None or Err", default_severity: IssueSeverity::Warning },
+    RuleInfo { id: "python::bare_except", language: "python", description: "except: catches every exception", default_severity: IssueSeverity::Warning },
+    RuleInfo { id: "python::eval", language: "python", description: "eval() runs arbitrary code", default_severity: IssueSeverity::Critical },
+    RuleInfo { id: "js::eqeqeq", language: "javascript", description: "== converts types before comparing", default_severity: IssueSeverity::Warning },
+    RuleInfo { id: "js::no_var", language: "javascript", description: "var is function-scoped; use let or const", default_severity: IssueSeverity::Info },
+];
+
+pub fn find_rule(id: &str) -> Option<&'static RuleInfo> {
+    LOCAL_RULES.iter().find(|rule| rule.id == id)
+}
+
+/// A rule as listed to users, with the settings in effect
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleStatus {
+    #[serde(flatten)]
+    pub rule: RuleInfo,
+    pub enabled: bool,
+    pub severity: IssueSeverity,
+}
+
+/// Which rules run, and at what severity
+#[derive(Debug, Clone, Default)]
+pub struct RuleSet {
+    settings: BTreeMap<String, RuleSetting>,
+}
+
+impl RuleSet {
+    pub fn new(settings: &BTreeMap<String, RuleSetting>) -> Self {
+        Self { settings: settings.clone() }
+    }
+
+    /// Layer `overrides` on top; each field an override sets replaces the one before
+    pub fn with_overrides(mut self, overrides: &BTreeMap<String, RuleSetting>) -> Self {
+        for (id, setting) in overrides {
+            let current = self.settings.entry(id.clone()).or_default();
+            current.enabled = setting.enabled.or(current.enabled);
+            current.severity = setting.severity.clone().or(current.severity.take());
+        }
+        self
+    }
+
+    /// Apply the project's [`PROJECT_RULES_FILE`] under `root`, if it has one
+    pub fn with_project_overrides(self, root: &Path) -> anyhow::Result<Self> {
+        let path = root.join(PROJECT_RULES_FILE);
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(self),
+            Err(e) => return Err(e.into()),
+        };
+        let overrides: BTreeMap<String, RuleSetting> = toml::from_str(&text)
+            .map_err(|e| anyhow::anyhow!("Invalid {}: {}", path.display(), e))?;
+        if let Some(id) = overrides.keys().find(|id| find_rule(id).is_none()) {
+            return Err(anyhow::anyhow!("Unknown analysis rule {} in {}", id, path.display()));
+        }
+        Ok(self.with_overrides(&overrides))
+    }
+
+    /// Rules are enabled unless configured otherwise
+    pub fn is_enabled(&self, id: &str) -> bool {
+        self.settings.get(id).and_then(|setting| setting.enabled).unwrap_or(true)
+    }
+
+    /// The configured severity of rule `id`, or its default
+    pub fn severity(&self, id: &str) -> IssueSeverity {
+        self.settings.get(id)
+            .and_then(|setting| setting.severity.clone())
+            .or_else(|| find_rule(id).map(|rule| rule.default_severity.clone()))
+            .unwrap_or(IssueSeverity::Warning)
+    }
+
+    /// Every rule with its settings in effect
+    pub fn catalog(&self) -> Vec<RuleStatus> {
+        LOCAL_RULES.iter()
+            .map(|rule| RuleStatus {
+                rule: rule.clone(),
+                enabled: self.is_enabled(rule.id),
+                severity: self.severity(rule.id),
+            })
+            .collect()
+    }
+}
+
+impl AiEngine {
+    /// Run local analysis rules as `rules` configures them
+    pub fn with_rules(mut self, rules: RuleSet) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    pub fn rules(&self) -> &RuleSet {
+        &self.rules
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::tests::test_config;
+
+    #[tokio::test]
+    async fn test_disabled_rule_is_not_reported() {
+        let dir = std::env::temp_dir().join(format!("super-ide-rules-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join(".super-ide")).unwrap();
+        std::fs::write(dir.join(PROJECT_RULES_FILE), "\"rust::expect_used\" = { severity = \"Error\" }\n").unwrap();
+
+        let configured = BTreeMap::from([
+            ("rust::unwrap_used".to_string(), RuleSetting { enabled: Some(false), severity: None }),
+            ("rust::expect_used".to_string(), RuleSetting { enabled: None, severity: Some(IssueSeverity::Info) }),
+        ]);
+        let rules = RuleSet::new(&configured).with_project_overrides(&dir).unwrap();
+        let engine = AiEngine::new_async(test_config("local", None)).await.unwrap().with_rules(rules);
+
+        let code = "fn main() {\n    let value = input.unwrap();\n    let copy = (&value).clone();\n    run(copy).expect();\n}";
+        let analysis = engine.advanced_analyze_code(code, "rust", None).await.unwrap();
+        assert!(analysis.bug_predictions.is_empty());
+        assert_eq!(analysis.code_smells.len(), 1);
+        assert_eq!(analysis.security_vulnerabilities.len(), 1);
+        assert_eq!(analysis.security_vulnerabilities[0].severity, IssueSeverity::Error);
+
+        let catalog = engine.rules().catalog();
+        let unwrap = catalog.iter().find(|status| status.rule.id == "rust::unwrap_used").unwrap();
+        assert!(!unwrap.enabled);
+        assert_eq!(catalog.len(), LOCAL_RULES.len());
+
+        std::fs::write(dir.join(PROJECT_RULES_FILE), "\"rust::no_such_rule\" = { enabled = false }\n").unwrap();
+        assert!(RuleSet::default().with_project_overrides(&dir).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_bare_except_is_reported_only_without_an_exception() {
+        let engine = AiEngine::new_async(test_config("local", None)).await.unwrap();
+        let code = "try:\n    run()\nexcept ValueError:\n    pass\ntry:\n    run()\nexcept :\n    pass\n";
+        let analysis = engine.advanced_analyze_code(code, "python", None).await.unwrap();
+        let lines: Vec<usize> = analysis.issues.iter()
+            .filter(|issue| issue.id.starts_with("python_bare_except"))
+            .map(|issue| issue.line)
+            .collect();
+        assert_eq!(lines, vec![7]);
+
+        let configured = BTreeMap::from([
+            ("python::bare_except".to_string(), RuleSetting { enabled: Some(false), severity: None }),
+        ]);
+        let engine = engine.with_rules(RuleSet::new(&configured));
+        let analysis = engine.advanced_analyze_code(code, "python", Some("other.py")).await.unwrap();
+        assert!(analysis.issues.iter().all(|issue| !issue.id.starts_with("python_bare_except")));
+    }
+}
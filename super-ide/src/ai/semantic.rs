@@ -0,0 +1,358 @@
+//! Semantic code search
+//!
+//! Source files are cut into overlapping windows of lines and each window is
+//! embedded once. The vectors are kept per workspace-relative file, with a hash of
+//! the content they came from, and saved under `.super-ide/` so a restart only
+//! re-embeds files that changed. Switching embedding models discards the saved
+//! index, since vectors from different models do not compare. When local data
+//! must stay encrypted the index is kept in memory only, as it holds plain copies
+//! of the code.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+
+use super::embeddings::{cosine_similarity, fnv1a, LOCAL_EMBEDDING_MODEL};
+use super::AiEngine;
+use crate::utils::event_bus::FileIndex;
+
+/// Saved index, relative to the workspace
+const INDEX_FILE: &str = ".super-ide/semantic-index.json";
+
+/// Bumped when the saved format changes
+const INDEX_VERSION: u32 = 1;
+
+/// Lines per chunk, and lines shared by neighbouring chunks
+const CHUNK_LINES: usize = 40;
+const CHUNK_OVERLAP: usize = 10;
+
+/// Files larger than this are not indexed
+const MAX_INDEXED_FILE_BYTES: u64 = 512 * 1024;
+
+const INDEXED_EXTENSIONS: &[&str] = &[
+    "rs", "py", "js", "jsx", "ts", "tsx", "mjs", "go", "java", "kt", "c", "h", "cpp", "hpp", "cs", "rb", "php", "swift",
+];
+
+/// A chunk of code and how close it is to the query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SemanticMatch {
+    /// Workspace-relative path
+    pub file: String,
+    /// 1-based, inclusive line range
+    pub start_line: usize,
+    pub end_line: usize,
+    /// Cosine similarity to the query
+    pub score: f32,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedChunk {
+    start_line: usize,
+    end_line: usize,
+    text: String,
+    vector: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedFile {
+    /// `fnv1a` of the content the chunks were cut from
+    hash: u64,
+    chunks: Vec<IndexedChunk>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedIndex {
+    version: u32,
+    model: String,
+    files: HashMap<String, IndexedFile>,
+}
+
+/// Cut `content` into chunks of `CHUNK_LINES` lines overlapping by `CHUNK_OVERLAP`,
+/// as (first line, last line, text), skipping blank ones
+fn chunk_lines(content: &str) -> Vec<(usize, usize, String)> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        let end = (start + CHUNK_LINES).min(lines.len());
+        let text = lines[start..end].join("\n");
+        if !text.trim().is_empty() {
+            chunks.push((start + 1, end, text));
+        }
+        if end == lines.len() {
+            break;
+        }
+        start += CHUNK_LINES - CHUNK_OVERLAP;
+    }
+    chunks
+}
+
+/// Embedding vectors of the workspace's source files
+#[derive(Debug)]
+pub struct SemanticIndex {
+    root: PathBuf,
+    ai_engine: Arc<AiEngine>,
+    model: String,
+    files: RwLock<HashMap<String, IndexedFile>>,
+    /// Whether the index is saved under `.super-ide/` between runs
+    persist: bool,
+    /// Keeps concurrent saves from interleaving
+    save_lock: Mutex<()>,
+}
+
+impl SemanticIndex {
+    /// Create an empty index for the workspace at `root`, embedding with the
+    /// provider's default model unless `embedding_model` names one
+    pub fn new(root: PathBuf, ai_engine: Arc<AiEngine>, embedding_model: Option<&str>) -> Self {
+        Self {
+            model: ai_engine.embedding_model(embedding_model),
+            root,
+            ai_engine,
+            files: RwLock::new(HashMap::new()),
+            persist: true,
+            save_lock: Mutex::new(()),
+        }
+    }
+
+    /// Embed locally whatever the provider, so no code leaves the machine
+    pub fn with_local_embeddings(mut self) -> Self {
+        self.model = LOCAL_EMBEDDING_MODEL.to_string();
+        self
+    }
+
+    /// Whether to save the index between runs, and load the saved one
+    pub fn with_persistence(mut self, persist: bool) -> Self {
+        self.persist = persist;
+        self
+    }
+
+    /// Load the saved index, then bring it up to date with the workspace. Returns
+    /// the number of files that had to be embedded.
+    pub async fn build(&self) -> usize {
+        if let Some(saved) = self.load().await {
+            *self.files.write().await = saved;
+        }
+
+        let root = self.root.clone();
+        let present: Vec<String> = tokio::task::spawn_blocking(move || {
+            ignore::WalkBuilder::new(&root).require_git(false).build()
+                .flatten()
+                .filter(|entry| Self::is_indexable(entry.path()))
+                .filter_map(|entry| Self::relative_path(&root, entry.path()))
+                .collect()
+        }).await.unwrap_or_default();
+
+        let present_set: HashSet<&String> = present.iter().collect();
+        self.files.write().await.retain(|file, _| present_set.contains(file));
+        let mut embedded = 0;
+        for file in &present {
+            match self.index_file(file).await {
+                Ok(true) => embedded += 1,
+                Ok(false) => {}
+                Err(e) => log::warn!("Failed to embed {}: {}", file, e),
+            }
+        }
+
+        self.save().await;
+        embedded
+    }
+
+    /// The `limit` chunks closest in meaning to `query`, best first
+    pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<SemanticMatch>> {
+        let query_vector = self.ai_engine.embed(&self.model, &[query.to_string()]).await?
+            .pop()
+            .unwrap_or_default();
+
+        let files = self.files.read().await;
+        let mut matches: Vec<SemanticMatch> = files.iter()
+            .flat_map(|(file, indexed)| indexed.chunks.iter().map(move |chunk| (file, chunk)))
+            .map(|(file, chunk)| SemanticMatch {
+                file: file.clone(),
+                start_line: chunk.start_line,
+                end_line: chunk.end_line,
+                score: cosine_similarity(&query_vector, &chunk.vector),
+                text: chunk.text.clone(),
+            })
+            .collect();
+
+        matches.sort_by(|a, b| {
+            b.score.total_cmp(&a.score)
+                .then_with(|| a.file.cmp(&b.file))
+                .then_with(|| a.start_line.cmp(&b.start_line))
+        });
+        matches.truncate(limit);
+        Ok(matches)
+    }
+
+    /// Number of indexed files
+    pub async fn file_count(&self) -> usize {
+        self.files.read().await.len()
+    }
+
+    /// Embed `relative` unless its content is unchanged; `Ok(true)` when it was embedded
+    async fn index_file(&self, relative: &str) -> Result<bool> {
+        let path = self.root.join(relative);
+        let content = if Self::is_indexable(&path) {
+            tokio::fs::read_to_string(&path).await.ok()
+        } else {
+            None
+        };
+        let Some(content) = content else {
+            self.files.write().await.remove(relative);
+            return Ok(false);
+        };
+
+        let hash = fnv1a(content.as_bytes());
+        if self.files.read().await.get(relative).is_some_and(|indexed| indexed.hash == hash) {
+            return Ok(false);
+        }
+
+        let chunks = chunk_lines(&content);
+        // The path tells the model what the file is about when the chunk alone does not
+        let inputs: Vec<String> = chunks.iter().map(|(_, _, text)| format!("{}\n{}", relative, text)).collect();
+        let vectors = self.ai_engine.embed(&self.model, &inputs).await?;
+        let chunks = chunks.into_iter().zip(vectors)
+            .map(|((start_line, end_line, text), vector)| IndexedChunk { start_line, end_line, text, vector })
+            .collect();
+
+        self.files.write().await.insert(relative.to_string(), IndexedFile { hash, chunks });
+        Ok(true)
+    }
+
+    async fn load(&self) -> Option<HashMap<String, IndexedFile>> {
+        if !self.persist {
+            return None;
+        }
+        let bytes = tokio::fs::read(self.root.join(INDEX_FILE)).await.ok()?;
+        let saved: SavedIndex = match serde_json::from_slice(&bytes) {
+            Ok(saved) => saved,
+            Err(e) => {
+                log::warn!("Ignoring unreadable semantic index: {}", e);
+                return None;
+            }
+        };
+        (saved.version == INDEX_VERSION && saved.model == self.model).then_some(saved.files)
+    }
+
+    async fn save(&self) {
+        if !self.persist {
+            return;
+        }
+        let _saving = self.save_lock.lock().await;
+        let saved = SavedIndex {
+            version: INDEX_VERSION,
+            model: self.model.clone(),
+            files: self.files.read().await.clone(),
+        };
+        let path = self.root.join(INDEX_FILE);
+        let result = async {
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            let bytes = serde_json::to_vec(&saved)?;
+            crate::file_ops::atomic::write_atomic(&path, &bytes, false).await?;
+            Ok::<_, anyhow::Error>(())
+        }.await;
+        if let Err(e) = result {
+            log::warn!("Failed to save semantic index: {}", e);
+        }
+    }
+
+    fn relative_path(root: &Path, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(root).ok()?;
+        Some(relative.to_string_lossy().replace('\\', "/"))
+    }
+
+    /// Whether `path` is a source file small enough to index
+    fn is_indexable(path: &Path) -> bool {
+        let supported = path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| INDEXED_EXTENSIONS.contains(&ext));
+        supported && std::fs::metadata(path).is_ok_and(|metadata| metadata.is_file() && metadata.len() <= MAX_INDEXED_FILE_BYTES)
+    }
+}
+
+#[async_trait::async_trait]
+impl FileIndex for SemanticIndex {
+    /// Re-embed one file if its content changed, dropping it if it no longer
+    /// exists or is not indexed
+    async fn update_file(&self, relative: &str) {
+        match self.index_file(relative).await {
+            Ok(true) => self.save().await,
+            Ok(false) => {}
+            Err(e) => log::warn!("Failed to embed {}: {}", relative, e),
+        }
+    }
+
+    async fn remove_file(&self, relative: &str) {
+        if self.files.write().await.remove(relative).is_some() {
+            self.save().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Configuration;
+
+    #[tokio::test]
+    async fn test_query_finds_closest_snippet_and_index_persists() {
+        let root = std::env::temp_dir().join(format!("super-ide-semantic-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/settings.rs"), "/// Read the settings file\npub fn load_settings(path: &Path) -> Settings {\n    let text = std::fs::read_to_string(path).unwrap();\n    serde_json::from_str(&text).unwrap()\n}\n").unwrap();
+        std::fs::write(root.join("src/server.rs"), "/// Accept HTTP connections and route each request\npub async fn serve(listener: TcpListener, router: Router) {\n    axum::serve(listener, router).await.unwrap();\n}\n").unwrap();
+        std::fs::write(root.join("src/math.rs"), "/// Factorial of n\npub fn factorial(n: u64) -> u64 {\n    (1..=n).product()\n}\n").unwrap();
+        let ai_engine = Arc::new(AiEngine::new(crate::ai::AiConfig::from(&Configuration::default())));
+
+        let index = SemanticIndex::new(root.clone(), ai_engine.clone(), None);
+        assert_eq!(index.build().await, 3);
+        let matches = index.search("load the JSON settings from a file", 2).await.unwrap();
+        assert_eq!(matches.len(), 2);
+        assert_eq!((matches[0].file.as_str(), matches[0].start_line, matches[0].end_line), ("src/settings.rs", 1, 5));
+        assert!(matches[0].score > matches[1].score);
+        assert_eq!(index.search("route incoming http requests", 1).await.unwrap()[0].file, "src/server.rs");
+
+        // A restart only embeds what changed since the index was saved
+        std::fs::write(root.join("src/math.rs"), "/// Greatest common divisor\npub fn gcd(a: u64, b: u64) -> u64 {\n    if b == 0 { a } else { gcd(b, a % b) }\n}\n").unwrap();
+        std::fs::remove_file(root.join("src/server.rs")).unwrap();
+        let reopened = SemanticIndex::new(root.clone(), ai_engine, None);
+        assert_eq!(reopened.build().await, 1);
+        assert_eq!(reopened.file_count().await, 2);
+        assert_eq!(reopened.search("greatest common divisor", 1).await.unwrap()[0].file, "src/math.rs");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_private_index_stays_local_and_in_memory() {
+        let root = std::env::temp_dir().join(format!("super-ide-semantic-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/math.rs"), "/// Factorial of n\npub fn factorial(n: u64) -> u64 {\n    (1..=n).product()\n}\n").unwrap();
+        // Nothing listens here, so any embedding request would fail
+        let config = crate::ai::AiConfig {
+            base_url: Some("http://127.0.0.1:1/v1".to_string()),
+            ..crate::ai::tests::test_config("openai", None)
+        };
+        let ai_engine = Arc::new(AiEngine::new_async(config).await.unwrap());
+
+        let index = SemanticIndex::new(root.clone(), ai_engine, None)
+            .with_local_embeddings()
+            .with_persistence(false);
+        assert_eq!(index.build().await, 1);
+        assert_eq!(index.search("factorial", 1).await.unwrap()[0].file, "src/math.rs");
+        assert!(!root.join(INDEX_FILE).exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_chunks_overlap_and_cover_the_file() {
+        let content: String = (1..=75).map(|i| format!("line {}\n", i)).collect();
+        let ranges: Vec<(usize, usize)> = chunk_lines(&content).into_iter().map(|(start, end, _)| (start, end)).collect();
+        assert_eq!(ranges, vec![(1, 40), (31, 70), (61, 75)]);
+        assert!(chunk_lines("\n\n  \n").is_empty());
+    }
+}
@@ -0,0 +1,278 @@
+//! User-defined AI tools
+//!
+//! Tools are loaded from the `ai.tools` configuration section. Each one is a prompt
+//! template with `{{placeholder}}` slots that are filled from the code and context
+//! submitted when the tool runs. Templates are checked when the registry is built,
+//! so a typo in a placeholder fails at startup rather than on first use.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use super::{AiEngine, AiError};
+use crate::config::AiToolConfig;
+
+/// Placeholders a prompt template may use
+pub const TOOL_PLACEHOLDERS: &[&str] = &["code", "language", "context", "file_path"];
+
+/// Tool configuration errors
+#[derive(Debug, thiserror::Error)]
+pub enum ToolError {
+    #[error("Tool `{0}` is defined more than once")]
+    DuplicateId(String),
+
+    #[error("Tool `{id}` has an invalid prompt template: {reason}")]
+    InvalidTemplate { id: String, reason: String },
+
+    #[error("Tool not found: {0}")]
+    NotFound(String),
+
+    #[error("Tool `{id}` does not apply to {language}")]
+    LanguageNotSupported { id: String, language: String },
+}
+
+/// A validated user-defined AI action
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiTool {
+    pub id: String,
+    pub name: String,
+    pub prompt_template: String,
+    pub applies_to_languages: Vec<String>,
+}
+
+impl AiTool {
+    /// Whether the tool is offered for `language`
+    pub fn applies_to(&self, language: &str) -> bool {
+        self.applies_to_languages.is_empty()
+            || self.applies_to_languages.iter().any(|l| l.eq_ignore_ascii_case(language))
+    }
+
+    /// Fill the template with the submitted input in one pass, so placeholders
+    /// inside the submitted values are left as they are
+    pub fn render(&self, input: &ToolInput) -> String {
+        let mut prompt = String::with_capacity(self.prompt_template.len() + input.code.len());
+        let mut rest = self.prompt_template.as_str();
+        while let Some(start) = rest.find("{{") {
+            prompt.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            let Some(end) = after.find("}}") else {
+                rest = &rest[start..];
+                break;
+            };
+            match &after[..end] {
+                "code" => prompt.push_str(&input.code),
+                "language" => prompt.push_str(&input.language),
+                "context" => prompt.push_str(input.context.as_deref().unwrap_or("")),
+                "file_path" => prompt.push_str(input.file_path.as_deref().unwrap_or("")),
+                _ => prompt.push_str(&rest[start..start + 2 + end + 2]),
+            }
+            rest = &after[end + 2..];
+        }
+        prompt.push_str(rest);
+        prompt
+    }
+}
+
+/// Input submitted when running a tool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolInput {
+    pub code: String,
+    pub language: String,
+    pub context: Option<String>,
+    pub file_path: Option<String>,
+}
+
+/// Output of a tool run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolOutput {
+    pub tool_id: String,
+    pub output: String,
+}
+
+/// Check that every `{{...}}` in `template` names a known placeholder, and that the
+/// template uses the submitted code
+fn validate_template(template: &str) -> std::result::Result<(), String> {
+    let mut used = HashSet::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let end = after.find("}}").ok_or_else(|| "unclosed `{{`".to_string())?;
+        let name = after[..end].trim();
+        if !TOOL_PLACEHOLDERS.contains(&name) {
+            return Err(format!(
+                "unknown placeholder `{{{{{}}}}}` (expected one of: {})",
+                name,
+                TOOL_PLACEHOLDERS.join(", ")
+            ));
+        }
+        if name != &after[..end] {
+            return Err(format!("placeholder `{{{{{}}}}}` must not contain spaces", name));
+        }
+        used.insert(name);
+        rest = &after[end + 2..];
+    }
+
+    if !used.contains("code") {
+        return Err("template must include `{{code}}`".to_string());
+    }
+    Ok(())
+}
+
+/// Registry of the configured AI tools
+#[derive(Debug, Clone, Default)]
+pub struct ToolRegistry {
+    tools: Vec<AiTool>,
+}
+
+impl ToolRegistry {
+    /// Build the registry from configuration, validating every template
+    pub fn from_config(configs: &[AiToolConfig]) -> std::result::Result<Self, ToolError> {
+        let mut tools: Vec<AiTool> = Vec::with_capacity(configs.len());
+        for config in configs {
+            if tools.iter().any(|tool| tool.id == config.id) {
+                return Err(ToolError::DuplicateId(config.id.clone()));
+            }
+            validate_template(&config.prompt_template).map_err(|reason| ToolError::InvalidTemplate {
+                id: config.id.clone(),
+                reason,
+            })?;
+
+            tools.push(AiTool {
+                id: config.id.clone(),
+                name: config.name.clone(),
+                prompt_template: config.prompt_template.clone(),
+                applies_to_languages: config.applies_to_languages.clone(),
+            });
+        }
+        Ok(Self { tools })
+    }
+
+    /// All tools, or only those applying to `language`
+    pub fn list(&self, language: Option<&str>) -> Vec<&AiTool> {
+        self.tools.iter()
+            .filter(|tool| language.map_or(true, |language| tool.applies_to(language)))
+            .collect()
+    }
+
+    /// Look up a tool by id
+    pub fn get(&self, id: &str) -> Option<&AiTool> {
+        self.tools.iter().find(|tool| tool.id == id)
+    }
+
+    /// Run a tool against the AI engine and return the model output
+    pub async fn run(&self, engine: &AiEngine, id: &str, input: &ToolInput) -> Result<ToolOutput> {
+        let tool = self.get(id).ok_or_else(|| ToolError::NotFound(id.to_string()))?;
+        if !tool.applies_to(&input.language) {
+            return Err(ToolError::LanguageNotSupported {
+                id: id.to_string(),
+                language: input.language.clone(),
+            }.into());
+        }
+
+        let output = engine.run_prompt(&tool.render(input), &input.language).await?;
+        Ok(ToolOutput {
+            tool_id: tool.id.clone(),
+            output,
+        })
+    }
+}
+
+impl AiEngine {
+    /// Send a free-form prompt to the configured provider, or to the fallbacks while
+    /// providers are failing
+    pub async fn run_prompt(&self, prompt: &str, language: &str) -> Result<String> {
+        if !self.initialized {
+            return Err(anyhow::anyhow!("AI Engine not initialized"));
+        }
+
+        self.with_provider_fallback(|provider| async move {
+            match provider.as_str() {
+                "openai" => {
+                    let system_prompt = format!("You are an expert {} developer. Follow the instructions exactly.", language);
+                    self.track("prompt", self.openai_chat(&system_prompt, prompt, self.config.max_tokens)).await
+                }
+                provider => Err(AiError::Unavailable(format!("prompts are not supported by the {} provider", provider)).into()),
+            }
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::tests::{mock_openai_server, openai_config, test_config};
+
+    fn tool_config(id: &str, template: &str) -> AiToolConfig {
+        AiToolConfig {
+            id: id.to_string(),
+            name: "Generate docstring".to_string(),
+            prompt_template: template.to_string(),
+            applies_to_languages: vec!["python".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_invalid_templates_fail_at_load() {
+        for template in ["Document {{cdoe}}", "Document {{code", "Explain {{language}} only"] {
+            let error = ToolRegistry::from_config(&[tool_config("docstring", template)]).unwrap_err();
+            assert!(matches!(error, ToolError::InvalidTemplate { .. }), "{}", template);
+        }
+
+        let duplicate = tool_config("docstring", "Document {{code}}");
+        assert!(matches!(
+            ToolRegistry::from_config(&[duplicate.clone(), duplicate]),
+            Err(ToolError::DuplicateId(_))
+        ));
+    }
+
+    #[test]
+    fn test_placeholders_in_submitted_code_are_not_expanded() {
+        let registry = ToolRegistry::from_config(&[tool_config("docstring", "{{language}}: {{code}} ({{file_path}})")]).unwrap();
+        let input = ToolInput {
+            code: "print('{{language}} {{file_path}}')".to_string(),
+            language: "python".to_string(),
+            context: None,
+            file_path: Some("app.py".to_string()),
+        };
+        assert_eq!(
+            registry.get("docstring").unwrap().render(&input),
+            "python: print('{{language}} {{file_path}}') (app.py)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_custom_tool_runs_against_provider() {
+        let (addr, server) = mock_openai_server("def add(a, b):\\n    # Add two numbers.\\n    return a + b").await;
+        let engine = AiEngine::new_async(openai_config(addr)).await.unwrap();
+
+        let registry = ToolRegistry::from_config(&[tool_config(
+            "docstring",
+            "Add a docstring to this {{language}} function:\n{{code}}",
+        )]).unwrap();
+        assert_eq!(registry.list(Some("python")).len(), 1);
+        assert!(registry.list(Some("rust")).is_empty());
+
+        let input = ToolInput {
+            code: "def add(a, b): return a + b".to_string(),
+            language: "python".to_string(),
+            context: None,
+            file_path: None,
+        };
+        let output = registry.run(&engine, "docstring", &input).await.unwrap();
+        assert_eq!(output.tool_id, "docstring");
+        assert!(output.output.contains("Add two numbers."));
+
+        let request = server.await.unwrap();
+        assert!(request.contains("Add a docstring to this python function:\\ndef add(a, b): return a + b"));
+    }
+
+    #[tokio::test]
+    async fn test_prompt_falls_back_to_the_next_provider() {
+        let (addr, server) = mock_openai_server("from the fallback").await;
+        let engine = AiEngine::new_async(test_config("local", Some(format!("http://{}/v1", addr)))).await.unwrap()
+            .with_fallback_providers(vec!["openai".to_string()]);
+
+        assert_eq!(engine.run_prompt("Say hello", "text").await.unwrap(), "from the fallback");
+        server.await.unwrap();
+    }
+}
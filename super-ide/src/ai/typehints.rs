@@ -0,0 +1,306 @@
+//! Type annotations for function signatures
+//!
+//! The model proposes parameter and return types for one function, or for every
+//! top-level function of a file, in the annotation syntax of the language: type
+//! hints for Python, type annotations for TypeScript and explicit types for Rust.
+//! The annotated code must parse no worse than the original and define the same
+//! functions before its signatures are returned.
+
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::fix::first_code_block;
+use super::AiEngine;
+use crate::utils::language_tools::syntax_error_count;
+
+/// Lines a signature may span
+const MAX_SIGNATURE_LINES: usize = 20;
+
+static PYTHON_DEF: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(\s*)(?:async\s+)?def\s+([A-Za-z_][A-Za-z0-9_]*)").unwrap()
+});
+static TYPESCRIPT_FUNCTION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(\s*)(?:export\s+(?:default\s+)?)?(?:declare\s+)?(?:async\s+)?function\s*\*?\s*([A-Za-z_$][A-Za-z0-9_$]*)").unwrap()
+});
+static RUST_FN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"^(\s*)(?:pub(?:\([^)]*\))?\s+)?(?:const\s+)?(?:async\s+)?(?:unsafe\s+)?(?:extern\s+"[^"]*"\s+)?fn\s+([A-Za-z_][A-Za-z0-9_]*)"#).unwrap()
+});
+
+/// Annotation syntaxes supported for type inference
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TypeSyntax {
+    /// PEP 484 hints on parameters and `->` return types
+    PythonHints,
+    /// `: Type` on parameters and after the parameter list
+    TypeScript,
+    /// Explicit Rust types in place of inferred or placeholder ones
+    Rust,
+}
+
+impl TypeSyntax {
+    /// Syntax used for `language`, if type inference supports it
+    pub fn for_language(language: &str) -> Option<Self> {
+        match language.to_lowercase().as_str() {
+            "python" | "py" => Some(Self::PythonHints),
+            "typescript" | "ts" | "tsx" => Some(Self::TypeScript),
+            "rust" | "rs" => Some(Self::Rust),
+            _ => None,
+        }
+    }
+
+    fn instructions(self) -> &'static str {
+        match self {
+            Self::PythonHints => "Use PEP 484 type hints, preferring builtin generics such as `list[int]` and `X | None` and importing from `typing` only what they cannot express.",
+            Self::TypeScript => "Use TypeScript type annotations with the most precise types the code supports, avoiding `any`.",
+            Self::Rust => "Spell out every type the signature leaves to inference or to `_` placeholders, keeping lifetimes elided where the compiler allows it.",
+        }
+    }
+
+    fn function_pattern(self) -> &'static Regex {
+        match self {
+            Self::PythonHints => &PYTHON_DEF,
+            Self::TypeScript => &TYPESCRIPT_FUNCTION,
+            Self::Rust => &RUST_FN,
+        }
+    }
+}
+
+/// How much of the code is annotated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InferScope {
+    /// The first function in the code
+    #[default]
+    Function,
+    /// Every top-level function of a file
+    File,
+}
+
+/// Type inference errors
+#[derive(Debug, thiserror::Error)]
+pub enum InferTypesError {
+    #[error("Type inference is not supported for {0}")]
+    UnsupportedLanguage(String),
+
+    #[error("The code has no function to annotate")]
+    NoFunction,
+
+    #[error("Model reply does not contain the annotated code")]
+    MissingCode,
+
+    #[error("Annotated code does not parse: {errors} syntax error(s)")]
+    InvalidSyntax { errors: usize },
+
+    #[error("Annotated code defines functions {found:?} instead of {expected:?}")]
+    FunctionsChanged { expected: Vec<String>, found: Vec<String> },
+}
+
+/// A function signature of the annotated code
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnnotatedSignature {
+    pub name: String,
+    /// 1-based line of the annotated code the signature starts on
+    pub line: usize,
+    /// Signature text, up to the body
+    pub signature: String,
+}
+
+/// Code with type annotations added to its signatures
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InferredTypes {
+    pub language: String,
+    pub syntax: TypeSyntax,
+    pub scope: InferScope,
+    pub annotated_code: String,
+    pub signatures: Vec<AnnotatedSignature>,
+    /// Whether the annotated code was checked with a parser for the language
+    pub syntax_checked: bool,
+}
+
+/// Signature starting on the first of `lines`: through the `:` ending a Python
+/// header, before the `{` opening a body, or through the `;` of a declaration
+fn signature_text(syntax: TypeSyntax, lines: &[&str]) -> String {
+    let mut text = Vec::new();
+    let mut depth = 0usize;
+    let mut seen_parameters = false;
+    for line in lines.iter().take(MAX_SIGNATURE_LINES).map(|line| line.trim_end()) {
+        for (position, c) in line.char_indices() {
+            match c {
+                '(' | '[' => {
+                    depth += 1;
+                    seen_parameters = true;
+                }
+                ')' | ']' => depth = depth.saturating_sub(1),
+                _ if depth > 0 || !seen_parameters => {}
+                ':' if syntax == TypeSyntax::PythonHints => {
+                    text.push(&line[..=position]);
+                    return text.join("\n");
+                }
+                '{' if syntax != TypeSyntax::PythonHints => {
+                    text.push(line[..position].trim_end());
+                    return text.join("\n");
+                }
+                ';' if syntax != TypeSyntax::PythonHints => {
+                    text.push(&line[..=position]);
+                    return text.join("\n");
+                }
+                _ => {}
+            }
+        }
+        text.push(line);
+    }
+    text.join("\n")
+}
+
+/// Signatures of the functions `scope` covers: the first function at any
+/// indentation, or every function at the top level of the file
+fn signatures(syntax: TypeSyntax, scope: InferScope, code: &str) -> Vec<AnnotatedSignature> {
+    let lines: Vec<&str> = code.lines().collect();
+    let mut found = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let Some(caps) = syntax.function_pattern().captures(line) else {
+            continue;
+        };
+        if scope == InferScope::File && !caps[1].is_empty() {
+            continue;
+        }
+        found.push(AnnotatedSignature {
+            name: caps[2].to_string(),
+            line: index + 1,
+            signature: signature_text(syntax, &lines[index..]),
+        });
+        if scope == InferScope::Function {
+            break;
+        }
+    }
+    found
+}
+
+fn infer_prompt(syntax: TypeSyntax, scope: InferScope, language: &str, code: &str) -> String {
+    let target = match scope {
+        InferScope::Function => "the first function in this code",
+        InferScope::File => "every top-level function in this file",
+    };
+    format!(
+        "Add parameter and return type annotations to {target}. {instructions}\n\
+         Change nothing but the signatures: keep names, defaults, bodies and comments exactly as they are.\n\
+         Reply with the complete annotated code in a single ```{language} code block.\n\n{code}",
+        target = target,
+        instructions = syntax.instructions(),
+        language = language,
+        code = code,
+    )
+}
+
+/// Check the reply against the original code and the language grammar
+pub fn validate_annotations(
+    code: &str,
+    language: &str,
+    scope: InferScope,
+    reply: &str,
+) -> Result<InferredTypes, InferTypesError> {
+    let syntax = TypeSyntax::for_language(language)
+        .ok_or_else(|| InferTypesError::UnsupportedLanguage(language.to_string()))?;
+    let mut annotated_code = first_code_block(reply).ok_or(InferTypesError::MissingCode)?.to_string();
+    if code.ends_with('\n') {
+        annotated_code.push('\n');
+    }
+
+    let syntax_checked = match (syntax_error_count(code, language), syntax_error_count(&annotated_code, language)) {
+        (Some(before), Some(after)) => {
+            if after > before {
+                return Err(InferTypesError::InvalidSyntax { errors: after });
+            }
+            true
+        }
+        _ => false,
+    };
+
+    let expected: Vec<String> = signatures(syntax, scope, code).into_iter().map(|signature| signature.name).collect();
+    let signatures = signatures(syntax, scope, &annotated_code);
+    let found: Vec<String> = signatures.iter().map(|signature| signature.name.clone()).collect();
+    if found != expected {
+        return Err(InferTypesError::FunctionsChanged { expected, found });
+    }
+
+    Ok(InferredTypes {
+        language: language.to_string(),
+        syntax,
+        scope,
+        annotated_code,
+        signatures,
+        syntax_checked,
+    })
+}
+
+impl AiEngine {
+    /// Ask the model for the parameter and return types of the functions `scope`
+    /// covers in `code`
+    pub async fn infer_types(&self, code: &str, language: &str, scope: InferScope) -> Result<InferredTypes> {
+        let syntax = TypeSyntax::for_language(language)
+            .ok_or_else(|| InferTypesError::UnsupportedLanguage(language.to_string()))?;
+        if signatures(syntax, scope, code).is_empty() {
+            return Err(InferTypesError::NoFunction.into());
+        }
+
+        let reply = self.run_prompt(&infer_prompt(syntax, scope, language, code), language).await?;
+        Ok(validate_annotations(code, language, scope, &reply)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::tests::{mock_openai_server, openai_config};
+
+    #[tokio::test]
+    async fn test_python_function_gets_type_hints() {
+        let (addr, server) = mock_openai_server(
+            "```python\\ndef scale(values: list[float], factor: float = 2) -> list[float]:\\n    return [v * factor for v in values]\\n```"
+        ).await;
+        let engine = AiEngine::new_async(openai_config(addr)).await.unwrap();
+
+        let code = "def scale(values, factor=2):\n    return [v * factor for v in values]\n";
+        let inferred = engine.infer_types(code, "python", InferScope::Function).await.unwrap();
+        assert_eq!(inferred.syntax, TypeSyntax::PythonHints);
+        assert_eq!(inferred.signatures, [AnnotatedSignature {
+            name: "scale".to_string(),
+            line: 1,
+            signature: "def scale(values: list[float], factor: float = 2) -> list[float]:".to_string(),
+        }]);
+        assert!(inferred.annotated_code.ends_with("for v in values]\n"));
+        assert!(inferred.syntax_checked);
+
+        let request = server.await.unwrap();
+        assert!(request.contains("PEP 484 type hints"));
+        assert!(request.contains("the first function in this code"));
+    }
+
+    #[test]
+    fn test_whole_file_keeps_its_top_level_functions() {
+        let code = "def load(path):\n    def parse(line):\n        return line.split()\n    return [parse(l) for l in open(path)]\n\ndef save(path, rows):\n    pass\n";
+        let reply = "```python\ndef load(path: str) -> list[list[str]]:\n    def parse(line):\n        return line.split()\n    return [parse(l) for l in open(path)]\n\ndef save(\n    path: str,\n    rows: list[list[str]],\n) -> None:\n    pass\n```";
+        let inferred = validate_annotations(code, "python", InferScope::File, reply).unwrap();
+        let names: Vec<_> = inferred.signatures.iter().map(|signature| (signature.name.as_str(), signature.line)).collect();
+        assert_eq!(names, [("load", 1), ("save", 6)]);
+        assert_eq!(inferred.signatures[1].signature, "def save(\n    path: str,\n    rows: list[list[str]],\n) -> None:");
+
+        let dropped = "```python\ndef load(path: str) -> list[list[str]]:\n    return []\n```";
+        assert!(matches!(
+            validate_annotations(code, "python", InferScope::File, dropped),
+            Err(InferTypesError::FunctionsChanged { .. })
+        ));
+        let broken = "```python\ndef load(path: str -> list:\n    return []\n\ndef save(path, rows):\n    pass\n```";
+        assert!(matches!(
+            validate_annotations(code, "python", InferScope::File, broken),
+            Err(InferTypesError::InvalidSyntax { .. })
+        ));
+
+        let rust = "```rust\npub fn area(width: f64, height: f64) -> f64 {\n    width * height\n}\n```";
+        let inferred = validate_annotations("pub fn area(width: f64, height: f64) -> f64 { width * height }", "rust", InferScope::Function, rust).unwrap();
+        assert_eq!(inferred.signatures[0].signature, "pub fn area(width: f64, height: f64) -> f64");
+    }
+}
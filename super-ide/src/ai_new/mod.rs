@@ -62,6 +62,8 @@ impl AiEngine {
             text: "// Completion not yet implemented in refactored version".to_string(),
             confidence: 0.0,
             suggestions: vec![],
+            served_by: None,
+            fallback: false,
         })
     }
 
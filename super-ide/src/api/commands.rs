@@ -0,0 +1,334 @@
+//! Command palette
+//!
+//! The palette lists every command in `CommandCatalog` and runs them through
+//! `invoke`, so the frontend needs one endpoint to discover actions and one to run
+//! them instead of knowing each feature's route. Parameters are described with JSON
+//! Schema and checked against the command's argument type when it is invoked.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::core::IdeError;
+use crate::editor::location::FileLocation;
+use crate::git::GitError;
+use crate::ui::AppState;
+
+/// A command the palette can show and invoke
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandDescriptor {
+    pub id: String,
+    pub title: String,
+    pub category: String,
+    /// Default key binding, e.g. `Ctrl+Shift+F`
+    pub shortcut: Option<String>,
+    /// JSON Schema of the arguments object
+    pub parameters: Value,
+}
+
+/// Command invocation errors
+#[derive(Debug, thiserror::Error)]
+pub enum CommandError {
+    #[error("Unknown command: {0}")]
+    UnknownCommand(String),
+
+    #[error("Invalid arguments for {command}: {message}")]
+    InvalidArguments { command: String, message: String },
+
+    #[error("No active document")]
+    NoActiveDocument,
+
+    #[error(transparent)]
+    Failed(#[from] IdeError),
+}
+
+/// Commands offered by the palette
+#[derive(Debug, Clone)]
+pub struct CommandCatalog {
+    commands: Vec<CommandDescriptor>,
+}
+
+impl Default for CommandCatalog {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+fn command(id: &str, title: &str, category: &str, shortcut: Option<&str>, parameters: Value) -> CommandDescriptor {
+    CommandDescriptor {
+        id: id.to_string(),
+        title: title.to_string(),
+        category: category.to_string(),
+        shortcut: shortcut.map(str::to_string),
+        parameters,
+    }
+}
+
+fn no_parameters() -> Value {
+    json!({ "type": "object", "properties": {} })
+}
+
+impl CommandCatalog {
+    /// The commands built into the IDE
+    pub fn builtin() -> Self {
+        let commands = vec![
+            command("file.open", "Open File", "File", Some("Ctrl+P"), json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Path relative to the workspace" },
+                    "line": { "type": "integer", "minimum": 1, "description": "1-based line to put the cursor on" },
+                    "column": { "type": "integer", "minimum": 1, "description": "1-based column, with `line`" }
+                },
+                "required": ["path"]
+            })),
+            command("file.save", "Save File", "File", Some("Ctrl+S"), no_parameters()),
+            command("editor.format_document", "Format Document", "Editor", Some("Shift+Alt+F"), no_parameters()),
+            command("editor.toggle_comment", "Toggle Line Comment", "Editor", Some("Ctrl+/"), json!({
+                "type": "object",
+                "properties": {
+                    "document_id": { "type": "string", "description": "Defaults to the active document" },
+                    "start_line": { "type": "integer", "minimum": 0 },
+                    "end_line": { "type": "integer", "minimum": 0 }
+                },
+                "required": ["start_line", "end_line"]
+            })),
+            command("task.run", "Run Task", "Tasks", None, json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" },
+                    "command": { "type": "string" },
+                    "cwd": { "type": "string", "description": "Directory relative to the workspace" }
+                },
+                "required": ["name", "command"]
+            })),
+            command("git.commit", "Git: Commit", "Git", Some("Ctrl+Enter"), json!({
+                "type": "object",
+                "properties": {
+                    "message": { "type": "string" },
+                    "files": { "type": "array", "items": { "type": "string" }, "description": "Files to stage first" },
+                    "run_hooks": { "type": "boolean", "description": "Run pre-commit and commit-msg hooks; defaults to true" },
+                    "sign": { "type": "boolean", "description": "Sign the commit; defaults to the git.sign_commits setting" },
+                    "allow_secrets": { "type": "boolean", "description": "Commit even when the staged changes hold likely secrets" }
+                },
+                "required": ["message"]
+            })),
+            command("ai.explain", "AI: Explain Code", "AI", Some("Ctrl+Shift+E"), json!({
+                "type": "object",
+                "properties": {
+                    "code": { "type": "string", "description": "Defaults to the active document" },
+                    "language": { "type": "string" },
+                    "level": { "type": "string", "enum": ["basic", "detailed", "expert"] },
+                    "no_cache": { "type": "boolean", "description": "Ask the provider even if a cached explanation exists" }
+                }
+            })),
+        ];
+        Self { commands }
+    }
+
+    pub fn commands(&self) -> &[CommandDescriptor] {
+        &self.commands
+    }
+
+    pub fn get(&self, id: &str) -> Option<&CommandDescriptor> {
+        self.commands.iter().find(|command| command.id == id)
+    }
+}
+
+#[derive(Deserialize)]
+struct OpenFileArgs {
+    path: String,
+    line: Option<usize>,
+    column: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct ToggleCommentArgs {
+    document_id: Option<String>,
+    start_line: usize,
+    end_line: usize,
+}
+
+#[derive(Deserialize)]
+struct RunTaskArgs {
+    name: String,
+    command: String,
+    cwd: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CommitArgs {
+    message: String,
+    #[serde(default)]
+    files: Vec<String>,
+    run_hooks: Option<bool>,
+    sign: Option<bool>,
+    #[serde(default)]
+    allow_secrets: bool,
+}
+
+#[derive(Deserialize)]
+struct ExplainArgs {
+    code: Option<String>,
+    language: Option<String>,
+    level: Option<String>,
+    no_cache: Option<bool>,
+}
+
+fn parse_args<T: DeserializeOwned>(id: &str, args: Value) -> Result<T, CommandError> {
+    // Commands without parameters may be invoked without a body
+    let args = if args.is_null() { json!({}) } else { args };
+    serde_json::from_value(args).map_err(|e| CommandError::InvalidArguments {
+        command: id.to_string(),
+        message: e.to_string(),
+    })
+}
+
+/// Run the command `id` with `args` and return its result
+pub async fn invoke(state: &AppState, id: &str, args: Value) -> Result<Value, CommandError> {
+    let ide = &state.ide;
+    let result = match id {
+        "file.open" => {
+            let args: OpenFileArgs = parse_args(id, args)?;
+            let workspace = ide.config().read().await.workspace_dir();
+            let path = crate::file_ops::FileManager::new(workspace).resolve(std::path::Path::new(&args.path)).map_err(IdeError::from)?;
+            let cursor = FileLocation { path: path.clone(), line: args.line, column: args.column }.cursor();
+            json!({ "document_id": ide.open_file_at(path, cursor).await? })
+        }
+        "file.save" => {
+            ide.save_active_file().await?;
+            Value::Null
+        }
+        "editor.format_document" => {
+            let editor = ide.editor().lock().await;
+            let result = editor.format_document().await.map_err(IdeError::from)?;
+            json!(result)
+        }
+        "editor.toggle_comment" => {
+            let args: ToggleCommentArgs = parse_args(id, args)?;
+            let editor = ide.editor().lock().await;
+            let document_id = match args.document_id {
+                Some(document_id) => document_id,
+                None => editor.get_active_document().await.ok_or(CommandError::NoActiveDocument)?.read().await.id.clone(),
+            };
+            let commented = editor.toggle_comment(&document_id, args.start_line, args.end_line).await
+                .map_err(IdeError::from)?;
+            json!({ "document_id": document_id, "commented": commented })
+        }
+        "task.run" => {
+            let args: RunTaskArgs = parse_args(id, args)?;
+            ide.workspace_trust().ensure_trusted()?;
+            let workspace = ide.config().read().await.workspace_dir();
+            let cwd = match args.cwd {
+                Some(cwd) => crate::file_ops::FileManager::new(workspace).resolve(std::path::Path::new(&cwd)).map_err(IdeError::from)?,
+                None => workspace,
+            };
+            json!({ "task_id": ide.task_runner().start(&args.name, &args.command, cwd).await? })
+        }
+        "git.commit" => {
+            let args: CommitArgs = parse_args(id, args)?;
+            let git_manager = &state.git_manager;
+            if !git_manager.is_repository().await {
+                let error = if git_manager.is_git_missing() {
+                    GitError::GitNotInstalled
+                } else {
+                    GitError::RepositoryNotFound(git_manager.repository_path().to_path_buf())
+                };
+                return Err(IdeError::from(error).into());
+            }
+            let (sign, scan_for_secrets) = {
+                let config = ide.config().read().await;
+                (args.sign.unwrap_or(config.git.sign_commits), config.security.scan_for_secrets && !args.allow_secrets)
+            };
+            git_manager.stage_files_optimized(&args.files).await.map_err(IdeError::from)?;
+            if scan_for_secrets {
+                git_manager.ensure_no_staged_secrets().await.map_err(IdeError::from)?;
+            }
+            let commit = git_manager.commit(&args.message, args.run_hooks.unwrap_or(true), sign).await.map_err(IdeError::from)?;
+            json!({ "commit": commit })
+        }
+        "ai.explain" => {
+            let args: ExplainArgs = parse_args(id, args)?;
+            let (code, language) = match args.code {
+                Some(code) => (code, args.language.unwrap_or_else(|| "plaintext".to_string())),
+                None => {
+                    let editor = ide.editor().lock().await;
+                    let active = editor.get_active_document().await.ok_or(CommandError::NoActiveDocument)?;
+                    let doc = active.read().await;
+                    let code = doc.content.read().await.to_string();
+                    (code, args.language.unwrap_or_else(|| doc.language.to_lowercase()))
+                }
+            };
+            let request = crate::ai::CodeExplanationRequest {
+                code,
+                language,
+                context: None,
+                explanation_level: match args.level.as_deref() {
+                    Some("basic") => crate::ai::ExplanationLevel::Basic,
+                    Some("expert") => crate::ai::ExplanationLevel::Expert,
+                    _ => crate::ai::ExplanationLevel::Detailed,
+                },
+                no_cache: args.no_cache.unwrap_or(false),
+            };
+            json!(ide.ai_engine().explain_code(request).await.map_err(IdeError::Ai)?)
+        }
+        _ => return Err(CommandError::UnknownCommand(id.to_string())),
+    };
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    async fn app_state(workspace: &std::path::Path) -> AppState {
+        let mut config = crate::config::Configuration::default();
+        config.ide.workspace_path = workspace.to_string_lossy().to_string();
+        let ide = Arc::new(crate::core::SuperIDE::new(config).await.unwrap());
+        AppState {
+            event_bus: ide.event_bus().clone(),
+            file_manager: Arc::new(RwLock::new(crate::utils::file_manager::FileManager::default())),
+            git_manager: Arc::new(crate::git::GitManager::new(workspace.to_path_buf())),
+            event_sender: tokio::sync::broadcast::channel(16).0,
+            ide,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_format_document_command_formats_active_document() {
+        let root = std::env::temp_dir().join(format!("super-ide-commands-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("notes.txt"), "first line   \nsecond line\t\n").unwrap();
+        let state = app_state(&root).await;
+
+        let opened = invoke(&state, "file.open", json!({ "path": "notes.txt" })).await.unwrap();
+        assert!(opened["document_id"].is_string());
+
+        let result = invoke(&state, "editor.format_document", Value::Null).await.unwrap();
+        assert_eq!(result["changed"], json!(true));
+        let editor = state.ide.editor().lock().await;
+        let active = editor.get_active_document().await.unwrap();
+        let doc = active.read().await;
+        assert_eq!(doc.content.read().await.to_string(), "first line\nsecond line\n");
+        assert!(doc.is_modified);
+        drop(doc);
+        drop(editor);
+
+        assert!(matches!(invoke(&state, "editor.explode", Value::Null).await, Err(CommandError::UnknownCommand(_))));
+        assert!(matches!(
+            invoke(&state, "file.open", json!({ "file": "notes.txt" })).await,
+            Err(CommandError::InvalidArguments { .. })
+        ));
+        for outside in ["../notes.txt", "/etc/passwd"] {
+            assert!(matches!(
+                invoke(&state, "file.open", json!({ "path": outside })).await,
+                Err(CommandError::Failed(IdeError::File(crate::file_ops::FileOperationError::InvalidPath(_))))
+            ));
+        }
+        let catalog = CommandCatalog::builtin();
+        assert!(catalog.get("editor.format_document").is_some());
+        assert!(catalog.commands().iter().all(|command| command.parameters["type"] == "object"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,111 @@
+//! Request body limits
+//!
+//! Each group of routes caps the bodies it accepts, so no client can make the
+//! server buffer an arbitrarily large payload. File routes allow the most, as
+//! saves and uploads carry whole files, and AI routes the least. A body over the
+//! cap is refused with 413 and a JSON error naming the limit, both when its
+//! declared length is too large and when it turns out longer while being read.
+
+use axum::extract::DefaultBodyLimit;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use tower_http::limit::RequestBodyLimitLayer;
+
+use super::ApiResponse;
+use crate::config::ApiSettings;
+
+/// Body caps in bytes, by group of routes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodyLimits {
+    pub default: usize,
+    pub files: usize,
+    pub ai: usize,
+}
+
+impl BodyLimits {
+    pub fn new(settings: &ApiSettings) -> Self {
+        Self {
+            default: settings.max_body_bytes,
+            files: settings.max_file_body_bytes,
+            ai: settings.max_ai_body_bytes,
+        }
+    }
+}
+
+impl Default for BodyLimits {
+    fn default() -> Self {
+        Self::new(&ApiSettings::default())
+    }
+}
+
+/// Cap the bodies of requests to `router`'s routes at `limit` bytes
+pub fn limit_body<S>(router: Router<S>, limit: usize) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router
+        // The cap takes the place of the 2 MB axum applies to body extractors
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(limit))
+        .layer(axum::middleware::map_response(move |response: Response| async move {
+            explain_payload_too_large(response, limit)
+        }))
+}
+
+/// Turn the plain-text 413 of the limit layer, or of an extractor that ran into
+/// it, into an API error. Handlers' own 413s are already JSON and pass through.
+fn explain_payload_too_large(response: Response, limit: usize) -> Response {
+    let is_json = response.headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|content_type| content_type.as_bytes().starts_with(b"application/json"));
+    if response.status() != StatusCode::PAYLOAD_TOO_LARGE || is_json {
+        return response;
+    }
+    let mut error = ApiResponse::<()>::error(format!("Request body is larger than the {} byte limit for this endpoint", limit));
+    error.error_code = Some("payload_too_large".to_string());
+    error.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::extract::Request;
+    use axum::routing::post;
+    use axum::Json;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_body_over_the_limit_gets_413() {
+        let app = limit_body(
+            Router::new().route("/analyze", post(|Json(body): Json<serde_json::Value>| async move { Json(body) })),
+            64,
+        );
+        let small = serde_json::json!({ "code": "fn main() {}" }).to_string();
+        let large = serde_json::json!({ "code": "x".repeat(100) }).to_string();
+
+        let response = app.clone()
+            .oneshot(Request::post("/analyze").header("content-type", "application/json").body(Body::from(small)).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let declared = Request::post("/analyze")
+            .header("content-type", "application/json")
+            .header("content-length", large.len())
+            .body(Body::from(large.clone()))
+            .unwrap();
+        let undeclared = Request::post("/analyze")
+            .header("content-type", "application/json")
+            .body(Body::from_stream(futures::stream::iter([Ok::<_, std::io::Error>(large)])))
+            .unwrap();
+        for request in [declared, undeclared] {
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+            let body: ApiResponse<()> = serde_json::from_slice(&to_bytes(response.into_body(), 4096).await.unwrap()).unwrap();
+            assert_eq!(body.error_code.as_deref(), Some("payload_too_large"));
+            assert!(body.error.unwrap().contains("64 byte limit"));
+        }
+    }
+}
@@ -0,0 +1,244 @@
+//! Request logging
+//!
+//! Every API call gets a correlation id, taken from the client's `x-request-id`
+//! header when it sent a usable one, and echoed back in the response. With request
+//! logging turned on, each call is logged at debug level with its method, path,
+//! status and latency, plus a preview of small request bodies. Anything that looks
+//! like a credential is masked before the line is built: authorization headers,
+//! JSON fields such as `api_key` or `password`, and key-shaped strings inside
+//! other values, like a `.env` file being saved.
+
+use axum::body::{to_bytes, Body};
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, HeaderValue, Method};
+use axum::middleware::Next;
+use axum::response::Response;
+use serde_json::Value;
+use std::time::{Duration, Instant};
+
+use crate::utils::secrets::{self, REDACTED};
+
+/// Header carrying the correlation id
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Bodies larger than this are not buffered for a preview
+const MAX_PREVIEW_BODY_BYTES: usize = 16 * 1024;
+
+/// Characters of the redacted body kept in the log line
+const PREVIEW_CHARS: usize = 512;
+
+/// Headers whose values are never logged
+const SENSITIVE_HEADERS: &[&str] = &["authorization", "proxy-authorization", "cookie", "set-cookie", "x-api-key"];
+
+/// JSON field names, lowercased without `_` or `-`, whose values are never logged
+const SENSITIVE_FIELDS: &[&str] = &["apikey", "password", "secret", "token", "authorization", "privatekey", "credentials"];
+
+/// Whether calls are logged; the correlation id is added either way
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestLogging {
+    pub enabled: bool,
+}
+
+/// Middleware that tags each call with a correlation id and, when enabled, logs it
+pub async fn log_requests(State(logging): State<RequestLogging>, request: Request, next: Next) -> Response {
+    let request_id = request_id(request.headers());
+    let started = Instant::now();
+
+    let (request, summary) = if logging.enabled && log::log_enabled!(log::Level::Debug) {
+        let method = request.method().clone();
+        let path = request.uri().path().to_string();
+        let headers = redact_headers(request.headers());
+        let (request, body) = body_preview(request).await;
+        (request, Some((method, path, headers, body)))
+    } else {
+        (request, None)
+    };
+
+    let mut response = next.run(request).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+
+    if let Some((method, path, headers, body)) = summary {
+        let line = RequestLogLine {
+            request_id: &request_id,
+            method: &method,
+            path: &path,
+            status: response.status().as_u16(),
+            latency: started.elapsed(),
+            body: body.as_deref(),
+        };
+        log::debug!("{}", line);
+        log::trace!("[{}] request headers: {}", request_id, headers);
+    }
+    response
+}
+
+/// The client's correlation id when it is short and printable, otherwise a new one
+fn request_id(headers: &HeaderMap) -> String {
+    headers.get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= 128 && id.chars().all(|c| c.is_ascii_graphic()))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+/// Buffer a small body to preview it, handing the request on with the same bytes
+async fn body_preview(request: Request) -> (Request, Option<String>) {
+    let small = request.headers().get(axum::http::header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|length| length.parse::<usize>().ok())
+        .is_some_and(|length| length > 0 && length <= MAX_PREVIEW_BODY_BYTES);
+    if !small {
+        return (request, None);
+    }
+
+    let (parts, body) = request.into_parts();
+    match to_bytes(body, MAX_PREVIEW_BODY_BYTES).await {
+        Ok(bytes) => {
+            let preview = redact_body(&bytes);
+            (Request::from_parts(parts, Body::from(bytes)), Some(preview))
+        }
+        // The body could not be read, so the handler would not have got it either
+        Err(_) => (Request::from_parts(parts, Body::empty()), None),
+    }
+}
+
+/// One logged call
+struct RequestLogLine<'a> {
+    request_id: &'a str,
+    method: &'a Method,
+    path: &'a str,
+    status: u16,
+    latency: Duration,
+    body: Option<&'a str>,
+}
+
+impl std::fmt::Display for RequestLogLine<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}] {} {} -> {} in {:.1}ms",
+            self.request_id,
+            self.method,
+            self.path,
+            self.status,
+            self.latency.as_secs_f64() * 1000.0
+        )?;
+        if let Some(body) = self.body {
+            write!(f, " body={}", body)?;
+        }
+        Ok(())
+    }
+}
+
+/// Headers as `name: value` pairs with credentials masked
+fn redact_headers(headers: &HeaderMap) -> String {
+    headers.iter()
+        .map(|(name, value)| {
+            let value = if SENSITIVE_HEADERS.contains(&name.as_str()) {
+                REDACTED.to_string()
+            } else {
+                secrets::redact(value.to_str().unwrap_or("<binary>"))
+            };
+            format!("{}: {}", name, value)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A request body with credentials masked, cut to `PREVIEW_CHARS`
+fn redact_body(bytes: &[u8]) -> String {
+    let redacted = match serde_json::from_slice::<Value>(bytes) {
+        Ok(mut json) => {
+            redact_json(&mut json);
+            json.to_string()
+        }
+        Err(_) => match std::str::from_utf8(bytes) {
+            Ok(text) => secrets::redact(text),
+            Err(_) => return format!("<{} bytes>", bytes.len()),
+        },
+    };
+
+    match redacted.char_indices().nth(PREVIEW_CHARS) {
+        Some((end, _)) => format!("{}...", &redacted[..end]),
+        None => redacted,
+    }
+}
+
+fn is_sensitive_field(name: &str) -> bool {
+    let normalized: String = name.chars().filter(|c| *c != '_' && *c != '-').collect::<String>().to_lowercase();
+    SENSITIVE_FIELDS.iter().any(|field| normalized.ends_with(field))
+}
+
+fn redact_json(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields.iter_mut() {
+                if is_sensitive_field(name) && !field.is_null() {
+                    *field = Value::String(REDACTED.to_string());
+                } else {
+                    redact_json(field);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_json),
+        Value::String(text) => *text = secrets::redact(text),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::post;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_api_key_in_request_body_is_not_logged() {
+        let api_key = "sk-proj-abcdefghijklmnopqrstuvwx";
+        let body = serde_json::json!({
+            "ai": { "provider": "openai", "api_key": api_key, "max_tokens": 512 },
+            "content": format!("OPENAI_API_KEY={}\nDEBUG=true\n", api_key),
+        });
+        let preview = redact_body(body.to_string().as_bytes());
+        let line = RequestLogLine {
+            request_id: "req-1",
+            method: &Method::PUT,
+            path: "/api/project/config",
+            status: 200,
+            latency: Duration::from_micros(2500),
+            body: Some(&preview),
+        }.to_string();
+
+        assert!(!line.contains(api_key), "{}", line);
+        assert!(line.starts_with("[req-1] PUT /api/project/config -> 200 in 2.5ms body="));
+        assert!(line.contains(r#""max_tokens":512"#) && line.contains("DEBUG=true"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", HeaderValue::from_str(&format!("Bearer {}", api_key)).unwrap());
+        headers.insert("content-type", HeaderValue::from_static("application/json"));
+        assert_eq!(redact_headers(&headers), "authorization: [REDACTED], content-type: application/json");
+    }
+
+    #[tokio::test]
+    async fn test_correlation_id_is_echoed_and_body_passed_on() {
+        let app = Router::new()
+            .route("/echo", post(|body: String| async move { body }))
+            .layer(axum::middleware::from_fn_with_state(RequestLogging { enabled: true }, log_requests));
+
+        let request = Request::post("/echo")
+            .header(REQUEST_ID_HEADER, "client-42")
+            .header("content-length", "5")
+            .body(Body::from("hello"))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "client-42");
+        assert_eq!(to_bytes(response.into_body(), 64).await.unwrap(), "hello");
+
+        let response = app.oneshot(Request::post("/echo").body(Body::empty()).unwrap()).await.unwrap();
+        let generated = response.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+        assert!(uuid::Uuid::parse_str(generated).is_ok());
+    }
+}
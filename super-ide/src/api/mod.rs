@@ -7,9 +7,11 @@
 //! - Project management (/api/project/*)
 
 use axum::{
-    extract::{Path, State, Query},
+    extract::{Multipart, Path, State, Query, WebSocketUpgrade},
+    extract::ws::{Message, WebSocket},
     http::StatusCode,
     response::{IntoResponse, Json},
+    response::sse::{Event, KeepAlive, Sse},
     routing::{get, post, put, delete},
     Router,
 };
@@ -22,11 +24,25 @@ use chrono::Utc;
 use base64::Engine;
 
 use crate::utils::event_bus::EventBus;
+use crate::utils::api_tester::{ApiTestError, CollectionStore, HttpRequestSpec, RequestCollection};
+use crate::core::IdeError;
+use crate::core::state::PersistedState;
 use crate::git::{GitManager, GitRepository, GitStatus, GitCommit, GitError};
+use crate::file_ops::tree_watch::{next_tree_update, TreeUpdateBatcher};
+use crate::file_ops::scaffold::{ScaffoldError, ScaffoldPlan};
+use crate::file_ops::templates::{TemplateError, TemplateRegistry};
 use crate::file_ops::{FileManager, FileInfo, ProjectStructure, FileOperationResult, FileOperationError, FileChangeEvent, FileChangeType};
+use crate::ai::chat::{ChatMessage, ChatRole};
 use crate::ai::{AiEngine, AnalysisResult, BugPrediction, SecurityVulnerability, CodeExplanation, DebugSession};
+use crate::diagnostics::DiagnosticSource;
 use crate::collaboration::{CollaborationManager, CollaborationUser, Operation, UserPresence, CollaborationEvent};
 
+pub mod commands;
+pub mod limits;
+pub mod logging;
+
+use commands::{CommandCatalog, CommandError};
+
 // API State
 #[derive(Clone)]
 pub struct ApiState {
@@ -42,6 +58,8 @@ pub struct ApiResponse<T> {
     pub success: bool,
     pub data: Option<T>,
     pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
     pub timestamp: String,
 }
 
@@ -58,13 +76,223 @@ pub struct FileCreateRequest {
     pub is_directory: bool,
 }
 
+/// AI tool listing query
 #[derive(Debug, Serialize, Deserialize)]
-pub struct AIChatRequest {
+pub struct AiToolQuery {
+    pub language: Option<String>,
+}
+
+/// Symbol search query
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SymbolQuery {
+    pub query: String,
+    pub limit: Option<usize>,
+}
+
+/// Request to convert a document's indentation; unset fields come from
+/// `.editorconfig` or the editor settings
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConvertIndentRequest {
+    pub document_id: String,
+    /// `space` or `tab`
+    pub indent_style: Option<crate::editor::editorconfig::IndentStyle>,
+    /// Columns per indentation level
+    pub indent_size: Option<usize>,
+}
+
+/// Selection reported by the frontend, in 0-based lines and columns
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SelectionRange {
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    #[serde(default)]
+    pub rectangular: bool,
+}
+
+/// Request to record a document's selection; no selection clears it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetSelectionRequest {
+    pub document_id: String,
+    pub selection: Option<SelectionRange>,
+}
+
+/// Request to reload a document from its file
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevertDocumentRequest {
+    pub document_id: String,
+    /// Discard unsaved edits; without it a modified document is not reverted
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+/// Request to switch to an open document
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ActivateDocumentRequest {
+    pub document_id: String,
+}
+
+/// Request to place a document's cursors; no cursors leaves only the main one
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetCursorsRequest {
+    pub document_id: String,
+    pub cursors: Vec<crate::editor::CursorPosition>,
+}
+
+/// Text to insert at every cursor of a document
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CursorInsertRequest {
+    pub document_id: String,
+    pub text: String,
+}
+
+/// Characters to delete before every cursor of a document
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CursorDeleteRequest {
+    pub document_id: String,
+    #[serde(default = "default_cursor_delete_count")]
+    pub count: usize,
+}
+
+fn default_cursor_delete_count() -> usize {
+    1
+}
+
+/// Options for an AI action on the active selection; each action reads the ones it needs
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SelectionActionRequest {
+    /// Explanation level: `basic`, `detailed` or `expert`
+    pub level: Option<String>,
+    pub test_framework: Option<String>,
+    /// Language to translate into
+    pub to_language: Option<String>,
+    #[serde(default)]
+    pub no_cache: bool,
+}
+
+/// Semantic code search query
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SemanticSearchQuery {
+    pub query: String,
+    /// Chunks to return, 10 by default
+    pub limit: Option<usize>,
+}
+
+/// Symbol definition query
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SymbolDefinitionQuery {
+    pub name: String,
+}
+
+/// Project-wide rename; previews unless confirmed
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RenameSymbolRequest {
+    pub old_name: String,
+    pub new_name: String,
+    /// Write the rename; needs the `fingerprint` of the previewed plan
+    #[serde(default)]
+    pub confirm: bool,
+    pub fingerprint: Option<String>,
+}
+
+/// Stage, commit and push request
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitCommitFlowRequest {
+    pub files: Vec<String>,
     pub message: String,
+    /// Run the repository's commit hooks; `false` commits with `--no-verify`
+    #[serde(default = "default_run_hooks")]
+    pub run_hooks: bool,
+    /// Sign the commit; defaults to the `git.sign_commits` setting
+    pub sign: Option<bool>,
+    /// Commit even when the secret scan finds likely credentials in the staged changes
+    #[serde(default)]
+    pub allow_secrets: bool,
+    #[serde(default)]
+    pub push: bool,
+    pub remote: Option<String>,
+    pub branch: Option<String>,
+}
+
+fn default_run_hooks() -> bool {
+    true
+}
+
+/// Load file query
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct LoadFileQuery {
+    /// Load the file even if it exceeds the configured size limit
+    #[serde(default)]
+    pub open_anyway: bool,
+}
+
+/// Where uploaded files go
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UploadQuery {
+    /// Directory under the workspace; the workspace root when unset
+    #[serde(default)]
+    pub dir: Option<String>,
+}
+
+/// A file written by an upload
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UploadedFile {
+    pub path: String,
+    pub bytes: u64,
+}
+
+/// File history query
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileHistoryQuery {
+    pub path: String,
+}
+
+/// Restore a file from its local history
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RestoreHistoryRequest {
+    pub path: String,
+    /// `id` of one of the file's history entries
+    pub id: String,
+}
+
+/// Restore a deleted file or directory from the trash
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RestoreTrashRequest {
+    pub id: String,
+}
+
+/// AI chat request, either a whole conversation or a single `message`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AIChatRequest {
+    /// One user message; kept for clients that predate `messages`
+    #[serde(default)]
+    pub message: Option<String>,
+    /// Conversation so far, oldest first
+    #[serde(default)]
+    pub messages: Vec<ChatMessage>,
     pub context: Option<AIContext>,
     pub settings: Option<AISettings>,
 }
 
+impl AIChatRequest {
+    /// Messages to send, with a lone `message` appended as a user turn
+    pub fn conversation(&self) -> Vec<ChatMessage> {
+        let mut messages = self.messages.clone();
+        if let Some(message) = self.message.as_ref().filter(|message| !message.is_empty()) {
+            messages.push(ChatMessage::user(message.clone()));
+        }
+        messages
+    }
+
+    /// The latest user turn; a conversation may end with the assistant's reply
+    pub fn last_user_message(&self) -> Option<String> {
+        self.conversation().into_iter().rev()
+            .find(|message| message.role == ChatRole::User)
+            .map(|message| message.content)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AIContext {
     pub file_path: Option<String>,
@@ -73,22 +301,88 @@ pub struct AIContext {
     pub cursor_position: Option<(usize, usize)>,
 }
 
+impl AIContext {
+    /// The open file as a note for the model; `None` without file content
+    fn describe(&self) -> Option<String> {
+        let content = self.file_content.as_ref().filter(|content| !content.trim().is_empty())?;
+        let mut description = match &self.file_path {
+            Some(path) => format!("The user has {} open:\n", path),
+            None => "The user's open file:\n".to_string(),
+        };
+        description.push_str(&format!("```{}\n{}\n```", self.language.as_deref().unwrap_or(""), content));
+        if let Some((line, column)) = self.cursor_position {
+            description.push_str(&format!("\nThe cursor is at line {}, column {}.", line, column));
+        }
+        Some(description)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AISettings {
     pub provider: Option<String>,
     pub temperature: Option<f32>,
     pub max_tokens: Option<u32>,
     pub model: Option<String>,
+    /// OpenAI-compatible server to use for this request only
+    pub base_url: Option<String>,
+    /// Key for `base_url`; without it the request is sent unauthenticated
+    #[serde(skip_serializing)]
+    pub api_key: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CodeCompletionRequest {
     pub code: String,
+    /// Cursor, as a character offset into `code`
     pub position: usize,
     pub language: String,
     pub file_path: Option<String>,
 }
 
+/// Code analysis request
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CodeAnalysisRequest {
+    pub code: String,
+    pub language: String,
+    pub file_path: Option<String>,
+    /// Leave findings less severe than this out of the response
+    #[serde(default)]
+    pub min_severity: Option<crate::ai::IssueSeverity>,
+}
+
+/// Files analyzed in one batch request
+const MAX_BATCH_ANALYSIS_FILES: usize = 100;
+
+/// Batch analysis request
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchAnalysisRequest {
+    pub files: Vec<crate::ai::batch::BatchFile>,
+}
+
+/// Lines of a file whose history to explain, 1-based and inclusive
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExplainHistoryRequest {
+    /// Relative to the repository root
+    pub path: String,
+    pub start_line: u32,
+    pub end_line: u32,
+}
+
+/// Commits to write a changelog for: those reachable from `to` but not `from`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangelogRequest {
+    pub from: String,
+    #[serde(default = "default_changelog_to")]
+    pub to: String,
+    /// Show the model each commit's diff as well as its message
+    #[serde(default)]
+    pub include_diffs: bool,
+}
+
+fn default_changelog_to() -> String {
+    "HEAD".to_string()
+}
+
 /// Advanced code analysis request
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AdvancedAnalysisRequest {
@@ -98,6 +392,10 @@ pub struct AdvancedAnalysisRequest {
     pub include_bug_prediction: Option<bool>,
     pub include_security_analysis: Option<bool>,
     pub include_performance_analysis: Option<bool>,
+    /// Leave findings less severe than this out of the response; diagnostics
+    /// still get all of them
+    #[serde(default)]
+    pub min_severity: Option<crate::ai::IssueSeverity>,
 }
 
 /// Bug prediction request
@@ -105,6 +403,9 @@ pub struct AdvancedAnalysisRequest {
 pub struct BugPredictionRequest {
     pub code: String,
     pub language: String,
+    /// Leave predictions less severe than this out of the response
+    #[serde(default)]
+    pub min_severity: Option<crate::ai::IssueSeverity>,
 }
 
 /// Security analysis request
@@ -112,6 +413,9 @@ pub struct BugPredictionRequest {
 pub struct SecurityAnalysisRequest {
     pub code: String,
     pub language: String,
+    /// Leave vulnerabilities less severe than this out of the response
+    #[serde(default)]
+    pub min_severity: Option<crate::ai::IssueSeverity>,
 }
 
 /// Code explanation request
@@ -121,6 +425,22 @@ pub struct CodeExplanationRequest {
     pub language: String,
     pub context: Option<String>,
     pub explanation_level: String,
+    /// Skip the explanation cache
+    #[serde(default)]
+    pub no_cache: bool,
+}
+
+/// Deepest directory level a project overview scans
+const MAX_OVERVIEW_DEPTH: u32 = 16;
+
+/// Most key files a project overview reads
+const MAX_OVERVIEW_FILES: usize = 50;
+
+/// Project overview request; limits above the server's maximums are lowered to them
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProjectOverviewRequest {
+    pub max_files: Option<usize>,
+    pub max_depth: Option<u32>,
 }
 
 /// Unit test generation request
@@ -131,6 +451,50 @@ pub struct TestGenerationRequest {
     pub test_framework: Option<String>,
 }
 
+/// Doc comment generation request
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DocGenerationRequest {
+    pub code: String,
+    pub language: String,
+}
+
+/// Diagnostic fix request
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FixDiagnosticRequest {
+    pub code: String,
+    pub language: String,
+    pub issue: crate::ai::CodeIssue,
+}
+
+/// Type inference request
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InferTypesRequest {
+    pub code: String,
+    pub language: String,
+    /// The first function only, or every top-level function of the file
+    #[serde(default)]
+    pub scope: crate::ai::typehints::InferScope,
+}
+
+/// Request to apply one suggestion to a file
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SuggestionPatchRequest {
+    pub code: String,
+    pub language: String,
+    pub suggestion: String,
+    /// Names the file in the diff headers
+    pub file_path: Option<String>,
+}
+
+/// Request for a regex built from a description
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BuildRegexRequest {
+    pub description: String,
+    /// Inputs the pattern must handle as described
+    #[serde(default)]
+    pub samples: Vec<crate::ai::regex_builder::RegexSample>,
+}
+
 /// Code improvement request
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CodeImprovementRequest {
@@ -189,6 +553,13 @@ pub struct ApplyOperationRequest {
     pub operation: Operation,
 }
 
+/// Collaboration WebSocket query
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CollaborationSocketQuery {
+    /// Participant the connection is for, who must have joined the session
+    pub user_id: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdatePresenceRequest {
     pub session_id: String,
@@ -228,6 +599,33 @@ pub struct SmartSearchRequest {
     pub search_in_names: Option<bool>,
 }
 
+/// Workspace grep request
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GrepRequest {
+    pub pattern: String,
+    #[serde(flatten)]
+    pub options: crate::file_ops::grep::GrepOptions,
+}
+
+/// Message from a `/search/ws` client: a search to start, or cancelling the running one
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum SearchStreamRequest {
+    Search(GrepRequest),
+    Cancel,
+}
+
+/// Message streamed to a `/search/ws` client
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum SearchStreamEvent {
+    /// Matches in one file, sent as soon as the file was searched
+    Result(crate::file_ops::grep::GrepFileResult),
+    /// Sent once when the walk ends, finished or cancelled
+    Done(crate::file_ops::grep::GrepSummary),
+    Error { message: String },
+}
+
 /// API test request
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ApiTestRequest {
@@ -238,6 +636,119 @@ pub struct ApiTestRequest {
     pub timeout: Option<u64>,
 }
 
+/// Variables for one run of a saved request, overriding the collection's
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RunSavedRequest {
+    #[serde(default)]
+    pub variables: std::collections::HashMap<String, String>,
+}
+
+/// Project task request
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunTaskRequest {
+    pub name: String,
+    pub command: String,
+    /// Working directory relative to the workspace
+    pub cwd: Option<String>,
+}
+
+/// Code snippet run request
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunSnippetRequest {
+    /// `rust` or `python`
+    pub language: String,
+    pub code: String,
+}
+
+/// Terminal scrollback query
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScrollbackQuery {
+    pub lines: Option<usize>,
+}
+
+/// File at revision query
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitFileAtRevisionQuery {
+    pub rev: String,
+    pub path: String,
+}
+
+/// Two files to compare, each from the working tree or at a revision
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DiffRequest {
+    pub old: crate::git::DiffSide,
+    pub new: crate::git::DiffSide,
+}
+
+/// Submodule update request; without a path every submodule is updated
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GitSubmoduleUpdateRequest {
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// Undo-last-commit request
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitUndoCommitRequest {
+    /// Leave the commit's changes staged; `false` leaves them only in the working tree
+    #[serde(default = "default_keep_changes")]
+    pub keep_changes: bool,
+    /// Undo even a commit that has been pushed
+    #[serde(default)]
+    pub force: bool,
+}
+
+fn default_keep_changes() -> bool {
+    true
+}
+
+/// Repository initialization request; an empty object or no body at all runs a
+/// plain `git init`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GitInitRequest {
+    pub default_branch: Option<String>,
+    /// Project template whose `.gitignore` to write, e.g. `rust` or `python`
+    pub project_type: Option<String>,
+    #[serde(default)]
+    pub initial_commit: bool,
+    /// Sign the initial commit; defaults to the git.sign_commits setting
+    #[serde(default)]
+    pub sign: Option<bool>,
+}
+
+/// History rewrite request: squash the last commits or reword the latest one
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum GitRewriteRequest {
+    Squash {
+        count: usize,
+        message: String,
+        /// Squash even commits that have been pushed
+        #[serde(default)]
+        force: bool,
+        /// Sign the new commit; defaults to the git.sign_commits setting
+        #[serde(default)]
+        sign: Option<bool>,
+    },
+    Reword {
+        hash: String,
+        message: String,
+        /// Reword even a commit that has been pushed
+        #[serde(default)]
+        force: bool,
+        /// Sign the reworded commit; defaults to the git.sign_commits setting
+        #[serde(default)]
+        sign: Option<bool>,
+    },
+}
+
+/// Single-hunk staging request
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StageHunkRequest {
+    pub file_path: String,
+    pub hunk: crate::git::GitHunk,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GitStatusRequest {
     pub path: Option<String>,
@@ -275,6 +786,7 @@ impl<T> ApiResponse<T> {
             success: true,
             data: Some(data),
             error: None,
+            error_code: None,
             timestamp: Utc::now().to_rfc3339(),
         }
     }
@@ -284,9 +796,51 @@ impl<T> ApiResponse<T> {
             success: false,
             data: None,
             error: Some(error),
+            error_code: None,
             timestamp: Utc::now().to_rfc3339(),
         }
     }
+
+    /// Error from the AI engine, tagging provider timeouts so the UI can offer a retry
+    /// and proxy failures so it can point at the proxy settings
+    pub fn ai_error(context: &str, error: &anyhow::Error) -> Self {
+        let mut response = Self::error(format!("{}: {}", context, error));
+        match error.downcast_ref::<crate::ai::AiError>() {
+            Some(crate::ai::AiError::Timeout(_)) => response.error_code = Some("ai_timeout".to_string()),
+            Some(crate::ai::AiError::Proxy(_)) => response.error_code = Some("proxy_error".to_string()),
+            _ => {}
+        }
+        response
+    }
+
+    /// Error classified by `IdeError`, carrying its `error_code`
+    pub fn ide_error(context: &str, error: impl Into<IdeError>) -> Self {
+        let error = error.into();
+        // The wrapped module error already reads well on its own
+        let detail = std::error::Error::source(&error)
+            .map(|source| source.to_string())
+            .unwrap_or_else(|| error.to_string());
+        let mut response = Self::error(format!("{}: {}", context, detail));
+        response.error_code = Some(error.error_code().to_string());
+        response
+    }
+}
+
+/// HTTP status of a failed response, from its error code
+fn error_status(error_code: Option<&str>) -> StatusCode {
+    match error_code {
+        Some("ai_timeout" | "external_timeout" | "navigation_timeout") => StatusCode::GATEWAY_TIMEOUT,
+        Some("not_found") => StatusCode::NOT_FOUND,
+        Some("conflict" | "commit_pushed" | "non_fast_forward" | "secrets_found") => StatusCode::CONFLICT,
+        Some("permission_denied" | "workspace_not_trusted") => StatusCode::FORBIDDEN,
+        Some("auth_failed" | "remote_permission_denied") => StatusCode::BAD_GATEWAY,
+        Some("file_too_large" | "payload_too_large") => StatusCode::PAYLOAD_TOO_LARGE,
+        Some("browser_unavailable" | "mcp_unreachable" | "git_not_installed" | "tool_missing" | "not_ready") => StatusCode::SERVICE_UNAVAILABLE,
+        Some("ai_unavailable" | "ai_error" | "invalid_ai_output" | "external_error" | "proxy_error") => StatusCode::BAD_GATEWAY,
+        Some("git_failed" | "io_error" | "watch_failed" | "editor_error" | "terminal_error"
+            | "config_error" | "database_error" | "internal") => StatusCode::INTERNAL_SERVER_ERROR,
+        _ => StatusCode::BAD_REQUEST,
+    }
 }
 
 impl<T> IntoResponse for ApiResponse<T>
@@ -294,25 +848,42 @@ where
     T: Serialize,
 {
     fn into_response(self) -> axum::response::Response {
-        if self.success {
-            (StatusCode::OK, Json(self)).into_response()
+        let status = if self.success {
+            StatusCode::OK
         } else {
-            (StatusCode::BAD_REQUEST, Json(self)).into_response()
-        }
+            error_status(self.error_code.as_deref())
+        };
+        (status, Json(self)).into_response()
+    }
+}
+
+/// Response for git endpoints called outside a repository, or without git installed
+fn not_a_repository<T>(git_manager: &GitManager) -> ApiResponse<T> {
+    if git_manager.is_git_missing() {
+        return ApiResponse::ide_error("Git unavailable", GitError::GitNotInstalled);
     }
+    ApiResponse::ide_error("Not a git repository", GitError::RepositoryNotFound(git_manager.repository_path().to_path_buf()))
 }
 
 // Router creation
-pub fn create_api_router(app_state: super::ui::AppState) -> Router<super::ui::AppState> {
-    Router::new()
+pub fn create_api_router(app_state: super::ui::AppState, limits: limits::BodyLimits) -> Router<super::ui::AppState> {
+    let files = Router::new()
         // File operations
         .route("/files/:path", get(load_file))
         .route("/files/:path", put(save_file))
         .route("/files/create", post(create_file))
+        .route("/files/upload", post(upload_files))
+        .route("/files/raw/*path", get(download_raw_file))
         .route("/files/:path", delete(delete_file))
         .route("/files/tree", get(get_file_tree))
         .route("/files/search", get(search_files))
+        .route("/files/tree/ws", get(file_tree_websocket))
+        .route("/files/history", get(get_file_history))
+        .route("/files/history/restore", post(restore_file_history))
+        .route("/files/trash", get(list_trash))
+        .route("/files/trash/restore", post(restore_from_trash));
         
+    let ai = Router::new()
         // AI endpoints
         .route("/ai/chat", post(ai_chat))
         .route("/ai/completions", post(get_completions))
@@ -336,17 +907,36 @@ pub fn create_api_router(app_state: super::ui::AppState) -> Router<super::ui::Ap
         
         // Phase 4: Enhanced Code Intelligence & Debugging
         .route("/ai/advanced-analysis", post(advanced_code_analysis))
+        .route("/ai/auto-analysis", post(auto_code_analysis))
+        .route("/ai/analyze-batch", post(analyze_batch))
+        .route("/ai/explain-history", post(explain_history))
+        .route("/ai/changelog", post(generate_changelog))
+        .route("/ai/advanced-analysis/stream", post(advanced_code_analysis_stream))
         .route("/ai/bug-prediction", post(predict_bugs))
         .route("/ai/security-vulnerabilities", post(analyze_security_vulnerabilities))
         .route("/ai/code-explanation", post(explain_code))
+        .route("/ai/project-overview", post(project_overview))
+        .route("/ai/models", get(list_models))
+        .route("/ai/tools", get(list_ai_tools))
+        .route("/ai/rules", get(list_analysis_rules))
+        .route("/ai/tools/:id/run", post(run_ai_tool))
         .route("/ai/generate-tests", post(generate_unit_tests))
+        .route("/ai/generate-docs", post(generate_docs))
+        .route("/ai/fix-diagnostic", post(fix_diagnostic))
+        .route("/ai/infer-types", post(infer_types))
         .route("/ai/code-improvements", post(suggest_improvements))
+        .route("/ai/suggestion-to-patch", post(suggestion_to_patch))
+        .route("/ai/build-regex", post(build_regex))
         .route("/ai/debug-session/start", post(start_debug_session))
         .route("/ai/debug-session/:id", get(get_debug_session))
         .route("/ai/debug-session/:id/breakpoints", post(set_breakpoints))
         .route("/ai/debug-session/:id/step", post(debug_step))
         .route("/ai/debug-session/:id/variables", get(get_debug_variables))
-        .route("/ai/debug-session/:id/stop", post(stop_debug_session))
+        .route("/ai/debug-session/:id/stop", post(stop_debug_session));
+        
+    let other = Router::new()
+        // Diagnostics
+        .route("/diagnostics/*path", get(get_diagnostics))
         
         // Collaboration endpoints
         .route("/collaboration/session", post(create_collaboration_session))
@@ -364,15 +954,29 @@ pub fn create_api_router(app_state: super::ui::AppState) -> Router<super::ui::Ap
         // Smart search endpoints
         .route("/search/smart", post(smart_search))
         .route("/search/files", post(search_files_advanced))
+        .route("/search/grep", post(grep_workspace))
+        .route("/search/ws", get(search_websocket))
+        .route("/search/semantic", get(semantic_search))
+        
+        // Command palette
+        .route("/commands", get(list_commands))
+        .route("/commands/:id/invoke", post(invoke_command))
         
         // Auto-save endpoints
         .route("/autosave/enable", post(enable_autosave))
         .route("/autosave/disable", post(disable_autosave))
         .route("/autosave/status", get(get_autosave_status))
+        .route("/autosave/run", post(run_autosave))
         
         // API testing endpoints
         .route("/api-test/request", post(test_api_request))
         .route("/api-test/history", get(get_api_test_history))
+        .route("/api-test/collections", get(list_api_collections))
+        .route("/api-test/collections", post(create_api_collection))
+        .route("/api-test/collections/:id", get(get_api_collection))
+        .route("/api-test/collections/:id", put(update_api_collection))
+        .route("/api-test/collections/:id", delete(delete_api_collection))
+        .route("/api-test/collections/:id/requests/:request_id/run", post(run_saved_api_request))
         
         // Learning endpoints
         .route("/learning/profile", get(get_learning_profile))
@@ -386,25 +990,69 @@ pub fn create_api_router(app_state: super::ui::AppState) -> Router<super::ui::Ap
         .route("/learning/tour", post(create_code_tour))
         .route("/learning/achievements", get(get_achievements))
         
+        // Editor
+        .route("/editor/format", post(format_active_document))
+        .route("/editor/convert-indent", post(convert_indentation))
+        .route("/editor/selection", post(set_selection))
+        .route("/editor/cursors", post(set_cursors))
+        .route("/editor/cursors/insert", post(insert_at_cursors))
+        .route("/editor/cursors/delete", post(delete_at_cursors))
+        .route("/editor/activate", post(activate_document))
+        .route("/editor/revert", post(revert_document))
+        .route("/editor/navigate/back", post(navigate_back))
+        .route("/editor/navigate/forward", post(navigate_forward))
+        .route("/editor/recent-files", get(recent_files))
+        .route("/editor/outline/:doc_id", get(get_document_outline))
+        .route("/editor/selection/:action", post(selection_action))
+        .route("/symbols", get(search_symbols))
+        .route("/symbols/definition", get(symbol_definition))
+        .route("/symbols/rename", post(rename_symbol))
+        
+        // Terminal
+        .route("/terminal/:session/scrollback", get(terminal_scrollback))
+        
         // Git operations
         .route("/git/status", get(git_status))
         .route("/git/branches", get(git_branches))
         .route("/git/commit", post(git_commit))
+        .route("/git/commit-flow", post(git_commit_flow))
+        .route("/git/undo-commit", post(git_undo_commit))
+        .route("/git/rewrite", post(git_rewrite))
+        .route("/git/secret-scan", get(git_secret_scan))
         .route("/git/push", post(git_push))
         .route("/git/pull", post(git_pull))
         .route("/git/diff", get(git_diff))
         .route("/git/log", get(git_log))
+        .route("/git/file-at-revision", get(git_file_at_revision))
+        .route("/diff", post(diff_files))
+        .route("/git/contributors", get(git_contributors))
+        .route("/git/submodules", get(git_submodules))
+        .route("/git/submodules", post(git_update_submodules))
         .route("/git/branch", post(git_create_branch))
         .route("/git/checkout", post(git_checkout_branch))
         .route("/git/stage", post(git_stage_files))
+        .route("/git/stage-hunk", post(git_stage_hunk))
         .route("/git/unstage", post(git_unstage_files))
         .route("/git/discard", post(git_discard_changes))
         .route("/git/init", post(git_init_repository))
         
         // Project operations
         .route("/project/info", get(project_info))
+        .route("/project/recent", get(recent_projects))
+        .route("/project/trust", get(project_trust))
+        .route("/project/trust", post(trust_project))
+        .route("/project/templates", get(list_project_templates))
         .route("/project/config", get(get_config))
+        .route("/project/config/diff", get(get_config_diff))
+        .route("/project/config/reset", post(reset_config))
+        .route("/project/dependencies", get(list_dependencies))
+        .route("/project/dependencies/outdated", get(list_outdated_dependencies))
+        .route("/project/stats", get(project_stats))
+        .route("/project/tasks", get(list_tasks))
+        .route("/project/tasks", post(run_task))
+        .route("/project/tasks/:id/cancel", post(cancel_task))
         .route("/health", get(health_check))
+        .route("/ready", get(readiness_check))
 
         // External integrations
         .route("/external/mcp/search_tweets", post(mcp_search_tweets))
@@ -419,8 +1067,13 @@ pub fn create_api_router(app_state: super::ui::AppState) -> Router<super::ui::Ap
         .route("/external/browser/wait", post(browser_wait))
         .route("/external/browser/page_info", get(browser_get_page_info))
         .route("/external/browser/element_info", post(browser_get_element_info))
-        .route("/external/status", get(external_status))
+        .route("/external/status", get(external_status));
+    #[cfg(feature = "snippet-runner")]
+    let other = other.route("/learning/run", post(run_snippet));
 
+    limits::limit_body(other, limits.default)
+        .merge(limits::limit_body(files, limits.files))
+        .merge(limits::limit_body(ai, limits.ai))
         .with_state(app_state)
 }
 
@@ -430,18 +1083,105 @@ pub fn create_api_router(app_state: super::ui::AppState) -> Router<super::ui::Ap
 pub async fn load_file(
     State(_state): State<super::ui::AppState>,
     Path(path): Path<String>,
+    Query(params): Query<LoadFileQuery>,
 ) -> impl IntoResponse {
-    let file_manager = _state.file_manager.read().await;
+    let config = _state.ide.config().read().await;
+    let file_manager = FileManager::new(config.workspace_dir())
+        .with_max_open_file_bytes(config.editor.max_open_file_bytes)
+        .with_fallback_encoding(&config.editor.fallback_encoding);
+    drop(config);
     let path_buf = PathBuf::from(path);
     
-    match file_manager.read_file(&path_buf).await {
+    match file_manager.read_file_with_override(&path_buf, params.open_anyway).await {
         Ok(content) => {
             info!("Successfully loaded file: {}", path_buf.display());
             ApiResponse::success(content)
         }
         Err(e) => {
             warn!("Failed to load file {}: {}", path_buf.display(), e);
-            ApiResponse::error(format!("Failed to load file: {}", e))
+            ApiResponse::ide_error("Failed to load file", e)
+        }
+    }
+}
+
+/// Write each file part of a multipart body under the workspace, streaming its
+/// bytes to disk as they arrive
+pub async fn upload_files(
+    State(_state): State<super::ui::AppState>,
+    Query(query): Query<UploadQuery>,
+    mut multipart: Multipart,
+) -> axum::response::Response {
+    use futures::TryStreamExt;
+
+    let config = _state.ide.config().read().await;
+    let file_manager = FileManager::new(config.workspace_dir())
+        .with_max_open_file_bytes(config.editor.max_open_file_bytes)
+        .with_fsync_on_save(config.editor.fsync_on_save);
+    drop(config);
+    let dir = PathBuf::from(query.dir.unwrap_or_default());
+
+    let mut uploaded = Vec::new();
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return ApiResponse::<Vec<UploadedFile>>::error(format!("Invalid upload: {}", e)).into_response(),
+        };
+        // Parts without a file name are form fields, not files
+        let Some(file_name) = field.file_name().map(str::to_string) else {
+            continue;
+        };
+        let path = dir.join(&file_name);
+        let reader = tokio_util::io::StreamReader::new(field.map_err(std::io::Error::other));
+        tokio::pin!(reader);
+        let existed = file_manager.path_exists(&path);
+        match file_manager.write_raw(&path, reader).await {
+            Ok(result) => {
+                info!("Uploaded {} ({} bytes)", path.display(), result.bytes_written.unwrap_or(0));
+                let _ = _state.event_bus.broadcast(crate::utils::event_bus::IdeEvent::FileChanged {
+                    path: path.to_string_lossy().to_string(),
+                    event_type: if existed {
+                        crate::utils::event_bus::FileEventType::Modified
+                    } else {
+                        crate::utils::event_bus::FileEventType::Created
+                    },
+                });
+                uploaded.push(UploadedFile {
+                    path: path.to_string_lossy().to_string(),
+                    bytes: result.bytes_written.unwrap_or(0),
+                });
+            }
+            Err(e) => {
+                warn!("Failed to upload {}: {}", path.display(), e);
+                return ApiResponse::<Vec<UploadedFile>>::ide_error("Failed to upload file", e).into_response();
+            }
+        }
+    }
+    ApiResponse::success(uploaded).into_response()
+}
+
+/// Stream a file's bytes as they are, for images and other binary assets
+pub async fn download_raw_file(
+    State(_state): State<super::ui::AppState>,
+    Path(path): Path<String>,
+) -> axum::response::Response {
+    let config = _state.ide.config().read().await;
+    let file_manager = FileManager::new(config.workspace_dir())
+        .with_max_open_file_bytes(config.editor.max_open_file_bytes);
+    drop(config);
+    let path_buf = PathBuf::from(path);
+
+    match file_manager.open_raw(&path_buf).await {
+        Ok((file, size)) => (
+            [
+                (axum::http::header::CONTENT_TYPE, "application/octet-stream".to_string()),
+                (axum::http::header::CONTENT_LENGTH, size.to_string()),
+            ],
+            axum::body::Body::from_stream(tokio_util::io::ReaderStream::new(file)),
+        ).into_response(),
+        Err(e) => {
+            warn!("Failed to download file {}: {}", path_buf.display(), e);
+            ApiResponse::<()>::ide_error("Failed to download file", e).into_response()
         }
     }
 }
@@ -452,7 +1192,12 @@ pub async fn save_file(
     Path(path): Path<String>,
     Json(request): Json<FileContentRequest>,
 ) -> impl IntoResponse {
-    let file_manager = _state.file_manager.read().await;
+    // Saves go through the workspace file manager so they land in the local history
+    let config = _state.ide.config().read().await;
+    let file_manager = FileManager::new(config.workspace_dir())
+        .with_fsync_on_save(config.editor.fsync_on_save)
+        .with_fallback_encoding(&config.editor.fallback_encoding);
+    drop(config);
     let path_buf = PathBuf::from(path);
     
     match file_manager.write_file(&path_buf, &request.content).await {
@@ -471,7 +1216,7 @@ pub async fn save_file(
         }
         Err(e) => {
             error!("Failed to save file {}: {}", path_buf.display(), e);
-            ApiResponse::error(format!("Failed to save file: {}", e))
+            ApiResponse::ide_error("Failed to save file", e)
         }
     }
 }
@@ -481,7 +1226,10 @@ pub async fn create_file(
     State(_state): State<super::ui::AppState>,
     Json(request): Json<FileCreateRequest>,
 ) -> impl IntoResponse {
-    let file_manager = _state.file_manager.read().await;
+    let config = _state.ide.config().read().await;
+    let file_manager = FileManager::new(config.workspace_dir())
+        .with_fsync_on_save(config.editor.fsync_on_save);
+    drop(config);
     let path_buf = PathBuf::from(&request.path);
     
     match if request.is_directory {
@@ -505,7 +1253,7 @@ pub async fn create_file(
         }
         Err(e) => {
             error!("Failed to create {}: {}", path_buf.display(), e);
-            ApiResponse::error(format!("Failed to create: {}", e))
+            ApiResponse::ide_error("Failed to create", e)
         }
     }
 }
@@ -515,10 +1263,16 @@ pub async fn delete_file(
     State(_state): State<super::ui::AppState>,
     Path(path): Path<String>,
 ) -> impl IntoResponse {
-    let file_manager = _state.file_manager.read().await;
+    let (workspace_path, delete_to_trash, fsync_on_save) = {
+        let config = _state.ide.config().read().await;
+        (config.workspace_dir(), config.editor.delete_to_trash, config.editor.fsync_on_save)
+    };
+    let file_manager = FileManager::new(workspace_path.clone())
+        .with_delete_to_trash(delete_to_trash)
+        .with_fsync_on_save(fsync_on_save);
     let path_buf = PathBuf::from(path);
     
-    match if path_buf.is_dir() {
+    match if workspace_path.join(&path_buf).is_dir() {
         file_manager.delete_directory(&path_buf).await
     } else {
         file_manager.delete_file(&path_buf).await
@@ -536,7 +1290,7 @@ pub async fn delete_file(
         }
         Err(e) => {
             error!("Failed to delete {}: {}", path_buf.display(), e);
-            ApiResponse::error(format!("Failed to delete: {}", e))
+            ApiResponse::ide_error("Failed to delete", e)
         }
     }
 }
@@ -544,12 +1298,19 @@ pub async fn delete_file(
 /// Get file tree structure
 pub async fn get_file_tree(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
     let file_manager = _state.file_manager.read().await;
-    let workspace_path = _state.ide.config().read().await.workspace_dir();
+    let (workspace_path, size_limit) = {
+        let config = _state.ide.config().read().await;
+        (config.workspace_dir(), config.editor.max_open_file_bytes)
+    };
     
     match file_manager.list_directory(&workspace_path).await {
         Ok(entries) => {
             let file_tree: Vec<FileTreeNode> = entries.into_iter()
-                .map(|entry| FileTreeNode::from(entry))
+                .map(|entry| {
+                    let mut node = FileTreeNode::from(entry);
+                    node.too_large = node.r#type == "file" && node.size > size_limit;
+                    node
+                })
                 .collect();
             
             info!("Successfully loaded file tree with {} items", file_tree.len());
@@ -557,7 +1318,7 @@ pub async fn get_file_tree(State(_state): State<super::ui::AppState>) -> impl In
         }
         Err(e) => {
             error!("Failed to load file tree: {}", e);
-            ApiResponse::error(format!("Failed to load file tree: {}", e))
+            ApiResponse::ide_error("Failed to load file tree", e)
         }
     }
 }
@@ -598,92 +1359,256 @@ pub async fn search_files(
         }
         Err(e) => {
             error!("Search failed: {}", e);
-            ApiResponse::error(format!("Search failed: {}", e))
+            ApiResponse::ide_error("Search failed", e)
         }
     }
 }
 
-// AI Handlers
-
-/// AI chat endpoint
-pub async fn ai_chat(
+/// Push batched file tree changes to the client as they happen
+pub async fn file_tree_websocket(
+    ws: WebSocketUpgrade,
     State(_state): State<super::ui::AppState>,
-    Json(request): Json<AIChatRequest>,
 ) -> impl IntoResponse {
-    let ai_engine = _state.ide.ai_engine();
-
-    // Extract language and content from request
-    let language = request.context.as_ref()
-        .and_then(|ctx| ctx.language.as_ref())
-        .cloned()
-        .unwrap_or_else(|| "rust".to_string());
-    let content = request.context.as_ref()
-        .and_then(|ctx| ctx.file_content.as_ref())
-        .cloned()
-        .unwrap_or_else(|| "".to_string());
+    ws.on_upgrade(move |socket| file_tree_connection(socket, _state))
+}
 
-    // Create AI completion request
-    let completion_request = crate::ai::CompletionRequest {
-        prompt: request.message,
-        context: request.context.as_ref().and_then(|ctx| ctx.file_content.as_ref()).cloned().unwrap_or_default(),
-        language: request.context.as_ref().and_then(|ctx| ctx.language.as_ref()).cloned().unwrap_or_else(|| "rust".to_string()),
-        max_tokens: request.settings.and_then(|s| s.max_tokens),
-        position: None,
-        cursor_position: request.context.as_ref().and_then(|ctx| ctx.cursor_position),
-        text_before_cursor: "",
-    };
-    
-    match ai_engine.generate_completion(completion_request).await {
-        Ok(completion) => {
-            info!("AI chat completed successfully");
-            ApiResponse::success(completion.text)
-        }
+/// Stream tree updates until the client disconnects
+async fn file_tree_connection(mut socket: WebSocket, state: super::ui::AppState) {
+    if let Err(e) = state.ide.watch_workspace().await {
+        error!("Failed to watch workspace: {}", e);
+        let _ = socket.send(Message::Close(None)).await;
+        return;
+    }
+    let mut subscriber = match state.event_bus.subscribe("files") {
+        Ok(subscriber) => subscriber,
+        Err(e) => {
+            error!("Failed to subscribe to file events: {}", e);
+            return;
+        }
+    };
+    let workspace_path = state.ide.config().read().await.workspace_dir();
+    let mut batcher = TreeUpdateBatcher::new(workspace_path);
+    
+    loop {
+        tokio::select! {
+            update = next_tree_update(&mut subscriber, &mut batcher, std::time::Duration::from_millis(150)) => {
+                let Some(update) = update else { break };
+                let Ok(json) = serde_json::to_string(&update) else { continue };
+                if socket.send(Message::Text(json)).await.is_err() {
+                    break;
+                }
+            }
+            message = socket.recv() => {
+                match message {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// List saved snapshots of a file
+pub async fn get_file_history(
+    State(_state): State<super::ui::AppState>,
+    Query(params): Query<FileHistoryQuery>,
+) -> impl IntoResponse {
+    let workspace_path = _state.ide.config().read().await.workspace_dir();
+    let file_manager = FileManager::new(workspace_path);
+    
+    match file_manager.list_history(&PathBuf::from(&params.path)).await {
+        Ok(history) => ApiResponse::success(history),
+        Err(e) => {
+            warn!("Failed to list history for {}: {}", params.path, e);
+            ApiResponse::ide_error("Failed to list history", e)
+        }
+    }
+}
+
+/// Restore a file to a saved snapshot
+pub async fn restore_file_history(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<RestoreHistoryRequest>,
+) -> impl IntoResponse {
+    let config = _state.ide.config().read().await;
+    let file_manager = FileManager::new(config.workspace_dir())
+        .with_fsync_on_save(config.editor.fsync_on_save)
+        .with_fallback_encoding(&config.editor.fallback_encoding);
+    drop(config);
+    
+    match file_manager.restore_history(&PathBuf::from(&request.path), &request.id).await {
+        Ok(result) => {
+            info!("Restored {} to snapshot {}", request.path, request.id);
+            let _ = _state.event_bus.broadcast(crate::utils::event_bus::IdeEvent::FileChanged {
+                path: request.path.clone(),
+                event_type: crate::utils::event_bus::FileEventType::Modified,
+            });
+            ApiResponse::success(result)
+        }
+        Err(e) => {
+            error!("Failed to restore {}: {}", request.path, e);
+            ApiResponse::ide_error("Failed to restore history", e)
+        }
+    }
+}
+
+/// List deleted files and directories that can be restored
+pub async fn list_trash(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
+    let workspace_path = _state.ide.config().read().await.workspace_dir();
+    let file_manager = FileManager::new(workspace_path);
+    
+    match file_manager.list_trash().await {
+        Ok(entries) => ApiResponse::success(entries),
+        Err(e) => {
+            warn!("Failed to list trash: {}", e);
+            ApiResponse::ide_error("Failed to list trash", e)
+        }
+    }
+}
+
+/// Move a trashed file or directory back to where it was deleted from
+pub async fn restore_from_trash(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<RestoreTrashRequest>,
+) -> impl IntoResponse {
+    let config = _state.ide.config().read().await;
+    let file_manager = FileManager::new(config.workspace_dir())
+        .with_fsync_on_save(config.editor.fsync_on_save);
+    drop(config);
+    
+    match file_manager.restore_from_trash(&request.id).await {
+        Ok(entry) => {
+            info!("Restored {} from trash", entry.original_path.display());
+            let _ = _state.event_bus.broadcast(crate::utils::event_bus::IdeEvent::FileChanged {
+                path: entry.original_path.to_string_lossy().to_string(),
+                event_type: crate::utils::event_bus::FileEventType::Created,
+            });
+            ApiResponse::success(entry)
+        }
+        Err(e) => {
+            error!("Failed to restore {} from trash: {}", request.id, e);
+            ApiResponse::ide_error("Failed to restore from trash", e)
+        }
+    }
+}
+
+// AI Handlers
+
+/// AI chat endpoint
+pub async fn ai_chat(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<AIChatRequest>,
+) -> impl IntoResponse {
+    let ai_engine = _state.ide.ai_engine();
+
+    let messages = request.conversation();
+    if !messages.iter().any(|message| message.role == ChatRole::User) {
+        let mut response = ApiResponse::error("A chat needs at least one user message".to_string());
+        response.error_code = Some("invalid_request".to_string());
+        return response;
+    }
+
+    let language = request.context.as_ref()
+        .and_then(|ctx| ctx.language.clone())
+        .unwrap_or_else(|| "rust".to_string());
+    let context = request.context.as_ref().and_then(AIContext::describe);
+    let max_tokens = request.settings.as_ref().and_then(|s| s.max_tokens);
+    
+    // Honor per-request provider settings with a transient engine
+    let result = match request.settings {
+        Some(settings) => {
+            let overrides = crate::ai::AiOverrides {
+                provider: settings.provider,
+                model: settings.model,
+                base_url: settings.base_url,
+                api_key: settings.api_key,
+                temperature: settings.temperature,
+                max_tokens: settings.max_tokens,
+            };
+            match ai_engine.with_overrides(&overrides).await {
+                Ok(engine) => engine.chat(&messages, &language, context.as_deref(), max_tokens).await,
+                Err(e) => Err(e),
+            }
+        }
+        None => ai_engine.chat(&messages, &language, context.as_deref(), max_tokens).await,
+    };
+    
+    match result {
+        Ok(completion) => {
+            info!("AI chat completed successfully");
+            ApiResponse::success(completion.text)
+        }
         Err(e) => {
             error!("AI chat failed: {}", e);
-            ApiResponse::error(format!("AI chat failed: {}", e))
+            ApiResponse::ai_error("AI chat failed", &e)
         }
     }
 }
 
-/// Get code completions
+/// Get code completions, each anchored to the word at the cursor
 pub async fn get_completions(
     State(_state): State<super::ui::AppState>,
-    Json(_request): Json<CodeCompletionRequest>,
+    Json(request): Json<CodeCompletionRequest>,
 ) -> impl IntoResponse {
-    let editor = _state.ide.editor();
-    let _editor_lock = editor.lock().await;
-    
-    // For now, return a simple completion response
-    // In a real implementation, this would use the editor's completion system
-    let completions = vec![
-        crate::editor::CompletionItem {
-            label: "completion_placeholder".to_string(),
-            kind: crate::editor::CompletionKind::Keyword,
-            detail: Some("AI-generated completion".to_string()),
-            documentation: None,
-            insert_text: "completion_text".to_string(),
-            sort_text: "completion_text".to_string(),
-        }
-    ];
+    let ai_engine = _state.ide.ai_engine();
+    
+    let cursor = request.position.min(request.code.chars().count());
+    let text_before_cursor: String = request.code.chars().take(cursor).collect();
+    let line = text_before_cursor.matches('\n').count();
+    let column = text_before_cursor.chars().rev().take_while(|c| *c != '\n').count();
+    let completion_request = crate::ai::CompletionRequest {
+        language: request.language.clone(),
+        context: ai_engine.context_window(&request.code, &request.language, line).text,
+        position: Some((line, column)),
+        prompt: format!("Complete this {} code", request.language),
+        max_tokens: Some(100),
+        cursor_position: Some((line, column)),
+        text_before_cursor,
+        document: request.file_path.clone(),
+    };
     
-    ApiResponse::success(completions)
+    match ai_engine.complete_code(completion_request).await {
+        Ok(mut completion) => {
+            // Providers that return a single completion get it offered as a suggestion too
+            if completion.suggestions.is_empty() && !completion.text.is_empty() {
+                completion.suggestions.push(crate::ai::CompletionSuggestion {
+                    text: completion.text.clone(),
+                    confidence: completion.confidence,
+                    kind: "ai".to_string(),
+                    replace_range: None,
+                    insert_text: None,
+                });
+            }
+            completion.suggestions = completion.suggestions.into_iter()
+                .map(|suggestion| suggestion.anchored(&request.code, cursor))
+                .collect();
+            ApiResponse::success(completion)
+        }
+        Err(e) => {
+            error!("Completion failed: {}", e);
+            ApiResponse::ai_error("Completion failed", &e)
+        }
+    }
 }
 
 /// Analyze code using AI
 pub async fn analyze_code(
     State(_state): State<super::ui::AppState>,
-    Json(request): Json<CodeCompletionRequest>,
+    Json(request): Json<CodeAnalysisRequest>,
 ) -> impl IntoResponse {
     let ai_engine = _state.ide.ai_engine();
     
     match ai_engine.analyze_code(&request.code, &request.language).await {
-        Ok(analysis) => {
+        Ok(mut analysis) => {
             info!("Code analysis completed");
+            if let Some(min_severity) = &request.min_severity {
+                analysis.retain_min_severity(min_severity);
+            }
             ApiResponse::success(analysis)
         }
         Err(e) => {
             error!("Code analysis failed: {}", e);
-            ApiResponse::error(format!("Code analysis failed: {}", e))
+            ApiResponse::ai_error("Code analysis failed", &e)
         }
     }
 }
@@ -699,45 +1624,43 @@ pub async fn smart_completions(
     
     let file_path = request.get("file_path").and_then(|v| v.as_str()).unwrap_or("");
     let content = request.get("content").and_then(|v| v.as_str()).unwrap_or("");
-    let _position = request.get("position");
+    let line = request.get("position")
+        .and_then(|position| position.get("line"))
+        .and_then(|line| line.as_u64())
+        .unwrap_or(0) as usize;
+    let language = get_language_from_file_path(file_path);
     
     // Create completion request with enhanced context
     let completion_request = crate::ai::CompletionRequest {
         prompt: format!("Provide context-aware completions for file: {}", file_path),
-        context: content.to_string(),
-        language: get_language_from_file_path(file_path),
+        context: ai_engine.context_window(content, &language, line).text,
+        language: language.clone(),
         max_tokens: Some(200),
         position: None,
         cursor_position: None,
-        text_before_cursor: content,
+        text_before_cursor: content.to_string(),
+        document: None,
     };
     
     match ai_engine.complete_code(completion_request).await {
-        Ok(_completion) => {
-            // Convert AI completion to structured suggestions
-            let suggestions = vec![
-                crate::ai::CodeSuggestion {
-                    id: uuid::Uuid::new_v4().to_string(),
-                    title: "AI Suggestion".to_string(),
-                    description: "AI-generated suggestion".to_string(),
-                    message: "AI-generated suggestion".to_string(),
-                    code: "Sample completion".to_string(),
-                    confidence: 0.8,
-                }
-            ];
+        Ok(completion) => {
+            // Typed like editor completions, so the UI can show the right icons
+            let suggestions: Vec<crate::editor::CompletionItem> = completion.suggestions.iter()
+                .map(|suggestion| crate::editor::CompletionItem::from_ai_suggestion(suggestion, completion.confidence, &language))
+                .collect();
             
             ApiResponse::success(serde_json::json!({
                 "suggestions": suggestions,
                 "context": {
                     "file_path": file_path,
-                    "language": get_language_from_file_path(file_path),
+                    "language": language,
                     "completion_count": suggestions.len()
                 }
             }))
         }
         Err(e) => {
             error!("Smart completions failed: {}", e);
-            ApiResponse::error(format!("Smart completions failed: {}", e))
+            ApiResponse::ai_error("Smart completions failed", &e)
         }
     }
 }
@@ -755,24 +1678,13 @@ pub async fn code_review(
     match ai_engine.analyze_code(code, language).await {
         Ok(analysis) => {
             // Convert analysis results to review format
-            let review_results = analysis.issues.into_iter().map(|issue| {
-                serde_json::json!({
-                    "id": issue.id,
-                    "title": format!("{} Issue", issue.severity),
-                    "description": issue.message,
-                    "severity": format!("{:?}", issue.severity).to_lowercase(),
-                    "line": issue.line,
-                    "category": "code_quality",
-                    "suggestion": "Consider refactoring this code",
-                    "canAutoFix": issue.severity == crate::ai::IssueSeverity::Info
-                })
-            }).collect::<Vec<_>>();
+            let review_results = crate::ai::review::review_items(&analysis, code);
             
             ApiResponse::success(review_results)
         }
         Err(e) => {
             error!("Code review failed: {}", e);
-            ApiResponse::error(format!("Code review failed: {}", e))
+            ApiResponse::ai_error("Code review failed", &e)
         }
     }
 }
@@ -787,6 +1699,9 @@ pub async fn debug_assistance(
     let code = request.get("code").and_then(|v| v.as_str()).unwrap_or("");
     let language = request.get("language").and_then(|v| v.as_str()).unwrap_or("rust");
     let issue = request.get("issue").and_then(|v| v.as_str());
+    // 1-based line the issue is at, which the code sent is centred on
+    let line = request.get("line").and_then(|v| v.as_u64()).unwrap_or(1).saturating_sub(1) as usize;
+    let code = &ai_engine.context_window(code, language, line).text;
     
     let prompt = if let Some(issue_desc) = issue {
         format!("Debug this {} code. Issue: {}\n\nCode:\n{}", language, issue_desc, code)
@@ -802,6 +1717,7 @@ pub async fn debug_assistance(
         position: None,
         cursor_position: request.context.as_ref().and_then(|ctx| ctx.cursor_position),
         text_before_cursor: "",
+        document: None,
     };
     
     match ai_engine.complete_code(completion_request).await {
@@ -823,7 +1739,7 @@ pub async fn debug_assistance(
         }
         Err(e) => {
             error!("Debug assistance failed: {}", e);
-            ApiResponse::error(format!("Debug assistance failed: {}", e))
+            ApiResponse::ai_error("Debug assistance failed", &e)
         }
     }
 }
@@ -840,14 +1756,71 @@ pub async fn generate_project(
     let description = config.get("description").and_then(|v| v.as_str()).unwrap_or("");
     let features = config.get("features").and_then(|v| v.as_array()).unwrap_or_else(|| -> &Vec<serde_json::Value> { static EMPTY_VEC: Vec<serde_json::Value> = Vec::new(); &EMPTY_VEC });
     
+    // Preview returns the files without writing them; applying keeps existing files unless forced
+    let preview = request.get("preview").and_then(|v| v.as_bool()).unwrap_or(false);
+    let force = request.get("force").and_then(|v| v.as_bool()).unwrap_or(false);
+    
+    let mut name_components = std::path::Path::new(project_name).components();
+    if !matches!((name_components.next(), name_components.next()), (Some(std::path::Component::Normal(_)), None)) {
+        let mut response = ApiResponse::error(format!("Invalid project name: {}", project_name));
+        response.error_code = Some("invalid_request".to_string());
+        return response;
+    }
+    
+    let registry = match template_registry() {
+        Ok(registry) => registry,
+        Err(e) => return template_error("Failed to load project templates", e),
+    };
+    let root = _state.ide.config().read().await.workspace_dir().join(project_name);
+    
     // Generate project structure based on template
-    let project_structure = match template {
-        "rust-web-api" => generate_rust_web_api_structure(project_name, description, features),
-        "python-web-app" => generate_python_web_app_structure(project_name, description, features),
-        "react-frontend" => generate_react_frontend_structure(project_name, description, features),
-        _ => generate_generic_project_structure(project_name, description, features)
+    let mut project_structure = if registry.get(template).is_some() {
+        // `config.placeholders` fills the template's placeholders besides the name
+        let mut values: std::collections::HashMap<String, String> = config.get("placeholders")
+            .and_then(|v| v.as_object())
+            .into_iter()
+            .flatten()
+            .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+            .collect();
+        values.entry("description".to_string()).or_insert_with(|| description.to_string());
+        match registry.plan(template, &root, project_name, &values) {
+            Ok(plan) => serde_json::json!({
+                "projectName": project_name,
+                "description": description,
+                "template": template,
+                "files": plan.files,
+            }),
+            Err(e) => return template_error("Project generation failed", e),
+        }
+    } else {
+        match template {
+            "rust-web-api" => generate_rust_web_api_structure(project_name, description, features),
+            "python-web-app" => generate_python_web_app_structure(project_name, description, features),
+            "react-frontend" => generate_react_frontend_structure(project_name, description, features),
+            _ => generate_generic_project_structure(project_name, description, features)
+        }
     };
     
+    let plan = project_structure["files"].as_array().into_iter().flatten()
+        .fold(ScaffoldPlan::new(root), |plan, file| plan.file(
+            file["path"].as_str().unwrap_or_default(),
+            file["content"].as_str().unwrap_or_default(),
+        ));
+    
+    project_structure["preview"] = serde_json::json!(preview);
+    project_structure["conflicts"] = serde_json::json!(plan.conflicts());
+    if !preview {
+        if let Err(e) = plan.apply(force) {
+            let mut response = ApiResponse::error(format!("Project generation failed: {}", e));
+            response.error_code = Some(match e {
+                ScaffoldError::Conflicts(_) => "conflict",
+                ScaffoldError::InvalidPath(_) => "invalid_request",
+                ScaffoldError::Io(_) => "io_error",
+            }.to_string());
+            return response;
+        }
+    }
+    
     ApiResponse::success(project_structure)
 }
 
@@ -877,6 +1850,7 @@ pub async fn context_help(
         position: None,
         cursor_position: request.context.as_ref().and_then(|ctx| ctx.cursor_position),
         text_before_cursor: "",
+        document: None,
     };
     
     match ai_engine.complete_code(completion_request).await {
@@ -896,7 +1870,7 @@ pub async fn context_help(
         }
         Err(e) => {
             error!("Context help failed: {}", e);
-            ApiResponse::error(format!("Context help failed: {}", e))
+            ApiResponse::ai_error("Context help failed", &e)
         }
     }
 }
@@ -944,6 +1918,7 @@ pub async fn optimize_advanced(
         position: None,
         cursor_position: request.context.as_ref().and_then(|ctx| ctx.cursor_position),
         text_before_cursor: "",
+        document: None,
     };
     
     match ai_engine.complete_code(completion_request).await {
@@ -959,7 +1934,7 @@ pub async fn optimize_advanced(
         }
         Err(e) => {
             error!("Advanced optimization failed: {}", e);
-            ApiResponse::error(format!("Advanced optimization failed: {}", e))
+            ApiResponse::ai_error("Advanced optimization failed", &e)
         }
     }
 }
@@ -1050,6 +2025,7 @@ pub async fn generate_tests_advanced(
         position: None,
         cursor_position: request.context.as_ref().and_then(|ctx| ctx.cursor_position),
         text_before_cursor: "",
+        document: None,
     };
     
     match ai_engine.complete_code(completion_request).await {
@@ -1070,7 +2046,7 @@ pub async fn generate_tests_advanced(
         }
         Err(e) => {
             error!("Advanced test generation failed: {}", e);
-            ApiResponse::error(format!("Advanced test generation failed: {}", e))
+            ApiResponse::ai_error("Advanced test generation failed", &e)
         }
     }
 }
@@ -1169,12 +2145,7 @@ pub async fn translate_languages(
     let to_language = request.get("toLanguage").and_then(|v| v.as_str()).unwrap_or("python");
     let preserve_comments = request.get("preserveComments").and_then(|v| v.as_bool()).unwrap_or(true);
     
-    let prompt = format!(
-        "Translate this {} code to {}. {} Preserve comments and documentation.\n\n{} code:\n{}",
-        from_language, to_language,
-        if preserve_comments { "Please" } else { "Do not" },
-        from_language, code
-    );
+    let prompt = translation_prompt(code, from_language, to_language, preserve_comments);
     
     let completion_request = crate::ai::CompletionRequest {
         prompt,
@@ -1184,6 +2155,7 @@ pub async fn translate_languages(
         position: None,
         cursor_position: request.context.as_ref().and_then(|ctx| ctx.cursor_position),
         text_before_cursor: "",
+        document: None,
     };
     
     match ai_engine.complete_code(completion_request).await {
@@ -1200,11 +2172,20 @@ pub async fn translate_languages(
         }
         Err(e) => {
             error!("Language translation failed: {}", e);
-            ApiResponse::error(format!("Language translation failed: {}", e))
+            ApiResponse::ai_error("Language translation failed", &e)
         }
     }
 }
 
+fn translation_prompt(code: &str, from_language: &str, to_language: &str, preserve_comments: bool) -> String {
+    format!(
+        "Translate this {} code to {}. {} Preserve comments and documentation.\n\n{} code:\n{}",
+        from_language, to_language,
+        if preserve_comments { "Please" } else { "Do not" },
+        from_language, code
+    )
+}
+
 /// Get code metrics and analysis
 pub async fn code_metrics(
     State(_state): State<super::ui::AppState>,
@@ -1353,163 +2334,622 @@ fn get_language_from_file_path(file_path: &str) -> String {
     }
 }
 
-// Git Handlers
+// Editor Handlers
 
-/// Get git status
-pub async fn git_status(
+/// Format the active document
+pub async fn format_active_document(
     State(_state): State<super::ui::AppState>,
-    Query(params): Query<GitStatusRequest>,
 ) -> impl IntoResponse {
-    let git_manager = &_state.git_manager;
-    let workspace_path = _state.ide.config().read().await.workspace_dir();
-    
-    let path = params.path.as_ref()
-        .map(|p| PathBuf::from(p))
-        .unwrap_or(workspace_path);
-    
-    // Check if this is a repository
-    if !git_manager.is_repository().await {
-        return ApiResponse::error("Not a git repository".to_string());
-    }
-    
-    match git_manager.get_status().await {
-        Ok(status) => {
-            info!("Git status retrieved successfully");
-            
-            // Convert GitStatus to a JSON-friendly format
-            let status_json = serde_json::json!({
-                "staged_files": status.staged_files,
-                "unstaged_files": status.unstaged_files,
-                "untracked_files": status.untracked_files,
-                "ahead_count": status.ahead_count,
-                "behind_count": status.behind_count
-            });
-            
-            ApiResponse::success(status_json)
+    // The formatter runs without the editor lock, so other requests are not held up
+    let editor = _state.ide.editor();
+    let job = editor.lock().await.format_active_job().await;
+    let result = match job {
+        Ok(job) => {
+            let formatted = job.run().await;
+            editor.lock().await.apply_format(formatted).await
+        }
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(result) => {
+            info!("Formatted active document with {}", result.formatter.as_deref().unwrap_or("built-in fallback"));
+            ApiResponse::success(result)
         }
         Err(e) => {
-            error!("Git status failed: {}", e);
-            ApiResponse::error(format!("Git status failed: {}", e))
+            warn!("Format failed: {}", e);
+            ApiResponse::ide_error("Format failed", e)
         }
     }
 }
 
-/// Get git branches
-pub async fn git_branches(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
-    let git_manager = &_state.git_manager;
-    
-    // Check if this is a repository
-    if !git_manager.is_repository().await {
-        return ApiResponse::error("Not a git repository".to_string());
-    }
-    
-    match git_manager.get_branches().await {
-        Ok(branches) => {
-            info!("Retrieved {} git branches", branches.len());
-            ApiResponse::success(branches)
-        }
+/// Convert a document's indentation between tabs and spaces
+pub async fn convert_indentation(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<ConvertIndentRequest>,
+) -> impl IntoResponse {
+    let editor = _state.ide.editor();
+    let editor_lock = editor.lock().await;
+
+    match editor_lock.convert_indentation(&request.document_id, request.indent_style, request.indent_size).await {
+        Ok(changed) => ApiResponse::success(serde_json::json!({
+            "document_id": request.document_id,
+            "changed": changed,
+        })),
         Err(e) => {
-            error!("Git branches failed: {}", e);
-            ApiResponse::error(format!("Git branches failed: {}", e))
+            warn!("Indentation conversion failed: {}", e);
+            ApiResponse::ide_error("Indentation conversion failed", e)
         }
     }
 }
 
-/// Commit changes
-pub async fn git_commit(
+/// Record the selection in a document, for the selection AI actions
+pub async fn set_selection(
     State(_state): State<super::ui::AppState>,
-    Json(request): Json<serde_json::Value>,
+    Json(request): Json<SetSelectionRequest>,
 ) -> impl IntoResponse {
-    let git_manager = &_state.git_manager;
-    
-    // Check if this is a repository
-    if !git_manager.is_repository().await {
-        return ApiResponse::error("Not a git repository".to_string());
-    }
-    
-    let message = request.get("message")
-        .and_then(|v| v.as_str())
-        .unwrap_or("Commit from Super IDE");
-    
-    // First stage all changes, then commit
-    let all_files = vec![".".to_string()];
-    match git_manager.stage_files(&all_files).await {
-        Ok(_) => {
-            match git_manager.commit(message).await {
-                Ok(commit_hash) => {
-                    info!("Git commit successful: {} - {}", commit_hash, message);
-                    ApiResponse::success(format!("Commit successful: {}", commit_hash))
-                }
-                Err(e) => {
-                    error!("Git commit failed: {}", e);
-                    ApiResponse::error(format!("Git commit failed: {}", e))
-                }
-            }
-        }
+    let selection = request.selection.map(|range| crate::editor::Selection {
+        start: crate::editor::CursorPosition { line: range.start_line, column: range.start_column },
+        end: crate::editor::CursorPosition { line: range.end_line, column: range.end_column },
+        is_rectangular: range.rectangular,
+    });
+
+    let editor = _state.ide.editor();
+    let editor_lock = editor.lock().await;
+    match editor_lock.set_selection(&request.document_id, selection).await {
+        Ok(()) => ApiResponse::success(request.document_id),
         Err(e) => {
-            error!("Git stage failed: {}", e);
-            ApiResponse::error(format!("Git stage failed: {}", e))
+            warn!("Setting the selection failed: {}", e);
+            ApiResponse::ide_error("Setting the selection failed", e)
         }
     }
 }
 
-// Enhanced Git Handlers
-
-/// Push changes to remote
-pub async fn git_push(
+/// Switch to an open document, recording the jump in the navigation history
+pub async fn activate_document(
     State(_state): State<super::ui::AppState>,
-    Json(request): Json<serde_json::Value>,
+    Json(request): Json<ActivateDocumentRequest>,
 ) -> impl IntoResponse {
-    let git_manager = &_state.git_manager;
-    
-    // Check if this is a repository
-    if !git_manager.is_repository().await {
-        return ApiResponse::error("Not a git repository".to_string());
-    }
-    
-    let remote = request.get("remote").and_then(|v| v.as_str());
-    let branch = request.get("branch").and_then(|v| v.as_str());
-    
-    match git_manager.push(remote, branch).await {
-        Ok(message) => {
-            info!("Git push successful");
-            ApiResponse::success(message)
-        }
+    let editor = _state.ide.editor();
+    let editor_lock = editor.lock().await;
+    match editor_lock.activate_document(&request.document_id).await {
+        Ok(()) => ApiResponse::success(request.document_id),
         Err(e) => {
-            error!("Git push failed: {}", e);
-            ApiResponse::error(format!("Git push failed: {}", e))
+            warn!("Activating the document failed: {}", e);
+            ApiResponse::ide_error("Activating the document failed", e)
         }
     }
 }
 
-/// Pull changes from remote
-pub async fn git_pull(
+/// Reload a document from its file, discarding its edits
+pub async fn revert_document(
     State(_state): State<super::ui::AppState>,
-    Json(request): Json<serde_json::Value>,
+    Json(request): Json<RevertDocumentRequest>,
 ) -> impl IntoResponse {
-    let git_manager = &_state.git_manager;
-    
-    // Check if this is a repository
-    if !git_manager.is_repository().await {
-        return ApiResponse::error("Not a git repository".to_string());
+    let editor = _state.ide.editor();
+    let editor_lock = editor.lock().await;
+    match editor_lock.revert_document(&request.document_id, request.confirm).await {
+        Ok(()) => ApiResponse::success(request.document_id),
+        Err(e) => {
+            warn!("Reverting the document failed: {}", e);
+            ApiResponse::ide_error("Reverting the document failed", e)
+        }
     }
-    
-    let remote = request.get("remote").and_then(|v| v.as_str());
-    let branch = request.get("branch").and_then(|v| v.as_str());
-    
-    match git_manager.pull(remote, branch).await {
-        Ok(message) => {
-            info!("Git pull successful");
-            ApiResponse::success(message)
+}
+
+/// Go back to where the last jump left from; `null` when there is nowhere to go
+pub async fn navigate_back(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
+    let editor = _state.ide.editor();
+    let editor_lock = editor.lock().await;
+    match editor_lock.navigate_back().await {
+        Ok(entry) => ApiResponse::success(entry),
+        Err(e) => {
+            warn!("Navigating back failed: {}", e);
+            ApiResponse::ide_error("Navigating back failed", e)
         }
+    }
+}
+
+/// Undo the last navigation back; `null` when there is nothing to redo
+pub async fn navigate_forward(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
+    let editor = _state.ide.editor();
+    let editor_lock = editor.lock().await;
+    match editor_lock.navigate_forward().await {
+        Ok(entry) => ApiResponse::success(entry),
         Err(e) => {
-            error!("Git pull failed: {}", e);
-            ApiResponse::error(format!("Git pull failed: {}", e))
+            warn!("Navigating forward failed: {}", e);
+            ApiResponse::ide_error("Navigating forward failed", e)
         }
     }
 }
 
-/// Get file diff
+/// Files focused in the editor, most recent first
+pub async fn recent_files(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
+    let editor = _state.ide.editor();
+    let recent = editor.lock().await.recent_files().await;
+    ApiResponse::success(recent)
+}
+
+/// Nested symbols of a document with their line ranges
+pub async fn get_document_outline(
+    State(_state): State<super::ui::AppState>,
+    Path(document_id): Path<String>,
+) -> impl IntoResponse {
+    let editor = _state.ide.editor();
+    let editor_lock = editor.lock().await;
+    match editor_lock.document_outline(&document_id).await {
+        Ok(outline) => ApiResponse::success(outline),
+        Err(e) => {
+            warn!("Outline failed: {}", e);
+            ApiResponse::ide_error("Outline failed", e)
+        }
+    }
+}
+
+/// Place a document's cursors for multi-cursor edits
+pub async fn set_cursors(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<SetCursorsRequest>,
+) -> impl IntoResponse {
+    let editor = _state.ide.editor();
+    let editor_lock = editor.lock().await;
+    match editor_lock.set_cursors(&request.document_id, request.cursors).await {
+        Ok(()) => ApiResponse::success(request.document_id),
+        Err(e) => {
+            warn!("Setting the cursors failed: {}", e);
+            ApiResponse::ide_error("Setting the cursors failed", e)
+        }
+    }
+}
+
+/// Insert text at every cursor, returning the moved cursors
+pub async fn insert_at_cursors(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<CursorInsertRequest>,
+) -> impl IntoResponse {
+    let editor = _state.ide.editor();
+    let editor_lock = editor.lock().await;
+    match editor_lock.insert_at_cursors(&request.document_id, &request.text).await {
+        Ok(cursors) => ApiResponse::success(cursors),
+        Err(e) => {
+            warn!("Multi-cursor insert failed: {}", e);
+            ApiResponse::ide_error("Multi-cursor insert failed", e)
+        }
+    }
+}
+
+/// Delete characters before every cursor, returning the moved cursors
+pub async fn delete_at_cursors(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<CursorDeleteRequest>,
+) -> impl IntoResponse {
+    let editor = _state.ide.editor();
+    let editor_lock = editor.lock().await;
+    match editor_lock.delete_at_cursors(&request.document_id, request.count).await {
+        Ok(cursors) => ApiResponse::success(cursors),
+        Err(e) => {
+            warn!("Multi-cursor delete failed: {}", e);
+            ApiResponse::ide_error("Multi-cursor delete failed", e)
+        }
+    }
+}
+
+/// Run `explain`, `improve`, `tests` or `translate` on the active document's
+/// selection, or on the whole document when nothing is selected
+pub async fn selection_action(
+    State(_state): State<super::ui::AppState>,
+    Path(action): Path<String>,
+    request: Option<Json<SelectionActionRequest>>,
+) -> impl IntoResponse {
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+
+    let (code, language) = {
+        let editor = _state.ide.editor();
+        let editor_lock = editor.lock().await;
+        let Some(active) = editor_lock.get_active_document().await else {
+            let mut response = ApiResponse::error("No active document".to_string());
+            response.error_code = Some("invalid_request".to_string());
+            return response;
+        };
+        let (document_id, language) = {
+            let doc = active.read().await;
+            (doc.id.clone(), doc.language.to_lowercase())
+        };
+        match editor_lock.selection_text(&document_id).await {
+            Ok(code) => (code, language),
+            Err(e) => return ApiResponse::ide_error("Reading the selection failed", e),
+        }
+    };
+
+    let ai_engine = _state.ide.ai_engine();
+    let result = match action.as_str() {
+        "explain" => ai_engine.explain_code(crate::ai::CodeExplanationRequest {
+            code,
+            language,
+            context: None,
+            explanation_level: match request.level.as_deref() {
+                Some("basic") => crate::ai::ExplanationLevel::Basic,
+                Some("expert") => crate::ai::ExplanationLevel::Expert,
+                _ => crate::ai::ExplanationLevel::Detailed,
+            },
+            no_cache: request.no_cache,
+        }).await.and_then(|explanation| Ok(serde_json::to_value(explanation)?)),
+        "improve" => ai_engine.suggest_improvements(&code, &language).await
+            .map(|suggestions| serde_json::json!(suggestions)),
+        "tests" => ai_engine.generate_tests(&code, &language, request.test_framework.as_deref()).await
+            .map(|tests| serde_json::json!(tests)),
+        "translate" => {
+            let to_language = request.to_language.as_deref().unwrap_or("python");
+            ai_engine.complete_code(crate::ai::CompletionRequest {
+                prompt: translation_prompt(&code, &language, to_language, true),
+                context: code.clone(),
+                language: language.clone(),
+                max_tokens: Some(2000),
+                position: None,
+                cursor_position: None,
+                text_before_cursor: String::new(),
+                document: None,
+            }).await.map(|completion| serde_json::json!({
+                "translatedCode": completion.text,
+                "fromLanguage": language,
+                "toLanguage": to_language,
+            }))
+        }
+        _ => {
+            let mut response = ApiResponse::error(format!("Unknown selection action: {}", action));
+            response.error_code = Some("not_found".to_string());
+            return response;
+        }
+    };
+
+    match result {
+        Ok(value) => {
+            info!("Selection {} completed", action);
+            ApiResponse::success(value)
+        }
+        Err(e) => {
+            error!("Selection {} failed: {}", action, e);
+            let mut response = ApiResponse::ai_error(&format!("Selection {} failed", action), &e);
+            if e.downcast_ref::<crate::ai::testgen::TestFrameworkError>().is_some() {
+                response.error_code = Some("unsupported_test_framework".to_string());
+            }
+            response
+        }
+    }
+}
+
+/// Fuzzy search symbols across the workspace
+pub async fn search_symbols(
+    State(_state): State<super::ui::AppState>,
+    Query(params): Query<SymbolQuery>,
+) -> impl IntoResponse {
+    match _state.ide.symbol_index().await {
+        Ok(index) => ApiResponse::success(index.search(&params.query, params.limit.unwrap_or(50)).await),
+        Err(e) => {
+            error!("Symbol index unavailable: {}", e);
+            ApiResponse::error(format!("Symbol search failed: {}", e))
+        }
+    }
+}
+
+/// Candidate definition locations for a symbol name
+pub async fn symbol_definition(
+    State(_state): State<super::ui::AppState>,
+    Query(params): Query<SymbolDefinitionQuery>,
+) -> impl IntoResponse {
+    match _state.ide.symbol_index().await {
+        Ok(index) => ApiResponse::success(index.definitions(&params.name).await),
+        Err(e) => {
+            error!("Symbol index unavailable: {}", e);
+            ApiResponse::error(format!("Symbol lookup failed: {}", e))
+        }
+    }
+}
+
+/// Rename a symbol across the project: without `confirm` this only returns the
+/// plan, and confirming applies it if it still matches the previewed fingerprint
+pub async fn rename_symbol(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<RenameSymbolRequest>,
+) -> impl IntoResponse {
+    use crate::editor::refactor::RenameError;
+    
+    let index = match _state.ide.symbol_index().await {
+        Ok(index) => index,
+        Err(e) => {
+            error!("Symbol index unavailable: {}", e);
+            return ApiResponse::error(format!("Rename failed: {}", e));
+        }
+    };
+    let rename_error = |e: RenameError| {
+        let mut response = ApiResponse::error(format!("Rename failed: {}", e));
+        response.error_code = Some(match e {
+            RenameError::InvalidName(_) => "invalid_request",
+            RenameError::SymbolNotFound(_) => "not_found",
+            RenameError::Stale(_) => "conflict",
+            RenameError::Io(_) => "io_error",
+        }.to_string());
+        response
+    };
+    
+    let plan = match index.rename_symbol_project(&request.old_name, &request.new_name).await {
+        Ok(plan) => plan,
+        Err(e) => return rename_error(e),
+    };
+    if !request.confirm {
+        return ApiResponse::success(plan);
+    }
+    if request.fingerprint.as_deref() != Some(plan.fingerprint.as_str()) {
+        let mut response = ApiResponse::error("Rename failed: the project changed since the rename was previewed".to_string());
+        response.error_code = Some("conflict".to_string());
+        return response;
+    }
+    
+    match plan.apply(index.root()).await {
+        Ok(files) => {
+            info!("Renamed {} to {} in {} files", plan.old_name, plan.new_name, files.len());
+            for file in files {
+                let _ = _state.event_bus.broadcast(crate::utils::event_bus::IdeEvent::FileChanged {
+                    path: file,
+                    event_type: crate::utils::event_bus::FileEventType::Modified,
+                });
+            }
+            ApiResponse::success(plan)
+        }
+        Err(e) => rename_error(e),
+    }
+}
+
+/// Code chunks closest in meaning to a natural-language query
+pub async fn semantic_search(
+    State(_state): State<super::ui::AppState>,
+    Query(params): Query<SemanticSearchQuery>,
+) -> impl IntoResponse {
+    if params.query.trim().is_empty() {
+        let mut response = ApiResponse::error("Query must not be empty".to_string());
+        response.error_code = Some("invalid_request".to_string());
+        return response;
+    }
+
+    let index = match _state.ide.semantic_index().await {
+        Ok(index) => index,
+        Err(e) => {
+            error!("Semantic index unavailable: {}", e);
+            return ApiResponse::ide_error("Semantic search failed", e);
+        }
+    };
+    match index.search(&params.query, params.limit.unwrap_or(10)).await {
+        Ok(matches) => ApiResponse::success(matches),
+        Err(e) => {
+            error!("Semantic search failed: {}", e);
+            ApiResponse::ai_error("Semantic search failed", &e)
+        }
+    }
+}
+
+// Terminal Handlers
+
+/// Get recent terminal output so a reconnecting client can repaint
+pub async fn terminal_scrollback(
+    State(_state): State<super::ui::AppState>,
+    Path(session): Path<String>,
+    Query(params): Query<ScrollbackQuery>,
+) -> impl IntoResponse {
+    let lines = params.lines.unwrap_or(500);
+    
+    match _state.ide.terminal_manager().scrollback(&session, lines).await {
+        Ok(scrollback) => ApiResponse::success(scrollback),
+        Err(e) => {
+            warn!("Scrollback unavailable for terminal {}: {}", session, e);
+            ApiResponse::error(format!("Scrollback failed: {}", e))
+        }
+    }
+}
+
+// Git Handlers
+
+/// Get git status
+pub async fn git_status(
+    State(_state): State<super::ui::AppState>,
+    Query(params): Query<GitStatusRequest>,
+) -> impl IntoResponse {
+    let git_manager = &_state.git_manager;
+    let workspace_path = _state.ide.config().read().await.workspace_dir();
+    
+    let path = params.path.as_ref()
+        .map(|p| PathBuf::from(p))
+        .unwrap_or(workspace_path);
+    
+    // Check if this is a repository
+    if !git_manager.is_repository().await {
+        return not_a_repository(git_manager);
+    }
+    
+    match git_manager.get_status().await {
+        Ok(status) => {
+            info!("Git status retrieved successfully");
+            
+            // Convert GitStatus to a JSON-friendly format
+            let status_json = serde_json::json!({
+                "staged_files": status.staged_files,
+                "unstaged_files": status.unstaged_files,
+                "untracked_files": status.untracked_files,
+                "ahead_count": status.ahead_count,
+                "behind_count": status.behind_count
+            });
+            
+            ApiResponse::success(status_json)
+        }
+        Err(e) => {
+            error!("Git status failed: {}", e);
+            ApiResponse::ide_error("Git status failed", e)
+        }
+    }
+}
+
+/// Get git branches
+pub async fn git_branches(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
+    let git_manager = &_state.git_manager;
+    
+    // Check if this is a repository
+    if !git_manager.is_repository().await {
+        return not_a_repository(git_manager);
+    }
+    
+    match git_manager.get_branches().await {
+        Ok(branches) => {
+            info!("Retrieved {} git branches", branches.len());
+            ApiResponse::success(branches)
+        }
+        Err(e) => {
+            error!("Git branches failed: {}", e);
+            ApiResponse::ide_error("Git branches failed", e)
+        }
+    }
+}
+
+/// Commit changes
+pub async fn git_commit(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let git_manager = &_state.git_manager;
+    
+    // Check if this is a repository
+    if !git_manager.is_repository().await {
+        return not_a_repository(git_manager);
+    }
+    
+    let message = request.get("message")
+        .and_then(|v| v.as_str())
+        .unwrap_or("Commit from Super IDE");
+    
+    // Stage only the files the caller listed; without a list, commit what is already staged
+    let files = request.get("files")
+        .and_then(|v| v.as_array())
+        .map(|arr| arr.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let run_hooks = request.get("run_hooks").and_then(|v| v.as_bool()).unwrap_or(true);
+    // Likely credentials in the staged changes block the commit unless `allow_secrets` is set
+    let allow_secrets = request.get("allow_secrets").and_then(|v| v.as_bool()).unwrap_or(false);
+    let (sign, scan_for_secrets) = {
+        let config = _state.ide.config().read().await;
+        let sign = request.get("sign").and_then(|v| v.as_bool()).unwrap_or(config.git.sign_commits);
+        (sign, config.security.scan_for_secrets && !allow_secrets)
+    };
+    match git_manager.stage_files_optimized(&files).await {
+        Ok(_) => {
+            if scan_for_secrets {
+                if let Err(e) = git_manager.ensure_no_staged_secrets().await {
+                    warn!("Git commit blocked: {}", e);
+                    return ApiResponse::ide_error("Git commit blocked", e);
+                }
+            }
+            match git_manager.commit(message, run_hooks, sign).await {
+                Ok(commit_hash) => {
+                    info!("Git commit successful: {} - {}", commit_hash, message);
+                    ApiResponse::success(format!("Commit successful: {}", commit_hash))
+                }
+                Err(e) => {
+                    error!("Git commit failed: {}", e);
+                    ApiResponse::ide_error("Git commit failed", e)
+                }
+            }
+        }
+        Err(e) => {
+            error!("Git stage failed: {}", e);
+            ApiResponse::ide_error("Git stage failed", e)
+        }
+    }
+}
+
+/// Stage, commit and optionally push in one call, reporting each step
+pub async fn git_commit_flow(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<GitCommitFlowRequest>,
+) -> impl IntoResponse {
+    let git_manager = &_state.git_manager;
+    
+    if !git_manager.is_repository().await {
+        return not_a_repository(git_manager);
+    }
+    
+    let (sign, block_on_secrets) = {
+        let config = _state.ide.config().read().await;
+        (
+            request.sign.unwrap_or(config.git.sign_commits),
+            config.security.scan_for_secrets && !request.allow_secrets,
+        )
+    };
+    let push = request.push.then(|| (request.remote.as_deref(), request.branch.as_deref()));
+    let result = git_manager.commit_flow(
+        &request.files, &request.message, request.run_hooks, sign, block_on_secrets, push,
+    ).await;
+    
+    match &result.stopped_at {
+        Some(step) => warn!("Git commit flow stopped at {}", step),
+        None => info!("Git commit flow completed: {}", result.commit_hash.as_deref().unwrap_or("")),
+    }
+    ApiResponse::success(result)
+}
+
+// Enhanced Git Handlers
+
+/// Push changes to remote
+pub async fn git_push(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let git_manager = &_state.git_manager;
+    
+    // Check if this is a repository
+    if !git_manager.is_repository().await {
+        return not_a_repository(git_manager);
+    }
+    
+    let remote = request.get("remote").and_then(|v| v.as_str());
+    let branch = request.get("branch").and_then(|v| v.as_str());
+    
+    match git_manager.push(remote, branch).await {
+        Ok(message) => {
+            info!("Git push successful");
+            ApiResponse::success(message)
+        }
+        Err(e) => {
+            error!("Git push failed: {}", e);
+            ApiResponse::ide_error("Git push failed", e)
+        }
+    }
+}
+
+/// Pull changes from remote
+pub async fn git_pull(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let git_manager = &_state.git_manager;
+    
+    // Check if this is a repository
+    if !git_manager.is_repository().await {
+        return not_a_repository(git_manager);
+    }
+    
+    let remote = request.get("remote").and_then(|v| v.as_str());
+    let branch = request.get("branch").and_then(|v| v.as_str());
+    
+    match git_manager.pull(remote, branch).await {
+        Ok(message) => {
+            info!("Git pull successful");
+            ApiResponse::success(message)
+        }
+        Err(e) => {
+            error!("Git pull failed: {}", e);
+            ApiResponse::ide_error("Git pull failed", e)
+        }
+    }
+}
+
+/// Get file diff
 pub async fn git_diff(
     State(_state): State<super::ui::AppState>,
     Query(params): Query<std::collections::HashMap<String, String>>,
@@ -1518,7 +2958,7 @@ pub async fn git_diff(
     
     // Check if this is a repository
     if !git_manager.is_repository().await {
-        return ApiResponse::error("Not a git repository".to_string());
+        return not_a_repository(git_manager);
     }
     
     let file_path = params.get("file").map(|s| s.as_str());
@@ -1531,7 +2971,55 @@ pub async fn git_diff(
         }
         Err(e) => {
             error!("Git diff failed: {}", e);
-            ApiResponse::error(format!("Git diff failed: {}", e))
+            ApiResponse::ide_error("Git diff failed", e)
+        }
+    }
+}
+
+/// Read a file as it existed at a given revision
+pub async fn git_file_at_revision(
+    State(_state): State<super::ui::AppState>,
+    Query(query): Query<GitFileAtRevisionQuery>,
+) -> impl IntoResponse {
+    let git_manager = &_state.git_manager;
+    
+    if !git_manager.is_repository().await {
+        return not_a_repository(git_manager);
+    }
+    
+    match git_manager.show_file_at(&query.rev, &query.path).await {
+        Ok(file) => {
+            info!("Read {} at revision {}", query.path, query.rev);
+            ApiResponse::success(file)
+        }
+        Err(e) => {
+            warn!("Reading {} at revision {} failed: {}", query.path, query.rev, e);
+            ApiResponse::ide_error("Git show failed", e)
+        }
+    }
+}
+
+/// Diff two files or revisions of files. Working-tree paths are resolved against
+/// the repository root; comparing revisions needs a repository.
+pub async fn diff_files(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<DiffRequest>,
+) -> impl IntoResponse {
+    let git_manager = &_state.git_manager;
+    
+    let uses_revisions = request.old.rev.is_some() || request.new.rev.is_some();
+    if uses_revisions && !git_manager.is_repository().await {
+        return not_a_repository(git_manager);
+    }
+    
+    match git_manager.compare_files(&request.old, &request.new).await {
+        Ok(comparison) => {
+            info!("Compared {} with {}", request.old.path, request.new.path);
+            ApiResponse::success(comparison)
+        }
+        Err(e) => {
+            warn!("Comparing {} with {} failed: {}", request.old.path, request.new.path, e);
+            ApiResponse::ide_error("Diff failed", e)
         }
     }
 }
@@ -1545,7 +3033,7 @@ pub async fn git_log(
     
     // Check if this is a repository
     if !git_manager.is_repository().await {
-        return ApiResponse::error("Not a git repository".to_string());
+        return not_a_repository(git_manager);
     }
     
     let limit = params.get("limit").and_then(|v| v.parse::<u32>().ok()).unwrap_or(10);
@@ -1557,7 +3045,68 @@ pub async fn git_log(
         }
         Err(e) => {
             error!("Git log failed: {}", e);
-            ApiResponse::error(format!("Git log failed: {}", e))
+            ApiResponse::ide_error("Git log failed", e)
+        }
+    }
+}
+
+/// List the repository's contributors with their commit counts
+pub async fn git_contributors(
+    State(_state): State<super::ui::AppState>,
+) -> impl IntoResponse {
+    let git_manager = &_state.git_manager;
+    
+    if !git_manager.is_repository().await {
+        return not_a_repository(git_manager);
+    }
+    
+    match git_manager.contributors().await {
+        Ok(contributors) => ApiResponse::success(contributors),
+        Err(e) => {
+            error!("Git contributors failed: {}", e);
+            ApiResponse::ide_error("Git contributors failed", e)
+        }
+    }
+}
+
+/// List submodules with their checkout state
+pub async fn git_submodules(
+    State(_state): State<super::ui::AppState>,
+) -> impl IntoResponse {
+    let git_manager = &_state.git_manager;
+    
+    if !git_manager.is_repository().await {
+        return not_a_repository(git_manager);
+    }
+    
+    match git_manager.submodule_status().await {
+        Ok(submodules) => ApiResponse::success(submodules),
+        Err(e) => {
+            error!("Git submodule status failed: {}", e);
+            ApiResponse::ide_error("Git submodule status failed", e)
+        }
+    }
+}
+
+/// Initialize and update one submodule, or all of them
+pub async fn git_update_submodules(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<GitSubmoduleUpdateRequest>,
+) -> impl IntoResponse {
+    let git_manager = &_state.git_manager;
+    
+    if !git_manager.is_repository().await {
+        return not_a_repository(git_manager);
+    }
+    
+    match git_manager.submodule_update(request.path.as_deref()).await {
+        Ok(output) => {
+            info!("Git submodules updated: {}", request.path.as_deref().unwrap_or("all"));
+            ApiResponse::success(output)
+        }
+        Err(e) => {
+            error!("Git submodule update failed: {}", e);
+            ApiResponse::ide_error("Git submodule update failed", e)
         }
     }
 }
@@ -1571,7 +3120,7 @@ pub async fn git_create_branch(
     
     // Check if this is a repository
     if !git_manager.is_repository().await {
-        return ApiResponse::error("Not a git repository".to_string());
+        return not_a_repository(git_manager);
     }
     
     let branch_name = request.get("name")
@@ -1589,7 +3138,7 @@ pub async fn git_create_branch(
         }
         Err(e) => {
             error!("Git branch creation failed: {}", e);
-            ApiResponse::error(format!("Git branch creation failed: {}", e))
+            ApiResponse::ide_error("Git branch creation failed", e)
         }
     }
 }
@@ -1603,7 +3152,7 @@ pub async fn git_checkout_branch(
     
     // Check if this is a repository
     if !git_manager.is_repository().await {
-        return ApiResponse::error("Not a git repository".to_string());
+        return not_a_repository(git_manager);
     }
     
     let branch_name = request.get("name")
@@ -1621,7 +3170,7 @@ pub async fn git_checkout_branch(
         }
         Err(e) => {
             error!("Git branch checkout failed: {}", e);
-            ApiResponse::error(format!("Git branch checkout failed: {}", e))
+            ApiResponse::ide_error("Git branch checkout failed", e)
         }
     }
 }
@@ -1635,7 +3184,7 @@ pub async fn git_stage_files(
     
     // Check if this is a repository
     if !git_manager.is_repository().await {
-        return ApiResponse::error("Not a git repository".to_string());
+        return not_a_repository(git_manager);
     }
     
     let files = request.get("files")
@@ -1650,7 +3199,109 @@ pub async fn git_stage_files(
         }
         Err(e) => {
             error!("Git staging failed: {}", e);
-            ApiResponse::error(format!("Git staging failed: {}", e))
+            ApiResponse::ide_error("Git staging failed", e)
+        }
+    }
+}
+
+/// Stage one hunk of a file's changes
+pub async fn git_stage_hunk(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<StageHunkRequest>,
+) -> impl IntoResponse {
+    let git_manager = &_state.git_manager;
+    
+    if !git_manager.is_repository().await {
+        return not_a_repository(git_manager);
+    }
+    
+    match git_manager.stage_hunk(&request.file_path, &request.hunk).await {
+        Ok(()) => {
+            info!("Staged hunk at line {} of {}", request.hunk.new_start, request.file_path);
+            ApiResponse::success(format!("Staged hunk in {}", request.file_path))
+        }
+        Err(e) => {
+            warn!("Staging hunk in {} failed: {}", request.file_path, e);
+            ApiResponse::ide_error("Git staging failed", e)
+        }
+    }
+}
+
+/// Squash the last commits of the branch or reword the latest one. Pushed
+/// commits are refused with `commit_pushed` unless `force` is set.
+pub async fn git_rewrite(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<GitRewriteRequest>,
+) -> impl IntoResponse {
+    let git_manager = &_state.git_manager;
+    
+    if !git_manager.is_repository().await {
+        return not_a_repository(git_manager);
+    }
+    
+    let sign_commits = _state.ide.config().read().await.git.sign_commits;
+    let result = match &request {
+        GitRewriteRequest::Squash { count, message, force, sign } => {
+            git_manager.squash_last_n(*count, message, *force, sign.unwrap_or(sign_commits)).await
+        }
+        GitRewriteRequest::Reword { hash, message, force, sign } => {
+            git_manager.reword_commit(hash, message, *force, sign.unwrap_or(sign_commits)).await
+        }
+    };
+    match result {
+        Ok(rewritten) => {
+            info!("Git history rewritten: {} replaced {} commit(s)", rewritten.hash, rewritten.replaced.len());
+            ApiResponse::success(rewritten)
+        }
+        Err(e) => {
+            error!("Git rewrite failed: {}", e);
+            ApiResponse::ide_error("Git rewrite failed", e)
+        }
+    }
+}
+
+/// Take the last commit off the branch. A pushed commit is refused with
+/// `commit_pushed` unless `force` is set.
+pub async fn git_undo_commit(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<GitUndoCommitRequest>,
+) -> impl IntoResponse {
+    let git_manager = &_state.git_manager;
+    
+    if !git_manager.is_repository().await {
+        return not_a_repository(git_manager);
+    }
+    
+    match git_manager.undo_last_commit(request.keep_changes, request.force).await {
+        Ok(undone) => {
+            info!("Git commit undone: {}", undone.hash);
+            ApiResponse::success(undone)
+        }
+        Err(e) => {
+            error!("Git undo commit failed: {}", e);
+            ApiResponse::ide_error("Git undo commit failed", e)
+        }
+    }
+}
+
+/// Likely credentials in the staged changes
+pub async fn git_secret_scan(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
+    let git_manager = &_state.git_manager;
+    
+    if !git_manager.is_repository().await {
+        return not_a_repository(git_manager);
+    }
+    
+    match git_manager.scan_staged_for_secrets().await {
+        Ok(findings) => {
+            if !findings.is_empty() {
+                warn!("Secret scan found {} likely secrets in staged changes", findings.len());
+            }
+            ApiResponse::success(findings)
+        }
+        Err(e) => {
+            error!("Git secret scan failed: {}", e);
+            ApiResponse::ide_error("Git secret scan failed", e)
         }
     }
 }
@@ -1664,7 +3315,7 @@ pub async fn git_unstage_files(
     
     // Check if this is a repository
     if !git_manager.is_repository().await {
-        return ApiResponse::error("Not a git repository".to_string());
+        return not_a_repository(git_manager);
     }
     
     let files = request.get("files")
@@ -1679,7 +3330,7 @@ pub async fn git_unstage_files(
         }
         Err(e) => {
             error!("Git unstaging failed: {}", e);
-            ApiResponse::error(format!("Git unstaging failed: {}", e))
+            ApiResponse::ide_error("Git unstaging failed", e)
         }
     }
 }
@@ -1693,7 +3344,7 @@ pub async fn git_discard_changes(
     
     // Check if this is a repository
     if !git_manager.is_repository().await {
-        return ApiResponse::error("Not a git repository".to_string());
+        return not_a_repository(git_manager);
     }
     
     let files = request.get("files")
@@ -1708,7 +3359,7 @@ pub async fn git_discard_changes(
         }
         Err(e) => {
             error!("Git discard failed: {}", e);
-            ApiResponse::error(format!("Git discard failed: {}", e))
+            ApiResponse::ide_error("Git discard failed", e)
         }
     }
 }
@@ -1716,17 +3367,57 @@ pub async fn git_discard_changes(
 /// Initialize a new git repository
 pub async fn git_init_repository(
     State(_state): State<super::ui::AppState>,
+    body: axum::body::Bytes,
 ) -> impl IntoResponse {
+    // A bodiless POST runs a plain `git init`; a body that is not a request is refused
+    let request: GitInitRequest = if body.iter().all(u8::is_ascii_whitespace) {
+        GitInitRequest::default()
+    } else {
+        match serde_json::from_slice(&body) {
+            Ok(request) => request,
+            Err(e) => {
+                let mut response = ApiResponse::error(format!("Invalid git init request: {}", e));
+                response.error_code = Some("invalid_request".to_string());
+                return response;
+            }
+        }
+    };
     let git_manager = &_state.git_manager;
     
-    match git_manager.init().await {
-        Ok(_) => {
-            info!("Git repository initialized successfully");
-            ApiResponse::success("Git repository initialized successfully")
+    let gitignore = match &request.project_type {
+        Some(project_type) => {
+            let registry = match template_registry() {
+                Ok(registry) => registry,
+                Err(e) => return template_error("Failed to load project templates", e),
+            };
+            let Some(template) = registry.get(project_type) else {
+                return template_error("Git init failed", TemplateError::UnknownTemplate(project_type.clone()));
+            };
+            template.files.iter()
+                .find(|file| file.path == ".gitignore")
+                .map(|file| file.content.clone())
+        }
+        None => None,
+    };
+    let sign = match request.sign {
+        Some(sign) => sign,
+        None => _state.ide.config().read().await.git.sign_commits,
+    };
+    let options = crate::git::InitOptions {
+        default_branch: request.default_branch,
+        gitignore,
+        initial_commit: request.initial_commit,
+        sign,
+    };
+    
+    match git_manager.init_with_options(&options).await {
+        Ok(repository) => {
+            info!("Git repository initialized on branch {}", repository.branch);
+            ApiResponse::success(repository)
         }
         Err(e) => {
             error!("Git init failed: {}", e);
-            ApiResponse::error(format!("Git init failed: {}", e))
+            ApiResponse::ide_error("Git init failed", e)
         }
     }
 }
@@ -1753,6 +3444,79 @@ pub async fn project_info(State(_state): State<super::ui::AppState>) -> impl Int
     ApiResponse::success(project_info)
 }
 
+/// The built-in project templates and the user's own
+fn template_registry() -> Result<TemplateRegistry, TemplateError> {
+    match crate::config::Configuration::templates_dir() {
+        Ok(dir) => TemplateRegistry::load(&dir),
+        Err(_) => Ok(TemplateRegistry::builtin()),
+    }
+}
+
+/// Failed template lookup or rendering, with an error code matching the failure
+fn template_error<T>(context: &str, error: TemplateError) -> ApiResponse<T> {
+    let mut response = ApiResponse::error(format!("{}: {}", context, error));
+    response.error_code = Some(match error {
+        TemplateError::UnknownTemplate(_) => "not_found",
+        TemplateError::UndefinedPlaceholder { .. } => "invalid_request",
+        TemplateError::Invalid { .. } => "config_error",
+        TemplateError::Io(_) => "io_error",
+    }.to_string());
+    response
+}
+
+/// List the templates new projects can be created from
+pub async fn list_project_templates(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
+    match template_registry() {
+        Ok(registry) => ApiResponse::success(registry.list()),
+        Err(e) => template_error("Failed to load project templates", e),
+    }
+}
+
+/// Get the first-run flag, the last opened workspace and the recent projects
+pub async fn recent_projects(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
+    let Some(path) = PersistedState::default_path() else {
+        return ApiResponse::success(PersistedState::default());
+    };
+    match PersistedState::load(&path).await {
+        Ok(state) => ApiResponse::success(state),
+        Err(e) => ApiResponse::error(format!("Failed to read IDE state: {}", e)),
+    }
+}
+
+/// Whether the workspace is trusted to run code
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProjectTrust {
+    pub workspace: PathBuf,
+    pub trusted: bool,
+    /// Whether trust is configured to be needed at all
+    pub required: bool,
+}
+
+fn project_trust_status(trust: &crate::core::trust::WorkspaceTrust) -> ProjectTrust {
+    ProjectTrust {
+        workspace: trust.workspace().to_path_buf(),
+        trusted: trust.is_trusted(),
+        required: trust.is_required(),
+    }
+}
+
+/// Get whether the workspace may run tasks, terminals, formatters and language servers
+pub async fn project_trust(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
+    ApiResponse::success(project_trust_status(_state.ide.workspace_trust()))
+}
+
+/// Trust the workspace to run code, now and in later sessions
+pub async fn trust_project(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
+    let trust = _state.ide.workspace_trust();
+    match trust.grant().await {
+        Ok(()) => ApiResponse::success(project_trust_status(trust)),
+        Err(e) => {
+            error!("Trusting {} failed: {}", trust.workspace().display(), e);
+            ApiResponse::ide_error("Trusting the workspace failed", e)
+        }
+    }
+}
+
 /// Get configuration
 pub async fn get_config(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
     let config = _state.ide.config().read().await;
@@ -1769,6 +3533,140 @@ pub async fn get_config(State(_state): State<super::ui::AppState>) -> impl IntoR
     ApiResponse::success(config_info)
 }
 
+/// List the dependencies declared by the project's manifests
+pub async fn list_dependencies(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
+    let workspace_path = _state.ide.config().read().await.workspace_dir();
+    let inspector = crate::file_ops::dependencies::DependencyInspector::new(workspace_path);
+    
+    match inspector.inspect().await {
+        Ok(dependencies) => ApiResponse::success(dependencies),
+        Err(e) => {
+            error!("Dependency inspection failed: {}", e);
+            let mut response = ApiResponse::error(format!("Dependency inspection failed: {}", e));
+            if matches!(e, crate::file_ops::dependencies::DependencyError::InvalidManifest { .. }) {
+                response.error_code = Some("invalid_manifest".to_string());
+            }
+            response
+        }
+    }
+}
+
+/// Compare the project's dependencies with the latest registry versions
+pub async fn list_outdated_dependencies(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
+    let workspace_path = _state.ide.config().read().await.workspace_dir();
+    let inspector = crate::file_ops::dependencies::DependencyInspector::new(workspace_path);
+    
+    match inspector.inspect().await {
+        Ok(dependencies) => ApiResponse::success(_state.ide.outdated_checker().check(dependencies).await),
+        Err(e) => {
+            error!("Dependency inspection failed: {}", e);
+            let mut response = ApiResponse::error(format!("Dependency inspection failed: {}", e));
+            if matches!(e, crate::file_ops::dependencies::DependencyError::InvalidManifest { .. }) {
+                response.error_code = Some("invalid_manifest".to_string());
+            }
+            response
+        }
+    }
+}
+
+/// Files, lines and bytes per language, with the largest files
+pub async fn project_stats(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
+    let workspace_path = _state.ide.config().read().await.workspace_dir();
+    
+    match _state.ide.project_stats().collect(&workspace_path).await {
+        Ok(stats) => ApiResponse::success(stats),
+        Err(e) => {
+            error!("Project statistics failed: {}", e);
+            ApiResponse::ide_error("Project statistics failed", e)
+        }
+    }
+}
+
+/// List settings that differ from the defaults
+pub async fn get_config_diff(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
+    let config = _state.ide.config().read().await;
+    
+    match config.diff_from_default() {
+        Ok(changes) => ApiResponse::success(changes),
+        Err(e) => {
+            error!("Config diff failed: {}", e);
+            ApiResponse::error(format!("Config diff failed: {}", e))
+        }
+    }
+}
+
+/// Reset the saved configuration to the defaults, keeping a backup of the old file
+pub async fn reset_config(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
+    let path = match crate::config::Configuration::user_config_file() {
+        Ok(path) => path,
+        Err(e) => return ApiResponse::error(format!("Config reset failed: {}", e)),
+    };
+    
+    match crate::config::Configuration::reset_file(&path) {
+        Ok(backup) => {
+            *_state.ide.config().write().await = crate::config::Configuration::default();
+            info!("Configuration reset to defaults at {}", path.display());
+            ApiResponse::success(backup.map(|backup| backup.to_string_lossy().to_string()))
+        }
+        Err(e) => {
+            error!("Config reset failed: {}", e);
+            ApiResponse::error(format!("Config reset failed: {}", e))
+        }
+    }
+}
+
+/// Start a project task such as a build or test run
+pub async fn run_task(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<RunTaskRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = _state.ide.workspace_trust().ensure_trusted() {
+        warn!("Refused to run task {}: {}", request.name, e);
+        return ApiResponse::ide_error("Task refused", e);
+    }
+    let workspace_path = _state.ide.config().read().await.workspace_dir();
+    let cwd = match request.cwd.as_deref() {
+        Some(cwd) => match FileManager::new(workspace_path).resolve(std::path::Path::new(cwd)) {
+            Ok(cwd) => cwd,
+            Err(e) => return ApiResponse::ide_error("Task refused", e),
+        },
+        None => workspace_path,
+    };
+    
+    match _state.ide.task_runner().start(&request.name, &request.command, cwd).await {
+        Ok(task_id) => {
+            info!("Started task {} ({}): {}", request.name, task_id, request.command);
+            ApiResponse::success(task_id)
+        }
+        Err(e) => {
+            error!("Failed to start task {}: {}", request.name, e);
+            ApiResponse::error(format!("Failed to start task: {}", e))
+        }
+    }
+}
+
+/// List project tasks
+pub async fn list_tasks(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
+    ApiResponse::success(_state.ide.task_runner().list().await)
+}
+
+/// Cancel a running project task
+pub async fn cancel_task(
+    State(_state): State<super::ui::AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match _state.ide.task_runner().cancel(&id).await {
+        Ok(task) => {
+            info!("Cancelled task {}", id);
+            ApiResponse::success(task)
+        }
+        Err(e) => {
+            warn!("Failed to cancel task {}: {}", id, e);
+            ApiResponse::error(format!("Failed to cancel task: {}", e))
+        }
+    }
+}
+
 /// Health check
 pub async fn health_check(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
     let ide_state = _state.ide.get_state().await;
@@ -1782,6 +3680,20 @@ pub async fn health_check(State(_state): State<super::ui::AppState>) -> impl Int
     })
 }
 
+/// Readiness check: unlike `/health`, which answers as soon as the process does,
+/// this fails with 503 until the IDE has finished initializing
+pub async fn readiness_check(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
+    if !_state.ide.is_ready() {
+        let mut response = ApiResponse::error("Super IDE is still initializing".to_string());
+        response.error_code = Some("not_ready".to_string());
+        return response;
+    }
+    ApiResponse::success(ReadinessStatus {
+        ready: true,
+        timestamp: Utc::now().to_rfc3339(),
+    })
+}
+
 // Supporting types
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -1790,6 +3702,9 @@ pub struct FileTreeNode {
     pub path: String,
     pub r#type: String, // "file" or "directory"
     pub size: u64,
+    /// Whether the file exceeds the open size limit, so the UI can warn before loading it
+    #[serde(default)]
+    pub too_large: bool,
     pub modified: String,
     pub children: Option<Vec<FileTreeNode>>,
 }
@@ -1801,6 +3716,7 @@ impl From<FileInfo> for FileTreeNode {
             path: entry.path.to_string_lossy().to_string(),
             r#type: if entry.is_directory { "directory".to_string() } else { "file".to_string() },
             size: entry.size,
+            too_large: false,
             modified: entry.modified_at.to_rfc3339(),
             children: None, // Will be populated recursively for directories
         }
@@ -1851,8 +3767,58 @@ pub struct HealthStatus {
     pub timestamp: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadinessStatus {
+    pub ready: bool,
+    pub timestamp: String,
+}
+
+// Command Palette Handlers
+
+/// Commands the palette offers, with their shortcuts and parameter schemas
+pub async fn list_commands(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
+    ApiResponse::success(CommandCatalog::builtin().commands().to_vec())
+}
+
+/// Invoke a palette command; the body holds its arguments and may be omitted
+pub async fn invoke_command(
+    State(_state): State<super::ui::AppState>,
+    Path(id): Path<String>,
+    args: Option<Json<serde_json::Value>>,
+) -> impl IntoResponse {
+    let args = args.map(|Json(args)| args).unwrap_or(serde_json::Value::Null);
+
+    match commands::invoke(&_state, &id, args).await {
+        Ok(result) => {
+            info!("Command {} invoked", id);
+            ApiResponse::success(result)
+        }
+        Err(CommandError::Failed(e)) => {
+            error!("Command {} failed: {}", id, e);
+            ApiResponse::ide_error(&format!("Command {} failed", id), e)
+        }
+        Err(e) => {
+            warn!("Command {} rejected: {}", id, e);
+            let mut response = ApiResponse::error(e.to_string());
+            response.error_code = Some(match e {
+                CommandError::UnknownCommand(_) => "not_found".to_string(),
+                _ => "invalid_request".to_string(),
+            });
+            response
+        }
+    }
+}
+
 // External Integration Handlers
 
+/// Settings of the MCP and browser clients, with requests leaving the machine going through the configured proxy
+async fn external_config(state: &super::ui::AppState) -> crate::external::ExternalConfig {
+    crate::external::ExternalConfig {
+        proxy: state.ide.config().read().await.proxy.clone(),
+        ..crate::external::ExternalConfig::default()
+    }
+}
+
 /// Search Twitter tweets via MCP
 pub async fn mcp_search_tweets(
     State(_state): State<super::ui::AppState>,
@@ -1860,7 +3826,7 @@ pub async fn mcp_search_tweets(
 ) -> impl IntoResponse {
     // For now, we'll create a simple MCP client
     // In a real implementation, this would be managed by the IDE state
-    let config = crate::external::ExternalConfig::default();
+    let config = external_config(&_state).await;
     let client = crate::external::api::McpApiClient::new(config);
 
     match client.search_tweets(request).await {
@@ -1870,7 +3836,7 @@ pub async fn mcp_search_tweets(
         }
         Err(e) => {
             error!("MCP Twitter search failed: {}", e);
-            ApiResponse::error(format!("Twitter search failed: {}", e))
+            ApiResponse::ide_error("Twitter search failed", e)
         }
     }
 }
@@ -1880,7 +3846,7 @@ pub async fn mcp_get_user_info(
     State(_state): State<super::ui::AppState>,
     Json(request): Json<crate::external::api::TwitterUserRequest>,
 ) -> impl IntoResponse {
-    let config = crate::external::ExternalConfig::default();
+    let config = external_config(&_state).await;
     let client = crate::external::api::McpApiClient::new(config);
 
     match client.get_twitter_user_info(request).await {
@@ -1890,7 +3856,7 @@ pub async fn mcp_get_user_info(
         }
         Err(e) => {
             error!("MCP Twitter user info failed: {}", e);
-            ApiResponse::error(format!("Twitter user info failed: {}", e))
+            ApiResponse::ide_error("Twitter user info failed", e)
         }
     }
 }
@@ -1900,7 +3866,7 @@ pub async fn mcp_get_user_tweets(
     State(_state): State<super::ui::AppState>,
     Json(request): Json<crate::external::api::TwitterUserTweetsRequest>,
 ) -> impl IntoResponse {
-    let config = crate::external::ExternalConfig::default();
+    let config = external_config(&_state).await;
     let client = crate::external::api::McpApiClient::new(config);
 
     match client.get_twitter_user_tweets(request).await {
@@ -1910,7 +3876,7 @@ pub async fn mcp_get_user_tweets(
         }
         Err(e) => {
             error!("MCP Twitter user tweets failed: {}", e);
-            ApiResponse::error(format!("Twitter user tweets failed: {}", e))
+            ApiResponse::ide_error("Twitter user tweets failed", e)
         }
     }
 }
@@ -1919,7 +3885,7 @@ pub async fn mcp_get_user_tweets(
 pub async fn mcp_get_functions(
     State(_state): State<super::ui::AppState>,
 ) -> impl IntoResponse {
-    let config = crate::external::ExternalConfig::default();
+    let config = external_config(&_state).await;
     let client = crate::external::api::McpApiClient::new(config);
 
     match client.get_available_functions().await {
@@ -1929,7 +3895,7 @@ pub async fn mcp_get_functions(
         }
         Err(e) => {
             error!("Failed to get MCP functions: {}", e);
-            ApiResponse::error(format!("Failed to get MCP functions: {}", e))
+            ApiResponse::ide_error("Failed to get MCP functions", e)
         }
     }
 }
@@ -1939,7 +3905,7 @@ pub async fn browser_navigate(
     State(_state): State<super::ui::AppState>,
     Json(request): Json<crate::external::browser::BrowserNavigateRequest>,
 ) -> impl IntoResponse {
-    let config = crate::external::ExternalConfig::default();
+    let config = external_config(&_state).await;
     let client = crate::external::browser::BrowserClient::new(config);
 
     match client.navigate(request).await {
@@ -1949,7 +3915,7 @@ pub async fn browser_navigate(
         }
         Err(e) => {
             error!("Browser navigation failed: {}", e);
-            ApiResponse::error(format!("Browser navigation failed: {}", e))
+            ApiResponse::ide_error("Browser navigation failed", e)
         }
     }
 }
@@ -1959,7 +3925,7 @@ pub async fn browser_screenshot(
     State(_state): State<super::ui::AppState>,
     Json(request): Json<crate::external::browser::BrowserScreenshotRequest>,
 ) -> impl IntoResponse {
-    let config = crate::external::ExternalConfig::default();
+    let config = external_config(&_state).await;
     let client = crate::external::browser::BrowserClient::new(config);
 
     match client.screenshot(request).await {
@@ -1971,7 +3937,7 @@ pub async fn browser_screenshot(
         }
         Err(e) => {
             error!("Browser screenshot failed: {}", e);
-            ApiResponse::error(format!("Browser screenshot failed: {}", e))
+            ApiResponse::ide_error("Browser screenshot failed", e)
         }
     }
 }
@@ -1981,7 +3947,7 @@ pub async fn browser_execute_script(
     State(_state): State<super::ui::AppState>,
     Json(request): Json<crate::external::browser::BrowserExecuteScriptRequest>,
 ) -> impl IntoResponse {
-    let config = crate::external::ExternalConfig::default();
+    let config = external_config(&_state).await;
     let client = crate::external::browser::BrowserClient::new(config);
 
     match client.execute_script(request).await {
@@ -1991,7 +3957,7 @@ pub async fn browser_execute_script(
         }
         Err(e) => {
             error!("Browser script execution failed: {}", e);
-            ApiResponse::error(format!("Browser script execution failed: {}", e))
+            ApiResponse::ide_error("Browser script execution failed", e)
         }
     }
 }
@@ -2001,7 +3967,7 @@ pub async fn browser_click(
     State(_state): State<super::ui::AppState>,
     Json(request): Json<crate::external::browser::BrowserClickRequest>,
 ) -> impl IntoResponse {
-    let config = crate::external::ExternalConfig::default();
+    let config = external_config(&_state).await;
     let client = crate::external::browser::BrowserClient::new(config);
 
     match client.click(request).await {
@@ -2011,7 +3977,7 @@ pub async fn browser_click(
         }
         Err(e) => {
             error!("Browser click failed: {}", e);
-            ApiResponse::error(format!("Browser click failed: {}", e))
+            ApiResponse::ide_error("Browser click failed", e)
         }
     }
 }
@@ -2021,7 +3987,7 @@ pub async fn browser_type(
     State(_state): State<super::ui::AppState>,
     Json(request): Json<crate::external::browser::BrowserTypeRequest>,
 ) -> impl IntoResponse {
-    let config = crate::external::ExternalConfig::default();
+    let config = external_config(&_state).await;
     let client = crate::external::browser::BrowserClient::new(config);
 
     match client.type_text(request).await {
@@ -2031,7 +3997,7 @@ pub async fn browser_type(
         }
         Err(e) => {
             error!("Browser text input failed: {}", e);
-            ApiResponse::error(format!("Browser text input failed: {}", e))
+            ApiResponse::ide_error("Browser text input failed", e)
         }
     }
 }
@@ -2041,7 +4007,7 @@ pub async fn browser_wait(
     State(_state): State<super::ui::AppState>,
     Json(request): Json<crate::external::browser::BrowserWaitRequest>,
 ) -> impl IntoResponse {
-    let config = crate::external::ExternalConfig::default();
+    let config = external_config(&_state).await;
     let client = crate::external::browser::BrowserClient::new(config);
 
     match client.wait_for_element(request).await {
@@ -2051,7 +4017,7 @@ pub async fn browser_wait(
         }
         Err(e) => {
             error!("Browser element wait failed: {}", e);
-            ApiResponse::error(format!("Browser element wait failed: {}", e))
+            ApiResponse::ide_error("Browser element wait failed", e)
         }
     }
 }
@@ -2060,7 +4026,7 @@ pub async fn browser_wait(
 pub async fn browser_get_page_info(
     State(_state): State<super::ui::AppState>,
 ) -> impl IntoResponse {
-    let config = crate::external::ExternalConfig::default();
+    let config = external_config(&_state).await;
     let client = crate::external::browser::BrowserClient::new(config);
 
     match client.get_page_info().await {
@@ -2070,7 +4036,7 @@ pub async fn browser_get_page_info(
         }
         Err(e) => {
             error!("Browser page info retrieval failed: {}", e);
-            ApiResponse::error(format!("Browser page info retrieval failed: {}", e))
+            ApiResponse::ide_error("Browser page info retrieval failed", e)
         }
     }
 }
@@ -2088,7 +4054,7 @@ pub async fn browser_get_element_info(
         return ApiResponse::error("Selector is required".to_string());
     }
 
-    let config = crate::external::ExternalConfig::default();
+    let config = external_config(&_state).await;
     let client = crate::external::browser::BrowserClient::new(config);
 
     match client.get_element_info(selector).await {
@@ -2098,7 +4064,7 @@ pub async fn browser_get_element_info(
         }
         Err(e) => {
             error!("Browser element info retrieval failed: {}", e);
-            ApiResponse::error(format!("Browser element info retrieval failed: {}", e))
+            ApiResponse::ide_error("Browser element info retrieval failed", e)
         }
     }
 }
@@ -2107,20 +4073,42 @@ pub async fn browser_get_element_info(
 pub async fn external_status(
     State(_state): State<super::ui::AppState>,
 ) -> impl IntoResponse {
-    let config = crate::external::ExternalConfig::default();
-    let browser_client = crate::external::browser::BrowserClient::new(config.clone());
+    use crate::external::{CapabilityStatus, ExternalResult, ExternalService, ExternalStatus};
 
-    let browser_available = browser_client.is_browser_available().await;
+    let config = external_config(&_state).await;
+    let browser_client = crate::external::browser::BrowserClient::new(config.clone());
+    let mcp_client = crate::external::api::McpApiClient::new(config.clone());
+
+    let capability = |service: ExternalService, capabilities: Vec<String>, probe: ExternalResult<()>| {
+        let (error_code, error) = match probe {
+            Ok(()) => (None, None),
+            Err(e) => {
+                let message = e.to_string();
+                (Some(IdeError::from(e).error_code().to_string()), Some(message))
+            }
+        };
+        CapabilityStatus {
+            available: error.is_none(),
+            endpoint: service.endpoint(&config),
+            capabilities,
+            error_code,
+            error,
+        }
+    };
 
-    // For MCP status, we could check if the server is running
-    // For now, we'll just return basic status
-    let status = serde_json::json!({
-        "mcp_server_port": config.mcp_server_port,
-        "browser_debug_port": config.browser_debug_port,
-        "browser_available": browser_available,
-        "external_api_path": config.external_api_path,
-        "browser_path": config.browser_path,
-    });
+    let mcp_functions = mcp_client.get_available_functions().await
+        .map(|functions| functions.into_iter().map(|function| function.name).collect())
+        .unwrap_or_default();
+    let status = ExternalStatus {
+        browser: capability(
+            ExternalService::Browser,
+            crate::external::browser::BROWSER_ACTIONS.iter().map(|action| action.to_string()).collect(),
+            browser_client.check_available().await,
+        ),
+        mcp: capability(ExternalService::Mcp, mcp_functions, mcp_client.check_reachable().await),
+        external_api_path: config.external_api_path.clone(),
+        browser_path: config.browser_path.clone(),
+    };
 
     info!("External integrations status retrieved");
     ApiResponse::success(status)
@@ -2279,13 +4267,14 @@ pub async fn tutor_chat(
     
     // Create AI completion request with learning context
     let completion_request = crate::ai::CompletionRequest {
-        prompt: format!("[LEARNING TUTOR] {}", request.message),
+        prompt: format!("[LEARNING TUTOR] {}", request.last_user_message().unwrap_or_default()),
         context: request.context.as_ref().and_then(|ctx| ctx.file_content.as_ref()).cloned().unwrap_or_default(),
         language: request.context.as_ref().and_then(|ctx| ctx.language.as_ref()).cloned().unwrap_or_else(|| "rust".to_string()),
         max_tokens: request.settings.and_then(|s| s.max_tokens),
         position: None,
         cursor_position: None,
         text_before_cursor: "",
+        document: None,
     };
     
     match ai_engine.generate_completion(completion_request).await {
@@ -2295,7 +4284,7 @@ pub async fn tutor_chat(
         }
         Err(e) => {
             error!("AI tutor chat failed: {}", e);
-            ApiResponse::error(format!("AI tutor chat failed: {}", e))
+            ApiResponse::ai_error("AI tutor chat failed", &e)
         }
     }
 }
@@ -2340,132 +4329,659 @@ pub async fn create_code_tour(
 pub async fn get_achievements(
     State(_state): State<super::ui::AppState>,
 ) -> impl IntoResponse {
-    let achievements = vec![
-        crate::learning::Achievement {
-            id: "first-function".to_string(),
-            title: "First Function".to_string(),
-            description: "Created your first function".to_string(),
-            icon: "🎯".to_string(),
-            earned_at: chrono::Utc::now(),
-            category: crate::learning::AchievementCategory::FirstSteps,
-        },
-        crate::learning::Achievement {
-            id: "debug-master".to_string(),
-            title: "Debug Master".to_string(),
-            description: "Fixed 10 bugs on your own".to_string(),
-            icon: "🐛".to_string(),
-            earned_at: chrono::Utc::now(),
-            category: crate::learning::AchievementCategory::Debugging,
-        },
-    ];
+    let achievements = vec![
+        crate::learning::Achievement {
+            id: "first-function".to_string(),
+            title: "First Function".to_string(),
+            description: "Created your first function".to_string(),
+            icon: "🎯".to_string(),
+            earned_at: chrono::Utc::now(),
+            category: crate::learning::AchievementCategory::FirstSteps,
+        },
+        crate::learning::Achievement {
+            id: "debug-master".to_string(),
+            title: "Debug Master".to_string(),
+            description: "Fixed 10 bugs on your own".to_string(),
+            icon: "🐛".to_string(),
+            earned_at: chrono::Utc::now(),
+            category: crate::learning::AchievementCategory::Debugging,
+        },
+    ];
+    
+    ApiResponse::success(achievements)
+}
+
+/// Run a code snippet for a learning demo and return what it printed
+#[cfg(feature = "snippet-runner")]
+pub async fn run_snippet(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<RunSnippetRequest>,
+) -> impl IntoResponse {
+    use crate::learning::snippets::{self, SnippetError, SnippetLimits};
+
+    if let Err(e) = _state.ide.workspace_trust().ensure_trusted() {
+        warn!("Refused to run {} snippet: {}", request.language, e);
+        return ApiResponse::ide_error("Snippet refused", e);
+    }
+    match snippets::run_snippet(&request.language, &request.code, &SnippetLimits::default()).await {
+        Ok(output) => {
+            info!("Ran {} snippet in {}ms, exit code {:?}", request.language, output.duration_ms, output.exit_code);
+            ApiResponse::success(output)
+        }
+        Err(e) => {
+            warn!("Failed to run {} snippet: {}", request.language, e);
+            let code = match &e {
+                SnippetError::UnsupportedLanguage(_) => "unsupported_language",
+                SnippetError::TooLarge { .. } => "payload_too_large",
+                SnippetError::ToolMissing(_) => "tool_missing",
+                SnippetError::Io(_) => "io_error",
+            };
+            let mut response = ApiResponse::error(format!("Failed to run snippet: {}", e));
+            response.error_code = Some(code.to_string());
+            response
+        }
+    }
+}
+
+// Phase 4: Enhanced Code Intelligence & Debugging Handlers
+
+/// Advanced code analysis with comprehensive analysis
+pub async fn advanced_code_analysis(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<AdvancedAnalysisRequest>,
+) -> impl IntoResponse {
+    let ai_engine = _state.ide.ai_engine();
+    
+    match ai_engine.advanced_analyze_code(&request.code, &request.language, request.file_path.as_deref()).await {
+        Ok(mut analysis) => {
+            info!("Advanced code analysis completed for {} lines", request.code.lines().count());
+            if let Some(file_path) = request.file_path.as_deref() {
+                let source = match ai_engine.ai_provider().await.as_deref() {
+                    Ok("local") => DiagnosticSource::LocalAnalysis,
+                    _ => DiagnosticSource::AiAnalysis,
+                };
+                _state.ide.diagnostics().publish_analysis(file_path, source, &analysis).await;
+            }
+            if let Some(min_severity) = &request.min_severity {
+                analysis.retain_min_severity(min_severity);
+            }
+            ApiResponse::success(analysis)
+        }
+        Err(e) => {
+            error!("Advanced code analysis failed: {}", e);
+            ApiResponse::ai_error("Analysis failed", &e)
+        }
+    }
+}
+
+/// Analysis the editor runs on its own, such as when a file is saved or edits pause.
+/// Files the `ai.auto_analysis` gates rule out come back skipped, with the reason.
+pub async fn auto_code_analysis(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<AdvancedAnalysisRequest>,
+) -> impl IntoResponse {
+    let ai_engine = _state.ide.ai_engine();
+    
+    match ai_engine.auto_analyze(&request.code, &request.language, request.file_path.as_deref()).await {
+        Ok(mut outcome) => {
+            if let (Some(analysis), Some(file_path)) = (&outcome.analysis, request.file_path.as_deref()) {
+                let source = match ai_engine.ai_provider().await.as_deref() {
+                    Ok("local") => DiagnosticSource::LocalAnalysis,
+                    _ => DiagnosticSource::AiAnalysis,
+                };
+                _state.ide.diagnostics().publish_analysis(file_path, source, analysis).await;
+            }
+            if let (Some(analysis), Some(min_severity)) = (&mut outcome.analysis, &request.min_severity) {
+                analysis.retain_min_severity(min_severity);
+            }
+            ApiResponse::success(outcome)
+        }
+        Err(e) => {
+            error!("Automatic code analysis failed: {}", e);
+            ApiResponse::ai_error("Analysis failed", &e)
+        }
+    }
+}
+
+/// Analyze several files at once, such as the files of a changeset. Files that
+/// fail carry their own error; the batch only fails when it is empty or too large.
+pub async fn analyze_batch(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<BatchAnalysisRequest>,
+) -> impl IntoResponse {
+    if request.files.is_empty() || request.files.len() > MAX_BATCH_ANALYSIS_FILES {
+        let mut response = ApiResponse::error(format!(
+            "A batch must contain between 1 and {} files, got {}",
+            MAX_BATCH_ANALYSIS_FILES,
+            request.files.len()
+        ));
+        response.error_code = Some("invalid_request".to_string());
+        return response;
+    }
+
+    let ai_engine = _state.ide.ai_engine();
+    let batch = ai_engine.analyze_batch(&request.files).await;
+
+    let source = match ai_engine.ai_provider().await.as_deref() {
+        Ok("local") => DiagnosticSource::LocalAnalysis,
+        _ => DiagnosticSource::AiAnalysis,
+    };
+    for file in &batch.files {
+        if let Some(analysis) = &file.analysis {
+            _state.ide.diagnostics().publish_analysis(&file.path, source, analysis).await;
+        }
+    }
+
+    info!(
+        "Batch analysis of {} files completed, {} failed",
+        batch.files.len(),
+        batch.summary.files_failed
+    );
+    ApiResponse::success(batch)
+}
+
+/// Explain how the selected lines came to be from the commits that changed them
+pub async fn explain_history(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<ExplainHistoryRequest>,
+) -> impl IntoResponse {
+    let git_manager = &_state.git_manager;
+    if !git_manager.is_repository().await {
+        return not_a_repository(git_manager);
+    }
+    let escapes = std::path::Path::new(&request.path).components()
+        .any(|component| !matches!(component, std::path::Component::Normal(_) | std::path::Component::CurDir));
+    if escapes || request.start_line == 0 || request.end_line < request.start_line {
+        let mut response = ApiResponse::error(format!(
+            "Invalid selection {}:{}-{}",
+            request.path, request.start_line, request.end_line
+        ));
+        response.error_code = Some("invalid_request".to_string());
+        return response;
+    }
+
+    let code = match tokio::fs::read_to_string(git_manager.repository_root().join(&request.path)).await {
+        Ok(content) => content.lines()
+            .skip(request.start_line as usize - 1)
+            .take((request.end_line - request.start_line) as usize + 1)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Err(e) => return ApiResponse::ide_error(&format!("Failed to read {}", request.path), e),
+    };
+    let history = match git_manager.line_history(&request.path, request.start_line, request.end_line).await {
+        Ok(history) => history,
+        Err(e) => {
+            error!("Line history of {} failed: {}", request.path, e);
+            return ApiResponse::ide_error("Line history failed", e);
+        }
+    };
+
+    match _state.ide.ai_engine().explain_history(&request.path, &code, history).await {
+        Ok(explanation) => ApiResponse::success(explanation),
+        Err(e) => {
+            error!("History explanation failed: {}", e);
+            ApiResponse::ai_error("History explanation failed", &e)
+        }
+    }
+}
+
+/// Write a categorized changelog for a range of commits
+pub async fn generate_changelog(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<ChangelogRequest>,
+) -> impl IntoResponse {
+    let git_manager = &_state.git_manager;
+    if !git_manager.is_repository().await {
+        return not_a_repository(git_manager);
+    }
+
+    let commits = match git_manager.commits_in_range(&request.from, &request.to, request.include_diffs).await {
+        Ok(commits) => commits,
+        Err(e) => {
+            warn!("Commits from {} to {} could not be listed: {}", request.from, request.to, e);
+            return ApiResponse::ide_error("Listing the commit range failed", e);
+        }
+    };
+
+    match _state.ide.ai_engine().changelog(&request.from, &request.to, &commits).await {
+        Ok(changelog) => {
+            info!("Changelog written for {} commits in {} chunks", changelog.commit_count, changelog.chunks);
+            ApiResponse::success(changelog)
+        }
+        Err(e) => {
+            error!("Changelog generation failed: {}", e);
+            ApiResponse::ai_error("Changelog generation failed", &e)
+        }
+    }
+}
+
+/// Advanced code analysis streamed as server-sent events: one `progress` event per
+/// completed phase, then a `complete` event with the full result (or `error`)
+pub async fn advanced_code_analysis_stream(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<AdvancedAnalysisRequest>,
+) -> impl IntoResponse {
+    let ai_engine = _state.ide.ai_engine().clone();
+    let diagnostics = _state.ide.diagnostics().clone();
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<Event>();
+
+    tokio::spawn(async move {
+        let progress_tx = tx.clone();
+        let analysis = ai_engine.analyze_code_in_phases(
+            &request.code,
+            &request.language,
+            request.file_path.as_deref(),
+            |progress| {
+                if let Ok(event) = Event::default().event("progress").json_data(&progress) {
+                    let _ = progress_tx.send(event);
+                }
+            },
+        );
+
+        // Dropping the analysis when the client goes away skips the remaining phases
+        let result = tokio::select! {
+            _ = tx.closed() => {
+                info!("Client disconnected, cancelling streamed analysis");
+                return;
+            }
+            result = analysis => result,
+        };
+
+        let event = match result {
+            Ok(mut analysis) => {
+                info!("Streamed code analysis completed for {} lines", request.code.lines().count());
+                if let Some(file_path) = request.file_path.as_deref() {
+                    let source = match ai_engine.ai_provider().await.as_deref() {
+                        Ok("local") => DiagnosticSource::LocalAnalysis,
+                        _ => DiagnosticSource::AiAnalysis,
+                    };
+                    diagnostics.publish_analysis(file_path, source, &analysis).await;
+                }
+                if let Some(min_severity) = &request.min_severity {
+                    analysis.retain_min_severity(min_severity);
+                }
+                Event::default().event("complete").json_data(&analysis)
+            }
+            Err(e) => {
+                error!("Streamed code analysis failed: {}", e);
+                Event::default().event("error").json_data(serde_json::json!({
+                    "message": format!("Analysis failed: {}", e),
+                }))
+            }
+        };
+        if let Ok(event) = event {
+            let _ = tx.send(event);
+        }
+    });
+
+    let stream = futures::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|event| (Ok::<_, std::convert::Infallible>(event), rx))
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// Get merged diagnostics for a file
+pub async fn get_diagnostics(
+    State(_state): State<super::ui::AppState>,
+    Path(path): Path<String>,
+) -> impl IntoResponse {
+    // The server is started and asked only after the editor lock is released
+    let query = _state.ide.editor().lock().await
+        .language_server_diagnostics(std::path::Path::new(&path))
+        .await;
+    let language_server = match query {
+        Some(query) => query.run().await,
+        None => None,
+    };
+    if let Some(diagnostics) = language_server {
+        _state.ide.diagnostics().publish(&path, DiagnosticSource::LanguageServer, diagnostics).await;
+    }
+    let diagnostics = _state.ide.diagnostics().get(&path).await;
+    info!("Returning {} diagnostics for {}", diagnostics.len(), path);
+    ApiResponse::success(diagnostics)
+}
+
+/// Predict potential bugs in code
+pub async fn predict_bugs(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<BugPredictionRequest>,
+) -> impl IntoResponse {
+    let ai_engine = _state.ide.ai_engine();
+    
+    match ai_engine.predict_bugs(&request.code, &request.language).await {
+        Ok(mut predictions) => {
+            info!("Bug prediction completed, found {} potential issues", predictions.len());
+            if let Some(min_severity) = &request.min_severity {
+                predictions.retain(|prediction| prediction.severity >= *min_severity);
+            }
+            ApiResponse::success(predictions)
+        }
+        Err(e) => {
+            error!("Bug prediction failed: {}", e);
+            ApiResponse::ai_error("Bug prediction failed", &e)
+        }
+    }
+}
+
+/// Analyze security vulnerabilities
+pub async fn analyze_security_vulnerabilities(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<SecurityAnalysisRequest>,
+) -> impl IntoResponse {
+    let ai_engine = _state.ide.ai_engine();
+    
+    match ai_engine.analyze_security(&request.code, &request.language).await {
+        Ok(mut vulnerabilities) => {
+            info!("Security analysis completed, found {} vulnerabilities", vulnerabilities.len());
+            if let Some(min_severity) = &request.min_severity {
+                vulnerabilities.retain(|vulnerability| vulnerability.severity >= *min_severity);
+            }
+            ApiResponse::success(vulnerabilities)
+        }
+        Err(e) => {
+            error!("Security analysis failed: {}", e);
+            ApiResponse::ai_error("Security analysis failed", &e)
+        }
+    }
+}
+
+/// Explain code functionality
+pub async fn explain_code(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<CodeExplanationRequest>,
+) -> impl IntoResponse {
+    let ai_engine = _state.ide.ai_engine();
+    
+    let explanation_request = crate::ai::CodeExplanationRequest {
+        code: request.code,
+        language: request.language,
+        context: request.context,
+        explanation_level: match request.explanation_level.as_str() {
+            "basic" => crate::ai::ExplanationLevel::Basic,
+            "expert" => crate::ai::ExplanationLevel::Expert,
+            _ => crate::ai::ExplanationLevel::Detailed,
+        },
+        no_cache: request.no_cache,
+    };
+    
+    match ai_engine.explain_code(explanation_request).await {
+        Ok(explanation) => {
+            info!("Code explanation completed");
+            ApiResponse::success(explanation)
+        }
+        Err(e) => {
+            error!("Code explanation failed: {}", e);
+            ApiResponse::ai_error("Code explanation failed", &e)
+        }
+    }
+}
+
+/// List models available from the configured AI provider
+pub async fn list_models(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
+    let models = _state.ide.ai_engine().list_models().await;
+    if let Some(reason) = &models.reason {
+        warn!("Model listing for {} unavailable: {}", models.provider, reason);
+    }
+    ApiResponse::success(models)
+}
+
+/// List user-defined AI tools, optionally only those for one language
+pub async fn list_ai_tools(
+    State(_state): State<super::ui::AppState>,
+    Query(query): Query<AiToolQuery>,
+) -> impl IntoResponse {
+    let tools: Vec<crate::ai::tools::AiTool> = _state.ide.tool_registry()
+        .list(query.language.as_deref())
+        .into_iter()
+        .cloned()
+        .collect();
+    ApiResponse::success(tools)
+}
+
+/// The local analysis rules, as the configuration and project have set them
+pub async fn list_analysis_rules(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
+    ApiResponse::success(_state.ide.ai_engine().rules().catalog())
+}
+
+/// Run a user-defined AI tool on the submitted code
+pub async fn run_ai_tool(
+    State(_state): State<super::ui::AppState>,
+    Path(id): Path<String>,
+    Json(input): Json<crate::ai::tools::ToolInput>,
+) -> impl IntoResponse {
+    match _state.ide.tool_registry().run(_state.ide.ai_engine(), &id, &input).await {
+        Ok(output) => {
+            info!("AI tool {} completed", id);
+            ApiResponse::success(output)
+        }
+        Err(e) => {
+            error!("AI tool {} failed: {}", id, e);
+            ApiResponse::ai_error("Tool run failed", &e)
+        }
+    }
+}
+
+/// Generate an onboarding overview of the whole project
+pub async fn project_overview(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<ProjectOverviewRequest>,
+) -> impl IntoResponse {
+    const MAX_FILE_BYTES: usize = 16 * 1024;
+
+    let workspace_path = _state.ide.config().read().await.workspace_dir();
+    let file_manager = FileManager::new(workspace_path.clone());
+
+    let structure = match file_manager.scan_project(request.max_depth.unwrap_or(8).clamp(1, MAX_OVERVIEW_DEPTH)).await {
+        Ok(structure) => structure,
+        Err(e) => {
+            error!("Project scan failed: {}", e);
+            return ApiResponse::error(format!("Project overview failed: {}", e));
+        }
+    };
+
+    let mut layout: Vec<String> = structure.directories.iter()
+        .map(|dir| format!("{}/", dir.path.display()))
+        .chain(structure.files.iter().map(|file| file.path.display().to_string()))
+        .collect();
+    layout.sort();
+
+    let mut files = Vec::new();
+    for file in structure.key_files().into_iter().take(request.max_files.unwrap_or(12).clamp(1, MAX_OVERVIEW_FILES)) {
+        let Ok(mut content) = tokio::fs::read_to_string(workspace_path.join(&file.path)).await else {
+            continue;
+        };
+        if content.len() > MAX_FILE_BYTES {
+            let mut end = MAX_FILE_BYTES;
+            while !content.is_char_boundary(end) {
+                end -= 1;
+            }
+            content.truncate(end);
+        }
+        files.push(crate::ai::ProjectSourceFile {
+            path: file.path.display().to_string(),
+            content,
+        });
+    }
+
+    match _state.ide.ai_engine().project_overview(&layout, &files).await {
+        Ok(overview) => {
+            info!("Project overview generated from {} key files", overview.files_included.len());
+            ApiResponse::success(overview)
+        }
+        Err(e) => {
+            error!("Project overview failed: {}", e);
+            ApiResponse::ai_error("Project overview failed", &e)
+        }
+    }
+}
+
+/// Generate unit tests for code
+pub async fn generate_unit_tests(
+    State(_state): State<super::ui::AppState>,
+    Json(request): Json<TestGenerationRequest>,
+) -> impl IntoResponse {
+    let ai_engine = _state.ide.ai_engine();
     
-    ApiResponse::success(achievements)
+    match ai_engine.generate_tests(&request.code, &request.language, request.test_framework.as_deref()).await {
+        Ok(test_code) => {
+            info!("Unit test generation completed");
+            ApiResponse::success(test_code)
+        }
+        Err(e) => {
+            error!("Unit test generation failed: {}", e);
+            let mut response = ApiResponse::ai_error("Test generation failed", &e);
+            if e.downcast_ref::<crate::ai::testgen::TestFrameworkError>().is_some() {
+                response.error_code = Some("unsupported_test_framework".to_string());
+            }
+            response
+        }
+    }
 }
 
-// Phase 4: Enhanced Code Intelligence & Debugging Handlers
-
-/// Advanced code analysis with comprehensive analysis
-pub async fn advanced_code_analysis(
+/// Generate the doc comment for a single function
+pub async fn generate_docs(
     State(_state): State<super::ui::AppState>,
-    Json(request): Json<AdvancedAnalysisRequest>,
+    Json(request): Json<DocGenerationRequest>,
 ) -> impl IntoResponse {
     let ai_engine = _state.ide.ai_engine();
     
-    match ai_engine.advanced_analyze_code(&request.code, &request.language, request.file_path.as_deref()).await {
-        Ok(analysis) => {
-            info!("Advanced code analysis completed for {} lines", request.code.lines().count());
-            ApiResponse::success(analysis)
+    match ai_engine.generate_doc_comment(&request.code, &request.language).await {
+        Ok(doc) => {
+            info!("Doc comment generated for {} code", request.language);
+            ApiResponse::success(doc)
         }
         Err(e) => {
-            error!("Advanced code analysis failed: {}", e);
-            ApiResponse::error(format!("Analysis failed: {}", e))
+            error!("Doc comment generation failed: {}", e);
+            let mut response = ApiResponse::ai_error("Doc generation failed", &e);
+            match e.downcast_ref::<crate::ai::docs::DocCommentError>() {
+                Some(crate::ai::docs::DocCommentError::UnsupportedLanguage(_)) => {
+                    response.error_code = Some("unsupported_language".to_string());
+                }
+                Some(crate::ai::docs::DocCommentError::NotAComment(_)) => {
+                    response.error_code = Some("invalid_ai_output".to_string());
+                }
+                None => {}
+            }
+            response
         }
     }
 }
 
-/// Predict potential bugs in code
-pub async fn predict_bugs(
+/// Ask the AI for a minimal edit resolving one diagnostic
+pub async fn fix_diagnostic(
     State(_state): State<super::ui::AppState>,
-    Json(request): Json<BugPredictionRequest>,
+    Json(request): Json<FixDiagnosticRequest>,
 ) -> impl IntoResponse {
     let ai_engine = _state.ide.ai_engine();
     
-    match ai_engine.predict_bugs(&request.code, &request.language).await {
-        Ok(predictions) => {
-            info!("Bug prediction completed, found {} potential issues", predictions.len());
-            ApiResponse::success(predictions)
+    match ai_engine.fix_diagnostic(&request.code, &request.language, &request.issue).await {
+        Ok(fix) => {
+            info!("Diagnostic fix generated for line {} ({} lines changed)", request.issue.line, fix.changed_lines.len());
+            ApiResponse::success(fix)
         }
         Err(e) => {
-            error!("Bug prediction failed: {}", e);
-            ApiResponse::error(format!("Bug prediction failed: {}", e))
+            error!("Diagnostic fix failed: {}", e);
+            let mut response = ApiResponse::ai_error("Diagnostic fix failed", &e);
+            match e.downcast_ref::<crate::ai::fix::FixDiagnosticError>() {
+                Some(crate::ai::fix::FixDiagnosticError::LineOutOfRange { .. }) => {
+                    response.error_code = Some("invalid_request".to_string());
+                }
+                Some(_) => {
+                    response.error_code = Some("invalid_ai_output".to_string());
+                }
+                None => {}
+            }
+            response
         }
     }
 }
 
-/// Analyze security vulnerabilities
-pub async fn analyze_security_vulnerabilities(
+/// Ask the AI for parameter and return type annotations
+pub async fn infer_types(
     State(_state): State<super::ui::AppState>,
-    Json(request): Json<SecurityAnalysisRequest>,
+    Json(request): Json<InferTypesRequest>,
 ) -> impl IntoResponse {
     let ai_engine = _state.ide.ai_engine();
-    
-    match ai_engine.analyze_security(&request.code, &request.language).await {
-        Ok(vulnerabilities) => {
-            info!("Security analysis completed, found {} vulnerabilities", vulnerabilities.len());
-            ApiResponse::success(vulnerabilities)
+
+    match ai_engine.infer_types(&request.code, &request.language, request.scope).await {
+        Ok(inferred) => {
+            info!("Types inferred for {} {} function(s)", inferred.signatures.len(), request.language);
+            ApiResponse::success(inferred)
         }
         Err(e) => {
-            error!("Security analysis failed: {}", e);
-            ApiResponse::error(format!("Security analysis failed: {}", e))
+            error!("Type inference failed: {}", e);
+            let mut response = ApiResponse::ai_error("Type inference failed", &e);
+            match e.downcast_ref::<crate::ai::typehints::InferTypesError>() {
+                Some(crate::ai::typehints::InferTypesError::UnsupportedLanguage(_)) => {
+                    response.error_code = Some("unsupported_language".to_string());
+                }
+                Some(crate::ai::typehints::InferTypesError::NoFunction) => {
+                    response.error_code = Some("invalid_request".to_string());
+                }
+                Some(_) => {
+                    response.error_code = Some("invalid_ai_output".to_string());
+                }
+                None => {}
+            }
+            response
         }
     }
 }
 
-/// Explain code functionality
-pub async fn explain_code(
+/// Apply a suggestion with the AI and return the edited file with its diff
+pub async fn suggestion_to_patch(
     State(_state): State<super::ui::AppState>,
-    Json(request): Json<CodeExplanationRequest>,
+    Json(request): Json<SuggestionPatchRequest>,
 ) -> impl IntoResponse {
     let ai_engine = _state.ide.ai_engine();
-    
-    let explanation_request = crate::ai::CodeExplanationRequest {
-        code: request.code,
-        language: request.language,
-        context: request.context,
-        explanation_level: match request.explanation_level.as_str() {
-            "basic" => crate::ai::ExplanationLevel::Basic,
-            "expert" => crate::ai::ExplanationLevel::Expert,
-            _ => crate::ai::ExplanationLevel::Detailed,
-        },
-    };
-    
-    match ai_engine.explain_code(explanation_request).await {
-        Ok(explanation) => {
-            info!("Code explanation completed");
-            ApiResponse::success(explanation)
+
+    match ai_engine.suggestion_to_patch(&request.code, &request.language, &request.suggestion, request.file_path.as_deref()).await {
+        Ok(patch) => {
+            info!("Suggestion applied to {} code ({} diff lines)", request.language, patch.diff.lines().count());
+            ApiResponse::success(patch)
         }
         Err(e) => {
-            error!("Code explanation failed: {}", e);
-            ApiResponse::error(format!("Code explanation failed: {}", e))
+            error!("Suggestion patch failed: {}", e);
+            let mut response = ApiResponse::ai_error("Suggestion patch failed", &e);
+            match e.downcast_ref::<crate::ai::patch::SuggestionPatchError>() {
+                Some(crate::ai::patch::SuggestionPatchError::EmptySuggestion) => {
+                    response.error_code = Some("invalid_request".to_string());
+                }
+                Some(_) => {
+                    response.error_code = Some("invalid_ai_output".to_string());
+                }
+                None => {}
+            }
+            response
         }
     }
 }
 
-/// Generate unit tests for code
-pub async fn generate_unit_tests(
+/// Build a regex from a description and check it against the samples
+pub async fn build_regex(
     State(_state): State<super::ui::AppState>,
-    Json(request): Json<TestGenerationRequest>,
+    Json(request): Json<BuildRegexRequest>,
 ) -> impl IntoResponse {
     let ai_engine = _state.ide.ai_engine();
-    
-    match ai_engine.generate_tests(&request.code, &request.language).await {
-        Ok(test_code) => {
-            info!("Unit test generation completed");
-            ApiResponse::success(test_code)
+
+    match ai_engine.build_regex(&request.description, &request.samples).await {
+        Ok(built) => {
+            info!("Built regex in {} attempt(s), checked on {} sample(s)", built.attempts, built.samples.len());
+            ApiResponse::success(built)
         }
         Err(e) => {
-            error!("Unit test generation failed: {}", e);
-            ApiResponse::error(format!("Test generation failed: {}", e))
+            error!("Regex build failed: {}", e);
+            let mut response = ApiResponse::ai_error("Regex build failed", &e);
+            match e.downcast_ref::<crate::ai::regex_builder::RegexBuildError>() {
+                Some(crate::ai::regex_builder::RegexBuildError::EmptyDescription) => {
+                    response.error_code = Some("invalid_request".to_string());
+                }
+                Some(crate::ai::regex_builder::RegexBuildError::NoWorkingPattern { .. }) => {
+                    response.error_code = Some("invalid_ai_output".to_string());
+                }
+                None => {}
+            }
+            response
         }
     }
 }
@@ -2484,7 +5000,7 @@ pub async fn suggest_improvements(
         }
         Err(e) => {
             error!("Code improvement suggestions failed: {}", e);
-            ApiResponse::error(format!("Improvement suggestions failed: {}", e))
+            ApiResponse::ai_error("Improvement suggestions failed", &e)
         }
     }
 }
@@ -2855,18 +5371,87 @@ pub async fn get_collaboration_document(
     }
 }
 
-/// WebSocket handler for real-time collaboration
+/// WebSocket handler for real-time collaboration: sends the session's events to
+/// the client and takes the participant's presence, sent as `UserPresence` JSON.
+/// Only users who joined the session may connect.
 pub async fn collaboration_websocket(
+    ws: WebSocketUpgrade,
     State(_state): State<super::ui::AppState>,
     Path(session_id): Path<String>,
-) -> impl IntoResponse {
-    // TODO: Implement WebSocket handler for real-time collaboration
-    // This would handle:
-    // - Real-time operation synchronization
-    // - Live cursor/selection updates
-    // - User presence notifications
-    // - Comment updates
-    ApiResponse::success("WebSocket endpoint - implementation pending")
+    Query(query): Query<CollaborationSocketQuery>,
+) -> axum::response::Response {
+    let collaboration_manager = _state.ide.collaboration_manager().clone();
+    let participants = collaboration_manager.get_session_participants(&session_id).await;
+    let events = collaboration_manager.get_event_broadcaster(&session_id).await;
+    let (Some(participants), Some(events)) = (participants, events) else {
+        let mut response = ApiResponse::<()>::error("Session not found".to_string());
+        response.error_code = Some("not_found".to_string());
+        return response.into_response();
+    };
+    if !participants.contains_key(&query.user_id) {
+        let mut response = ApiResponse::<()>::error(format!("{} has not joined session {}", query.user_id, session_id));
+        response.error_code = Some("permission_denied".to_string());
+        return response.into_response();
+    }
+    ws.on_upgrade(move |socket| collaboration_connection(socket, collaboration_manager, session_id, query.user_id, events))
+        .into_response()
+}
+
+/// Relay events and presence until the client disconnects, then take the
+/// participant out of the session unless they are still connected elsewhere
+async fn collaboration_connection(
+    mut socket: WebSocket,
+    manager: Arc<CollaborationManager>,
+    session_id: String,
+    user_id: String,
+    mut events: tokio::sync::broadcast::Receiver<CollaborationEvent>,
+) {
+    manager.connection_opened(&session_id, &user_id).await;
+    // An idle but connected participant is not silent
+    let mut keepalive = tokio::time::interval(crate::collaboration::presence::PRESENCE_TIMEOUT / 3);
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Collaboration client {} missed {} event(s)", user_id, skipped);
+                        continue;
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                };
+                // Clients draw their own cursor already
+                if matches!(&event, CollaborationEvent::PresenceChanged { presence } if presence.user_id == user_id) {
+                    continue;
+                }
+                let Ok(json) = serde_json::to_string(&event) else { continue };
+                if socket.send(Message::Text(json)).await.is_err() {
+                    break;
+                }
+            }
+            message = socket.recv() => {
+                match message {
+                    Some(Ok(Message::Text(text))) => match serde_json::from_str::<UserPresence>(&text) {
+                        Ok(mut presence) => {
+                            presence.user_id = user_id.clone();
+                            if let Err(e) = manager.update_presence(&session_id, presence).await {
+                                warn!("Failed to update presence of {}: {}", user_id, e);
+                            }
+                        }
+                        Err(e) => warn!("Ignoring collaboration message from {}: {}", user_id, e),
+                    },
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    _ => {}
+                }
+            }
+            _ = keepalive.tick() => {
+                let _ = manager.touch_presence(&session_id, &user_id).await;
+            }
+        }
+    }
+    if let Err(e) = manager.connection_closed(&session_id, &user_id).await {
+        warn!("Failed to remove {} from session {}: {}", user_id, session_id, e);
+    }
 }
 
 /// Smart search across project
@@ -2908,28 +5493,134 @@ pub async fn search_files_advanced(
     ApiResponse::success(results)
 }
 
-/// Enable auto-save
-pub async fn enable_autosave(
+/// Search file contents across the workspace
+pub async fn grep_workspace(
     State(_state): State<super::ui::AppState>,
-    Json(request): Json<serde_json::Value>,
+    Json(request): Json<GrepRequest>,
 ) -> impl IntoResponse {
-    // TODO: Implement auto-save functionality
-    // This would include:
-    // - Periodic file saving
-    // - Cloud synchronization
-    // - Conflict resolution
-    // - Backup management
+    let workspace_path = _state.ide.config().read().await.workspace_dir();
+    let file_manager = FileManager::new(workspace_path);
     
+    match file_manager.grep(&request.pattern, &request.options).await {
+        Ok(results) => {
+            info!("Grep for {:?} found {} matches in {} files", request.pattern, results.total_matches, results.files.len());
+            ApiResponse::success(results)
+        }
+        Err(e) => {
+            error!("Grep failed: {}", e);
+            ApiResponse::ide_error("Search failed", e)
+        }
+    }
+}
+
+/// Stream search results over a WebSocket as the workspace is walked
+pub async fn search_websocket(
+    ws: WebSocketUpgrade,
+    State(_state): State<super::ui::AppState>,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| search_connection(socket, _state))
+}
+
+async fn send_search_event(socket: &mut WebSocket, event: &SearchStreamEvent) -> bool {
+    let Ok(json) = serde_json::to_string(event) else { return true };
+    socket.send(Message::Text(json)).await.is_ok()
+}
+
+/// Run one search per `search` message, streaming results until the walk ends, the
+/// client cancels it or the client disconnects
+async fn search_connection(mut socket: WebSocket, state: super::ui::AppState) {
+    let workspace_path = state.ide.config().read().await.workspace_dir();
+
+    while let Some(Ok(message)) = socket.recv().await {
+        let Message::Text(text) = message else {
+            if matches!(message, Message::Close(_)) {
+                return;
+            }
+            continue;
+        };
+        let request = match serde_json::from_str::<SearchStreamRequest>(&text) {
+            Ok(SearchStreamRequest::Search(request)) => request,
+            // Nothing is running between searches
+            Ok(SearchStreamRequest::Cancel) => continue,
+            Err(e) => {
+                let event = SearchStreamEvent::Error { message: format!("Invalid search request: {}", e) };
+                if !send_search_event(&mut socket, &event).await {
+                    return;
+                }
+                continue;
+            }
+        };
+
+        let cancelled = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let (results_tx, mut results_rx) = tokio::sync::mpsc::unbounded_channel();
+        let walk = {
+            let file_manager = FileManager::new(workspace_path.clone());
+            let cancelled = cancelled.clone();
+            tokio::spawn(async move {
+                file_manager.grep_streaming(&request.pattern, &request.options, &cancelled, |file| {
+                    results_tx.send(file).is_ok()
+                }).await
+            })
+        };
+
+        // Results stop arriving once the walk has finished and dropped the sender
+        let mut connected = true;
+        loop {
+            tokio::select! {
+                file = results_rx.recv() => {
+                    let Some(file) = file else { break };
+                    if connected && !send_search_event(&mut socket, &SearchStreamEvent::Result(file)).await {
+                        connected = false;
+                        cancelled.store(true, std::sync::atomic::Ordering::Relaxed);
+                    }
+                }
+                message = socket.recv(), if connected => match message {
+                    Some(Ok(Message::Text(text))) => {
+                        if matches!(serde_json::from_str::<SearchStreamRequest>(&text), Ok(SearchStreamRequest::Cancel)) {
+                            cancelled.store(true, std::sync::atomic::Ordering::Relaxed);
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                        connected = false;
+                        cancelled.store(true, std::sync::atomic::Ordering::Relaxed);
+                    }
+                    _ => {}
+                },
+            }
+        }
+        if !connected {
+            return;
+        }
+
+        let event = match walk.await {
+            Ok(Ok(summary)) => {
+                info!("Streamed search found {} matches in {} files", summary.total_matches, summary.files_with_matches);
+                SearchStreamEvent::Done(summary)
+            }
+            Ok(Err(e)) => SearchStreamEvent::Error { message: format!("Search failed: {}", e) },
+            Err(e) => SearchStreamEvent::Error { message: format!("Search failed: {}", e) },
+        };
+        if !send_search_event(&mut socket, &event).await {
+            return;
+        }
+    }
+}
+
+/// Turn the autosave timer back on; it saves every `ide.auto_save_interval` seconds
+pub async fn enable_autosave(
+    State(_state): State<super::ui::AppState>,
+) -> impl IntoResponse {
+    _state.ide.set_autosave_enabled(true);
+    info!("Autosave enabled");
     ApiResponse::success("Auto-save enabled")
 }
 
-/// Disable auto-save
+/// Turn the autosave timer off until it is enabled again
 pub async fn disable_autosave(
     State(_state): State<super::ui::AppState>,
-    Json(request): Json<serde_json::Value>,
 ) -> impl IntoResponse {
-    // TODO: Implement auto-save disable logic
-    
+    _state.ide.set_autosave_enabled(false);
+    info!("Autosave disabled");
     ApiResponse::success("Auto-save disabled")
 }
 
@@ -2937,9 +5628,10 @@ pub async fn disable_autosave(
 pub async fn get_autosave_status(
     State(_state): State<super::ui::AppState>,
 ) -> impl IntoResponse {
+    let interval = _state.ide.config().read().await.ide.auto_save_interval;
     let status = serde_json::json!({
-        "enabled": false,
-        "interval": 30,
+        "enabled": _state.ide.is_autosave_enabled().await,
+        "interval": interval,
         "last_save": null,
         "pending_changes": 0
     });
@@ -2947,24 +5639,130 @@ pub async fn get_autosave_status(
     ApiResponse::success(status)
 }
 
-/// Test API endpoint
+/// Save every modified document now, as the autosave timer does. Files changed on
+/// disk are handled by the `editor.save_conflict_strategy` setting, and each
+/// document's outcome is reported.
+pub async fn run_autosave(State(_state): State<super::ui::AppState>) -> impl IntoResponse {
+    let entries = _state.ide.editor().lock().await.autosave().await;
+    let conflicts = entries.iter()
+        .filter(|entry| entry.status == crate::editor::save_conflict::AutosaveStatus::Conflict)
+        .count();
+    if conflicts > 0 {
+        warn!("Autosave left {} documents changed on disk unsaved", conflicts);
+    }
+    ApiResponse::success(entries)
+}
+
+/// Send an HTTP request and return the response
 pub async fn test_api_request(
     State(_state): State<super::ui::AppState>,
     Json(request): Json<ApiTestRequest>,
 ) -> impl IntoResponse {
-    // TODO: Implement API testing functionality
-    // This would include:
-    // - HTTP request execution
-    // - Response validation
-    // - Header and body inspection
-    // - Test history storage
-    
-    let response = serde_json::json!({
-        "status": "pending",
-        "message": "API testing functionality - implementation pending"
-    });
-    
-    ApiResponse::success(response)
+    let proxy = _state.ide.config().read().await.proxy.clone();
+    let spec = HttpRequestSpec {
+        method: request.method,
+        url: request.url,
+        headers: request.headers.unwrap_or_default(),
+        body: request.body,
+        timeout: request.timeout,
+    };
+
+    match spec.send(&proxy).await {
+        Ok(response) => ApiResponse::success(response),
+        Err(e) => api_test_error("Request failed", e),
+    }
+}
+
+/// Failed API tester call, with an error code matching the failure
+fn api_test_error<T>(context: &str, error: ApiTestError) -> ApiResponse<T> {
+    let mut response = ApiResponse::error(format!("{}: {}", context, error));
+    response.error_code = Some(match error {
+        ApiTestError::CollectionNotFound(_) | ApiTestError::RequestNotFound { .. } => "not_found",
+        ApiTestError::UndefinedVariable(_) | ApiTestError::InvalidRequest(_) => "invalid_request",
+        ApiTestError::Timeout(_) => "external_timeout",
+        ApiTestError::Request(_) => "external_error",
+        ApiTestError::Json(_) | ApiTestError::Io(_) => "io_error",
+    }.to_string());
+    response
+}
+
+async fn collection_store(state: &super::ui::AppState) -> CollectionStore {
+    CollectionStore::new(&state.ide.config().read().await.workspace_dir())
+}
+
+/// List saved request collections
+pub async fn list_api_collections(
+    State(_state): State<super::ui::AppState>,
+) -> impl IntoResponse {
+    match collection_store(&_state).await.list().await {
+        Ok(collections) => ApiResponse::success(collections),
+        Err(e) => api_test_error("Failed to list collections", e),
+    }
+}
+
+/// Create a request collection
+pub async fn create_api_collection(
+    State(_state): State<super::ui::AppState>,
+    Json(mut collection): Json<RequestCollection>,
+) -> impl IntoResponse {
+    collection.id.clear();
+    match collection_store(&_state).await.save(collection).await {
+        Ok(collection) => ApiResponse::success(collection),
+        Err(e) => api_test_error("Failed to save collection", e),
+    }
+}
+
+/// Get a request collection
+pub async fn get_api_collection(
+    State(_state): State<super::ui::AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match collection_store(&_state).await.get(&id).await {
+        Ok(collection) => ApiResponse::success(collection),
+        Err(e) => api_test_error("Failed to load collection", e),
+    }
+}
+
+/// Replace a request collection
+pub async fn update_api_collection(
+    State(_state): State<super::ui::AppState>,
+    Path(id): Path<String>,
+    Json(mut collection): Json<RequestCollection>,
+) -> impl IntoResponse {
+    let store = collection_store(&_state).await;
+    if let Err(e) = store.get(&id).await {
+        return api_test_error("Failed to update collection", e);
+    }
+    collection.id = id;
+    match store.save(collection).await {
+        Ok(collection) => ApiResponse::success(collection),
+        Err(e) => api_test_error("Failed to update collection", e),
+    }
+}
+
+/// Delete a request collection
+pub async fn delete_api_collection(
+    State(_state): State<super::ui::AppState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match collection_store(&_state).await.delete(&id).await {
+        Ok(()) => ApiResponse::success(serde_json::json!({ "deleted": id })),
+        Err(e) => api_test_error("Failed to delete collection", e),
+    }
+}
+
+/// Send a saved request with its collection's variables resolved
+pub async fn run_saved_api_request(
+    State(_state): State<super::ui::AppState>,
+    Path((id, request_id)): Path<(String, String)>,
+    request: Option<Json<RunSavedRequest>>,
+) -> impl IntoResponse {
+    let variables = request.map(|Json(request)| request.variables).unwrap_or_default();
+    let proxy = _state.ide.config().read().await.proxy.clone();
+    match collection_store(&_state).await.run(&id, &request_id, &variables, &proxy).await {
+        Ok(response) => ApiResponse::success(response),
+        Err(e) => api_test_error("Request failed", e),
+    }
 }
 
 /// Get API test history
@@ -2976,3 +5774,421 @@ pub async fn get_api_test_history(
     let history = vec![];
     ApiResponse::success(history)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_module_errors_keep_their_code_and_status() {
+        let root = std::env::temp_dir().join(format!("super-ide-errors-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("big.txt"), "x".repeat(64)).unwrap();
+        let file_manager = FileManager::new(root.clone()).with_max_open_file_bytes(16);
+        let git_manager = GitManager::new(root.clone());
+
+        let missing = file_manager.read_file(std::path::Path::new("missing.txt")).await.unwrap_err();
+        let response = ApiResponse::<String>::ide_error("Failed to load file", missing);
+        assert_eq!(response.error_code.as_deref(), Some("not_found"));
+        assert!(response.error.as_deref().unwrap().starts_with("Failed to load file: File not found"));
+        assert_eq!(response.into_response().status(), StatusCode::NOT_FOUND);
+
+        let too_large = file_manager.read_file(std::path::Path::new("big.txt")).await.unwrap_err();
+        let response = ApiResponse::<String>::ide_error("Failed to load file", too_large);
+        assert_eq!(response.error_code.as_deref(), Some("file_too_large"));
+        assert_eq!(response.into_response().status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let not_a_repository = git_manager.get_repository_info().await.unwrap_err();
+        let error = IdeError::from(not_a_repository);
+        assert!(matches!(error, IdeError::Git(GitError::RepositoryNotFound(_))));
+        assert!(std::error::Error::source(&error).unwrap().is::<GitError>());
+        let response = ApiResponse::<String>::ide_error("Git status failed", error);
+        assert_eq!(response.error_code.as_deref(), Some("not_a_repository"));
+        assert_eq!(response.into_response().status(), StatusCode::BAD_REQUEST);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_ready_only_after_initialization() {
+        use tower::ServiceExt;
+
+        let root = std::env::temp_dir().join(format!("super-ide-ready-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("main.rs"), "fn main() {}\n").unwrap();
+        let mut config = crate::config::Configuration::default();
+        config.ide.workspace_path = root.to_string_lossy().to_string();
+        let ide = Arc::new(crate::core::SuperIDE::new(config).await.unwrap());
+        let state = super::super::ui::AppState {
+            event_bus: ide.event_bus().clone(),
+            file_manager: Arc::new(RwLock::new(crate::utils::file_manager::FileManager::default())),
+            git_manager: Arc::new(GitManager::new(root.clone())),
+            event_sender: tokio::sync::broadcast::channel(16).0,
+            ide: ide.clone(),
+        };
+        let app = Router::new()
+            .route("/health", get(health_check))
+            .route("/ready", get(readiness_check))
+            .with_state(state.clone());
+        let get_status = |uri: &'static str| {
+            let app = app.clone();
+            async move {
+                let request = axum::http::Request::get(uri).body(axum::body::Body::empty()).unwrap();
+                app.oneshot(request).await.unwrap().status()
+            }
+        };
+
+        assert_eq!(get_status("/health").await, StatusCode::OK);
+        assert_eq!(get_status("/ready").await, StatusCode::SERVICE_UNAVAILABLE);
+        ide.initialize(&state.git_manager).await;
+        assert_eq!(get_status("/ready").await, StatusCode::OK);
+        assert_eq!(ide.symbol_index().await.unwrap().file_count().await, 1);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_saving_through_the_api_keeps_history() {
+        use tower::ServiceExt;
+
+        let root = std::env::temp_dir().join(format!("super-ide-save-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("notes.txt"), "first").unwrap();
+        let mut config = crate::config::Configuration::default();
+        config.ide.workspace_path = root.to_string_lossy().to_string();
+        let ide = Arc::new(crate::core::SuperIDE::new(config).await.unwrap());
+        let app = Router::new()
+            .route("/files/history", get(get_file_history))
+            .route("/files/:path", put(save_file))
+            .with_state(super::super::ui::AppState {
+                event_bus: ide.event_bus().clone(),
+                file_manager: Arc::new(RwLock::new(crate::utils::file_manager::FileManager::default())),
+                git_manager: Arc::new(GitManager::new(root.clone())),
+                event_sender: tokio::sync::broadcast::channel(16).0,
+                ide: ide.clone(),
+            });
+
+        let save = axum::http::Request::put("/files/notes.txt")
+            .header("content-type", "application/json")
+            .body(axum::body::Body::from(serde_json::json!({ "content": "second" }).to_string()))
+            .unwrap();
+        assert_eq!(app.clone().oneshot(save).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(std::fs::read_to_string(root.join("notes.txt")).unwrap(), "second");
+
+        let list = axum::http::Request::get("/files/history?path=notes.txt").body(axum::body::Body::empty()).unwrap();
+        let response = app.clone().oneshot(list).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: ApiResponse<Vec<crate::file_ops::HistoryEntry>> =
+            serde_json::from_slice(&axum::body::to_bytes(response.into_body(), 4096).await.unwrap()).unwrap();
+        assert_eq!(body.data.unwrap().len(), 1);
+
+        let escape = axum::http::Request::get("/files/history?path=../notes.txt").body(axum::body::Body::empty()).unwrap();
+        assert_eq!(app.oneshot(escape).await.unwrap().status(), StatusCode::BAD_REQUEST);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_disabled_autosave_does_not_save() {
+        use tower::ServiceExt;
+
+        let root = std::env::temp_dir().join(format!("super-ide-autosave-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("notes.txt"), "draft\n").unwrap();
+        let mut config = crate::config::Configuration::default();
+        config.ide.workspace_path = root.to_string_lossy().to_string();
+        let ide = Arc::new(crate::core::SuperIDE::new(config).await.unwrap());
+        let app = Router::new()
+            .route("/autosave/enable", post(enable_autosave))
+            .route("/autosave/disable", post(disable_autosave))
+            .route("/autosave/status", get(get_autosave_status))
+            .with_state(super::super::ui::AppState {
+                event_bus: ide.event_bus().clone(),
+                file_manager: Arc::new(RwLock::new(crate::utils::file_manager::FileManager::default())),
+                git_manager: Arc::new(GitManager::new(root.clone())),
+                event_sender: tokio::sync::broadcast::channel(16).0,
+                ide: ide.clone(),
+            });
+        let call = |request: axum::http::Request<axum::body::Body>| {
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let body: ApiResponse<serde_json::Value> =
+                    serde_json::from_slice(&axum::body::to_bytes(response.into_body(), 4096).await.unwrap()).unwrap();
+                body.data.unwrap()
+            }
+        };
+        let post_to = |uri: &'static str| axum::http::Request::post(uri).body(axum::body::Body::empty()).unwrap();
+        let status = || axum::http::Request::get("/autosave/status").body(axum::body::Body::empty()).unwrap();
+        {
+            let editor = ide.editor().lock().await;
+            editor.open_file(root.join("notes.txt")).await.unwrap();
+            editor.insert_text("final ").await.unwrap();
+        }
+
+        call(post_to("/autosave/disable")).await;
+        assert_eq!(call(status()).await["enabled"], serde_json::json!(false));
+        assert!(ide.autosave().await.is_empty());
+        assert_eq!(std::fs::read_to_string(root.join("notes.txt")).unwrap(), "draft\n");
+
+        call(post_to("/autosave/enable")).await;
+        assert_eq!(call(status()).await["enabled"], serde_json::json!(true));
+        assert_eq!(ide.autosave().await.len(), 1);
+        assert_eq!(std::fs::read_to_string(root.join("notes.txt")).unwrap(), "final draft\n");
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_git_init_body_is_optional() {
+        use tower::ServiceExt;
+
+        let root = std::env::temp_dir().join(format!("super-ide-init-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let mut config = crate::config::Configuration::default();
+        config.ide.workspace_path = root.to_string_lossy().to_string();
+        let ide = Arc::new(crate::core::SuperIDE::new(config).await.unwrap());
+        let app = Router::new()
+            .route("/git/init", post(git_init_repository))
+            .with_state(super::super::ui::AppState {
+                event_bus: ide.event_bus().clone(),
+                file_manager: Arc::new(RwLock::new(crate::utils::file_manager::FileManager::default())),
+                git_manager: Arc::new(GitManager::new(root.clone())),
+                event_sender: tokio::sync::broadcast::channel(16).0,
+                ide: ide.clone(),
+            });
+        let init = |body: &'static str| {
+            let app = app.clone();
+            async move {
+                let request = axum::http::Request::post("/git/init")
+                    .header("content-type", "application/json")
+                    .body(axum::body::Body::from(body))
+                    .unwrap();
+                app.oneshot(request).await.unwrap().status()
+            }
+        };
+
+        assert_eq!(init("{\"default_branch\": ").await, StatusCode::BAD_REQUEST);
+        assert!(!root.join(".git").exists());
+
+        let bodiless = axum::http::Request::post("/git/init").body(axum::body::Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(bodiless).await.unwrap().status(), StatusCode::OK);
+        assert!(root.join(".git").is_dir());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_generate_project_previews_then_writes() {
+        use tower::ServiceExt;
+
+        let root = std::env::temp_dir().join(format!("super-ide-scaffold-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let mut config = crate::config::Configuration::default();
+        config.ide.workspace_path = root.to_string_lossy().to_string();
+        let ide = Arc::new(crate::core::SuperIDE::new(config).await.unwrap());
+        let app = Router::new()
+            .route("/ai/generate-project", post(generate_project))
+            .with_state(super::super::ui::AppState {
+                event_bus: ide.event_bus().clone(),
+                file_manager: Arc::new(RwLock::new(crate::utils::file_manager::FileManager::default())),
+                git_manager: Arc::new(GitManager::new(root.clone())),
+                event_sender: tokio::sync::broadcast::channel(16).0,
+                ide: ide.clone(),
+            });
+        let generate = |body: serde_json::Value| {
+            let app = app.clone();
+            async move {
+                let request = axum::http::Request::post("/ai/generate-project")
+                    .header("content-type", "application/json")
+                    .body(axum::body::Body::from(body.to_string()))
+                    .unwrap();
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice::<ApiResponse<serde_json::Value>>(&body).unwrap())
+            }
+        };
+        let request = |preview: bool, force: bool| serde_json::json!({
+            "template": "generic",
+            "config": { "name": "demo", "description": "A demo" },
+            "preview": preview,
+            "force": force,
+        });
+        let readme = root.join("demo/README.md");
+
+        // A dry run lists the files and writes nothing
+        let (status, response) = generate(request(true, false)).await;
+        assert_eq!(status, StatusCode::OK);
+        let data = response.data.unwrap();
+        assert_eq!(data["preview"], serde_json::json!(true));
+        assert_eq!(data["files"][0]["path"], serde_json::json!("README.md"));
+        assert!(!readme.exists());
+
+        let (status, _) = generate(request(false, false)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(std::fs::read_to_string(&readme).unwrap().starts_with("# demo\n\nA demo"));
+
+        // Existing files are kept unless forced
+        std::fs::write(&readme, "mine\n").unwrap();
+        let (status, response) = generate(request(false, false)).await;
+        assert_eq!((status, response.error_code.as_deref()), (StatusCode::CONFLICT, Some("conflict")));
+        assert_eq!(std::fs::read_to_string(&readme).unwrap(), "mine\n");
+        assert_eq!(generate(request(false, true)).await.0, StatusCode::OK);
+        assert_ne!(std::fs::read_to_string(&readme).unwrap(), "mine\n");
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_diagnostics_of_a_nested_file() {
+        use tower::ServiceExt;
+
+        let ide = Arc::new(crate::core::SuperIDE::new(crate::config::Configuration::default()).await.unwrap());
+        let diagnostic = crate::diagnostics::Diagnostic {
+            line: 3,
+            column: 1,
+            message: "unfinished item".to_string(),
+            severity: crate::ai::IssueSeverity::Warning,
+            sources: vec![DiagnosticSource::LocalAnalysis],
+            rule_id: None,
+            fix_suggestion: None,
+        };
+        ide.diagnostics().publish("docs/notes/todo.txt", DiagnosticSource::LocalAnalysis, vec![diagnostic]).await;
+        let app = Router::new()
+            .route("/diagnostics/*path", get(get_diagnostics))
+            .with_state(super::super::ui::AppState {
+                event_bus: ide.event_bus().clone(),
+                file_manager: Arc::new(RwLock::new(crate::utils::file_manager::FileManager::default())),
+                git_manager: Arc::new(GitManager::new(std::env::temp_dir())),
+                event_sender: tokio::sync::broadcast::channel(16).0,
+                ide: ide.clone(),
+            });
+
+        let request = axum::http::Request::get("/diagnostics/docs/notes/todo.txt").body(axum::body::Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let response: ApiResponse<Vec<crate::diagnostics::Diagnostic>> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(response.data.unwrap()[0].message, "unfinished item");
+    }
+
+    #[tokio::test]
+    async fn test_build_task_needs_a_trusted_workspace() {
+        use tower::ServiceExt;
+
+        let root = std::env::temp_dir().join(format!("super-ide-trust-{}", uuid::Uuid::new_v4()));
+        let workspace = root.join("project");
+        std::fs::create_dir_all(&workspace).unwrap();
+        let state_path = root.join("state.json");
+        let mut config = crate::config::Configuration::default();
+        config.ide.workspace_path = workspace.to_string_lossy().to_string();
+        let ide = Arc::new(crate::core::SuperIDE::with_state_path(config, Some(state_path.clone())).await.unwrap());
+        let app = Router::new()
+            .route("/project/tasks", post(run_task))
+            .route("/project/trust", post(trust_project))
+            .with_state(super::super::ui::AppState {
+                event_bus: ide.event_bus().clone(),
+                file_manager: Arc::new(RwLock::new(crate::utils::file_manager::FileManager::default())),
+                git_manager: Arc::new(GitManager::new(workspace.clone())),
+                event_sender: tokio::sync::broadcast::channel(16).0,
+                ide: ide.clone(),
+            });
+        let post_json = |uri: &'static str, body: serde_json::Value| {
+            let app = app.clone();
+            async move {
+                let request = axum::http::Request::post(uri)
+                    .header("content-type", "application/json")
+                    .body(axum::body::Body::from(body.to_string()))
+                    .unwrap();
+                app.oneshot(request).await.unwrap()
+            }
+        };
+        let build = serde_json::json!({ "name": "build", "command": "true" });
+
+        let refused = post_json("/project/tasks", build.clone()).await;
+        assert_eq!(refused.status(), StatusCode::FORBIDDEN);
+        let body: ApiResponse<String> = serde_json::from_slice(&axum::body::to_bytes(refused.into_body(), 4096).await.unwrap()).unwrap();
+        assert_eq!(body.error_code.as_deref(), Some("workspace_not_trusted"));
+        assert!(ide.task_runner().list().await.is_empty());
+
+        assert_eq!(post_json("/project/trust", serde_json::Value::Null).await.status(), StatusCode::OK);
+        assert_eq!(post_json("/project/tasks", build).await.status(), StatusCode::OK);
+        assert_eq!(ide.task_runner().list().await.len(), 1);
+
+        // Trust outlives the session
+        let reloaded = crate::core::trust::WorkspaceTrust::load(&workspace, Some(state_path), true).await;
+        assert!(reloaded.is_trusted());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_tutor_prompt_is_the_last_user_message() {
+        let request: AIChatRequest = serde_json::from_value(serde_json::json!({
+            "messages": [
+                { "role": "user", "content": "What is a lifetime?" },
+                { "role": "assistant", "content": "A lifetime is how long a reference is valid." },
+            ]
+        })).unwrap();
+        assert_eq!(request.last_user_message().as_deref(), Some("What is a lifetime?"));
+
+        let request: AIChatRequest = serde_json::from_value(serde_json::json!({ "message": "Why?" })).unwrap();
+        assert_eq!(request.last_user_message().as_deref(), Some("Why?"));
+    }
+
+    #[test]
+    fn test_external_errors_map_to_distinct_codes() {
+        use crate::external::ExternalError;
+
+        let cases = [
+            (ExternalError::BrowserUnavailable("http://localhost:9222".to_string()), "browser_unavailable", StatusCode::SERVICE_UNAVAILABLE),
+            (ExternalError::McpUnreachable("http://localhost:12306".to_string()), "mcp_unreachable", StatusCode::SERVICE_UNAVAILABLE),
+            (ExternalError::NavigationTimeout("https://example.com".to_string()), "navigation_timeout", StatusCode::GATEWAY_TIMEOUT),
+            (
+                ExternalError::BadSelector { selector: "div[".to_string(), reason: "unexpected end".to_string() },
+                "invalid_selector",
+                StatusCode::BAD_REQUEST,
+            ),
+            (ExternalError::ElementNotFound("#submit".to_string()), "not_found", StatusCode::NOT_FOUND),
+            (ExternalError::Timeout(30), "external_timeout", StatusCode::GATEWAY_TIMEOUT),
+            (ExternalError::ProxyError("proxy refused the connection".to_string()), "proxy_error", StatusCode::BAD_GATEWAY),
+            (ExternalError::HttpError("connection reset".to_string()), "external_error", StatusCode::BAD_GATEWAY),
+            (ExternalError::JsonError("expected value".to_string()), "external_error", StatusCode::BAD_GATEWAY),
+            (ExternalError::BrowserError("page crashed".to_string()), "external_error", StatusCode::BAD_GATEWAY),
+            (ExternalError::McpError("quota exceeded".to_string()), "external_error", StatusCode::BAD_GATEWAY),
+            (ExternalError::PythonError("traceback".to_string()), "external_error", StatusCode::BAD_GATEWAY),
+            (ExternalError::ProcessError("python not found".to_string()), "external_error", StatusCode::BAD_GATEWAY),
+            (ExternalError::IoError(std::io::ErrorKind::NotFound.into()), "external_error", StatusCode::BAD_GATEWAY),
+        ];
+        for (error, code, status) in cases {
+            let message = error.to_string();
+            let response = ApiResponse::<String>::ide_error("Browser click failed", error);
+            assert_eq!(response.error_code.as_deref(), Some(code), "{}", message);
+            assert_eq!(response.error.as_deref(), Some(format!("Browser click failed: {}", message).as_str()));
+            assert_eq!(response.into_response().status(), status, "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_ai_and_unclassified_errors() {
+        let timeout = IdeError::Ai(crate::ai::AiError::Timeout(30).into());
+        assert_eq!(timeout.error_code(), "ai_timeout");
+        assert_eq!(
+            ApiResponse::<String>::ide_error("Completion failed", timeout).into_response().status(),
+            StatusCode::GATEWAY_TIMEOUT
+        );
+
+        let overloaded = IdeError::Ai(crate::ai::AiError::Status { status: 503, message: "overloaded".to_string() }.into());
+        assert_eq!(overloaded.error_code(), "ai_unavailable");
+        let rejected = IdeError::Ai(crate::ai::AiError::Status { status: 400, message: "max_tokens is too large".to_string() }.into());
+        assert_eq!(rejected.error_code(), "ai_error");
+
+        let unclassified = IdeError::from(anyhow::anyhow!("something odd"));
+        assert_eq!(unclassified.error_code(), "internal");
+        let response = ApiResponse::<String>::ide_error("Request failed", unclassified);
+        assert_eq!(response.error.as_deref(), Some("Request failed: something odd"));
+        assert_eq!(response.into_response().status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -6,15 +6,23 @@
 //! - User presence indicators
 //! - Live cursors and selections
 //! - Comment threads and discussions
+//! - Snapshots on disk, restored on startup
+
+pub mod presence;
+pub mod store;
 
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use tokio::sync::{RwLock, broadcast};
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock, broadcast};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 
+use presence::{PresenceFanout, PRESENCE_TIMEOUT};
+use store::{CollaborationStore, DocumentSnapshot};
+
 /// Collaboration session identifier
 pub type SessionId = String;
 
@@ -128,8 +136,8 @@ pub enum CollaborationEvent {
         operation: Operation,
         new_version: u64,
     },
-    /// Cursor/selection updated
-    PresenceUpdated {
+    /// Cursors/selections updated, at most once per `PRESENCE_DEBOUNCE`
+    PresenceChanged {
         presence: UserPresence,
     },
     /// New comment added
@@ -157,10 +165,22 @@ pub struct CollaborationManager {
     documents: Arc<RwLock<HashMap<DocumentId, CollaborationDocument>>>,
     users: Arc<RwLock<HashMap<UserId, CollaborationUser>>>,
     event_broadcasters: Arc<RwLock<HashMap<SessionId, broadcast::Sender<CollaborationEvent>>>>,
+    /// Presence updates of each session, debounced before broadcast
+    presence: Arc<RwLock<HashMap<SessionId, PresenceFanout>>>,
+    /// Participants silent for this long are dropped
+    presence_timeout: Duration,
+    /// Where documents are snapshotted, if anywhere
+    store: Option<CollaborationStore>,
+    /// Changes to a document after which it is snapshotted without waiting
+    snapshot_every: u64,
+    /// Changes per document since its last snapshot
+    unsaved_changes: Arc<Mutex<HashMap<DocumentId, u64>>>,
+    /// Open connections of each participant, such as one per browser tab
+    connections: Arc<Mutex<HashMap<(SessionId, UserId), usize>>>,
 }
 
 /// Individual collaboration session
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CollaborationSession {
     pub id: SessionId,
     pub document_id: DocumentId,
@@ -179,17 +199,153 @@ impl CollaborationManager {
             documents: Arc::new(RwLock::new(HashMap::new())),
             users: Arc::new(RwLock::new(HashMap::new())),
             event_broadcasters: Arc::new(RwLock::new(HashMap::new())),
+            presence: Arc::new(RwLock::new(HashMap::new())),
+            presence_timeout: PRESENCE_TIMEOUT,
+            store: None,
+            snapshot_every: u64::MAX,
+            unsaved_changes: Arc::new(Mutex::new(HashMap::new())),
+            connections: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
+    /// Snapshot documents to `store`, each one at the latest after `snapshot_every`
+    /// changes and otherwise whenever [`Self::snapshot`] runs
+    pub fn with_store(mut self, store: CollaborationStore, snapshot_every: u64) -> Self {
+        self.store = Some(store);
+        self.snapshot_every = snapshot_every.max(1);
+        self
+    }
+
+    /// Drop participants not heard from for `timeout` instead of `PRESENCE_TIMEOUT`.
+    /// Applies to sessions created afterwards.
+    pub fn with_presence_timeout(mut self, timeout: Duration) -> Self {
+        self.presence_timeout = timeout;
+        self
+    }
+
+    /// Load the documents and sessions saved in the store. Returns the number of
+    /// documents restored.
+    pub async fn restore(&self) -> Result<usize> {
+        let Some(store) = &self.store else {
+            return Ok(0);
+        };
+        let snapshots = store.load_all().await?;
+        for snapshot in &snapshots {
+            for session in &snapshot.sessions {
+                self.sessions.write().await.insert(session.id.clone(), session.clone());
+                self.open_channels(&session.id).await;
+            }
+            let mut document = snapshot.document.clone();
+            // Participants rejoin after a restart
+            document.participants.clear();
+            self.documents.write().await.insert(document.id.clone(), document);
+        }
+        Ok(snapshots.len())
+    }
+
+    /// Snapshot every document changed since its last snapshot. Returns the number
+    /// of documents saved.
+    pub async fn snapshot(&self) -> Result<usize> {
+        if self.store.is_none() {
+            return Ok(0);
+        }
+        let changed: Vec<DocumentId> = self.unsaved_changes.lock().await.keys().cloned().collect();
+        for document_id in &changed {
+            self.snapshot_document(document_id).await?;
+        }
+        Ok(changed.len())
+    }
+
+    /// Snapshot changed documents every `interval` for as long as the manager lives
+    pub fn start_snapshots(self: &Arc<Self>, interval: Duration) {
+        if self.store.is_none() {
+            return;
+        }
+        let manager = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut timer = tokio::time::interval(interval);
+            // The first tick completes immediately
+            timer.tick().await;
+            loop {
+                timer.tick().await;
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                if let Err(e) = manager.snapshot().await {
+                    log::warn!("Collaboration snapshot failed: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Start the event broadcaster and presence fan-out of a session
+    async fn open_channels(&self, session_id: &SessionId) {
+        let events = self.event_broadcasters.write().await
+            .entry(session_id.clone())
+            .or_insert_with(|| broadcast::channel(1000).0)
+            .clone();
+        let fanout = PresenceFanout::start(
+            session_id.clone(),
+            self.sessions.clone(),
+            self.documents.clone(),
+            events,
+            self.presence_timeout,
+        );
+        self.presence.write().await.insert(session_id.clone(), fanout);
+    }
+
+    /// Count a change to a document, snapshotting it once enough have built up.
+    /// Must be called without the session or document locks held.
+    async fn record_change(&self, document_id: &DocumentId) {
+        if self.store.is_none() {
+            return;
+        }
+        let changes = {
+            let mut unsaved = self.unsaved_changes.lock().await;
+            let changes = unsaved.entry(document_id.clone()).or_insert(0);
+            *changes += 1;
+            *changes
+        };
+        if changes >= self.snapshot_every {
+            if let Err(e) = self.snapshot_document(document_id).await {
+                log::warn!("Could not snapshot collaborative document {}: {}", document_id, e);
+            }
+        }
+    }
+
+    async fn snapshot_document(&self, document_id: &DocumentId) -> Result<()> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        // Take the count before copying the document, so a change made meanwhile
+        // is counted towards the next snapshot rather than lost with this one
+        let changes = self.unsaved_changes.lock().await.remove(document_id);
+        let Some(document) = self.get_document(document_id).await else {
+            return Ok(());
+        };
+        let sessions = self.sessions.read().await
+            .values()
+            .filter(|session| &session.document_id == document_id)
+            .cloned()
+            .collect();
+        if let Err(e) = store.save(&DocumentSnapshot::new(document, sessions)).await {
+            // Keep the changes counted so the next snapshot retries
+            if let Some(changes) = changes {
+                *self.unsaved_changes.lock().await.entry(document_id.clone()).or_insert(0) += changes;
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
     /// Create new collaboration session
     pub async fn create_session(&self, document_id: DocumentId, creator_id: UserId) -> Result<SessionId> {
         let session_id = Uuid::new_v4().to_string();
         
         // Create collaboration document if it doesn't exist
-        let mut documents = self.documents.write().await;
-        if !documents.contains_key(&document_id) {
-            let document = CollaborationDocument {
+        let version = {
+            let mut documents = self.documents.write().await;
+            let document = documents.entry(document_id.clone()).or_insert_with(|| CollaborationDocument {
                 id: document_id.clone(),
                 path: "unknown".to_string(), // Will be updated by file manager
                 content: String::new(),
@@ -198,30 +354,28 @@ impl CollaborationManager {
                 updated_at: Utc::now(),
                 participants: HashSet::new(),
                 comments: Vec::new(),
-            };
-            documents.insert(document_id.clone(), document);
-        }
+            });
+            document.version
+        };
 
         // Create collaboration session
-        let mut sessions = self.sessions.write().await;
         let session = CollaborationSession {
             id: session_id.clone(),
             document_id: document_id.clone(),
             participants: HashMap::new(),
             operations: Vec::new(),
-            version: 0,
+            version,
             created_at: Utc::now(),
             last_activity: Utc::now(),
         };
-        sessions.insert(session_id.clone(), session);
+        self.sessions.write().await.insert(session_id.clone(), session);
 
-        // Create event broadcaster
-        let (tx, _) = broadcast::channel(1000);
-        let mut broadcasters = self.event_broadcasters.write().await;
-        broadcasters.insert(session_id.clone(), tx);
+        // Create event broadcaster and presence fan-out
+        self.open_channels(&session_id).await;
 
         // Add creator as participant
         self.join_session(&session_id, &creator_id).await?;
+        self.record_change(&document_id).await;
 
         Ok(session_id)
     }
@@ -291,6 +445,29 @@ impl CollaborationManager {
         Ok(())
     }
 
+    /// Count a connection of `user_id` to a session opening
+    pub async fn connection_opened(&self, session_id: &SessionId, user_id: &UserId) {
+        *self.connections.lock().await.entry((session_id.clone(), user_id.clone())).or_insert(0) += 1;
+    }
+
+    /// Count a connection of `user_id` closing. The participant leaves the session
+    /// with their last connection; returns whether they did.
+    pub async fn connection_closed(&self, session_id: &SessionId, user_id: &UserId) -> Result<bool> {
+        {
+            let mut connections = self.connections.lock().await;
+            let key = (session_id.clone(), user_id.clone());
+            if let Some(count) = connections.get_mut(&key) {
+                *count -= 1;
+                if *count > 0 {
+                    return Ok(false);
+                }
+                connections.remove(&key);
+            }
+        }
+        self.leave_session(session_id, user_id).await?;
+        Ok(true)
+    }
+
     /// Apply operation to document
     pub async fn apply_operation(&self, session_id: &SessionId, operation: Operation) -> Result<u64> {
         let mut sessions = self.sessions.write().await;
@@ -331,31 +508,51 @@ impl CollaborationManager {
         session.last_activity = Utc::now();
 
         // Broadcast operation applied event
+        let version = document.version;
+        let document_id = session.document_id.clone();
         self.broadcast_event(session_id, CollaborationEvent::OperationApplied {
             operation,
-            new_version: document.version,
+            new_version: version,
         }).await;
+        drop(documents);
+        drop(sessions);
+        self.record_change(&document_id).await;
 
-        Ok(document.version)
+        Ok(version)
     }
 
-    /// Update user presence (cursor position, selection, typing status)
-    pub async fn update_presence(&self, session_id: &SessionId, presence: UserPresence) -> Result<()> {
+    /// Update user presence (cursor position, selection, typing status); the other
+    /// participants are told shortly after, along with any further updates
+    pub async fn update_presence(&self, session_id: &SessionId, mut presence: UserPresence) -> Result<()> {
         let mut sessions = self.sessions.write().await;
         let session = sessions.get_mut(session_id)
             .ok_or_else(|| anyhow::anyhow!("Session not found"))?;
 
+        // Staleness is judged by the server's clock
+        presence.last_seen = Utc::now();
         session.participants.insert(presence.user_id.clone(), presence.clone());
         session.last_activity = Utc::now();
+        drop(sessions);
 
-        // Broadcast presence updated event
-        self.broadcast_event(session_id, CollaborationEvent::PresenceUpdated {
-            presence,
-        }).await;
+        if let Some(fanout) = self.presence.read().await.get(session_id) {
+            fanout.send(presence);
+        }
 
         Ok(())
     }
 
+    /// Keep a participant from being dropped as silent without telling anyone,
+    /// for clients that are connected but idle
+    pub async fn touch_presence(&self, session_id: &SessionId, user_id: &UserId) -> Result<()> {
+        let mut sessions = self.sessions.write().await;
+        let session = sessions.get_mut(session_id)
+            .ok_or_else(|| anyhow::anyhow!("Session not found"))?;
+        let presence = session.participants.get_mut(user_id)
+            .ok_or_else(|| anyhow::anyhow!("User {} is not in the session", user_id))?;
+        presence.last_seen = Utc::now();
+        Ok(())
+    }
+
     /// Add comment to document
     pub async fn add_comment(&self, session_id: &SessionId, comment: Comment) -> Result<()> {
         let sessions = self.sessions.read().await;
@@ -372,6 +569,10 @@ impl CollaborationManager {
         self.broadcast_event(session_id, CollaborationEvent::CommentAdded {
             comment,
         }).await;
+        let document_id = session.document_id.clone();
+        drop(documents);
+        drop(sessions);
+        self.record_change(&document_id).await;
 
         Ok(())
     }
@@ -528,3 +729,115 @@ impl OperationalTransform {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(position: usize, text: &str) -> Operation {
+        Operation::Insert { position, text: text.to_string(), timestamp: Utc::now(), user_id: "alice".to_string() }
+    }
+
+    async fn restarted(workspace: &std::path::Path) -> CollaborationManager {
+        let manager = CollaborationManager::new().with_store(CollaborationStore::new(workspace), 2);
+        manager.restore().await.unwrap();
+        manager
+    }
+
+    #[tokio::test]
+    async fn test_restart_restores_document_content_and_version() {
+        let workspace = std::env::temp_dir().join(format!("super-ide-collab-{}", Uuid::new_v4()));
+        let manager = CollaborationManager::new().with_store(CollaborationStore::new(&workspace), 2);
+        let document_id = "notes.md".to_string();
+        let session_id = manager.create_session(document_id.clone(), "alice".to_string()).await.unwrap();
+        manager.apply_operation(&session_id, insert(0, "hello")).await.unwrap();
+        manager.apply_operation(&session_id, insert(5, " world")).await.unwrap();
+        manager.add_comment(&session_id, Comment {
+            id: "c1".to_string(),
+            author_id: "alice".to_string(),
+            content: "Capitalize?".to_string(),
+            line_number: Some(1),
+            column_start: None,
+            column_end: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            resolved: false,
+            replies: Vec::new(),
+        }).await.unwrap();
+        let delete = Operation::Delete { position: 0, length: 1, timestamp: Utc::now(), user_id: "alice".to_string() };
+        assert_eq!(manager.apply_operation(&session_id, delete).await.unwrap(), 3);
+
+        // Every second change is snapshotted, so the last operation is not on disk yet
+        let document = restarted(&workspace).await.get_document(&document_id).await.unwrap();
+        assert_eq!((document.content.as_str(), document.version), ("hello world", 2));
+        assert_eq!(document.comments.len(), 1);
+
+        assert_eq!(manager.snapshot().await.unwrap(), 1);
+        drop(manager);
+        let manager = restarted(&workspace).await;
+        let document = manager.get_document(&document_id).await.unwrap();
+        assert_eq!((document.content.as_str(), document.version), ("ello world", 3));
+        assert!(document.participants.is_empty());
+
+        // The session resumes under its old id
+        manager.join_session(&session_id, &"bob".to_string()).await.unwrap();
+        assert_eq!(manager.apply_operation(&session_id, insert(0, "H")).await.unwrap(), 4);
+        assert_eq!(manager.get_document(&document_id).await.unwrap().content, "Hello world");
+
+        std::fs::remove_dir_all(&workspace).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_presence_update_notifies_the_other_participants() {
+        let manager = CollaborationManager::new().with_presence_timeout(Duration::from_millis(300));
+        let (alice, bob) = ("alice".to_string(), "bob".to_string());
+        let session_id = manager.create_session("notes.md".to_string(), alice.clone()).await.unwrap();
+        manager.join_session(&session_id, &bob).await.unwrap();
+        let mut bob_events = manager.get_event_broadcaster(&session_id).await.unwrap();
+
+        for column in 1..=3 {
+            manager.update_presence(&session_id, UserPresence {
+                user_id: alice.clone(),
+                cursor_position: Some((4, column)),
+                selection_range: None,
+                is_typing: true,
+                last_seen: Utc::now(),
+            }).await.unwrap();
+        }
+        // The burst of cursor moves arrives as one event with the latest position
+        match tokio::time::timeout(Duration::from_secs(1), bob_events.recv()).await.unwrap().unwrap() {
+            CollaborationEvent::PresenceChanged { presence } => {
+                assert_eq!((presence.user_id.as_str(), presence.cursor_position), ("alice", Some((4, 3))));
+            }
+            event => panic!("Expected a presence change, got {:?}", event),
+        }
+
+        // Bob goes silent and is dropped, while Alice stays connected
+        let left = tokio::time::timeout(Duration::from_secs(3), async {
+            loop {
+                manager.touch_presence(&session_id, &alice).await.unwrap();
+                let event = tokio::time::timeout(Duration::from_millis(50), bob_events.recv()).await;
+                if let Ok(Ok(CollaborationEvent::UserLeft { user_id })) = event {
+                    return user_id;
+                }
+            }
+        }).await.unwrap();
+        assert_eq!(left, "bob");
+        let participants = manager.get_session_participants(&session_id).await.unwrap();
+        assert_eq!(participants.keys().collect::<Vec<_>>(), [&alice]);
+    }
+
+    #[tokio::test]
+    async fn test_participant_leaves_with_their_last_connection() {
+        let manager = CollaborationManager::new();
+        let alice = "alice".to_string();
+        let session_id = manager.create_session("notes.md".to_string(), alice.clone()).await.unwrap();
+        manager.connection_opened(&session_id, &alice).await;
+        manager.connection_opened(&session_id, &alice).await;
+
+        assert!(!manager.connection_closed(&session_id, &alice).await.unwrap());
+        assert!(manager.get_session_participants(&session_id).await.unwrap().contains_key(&alice));
+        assert!(manager.connection_closed(&session_id, &alice).await.unwrap());
+        assert!(manager.get_session_participants(&session_id).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,102 @@
+//! Presence fan-out
+//!
+//! Cursors move far more often than anyone can follow, so each session's presence
+//! updates go through a channel to a task of its own. The task keeps only the
+//! latest presence of each user and passes them on as `PresenceChanged` events at
+//! most once per `PRESENCE_DEBOUNCE`. It also drops participants not heard from
+//! within the presence timeout, announcing them as having left, since a closed
+//! browser tab never says goodbye.
+
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, RwLock};
+
+use super::{CollaborationDocument, CollaborationEvent, CollaborationSession, DocumentId, SessionId, UserPresence};
+
+/// Shortest time between two batches of presence events of a session
+pub const PRESENCE_DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// Participants silent for this long are removed from their session
+pub const PRESENCE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Sending end of a session's presence task; the task ends when this is dropped
+#[derive(Debug)]
+pub(super) struct PresenceFanout {
+    updates: mpsc::UnboundedSender<UserPresence>,
+}
+
+impl PresenceFanout {
+    pub(super) fn start(
+        session_id: SessionId,
+        sessions: Arc<RwLock<HashMap<SessionId, CollaborationSession>>>,
+        documents: Arc<RwLock<HashMap<DocumentId, CollaborationDocument>>>,
+        events: broadcast::Sender<CollaborationEvent>,
+        timeout: Duration,
+    ) -> Self {
+        let (updates, mut received) = mpsc::unbounded_channel::<UserPresence>();
+        tokio::spawn(async move {
+            let mut prune = tokio::time::interval((timeout / 2).max(PRESENCE_DEBOUNCE));
+            loop {
+                tokio::select! {
+                    update = received.recv() => {
+                        let Some(update) = update else { break };
+                        let mut latest = HashMap::from([(update.user_id.clone(), update)]);
+                        tokio::time::sleep(PRESENCE_DEBOUNCE).await;
+                        while let Ok(update) = received.try_recv() {
+                            latest.insert(update.user_id.clone(), update);
+                        }
+                        for presence in latest.into_values() {
+                            let _ = events.send(CollaborationEvent::PresenceChanged { presence });
+                        }
+                    }
+                    _ = prune.tick() => {
+                        for user_id in prune_stale(&session_id, &sessions, &documents, timeout).await {
+                            log::info!("Dropped silent participant {} from session {}", user_id, session_id);
+                            let _ = events.send(CollaborationEvent::UserLeft { user_id });
+                        }
+                    }
+                }
+            }
+        });
+        Self { updates }
+    }
+
+    pub(super) fn send(&self, presence: UserPresence) {
+        let _ = self.updates.send(presence);
+    }
+}
+
+/// Remove the participants of a session last seen more than `timeout` ago,
+/// returning their ids
+async fn prune_stale(
+    session_id: &SessionId,
+    sessions: &RwLock<HashMap<SessionId, CollaborationSession>>,
+    documents: &RwLock<HashMap<DocumentId, CollaborationDocument>>,
+    timeout: Duration,
+) -> Vec<String> {
+    let Some(cutoff) = chrono::Duration::from_std(timeout).ok().and_then(|timeout| Utc::now().checked_sub_signed(timeout)) else {
+        return Vec::new();
+    };
+    let mut sessions = sessions.write().await;
+    let Some(session) = sessions.get_mut(session_id) else {
+        return Vec::new();
+    };
+    let stale: Vec<String> = session.participants.values()
+        .filter(|presence| presence.last_seen < cutoff)
+        .map(|presence| presence.user_id.clone())
+        .collect();
+    if stale.is_empty() {
+        return stale;
+    }
+    for user_id in &stale {
+        session.participants.remove(user_id);
+    }
+    if let Some(document) = documents.write().await.get_mut(&session.document_id) {
+        for user_id in &stale {
+            document.participants.remove(user_id);
+        }
+    }
+    stale
+}
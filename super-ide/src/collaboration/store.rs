@@ -0,0 +1,94 @@
+//! Collaborative document persistence
+//!
+//! Each collaborative document is snapshotted to a JSON file of its own under
+//! `.super-ide/collaboration/` in the workspace, together with the sessions
+//! editing it, so that after a restart clients can rejoin the same session and
+//! find the content, version and comments they left. Presence and the operation
+//! history are not saved: participants rejoin, and the operations are already
+//! part of the content.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::{CollaborationDocument, CollaborationSession, DocumentId};
+
+/// Directory of the snapshots, relative to the workspace
+pub const COLLABORATION_DIR: &str = ".super-ide/collaboration";
+
+/// Bumped when the saved format changes
+const SNAPSHOT_FORMAT: u32 = 1;
+
+/// A document and the sessions on it, as saved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentSnapshot {
+    pub format: u32,
+    pub document: CollaborationDocument,
+    /// Without participants or operations
+    pub sessions: Vec<CollaborationSession>,
+}
+
+impl DocumentSnapshot {
+    pub fn new(document: CollaborationDocument, sessions: Vec<CollaborationSession>) -> Self {
+        let sessions = sessions.into_iter()
+            .map(|mut session| {
+                session.participants.clear();
+                session.operations.clear();
+                session
+            })
+            .collect();
+        Self { format: SNAPSHOT_FORMAT, document, sessions }
+    }
+}
+
+/// Snapshot files of a workspace's collaborative documents
+#[derive(Debug, Clone)]
+pub struct CollaborationStore {
+    dir: PathBuf,
+}
+
+impl CollaborationStore {
+    /// Store for the workspace at `workspace`
+    pub fn new(workspace: &Path) -> Self {
+        Self { dir: workspace.join(COLLABORATION_DIR) }
+    }
+
+    /// Snapshot file of a document; ids are hex-encoded since clients pick them
+    fn path(&self, document_id: &DocumentId) -> PathBuf {
+        let name: String = document_id.bytes().map(|byte| format!("{:02x}", byte)).collect();
+        self.dir.join(format!("{}.json", name))
+    }
+
+    pub async fn save(&self, snapshot: &DocumentSnapshot) -> Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let bytes = serde_json::to_vec(snapshot)?;
+        crate::file_ops::atomic::write_atomic(&self.path(&snapshot.document.id), &bytes, false).await?;
+        Ok(())
+    }
+
+    /// Every saved snapshot; files that cannot be read, or are of another
+    /// format, are skipped with a warning
+    pub async fn load_all(&self) -> Result<Vec<DocumentSnapshot>> {
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut snapshots = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let snapshot = tokio::fs::read(&path).await
+                .map_err(anyhow::Error::from)
+                .and_then(|bytes| Ok(serde_json::from_slice::<DocumentSnapshot>(&bytes)?));
+            match snapshot {
+                Ok(snapshot) if snapshot.format == SNAPSHOT_FORMAT => snapshots.push(snapshot),
+                Ok(snapshot) => log::warn!("Skipping {}: snapshot format {}", path.display(), snapshot.format),
+                Err(e) => log::warn!("Skipping unreadable snapshot {}: {}", path.display(), e),
+            }
+        }
+        Ok(snapshots)
+    }
+}
@@ -1,16 +1,37 @@
 //! Core IDE functionality and main application state
 
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::sync::{Mutex, RwLock};
 use anyhow::Result;
 use thiserror::Error;
 
 use crate::ai::{AiEngine, AiConfig};
+use crate::ai::tools::ToolRegistry;
+use crate::ai::auto_analysis::AnalysisGates;
+use crate::ai::context::ContextBuilder;
+use crate::ai::rules::RuleSet;
 use crate::editor::Editor;
+use crate::editor::symbols::SymbolIndex;
+use crate::editor::save_conflict::{AutosaveEntry, AutosaveStatus, SaveOutcome};
+use crate::ai::semantic::SemanticIndex;
 use crate::config::Configuration;
-use crate::utils::event_bus::EventBus;
+use crate::core::state::PersistedState;
+use crate::core::trust::WorkspaceTrust;
+use crate::utils::event_bus::{EventBus, FileIndex};
 use crate::terminal::{TerminalManager, TerminalConfig};
+use crate::terminal::tasks::TaskRunner;
 use crate::collaboration::CollaborationManager;
+use crate::collaboration::store::CollaborationStore;
+use crate::diagnostics::DiagnosticsManager;
+use crate::file_ops::outdated::OutdatedChecker;
+use crate::file_ops::stats::ProjectStatsCollector;
+
+pub mod state;
+pub mod trust;
+
+/// How often the autosave task looks at the interval again while autosave is off
+const AUTOSAVE_DISABLED_RECHECK: std::time::Duration = std::time::Duration::from_secs(5);
 
 /// Document context information
 #[derive(Debug, Clone)]
@@ -23,31 +44,182 @@ struct DocumentContext {
 pub type IdeResult<T> = Result<T, IdeError>;
 
 /// Main IDE errors
+///
+/// Module errors are wrapped rather than flattened into strings, so `source()`
+/// still reaches the original error and `error_code` can classify it.
 #[derive(thiserror::Error, Debug)]
 pub enum IdeError {
     #[error("Configuration error: {0}")]
-    ConfigError(String),
+    Config(#[from] crate::config::ConfigError),
     
-    #[error("AI Engine error: {0}")]
-    AiEngine(String),
+    /// Error from the AI engine, which reports failures through `anyhow`
+    #[error("AI error: {0}")]
+    Ai(#[source] anyhow::Error),
     
-    #[error("Editor error: {0}")]
-    Editor(String),
+    #[error("Git error: {0}")]
+    Git(#[from] crate::git::GitError),
     
-    #[error("IO error: {0}")]
-    Io(#[from] std::io::Error),
+    #[error("File error: {0}")]
+    File(#[from] crate::file_ops::FileOperationError),
     
     #[error("Editor error: {0}")]
-    EditorError(#[from] crate::editor::EditorError),
+    Editor(#[from] crate::editor::EditorError),
+    
+    #[error("External service error: {0}")]
+    External(#[from] crate::external::ExternalError),
     
     #[error("Terminal error: {0}")]
     Terminal(#[from] crate::terminal::TerminalError),
     
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    
     #[error("Database error: {0}")]
     Database(String),
     
-    #[error("Configuration error: {0}")]
-    Configuration(#[from] crate::config::ConfigError),
+    #[error("Workspace {} is not trusted; trust it to run tasks, terminals, formatters and language servers", .0.display())]
+    WorkspaceNotTrusted(std::path::PathBuf),
+    
+    /// Error that no module claimed
+    #[error("{0}")]
+    Internal(#[source] anyhow::Error),
+}
+
+/// Recover the module error carried by an `anyhow::Error`. AI errors are not
+/// recognised here: callers of the AI engine wrap them in `IdeError::Ai`.
+impl From<anyhow::Error> for IdeError {
+    fn from(error: anyhow::Error) -> Self {
+        let error = match error.downcast::<IdeError>() {
+            Ok(ide_error) => return ide_error,
+            Err(error) => error,
+        };
+        let error = match error.downcast::<crate::git::GitError>() {
+            Ok(git_error) => return Self::Git(git_error),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<crate::file_ops::FileOperationError>() {
+            Ok(file_error) => return Self::File(file_error),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<crate::config::ConfigError>() {
+            Ok(config_error) => return Self::Config(config_error),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<crate::editor::EditorError>() {
+            Ok(editor_error) => return Self::Editor(editor_error),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<crate::external::ExternalError>() {
+            Ok(external_error) => return Self::External(external_error),
+            Err(error) => error,
+        };
+        match error.downcast::<std::io::Error>() {
+            Ok(io_error) => Self::Io(io_error),
+            Err(error) => Self::Internal(error),
+        }
+    }
+}
+
+/// Errors of the workspace file manager used by the API's shared state
+impl From<crate::utils::file_manager::FileManagerError> for IdeError {
+    fn from(error: crate::utils::file_manager::FileManagerError) -> Self {
+        use crate::file_ops::FileOperationError;
+        use crate::utils::file_manager::FileManagerError;
+        
+        Self::File(match error {
+            FileManagerError::NotFound(path) => FileOperationError::FileNotFound(path.into()),
+            FileManagerError::Permission(path) => FileOperationError::PermissionDenied(path.into()),
+            FileManagerError::Io(error) => FileOperationError::Io(error),
+            FileManagerError::Watch(error) => FileOperationError::WatchError(error.to_string()),
+        })
+    }
+}
+
+fn io_error_code(error: &std::io::Error) -> &'static str {
+    match error.kind() {
+        std::io::ErrorKind::NotFound => "not_found",
+        std::io::ErrorKind::PermissionDenied => "permission_denied",
+        _ => "io_error",
+    }
+}
+
+impl IdeError {
+    /// Stable, machine-readable code for API clients
+    pub fn error_code(&self) -> &'static str {
+        use crate::editor::EditorError;
+        use crate::external::ExternalError;
+        use crate::file_ops::FileOperationError;
+        use crate::git::GitError;
+        
+        match self {
+            Self::Config(_) => "config_error",
+            Self::Ai(error) => match error.downcast_ref::<crate::ai::AiError>() {
+                Some(crate::ai::AiError::Timeout(_)) => "ai_timeout",
+                Some(crate::ai::AiError::Proxy(_)) => "proxy_error",
+                Some(error) if error.is_provider_failure() => "ai_unavailable",
+                // The provider answered but rejected the request itself
+                Some(_) | None => "ai_error",
+            },
+            Self::Git(error) => match error {
+                GitError::RepositoryNotFound(_) => "not_a_repository",
+                GitError::PathNotFound { .. } => "not_found",
+                GitError::RevisionNotFound(_) => "invalid_revision",
+                GitError::HunkConflict { .. } => "conflict",
+                GitError::HookFailed { .. } => "hook_failed",
+                GitError::SigningFailed(_) => "signing_failed",
+                GitError::RootCommit(_) => "root_commit",
+                GitError::CommitPushed { .. } => "commit_pushed",
+                GitError::SquashCountTooSmall(_) | GitError::TooFewCommits { .. } | GitError::NotLatestCommit(_) => "invalid_request",
+                GitError::StagedChanges => "conflict",
+                GitError::AuthenticationFailed(_) => "auth_failed",
+                GitError::RemotePermissionDenied(_) => "remote_permission_denied",
+                GitError::NonFastForward(_) => "non_fast_forward",
+                GitError::NoUpstream(_) => "no_upstream",
+                GitError::InvalidBranchName(_) | GitError::InvalidPath(_) | GitError::BinaryFile(_) => "invalid_request",
+                GitError::SecretsFound { .. } => "secrets_found",
+                GitError::GitNotInstalled => "git_not_installed",
+                GitError::CommandFailed(_) | GitError::ParseError(_) => "git_failed",
+                GitError::Io(error) => io_error_code(error),
+            },
+            Self::File(error) => match error {
+                FileOperationError::FileNotFound(_) => "not_found",
+                FileOperationError::PermissionDenied(_) => "permission_denied",
+                FileOperationError::InvalidPath(_) => "invalid_path",
+                FileOperationError::InvalidPattern(_) => "invalid_pattern",
+                FileOperationError::FileTooLarge { .. } => "file_too_large",
+                FileOperationError::AlreadyExists(_) => "conflict",
+                FileOperationError::WatchError(_) => "watch_failed",
+                FileOperationError::Io(error) => io_error_code(error),
+            },
+            Self::Editor(error) => match error {
+                EditorError::FileNotFound(_) => "not_found",
+                EditorError::FileTooLarge { .. } => "file_too_large",
+                EditorError::SyntaxError(_) => "invalid_syntax",
+                EditorError::ReadOnly(_) => "read_only",
+                EditorError::SaveConflict(_) | EditorError::UnsavedChanges(_) | EditorError::FormatConflict(_) => "conflict",
+                EditorError::WorkspaceNotTrusted(_) => "workspace_not_trusted",
+                EditorError::PermissionDenied(_) => "permission_denied",
+                EditorError::Document(_) | EditorError::Config(_) => "editor_error",
+            },
+            Self::External(error) => match error {
+                ExternalError::Timeout(_) => "external_timeout",
+                ExternalError::ProxyError(_) => "proxy_error",
+                ExternalError::BrowserUnavailable(_) => "browser_unavailable",
+                ExternalError::McpUnreachable(_) => "mcp_unreachable",
+                ExternalError::NavigationTimeout(_) => "navigation_timeout",
+                ExternalError::BadSelector { .. } => "invalid_selector",
+                ExternalError::ElementNotFound(_) => "not_found",
+                _ => "external_error",
+            },
+            Self::Terminal(crate::terminal::TerminalError::SessionNotFound(_))
+            | Self::Terminal(crate::terminal::TerminalError::TaskNotFound(_)) => "not_found",
+            Self::Terminal(_) => "terminal_error",
+            Self::Io(error) => io_error_code(error),
+            Self::Database(_) => "database_error",
+            Self::WorkspaceNotTrusted(_) => "workspace_not_trusted",
+            Self::Internal(_) => "internal",
+        }
+    }
 }
 
 /// Main SuperIDE application state
@@ -68,11 +240,44 @@ pub struct SuperIDE {
     /// Terminal manager for command execution
     terminal_manager: Arc<TerminalManager>,
     
+    /// Runner for project tasks such as builds and tests
+    task_runner: Arc<TaskRunner>,
+    
+    /// Registry lookups for outdated dependencies, with their cache
+    outdated_checker: Arc<OutdatedChecker>,
+    
+    /// Language and size statistics, cached per workspace tree
+    project_stats: Arc<ProjectStatsCollector>,
+    
     /// Collaboration manager for real-time editing
     collaboration_manager: Arc<CollaborationManager>,
     
+    /// Merged diagnostics from all analysis sources
+    diagnostics: Arc<DiagnosticsManager>,
+    
+    /// User-defined AI tools from configuration
+    tool_registry: Arc<ToolRegistry>,
+    
+    /// Workspace watcher feeding file change events, started on first use
+    workspace_watcher: Arc<Mutex<Option<notify::RecommendedWatcher>>>,
+    
+    /// Project-wide symbol index, built on first use
+    symbol_index: Arc<tokio::sync::OnceCell<Arc<SymbolIndex>>>,
+    
+    /// Embedding index for semantic search, filled in the background from first use
+    semantic_index: Arc<tokio::sync::OnceCell<Arc<SemanticIndex>>>,
+    
     /// Application state
     state: Arc<RwLock<IdeState>>,
+    
+    /// Set by `initialize` once the subsystems serving requests needs are up
+    ready: Arc<AtomicBool>,
+    
+    /// Set while autosave is turned off at runtime, keeping the configured interval
+    autosave_paused: Arc<AtomicBool>,
+    
+    /// Whether the workspace may run tasks, terminals and other processes
+    workspace_trust: Arc<WorkspaceTrust>,
 }
 
 /// IDE application state
@@ -112,6 +317,7 @@ pub struct EditorTab {
     pub is_modified: bool,
     pub language: String,
     pub cursor_position: (usize, usize),
+    pub encoding: crate::file_ops::encoding::TextEncoding,
 }
 
 /// User preferences
@@ -176,9 +382,35 @@ pub struct AIInteraction {
 impl SuperIDE {
     /// Create a new IDE instance
     pub async fn new(config: Configuration) -> IdeResult<Self> {
-        let ai_engine = AiEngine::new_async(AiConfig::from(&config)).await.map_err(|e| IdeError::Editor(e.to_string()))?;
-        let editor = Editor::new(&config, Arc::new(ai_engine.clone())).await.map_err(|e| IdeError::Editor(e.to_string()))?;
-        let event_bus = EventBus::new();
+        Self::with_state_path(config, PersistedState::default_path()).await
+    }
+    
+    /// Create a new IDE instance whose workspace trust is recorded in the persisted
+    /// state at `state_path`
+    pub async fn with_state_path(config: Configuration, state_path: Option<std::path::PathBuf>) -> IdeResult<Self> {
+        let event_bus = Arc::new(EventBus::new());
+        let workspace_trust = Arc::new(WorkspaceTrust::load(
+            &config.workspace_dir(),
+            state_path,
+            config.security.require_workspace_trust,
+        ).await);
+        let rules = RuleSet::new(&config.rules)
+            .with_project_overrides(&config.workspace_dir())
+            .unwrap_or_else(|e| {
+                log::warn!("Ignoring project analysis rules: {}", e);
+                RuleSet::new(&config.rules)
+            });
+        let ai_engine = AiEngine::new_async(AiConfig::from(&config)).await
+            .map_err(IdeError::Ai)?
+            .with_event_bus(event_bus.clone())
+            .with_fallback_providers(config.ai.fallback_providers.clone())
+            .with_completion_timeout(config.ai.completion_timeout_ms)
+            .with_analysis_gates(AnalysisGates::new(&config.ai.auto_analysis).with_workspace(config.workspace_dir()))
+            .with_context_builder(ContextBuilder::new(config.ai.context_tokens))
+            .with_rules(rules);
+        let editor = Editor::new(&config, Arc::new(ai_engine.clone())).await?
+            .with_event_bus(event_bus.clone())
+            .with_workspace_trust(workspace_trust.clone());
         
         // Initialize terminal manager with default config
         let terminal_config = TerminalConfig {
@@ -193,8 +425,20 @@ impl SuperIDE {
             max_output_lines: 1000,
             command_timeout: tokio::time::Duration::from_secs(30),
         };
+        let task_runner = Arc::new(TaskRunner::new(terminal_config.clone()));
         let terminal_manager = Arc::new(TerminalManager::new(terminal_config));
-        let collaboration_manager = Arc::new(CollaborationManager::new());
+        let mut collaboration_manager = CollaborationManager::new();
+        if config.collaboration.persist_documents {
+            collaboration_manager = collaboration_manager.with_store(
+                CollaborationStore::new(&config.workspace_dir()),
+                config.collaboration.snapshot_every_operations,
+            );
+        }
+        let collaboration_manager = Arc::new(collaboration_manager);
+        let diagnostics = Arc::new(DiagnosticsManager::new());
+        let tool_registry = ToolRegistry::from_config(&config.ai.tools)
+            .map_err(|e| crate::config::ConfigError::Validation(e.to_string()))?;
+        let outdated_checker = OutdatedChecker::default().with_proxy(&config.proxy);
         
         let state = IdeState {
             projects: Vec::new(),
@@ -207,13 +451,79 @@ impl SuperIDE {
             config: Arc::new(RwLock::new(config)),
             ai_engine,
             editor: Arc::new(Mutex::new(editor)),
-            event_bus: Arc::new(event_bus),
+            event_bus,
             terminal_manager,
+            task_runner,
+            outdated_checker: Arc::new(outdated_checker),
+            project_stats: Arc::new(ProjectStatsCollector::default()),
             collaboration_manager,
+            diagnostics,
+            tool_registry: Arc::new(tool_registry),
+            workspace_watcher: Arc::new(Mutex::new(None)),
+            symbol_index: Arc::new(tokio::sync::OnceCell::new()),
+            semantic_index: Arc::new(tokio::sync::OnceCell::new()),
             state: Arc::new(RwLock::new(state)),
+            ready: Arc::new(AtomicBool::new(false)),
+            autosave_paused: Arc::new(AtomicBool::new(false)),
+            workspace_trust,
         })
     }
     
+    /// Bring up what serving requests needs beyond the AI client `new` builds:
+    /// probe for git and scan the workspace into the symbol index. The IDE is
+    /// ready afterwards even when git is missing or the scan failed, as requests
+    /// needing them then fail with errors of their own.
+    pub async fn initialize(&self, git_manager: &crate::git::GitManager) {
+        match git_manager.git_version().await {
+            Some(version) => log::info!("Using {}", version),
+            None => log::warn!("Git is not installed; version control is unavailable"),
+        }
+        if let Err(e) = self.symbol_index().await {
+            log::warn!("Workspace scan failed: {}", e);
+        }
+        match self.collaboration_manager.restore().await {
+            Ok(0) => {}
+            Ok(restored) => log::info!("Restored {} collaborative document(s)", restored),
+            Err(e) => log::warn!("Could not restore collaborative documents: {}", e),
+        }
+        let snapshot_interval = self.config.read().await.collaboration.snapshot_interval_secs;
+        self.collaboration_manager.start_snapshots(std::time::Duration::from_secs(snapshot_interval.max(1)));
+        self.ready.store(true, Ordering::Release);
+        log::info!("Super IDE is ready");
+    }
+    
+    /// Whether `initialize` has finished
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+    
+    /// Turn the autosave timer on or off without changing `ide.auto_save_interval`
+    pub fn set_autosave_enabled(&self, enabled: bool) {
+        self.autosave_paused.store(!enabled, Ordering::Release);
+    }
+    
+    /// Whether the autosave timer saves: it is not turned off and has an interval
+    pub async fn is_autosave_enabled(&self) -> bool {
+        !self.autosave_paused.load(Ordering::Acquire) && self.config.read().await.ide.auto_save_interval > 0
+    }
+    
+    /// One round of the autosave timer: save modified documents unless autosave
+    /// is off, logging the ones that could not be saved
+    pub async fn autosave(&self) -> Vec<AutosaveEntry> {
+        if !self.is_autosave_enabled().await {
+            return Vec::new();
+        }
+        let entries = self.editor.lock().await.autosave().await;
+        for entry in &entries {
+            match &entry.status {
+                AutosaveStatus::Conflict => log::warn!("Autosave skipped {}: changed on disk", entry.path.display()),
+                AutosaveStatus::Failed { error } => log::warn!("Autosave of {} failed: {}", entry.path.display(), error),
+                AutosaveStatus::Saved | AutosaveStatus::Skipped => {}
+            }
+        }
+        entries
+    }
+    
     /// Get AI engine reference
     pub fn ai_engine(&self) -> &AiEngine {
         &self.ai_engine
@@ -224,6 +534,16 @@ impl SuperIDE {
         &self.collaboration_manager
     }
     
+    /// Get diagnostics manager reference
+    pub fn diagnostics(&self) -> &Arc<DiagnosticsManager> {
+        &self.diagnostics
+    }
+    
+    /// Get the registry of user-defined AI tools
+    pub fn tool_registry(&self) -> &Arc<ToolRegistry> {
+        &self.tool_registry
+    }
+    
     /// Get editor reference
     pub fn editor(&self) -> &Arc<Mutex<Editor>> {
         &self.editor
@@ -244,6 +564,89 @@ impl SuperIDE {
         &self.terminal_manager
     }
     
+    /// Get task runner reference
+    pub fn task_runner(&self) -> &Arc<TaskRunner> {
+        &self.task_runner
+    }
+    
+    /// Get the workspace's trust, which gates running its code
+    pub fn workspace_trust(&self) -> &Arc<WorkspaceTrust> {
+        &self.workspace_trust
+    }
+    
+    /// Get the outdated dependency checker
+    pub fn outdated_checker(&self) -> &Arc<OutdatedChecker> {
+        &self.outdated_checker
+    }
+    
+    /// Get the project statistics collector
+    pub fn project_stats(&self) -> &Arc<ProjectStatsCollector> {
+        &self.project_stats
+    }
+    
+    /// Get the symbol index, scanning the workspace and subscribing to file changes on first use
+    pub async fn symbol_index(&self) -> IdeResult<Arc<SymbolIndex>> {
+        let index = self.symbol_index.get_or_try_init(|| async {
+            self.watch_workspace().await?;
+            let subscriber = self.event_bus.subscribe("symbols")
+                .map_err(|e| crate::file_ops::FileOperationError::WatchError(e.to_string()))?;
+
+            let workspace = self.config.read().await.workspace_dir();
+            let index = Arc::new(SymbolIndex::new(workspace));
+            index.build().await;
+
+            let follower = index.clone();
+            tokio::spawn(async move { follower.follow(subscriber).await });
+            Ok::<_, IdeError>(index)
+        }).await?;
+        Ok(index.clone())
+    }
+    
+    /// Get the semantic search index. The first call subscribes to file changes and
+    /// starts embedding the workspace in the background; searches meanwhile see the
+    /// files indexed so far. In privacy mode the code is embedded locally, and with
+    /// local data encryption on the index is not saved to disk.
+    pub async fn semantic_index(&self) -> IdeResult<Arc<SemanticIndex>> {
+        let index = self.semantic_index.get_or_try_init(|| async {
+            self.watch_workspace().await?;
+            let subscriber = self.event_bus.subscribe("semantic-index")
+                .map_err(|e| crate::file_ops::FileOperationError::WatchError(e.to_string()))?;
+
+            let config = self.config.read().await;
+            let mut index = SemanticIndex::new(
+                config.workspace_dir(),
+                Arc::new(self.ai_engine.clone()),
+                config.ai.embedding_model.as_deref(),
+            ).with_persistence(!config.security.encrypt_local_data);
+            if config.ai.privacy_mode || config.security.local_llm_only {
+                index = index.with_local_embeddings();
+            }
+            let index = Arc::new(index);
+            drop(config);
+
+            let indexer = index.clone();
+            tokio::spawn(async move {
+                let embedded = indexer.build().await;
+                log::info!("Semantic index ready, {} file(s) embedded", embedded);
+                indexer.follow(subscriber).await;
+            });
+            Ok::<_, IdeError>(index)
+        }).await?;
+        Ok(index.clone())
+    }
+    
+    /// Start publishing workspace file changes on the event bus; a no-op once running
+    pub async fn watch_workspace(&self) -> IdeResult<()> {
+        let mut watcher = self.workspace_watcher.lock().await;
+        if watcher.is_none() {
+            let workspace = self.config.read().await.workspace_dir();
+            let started = crate::file_ops::tree_watch::watch_workspace(workspace, self.event_bus.clone())
+                .map_err(|e| crate::file_ops::FileOperationError::WatchError(e.to_string()))?;
+            *watcher = Some(started);
+        }
+        Ok(())
+    }
+    
     /// Get current IDE state
     pub async fn get_state(&self) -> IdeState {
         self.state.read().await.clone()
@@ -329,6 +732,28 @@ impl SuperIDE {
         // This would typically start the web server or desktop app
         log::info!("Starting Super IDE v{}", crate::VERSION);
         
+        // Start embedding the workspace for semantic search, when asked to
+        if self.config.read().await.ai.semantic_index_on_startup {
+            if let Err(e) = self.semantic_index().await {
+                log::warn!("Semantic index unavailable: {}", e);
+            }
+        }
+        
+        // Save modified documents every `ide.auto_save_interval` seconds; the setting
+        // is read again each round, and 0 or turning autosave off pauses it
+        let ide = self.clone();
+        tokio::spawn(async move {
+            loop {
+                if !ide.is_autosave_enabled().await {
+                    tokio::time::sleep(AUTOSAVE_DISABLED_RECHECK).await;
+                    continue;
+                }
+                let interval = ide.config.read().await.ide.auto_save_interval;
+                tokio::time::sleep(std::time::Duration::from_secs(interval)).await;
+                ide.autosave().await;
+            }
+        });
+        
         // Subscribe to AI suggestions
         let _ai_engine = self.ai_engine.clone();
         let _event_bus = self.event_bus.clone();
@@ -350,11 +775,13 @@ impl SuperIDE {
     
     /// Create a new terminal session
     pub async fn create_terminal(&self, title: Option<String>) -> IdeResult<String> {
+        self.workspace_trust.ensure_trusted()?;
         self.terminal_manager.create_session(Some("bash"), None, title).await
     }
     
     /// Start a terminal session
     pub async fn start_terminal(&self, session_id: &str) -> IdeResult<()> {
+        self.workspace_trust.ensure_trusted()?;
         self.terminal_manager.start_terminal(session_id).await
     }
     
@@ -389,8 +816,16 @@ impl SuperIDE {
 
     /// Open a file in the editor
     pub async fn open_file(&self, file_path: std::path::PathBuf) -> IdeResult<String> {
+        self.open_file_at(file_path, None).await
+    }
+
+    /// Open a file in the editor with the cursor at a 0-based (line, column)
+    pub async fn open_file_at(&self, file_path: std::path::PathBuf, cursor: Option<(usize, usize)>) -> IdeResult<String> {
         let editor = self.editor.lock().await;
         let document_id = editor.open_file(file_path.clone()).await?;
+        if let Some((line, column)) = cursor {
+            editor.set_cursor(&document_id, line, column).await?;
+        }
 
         // Get document info for state tracking
         let doc_info = {
@@ -404,9 +839,10 @@ impl SuperIDE {
                     is_modified: doc_read.is_modified,
                     language: doc_read.language.clone(),
                     cursor_position: (doc_read.cursor_line, doc_read.cursor_column),
+                    encoding: doc_read.encoding,
                 }
             } else {
-                return Err(IdeError::Editor("Failed to get document info".to_string()));
+                return Err(crate::editor::EditorError::Document("Failed to get document info".to_string()).into());
             }
         };
 
@@ -429,7 +865,9 @@ impl SuperIDE {
     /// Save the active document
     pub async fn save_active_file(&self) -> IdeResult<()> {
         let editor = self.editor.lock().await;
-        editor.save_active_document().await?;
+        if editor.save_active_document().await? == SaveOutcome::Skipped {
+            return Ok(());
+        }
 
         // Update state
         if let Some(active_doc) = editor.get_active_document().await {
@@ -495,8 +933,6 @@ impl SuperIDE {
 
     /// Get code completions for the current context
     pub async fn get_code_completions(&self, document_id: &str, cursor_position: (usize, usize), text_context: &str) -> IdeResult<Vec<crate::editor::CompletionItem>> {
-        let editor = self.editor.lock().await;
-
         // Use document_id to get document-specific context
         let document_context = self.get_document_context(document_id).await?;
         let language = document_context.language;
@@ -515,7 +951,14 @@ impl SuperIDE {
             text_after_cursor: String::new(),
         };
 
-        let completions = editor.get_completions(&context).await?;
+        // The language server is asked only after the editor lock is released
+        let query = self.editor.lock().await.language_server_completions(&context).await;
+        if let Some(query) = query {
+            if let Some(completions) = query.run().await {
+                return Ok(completions);
+            }
+        }
+        let completions = self.editor.lock().await.builtin_completions(&context).await?;
         Ok(completions)
     }
 
@@ -548,7 +991,7 @@ impl SuperIDE {
     pub async fn analyze_code(&self, code: &str, language: &str) -> IdeResult<crate::ai::AnalysisResult> {
         match self.ai_engine.analyze_code(code, language).await {
             Ok(analysis) => Ok(analysis),
-            Err(e) => Err(IdeError::AiEngine(e.to_string())),
+            Err(e) => Err(IdeError::Ai(e)),
         }
     }
 
@@ -562,11 +1005,12 @@ impl SuperIDE {
             cursor_position: request.context.as_ref().and_then(|ctx| ctx.cursor_position),
             text_before_cursor: "",
             position: None,
+            document: None,
         };
 
         match self.ai_engine.generate_completion(request).await {
             Ok(response) => Ok(response.text),
-            Err(e) => Err(IdeError::AiEngine(e.to_string())),
+            Err(e) => Err(IdeError::Ai(e)),
         }
     }
 }
@@ -0,0 +1,147 @@
+//! Persisted IDE state
+//!
+//! State that outlives a single launch and is not configuration: whether this is
+//! the first time the IDE has been opened, the workspace opened last, the
+//! projects opened recently and the workspaces the user trusts. It is kept in
+//! `.super-ide/state.json` under the home directory, since it spans workspaces,
+//! and where no workspace can vouch for itself.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// State file, relative to the home directory
+const STATE_FILE: &str = ".super-ide/state.json";
+
+/// Recent projects kept; older ones drop off the end
+pub const MAX_RECENT_PROJECTS: usize = 10;
+
+/// A workspace that has been opened
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecentProject {
+    pub path: PathBuf,
+    pub name: String,
+    pub opened_at: DateTime<Utc>,
+}
+
+/// IDE state carried between launches
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PersistedState {
+    /// True during the first launch, until a second workspace opening is recorded
+    pub first_run: bool,
+    pub last_opened_workspace: Option<PathBuf>,
+    /// Most recently opened first
+    pub recent_projects: Vec<RecentProject>,
+    /// Workspaces allowed to run code, canonicalized
+    pub trusted_workspaces: Vec<PathBuf>,
+}
+
+impl Default for PersistedState {
+    fn default() -> Self {
+        Self {
+            first_run: true,
+            last_opened_workspace: None,
+            recent_projects: Vec::new(),
+            trusted_workspaces: Vec::new(),
+        }
+    }
+}
+
+impl PersistedState {
+    /// Where the state is kept, or `None` without a home directory
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(STATE_FILE))
+    }
+
+    /// Read the state at `path`; a missing file is a first run
+    pub async fn load(path: &Path) -> Result<Self> {
+        match tokio::fs::read(path).await {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let bytes = serde_json::to_vec_pretty(self)?;
+        crate::file_ops::atomic::write_atomic(path, &bytes, false).await?;
+        Ok(())
+    }
+
+    /// Move `workspace` to the front of the recent projects, dropping any earlier
+    /// entry for the same directory and anything past `MAX_RECENT_PROJECTS`
+    pub fn record_workspace(&mut self, workspace: &Path) {
+        let path = std::fs::canonicalize(workspace).unwrap_or_else(|_| workspace.to_path_buf());
+        let name = path.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.to_string_lossy().to_string());
+
+        self.first_run = self.recent_projects.is_empty();
+        self.recent_projects.retain(|project| project.path != path);
+        self.recent_projects.insert(0, RecentProject {
+            path: path.clone(),
+            name,
+            opened_at: Utc::now(),
+        });
+        self.recent_projects.truncate(MAX_RECENT_PROJECTS);
+        self.last_opened_workspace = Some(path);
+    }
+
+    /// Whether `workspace` has been trusted
+    pub fn is_trusted(&self, workspace: &Path) -> bool {
+        let path = std::fs::canonicalize(workspace).unwrap_or_else(|_| workspace.to_path_buf());
+        self.trusted_workspaces.contains(&path)
+    }
+
+    /// Trust `workspace` to run code
+    pub fn trust_workspace(&mut self, workspace: &Path) {
+        let path = std::fs::canonicalize(workspace).unwrap_or_else(|_| workspace.to_path_buf());
+        if !self.trusted_workspaces.contains(&path) {
+            self.trusted_workspaces.push(path);
+        }
+    }
+}
+
+/// Record that `workspace` was opened in the state at `path`, returning the updated state
+pub async fn record_opened_workspace(path: &Path, workspace: &Path) -> Result<PersistedState> {
+    let mut state = PersistedState::load(path).await?;
+    state.record_workspace(workspace);
+    state.save(path).await?;
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_opening_two_workspaces_lists_the_latest_first() {
+        let root = std::env::temp_dir().join(format!("super-ide-state-{}", uuid::Uuid::new_v4()));
+        let (alpha, beta) = (root.join("alpha"), root.join("beta"));
+        std::fs::create_dir_all(&alpha).unwrap();
+        std::fs::create_dir_all(&beta).unwrap();
+        let state_path = root.join(STATE_FILE);
+
+        let first = record_opened_workspace(&state_path, &alpha).await.unwrap();
+        assert!(first.first_run);
+        let state = record_opened_workspace(&state_path, &beta).await.unwrap();
+        assert!(!state.first_run);
+
+        let state = PersistedState::load(&state_path).await.unwrap();
+        let names: Vec<&str> = state.recent_projects.iter().map(|project| project.name.as_str()).collect();
+        assert_eq!(names, ["beta", "alpha"]);
+        assert_eq!(state.last_opened_workspace, Some(std::fs::canonicalize(&beta).unwrap()));
+
+        // Reopening moves a project to the front instead of listing it twice
+        let state = record_opened_workspace(&state_path, &alpha.join("..").join("alpha")).await.unwrap();
+        let names: Vec<&str> = state.recent_projects.iter().map(|project| project.name.as_str()).collect();
+        assert_eq!(names, ["alpha", "beta"]);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,81 @@
+//! Workspace trust
+//!
+//! A project's task definitions, formatter and language server settings can run
+//! arbitrary commands, so opening a project must not be enough to run them. Until
+//! the user trusts the workspace, everything that spawns a process on its behalf
+//! is refused. Trust is recorded in the persisted state under the home directory,
+//! per canonical workspace path, rather than in the workspace itself.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::state::PersistedState;
+use super::{IdeError, IdeResult};
+
+/// Whether the open workspace may run code
+#[derive(Debug)]
+pub struct WorkspaceTrust {
+    workspace: PathBuf,
+    /// Persisted state trust is recorded in; without one, trust lasts until exit
+    state_path: Option<PathBuf>,
+    /// Whether trust is needed at all, as configured
+    required: bool,
+    trusted: AtomicBool,
+}
+
+impl WorkspaceTrust {
+    /// Trust of `workspace` as recorded in the state at `state_path`. A state that
+    /// cannot be read trusts nothing.
+    pub async fn load(workspace: &Path, state_path: Option<PathBuf>, required: bool) -> Self {
+        let trusted = match &state_path {
+            Some(path) => match PersistedState::load(path).await {
+                Ok(state) => state.is_trusted(workspace),
+                Err(e) => {
+                    log::warn!("Workspace trust unknown, state unreadable: {}", e);
+                    false
+                }
+            },
+            None => false,
+        };
+        Self {
+            workspace: workspace.to_path_buf(),
+            state_path,
+            required,
+            trusted: AtomicBool::new(trusted),
+        }
+    }
+
+    pub fn workspace(&self) -> &Path {
+        &self.workspace
+    }
+
+    /// Whether trust is configured to be needed
+    pub fn is_required(&self) -> bool {
+        self.required
+    }
+
+    /// Whether code may run, because the workspace is trusted or trust is not required
+    pub fn is_trusted(&self) -> bool {
+        !self.required || self.trusted.load(Ordering::Acquire)
+    }
+
+    /// Fail with [`IdeError::WorkspaceNotTrusted`] unless code may run
+    pub fn ensure_trusted(&self) -> IdeResult<()> {
+        if self.is_trusted() {
+            return Ok(());
+        }
+        Err(IdeError::WorkspaceNotTrusted(self.workspace.clone()))
+    }
+
+    /// Trust the workspace from now on, recording it for later launches
+    pub async fn grant(&self) -> IdeResult<()> {
+        if let Some(path) = &self.state_path {
+            let mut state = PersistedState::load(path).await.map_err(IdeError::Internal)?;
+            state.trust_workspace(&self.workspace);
+            state.save(path).await.map_err(IdeError::Internal)?;
+        }
+        self.trusted.store(true, Ordering::Release);
+        log::info!("Workspace {} trusted", self.workspace.display());
+        Ok(())
+    }
+}
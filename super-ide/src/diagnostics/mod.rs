@@ -0,0 +1,233 @@
+//! Diagnostics aggregation for Super IDE
+//!
+//! Merges findings from every analysis source into one list per file:
+//! - Local and AI code analysis issues
+//! - Security vulnerabilities
+//! - Bug predictions
+//! - Compiler output
+//! - Language servers
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::ai::{AnalysisResult, BugPrediction, CodeIssue, IssueSeverity, SecurityVulnerability};
+
+/// Where a diagnostic came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DiagnosticSource {
+    LocalAnalysis,
+    AiAnalysis,
+    Security,
+    BugPrediction,
+    Compiler,
+    LanguageServer,
+}
+
+/// A single diagnostic shown in the editor gutter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    pub severity: IssueSeverity,
+    pub sources: Vec<DiagnosticSource>,
+    pub rule_id: Option<String>,
+    pub fix_suggestion: Option<String>,
+}
+
+impl Diagnostic {
+    /// Build a diagnostic from a code analysis issue
+    pub fn from_issue(issue: &CodeIssue, source: DiagnosticSource) -> Self {
+        Self {
+            line: issue.line,
+            column: issue.column,
+            message: issue.message.clone(),
+            severity: issue.severity.clone(),
+            sources: vec![source],
+            rule_id: issue.rule_id.clone(),
+            fix_suggestion: issue.fix_suggestion.clone(),
+        }
+    }
+
+    /// Build a diagnostic from a security finding
+    pub fn from_vulnerability(vulnerability: &SecurityVulnerability) -> Self {
+        Self {
+            line: vulnerability.line,
+            column: vulnerability.column,
+            message: vulnerability.title.clone(),
+            severity: vulnerability.severity.clone(),
+            sources: vec![DiagnosticSource::Security],
+            rule_id: vulnerability.cwe_id.clone(),
+            fix_suggestion: Some(vulnerability.recommendation.clone()),
+        }
+    }
+
+    /// Build a diagnostic from a bug prediction
+    pub fn from_bug_prediction(prediction: &BugPrediction) -> Self {
+        Self {
+            line: prediction.line,
+            column: prediction.column,
+            message: prediction.description.clone(),
+            severity: prediction.severity.clone(),
+            sources: vec![DiagnosticSource::BugPrediction],
+            rule_id: Some(format!("{:?}", prediction.bug_type)),
+            fix_suggestion: Some(prediction.fix_suggestion.clone()),
+        }
+    }
+
+    /// Deduplication key: position plus whitespace/case-normalized message
+    fn key(&self) -> (usize, usize, String) {
+        let message = self.message
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+        (self.line, self.column, message)
+    }
+}
+
+/// Numeric rank used to keep the most severe duplicate
+fn severity_rank(severity: &IssueSeverity) -> u8 {
+    match severity {
+        IssueSeverity::Info => 0,
+        IssueSeverity::Warning => 1,
+        IssueSeverity::Error => 2,
+        IssueSeverity::Critical => 3,
+    }
+}
+
+/// Per-file diagnostics store, keyed by source so each source can be refreshed independently
+#[derive(Debug, Default)]
+pub struct DiagnosticsManager {
+    files: Arc<RwLock<HashMap<String, HashMap<DiagnosticSource, Vec<Diagnostic>>>>>,
+}
+
+impl DiagnosticsManager {
+    /// Create an empty diagnostics manager
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace all diagnostics from one source for a file
+    pub async fn publish(&self, file_path: &str, source: DiagnosticSource, diagnostics: Vec<Diagnostic>) {
+        let mut files = self.files.write().await;
+        files.entry(file_path.to_string())
+            .or_default()
+            .insert(source, diagnostics);
+    }
+
+    /// Publish every finding of an analysis run (issues, security and bug predictions)
+    pub async fn publish_analysis(&self, file_path: &str, source: DiagnosticSource, analysis: &AnalysisResult) {
+        let issues = analysis.issues.iter()
+            .map(|issue| Diagnostic::from_issue(issue, source))
+            .collect();
+        let vulnerabilities = analysis.security_vulnerabilities.iter()
+            .map(Diagnostic::from_vulnerability)
+            .collect();
+        let predictions = analysis.bug_predictions.iter()
+            .map(Diagnostic::from_bug_prediction)
+            .collect();
+
+        self.publish(file_path, source, issues).await;
+        self.publish(file_path, DiagnosticSource::Security, vulnerabilities).await;
+        self.publish(file_path, DiagnosticSource::BugPrediction, predictions).await;
+    }
+
+    /// Merged, deduplicated diagnostics for a file, ordered by position
+    pub async fn get(&self, file_path: &str) -> Vec<Diagnostic> {
+        let files = self.files.read().await;
+        let Some(sources) = files.get(file_path) else {
+            return Vec::new();
+        };
+
+        let mut merged: HashMap<(usize, usize, String), Diagnostic> = HashMap::new();
+        for diagnostic in sources.values().flatten() {
+            match merged.get_mut(&diagnostic.key()) {
+                Some(existing) => {
+                    for source in &diagnostic.sources {
+                        if !existing.sources.contains(source) {
+                            existing.sources.push(*source);
+                        }
+                    }
+                    if severity_rank(&diagnostic.severity) > severity_rank(&existing.severity) {
+                        existing.severity = diagnostic.severity.clone();
+                    }
+                    if existing.fix_suggestion.is_none() {
+                        existing.fix_suggestion = diagnostic.fix_suggestion.clone();
+                    }
+                    if existing.rule_id.is_none() {
+                        existing.rule_id = diagnostic.rule_id.clone();
+                    }
+                }
+                None => {
+                    merged.insert(diagnostic.key(), diagnostic.clone());
+                }
+            }
+        }
+
+        let mut diagnostics: Vec<Diagnostic> = merged.into_values().collect();
+        diagnostics.sort_by(|a, b| {
+            (a.line, a.column, &a.message).cmp(&(b.line, b.column, &b.message))
+        });
+        diagnostics
+    }
+
+    /// Drop all diagnostics for a file, e.g. when it is closed or deleted
+    pub async fn clear(&self, file_path: &str) {
+        self.files.write().await.remove(file_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(line: usize, message: &str, severity: IssueSeverity, source: DiagnosticSource) -> Diagnostic {
+        Diagnostic {
+            line,
+            column: 4,
+            message: message.to_string(),
+            severity,
+            sources: vec![source],
+            rule_id: None,
+            fix_suggestion: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_merges_overlapping_findings() {
+        let manager = DiagnosticsManager::new();
+
+        manager.publish("src/main.rs", DiagnosticSource::LocalAnalysis, vec![
+            diagnostic(3, "Avoid unwrap()", IssueSeverity::Warning, DiagnosticSource::LocalAnalysis),
+            diagnostic(9, "Unused variable", IssueSeverity::Info, DiagnosticSource::LocalAnalysis),
+        ]).await;
+        manager.publish("src/main.rs", DiagnosticSource::AiAnalysis, vec![
+            diagnostic(3, "avoid  unwrap()", IssueSeverity::Error, DiagnosticSource::AiAnalysis),
+        ]).await;
+
+        let diagnostics = manager.get("src/main.rs").await;
+        assert_eq!(diagnostics.len(), 2);
+
+        let merged = &diagnostics[0];
+        assert_eq!(merged.line, 3);
+        assert_eq!(merged.severity, IssueSeverity::Error);
+        assert!(merged.sources.contains(&DiagnosticSource::LocalAnalysis));
+        assert!(merged.sources.contains(&DiagnosticSource::AiAnalysis));
+        assert_eq!(diagnostics[1].sources, vec![DiagnosticSource::LocalAnalysis]);
+    }
+
+    #[tokio::test]
+    async fn test_republishing_replaces_source() {
+        let manager = DiagnosticsManager::new();
+
+        manager.publish("lib.rs", DiagnosticSource::Compiler, vec![
+            diagnostic(1, "expected `;`", IssueSeverity::Error, DiagnosticSource::Compiler),
+        ]).await;
+        manager.publish("lib.rs", DiagnosticSource::Compiler, Vec::new()).await;
+
+        assert!(manager.get("lib.rs").await.is_empty());
+    }
+}
@@ -0,0 +1,100 @@
+//! Completion kinds of AI suggestions
+//!
+//! Providers return bare text, so the kind the UI picks an icon by is guessed
+//! from its shape: text opening with a keyword such as `fn` or `def` is a keyword
+//! snippet, a call is a function (a method after `.`, a constructor when the name
+//! is capitalized), and a capitalized name is a class, or a struct in Rust. Other
+//! multi-line text is a snippet, anything else plain text.
+
+use crate::ai::CompletionSuggestion;
+
+use super::{CompletionItem, CompletionKind};
+
+/// Words that open a definition or statement in one of the supported languages
+const KEYWORDS: &[&str] = &[
+    "fn", "def", "function", "func", "let", "const", "var", "class", "struct", "enum", "trait", "impl", "interface",
+    "type", "mod", "module", "import", "from", "use", "pub", "async", "return", "if", "else", "for", "while", "match",
+    "lambda",
+];
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|first| first.is_alphabetic() || first == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn is_capitalized(name: &str) -> bool {
+    name.chars().next().is_some_and(char::is_uppercase)
+}
+
+/// Kind of the completion `text` suggested by the AI for `language`
+pub fn suggestion_kind(text: &str, language: &str) -> CompletionKind {
+    let text = text.trim();
+    let first_word = text.split(|c: char| !(c.is_alphanumeric() || c == '_')).next().unwrap_or("");
+    if KEYWORDS.contains(&first_word) {
+        return CompletionKind::Keyword;
+    }
+    if text.contains('\n') {
+        return CompletionKind::Snippet;
+    }
+
+    if let Some(paren) = text.find('(') {
+        // The callee is the name right before the parenthesis; macros count as functions
+        let head = text[..paren].trim_end().trim_end_matches('!');
+        let name_start = head.char_indices()
+            .rev()
+            .find(|(_, c)| !(c.is_alphanumeric() || *c == '_'))
+            .map_or(0, |(index, c)| index + c.len_utf8());
+        let name = &head[name_start..];
+        if is_identifier(name) {
+            return match head[..name_start].chars().last() {
+                Some('.') => CompletionKind::Method,
+                _ if is_capitalized(name) => CompletionKind::Constructor,
+                _ => CompletionKind::Function,
+            };
+        }
+    }
+
+    if is_identifier(text) && is_capitalized(text) {
+        return if language.eq_ignore_ascii_case("rust") {
+            CompletionKind::Struct
+        } else {
+            CompletionKind::Class
+        };
+    }
+    CompletionKind::Text
+}
+
+impl CompletionItem {
+    /// Item for a suggestion of an AI completion with overall `confidence`,
+    /// sorted ahead of keywords and built-ins
+    pub fn from_ai_suggestion(suggestion: &CompletionSuggestion, confidence: f32, language: &str) -> Self {
+        Self {
+            label: suggestion.text.clone(),
+            kind: suggestion_kind(&suggestion.text, language),
+            detail: Some("AI suggested".to_string()),
+            documentation: Some(format!("AI confidence: {:.1}%", confidence * 100.0)),
+            insert_text: suggestion.text.clone(),
+            sort_text: format!("a{}", suggestion.text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_call_suggestion_is_a_function() {
+        assert!(matches!(suggestion_kind("new_vec()", "rust"), CompletionKind::Function));
+        assert!(matches!(suggestion_kind("println!(", "rust"), CompletionKind::Function));
+        assert!(matches!(suggestion_kind("self.items.push(item)", "rust"), CompletionKind::Method));
+        assert!(matches!(suggestion_kind("Point(1, 2)", "python"), CompletionKind::Constructor));
+        assert!(matches!(suggestion_kind("HashMap", "rust"), CompletionKind::Struct));
+        assert!(matches!(suggestion_kind("HashMap", "typescript"), CompletionKind::Class));
+        assert!(matches!(suggestion_kind("fn parse(input: &str)", "rust"), CompletionKind::Keyword));
+        assert!(matches!(suggestion_kind("def area(self):", "python"), CompletionKind::Keyword));
+        assert!(matches!(suggestion_kind("x + 1\ny - 1", "python"), CompletionKind::Snippet));
+        assert!(matches!(suggestion_kind("count", "rust"), CompletionKind::Text));
+    }
+}
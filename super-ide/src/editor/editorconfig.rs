@@ -0,0 +1,483 @@
+//! `.editorconfig` support
+//!
+//! Settings come from every `.editorconfig` between a file and the nearest one marked
+//! `root = true`. Files closer to the document win, and within one file later sections
+//! win over earlier ones. Whatever they leave unset falls back to the editor settings.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::config::EditorSettings;
+
+const FILE_NAME: &str = ".editorconfig";
+
+/// `indent_style` values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndentStyle {
+    Space,
+    Tab,
+}
+
+/// `end_of_line` values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EndOfLine {
+    Lf,
+    Crlf,
+    Cr,
+}
+
+impl EndOfLine {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::Crlf => "\r\n",
+            Self::Cr => "\r",
+        }
+    }
+}
+
+/// Properties `.editorconfig` files set for one document; `None` where none applies
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EditorConfig {
+    pub indent_style: Option<IndentStyle>,
+    pub indent_size: Option<usize>,
+    pub end_of_line: Option<EndOfLine>,
+    pub insert_final_newline: Option<bool>,
+    pub trim_trailing_whitespace: Option<bool>,
+}
+
+impl EditorConfig {
+    /// Properties for the file at `path`, read from the `.editorconfig` files above it.
+    /// Unreadable files are skipped.
+    pub async fn for_file(path: &Path) -> Self {
+        let path = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            std::env::current_dir().map(|dir| dir.join(path)).unwrap_or_else(|_| path.to_path_buf())
+        };
+
+        // Nearest first, stopping at the first root file
+        let mut files = Vec::new();
+        for dir in path.ancestors().skip(1) {
+            let config_path = dir.join(FILE_NAME);
+            let content = match tokio::fs::read_to_string(&config_path).await {
+                Ok(content) => content,
+                Err(e) => {
+                    if e.kind() != std::io::ErrorKind::NotFound {
+                        log::warn!("Skipping {}: {}", config_path.display(), e);
+                    }
+                    continue;
+                }
+            };
+            let file = EditorConfigFile::parse(dir, &content);
+            let root = file.root;
+            files.push(file);
+            if root {
+                break;
+            }
+        }
+
+        let mut config = Self::default();
+        for file in files.iter().rev() {
+            file.apply_to(&path, &mut config);
+        }
+        config
+    }
+
+    fn set(&mut self, key: &str, value: &str) {
+        let value = value.to_lowercase();
+        let unset = value == "unset";
+        let flag = || match value.as_str() {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        };
+        match key {
+            "indent_style" => {
+                self.indent_style = match value.as_str() {
+                    "space" => Some(IndentStyle::Space),
+                    "tab" => Some(IndentStyle::Tab),
+                    _ if unset => None,
+                    _ => return,
+                }
+            }
+            "indent_size" => {
+                self.indent_size = match value.parse::<usize>() {
+                    Ok(size) if size > 0 => Some(size),
+                    _ if unset => None,
+                    _ => return,
+                }
+            }
+            "end_of_line" => {
+                self.end_of_line = match value.as_str() {
+                    "lf" => Some(EndOfLine::Lf),
+                    "crlf" => Some(EndOfLine::Crlf),
+                    "cr" => Some(EndOfLine::Cr),
+                    _ if unset => None,
+                    _ => return,
+                }
+            }
+            "insert_final_newline" if unset || flag().is_some() => self.insert_final_newline = flag(),
+            "trim_trailing_whitespace" if unset || flag().is_some() => self.trim_trailing_whitespace = flag(),
+            _ => {}
+        }
+    }
+
+    /// Combine with the editor settings, which fill in anything left unset
+    pub fn effective(&self, settings: &EditorSettings) -> EffectiveSettings {
+        EffectiveSettings {
+            indent_style: self.indent_style.unwrap_or(if settings.insert_spaces { IndentStyle::Space } else { IndentStyle::Tab }),
+            indent_size: self.indent_size.unwrap_or(settings.tab_size),
+            end_of_line: self.end_of_line,
+            insert_final_newline: self.insert_final_newline,
+            trim_trailing_whitespace: self.trim_trailing_whitespace,
+        }
+    }
+}
+
+/// Formatting settings in effect for one document. Line ending, final newline and
+/// trailing whitespace have no editor-wide setting: `None` leaves them as they are.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EffectiveSettings {
+    pub indent_style: IndentStyle,
+    pub indent_size: usize,
+    pub end_of_line: Option<EndOfLine>,
+    pub insert_final_newline: Option<bool>,
+    pub trim_trailing_whitespace: Option<bool>,
+}
+
+impl EffectiveSettings {
+    /// One level of indentation
+    pub fn indent_unit(&self) -> String {
+        super::indent_unit(self.indent_size, self.indent_style == IndentStyle::Space)
+    }
+
+    /// Apply the line ending, final newline and trailing whitespace settings to `content`
+    pub fn normalize(&self, content: &str) -> String {
+        if self.end_of_line.is_none() && self.insert_final_newline.is_none() && self.trim_trailing_whitespace != Some(true) {
+            return content.to_string();
+        }
+
+        let detected = if content.contains("\r\n") {
+            EndOfLine::Crlf
+        } else if content.contains('\r') {
+            EndOfLine::Cr
+        } else {
+            EndOfLine::Lf
+        };
+        let eol = self.end_of_line.unwrap_or(detected).as_str();
+
+        let unified = content.replace("\r\n", "\n").replace('\r', "\n");
+        let had_final_newline = unified.ends_with('\n');
+        let mut lines: Vec<&str> = unified.split('\n').collect();
+        if had_final_newline {
+            lines.pop();
+        }
+        if self.insert_final_newline == Some(false) {
+            while lines.last().is_some_and(|line| line.is_empty()) {
+                lines.pop();
+            }
+        }
+        if self.trim_trailing_whitespace == Some(true) {
+            for line in &mut lines {
+                *line = line.trim_end_matches([' ', '\t']);
+            }
+        }
+
+        let mut normalized = lines.join(eol);
+        if !lines.is_empty() && self.insert_final_newline.unwrap_or(had_final_newline) {
+            normalized.push_str(eol);
+        }
+        normalized
+    }
+}
+
+/// One parsed `.editorconfig`
+#[derive(Debug, Clone)]
+pub struct EditorConfigFile {
+    /// Directory the file is in; section globs are relative to it
+    pub dir: PathBuf,
+    /// Whether the search for further files stops here
+    pub root: bool,
+    sections: Vec<Section>,
+}
+
+#[derive(Debug, Clone)]
+struct Section {
+    /// `None` when the glob could not be compiled, so the section never applies
+    glob: Option<SectionGlob>,
+    properties: Vec<(String, String)>,
+}
+
+impl EditorConfigFile {
+    /// Parse the contents of the `.editorconfig` in `dir`
+    pub fn parse(dir: &Path, content: &str) -> Self {
+        let mut file = Self { dir: dir.to_path_buf(), root: false, sections: Vec::new() };
+
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+            if let Some(glob) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+                file.sections.push(Section { glob: SectionGlob::new(glob), properties: Vec::new() });
+                continue;
+            }
+            let Some((key, value)) = line.split_once(['=', ':']) else {
+                continue;
+            };
+            let (key, value) = (key.trim().to_lowercase(), value.trim().to_string());
+            match file.sections.last_mut() {
+                Some(section) => section.properties.push((key, value)),
+                None if key == "root" => file.root = value.eq_ignore_ascii_case("true"),
+                None => {}
+            }
+        }
+        file
+    }
+
+    /// Set the properties of every section matching `path` in file order
+    pub fn apply_to(&self, path: &Path, config: &mut EditorConfig) {
+        let Ok(relative) = path.strip_prefix(&self.dir) else {
+            return;
+        };
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        for section in &self.sections {
+            if section.glob.as_ref().is_some_and(|glob| glob.matches(&relative)) {
+                for (key, value) in &section.properties {
+                    config.set(key, value);
+                }
+            }
+        }
+    }
+}
+
+/// Section name such as `*.{js,ts}`, `lib/**.py` or `file[0-9].txt`
+#[derive(Debug, Clone)]
+struct SectionGlob {
+    regex: Regex,
+    /// Bounds of each `{n..m}` range, in the order of their capture groups
+    ranges: Vec<(i64, i64)>,
+}
+
+impl SectionGlob {
+    fn new(glob: &str) -> Option<Self> {
+        let mut ranges = Vec::new();
+        // Globs without a slash match the file name at any depth
+        let body = match glob.strip_prefix('/') {
+            Some(anchored) => translate_glob(anchored, &mut ranges),
+            None if glob.contains('/') => translate_glob(glob, &mut ranges),
+            None => format!("(?:.*/)?{}", translate_glob(glob, &mut ranges)),
+        };
+        let regex = Regex::new(&format!("^{}$", body)).ok()?;
+        Some(Self { regex, ranges })
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        let Some(captures) = self.regex.captures(path) else {
+            return false;
+        };
+        self.ranges.iter().enumerate().all(|(i, (low, high))| {
+            captures.get(i + 1)
+                .and_then(|number| number.as_str().parse::<i64>().ok())
+                .is_some_and(|number| (*low..=*high).contains(&number))
+        })
+    }
+}
+
+/// Regex source for an EditorConfig glob. `{n..m}` ranges become capture groups, checked
+/// against the bounds pushed to `ranges` after matching.
+fn translate_glob(glob: &str, ranges: &mut Vec<(i64, i64)>) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut regex = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                regex.push_str(".*");
+                i += 1;
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '\\' if i + 1 < chars.len() => {
+                regex.push_str(&regex::escape(&chars[i + 1].to_string()));
+                i += 1;
+            }
+            '[' => match chars[i + 1..].iter().position(|&c| c == ']') {
+                Some(length) => {
+                    let class: String = chars[i + 1..i + 1 + length].iter().collect();
+                    let class = match class.strip_prefix('!') {
+                        Some(negated) => format!("^{}", negated),
+                        None => class,
+                    };
+                    regex.push_str(&format!("[{}]", class.replace('\\', "\\\\").replace('[', "\\[")));
+                    i += length + 1;
+                }
+                None => regex.push_str("\\["),
+            },
+            '{' => match matching_brace(&chars, i) {
+                Some(end) => {
+                    let inner: String = chars[i + 1..end].iter().collect();
+                    regex.push_str(&translate_braces(&inner, ranges));
+                    i = end;
+                }
+                None => regex.push_str("\\{"),
+            },
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    regex
+}
+
+/// Index of the `}` closing the `{` at `open`
+fn matching_brace(chars: &[char], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = open;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// `{a,b}` alternatives or a `{n..m}` numeric range; a single word stays literal
+fn translate_braces(inner: &str, ranges: &mut Vec<(i64, i64)>) -> String {
+    if let Some((low, high)) = inner.split_once("..") {
+        if let (Ok(low), Ok(high)) = (low.parse::<i64>(), high.parse::<i64>()) {
+            ranges.push((low.min(high), low.max(high)));
+            return r"([+-]?\d+)".to_string();
+        }
+    }
+
+    let mut alternatives = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in inner.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                alternatives.push(&inner[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    alternatives.push(&inner[start..]);
+
+    if alternatives.len() == 1 {
+        return format!("\\{{{}\\}}", translate_glob(inner, ranges));
+    }
+    let alternatives: Vec<String> = alternatives.into_iter().map(|alternative| translate_glob(alternative, ranges)).collect();
+    format!("(?:{})", alternatives.join("|"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn config_for(files: &[(&str, &str)], path: &str) -> EditorConfig {
+        let root = std::env::temp_dir().join(format!("super-ide-editorconfig-{}", uuid::Uuid::new_v4()));
+        for (file, content) in files {
+            let file = root.join(file);
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            std::fs::write(file, content).unwrap();
+        }
+        let config = EditorConfig::for_file(&root.join(path)).await;
+        std::fs::remove_dir_all(&root).unwrap();
+        config
+    }
+
+    #[test]
+    fn test_section_globs() {
+        let glob = |pattern: &str| SectionGlob::new(pattern).unwrap();
+
+        assert!(glob("*.rs").matches("src/editor/mod.rs"));
+        assert!(!glob("*.rs").matches("src/mod.rsx"));
+        assert!(glob("*.{js,ts}").matches("web/app.ts"));
+        assert!(!glob("*.{js,ts}").matches("web/app.tsx"));
+        assert!(glob("src/*.py").matches("src/main.py"));
+        assert!(!glob("src/*.py").matches("src/pkg/main.py"));
+        assert!(!glob("src/*.py").matches("lib/src/main.py"));
+        assert!(glob("/lib/**.py").matches("lib/pkg/deep/mod.py"));
+        assert!(glob("Makefile").matches("tools/Makefile"));
+        assert!(glob("file[0-9].txt").matches("file7.txt"));
+        assert!(!glob("file[!0-9].txt").matches("file7.txt"));
+        assert!(glob("part{1..12}.md").matches("part10.md"));
+        assert!(!glob("part{1..12}.md").matches("part13.md"));
+        assert!(glob("{single}.txt").matches("{single}.txt"));
+    }
+
+    #[tokio::test]
+    async fn test_nearest_file_and_later_sections_win() {
+        let config = config_for(&[
+            (".editorconfig", "root = true\n\n[*]\nindent_style = space\nindent_size = 4\nend_of_line = lf\n\n[*.md]\ntrim_trailing_whitespace = false\n"),
+            ("web/.editorconfig", "[*.{js,ts}]\nindent_size = 2\ninsert_final_newline = true\n\n[legacy/*.js]\nindent_style = tab\nend_of_line = unset\n"),
+        ], "web/legacy/old.js").await;
+
+        assert_eq!(config, EditorConfig {
+            indent_style: Some(IndentStyle::Tab),
+            indent_size: Some(2),
+            end_of_line: None,
+            insert_final_newline: Some(true),
+            trim_trailing_whitespace: None,
+        });
+    }
+
+    #[tokio::test]
+    async fn test_root_stops_the_search() {
+        let files = [
+            (".editorconfig", "[*]\ntrim_trailing_whitespace = true\nindent_size = 8\n"),
+            ("project/.editorconfig", "root = true\n[*.py]\nindent_size = 4\n"),
+        ];
+        let config = config_for(&files, "project/app/main.py").await;
+        assert_eq!(config.indent_size, Some(4));
+        assert_eq!(config.trim_trailing_whitespace, None);
+
+        // Without the root marker the outer file applies too, but the nearer one still wins
+        let files = [files[0], ("project/.editorconfig", "[*.py]\nindent_size = 4\n")];
+        let config = config_for(&files, "project/app/main.py").await;
+        assert_eq!(config.indent_size, Some(4));
+        assert_eq!(config.trim_trailing_whitespace, Some(true));
+    }
+
+    #[test]
+    fn test_effective_settings_override_editor_settings() {
+        let editor = crate::config::Configuration::default().editor;
+        let config = EditorConfig { indent_style: Some(IndentStyle::Tab), ..EditorConfig::default() };
+        let settings = config.effective(&editor);
+        assert_eq!(settings.indent_unit(), "\t");
+        assert_eq!(settings.indent_size, editor.tab_size);
+
+        let settings = EffectiveSettings {
+            end_of_line: Some(EndOfLine::Crlf),
+            insert_final_newline: Some(true),
+            trim_trailing_whitespace: Some(true),
+            ..settings
+        };
+        assert_eq!(settings.normalize("fn a() {  \n}\t"), "fn a() {\r\n}\r\n");
+
+        let untouched = EditorConfig::default().effective(&editor);
+        assert_eq!(untouched.normalize("x = 1  \r\n"), "x = 1  \r\n");
+        let no_final_newline = EffectiveSettings { insert_final_newline: Some(false), ..untouched };
+        assert_eq!(no_final_newline.normalize("x = 1\n\n"), "x = 1");
+    }
+}
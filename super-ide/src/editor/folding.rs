@@ -0,0 +1,152 @@
+//! Code folding
+//!
+//! Fold regions come from one of two strategies, which each language's
+//! [`LanguageSupport`](super::LanguageSupport) picks. Brace languages fold from a
+//! line opening `{` to the line closing it. Python and YAML have no braces to go
+//! by: a region starts at a line followed by deeper-indented ones and ends at the
+//! last line before indentation returns to its level.
+
+use serde::{Deserialize, Serialize};
+
+use super::{lex_code, Editor, EditorError, FoldPoint, LexState};
+
+/// Columns a tab indents by
+const TAB_WIDTH: usize = 4;
+
+/// How a language's fold regions are found
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FoldStrategy {
+    #[default]
+    Braces,
+    Indentation,
+}
+
+/// Fold regions of `content`, 0-based and ordered by their first line. Only
+/// regions spanning more than one line can fold.
+pub fn compute_fold_points(content: &str, strategy: FoldStrategy) -> Vec<FoldPoint> {
+    let mut regions = match strategy {
+        FoldStrategy::Braces => brace_regions(content),
+        FoldStrategy::Indentation => indentation_regions(content),
+    };
+    regions.sort_unstable();
+    regions.into_iter()
+        .map(|(start_line, end_line)| FoldPoint { start_line, end_line, is_folded: false })
+        .collect()
+}
+
+/// Lines of matching `{` and `}`, skipping braces in strings, char literals and
+/// comments as reindenting does
+fn brace_regions(content: &str) -> Vec<(usize, usize)> {
+    let mut regions = Vec::new();
+    let mut open = Vec::new();
+    let mut state = LexState::Code;
+    for (index, line) in content.lines().enumerate() {
+        state = lex_code(line, state, |c| match c {
+            '{' => open.push(index),
+            '}' => {
+                if let Some(start) = open.pop().filter(|start| *start < index) {
+                    regions.push((start, index));
+                }
+            }
+            _ => {}
+        });
+    }
+    regions
+}
+
+fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+        .sum()
+}
+
+/// Blocks of deeper indentation, each starting at the line above them. Blank
+/// lines never end a block, nor does one end on them.
+fn indentation_regions(content: &str) -> Vec<(usize, usize)> {
+    let mut regions = Vec::new();
+    // Lines that may head a block, with their indentation, innermost last
+    let mut headers: Vec<(usize, usize)> = Vec::new();
+    let mut last_line = 0;
+    for (index, line) in content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let indent = indent_width(line);
+        while let Some(&(start, header_indent)) = headers.last() {
+            if header_indent < indent {
+                break;
+            }
+            headers.pop();
+            if last_line > start {
+                regions.push((start, last_line));
+            }
+        }
+        headers.push((index, indent));
+        last_line = index;
+    }
+    regions.extend(headers.into_iter().filter(|(start, _)| last_line > *start).map(|(start, _)| (start, last_line)));
+    regions
+}
+
+/// Keep folded the regions of `fold_points` starting on a line a folded region of
+/// `previous` started on
+pub(super) fn keep_folded(fold_points: &mut [FoldPoint], previous: &[FoldPoint]) {
+    for point in fold_points {
+        point.is_folded = previous.iter().any(|old| old.is_folded && old.start_line == point.start_line);
+    }
+}
+
+impl Editor {
+    /// Recompute a document's fold regions with its language's strategy now, as the
+    /// background parse does after edits; regions starting on a line that was
+    /// folded before stay folded
+    pub async fn update_fold_points(&self, document_id: &str) -> Result<Vec<FoldPoint>, EditorError> {
+        let document = self.find_document(document_id).await?;
+        let language = document.read().await.language.clone();
+        let strategy = self.fold_strategy(&language).await;
+
+        let mut doc = document.write().await;
+        let content = doc.content.read().await.to_string();
+        let mut fold_points = compute_fold_points(&content, strategy);
+        keep_folded(&mut fold_points, &doc.fold_points);
+        doc.fold_points = fold_points.clone();
+        Ok(fold_points)
+    }
+
+    /// The fold strategy of `language`, braces for languages without support
+    pub(super) async fn fold_strategy(&self, language: &str) -> FoldStrategy {
+        self.language_support.read().await.iter()
+            .find(|lang| lang.name == language)
+            .map(|lang| lang.fold_strategy)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn regions(content: &str, strategy: FoldStrategy) -> Vec<(usize, usize)> {
+        compute_fold_points(content, strategy).into_iter().map(|point| (point.start_line, point.end_line)).collect()
+    }
+
+    #[test]
+    fn test_python_function_folds_over_its_body() {
+        let code = "import os\n\ndef load(path):\n    if path:\n        return os.stat(path)\n\n    return None\n\nprint(load('x'))\n";
+        assert_eq!(regions(code, FoldStrategy::Indentation), [(2, 6), (3, 4)]);
+
+        let yaml = "server:\n  port: 8080\n  tls:\n    enabled: true\nname: demo\n";
+        assert_eq!(regions(yaml, FoldStrategy::Indentation), [(0, 3), (2, 3)]);
+    }
+
+    #[test]
+    fn test_brace_language_folds_on_matching_braces() {
+        let code = "fn main() {\n    let s = \"}\";\n    if s.is_empty() {\n        return;\n    }\n    let v = vec![1, 2]; { }\n}\n";
+        assert_eq!(regions(code, FoldStrategy::Braces), [(0, 6), (2, 4)]);
+    }
+
+    #[test]
+    fn test_braces_in_char_literals_and_comments_do_not_fold() {
+        let code = "fn is_open(c: char) -> bool {\n    c == '{' // not } here\n        || c == '\\''\n    # nor } here\n}\n";
+        assert_eq!(regions(code, FoldStrategy::Braces), [(0, 4)]);
+    }
+}
@@ -0,0 +1,85 @@
+//! `file:line:column` locations
+//!
+//! Compilers, test runners and stack traces print locations as a path followed by a
+//! 1-based line and column, each after a colon. Only trailing numeric parts are
+//! taken as the position, so the colon of a Windows drive letter stays in the path.
+
+use std::path::PathBuf;
+
+/// A file with an optional 1-based position in it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileLocation {
+    pub path: PathBuf,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+impl FileLocation {
+    /// Split `path`, `path:line` or `path:line:column` into its parts. A text with no
+    /// numeric suffix is all path.
+    pub fn parse(spec: &str) -> Self {
+        // `grep -n` and some compilers leave a colon after the position
+        let spec = spec.strip_suffix(':').unwrap_or(spec);
+
+        let mut path = spec;
+        let mut numbers = Vec::new();
+        while numbers.len() < 2 {
+            let Some((rest, last)) = path.rsplit_once(':') else {
+                break;
+            };
+            let Ok(number) = last.parse::<usize>() else {
+                break;
+            };
+            // `C:10` is a drive-relative path, not file `C` at line 10
+            if rest.is_empty() || is_drive_letter(rest) {
+                break;
+            }
+            numbers.insert(0, number);
+            path = rest;
+        }
+
+        Self {
+            path: PathBuf::from(path),
+            line: numbers.first().copied(),
+            column: numbers.get(1).copied(),
+        }
+    }
+
+    /// The 0-based (line, column) to put the cursor at, when a line was given
+    pub fn cursor(&self) -> Option<(usize, usize)> {
+        let line = self.line?;
+        Some((line.saturating_sub(1), self.column.unwrap_or(1).saturating_sub(1)))
+    }
+}
+
+fn is_drive_letter(text: &str) -> bool {
+    text.len() == 1 && text.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(path: &str, line: Option<usize>, column: Option<usize>) -> FileLocation {
+        FileLocation { path: PathBuf::from(path), line, column }
+    }
+
+    #[test]
+    fn test_windows_drive_letter_stays_in_the_path() {
+        assert_eq!(FileLocation::parse(r"C:\foo\bar.rs:10"), location(r"C:\foo\bar.rs", Some(10), None));
+        assert_eq!(FileLocation::parse(r"C:\foo\bar.rs:10:3"), location(r"C:\foo\bar.rs", Some(10), Some(3)));
+        assert_eq!(FileLocation::parse(r"C:\foo\bar.rs"), location(r"C:\foo\bar.rs", None, None));
+        assert_eq!(FileLocation::parse("C:10"), location("C:10", None, None));
+    }
+
+    #[test]
+    fn test_unix_locations() {
+        assert_eq!(FileLocation::parse("src/main.rs:42:5"), location("src/main.rs", Some(42), Some(5)));
+        assert_eq!(FileLocation::parse("src/main.rs:42:"), location("src/main.rs", Some(42), None));
+        assert_eq!(FileLocation::parse("notes/12:30.txt"), location("notes/12:30.txt", None, None));
+        assert_eq!(FileLocation::parse("a.rs:1:2:3").path, PathBuf::from("a.rs:1"));
+        assert_eq!(FileLocation::parse("src/main.rs:42:5").cursor(), Some((41, 4)));
+        assert_eq!(FileLocation::parse("src/main.rs:42").cursor(), Some((41, 0)));
+        assert_eq!(FileLocation::parse("src/main.rs").cursor(), None);
+    }
+}
@@ -0,0 +1,562 @@
+//! Language server passthrough
+//!
+//! Launches the language server configured for a language over stdio and proxies
+//! completion requests and published diagnostics. Callers fall back to the built-in
+//! heuristics whenever no server is configured, it fails to start, or it has exited.
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::{oneshot, Mutex, RwLock};
+use tokio::time::Duration;
+
+use super::{CompletionItem, CompletionKind};
+use crate::ai::IssueSeverity;
+use crate::config::LanguageServerConfig;
+use crate::diagnostics::{Diagnostic, DiagnosticSource};
+
+/// How long to wait for a response before treating the server as unresponsive
+const LSP_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Language server errors
+#[derive(thiserror::Error, Debug)]
+pub enum LspError {
+    #[error("Failed to start language server `{0}`: {1}")]
+    Spawn(String, String),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Language server protocol error: {0}")]
+    Protocol(String),
+
+    #[error("Language server did not answer {0} in time")]
+    Timeout(String),
+
+    #[error("Language server has exited")]
+    Exited,
+}
+
+type PendingRequests = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<Value, String>>>>>;
+type Writer = Arc<Mutex<Box<dyn AsyncWrite + Send + Unpin>>>;
+
+/// Connection to one running language server
+pub struct LspClient {
+    writer: Writer,
+    next_id: AtomicU64,
+    pending: PendingRequests,
+    diagnostics: Arc<RwLock<HashMap<String, Vec<Diagnostic>>>>,
+    alive: Arc<AtomicBool>,
+    /// Version of every document opened on the server
+    versions: Mutex<HashMap<String, i64>>,
+    _child: Option<Child>,
+}
+
+impl std::fmt::Debug for LspClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LspClient")
+            .field("alive", &self.is_alive())
+            .finish_non_exhaustive()
+    }
+}
+
+impl LspClient {
+    /// Launch the configured server and initialize it for the workspace at `root`
+    pub async fn spawn(config: &LanguageServerConfig, root: &Path) -> Result<Self, LspError> {
+        let mut child = Command::new(&config.command)
+            .args(&config.args)
+            .current_dir(root)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| LspError::Spawn(config.command.clone(), e.to_string()))?;
+
+        let stdin = child.stdin.take().ok_or_else(|| LspError::Protocol("server stdin unavailable".to_string()))?;
+        let stdout = child.stdout.take().ok_or_else(|| LspError::Protocol("server stdout unavailable".to_string()))?;
+
+        let mut client = Self::connect(stdout, stdin, root).await?;
+        client._child = Some(child);
+        Ok(client)
+    }
+
+    /// Speak the protocol over an existing transport and run the initialize handshake
+    pub async fn connect<R, W>(reader: R, writer: W, root: &Path) -> Result<Self, LspError>
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        let client = Self {
+            writer: Arc::new(Mutex::new(Box::new(writer))),
+            next_id: AtomicU64::new(1),
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            diagnostics: Arc::new(RwLock::new(HashMap::new())),
+            alive: Arc::new(AtomicBool::new(true)),
+            versions: Mutex::new(HashMap::new()),
+            _child: None,
+        };
+
+        tokio::spawn(read_loop(
+            BufReader::new(reader),
+            client.writer.clone(),
+            client.pending.clone(),
+            client.diagnostics.clone(),
+            client.alive.clone(),
+        ));
+
+        client.request("initialize", json!({
+            "processId": std::process::id(),
+            "rootUri": file_uri(root),
+            "capabilities": {
+                "textDocument": {
+                    "completion": { "completionItem": { "snippetSupport": false } },
+                    "publishDiagnostics": {},
+                },
+            },
+        })).await?;
+        client.notify("initialized", json!({})).await?;
+
+        Ok(client)
+    }
+
+    /// Whether the server is still running
+    pub fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::SeqCst)
+    }
+
+    /// Send the full text of a document, opening it on first use
+    pub async fn sync_document(&self, path: &Path, language_id: &str, text: &str) -> Result<(), LspError> {
+        let uri = file_uri(path);
+        let mut versions = self.versions.lock().await;
+        match versions.get_mut(&uri) {
+            Some(version) => {
+                *version += 1;
+                self.notify("textDocument/didChange", json!({
+                    "textDocument": { "uri": uri, "version": *version },
+                    "contentChanges": [{ "text": text }],
+                })).await
+            }
+            None => {
+                versions.insert(uri.clone(), 1);
+                self.notify("textDocument/didOpen", json!({
+                    "textDocument": { "uri": uri, "languageId": language_id, "version": 1, "text": text },
+                })).await
+            }
+        }
+    }
+
+    /// Completions at a 0-based position in a synced document
+    pub async fn completion(&self, path: &Path, line: usize, character: usize) -> Result<Vec<CompletionItem>, LspError> {
+        let result = self.request("textDocument/completion", json!({
+            "textDocument": { "uri": file_uri(path) },
+            "position": { "line": line, "character": character },
+        })).await?;
+
+        // Either a bare array or a CompletionList
+        let items = match &result {
+            Value::Array(items) => items.as_slice(),
+            Value::Object(list) => list.get("items").and_then(Value::as_array).map_or(&[][..], Vec::as_slice),
+            _ => &[],
+        };
+        Ok(items.iter().filter_map(completion_item).collect())
+    }
+
+    /// Latest diagnostics the server published for a document
+    pub async fn diagnostics(&self, path: &Path) -> Vec<Diagnostic> {
+        self.diagnostics.read().await
+            .get(&file_uri(path))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    async fn request(&self, method: &str, params: Value) -> Result<Value, LspError> {
+        if !self.is_alive() {
+            return Err(LspError::Exited);
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().await.insert(id, tx);
+        write_message(&self.writer, &json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })).await?;
+
+        match tokio::time::timeout(LSP_REQUEST_TIMEOUT, rx).await {
+            Ok(Ok(Ok(result))) => Ok(result),
+            Ok(Ok(Err(message))) => Err(LspError::Protocol(message)),
+            Ok(Err(_)) => Err(LspError::Exited),
+            Err(_) => {
+                self.pending.lock().await.remove(&id);
+                Err(LspError::Timeout(method.to_string()))
+            }
+        }
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> Result<(), LspError> {
+        if !self.is_alive() {
+            return Err(LspError::Exited);
+        }
+        write_message(&self.writer, &json!({ "jsonrpc": "2.0", "method": method, "params": params })).await
+    }
+}
+
+/// `file://` URI for a path
+fn file_uri(path: &Path) -> String {
+    reqwest::Url::from_file_path(path)
+        .map(|url| url.to_string())
+        .unwrap_or_else(|_| format!("file://{}", path.display()))
+}
+
+async fn write_message(writer: &Writer, message: &Value) -> Result<(), LspError> {
+    let body = serde_json::to_vec(message).map_err(|e| LspError::Protocol(e.to_string()))?;
+    let mut writer = writer.lock().await;
+    writer.write_all(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes()).await?;
+    writer.write_all(&body).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Read one `Content-Length` framed message; `None` at end of stream
+async fn read_message<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Option<Value>, LspError> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let length = content_length.ok_or_else(|| LspError::Protocol("missing Content-Length".to_string()))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+    serde_json::from_slice(&body).map(Some).map_err(|e| LspError::Protocol(e.to_string()))
+}
+
+/// Dispatch server messages until the stream closes, then fail outstanding requests
+async fn read_loop<R: AsyncBufRead + Unpin>(
+    mut reader: R,
+    writer: Writer,
+    pending: PendingRequests,
+    diagnostics: Arc<RwLock<HashMap<String, Vec<Diagnostic>>>>,
+    alive: Arc<AtomicBool>,
+) {
+    while let Ok(Some(message)) = read_message(&mut reader).await {
+        let method = message.get("method").and_then(Value::as_str);
+        let id = message.get("id").and_then(Value::as_u64);
+
+        match (method, id) {
+            // Response to one of our requests
+            (None, Some(id)) => {
+                if let Some(tx) = pending.lock().await.remove(&id) {
+                    let outcome = match message.get("error") {
+                        Some(error) => Err(error.get("message").and_then(Value::as_str).unwrap_or("request failed").to_string()),
+                        None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+                    };
+                    let _ = tx.send(outcome);
+                }
+            }
+            (Some("textDocument/publishDiagnostics"), None) => {
+                let params = &message["params"];
+                if let Some(uri) = params["uri"].as_str() {
+                    let published = params["diagnostics"].as_array()
+                        .map(|items| items.iter().filter_map(diagnostic).collect())
+                        .unwrap_or_default();
+                    diagnostics.write().await.insert(uri.to_string(), published);
+                }
+            }
+            // Server-to-client requests such as progress registration; acknowledge and move on
+            (Some(_), Some(_)) => {
+                let _ = write_message(&writer, &json!({ "jsonrpc": "2.0", "id": message["id"], "result": null })).await;
+            }
+            _ => {}
+        }
+    }
+
+    alive.store(false, Ordering::SeqCst);
+    pending.lock().await.clear();
+}
+
+/// Convert an LSP completion item
+fn completion_item(item: &Value) -> Option<CompletionItem> {
+    let label = item.get("label")?.as_str()?.to_string();
+    let kind = match item.get("kind").and_then(Value::as_u64) {
+        Some(2) => CompletionKind::Method,
+        Some(3) => CompletionKind::Function,
+        Some(4) => CompletionKind::Constructor,
+        Some(5) => CompletionKind::Field,
+        Some(6) => CompletionKind::Variable,
+        Some(7) => CompletionKind::Class,
+        Some(14) => CompletionKind::Keyword,
+        Some(15) => CompletionKind::Snippet,
+        _ => CompletionKind::Text,
+    };
+    let documentation = match item.get("documentation") {
+        Some(Value::String(text)) => Some(text.clone()),
+        Some(Value::Object(markup)) => markup.get("value").and_then(Value::as_str).map(str::to_string),
+        _ => None,
+    };
+
+    Some(CompletionItem {
+        kind,
+        detail: item.get("detail").and_then(Value::as_str).map(str::to_string),
+        documentation,
+        insert_text: item.get("insertText").and_then(Value::as_str).unwrap_or(&label).to_string(),
+        sort_text: item.get("sortText").and_then(Value::as_str).unwrap_or(&label).to_string(),
+        label,
+    })
+}
+
+/// Convert an LSP diagnostic; positions become 1-based
+fn diagnostic(item: &Value) -> Option<Diagnostic> {
+    let start = &item["range"]["start"];
+    let severity = match item.get("severity").and_then(Value::as_u64) {
+        Some(1) => IssueSeverity::Error,
+        Some(2) => IssueSeverity::Warning,
+        _ => IssueSeverity::Info,
+    };
+    let rule_id = match item.get("code") {
+        Some(Value::String(code)) => Some(code.clone()),
+        Some(Value::Number(code)) => Some(code.to_string()),
+        _ => None,
+    };
+
+    Some(Diagnostic {
+        line: start["line"].as_u64()? as usize + 1,
+        column: start["character"].as_u64()? as usize + 1,
+        message: item.get("message")?.as_str()?.to_string(),
+        severity,
+        sources: vec![DiagnosticSource::LanguageServer],
+        rule_id,
+        fix_suggestion: None,
+    })
+}
+
+/// Language servers for the workspace, started on first use
+#[derive(Debug)]
+pub struct LanguageServers {
+    root: PathBuf,
+    configs: HashMap<String, LanguageServerConfig>,
+    clients: Mutex<HashMap<String, Arc<LspClient>>>,
+}
+
+impl LanguageServers {
+    /// Servers from configuration, keyed by language name (matched case-insensitively)
+    pub fn new(root: PathBuf, configs: &HashMap<String, LanguageServerConfig>) -> Self {
+        Self {
+            root,
+            configs: configs.iter().map(|(language, config)| (language.to_lowercase(), config.clone())).collect(),
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether a server is configured for `language`
+    pub fn is_configured(&self, language: &str) -> bool {
+        self.configs.contains_key(&language.to_lowercase())
+    }
+
+    /// Workspace the servers run in
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Running client for `language`, starting the server if needed and restarting it
+    /// after a crash. `None` means the caller should use the built-in heuristics.
+    pub async fn client(&self, language: &str) -> Option<Arc<LspClient>> {
+        let key = language.to_lowercase();
+        let config = self.configs.get(&key)?;
+
+        let mut clients = self.clients.lock().await;
+        if let Some(client) = clients.get(&key) {
+            if client.is_alive() {
+                return Some(client.clone());
+            }
+            log::warn!("Language server for {} exited, restarting", language);
+            clients.remove(&key);
+        }
+
+        match LspClient::spawn(config, &self.root).await {
+            Ok(client) => {
+                let client = Arc::new(client);
+                clients.insert(key, client.clone());
+                Some(client)
+            }
+            Err(e) => {
+                log::warn!("Language server for {} unavailable: {}", language, e);
+                None
+            }
+        }
+    }
+}
+
+/// A diagnostics request taken from the editor, run once the editor is released
+/// since starting a server and waiting for it can take seconds
+pub struct DiagnosticsQuery {
+    pub(super) servers: Arc<LanguageServers>,
+    pub(super) language: String,
+    pub(super) path: PathBuf,
+    /// Text of the open buffer; the file on disk is read when it is not open
+    pub(super) content: Option<String>,
+}
+
+impl DiagnosticsQuery {
+    /// Send the server the current text and return what it published for the file
+    pub async fn run(self) -> Option<Vec<Diagnostic>> {
+        let client = self.servers.client(&self.language).await?;
+        let content = match self.content {
+            Some(content) => content,
+            None => tokio::fs::read_to_string(&self.path).await.ok()?,
+        };
+
+        if let Err(e) = client.sync_document(&self.path, &self.language.to_lowercase(), &content).await {
+            log::warn!("Language server diagnostics unavailable: {}", e);
+            return None;
+        }
+        Some(client.diagnostics(&self.path).await)
+    }
+}
+
+/// A completion request taken from the editor, run once the editor is released
+/// like [`DiagnosticsQuery`]
+pub struct CompletionQuery {
+    pub(super) servers: Arc<LanguageServers>,
+    pub(super) language: String,
+    pub(super) path: PathBuf,
+    /// Text of the active document when the query was made
+    pub(super) content: String,
+    pub(super) line: usize,
+    pub(super) column: usize,
+}
+
+impl CompletionQuery {
+    /// Send the server the text and return its completions at the cursor; `None`
+    /// to fall back to the built-in completions
+    pub async fn run(self) -> Option<Vec<CompletionItem>> {
+        let client = self.servers.client(&self.language).await?;
+        let result = match client.sync_document(&self.path, &self.language.to_lowercase(), &self.content).await {
+            Ok(()) => client.completion(&self.path, self.line, self.column).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(completions) => Some(completions),
+            Err(e) => {
+                log::warn!("Language server completion failed, using built-in completions: {}", e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal language server: answers initialize and completion, and publishes one
+    /// diagnostic whenever a document is opened. Exits after `shutdown`.
+    async fn mock_server<R, W>(reader: R, writer: W)
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        let mut reader = BufReader::new(reader);
+        let writer: Writer = Arc::new(Mutex::new(Box::new(writer)));
+        while let Ok(Some(message)) = read_message(&mut reader).await {
+            let reply = match message["method"].as_str() {
+                Some("initialize") => json!({ "capabilities": { "completionProvider": {} } }),
+                Some("textDocument/completion") => json!({
+                    "isIncomplete": false,
+                    "items": [{ "label": "println!", "kind": 3, "detail": "macro", "insertText": "println!(\"\")" }],
+                }),
+                Some("textDocument/didOpen") => {
+                    let uri = message["params"]["textDocument"]["uri"].clone();
+                    write_message(&writer, &json!({
+                        "jsonrpc": "2.0",
+                        "method": "textDocument/publishDiagnostics",
+                        "params": { "uri": uri, "diagnostics": [{
+                            "range": { "start": { "line": 0, "character": 4 }, "end": { "line": 0, "character": 8 } },
+                            "severity": 1,
+                            "code": "E0425",
+                            "message": "cannot find value `main`",
+                        }] },
+                    })).await.unwrap();
+                    continue;
+                }
+                Some("shutdown") => return,
+                _ => continue,
+            };
+            write_message(&writer, &json!({ "jsonrpc": "2.0", "id": message["id"], "result": reply })).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_completion_and_diagnostics_through_mock_server() {
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let (client_read, client_write) = tokio::io::split(client_io);
+        let (server_read, server_write) = tokio::io::split(server_io);
+        tokio::spawn(mock_server(server_read, server_write));
+
+        let root = std::env::temp_dir();
+        let file = root.join("main.rs");
+        let client = LspClient::connect(client_read, client_write, &root).await.unwrap();
+        client.sync_document(&file, "rust", "fn main() {}").await.unwrap();
+
+        let items = client.completion(&file, 0, 3).await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].label, "println!");
+        assert!(matches!(items[0].kind, CompletionKind::Function));
+        assert_eq!(items[0].insert_text, "println!(\"\")");
+
+        // The completion reply was written after the diagnostics, so they have arrived
+        let diagnostics = client.diagnostics(&file).await;
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (1, 5));
+        assert_eq!(diagnostics[0].rule_id.as_deref(), Some("E0425"));
+    }
+
+    #[tokio::test]
+    async fn test_crashed_or_missing_server_falls_back() {
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let (client_read, client_write) = tokio::io::split(client_io);
+        let (server_read, server_write) = tokio::io::split(server_io);
+        let server = tokio::spawn(mock_server(server_read, server_write));
+
+        let root = std::env::temp_dir();
+        let client = LspClient::connect(client_read, client_write, &root).await.unwrap();
+        client.request("shutdown", Value::Null).await.unwrap_err();
+        server.await.unwrap();
+
+        assert!(!client.is_alive());
+        assert!(matches!(client.completion(&root.join("main.rs"), 0, 0).await, Err(LspError::Exited)));
+
+        let mut configs = HashMap::new();
+        configs.insert("Rust".to_string(), LanguageServerConfig {
+            command: "super-ide-missing-language-server".to_string(),
+            args: Vec::new(),
+        });
+        let servers = LanguageServers::new(root.clone(), &configs);
+        assert!(servers.is_configured("rust"));
+        assert!(servers.client("rust").await.is_none());
+        assert!(servers.client("python").await.is_none());
+
+        let query = CompletionQuery {
+            servers: Arc::new(servers),
+            language: "Rust".to_string(),
+            path: root.join("main.rs"),
+            content: "fn main() {}".to_string(),
+            line: 0,
+            column: 3,
+        };
+        assert!(query.run().await.is_none());
+    }
+}
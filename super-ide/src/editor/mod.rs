@@ -1,14 +1,40 @@
 //! Code editor with syntax highlighting, auto-completion, and document management
 
+pub mod buffer;
+pub mod completion;
+pub mod editorconfig;
+pub mod folding;
+pub mod location;
+pub mod lsp;
+pub mod navigation;
+pub mod outline;
+pub mod preview;
+pub mod refactor;
+pub mod save_conflict;
+pub mod symbols;
+pub mod syntax;
+
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use anyhow::Result;
 use thiserror::Error;
 use serde::{Deserialize, Serialize};
 
-use crate::config::Configuration;
-use crate::utils::file_manager::FileManager;
+use crate::config::{Configuration, SaveConflictStrategy};
+use crate::utils::file_manager::{FileManager, FileManagerError};
+use crate::file_ops::encoding::TextEncoding;
 use crate::ai::{AiEngine, CompletionRequest};
+use crate::terminal::CommandExecutor;
+use buffer::TextBuffer;
+use editorconfig::{EditorConfig, EffectiveSettings, IndentStyle};
+use lsp::LanguageServers;
+use preview::{HexRow, OpenMode};
+use save_conflict::SaveOutcome;
+use syntax::BackgroundParser;
+
+/// Seconds to wait for an external formatter before falling back
+const FORMATTER_TIMEOUT_SECS: u64 = 10;
 
 
 
@@ -26,6 +52,27 @@ pub enum EditorError {
     
     #[error("Configuration error: {0}")]
     Config(String),
+    
+    #[error("File too large: {size} bytes exceeds the {limit} byte limit")]
+    FileTooLarge { size: u64, limit: u64 },
+    
+    #[error("{0} is open read-only")]
+    ReadOnly(String),
+    
+    #[error("{0} changed on disk since it was opened; overwrite it or reload it")]
+    SaveConflict(String),
+    
+    #[error("{0} has unsaved changes; confirm to discard them")]
+    UnsavedChanges(String),
+    
+    #[error("Workspace is not trusted; trust it to run {0}")]
+    WorkspaceNotTrusted(String),
+    
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+    
+    #[error("{0} changed while it was being formatted; format it again")]
+    FormatConflict(String),
 }
 
 /// Document model representing an open file
@@ -34,26 +81,88 @@ pub struct Document {
     pub id: String,
     pub path: std::path::PathBuf,
     pub title: String,
-    pub content: Arc<RwLock<String>>,
+    pub content: Arc<RwLock<TextBuffer>>,
     pub language: String,
     pub is_modified: bool,
     pub last_saved: Option<chrono::DateTime<chrono::Utc>>,
+    /// Tree and tokens of the latest completed background parse
     pub syntax_tree: Option<SyntaxTree>,
+    pub syntax_tokens: Vec<SyntaxToken>,
+    /// Outline from the latest completed background parse
+    pub outline: Option<Vec<outline::OutlineItem>>,
     pub bookmarks: Vec<Bookmark>,
     pub fold_points: Vec<FoldPoint>,
     pub cursor_line: usize,
     pub cursor_column: usize,
+    /// Selection last reported by the frontend
+    pub selection: Option<Selection>,
+    /// Every cursor of a multi-cursor edit, sorted, the main cursor first among
+    /// them; empty when only the main cursor is in use
+    pub cursors: Vec<CursorPosition>,
+    /// Encoding the file was read in, and is saved back in
+    pub encoding: TextEncoding,
+    /// Preview and hex documents reject edits and saves
+    pub mode: OpenMode,
+    /// Hash of the file's bytes when it was opened or last saved; `None` when the
+    /// file was not read, and a save then has nothing to compare against
+    pub disk_hash: Option<u64>,
+}
+
+/// Formatting taken from the editor, run once the editor is released since an
+/// external formatter can take seconds
+pub struct FormatJob {
+    document: Arc<RwLock<Document>>,
+    language: String,
+    path: std::path::PathBuf,
+    /// Text of the document when the job was made
+    content: String,
+    settings: EffectiveSettings,
+}
+
+impl FormatJob {
+    /// Format the text with the external formatter, falling back to the built-in one
+    pub async fn run(self) -> FormattedDocument {
+        let (formatted, formatter) = match Editor::run_external_formatter(&self.language, &self.path, &self.content).await {
+            Some((output, name)) => (self.settings.normalize(&output), Some(name)),
+            None => {
+                // Fall back to removing trailing whitespace, unless .editorconfig keeps it
+                let fallback = EffectiveSettings {
+                    trim_trailing_whitespace: Some(self.settings.trim_trailing_whitespace.unwrap_or(true)),
+                    ..self.settings
+                };
+                (fallback.normalize(&self.content), None)
+            }
+        };
+        FormattedDocument { document: self.document, original: self.content, formatted, formatter }
+    }
+}
+
+/// Text a [`FormatJob`] produced, for [`Editor::apply_format`]
+pub struct FormattedDocument {
+    document: Arc<RwLock<Document>>,
+    original: String,
+    formatted: String,
+    formatter: Option<String>,
+}
+
+/// Outcome of formatting a document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FormatResult {
+    /// Whether the buffer content changed
+    pub changed: bool,
+    /// External formatter that produced the result; `None` when the built-in fallback ran
+    pub formatter: Option<String>,
 }
 
 /// Syntax tree for code structure
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyntaxTree {
     pub nodes: Vec<SyntaxNode>,
     pub root: usize,
 }
 
 /// Syntax node in the parse tree
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyntaxNode {
     pub node_type: String,
     pub start_byte: usize,
@@ -72,7 +181,7 @@ pub struct Bookmark {
 }
 
 /// Code folding point
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FoldPoint {
     pub start_line: usize,
     pub end_line: usize,
@@ -80,13 +189,13 @@ pub struct FoldPoint {
 }
 
 /// Cursor position
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct CursorPosition {
     pub line: usize,
     pub column: usize,
 }
 
-/// Selection range
+/// Selection range; `start` may come after `end` when selecting backwards
 #[derive(Debug, Clone)]
 pub struct Selection {
     pub start: CursorPosition,
@@ -131,6 +240,7 @@ pub struct LanguageSupport {
     pub builtins: Vec<String>,
     pub comment_syntax: CommentSyntax,
     pub syntax_highlighting: SyntaxHighlighting,
+    pub fold_strategy: folding::FoldStrategy,
 }
 
 /// Comment syntax for a language
@@ -210,22 +320,60 @@ pub enum CompletionKind {
     Issue,
 }
 
+/// Open documents indexed by id, remembering the order they were opened in
+#[derive(Debug, Default)]
+struct DocumentStore {
+    order: Vec<String>,
+    by_id: HashMap<String, Arc<RwLock<Document>>>,
+}
+
+impl DocumentStore {
+    fn insert(&mut self, id: String, document: Arc<RwLock<Document>>) {
+        if self.by_id.insert(id.clone(), document).is_none() {
+            self.order.push(id);
+        }
+    }
+
+    fn get(&self, id: &str) -> Option<Arc<RwLock<Document>>> {
+        self.by_id.get(id).cloned()
+    }
+
+    fn remove(&mut self, id: &str) -> Option<Arc<RwLock<Document>>> {
+        let document = self.by_id.remove(id)?;
+        self.order.retain(|open| open != id);
+        Some(document)
+    }
+
+    /// Documents in the order they were opened
+    fn iter(&self) -> impl Iterator<Item = &Arc<RwLock<Document>>> {
+        self.order.iter().filter_map(|id| self.by_id.get(id))
+    }
+}
+
 /// Main editor instance
 #[derive(Debug)]
 pub struct Editor {
-    documents: Arc<RwLock<Vec<Arc<RwLock<Document>>>>>,
+    documents: Arc<RwLock<DocumentStore>>,
     active_document: Arc<RwLock<Option<Arc<RwLock<Document>>>>>,
     file_manager: FileManager,
     language_support: Arc<RwLock<Vec<LanguageSupport>>>,
     config: Arc<RwLock<Configuration>>,
     ai_engine: Arc<AiEngine>,
+    language_servers: Arc<LanguageServers>,
+    parser: BackgroundParser,
+    navigation: Arc<RwLock<navigation::NavigationHistory>>,
+    /// Gates external formatters and language servers; without one they always run
+    workspace_trust: Option<Arc<crate::core::trust::WorkspaceTrust>>,
 }
 
 impl Editor {
     /// Create a new editor instance
     pub async fn new(config: &Configuration, ai_engine: Arc<AiEngine>) -> Result<Self, EditorError> {
         let file_manager = FileManager::new().await
-            .map_err(|e| EditorError::Config(e.to_string()))?;
+            .map_err(|e| EditorError::Config(e.to_string()))?
+            .with_fsync_on_save(config.editor.fsync_on_save)
+            .with_fallback_encoding(&config.editor.fallback_encoding)
+            .with_local_history(config.workspace_dir());
 
         let mut language_support = Vec::new();
 
@@ -266,23 +414,138 @@ impl Editor {
                     is_regex: true,
                 }],
             },
+            fold_strategy: folding::FoldStrategy::Braces,
+        });
+
+        language_support.push(LanguageSupport {
+            name: "Python".to_string(),
+            extensions: vec!["py".to_string(), "pyi".to_string()],
+            keywords: vec!["def".to_string(), "class".to_string(), "return".to_string(), "import".to_string()],
+            builtins: vec!["print".to_string(), "len".to_string(), "range".to_string()],
+            comment_syntax: CommentSyntax {
+                line_comments: vec!["#".to_string()],
+                block_comments: None,
+            },
+            syntax_highlighting: SyntaxHighlighting {
+                keywords: vec![TokenRule {
+                    pattern: r"\b(def|class|return|if|elif|else|for|while|import|from|with|as)\b".to_string(),
+                    token_type: "keyword".to_string(),
+                    is_regex: true,
+                }],
+                strings: vec![TokenRule {
+                    pattern: r#""[^"]*"|'[^']*'"#.to_string(),
+                    token_type: "string".to_string(),
+                    is_regex: true,
+                }],
+                numbers: vec![TokenRule {
+                    pattern: r"\b\d+(\.\d+)?\b".to_string(),
+                    token_type: "number".to_string(),
+                    is_regex: true,
+                }],
+                comments: vec![TokenRule {
+                    pattern: r"#[^\n]*".to_string(),
+                    token_type: "comment".to_string(),
+                    is_regex: true,
+                }],
+                operators: vec![TokenRule {
+                    pattern: r"[+\-*/=<>!&|%@]".to_string(),
+                    token_type: "operator".to_string(),
+                    is_regex: true,
+                }],
+            },
+            fold_strategy: folding::FoldStrategy::Indentation,
+        });
+
+        language_support.push(LanguageSupport {
+            name: "YAML".to_string(),
+            extensions: vec!["yaml".to_string(), "yml".to_string()],
+            keywords: vec!["true".to_string(), "false".to_string(), "null".to_string()],
+            builtins: Vec::new(),
+            comment_syntax: CommentSyntax {
+                line_comments: vec!["#".to_string()],
+                block_comments: None,
+            },
+            syntax_highlighting: SyntaxHighlighting {
+                keywords: vec![TokenRule {
+                    pattern: r"\b(true|false|null|yes|no)\b".to_string(),
+                    token_type: "keyword".to_string(),
+                    is_regex: true,
+                }],
+                strings: vec![TokenRule {
+                    pattern: r#""[^"]*"|'[^']*'"#.to_string(),
+                    token_type: "string".to_string(),
+                    is_regex: true,
+                }],
+                numbers: vec![TokenRule {
+                    pattern: r"\b\d+(\.\d+)?\b".to_string(),
+                    token_type: "number".to_string(),
+                    is_regex: true,
+                }],
+                comments: vec![TokenRule {
+                    pattern: r"#[^\n]*".to_string(),
+                    token_type: "comment".to_string(),
+                    is_regex: true,
+                }],
+                operators: vec![TokenRule {
+                    pattern: r"[:\-|>]".to_string(),
+                    token_type: "operator".to_string(),
+                    is_regex: true,
+                }],
+            },
+            fold_strategy: folding::FoldStrategy::Indentation,
         });
 
         // Add more language support...
 
         Ok(Self {
-            documents: Arc::new(RwLock::new(Vec::new())),
+            documents: Arc::new(RwLock::new(DocumentStore::default())),
             active_document: Arc::new(RwLock::new(None)),
             file_manager,
             language_support: Arc::new(RwLock::new(language_support)),
             config: Arc::new(RwLock::new(config.clone())),
             ai_engine,
+            language_servers: Arc::new(LanguageServers::new(config.workspace_dir(), &config.editor.language_servers)),
+            parser: BackgroundParser::new(),
+            navigation: Arc::new(RwLock::new(navigation::NavigationHistory::default())),
+            workspace_trust: None,
         })
     }
+
+    /// Announce completed syntax parses on `event_bus`
+    pub fn with_event_bus(mut self, event_bus: Arc<crate::utils::event_bus::EventBus>) -> Self {
+        self.parser = self.parser.with_event_bus(event_bus);
+        self
+    }
+    
+    /// Run external formatters and language servers only while `trust` allows it
+    pub fn with_workspace_trust(mut self, trust: Arc<crate::core::trust::WorkspaceTrust>) -> Self {
+        self.workspace_trust = Some(trust);
+        self
+    }
+    
+    fn is_trusted(&self) -> bool {
+        self.workspace_trust.as_ref().map_or(true, |trust| trust.is_trusted())
+    }
     
-    /// Open a file in the editor
+    /// Open a file in the editor, refusing files over the configured size limit
     pub async fn open_file(&self, file_path: std::path::PathBuf) -> Result<String, EditorError> {
-        let content = self.file_manager.read_file(&file_path)
+        self.open_file_with_override(file_path, false).await
+    }
+    
+    /// Open a file in the editor; `open_anyway` skips the size limit
+    pub async fn open_file_with_override(&self, file_path: std::path::PathBuf, open_anyway: bool) -> Result<String, EditorError> {
+        if !open_anyway {
+            let limit = self.config.read().await.editor.max_open_file_bytes;
+            let size = self.file_manager.metadata(&file_path)
+                .await
+                .map_err(|e| EditorError::FileNotFound(e.to_string()))?
+                .len();
+            if size > limit {
+                return Err(EditorError::FileTooLarge { size, limit });
+            }
+        }
+        
+        let (content, encoding) = self.file_manager.read_file_with_encoding(&file_path)
             .await
             .map_err(|e| EditorError::FileNotFound(e.to_string()))?;
             
@@ -296,72 +559,197 @@ impl Editor {
                 .to_str()
                 .unwrap_or("Untitled")
                 .to_string(),
-            content: Arc::new(RwLock::new(content)),
+            content: Arc::new(RwLock::new(TextBuffer::from(content))),
             language,
             is_modified: false,
             last_saved: None,
             syntax_tree: None,
+            syntax_tokens: Vec::new(),
+            outline: None,
             bookmarks: Vec::new(),
             fold_points: Vec::new(),
             cursor_line: 0,
             cursor_column: 0,
+            selection: None,
+            cursors: Vec::new(),
+            encoding,
+            mode: OpenMode::Edit,
+            disk_hash: save_conflict::disk_hash(&file_path).await,
         };
         
-        let document_arc = Arc::new(RwLock::new(document));
+        let (document_id, document_arc) = self.add_document(document).await;
+        self.schedule_parse(&document_arc).await;
         
-        // Get document ID before moving
-        let document_id = {
-            let doc_read = document_arc.read().await;
-            doc_read.id.clone()
+        Ok(document_id)
+    }
+
+    /// Open a file as `mode` says, or as hex when it is binary. Previews skip the size
+    /// limit and parsing and cover the start of the file; a hex document's text is the
+    /// dump of the file's first bytes.
+    pub async fn open_file_as(&self, file_path: std::path::PathBuf, mode: OpenMode) -> Result<String, EditorError> {
+        let binary = preview::is_binary_file(&file_path)
+            .await
+            .map_err(|e| EditorError::FileNotFound(e.to_string()))?;
+        let mode = if binary { OpenMode::Hex } else { mode };
+
+        let (content, encoding, language) = match mode {
+            OpenMode::Edit => return self.open_file(file_path).await,
+            OpenMode::Preview => {
+                let mut bytes = preview::read_prefix(&file_path, preview::MAX_TEXT_PREVIEW_BYTES)
+                    .await
+                    .map_err(|e| EditorError::FileNotFound(e.to_string()))?;
+                preview::trim_partial_utf8(&mut bytes);
+                let (content, encoding) = self.file_manager.decode(&bytes);
+                (content, encoding, self.detect_language(&file_path).await)
+            }
+            OpenMode::Hex => {
+                let rows = preview::read_hex_rows(&file_path)
+                    .await
+                    .map_err(|e| EditorError::FileNotFound(e.to_string()))?;
+                let dump = rows.iter().map(HexRow::to_line).collect::<Vec<_>>().join("\n");
+                (dump, TextEncoding::default(), "Hex".to_string())
+            }
         };
-        
-        // Add to documents list
-        {
-            let mut documents = self.documents.write().await;
-            documents.push(document_arc.clone());
-        }
-        
-        // Set as active document
-        {
-            let mut active = self.active_document.write().await;
-            *active = Some(document_arc.clone());
+
+        let document = Document {
+            path: file_path.clone(),
+            title: file_path.file_name()
+                .unwrap_or_default()
+                .to_str()
+                .unwrap_or("Untitled")
+                .to_string(),
+            content: Arc::new(RwLock::new(TextBuffer::from(content))),
+            language,
+            encoding,
+            mode,
+            ..Document::default()
+        };
+        Ok(self.add_document(document).await.0)
+    }
+
+    /// How a document was opened
+    pub async fn document_mode(&self, document_id: &str) -> Result<OpenMode, EditorError> {
+        Ok(self.find_document(document_id).await?.read().await.mode)
+    }
+
+    /// Hex rows of a document opened as [`OpenMode::Hex`], read from its file
+    pub async fn hex_rows(&self, document_id: &str) -> Result<Vec<HexRow>, EditorError> {
+        let doc = self.find_document(document_id).await?;
+        let doc_read = doc.read().await;
+        if doc_read.mode != OpenMode::Hex {
+            return Err(EditorError::Document(format!("{} is not open as hex", doc_read.title)));
         }
-        
-        // Parse syntax tree
-        self.parse_syntax_tree(&document_arc).await;
-        
-        Ok(document_id)
+        preview::read_hex_rows(&doc_read.path)
+            .await
+            .map_err(|e| EditorError::FileNotFound(e.to_string()))
+    }
+
+    /// Add an opened document and make it the active one
+    async fn add_document(&self, document: Document) -> (String, Arc<RwLock<Document>>) {
+        let document_id = document.id.clone();
+        let document_arc = Arc::new(RwLock::new(document));
+        self.documents.write().await.insert(document_id.clone(), document_arc.clone());
+        self.focus_document(document_arc.clone()).await;
+        (document_id, document_arc)
     }
     
-    /// Save the active document
-    pub async fn save_active_document(&self) -> Result<(), EditorError> {
-        let active = self.active_document.read().await;
-        if let Some(doc) = active.as_ref() {
+    /// Save the active document, settling a conflict with an external change to
+    /// its file as the `editor.save_conflict_strategy` setting says
+    pub async fn save_active_document(&self) -> Result<SaveOutcome, EditorError> {
+        let strategy = self.config.read().await.editor.save_conflict_strategy;
+        self.save_active_document_with(strategy).await
+    }
+
+    /// Save the active document, settling a conflict with `strategy`, as when the
+    /// user chose to overwrite after being prompted. Formats it first when format
+    /// on save is enabled; a formatter failure does not stop the save.
+    pub async fn save_active_document_with(&self, strategy: SaveConflictStrategy) -> Result<SaveOutcome, EditorError> {
+        let Some(doc) = self.get_active_document().await else {
+            return Ok(SaveOutcome::Skipped);
+        };
+        if self.config.read().await.editor.format_on_save {
+            if let Err(e) = self.format(&doc).await {
+                log::warn!("Saving without formatting: {}", e);
+            }
+        }
+        self.save_document(&doc, strategy).await
+    }
+
+    /// Write a document to its file, unless the file changed on disk and
+    /// `strategy` says not to
+    async fn save_document(&self, doc: &Arc<RwLock<Document>>, strategy: SaveConflictStrategy) -> Result<SaveOutcome, EditorError> {
+        let path = {
             let doc_read = doc.read().await;
+            ensure_editable(&doc_read)?;
+            if doc_read.disk_hash.is_some() && save_conflict::disk_hash(&doc_read.path).await != doc_read.disk_hash {
+                match strategy {
+                    SaveConflictStrategy::Overwrite => {
+                        log::warn!("Overwriting external changes to {}", doc_read.path.display());
+                    }
+                    SaveConflictStrategy::Skip => {
+                        log::info!("Not saving {}: it changed on disk", doc_read.path.display());
+                        return Ok(SaveOutcome::Skipped);
+                    }
+                    SaveConflictStrategy::Prompt => return Err(EditorError::SaveConflict(doc_read.title.clone())),
+                }
+            }
+
+            let settings = self.file_settings(&doc_read.path).await;
             let content = {
-                let content_guard = doc_read.content.read().await;
-                content_guard.clone()
+                let mut content_guard = doc_read.content.write().await;
+                let content = content_guard.to_string();
+                let normalized = settings.normalize(&content);
+                if normalized != content {
+                    *content_guard = TextBuffer::from(normalized.clone());
+                }
+                normalized
             };
             
-            self.file_manager.write_file(&doc_read.path, &content)
+            self.file_manager.write_file_with_encoding(&doc_read.path, &content, doc_read.encoding)
                 .await
                 .map_err(|e| EditorError::Document(e.to_string()))?;
-                
-            // Mark as not modified
-            let doc_id = doc_read.id.clone();
-            drop(doc_read);
-            drop(active);
-            
-            let mut active = self.active_document.write().await;
-            if let Some(doc) = active.as_mut() {
-                let mut doc_write = doc.write().await;
-                if doc_write.id == doc_id {
-                    doc_write.is_modified = false;
-                    doc_write.last_saved = Some(chrono::Utc::now());
-                }
+            doc_read.path.clone()
+        };
+
+        let disk_hash = save_conflict::disk_hash(&path).await;
+        let mut doc_write = doc.write().await;
+        doc_write.is_modified = false;
+        doc_write.last_saved = Some(chrono::Utc::now());
+        doc_write.disk_hash = disk_hash;
+        Ok(SaveOutcome::Saved)
+    }
+    
+    /// Replace a document's text with its file's, discarding edits. A modified
+    /// document is only reverted with `confirm`, as the edits are lost for good.
+    pub async fn revert_document(&self, document_id: &str, confirm: bool) -> Result<(), EditorError> {
+        let doc = self.find_document(document_id).await?;
+        {
+            let mut doc_write = doc.write().await;
+            ensure_editable(&doc_write)?;
+            if doc_write.is_modified && !confirm {
+                return Err(EditorError::UnsavedChanges(doc_write.title.clone()));
             }
+
+            let (content, encoding) = self.file_manager.read_file_with_encoding(&doc_write.path)
+                .await
+                .map_err(|e| read_error(&doc_write.path, e))?;
+            let line_count = {
+                let mut buffer = doc_write.content.write().await;
+                *buffer = TextBuffer::from(content);
+                buffer.line_count()
+            };
+            doc_write.encoding = encoding;
+            doc_write.is_modified = false;
+            doc_write.disk_hash = save_conflict::disk_hash(&doc_write.path).await;
+            // Positions into the discarded text mean nothing in the file's
+            doc_write.cursor_line = doc_write.cursor_line.min(line_count.saturating_sub(1));
+            doc_write.cursor_column = 0;
+            doc_write.cursors.clear();
+            doc_write.selection = None;
+            doc_write.outline = None;
+            log::info!("Reverted {} to its file", doc_write.path.display());
         }
-        
+        self.schedule_parse(&doc).await;
         Ok(())
     }
     
@@ -374,7 +762,14 @@ impl Editor {
     /// Get all open documents
     pub async fn get_documents(&self) -> Vec<Arc<RwLock<Document>>> {
         let documents = self.documents.read().await;
-        documents.clone()
+        documents.iter().cloned().collect()
+    }
+
+    /// Look up an open document by id
+    async fn find_document(&self, document_id: &str) -> Result<Arc<RwLock<Document>>, EditorError> {
+        self.documents.read().await
+            .get(document_id)
+            .ok_or_else(|| EditorError::Document("Document not found".to_string()))
     }
     
     /// Close a document
@@ -382,45 +777,157 @@ impl Editor {
         let mut documents = self.documents.write().await;
         let mut active = self.active_document.write().await;
         
-        // Check if the document being removed is the active one
-        let is_active = {
-            if let Some(active_doc) = active.as_ref() {
-                if let Ok(active_read) = active_doc.try_read() {
-                    active_read.id == document_id
-                } else {
-                    false
-                }
-            } else {
-                false
-            }
+        let Some(closed) = documents.remove(document_id) else {
+            return Ok(false);
         };
+        self.parser.forget(document_id);
+        self.navigation.write().await.forget(document_id);
         
-        // Find and remove the document
-        let index = documents.iter().position(|doc| {
-            if let Ok(doc_read) = doc.try_read() {
-                doc_read.id == document_id
-            } else {
-                false
-            }
-        });
+        // If this was the active document, select another one
+        if active.as_ref().is_some_and(|doc| Arc::ptr_eq(doc, &closed)) {
+            *active = documents.iter().next().cloned();
+        }
         
-        if let Some(index) = index {
-            documents.remove(index);
-            
-            // If this was the active document, select another one
-            if is_active {
-                *active = None;
-                if !documents.is_empty() {
-                    *active = Some(documents[0].clone());
-                }
-            }
-            
-            Ok(true)
+        Ok(true)
+    }
+    
+    /// Move a document's cursor to the 0-based `line` and `column`, counted in
+    /// characters, clamping both to the text. A move of many lines is recorded in
+    /// the navigation history.
+    pub async fn set_cursor(&self, document_id: &str, line: usize, column: usize) -> Result<(), EditorError> {
+        let doc = self.find_document(document_id).await?;
+        let mut doc_write = doc.write().await;
+        let from = navigation::NavigationEntry::of(&doc_write);
+        let (line, column) = {
+            let content = doc_write.content.read().await;
+            let line = line.min(content.line_count().saturating_sub(1));
+            let text = content.line(line);
+            let column = text.char_indices().nth(column).map_or(text.len(), |(byte, _)| byte);
+            (line, column)
+        };
+        doc_write.cursor_line = line;
+        doc_write.cursor_column = column;
+        drop(doc_write);
+        if line.abs_diff(from.line) >= navigation::JUMP_LINES {
+            self.navigation.write().await.record_cursor_jump(document_id, from);
+        }
+        Ok(())
+    }
+
+    /// Record the selection in a document, or clear it with `None`
+    pub async fn set_selection(&self, document_id: &str, selection: Option<Selection>) -> Result<(), EditorError> {
+        let doc = self.find_document(document_id).await?;
+        doc.write().await.selection = selection;
+        Ok(())
+    }
+
+    /// Text of a document's selection, or the whole document when nothing is selected
+    pub async fn selection_text(&self, document_id: &str) -> Result<String, EditorError> {
+        let doc = self.find_document(document_id).await?;
+        let doc_read = doc.read().await;
+        let content = doc_read.content.read().await;
+        Ok(selected_text(&content, doc_read.selection.as_ref()))
+    }
+
+    /// Place a document's cursors for multi-cursor edits; the first one becomes the
+    /// main cursor. An empty list goes back to the main cursor alone.
+    pub async fn set_cursors(&self, document_id: &str, mut cursors: Vec<CursorPosition>) -> Result<(), EditorError> {
+        let doc = self.find_document(document_id).await?;
+        let mut doc_write = doc.write().await;
+        cursors.sort();
+        cursors.dedup();
+        if let Some(first) = cursors.first() {
+            doc_write.cursor_line = first.line;
+            doc_write.cursor_column = first.column;
+        }
+        doc_write.cursors = cursors;
+        Ok(())
+    }
+
+    /// Insert `text` at every cursor of a document, returning where the cursors end
+    /// up: each just after its copy of the text
+    pub async fn insert_at_cursors(&self, document_id: &str, text: &str) -> Result<Vec<CursorPosition>, EditorError> {
+        self.edit_at_cursors(document_id, |_, offset| (offset..offset, text)).await
+    }
+
+    /// Delete up to `chars_to_delete` characters before every cursor of a document,
+    /// as backspace does, returning where the cursors end up. A deletion stops at
+    /// the cursor before it rather than deleting the same text twice.
+    pub async fn delete_at_cursors(&self, document_id: &str, chars_to_delete: usize) -> Result<Vec<CursorPosition>, EditorError> {
+        self.edit_at_cursors(document_id, |content, offset| {
+            // A character is at most four bytes, so this slice holds enough of them
+            let before = content.slice(offset.saturating_sub(chars_to_delete.saturating_mul(4))..offset);
+            let bytes: usize = before.chars().rev().take(chars_to_delete).map(char::len_utf8).sum();
+            (offset - bytes..offset, "")
+        }).await
+    }
+
+    /// Replace text at every cursor of a document: `edit` maps a cursor's byte
+    /// offset to the range to replace and its replacement. Edits are applied from
+    /// the last cursor to the first, so each one's offsets are still those of the
+    /// unedited text; the cursors are then moved past their replacements.
+    async fn edit_at_cursors<'a>(
+        &self,
+        document_id: &str,
+        edit: impl Fn(&TextBuffer, usize) -> (std::ops::Range<usize>, &'a str),
+    ) -> Result<Vec<CursorPosition>, EditorError> {
+        let doc = self.find_document(document_id).await?;
+        let mut doc_write = doc.write().await;
+        ensure_editable(&doc_write)?;
+
+        let cursors = if doc_write.cursors.is_empty() {
+            vec![CursorPosition { line: doc_write.cursor_line, column: doc_write.cursor_column }]
         } else {
-            Ok(false)
+            doc_write.cursors.clone()
+        };
+
+        let (moved, changed) = {
+            let mut content = doc_write.content.write().await;
+            let mut offsets: Vec<usize> = cursors.iter()
+                .map(|cursor| content.position(cursor.line, cursor.column))
+                .collect();
+            offsets.dedup();
+
+            let mut edits = Vec::with_capacity(offsets.len());
+            let mut previous = 0;
+            for offset in offsets {
+                let (range, replacement) = edit(&content, offset);
+                edits.push((range.start.max(previous)..range.end, replacement));
+                previous = offset;
+            }
+            for (range, replacement) in edits.iter().rev() {
+                content.remove(range.clone());
+                content.insert(range.start, replacement);
+            }
+
+            let mut shift: isize = 0;
+            let mut moved = Vec::with_capacity(edits.len());
+            for (range, replacement) in &edits {
+                let offset = (range.start + replacement.len()).saturating_add_signed(shift);
+                shift += replacement.len() as isize - range.len() as isize;
+                let line = content.line_of(offset);
+                moved.push(CursorPosition { line, column: offset - content.line_start(line) });
+            }
+            moved.dedup();
+            let changed = edits.iter().any(|(range, replacement)| !range.is_empty() || !replacement.is_empty());
+            (moved, changed)
+        };
+
+        if let Some(first) = moved.first() {
+            doc_write.cursor_line = first.line;
+            doc_write.cursor_column = first.column;
+        }
+        if !doc_write.cursors.is_empty() {
+            doc_write.cursors = moved.clone();
+        }
+        if changed {
+            doc_write.is_modified = true;
+            drop(doc_write);
+            self.schedule_parse(&doc).await;
         }
+        Ok(moved)
     }
-    
+
     /// Insert text at cursor position
     pub async fn insert_text(&self, text: &str) -> Result<(), EditorError> {
         let active_doc_arc = {
@@ -430,16 +937,17 @@ impl Editor {
         
         if let Some(doc) = active_doc_arc {
             let mut doc_write = doc.write().await;
+            ensure_editable(&doc_write)?;
             
             // Calculate insertion position
             let position = {
                 let content = doc_write.content.read().await;
-                self.calculate_cursor_position(&content, &doc_write.cursor_line, &doc_write.cursor_column)
+                content.position(doc_write.cursor_line, doc_write.cursor_column)
             };
             
             {
                 let mut content = doc_write.content.write().await;
-                content.insert_str(position, text);
+                content.insert(position, text);
             }
             
             doc_write.is_modified = true;
@@ -448,6 +956,8 @@ impl Editor {
             doc_write.cursor_line += text.lines().count().saturating_sub(1);
             let last_line_len = text.lines().last().map_or(0, |l| l.len());
             doc_write.cursor_column += last_line_len;
+            drop(doc_write);
+            self.schedule_parse(&doc).await;
         }
         
         Ok(())
@@ -462,17 +972,18 @@ impl Editor {
         
         if let Some(doc) = active_doc_arc {
             let mut doc_write = doc.write().await;
+            ensure_editable(&doc_write)?;
             
             // Calculate deletion position
             let position = {
                 let content = doc_write.content.read().await;
-                self.calculate_cursor_position(&content, &doc_write.cursor_line, &doc_write.cursor_column)
+                content.position(doc_write.cursor_line, doc_write.cursor_column)
             };
             
             if position >= chars_to_delete {
                 {
                     let mut content = doc_write.content.write().await;
-                    content.drain(position - chars_to_delete..position);
+                    content.remove(position - chars_to_delete..position);
                 }
                 doc_write.is_modified = true;
             }
@@ -481,14 +992,32 @@ impl Editor {
         Ok(())
     }
     
-    /// Get auto-completion suggestions
+    /// Get auto-completion suggestions. Callers sharing the editor behind a lock
+    /// run [`Editor::language_server_completions`] without it, then fall back to
+    /// [`Editor::builtin_completions`].
     pub async fn get_completions(&self, context: &CompletionContext) -> Result<Vec<CompletionItem>, EditorError> {
+        // A configured language server beats the heuristics
+        if let Some(query) = self.language_server_completions(context).await {
+            if let Some(completions) = query.run().await {
+                return Ok(completions);
+            }
+        }
+        self.builtin_completions(context).await
+    }
+
+    /// Completions from the AI engine and the language's keywords and built-ins
+    pub async fn builtin_completions(&self, context: &CompletionContext) -> Result<Vec<CompletionItem>, EditorError> {
         let mut completions = Vec::new();
 
         // First try AI-powered completions
+        let code = format!("{}{}", context.text_before_cursor, context.text_after_cursor);
+        let document = match self.get_active_document().await {
+            Some(doc) => Some(doc.read().await.path.display().to_string()),
+            None => None,
+        };
         let ai_request = CompletionRequest {
             language: context.language.clone(),
-            context: context.text_before_cursor.clone(),
+            context: self.ai_engine.context_window(&code, &context.language, context.cursor_position.line).text,
             position: Some((context.cursor_position.line, context.cursor_position.column)),
             prompt: format!("Complete this {} code: {}", context.language, context.text_before_cursor),
             max_tokens: Some(100),
@@ -496,19 +1025,13 @@ impl Editor {
             text_before_cursor: context.text_before_cursor.clone(),
             cursor_position: None,
             text_before_cursor: context.text_before_cursor.clone(),
+            document,
         };
 
         if let Ok(ai_response) = self.ai_engine.generate_completion(ai_request).await {
             // Convert AI suggestions to CompletionItem format
             for suggestion in &ai_response.suggestions {
-                completions.push(CompletionItem {
-                    label: suggestion.text.clone(),
-                    kind: CompletionKind::Snippet,
-                    detail: Some("AI suggested".to_string()),
-                    documentation: Some(format!("AI confidence: {:.1}%", ai_response.confidence * 100.0)),
-                    insert_text: suggestion.text.clone(),
-                    sort_text: format!("a{}", suggestion.text), // Sort AI suggestions first
-                });
+                completions.push(CompletionItem::from_ai_suggestion(suggestion, ai_response.confidence, &context.language));
             }
         }
 
@@ -559,68 +1082,172 @@ impl Editor {
         Ok(completions)
     }
     
-    /// Format the active document
-    pub async fn format_document(&self) -> Result<(), EditorError> {
+    /// Query for the language server's completions in the active document; `None`
+    /// when no server is configured or the workspace is not trusted to start one
+    pub async fn language_server_completions(&self, context: &CompletionContext) -> Option<lsp::CompletionQuery> {
+        if !self.is_trusted() || !self.language_servers.is_configured(&context.language) {
+            return None;
+        }
         let active = self.active_document.read().await;
-        if let Some(doc) = active.as_ref() {
-            // Get content and language to format
-            let (content_to_format, language) = {
-                let doc_read = doc.read().await;
-                let content_clone = {
-                    let content = doc_read.content.read().await;
-                    content.clone()
-                };
-                (content_clone, doc_read.language.clone())
-            };
+        let doc_read = active.as_ref()?.read().await;
+        let content = doc_read.content.read().await.to_string();
+        Some(lsp::CompletionQuery {
+            servers: self.language_servers.clone(),
+            language: context.language.clone(),
+            path: self.language_servers.root().join(&doc_read.path),
+            content,
+            line: context.cursor_position.line,
+            column: context.cursor_position.column,
+        })
+    }
 
-            // Check configuration for format on save setting
-            let config = self.config.read().await;
-            let should_format = config.editor.format_on_save;
+    /// Query for the diagnostics the language server publishes for `path`, sending it
+    /// the current text (the open buffer if any, else the file on disk). Servers publish
+    /// asynchronously, so a fresh edit shows up on a later call. `None` when no server
+    /// is configured or the workspace is not trusted to start one.
+    pub async fn language_server_diagnostics(&self, path: &std::path::Path) -> Option<lsp::DiagnosticsQuery> {
+        if !self.is_trusted() {
+            return None;
+        }
+        let language = self.detect_language(path).await;
+        if !self.language_servers.is_configured(&language) {
+            return None;
+        }
+        let path = self.language_servers.root().join(path);
 
-            if !should_format {
-                return Ok(());
+        let mut content = None;
+        for doc in self.documents.read().await.iter() {
+            let doc_read = doc.read().await;
+            if self.language_servers.root().join(&doc_read.path) == path {
+                content = Some(doc_read.content.read().await.to_string());
+                break;
             }
+        }
 
-            // Apply formatting based on language
-            let formatted_content = match language.as_str() {
-                "Rust" => {
-                    // Apply basic Rust formatting (this would use rustfmt)
-                    let lines: Vec<String> = content_to_format.lines().map(|line| {
-                        line.trim_end().to_string()
-                    }).collect();
-                    lines.join("\n")
-                },
-                "Python" => {
-                    // Apply Python formatting (this would use black/autopep8)
-                    let lines: Vec<String> = content_to_format.lines().map(|line| {
-                        line.trim_end().to_string()
-                    }).collect();
-                    lines.join("\n")
-                },
-                _ => {
-                    // Generic formatting - remove trailing whitespace
-                    let lines: Vec<String> = content_to_format.lines().map(|line| {
-                        line.trim_end().to_string()
-                    }).collect();
-                    lines.join("\n")
-                }
-            };
+        Some(lsp::DiagnosticsQuery {
+            servers: self.language_servers.clone(),
+            language,
+            path,
+            content,
+        })
+    }
+    
+    /// Format the active document, using the language's external formatter when installed.
+    /// Callers sharing the editor behind a lock run [`Editor::format_active_job`] without it
+    /// and hand the result to [`Editor::apply_format`].
+    pub async fn format_document(&self) -> Result<FormatResult, EditorError> {
+        let Some(doc) = self.get_active_document().await else {
+            return Err(EditorError::Document("No active document".to_string()));
+        };
+        self.format(&doc).await
+    }
+
+    async fn format(&self, doc: &Arc<RwLock<Document>>) -> Result<FormatResult, EditorError> {
+        let job = self.format_job(doc).await?;
+        self.apply_format(job.run().await).await
+    }
 
-            // Update content and mark as modified
+    /// Formatting of the active document, to run once the editor is released
+    pub async fn format_active_job(&self) -> Result<FormatJob, EditorError> {
+        let Some(doc) = self.get_active_document().await else {
+            return Err(EditorError::Document("No active document".to_string()));
+        };
+        self.format_job(&doc).await
+    }
+
+    async fn format_job(&self, doc: &Arc<RwLock<Document>>) -> Result<FormatJob, EditorError> {
+        let (content, language, path) = {
+            let doc_read = doc.read().await;
+            ensure_editable(&doc_read)?;
+            let content = doc_read.content.read().await.to_string();
+            (content, doc_read.language.clone(), doc_read.path.clone())
+        };
+
+        if let Some((program, _)) = Self::external_formatter(&language, &path).filter(|_| !self.is_trusted()) {
+            return Err(EditorError::WorkspaceNotTrusted(program.to_string()));
+        }
+
+        let settings = self.file_settings(&path).await;
+        Ok(FormatJob { document: doc.clone(), language, path, content, settings })
+    }
+
+    /// Put formatted text into its document, unless the document was edited while
+    /// it was being formatted
+    pub async fn apply_format(&self, formatted: FormattedDocument) -> Result<FormatResult, EditorError> {
+        let changed = formatted.formatted != formatted.original;
+        if changed {
             {
-                let mut doc_write = doc.write().await;
+                let mut doc_write = formatted.document.write().await;
+                ensure_editable(&doc_write)?;
                 {
                     let mut content = doc_write.content.write().await;
-                    *content = formatted_content;
+                    if content.to_string() != formatted.original {
+                        return Err(EditorError::FormatConflict(doc_write.title.clone()));
+                    }
+                    *content = TextBuffer::from(formatted.formatted);
                 }
                 doc_write.is_modified = true;
             }
 
-            // Reparse syntax tree
-            self.parse_syntax_tree(doc).await;
+            self.schedule_parse(&formatted.document).await;
         }
 
-        Ok(())
+        Ok(FormatResult { changed, formatter: formatted.formatter })
+    }
+
+    /// Formatter command for a document, chosen by language and then by extension
+    fn external_formatter(language: &str, path: &std::path::Path) -> Option<(&'static str, Vec<String>)> {
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        match (language, extension) {
+            ("Rust", _) | (_, "rs") => Some(("rustfmt", vec!["--edition".to_string(), "2021".to_string()])),
+            ("Python", _) | (_, "py") => Some(("black", vec!["--quiet".to_string(), "-".to_string()])),
+            (_, "js" | "jsx" | "ts" | "tsx" | "json" | "css" | "scss" | "html" | "md" | "yaml" | "yml") => Some((
+                "prettier",
+                vec!["--stdin-filepath".to_string(), path.to_string_lossy().to_string()],
+            )),
+            _ => None,
+        }
+    }
+
+    /// Pipe content through the external formatter; `None` when it is missing, fails or times out
+    async fn run_external_formatter(
+        language: &str,
+        path: &std::path::Path,
+        content: &str,
+    ) -> Option<(String, String)> {
+        let (program, args) = Self::external_formatter(language, path)?;
+
+        let mut executor = CommandExecutor::default();
+        let result = tokio::time::timeout(
+            std::time::Duration::from_secs(FORMATTER_TIMEOUT_SECS),
+            executor.execute_with_input(program, &args, content),
+        ).await;
+
+        match result {
+            Ok(Ok(output)) if output.exit_code == 0 => Some((output.stdout, program.to_string())),
+            Ok(Ok(output)) => {
+                log::warn!("{} exited with {}: {}", program, output.exit_code, output.stderr.trim());
+                None
+            }
+            Ok(Err(e)) => {
+                log::debug!("{} unavailable: {}", program, e);
+                None
+            }
+            Err(_) => {
+                log::warn!("{} timed out after {}s", program, FORMATTER_TIMEOUT_SECS);
+                None
+            }
+        }
+    }
+
+    /// Formatting settings for the file at `path`: the editor settings with its `.editorconfig` applied.
+    /// Relative paths are taken from the workspace root, not the process's working directory.
+    pub async fn file_settings(&self, path: &std::path::Path) -> EffectiveSettings {
+        let (path, settings) = {
+            let config = self.config.read().await;
+            (config.workspace_dir().join(path), config.editor.clone())
+        };
+        EditorConfig::for_file(&path).await.effective(&settings)
     }
 
     /// Get editor configuration
@@ -636,78 +1263,131 @@ impl Editor {
         Ok(())
     }
     
-    /// Get syntax highlighting tokens for a range of text
+    /// Syntax highlighting tokens on lines `start_line..=end_line` from the latest completed parse
     pub async fn get_syntax_tokens(&self, document_id: &str, start_line: usize, end_line: usize) -> Result<Vec<SyntaxToken>, EditorError> {
-        let documents = self.documents.read().await;
-        let doc = documents.iter().find(|doc| {
-            if let Ok(doc_read) = doc.try_read() {
-                doc_read.id == document_id
-            } else {
-                false
-            }
-        }).ok_or_else(|| EditorError::Document("Document not found".to_string()))?;
-        
+        let doc = self.find_document(document_id).await?;
         let doc_read = doc.read().await;
-        let language_support = self.language_support.read().await;
-        let content = doc_read.content.read().await;
-        let lines: Vec<&str> = content.lines().collect();
-        
-        let mut tokens = Vec::new();
-        
-        // Get language support
-        if let Some(lang_support) = language_support.iter().find(|lang| lang.name == doc_read.language) {
-            for (line_num, line) in lines.iter().enumerate() {
-                if line_num >= start_line && line_num <= end_line {
-                    let line_tokens = self.tokenize_line(line, &lang_support.syntax_highlighting);
-                    for token in line_tokens {
-                        tokens.push(SyntaxToken {
-                            token_type: token.token_type,
-                            text: token.pattern.clone(),
-                            line: line_num,
-                            column: 0, // Would need more sophisticated parsing
-                        });
-                    }
-                }
-            }
-        }
-        
-        Ok(tokens)
+        Ok(doc_read.syntax_tokens.iter()
+            .filter(|token| (start_line..=end_line).contains(&token.line))
+            .cloned()
+            .collect())
     }
     
-    /// Parse syntax tree for a document
-    async fn parse_syntax_tree(&self, document: &Arc<RwLock<Document>>) {
-        // Extract content and document info without holding multiple borrows
-        let (content, _language) = {
-            let doc_read = document.read().await;
-            let content_clone = {
-                let content = doc_read.content.read().await;
-                content.clone()
+    /// Toggle line comments over `start_line..=end_line` (0-based); returns whether the lines are now commented
+    pub async fn toggle_comment(&self, document_id: &str, start_line: usize, end_line: usize) -> Result<bool, EditorError> {
+        let doc = self.find_document(document_id).await?;
+        ensure_editable(&*doc.read().await)?;
+
+        let language = doc.read().await.language.clone();
+        let token = {
+            let language_support = self.language_support.read().await;
+            language_support.iter()
+                .find(|lang| lang.name == language)
+                .and_then(|lang| lang.comment_syntax.line_comments.first().cloned())
+                .ok_or_else(|| EditorError::Document(format!("No line comment syntax for {}", language)))?
+        };
+
+        let commented = {
+            let mut doc_write = doc.write().await;
+            let commented = {
+                let mut content = doc_write.content.write().await;
+                let (toggled, commented) = toggle_line_comments(&content.to_string(), &token, start_line, end_line);
+                *content = TextBuffer::from(toggled);
+                commented
             };
-            (content_clone, doc_read.language.clone())
+            doc_write.is_modified = true;
+            commented
         };
-        
-        // Simple syntax tree generation (would use tree-sitter for real implementation)
-        let mut nodes = Vec::new();
-        
-        for (_line_num, line) in content.lines().enumerate() {
-            if line.starts_with("fn ") {
-                nodes.push(SyntaxNode {
-                    node_type: "function".to_string(),
-                    start_byte: 0,
-                    end_byte: 0,
-                    children: Vec::new(),
-                    text: line.to_string(),
-                });
-            }
+
+        self.schedule_parse(&doc).await;
+        Ok(commented)
+    }
+
+    /// Rewrite the leading whitespace of every line with `style` at `width` columns
+    /// per level, both defaulting to the file's `.editorconfig` or editor settings.
+    /// Returns whether anything changed.
+    pub async fn convert_indentation(
+        &self,
+        document_id: &str,
+        style: Option<IndentStyle>,
+        width: Option<usize>,
+    ) -> Result<bool, EditorError> {
+        let doc = self.find_document(document_id).await?;
+        ensure_editable(&*doc.read().await)?;
+
+        let path = doc.read().await.path.clone();
+        let settings = self.file_settings(&path).await;
+        let style = style.unwrap_or(settings.indent_style);
+        let width = width.unwrap_or(settings.indent_size).max(1);
+
+        let changed = {
+            let mut doc_write = doc.write().await;
+            let changed = {
+                let mut content = doc_write.content.write().await;
+                let original = content.to_string();
+                let converted = reindent(&original, style, width);
+                let changed = converted != original;
+                if changed {
+                    *content = TextBuffer::from(converted);
+                }
+                changed
+            };
+            doc_write.is_modified |= changed;
+            changed
+        };
+
+        if changed {
+            self.schedule_parse(&doc).await;
         }
-        
-        let syntax_tree = SyntaxTree {
-            nodes,
-            root: 0,
+        Ok(changed)
+    }
+    
+    /// Insert a line break at the document's cursor, carrying over the current line's
+    /// indentation when auto-indent is enabled
+    pub async fn insert_newline(&self, document_id: &str) -> Result<(), EditorError> {
+        let doc = self.find_document(document_id).await?;
+        ensure_editable(&*doc.read().await)?;
+
+        let path = doc.read().await.path.clone();
+        let unit = if self.config.read().await.editor.auto_indent {
+            Some(self.file_settings(&path).await.indent_unit())
+        } else {
+            None
         };
-        
-        let mut doc_write = document.write().await;
-        doc_write.syntax_tree = Some(syntax_tree);
+
+        {
+            let mut doc_write = doc.write().await;
+            let (cursor_line, cursor_column) = {
+                let mut content = doc_write.content.write().await;
+                let position = content.position(doc_write.cursor_line, doc_write.cursor_column);
+                let line = content.line_of(position);
+                let line_text = content.line(line);
+                let (before, after) = line_text.split_at(position - content.line_start(line));
+
+                let (text, line_offset, column) = match &unit {
+                    Some(unit) => newline_insertion(before, after, unit),
+                    None => ("\n".to_string(), 1, 0),
+                };
+                content.insert(position, &text);
+                (doc_write.cursor_line + line_offset, column)
+            };
+            doc_write.cursor_line = cursor_line;
+            doc_write.cursor_column = cursor_column;
+            doc_write.is_modified = true;
+        }
+
+        self.schedule_parse(&doc).await;
+        Ok(())
+    }
+    
+    /// Reparse a document in the background once edits to it pause
+    async fn schedule_parse(&self, document: &Arc<RwLock<Document>>) {
+        let language = document.read().await.language.clone();
+        let highlighting = self.language_support.read().await.iter()
+            .find(|lang| lang.name == language)
+            .map(|lang| lang.syntax_highlighting.clone());
+        let fold_strategy = self.fold_strategy(&language).await;
+        self.parser.schedule(document.clone(), highlighting, fold_strategy).await;
     }
     
     /// Detect language from file extension
@@ -727,58 +1407,230 @@ impl Editor {
         "Plain Text".to_string()
     }
     
-    /// Calculate cursor position in bytes
-    fn calculate_cursor_position(&self, content: &str, line: &usize, column: &usize) -> usize {
-        let lines: Vec<&str> = content.lines().collect();
-        
-        if *line >= lines.len() {
-            return content.len();
-        }
-        
-        let mut position = 0;
-        for (i, line_text) in lines.iter().enumerate() {
-            if i == *line {
-                break;
-            }
-            position += line_text.len() + 1; // +1 for newline
-        }
-        
-        if *line < lines.len() {
-            position + column.min(&lines[*line].len())
-        } else {
-            position
-        }
-    }
-    
     /// Get word at cursor
     fn get_word_at_cursor(&self, text_before_cursor: &str) -> String {
         let words: Vec<&str> = text_before_cursor.split_whitespace().collect();
         words.last().unwrap_or(&"").to_string()
     }
-    
-    /// Tokenize a line for syntax highlighting
-    fn tokenize_line(&self, line: &str, highlighting: &SyntaxHighlighting) -> Vec<TokenRule> {
-        let mut tokens = Vec::new();
-        
-        // Simple tokenization (would use more sophisticated regex for real implementation)
-        for rule in &highlighting.keywords {
-            if line.contains(&rule.pattern) {
-                tokens.push(rule.clone());
-            }
+}
+
+/// Text covered by `selection`, falling back to all of `content` when the selection
+/// is missing or empty. A rectangular selection takes the same columns from each line.
+fn selected_text(content: &TextBuffer, selection: Option<&Selection>) -> String {
+    let Some(selection) = selection else {
+        return content.to_string();
+    };
+    let (start, end) = (selection.start.min(selection.end), selection.start.max(selection.end));
+
+    let text = if selection.is_rectangular {
+        let (left, right) = (
+            selection.start.column.min(selection.end.column),
+            selection.start.column.max(selection.end.column),
+        );
+        (start.line..=end.line)
+            .map(|line| content.slice(content.position(line, left)..content.position(line, right)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    } else {
+        content.slice(content.position(start.line, start.column)..content.position(end.line, end.column))
+    };
+
+    if text.trim().is_empty() {
+        content.to_string()
+    } else {
+        text
+    }
+}
+
+/// Comment out `start..=end` if any non-blank line is uncommented, otherwise uncomment them all.
+/// The token goes at the shallowest indentation in the range so nested code stays aligned.
+fn toggle_line_comments(content: &str, token: &str, start: usize, end: usize) -> (String, bool) {
+    let mut lines: Vec<String> = content.split('\n').map(str::to_string).collect();
+    let end = end.min(lines.len().saturating_sub(1));
+    if start > end {
+        return (content.to_string(), false);
+    }
+
+    let range = start..=end;
+    let non_blank = || lines[range.clone()].iter().filter(|line| !line.trim().is_empty());
+    let comment = non_blank().any(|line| !line.trim_start().starts_with(token));
+
+    if comment {
+        let indent = non_blank()
+            .map(|line| line.len() - line.trim_start().len())
+            .min()
+            .unwrap_or(0);
+        for line in lines[range].iter_mut().filter(|line| !line.trim().is_empty()) {
+            line.insert_str(indent, &format!("{} ", token));
         }
-        
-        for rule in &highlighting.strings {
-            if line.contains(&rule.pattern) {
-                tokens.push(rule.clone());
+    } else {
+        for line in lines[range].iter_mut().filter(|line| !line.trim().is_empty()) {
+            let indent = line.len() - line.trim_start().len();
+            let rest = &line[indent + token.len()..];
+            let rest = rest.strip_prefix(' ').unwrap_or(rest);
+            *line = format!("{}{}", &line[..indent], rest);
+        }
+    }
+
+    (lines.join("\n"), comment)
+}
+
+/// Where a line starts, for `reindent` and brace folding: lines that begin inside
+/// a string literal or block comment keep their whitespace, since it belongs to
+/// the text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LexState {
+    Code,
+    String(&'static str),
+    BlockComment,
+}
+
+/// Lexer state after `line`, which starts in `state`. Knows `"`, backtick and
+/// triple-quoted strings, char literals such as `'"'`, and C-style and `#` line
+/// comments. A lone `'` is skipped because it also starts Rust lifetimes, and a
+/// `#` only starts a comment before whitespace, so Rust attributes and
+/// JavaScript private fields stay code.
+fn lex_line(line: &str, state: LexState) -> LexState {
+    lex_code(line, state, |_| {})
+}
+
+/// [`lex_line`], calling `code` with each character outside strings and comments
+fn lex_code(line: &str, mut state: LexState, mut code: impl FnMut(char)) -> LexState {
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        match state {
+            LexState::Code => {
+                if rest.starts_with("//") || is_hash_comment(rest) {
+                    break;
+                }
+                if let Some(length) = char_literal_len(rest) {
+                    rest = &rest[length..];
+                    continue;
+                }
+                if let Some(delimiter) = ["/*", "\"\"\"", "'''", "\"", "`"].into_iter().find(|d| rest.starts_with(d)) {
+                    state = if delimiter == "/*" { LexState::BlockComment } else { LexState::String(delimiter) };
+                    rest = &rest[delimiter.len()..];
+                    continue;
+                }
+                code(c);
+            }
+            LexState::String(delimiter) => {
+                if c == '\\' {
+                    let mut chars = rest.chars();
+                    chars.next();
+                    chars.next();
+                    rest = chars.as_str();
+                    continue;
+                }
+                if rest.starts_with(delimiter) {
+                    state = LexState::Code;
+                    rest = &rest[delimiter.len()..];
+                    continue;
+                }
+            }
+            LexState::BlockComment => {
+                if rest.starts_with("*/") {
+                    state = LexState::Code;
+                    rest = &rest[2..];
+                    continue;
+                }
             }
         }
-        
-        tokens
+        rest = &rest[c.len_utf8()..];
     }
+    state
+}
+
+/// Whether `text` starts with a `#` comment: a `#` followed by whitespace or
+/// nothing
+fn is_hash_comment(text: &str) -> bool {
+    text.strip_prefix('#').is_some_and(|rest| rest.chars().next().is_none_or(char::is_whitespace))
+}
+
+/// Length in bytes of the char literal `text` starts with, such as `'"'` or `'\''`
+fn char_literal_len(text: &str) -> Option<usize> {
+    let mut chars = text.char_indices();
+    if chars.next()?.1 != '\'' {
+        return None;
+    }
+    match chars.next()?.1 {
+        '\'' => None,
+        '\\' => {
+            // The escaped character, then the closing quote of `'\n'` or `'\u{1F600}'`
+            chars.next()?;
+            chars.take(8).find(|(_, c)| *c == '\'').map(|(index, _)| index + 1)
+        }
+        _ => chars.next().filter(|(_, c)| *c == '\'').map(|(index, _)| index + 1),
+    }
+}
+
+/// Re-express each line's leading whitespace in `style`, with tabs `width` columns
+/// wide. Columns that do not fill a whole tab stay spaces, so alignment is kept.
+fn reindent(content: &str, style: IndentStyle, width: usize) -> String {
+    let mut state = LexState::Code;
+    let lines: Vec<String> = content.split('\n')
+        .map(|line| {
+            let starts_in = state;
+            state = lex_line(line, state);
+            if starts_in != LexState::Code {
+                return line.to_string();
+            }
+
+            let body = line.trim_start_matches([' ', '\t']);
+            let columns = line[..line.len() - body.len()].chars().fold(0, |column, c| match c {
+                '\t' => (column / width + 1) * width,
+                _ => column + 1,
+            });
+            let indent = match style {
+                IndentStyle::Space => " ".repeat(columns),
+                IndentStyle::Tab => format!("{}{}", "\t".repeat(columns / width), " ".repeat(columns % width)),
+            };
+            format!("{}{}", indent, body)
+        })
+        .collect();
+    lines.join("\n")
+}
+
+/// One level of indentation for the given settings
+fn indent_unit(tab_size: usize, insert_spaces: bool) -> String {
+    if insert_spaces {
+        " ".repeat(tab_size.max(1))
+    } else {
+        "\t".to_string()
+    }
+}
+
+/// Text to insert for Enter between `before` and `after`, the parts of the cursor's line on
+/// either side of it, plus the cursor's line offset and column within that text.
+/// Indents one extra level after an opener and one less before a closing bracket.
+fn newline_insertion(before: &str, after: &str, unit: &str) -> (String, usize, usize) {
+    let indent = &before[..before.len() - before.trim_start().len()];
+    let opens = before.trim_end().ends_with(['{', '[', '(', ':']);
+    let closes = after.trim_start().starts_with(['}', ']', ')']);
+
+    if opens {
+        let inner = format!("{}{}", indent, unit);
+        let text = if closes {
+            // Between a pair of brackets: the closer moves to its own line
+            format!("\n{}\n{}", inner, indent)
+        } else {
+            format!("\n{}", inner)
+        };
+        return (text, 1, inner.len());
+    }
+
+    let indent = if closes {
+        indent.strip_suffix(unit)
+            .or_else(|| indent.strip_suffix('\t'))
+            .unwrap_or_else(|| indent.trim_end_matches(' '))
+    } else {
+        indent
+    };
+    (format!("\n{}", indent), 1, indent.len())
 }
 
 /// Syntax token for highlighting
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyntaxToken {
     pub token_type: String,
     pub text: String,
@@ -794,15 +1646,490 @@ impl Default for Document {
             id: uuid::Uuid::new_v4().to_string(),
             path: std::path::PathBuf::new(),
             title: "Untitled".to_string(),
-            content: Arc::new(RwLock::new("".to_string())),
+            content: Arc::new(RwLock::new(TextBuffer::default())),
             language: "Plain Text".to_string(),
             is_modified: false,
             last_saved: None,
             syntax_tree: None,
+            syntax_tokens: Vec::new(),
+            outline: None,
             bookmarks: Vec::new(),
             fold_points: Vec::new(),
             cursor_line: 0,
             cursor_column: 0,
+            selection: None,
+            cursors: Vec::new(),
+            encoding: TextEncoding::default(),
+            mode: OpenMode::Edit,
+            disk_hash: None,
         }
     }
 }
+
+/// Error for a document file that could not be read, keeping a missing file
+/// apart from one that is unreadable
+fn read_error(path: &std::path::Path, error: FileManagerError) -> EditorError {
+    let kind = match &error {
+        FileManagerError::NotFound(_) => std::io::ErrorKind::NotFound,
+        FileManagerError::Permission(_) => std::io::ErrorKind::PermissionDenied,
+        FileManagerError::Io(error) => error.kind(),
+        FileManagerError::Watch(_) => std::io::ErrorKind::Other,
+    };
+    match kind {
+        std::io::ErrorKind::NotFound => EditorError::FileNotFound(path.display().to_string()),
+        std::io::ErrorKind::PermissionDenied => EditorError::PermissionDenied(path.display().to_string()),
+        _ => EditorError::Document(format!("Reading {} failed: {}", path.display(), error)),
+    }
+}
+
+/// Refuse to change a document opened read-only
+fn ensure_editable(document: &Document) -> Result<(), EditorError> {
+    if document.mode.is_read_only() {
+        return Err(EditorError::ReadOnly(document.title.clone()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toggle_rust_comments_round_trip() {
+        let source = "fn main() {\n    let x = 1;\n\n    if x > 0 {\n        run();\n    }\n}\n";
+
+        let (commented, is_commented) = toggle_line_comments(source, "//", 1, 5);
+        assert!(is_commented);
+        assert_eq!(
+            commented,
+            "fn main() {\n    // let x = 1;\n\n    // if x > 0 {\n    //     run();\n    // }\n}\n"
+        );
+
+        let (restored, is_commented) = toggle_line_comments(&commented, "//", 1, 5);
+        assert!(!is_commented);
+        assert_eq!(restored, source);
+    }
+
+    #[test]
+    fn test_toggle_mixed_selection_comments_all() {
+        let source = "// already();\nnot_yet();\n  //tight();";
+
+        let (toggled, is_commented) = toggle_line_comments(source, "//", 0, 2);
+        assert!(is_commented);
+        assert_eq!(toggled, "// // already();\n// not_yet();\n//   //tight();");
+
+        let (uncommented, is_commented) = toggle_line_comments("// a\n  //b", "//", 0, 1);
+        assert!(!is_commented);
+        assert_eq!(uncommented, "a\n  b");
+    }
+
+    #[test]
+    fn test_selection_text_spans_lines() {
+        let content = TextBuffer::from("fn main() {\n    let total = add(1, 2);\n    println!(\"{}\", total);\n}\n");
+        let at = |line, column| CursorPosition { line, column };
+        let selection = |start, end, is_rectangular| Selection { start, end, is_rectangular };
+
+        let forward = selection(at(1, 8), at(2, 12), false);
+        assert_eq!(selected_text(&content, Some(&forward)), "total = add(1, 2);\n    println!");
+        let backward = selection(at(2, 12), at(1, 8), false);
+        assert_eq!(selected_text(&content, Some(&backward)), "total = add(1, 2);\n    println!");
+
+        let block = selection(at(1, 4), at(2, 11), true);
+        assert_eq!(selected_text(&content, Some(&block)), "let tot\nprintln");
+
+        let empty = selection(at(1, 4), at(1, 4), false);
+        assert_eq!(selected_text(&content, Some(&empty)), content.to_string());
+        assert_eq!(selected_text(&content, None), content.to_string());
+    }
+
+    #[test]
+    fn test_newline_carries_indentation() {
+        assert_eq!(newline_insertion("    let x = 1;", "", "    "), ("\n    ".to_string(), 1, 4));
+        assert_eq!(newline_insertion("\t\tcall();", "", "\t"), ("\n\t\t".to_string(), 1, 2));
+        assert_eq!(newline_insertion("plain", " text", "  "), ("\n".to_string(), 1, 0));
+    }
+
+    #[test]
+    fn test_newline_indents_after_opener() {
+        assert_eq!(newline_insertion("fn main() {", "", "    "), ("\n    ".to_string(), 1, 4));
+        assert_eq!(newline_insertion("  if ready:", "", "  "), ("\n    ".to_string(), 1, 4));
+        assert_eq!(newline_insertion("\tfoo(", "", "\t"), ("\n\t\t".to_string(), 1, 2));
+    }
+
+    #[test]
+    fn test_newline_dedents_before_closer() {
+        assert_eq!(newline_insertion("        value", "}", "    "), ("\n    ".to_string(), 1, 4));
+        assert_eq!(newline_insertion("\t\tvalue", " ]", "\t"), ("\n\t".to_string(), 1, 1));
+        assert_eq!(
+            newline_insertion("    let v = vec![", "];", "    "),
+            ("\n        \n    ".to_string(), 1, 8)
+        );
+    }
+
+    #[test]
+    fn test_reindent_tabs_to_spaces_and_back() {
+        let tabs = "fn main() {\n\tlet text = \"first\n\tkept as is\";\n\tif ready {\n\t\tgo(1,\t2);\n\t}\n\t/* note\n\t */\n}\n";
+        let spaces = "fn main() {\n    let text = \"first\n\tkept as is\";\n    if ready {\n        go(1,\t2);\n    }\n    /* note\n\t */\n}\n";
+
+        assert_eq!(reindent(tabs, IndentStyle::Space, 4), spaces);
+        assert_eq!(reindent(spaces, IndentStyle::Tab, 4), tabs);
+        assert_eq!(reindent("  \tmixed\n      six", IndentStyle::Tab, 4), "\tmixed\n\t  six");
+        assert_eq!(reindent("x = '''\n    raw\n'''\n  y", IndentStyle::Tab, 2), "x = '''\n    raw\n'''\n\ty");
+    }
+
+    #[test]
+    fn test_reindent_sees_past_char_literals_and_line_comments() {
+        let rust = "fn quote(c: char) -> bool {\n\tc == '\"' || c == '\\''\n}\n// it's \"done\n\tnext();\n";
+        assert_eq!(
+            reindent(rust, IndentStyle::Space, 4),
+            "fn quote(c: char) -> bool {\n    c == '\"' || c == '\\''\n}\n// it's \"done\n    next();\n"
+        );
+        let python = "# say \"hi\n\tgreet()\n";
+        assert_eq!(reindent(python, IndentStyle::Space, 4), "# say \"hi\n    greet()\n");
+        assert_eq!(lex_line("#[derive(Debug)] struct S { s: \"", LexState::Code), LexState::String("\""));
+    }
+
+    #[test]
+    fn test_indent_unit_follows_settings() {
+        assert_eq!(indent_unit(2, true), "  ");
+        assert_eq!(indent_unit(4, false), "\t");
+    }
+
+    #[tokio::test]
+    async fn test_close_document_while_another_is_read_locked() {
+        let dir = std::env::temp_dir().join(format!("super-ide-editor-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.rs"), "fn a() {}\n").unwrap();
+        std::fs::write(dir.join("b.rs"), "fn b() {}\n").unwrap();
+
+        let config = Configuration::default();
+        let ai_engine = Arc::new(AiEngine::new(crate::ai::AiConfig::from(&config)));
+        let editor = Editor::new(&config, ai_engine).await.unwrap();
+        let first = editor.open_file(dir.join("a.rs")).await.unwrap();
+        let second = editor.open_file(dir.join("b.rs")).await.unwrap();
+
+        let held = editor.find_document(&first).await.unwrap();
+        let (locked_tx, locked_rx) = tokio::sync::oneshot::channel();
+        let (release_tx, release_rx) = tokio::sync::oneshot::channel::<()>();
+        let reader = tokio::spawn(async move {
+            let _guard = held.read().await;
+            let _ = locked_tx.send(());
+            let _ = release_rx.await;
+        });
+        locked_rx.await.unwrap();
+
+        let closed = tokio::time::timeout(std::time::Duration::from_secs(5), editor.close_document(&second))
+            .await
+            .expect("close_document blocked on an unrelated lock");
+        assert!(closed.unwrap());
+        assert!(!editor.close_document(&second).await.unwrap());
+        assert_eq!(editor.get_documents().await.len(), 1);
+        assert!(editor.find_document(&second).await.is_err());
+
+        let active = editor.get_active_document().await.unwrap();
+        assert!(Arc::ptr_eq(&active, &editor.find_document(&first).await.unwrap()));
+
+        release_tx.send(()).unwrap();
+        reader.await.unwrap();
+        assert_eq!(active.read().await.id, first);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_rapid_edits_are_parsed_once() {
+        let dir = std::env::temp_dir().join(format!("super-ide-editor-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("main.rs"), "fn main() {\n    run();\n}\n").unwrap();
+
+        let event_bus = Arc::new(crate::utils::event_bus::EventBus::new());
+        let mut events = event_bus.subscribe("editor").unwrap();
+        let config = Configuration::default();
+        let ai_engine = Arc::new(AiEngine::new(crate::ai::AiConfig::from(&config)));
+        let editor = Editor::new(&config, ai_engine).await.unwrap().with_event_bus(event_bus);
+        let id = editor.open_file(dir.join("main.rs")).await.unwrap();
+        for _ in 0..5 {
+            editor.insert_text("\nfn helper() {}").await.unwrap();
+        }
+        let doc = editor.find_document(&id).await.unwrap();
+        assert!(doc.read().await.syntax_tree.is_none());
+
+        let wait = syntax::PARSE_DEBOUNCE * 4;
+        let Ok(Some(crate::utils::event_bus::IdeEvent::SyntaxParsed { document_id, revision, tree, .. })) = tokio::time::timeout(wait, events.recv()).await else {
+            panic!("no parse completed");
+        };
+        assert_eq!((document_id.as_str(), revision), (id.as_str(), 6));
+        assert!(tokio::time::timeout(wait, events.recv()).await.is_err(), "edits were parsed more than once");
+
+        // The one parse saw the text after the last edit
+        let content = doc.read().await.content.read().await.to_string();
+        assert_eq!(content.matches("fn helper() {}").count(), 5);
+        let functions: Vec<&str> = content.lines().filter(|line| line.starts_with("fn ")).collect();
+        let parsed: Vec<String> = tree.nodes.iter().map(|node| node.text.clone()).collect();
+        assert_eq!(parsed, functions);
+        assert_eq!(doc.read().await.syntax_tree.as_ref().unwrap().nodes.len(), functions.len());
+        let folds: Vec<(usize, usize)> = doc.read().await.fold_points.iter()
+            .map(|point| (point.start_line, point.end_line))
+            .collect();
+        assert_eq!(folds.len(), 1);
+        assert_eq!(folds, folding::compute_fold_points(&content, folding::FoldStrategy::Braces).iter()
+            .map(|point| (point.start_line, point.end_line))
+            .collect::<Vec<_>>());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_utf16_document_is_saved_in_its_encoding() {
+        let dir = std::env::temp_dir().join(format!("super-ide-editor-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.txt");
+        let utf16le = |text: &str| -> Vec<u8> {
+            [0xFF, 0xFE].into_iter().chain(text.encode_utf16().flat_map(u16::to_le_bytes)).collect()
+        };
+        std::fs::write(&path, utf16le("café\n")).unwrap();
+
+        let mut config = Configuration::default();
+        config.ide.workspace_path = dir.to_string_lossy().to_string();
+        let ai_engine = Arc::new(AiEngine::new(crate::ai::AiConfig::from(&config)));
+        let editor = Editor::new(&config, ai_engine).await.unwrap();
+        editor.open_file(path.clone()).await.unwrap();
+        let active = editor.get_active_document().await.unwrap();
+        {
+            let doc = active.read().await;
+            assert_eq!(doc.content.read().await.to_string(), "café\n");
+            assert_eq!((doc.encoding.name(), doc.encoding.has_bom()), ("UTF-16LE", true));
+        }
+
+        editor.insert_text("Déjà vu: ").await.unwrap();
+        editor.save_active_document().await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), utf16le("Déjà vu: café\n"));
+
+        // The overwritten content is in the local history, and comes back in its encoding
+        let files = crate::file_ops::FileManager::new(dir.clone());
+        let history = files.list_history(std::path::Path::new("notes.txt")).await.unwrap();
+        assert_eq!(history.len(), 1);
+        files.restore_history(std::path::Path::new("notes.txt"), &history[0].id).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), utf16le("café\n"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_external_change_is_handled_by_the_conflict_strategy() {
+        let dir = std::env::temp_dir().join(format!("super-ide-editor-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.txt");
+        std::fs::write(&path, "draft\n").unwrap();
+
+        let mut config = Configuration::default();
+        config.editor.save_conflict_strategy = SaveConflictStrategy::Skip;
+        let ai_engine = Arc::new(AiEngine::new(crate::ai::AiConfig::from(&config)));
+        let editor = Editor::new(&config, ai_engine).await.unwrap();
+        let id = editor.open_file(path.clone()).await.unwrap();
+        editor.insert_text("mine: ").await.unwrap();
+        std::fs::write(&path, "theirs\n").unwrap();
+
+        // Skip leaves both the file and the unsaved edit alone
+        let autosaved = editor.autosave().await;
+        assert_eq!(autosaved, vec![save_conflict::AutosaveEntry {
+            document_id: id.clone(),
+            path: path.clone(),
+            status: save_conflict::AutosaveStatus::Skipped,
+        }]);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "theirs\n");
+        assert!(editor.find_document(&id).await.unwrap().read().await.is_modified);
+
+        editor.update_config(crate::config::EditorSettings {
+            save_conflict_strategy: SaveConflictStrategy::Prompt,
+            ..config.editor.clone()
+        }).await.unwrap();
+        assert!(matches!(editor.save_active_document().await, Err(EditorError::SaveConflict(title)) if title == "notes.txt"));
+        assert_eq!(editor.autosave().await[0].status, save_conflict::AutosaveStatus::Conflict);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "theirs\n");
+
+        // Overwriting after the prompt makes the saved content the new baseline
+        assert_eq!(editor.save_active_document_with(SaveConflictStrategy::Overwrite).await.unwrap(), SaveOutcome::Saved);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "mine: draft\n");
+        editor.insert_text("more ").await.unwrap();
+        assert_eq!(editor.save_active_document().await.unwrap(), SaveOutcome::Saved);
+        assert!(editor.autosave().await.is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_newline_inside_a_character_goes_before_it() {
+        let config = Configuration::default();
+        let ai_engine = Arc::new(AiEngine::new(crate::ai::AiConfig::from(&config)));
+        let editor = Editor::new(&config, ai_engine).await.unwrap();
+        let document = Document {
+            content: Arc::new(RwLock::new(TextBuffer::from("café = 1;\n"))),
+            cursor_column: 4,
+            ..Document::default()
+        };
+        let (id, doc) = editor.add_document(document).await;
+
+        editor.insert_newline(&id).await.unwrap();
+        assert_eq!(doc.read().await.content.read().await.to_string(), "caf\né = 1;\n");
+        assert_eq!((doc.read().await.cursor_line, doc.read().await.cursor_column), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_insert_at_three_cursors_keeps_them_aligned() {
+        let config = Configuration::default();
+        let ai_engine = Arc::new(AiEngine::new(crate::ai::AiConfig::from(&config)));
+        let editor = Editor::new(&config, ai_engine).await.unwrap();
+        let document = Document {
+            content: Arc::new(RwLock::new(TextBuffer::from("let a = 1;\nlet b = 2; let c = 3;\n"))),
+            ..Document::default()
+        };
+        let (id, doc) = editor.add_document(document).await;
+        let at = |line, column| CursorPosition { line, column };
+        editor.set_cursors(&id, vec![at(1, 15), at(0, 4), at(1, 4)]).await.unwrap();
+
+        let cursors = editor.insert_at_cursors(&id, "mut ").await.unwrap();
+        assert_eq!(doc.read().await.content.read().await.to_string(), "let mut a = 1;\nlet mut b = 2; let mut c = 3;\n");
+        assert_eq!(cursors, vec![at(0, 8), at(1, 8), at(1, 23)]);
+        assert_eq!(doc.read().await.cursors, cursors);
+        assert_eq!((doc.read().await.cursor_line, doc.read().await.cursor_column), (0, 8));
+
+        // Each cursor types at its own place, and backspace undoes every copy
+        editor.insert_at_cursors(&id, "x").await.unwrap();
+        assert_eq!(doc.read().await.content.read().await.to_string(), "let mut xa = 1;\nlet mut xb = 2; let mut xc = 3;\n");
+        let cursors = editor.delete_at_cursors(&id, 5).await.unwrap();
+        assert_eq!(doc.read().await.content.read().await.to_string(), "let a = 1;\nlet b = 2; let c = 3;\n");
+        assert_eq!(cursors, vec![at(0, 4), at(1, 4), at(1, 15)]);
+        assert!(doc.read().await.is_modified);
+
+        // Any count deletes no further back than the start of the document
+        let cursors = editor.delete_at_cursors(&id, usize::MAX).await.unwrap();
+        assert_eq!(doc.read().await.content.read().await.to_string(), "c = 3;\n");
+        assert_eq!(cursors, vec![at(0, 0)]);
+    }
+
+    #[tokio::test]
+    async fn test_preview_documents_reject_edits() {
+        let dir = std::env::temp_dir().join(format!("super-ide-editor-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("build.log"), "Compiling super-ide\n").unwrap();
+        std::fs::write(dir.join("icon.png"), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x10").unwrap();
+
+        let config = Configuration::default();
+        let ai_engine = Arc::new(AiEngine::new(crate::ai::AiConfig::from(&config)));
+        let editor = Editor::new(&config, ai_engine).await.unwrap();
+        let log = editor.open_file_as(dir.join("build.log"), OpenMode::Preview).await.unwrap();
+        assert!(matches!(editor.insert_text("edited ").await, Err(EditorError::ReadOnly(title)) if title == "build.log"));
+        assert!(matches!(editor.toggle_comment(&log, 0, 0).await, Err(EditorError::ReadOnly(_))));
+        assert!(matches!(editor.save_active_document().await, Err(EditorError::ReadOnly(_))));
+        assert_eq!(std::fs::read_to_string(dir.join("build.log")).unwrap(), "Compiling super-ide\n");
+
+        let icon = editor.open_file_as(dir.join("icon.png"), OpenMode::Hex).await.unwrap();
+        let rows = editor.hex_rows(&icon).await.unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].offset, rows[0].ascii.as_str()), (0, ".PNG........IHDR"));
+        assert_eq!(rows[1].hex, "00 00 00 10");
+        let dump = editor.find_document(&icon).await.unwrap().read().await.content.read().await.to_string();
+        assert!(dump.starts_with("00000000  89 50 4e 47 0d 0a 1a 0a"));
+        assert!(matches!(editor.delete_text(1).await, Err(EditorError::ReadOnly(_))));
+        assert!(editor.hex_rows(&log).await.is_err());
+
+        // Binary files open as hex whatever was asked for
+        let image = editor.open_file_as(dir.join("icon.png"), OpenMode::Edit).await.unwrap();
+        assert_eq!(editor.document_mode(&image).await.unwrap(), OpenMode::Hex);
+
+        // UTF-16 text has NULs in it, but is text
+        let utf16le: Vec<u8> = [0xFF, 0xFE].into_iter()
+            .chain("Grüße\n".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        std::fs::write(dir.join("greeting.txt"), utf16le).unwrap();
+        let greeting = editor.open_file_as(dir.join("greeting.txt"), OpenMode::Edit).await.unwrap();
+        assert_eq!(editor.document_mode(&greeting).await.unwrap(), OpenMode::Edit);
+        editor.insert_text("Liebe ").await.unwrap();
+        let text = editor.find_document(&greeting).await.unwrap().read().await.content.read().await.to_string();
+        assert_eq!(text, "Liebe Grüße\n");
+
+        // Only the start of a large file is previewed
+        std::fs::write(dir.join("huge.log"), "é".repeat(preview::MAX_TEXT_PREVIEW_BYTES)).unwrap();
+        let huge = editor.open_file_as(dir.join("huge.log"), OpenMode::Preview).await.unwrap();
+        let text = editor.find_document(&huge).await.unwrap().read().await.content.read().await.to_string();
+        assert_eq!(text.len(), preview::MAX_TEXT_PREVIEW_BYTES);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_revert_discards_edits() {
+        let dir = std::env::temp_dir().join(format!("super-ide-editor-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.txt");
+        std::fs::write(&path, "draft\n").unwrap();
+
+        let config = Configuration::default();
+        let ai_engine = Arc::new(AiEngine::new(crate::ai::AiConfig::from(&config)));
+        let editor = Editor::new(&config, ai_engine).await.unwrap();
+        let id = editor.open_file(path.clone()).await.unwrap();
+        editor.insert_text("mine: ").await.unwrap();
+        std::fs::write(&path, "theirs\n").unwrap();
+
+        // Unsaved edits are only thrown away when the caller confirms
+        assert!(matches!(editor.revert_document(&id, false).await, Err(EditorError::UnsavedChanges(title)) if title == "notes.txt"));
+        let doc = editor.find_document(&id).await.unwrap();
+        assert_eq!(doc.read().await.content.read().await.to_string(), "mine: draft\n");
+
+        editor.revert_document(&id, true).await.unwrap();
+        assert_eq!(doc.read().await.content.read().await.to_string(), std::fs::read_to_string(&path).unwrap());
+        assert!(!doc.read().await.is_modified);
+        // The reverted text is the new baseline, so saving it is no conflict
+        assert_eq!(editor.save_active_document().await.unwrap(), SaveOutcome::Saved);
+
+        // A missing file and an unreadable one are different failures
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(editor.revert_document(&id, true).await, Err(EditorError::FileNotFound(_))));
+        std::fs::create_dir(&path).unwrap();
+        assert!(matches!(editor.revert_document(&id, true).await, Err(EditorError::Document(_))));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_explicit_format_ignores_format_on_save() {
+        let dir = std::env::temp_dir().join(format!("super-ide-editor-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.txt");
+        std::fs::write(&path, "first   \nsecond\t\n").unwrap();
+
+        let mut config = Configuration::default();
+        config.editor.format_on_save = false;
+        let ai_engine = Arc::new(AiEngine::new(crate::ai::AiConfig::from(&config)));
+        let editor = Editor::new(&config, ai_engine).await.unwrap();
+        editor.open_file(path.clone()).await.unwrap();
+
+        editor.save_active_document().await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "first   \nsecond\t\n");
+
+        assert!(editor.format_document().await.unwrap().changed);
+        editor.save_active_document().await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "first\nsecond\n");
+
+        // Text typed while the formatter runs is not overwritten by its output
+        editor.insert_text("\t\n").await.unwrap();
+        let job = editor.format_active_job().await.unwrap();
+        let doc = editor.get_active_document().await.unwrap();
+        doc.read().await.content.write().await.insert(0, "y");
+        let conflict = editor.apply_format(job.run().await).await;
+        assert!(matches!(conflict, Err(EditorError::FormatConflict(title)) if title == "notes.txt"));
+        assert_eq!(doc.read().await.content.read().await.to_string(), "y\t\nfirst\nsecond\n");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_relative_paths_use_the_workspace_editorconfig() {
+        let dir = std::env::temp_dir().join(format!("super-ide-editor-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join(".editorconfig"), "root = true\n\n[*.rs]\nindent_style = tab\n").unwrap();
+
+        let mut config = Configuration::default();
+        config.ide.workspace_path = dir.to_string_lossy().to_string();
+        let ai_engine = Arc::new(AiEngine::new(crate::ai::AiConfig::from(&config)));
+        let editor = Editor::new(&config, ai_engine).await.unwrap();
+
+        let settings = editor.file_settings(std::path::Path::new("src/main.rs")).await;
+        assert_eq!(settings.indent_style, IndentStyle::Tab);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,228 @@
+//! Navigation history and recent files
+//!
+//! Each jump, whether opening a document, switching to another one or moving the
+//! cursor far within one, records where the user was. `navigate_back` returns
+//! there and `navigate_forward` undoes the return, as in a browser; a new jump
+//! after going back drops the forward entries. Recent files are kept apart from
+//! the history: each file once, most recently focused first, remembered after
+//! its document is closed.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use super::{Document, Editor, EditorError};
+
+/// Locations kept to go back to
+const MAX_NAVIGATION_ENTRIES: usize = 50;
+
+/// A cursor move of at least this many lines is a jump
+pub(super) const JUMP_LINES: usize = 10;
+
+/// A place to go back to: a document and the cursor in it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NavigationEntry {
+    pub document_id: String,
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl NavigationEntry {
+    pub(super) fn of(doc: &Document) -> Self {
+        Self {
+            document_id: doc.id.clone(),
+            path: doc.path.clone(),
+            line: doc.cursor_line,
+            column: doc.cursor_column,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(super) struct NavigationHistory {
+    back: Vec<NavigationEntry>,
+    forward: Vec<NavigationEntry>,
+    /// Document just opened or focused, whose first cursor move is where it lands
+    /// rather than a jump away from the top of the file
+    landed: Option<String>,
+    recent_files: Vec<PathBuf>,
+}
+
+impl NavigationHistory {
+    /// Remember `from` as the place a jump left
+    fn record(&mut self, from: Option<NavigationEntry>) {
+        self.landed = None;
+        self.forward.clear();
+        let Some(from) = from else {
+            return;
+        };
+        if self.back.last() != Some(&from) {
+            self.back.push(from);
+        }
+        if self.back.len() > MAX_NAVIGATION_ENTRIES {
+            self.back.remove(0);
+        }
+    }
+
+    /// Record the jump of a cursor move in `document_id` from `from`
+    pub(super) fn record_cursor_jump(&mut self, document_id: &str, from: NavigationEntry) {
+        if self.landed.as_deref() == Some(document_id) {
+            self.landed = None;
+            return;
+        }
+        self.record(Some(from));
+    }
+
+    /// Record a switch from `from` to the document at `path`
+    fn record_focus(&mut self, from: Option<NavigationEntry>, document_id: &str, path: PathBuf, max_recent_files: usize) {
+        if from.as_ref().is_some_and(|from| from.document_id == document_id) {
+            return;
+        }
+        self.record(from);
+        self.landed = Some(document_id.to_string());
+        if !path.as_os_str().is_empty() {
+            self.recent_files.retain(|recent| *recent != path);
+            self.recent_files.insert(0, path);
+            self.recent_files.truncate(max_recent_files);
+        }
+    }
+
+    /// Drop the entries of a closed document
+    pub(super) fn forget(&mut self, document_id: &str) {
+        self.back.retain(|entry| entry.document_id != document_id);
+        self.forward.retain(|entry| entry.document_id != document_id);
+        if self.landed.as_deref() == Some(document_id) {
+            self.landed = None;
+        }
+    }
+}
+
+impl Editor {
+    /// Where the cursor of the active document is
+    async fn active_location(&self) -> Option<NavigationEntry> {
+        let active = self.get_active_document().await?;
+        let doc = active.read().await;
+        Some(NavigationEntry::of(&doc))
+    }
+
+    /// Make `document` the active one, recording the switch as a jump
+    pub(super) async fn focus_document(&self, document: Arc<RwLock<Document>>) {
+        let from = self.active_location().await;
+        let (document_id, path) = {
+            let doc = document.read().await;
+            (doc.id.clone(), doc.path.clone())
+        };
+        *self.active_document.write().await = Some(document);
+        let max_recent_files = self.config.read().await.ide.max_recent_files;
+        self.navigation.write().await.record_focus(from, &document_id, path, max_recent_files);
+    }
+
+    /// Switch to an open document
+    pub async fn activate_document(&self, document_id: &str) -> Result<(), EditorError> {
+        let document = self.find_document(document_id).await?;
+        self.focus_document(document).await;
+        Ok(())
+    }
+
+    /// Return to the location before the last jump, if there is one
+    pub async fn navigate_back(&self) -> Result<Option<NavigationEntry>, EditorError> {
+        let current = self.active_location().await;
+        let entry = {
+            let mut navigation = self.navigation.write().await;
+            let Some(entry) = navigation.back.pop() else {
+                return Ok(None);
+            };
+            navigation.forward.extend(current);
+            navigation.landed = None;
+            entry
+        };
+        self.restore_location(&entry).await?;
+        Ok(Some(entry))
+    }
+
+    /// Undo the last `navigate_back`, if no jump has been made since
+    pub async fn navigate_forward(&self) -> Result<Option<NavigationEntry>, EditorError> {
+        let current = self.active_location().await;
+        let entry = {
+            let mut navigation = self.navigation.write().await;
+            let Some(entry) = navigation.forward.pop() else {
+                return Ok(None);
+            };
+            navigation.back.extend(current);
+            navigation.landed = None;
+            entry
+        };
+        self.restore_location(&entry).await?;
+        Ok(Some(entry))
+    }
+
+    /// Files focused most recently first, open or not
+    pub async fn recent_files(&self) -> Vec<PathBuf> {
+        self.navigation.read().await.recent_files.clone()
+    }
+
+    /// Activate the entry's document and put its cursor back, clamped to the text
+    /// as it is now
+    async fn restore_location(&self, entry: &NavigationEntry) -> Result<(), EditorError> {
+        let document = self.find_document(&entry.document_id).await?;
+        *self.active_document.write().await = Some(document.clone());
+        let mut doc = document.write().await;
+        let (line, column) = {
+            let content = doc.content.read().await;
+            let line = entry.line.min(content.line_count().saturating_sub(1));
+            let text = content.line(line);
+            let mut column = entry.column.min(text.len());
+            while !text.is_char_boundary(column) {
+                column -= 1;
+            }
+            (line, column)
+        };
+        doc.cursor_line = line;
+        doc.cursor_column = column;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::AiEngine;
+    use crate::config::Configuration;
+
+    #[tokio::test]
+    async fn test_navigating_back_returns_to_earlier_documents() {
+        let dir = std::env::temp_dir().join(format!("super-ide-navigation-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["a.rs", "b.rs", "c.rs"] {
+            std::fs::write(dir.join(name), "fn f() {}\n".repeat(40)).unwrap();
+        }
+
+        let config = Configuration::default();
+        let ai_engine = Arc::new(AiEngine::new(crate::ai::AiConfig::from(&config)));
+        let editor = Editor::new(&config, ai_engine).await.unwrap();
+        let a = editor.open_file(dir.join("a.rs")).await.unwrap();
+        let b = editor.open_file(dir.join("b.rs")).await.unwrap();
+        // Opening at a line lands there; it is not a jump within the file
+        editor.set_cursor(&b, 30, 0).await.unwrap();
+        let c = editor.open_file(dir.join("c.rs")).await.unwrap();
+
+        let back = editor.navigate_back().await.unwrap().unwrap();
+        assert_eq!((back.document_id.as_str(), back.line), (b.as_str(), 30));
+        assert_eq!(editor.get_active_document().await.unwrap().read().await.id, b);
+        assert_eq!(editor.navigate_back().await.unwrap().unwrap().document_id, a);
+        assert!(editor.navigate_back().await.unwrap().is_none());
+
+        assert_eq!(editor.navigate_forward().await.unwrap().unwrap().document_id, b);
+        editor.set_cursor(&b, 2, 0).await.unwrap();
+        assert!(editor.navigate_forward().await.unwrap().is_none());
+        assert_eq!(editor.navigate_back().await.unwrap().unwrap().line, 30);
+
+        editor.close_document(&c).await.unwrap();
+        editor.activate_document(&a).await.unwrap();
+        let names: Vec<_> = editor.recent_files().await.iter().map(|path| path.file_name().unwrap().to_owned()).collect();
+        assert_eq!(names, ["a.rs", "b.rs", "c.rs"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
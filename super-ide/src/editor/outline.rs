@@ -0,0 +1,369 @@
+//! Document outlines
+//!
+//! An outline nests a document's symbols by the blocks that contain them:
+//! modules hold types and functions, types hold methods. For Rust, Python,
+//! JavaScript and TypeScript the symbols and their extents come from the
+//! tree-sitter syntax tree, so a signature spread over several lines, or a Rust
+//! `where` clause, still ends where the body does. The methods of a Rust `impl`
+//! block are listed under the type they are for when the file defines it. Go has
+//! no grammar here, so its symbols come from the symbol index's line patterns and
+//! each one's extent from the brace fold regions. Other languages, and files the
+//! parser cannot read, get a flat list of whatever looks like a definition.
+//!
+//! The background parser computes the outline along with the syntax tree, so
+//! reading it costs nothing once edits pause.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tree_sitter::{Node, Parser};
+
+use super::folding::{compute_fold_points, FoldStrategy};
+use super::symbols::{extract_symbols, SymbolKind};
+use super::{Editor, EditorError};
+use crate::utils::language_tools;
+
+static GENERIC_DEFINITION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\s*(?:(?:public|private|protected|internal|static|abstract|final|export|async)\s+)*(class|struct|interface|enum|module|def|function|func|fn|sub|proc)\s+([A-Za-z_][A-Za-z0-9_]*)").unwrap()
+});
+
+/// A symbol of a document and the symbols nested in it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutlineItem {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// 1-based, inclusive
+    pub start_line: usize,
+    pub end_line: usize,
+    pub children: Vec<OutlineItem>,
+}
+
+/// Outline of `content`, the text of the file at `path`
+pub fn compute_outline(path: &Path, content: &str) -> Vec<OutlineItem> {
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    let tree = language_tools::grammar(extension).and_then(|grammar| {
+        let mut parser = Parser::new();
+        parser.set_language(grammar).ok()?;
+        parser.parse(content, None)
+    });
+    match tree {
+        Some(tree) => merge_impls(outline_items(tree.root_node(), content.as_bytes(), None)),
+        None if extension == "go" => fold_outline(path, content),
+        None => flat_outline(content),
+    }
+}
+
+/// Outline from the symbol index's line patterns, each symbol ending where the
+/// widest brace fold starting on its line does
+fn fold_outline(path: &Path, content: &str) -> Vec<OutlineItem> {
+    let folds = compute_fold_points(content, FoldStrategy::Braces);
+    let items = extract_symbols(&path.to_string_lossy(), content)
+        .into_iter()
+        .map(|symbol| {
+            let end_line = folds.iter()
+                .filter(|fold| fold.start_line + 1 == symbol.line)
+                .map(|fold| fold.end_line + 1)
+                .max()
+                .unwrap_or(symbol.line);
+            OutlineItem {
+                name: symbol.name,
+                kind: symbol.kind,
+                start_line: symbol.line,
+                end_line,
+                children: Vec::new(),
+            }
+        })
+        .collect();
+    nest(items)
+}
+
+/// Nest `items`, ordered by first line, inside the items whose lines contain them
+fn nest(items: Vec<OutlineItem>) -> Vec<OutlineItem> {
+    let mut roots = Vec::new();
+    // Items still open to children, innermost last
+    let mut open: Vec<OutlineItem> = Vec::new();
+    for mut item in items {
+        while open.last().is_some_and(|parent| parent.end_line < item.start_line) {
+            close(&mut open, &mut roots);
+        }
+        if let Some(parent) = open.last() {
+            if item.kind == SymbolKind::Function && is_type(parent.kind) {
+                item.kind = SymbolKind::Method;
+            }
+        }
+        open.push(item);
+    }
+    while !open.is_empty() {
+        close(&mut open, &mut roots);
+    }
+    roots
+}
+
+/// Pop the innermost open item into its parent, or the roots
+fn close(open: &mut Vec<OutlineItem>, roots: &mut Vec<OutlineItem>) {
+    if let Some(item) = open.pop() {
+        match open.last_mut() {
+            Some(parent) => parent.children.push(item),
+            None => roots.push(item),
+        }
+    }
+}
+
+/// Kind of the symbol a syntax node defines, if it defines one
+fn node_kind(node: &Node) -> Option<SymbolKind> {
+    Some(match node.kind() {
+        "mod_item" | "internal_module" | "module" => SymbolKind::Module,
+        "struct_item" | "union_item" => SymbolKind::Struct,
+        "enum_item" | "enum_declaration" => SymbolKind::Enum,
+        "trait_item" => SymbolKind::Trait,
+        "impl_item" => SymbolKind::Impl,
+        "type_item" | "type_alias_declaration" => SymbolKind::Type,
+        "const_item" | "static_item" => SymbolKind::Constant,
+        "macro_definition" => SymbolKind::Macro,
+        "class_definition" | "class_declaration" | "abstract_class_declaration" => SymbolKind::Class,
+        "interface_declaration" => SymbolKind::Interface,
+        "method_definition" => SymbolKind::Method,
+        "function_item" | "function_signature_item" | "function_definition" | "function_declaration"
+        | "generator_function_declaration" => SymbolKind::Function,
+        // `const handler = () => {}` and the like
+        "variable_declarator" => match node.child_by_field_name("value")?.kind() {
+            "arrow_function" | "function" | "function_expression" | "generator_function" => SymbolKind::Function,
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
+
+/// Name of the symbol `node` defines; an `impl` block is named by its type,
+/// without path or generic arguments
+fn node_name(node: &Node, kind: SymbolKind, source: &[u8]) -> Option<String> {
+    let field = if kind == SymbolKind::Impl { "type" } else { "name" };
+    let text = node.child_by_field_name(field)?.utf8_text(source).ok()?;
+    let text = if kind == SymbolKind::Impl {
+        let base = text.split('<').next().unwrap_or(text);
+        base.rsplit("::").next().unwrap_or(base)
+    } else {
+        text
+    };
+    Some(text.trim().to_string())
+}
+
+/// The symbols defined under `node`, each holding those defined inside it.
+/// `parent` is the kind of the innermost enclosing symbol.
+fn outline_items(node: Node, source: &[u8], parent: Option<SymbolKind>) -> Vec<OutlineItem> {
+    let mut items = Vec::new();
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        let symbol = node_kind(&child)
+            .and_then(|kind| node_name(&child, kind, source).map(|name| (kind, name)))
+            .filter(|(_, name)| !name.is_empty());
+        let Some((mut kind, name)) = symbol else {
+            items.extend(outline_items(child, source, parent));
+            continue;
+        };
+        if kind == SymbolKind::Function && parent.is_some_and(is_type) {
+            kind = SymbolKind::Method;
+        }
+        items.push(OutlineItem {
+            name,
+            kind,
+            start_line: child.start_position().row + 1,
+            end_line: child.end_position().row + 1,
+            children: outline_items(child, source, Some(kind)),
+        });
+    }
+    items
+}
+
+fn is_type(kind: SymbolKind) -> bool {
+    matches!(
+        kind,
+        SymbolKind::Struct | SymbolKind::Enum | SymbolKind::Trait | SymbolKind::Class | SymbolKind::Interface | SymbolKind::Impl
+    )
+}
+
+/// Move the children of each `impl` block into the type of the same name at the
+/// same level, dropping the block; blocks for types defined elsewhere stay
+fn merge_impls(items: Vec<OutlineItem>) -> Vec<OutlineItem> {
+    let mut merged: Vec<OutlineItem> = Vec::with_capacity(items.len());
+    let mut impls = Vec::new();
+    for mut item in items {
+        item.children = merge_impls(item.children);
+        if item.kind == SymbolKind::Impl {
+            impls.push(item);
+        } else {
+            merged.push(item);
+        }
+    }
+    for block in impls {
+        let target = merged.iter_mut()
+            .find(|item| item.name == block.name && matches!(item.kind, SymbolKind::Struct | SymbolKind::Enum | SymbolKind::Type));
+        match target {
+            Some(target) => target.children.extend(block.children),
+            None => merged.push(block),
+        }
+    }
+    merged.sort_by_key(|item| item.start_line);
+    merged
+}
+
+/// One item per line that looks like a definition, for languages without a grammar
+fn flat_outline(content: &str) -> Vec<OutlineItem> {
+    content.lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let caps = GENERIC_DEFINITION.captures(line)?;
+            let kind = match &caps[1] {
+                "class" => SymbolKind::Class,
+                "struct" => SymbolKind::Struct,
+                "interface" => SymbolKind::Interface,
+                "enum" => SymbolKind::Enum,
+                "module" => SymbolKind::Module,
+                _ => SymbolKind::Function,
+            };
+            Some(OutlineItem {
+                name: caps[2].to_string(),
+                kind,
+                start_line: index + 1,
+                end_line: index + 1,
+                children: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+impl Editor {
+    /// Outline of a document as of its latest background parse, or of its current
+    /// text when it has not been parsed yet
+    pub async fn document_outline(&self, document_id: &str) -> Result<Vec<OutlineItem>, EditorError> {
+        let document = self.find_document(document_id).await?;
+        let doc = document.read().await;
+        if let Some(outline) = &doc.outline {
+            return Ok(outline.clone());
+        }
+        let content = doc.content.read().await.to_string();
+        Ok(compute_outline(&doc.path, &content))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape(items: &[OutlineItem]) -> Vec<(String, SymbolKind, usize, usize, usize)> {
+        items.iter()
+            .map(|item| (item.name.clone(), item.kind, item.start_line, item.end_line, item.children.len()))
+            .collect()
+    }
+
+    #[test]
+    fn test_struct_with_two_methods_nests_them() {
+        let code = "\
+mod shapes {
+    pub struct Circle {
+        radius: f64,
+    }
+
+    impl Circle {
+        pub fn new(radius: f64) -> Self {
+            Self { radius }
+        }
+
+        pub fn area(&self) -> f64 {
+            3.14 * self.radius * self.radius
+        }
+    }
+}
+
+fn main() {}
+";
+        let outline = compute_outline(Path::new("src/shapes.rs"), code);
+        assert_eq!(shape(&outline), [
+            ("shapes".to_string(), SymbolKind::Module, 1, 15, 1),
+            ("main".to_string(), SymbolKind::Function, 17, 17, 0),
+        ]);
+        let circle = &outline[0].children;
+        assert_eq!(shape(circle), [("Circle".to_string(), SymbolKind::Struct, 2, 4, 2)]);
+        assert_eq!(shape(&circle[0].children), [
+            ("new".to_string(), SymbolKind::Method, 7, 9, 0),
+            ("area".to_string(), SymbolKind::Method, 11, 13, 0),
+        ]);
+
+        let python = "class Circle:\n    def __init__(self, radius):\n        self.radius = radius\n\n    def area(self):\n        return 3.14 * self.radius ** 2\n";
+        let outline = compute_outline(Path::new("shapes.py"), python);
+        assert_eq!(shape(&outline), [("Circle".to_string(), SymbolKind::Class, 1, 6, 2)]);
+        assert_eq!(shape(&outline[0].children), [
+            ("__init__".to_string(), SymbolKind::Method, 2, 3, 0),
+            ("area".to_string(), SymbolKind::Method, 5, 6, 0),
+        ]);
+    }
+
+    #[test]
+    fn test_multi_line_signatures_end_with_their_body() {
+        let rust = "\
+pub fn merge<K, V>(
+    left: HashMap<K, V>,
+    right: HashMap<K, V>,
+) -> HashMap<K, V>
+where
+    K: Eq + Hash,
+{
+    left.into_iter().chain(right).collect()
+}
+";
+        let outline = compute_outline(Path::new("merge.rs"), rust);
+        assert_eq!(shape(&outline), [("merge".to_string(), SymbolKind::Function, 1, 9, 0)]);
+
+        let typescript = "\
+export class Circle {
+  area(
+    precision: number,
+  ): number {
+    return 3.14;
+  }
+}
+const handler = (event: Event) => {
+  console.log(event);
+};
+";
+        let outline = compute_outline(Path::new("shapes.ts"), typescript);
+        assert_eq!(shape(&outline), [
+            ("Circle".to_string(), SymbolKind::Class, 1, 7, 1),
+            ("handler".to_string(), SymbolKind::Function, 8, 10, 0),
+        ]);
+        assert_eq!(shape(&outline[0].children), [("area".to_string(), SymbolKind::Method, 2, 6, 0)]);
+    }
+
+    #[test]
+    fn test_go_symbols_span_their_bodies() {
+        let go = "\
+type Circle struct {
+	Radius float64
+}
+
+func (c *Circle) Area() float64 {
+	return 3.14 * c.Radius * c.Radius
+}
+
+func main() {}
+";
+        let outline = compute_outline(Path::new("shapes.go"), go);
+        assert_eq!(shape(&outline), [
+            ("Circle".to_string(), SymbolKind::Struct, 1, 3, 0),
+            ("Area".to_string(), SymbolKind::Function, 5, 7, 0),
+            ("main".to_string(), SymbolKind::Function, 9, 9, 0),
+        ]);
+    }
+
+    #[test]
+    fn test_language_without_a_grammar_gets_a_flat_outline() {
+        let ruby = "module Shapes\n  class Circle\n    def area\n      3.14\n    end\n  end\nend\n";
+        let outline = compute_outline(Path::new("shapes.rb"), ruby);
+        assert_eq!(shape(&outline), [
+            ("Shapes".to_string(), SymbolKind::Module, 1, 1, 0),
+            ("Circle".to_string(), SymbolKind::Class, 2, 2, 0),
+            ("area".to_string(), SymbolKind::Function, 3, 3, 0),
+        ]);
+    }
+}
@@ -0,0 +1,147 @@
+//! Read-only ways of opening a file
+//!
+//! Logs, generated files and binaries are for looking at, not editing. A preview
+//! loads the text as a document that rejects edits and saves; it skips the size
+//! limit and syntax parsing, since there is no editing state to keep, and covers
+//! the first [`MAX_TEXT_PREVIEW_BYTES`] of the file. Binary files open as a hex
+//! dump of their first [`MAX_HEX_PREVIEW_BYTES`], one row per [`HEX_ROW_BYTES`]
+//! bytes, whichever mode was asked for.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+
+/// Bytes shown per hex row
+pub const HEX_ROW_BYTES: usize = 16;
+
+/// Bytes of a file a hex preview covers
+pub const MAX_HEX_PREVIEW_BYTES: usize = 1024 * 1024;
+
+/// Bytes of a file a text preview covers
+pub const MAX_TEXT_PREVIEW_BYTES: usize = 4 * 1024 * 1024;
+
+/// Bytes looked at to tell a binary file from text
+const BINARY_SNIFF_BYTES: usize = 8192;
+
+/// How a document is opened
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OpenMode {
+    #[default]
+    Edit,
+    /// The text, read-only
+    Preview,
+    /// A read-only hex dump of the bytes
+    Hex,
+}
+
+impl OpenMode {
+    pub fn is_read_only(self) -> bool {
+        self != Self::Edit
+    }
+}
+
+/// One row of a hex dump
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HexRow {
+    pub offset: usize,
+    /// Bytes as space-separated pairs of hex digits
+    pub hex: String,
+    /// Printable ASCII bytes as themselves, anything else as `.`
+    pub ascii: String,
+}
+
+impl HexRow {
+    /// The row as `hexdump -C` prints it, which is what a hex document's text is made of
+    pub fn to_line(&self) -> String {
+        format!("{:08x}  {:<width$}  |{}|", self.offset, self.hex, self.ascii, width = HEX_ROW_BYTES * 3 - 1)
+    }
+}
+
+/// Rows of a hex dump of `bytes`
+pub fn hex_rows(bytes: &[u8]) -> Vec<HexRow> {
+    bytes.chunks(HEX_ROW_BYTES)
+        .enumerate()
+        .map(|(row, chunk)| HexRow {
+            offset: row * HEX_ROW_BYTES,
+            hex: chunk.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(" "),
+            ascii: chunk.iter()
+                .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Whether `bytes` look like a binary file: a NUL in the first 8 KiB, as git decides,
+/// unless those bytes are UTF-16 text
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let sniffed = &bytes[..bytes.len().min(BINARY_SNIFF_BYTES)];
+    sniffed.contains(&0) && !crate::file_ops::encoding::is_utf16(sniffed)
+}
+
+/// At most the first `limit` bytes of the file at `path`
+pub async fn read_prefix(path: &Path, limit: usize) -> std::io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    tokio::fs::File::open(path).await?
+        .take(limit as u64)
+        .read_to_end(&mut bytes)
+        .await?;
+    Ok(bytes)
+}
+
+/// Whether the file at `path` looks binary
+pub async fn is_binary_file(path: &Path) -> std::io::Result<bool> {
+    Ok(is_binary(&read_prefix(path, BINARY_SNIFF_BYTES).await?))
+}
+
+/// Drop a UTF-8 sequence cut off by reading only a prefix of a file, so the
+/// prefix still decodes as UTF-8
+pub fn trim_partial_utf8(bytes: &mut Vec<u8>) {
+    if let Err(e) = std::str::from_utf8(bytes) {
+        if e.error_len().is_none() {
+            bytes.truncate(e.valid_up_to());
+        }
+    }
+}
+
+/// Hex rows of the start of the file at `path`
+pub async fn read_hex_rows(path: &Path) -> std::io::Result<Vec<HexRow>> {
+    Ok(hex_rows(&read_prefix(path, MAX_HEX_PREVIEW_BYTES).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_rows_split_and_escape_bytes() {
+        let mut bytes = b"PNG\r\n\x1a\n\0".to_vec();
+        bytes.extend(0u8..12);
+        let rows = hex_rows(&bytes);
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].hex, "50 4e 47 0d 0a 1a 0a 00 00 01 02 03 04 05 06 07");
+        assert_eq!(rows[0].ascii, "PNG.............");
+        assert_eq!(rows[1], HexRow { offset: 16, hex: "08 09 0a 0b".to_string(), ascii: "....".to_string() });
+        assert_eq!(
+            rows[1].to_line(),
+            "00000010  08 09 0a 0b                                      |....|"
+        );
+        assert!(is_binary(&bytes));
+        assert!(!is_binary(b"plain text\n"));
+        let utf16le: Vec<u8> = "plain text\n".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert!(!is_binary(&utf16le));
+    }
+
+    #[test]
+    fn test_cut_utf8_sequence_is_trimmed() {
+        let mut bytes = "naïve".as_bytes()[..3].to_vec();
+        trim_partial_utf8(&mut bytes);
+        assert_eq!(bytes, b"na");
+
+        // Invalid bytes elsewhere are left for the decoder's fallback
+        let mut latin1 = b"caf\xe9 au lait".to_vec();
+        trim_partial_utf8(&mut latin1);
+        assert_eq!(latin1.len(), 12);
+    }
+}
@@ -0,0 +1,385 @@
+//! Project-wide rename
+//!
+//! This is a textual rename, not a semantic one: every identifier token spelled
+//! like the old name is renamed in source files of the languages that define it.
+//! A lexer per language family keeps strings and comments out of it and matches
+//! whole identifiers only, so `parse` does not touch `parse_args`. It cannot tell
+//! two different symbols with the same name apart, so the plan is always shown
+//! as a diff and only written once confirmed.
+
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+use super::symbols::SymbolIndex;
+use crate::utils::diff::unified_diff;
+
+/// Rename errors
+#[derive(Debug, thiserror::Error)]
+pub enum RenameError {
+    #[error("Invalid identifier: {0}")]
+    InvalidName(String),
+
+    #[error("No definition of {0} in the symbol index")]
+    SymbolNotFound(String),
+
+    #[error("Files changed since the rename was previewed: {}", .0.join(", "))]
+    Stale(Vec<String>),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// The rename of one file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileRename {
+    /// Workspace-relative path
+    pub file: String,
+    /// 1-based line of each renamed occurrence
+    pub lines: Vec<usize>,
+    pub diff: String,
+    #[serde(skip)]
+    original: String,
+    #[serde(skip)]
+    renamed: String,
+}
+
+/// Every change a rename makes, for preview before it is applied
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenamePlan {
+    pub old_name: String,
+    pub new_name: String,
+    pub files: Vec<FileRename>,
+    /// Identifies this exact plan; a confirmation must send it back
+    pub fingerprint: String,
+}
+
+impl RenamePlan {
+    /// Number of renamed occurrences across all files
+    pub fn occurrence_count(&self) -> usize {
+        self.files.iter().map(|file| file.lines.len()).sum()
+    }
+
+    /// Write the renamed files under `root`, refusing if any of them changed since
+    /// the plan was made. Returns the files written.
+    pub async fn apply(&self, root: &Path) -> Result<Vec<String>, RenameError> {
+        let mut stale = Vec::new();
+        for file in &self.files {
+            let current = tokio::fs::read_to_string(root.join(&file.file)).await?;
+            if current != file.original {
+                stale.push(file.file.clone());
+            }
+        }
+        if !stale.is_empty() {
+            return Err(RenameError::Stale(stale));
+        }
+
+        for file in &self.files {
+            crate::file_ops::atomic::write_atomic(&root.join(&file.file), file.renamed.as_bytes(), false).await?;
+        }
+        Ok(self.files.iter().map(|file| file.file.clone()).collect())
+    }
+}
+
+/// Languages whose files can refer to each other's symbols
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Family {
+    Rust,
+    Python,
+    JavaScript,
+    Go,
+}
+
+impl Family {
+    fn of(file: &str) -> Option<Self> {
+        match Path::new(file).extension().and_then(|ext| ext.to_str())? {
+            "rs" => Some(Self::Rust),
+            "py" => Some(Self::Python),
+            "js" | "jsx" | "ts" | "tsx" | "mjs" => Some(Self::JavaScript),
+            "go" => Some(Self::Go),
+            _ => None,
+        }
+    }
+
+    fn is_identifier_start(self, c: char) -> bool {
+        c.is_alphabetic() || c == '_' || (c == '$' && self == Self::JavaScript)
+    }
+
+    fn is_identifier_char(self, c: char) -> bool {
+        c.is_alphanumeric() || c == '_' || (c == '$' && self == Self::JavaScript)
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Byte ranges of identifier tokens in `content` outside strings and comments
+fn identifier_spans(content: &str, family: Family) -> Vec<(usize, usize)> {
+    let chars: Vec<char> = content.chars().collect();
+    let offsets: Vec<usize> = content.char_indices().map(|(offset, _)| offset).chain([content.len()]).collect();
+    let at = |i: usize, text: &str| text.chars().enumerate().all(|(k, c)| chars.get(i + k) == Some(&c));
+    // Index just past the next unescaped `close`, or the end of the text
+    let skip_quoted = |mut i: usize, close: char| {
+        while i < chars.len() {
+            match chars[i] {
+                '\\' => i += 2,
+                c if c == close => return i + 1,
+                _ => i += 1,
+            }
+        }
+        chars.len()
+    };
+    let skip_until = |mut i: usize, close: &str| {
+        while i < chars.len() && !at(i, close) {
+            i += 1;
+        }
+        (i + close.chars().count()).min(chars.len())
+    };
+
+    let mut spans = Vec::new();
+    // Brace depth of each `${` whose template literal resumes at its closing brace
+    let mut templates: Vec<usize> = Vec::new();
+    let mut braces = 0usize;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if family.is_identifier_start(c) {
+            let start = i;
+            while i < chars.len() && family.is_identifier_char(chars[i]) {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            if family == Family::Rust && matches!(word.as_str(), "r" | "br") && matches!(chars.get(i), Some('"' | '#')) {
+                // Raw string: r#"..."#
+                let hashes = chars[i..].iter().take_while(|c| **c == '#').count();
+                if chars.get(i + hashes) == Some(&'"') {
+                    let close = format!("\"{}", "#".repeat(hashes));
+                    i = skip_until(i + hashes + 1, &close);
+                    continue;
+                }
+            }
+            spans.push((offsets[start], offsets[i]));
+            continue;
+        }
+        if c.is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+                i += 1;
+            }
+            continue;
+        }
+
+        i = match (family, c) {
+            (Family::Python, '#') => skip_until(i, "\n"),
+            (Family::Python, '"' | '\'') if at(i, "\"\"\"") || at(i, "'''") => {
+                let quotes: String = chars[i..i + 3].iter().collect();
+                skip_until(i + 3, &quotes)
+            }
+            (Family::Python, '"' | '\'') => skip_quoted(i + 1, c),
+            (_, '/') if at(i, "//") => skip_until(i, "\n"),
+            (Family::Rust, '/') if at(i, "/*") => {
+                // Rust block comments nest
+                let mut depth = 0;
+                while i < chars.len() {
+                    if at(i, "/*") {
+                        depth += 1;
+                        i += 2;
+                    } else if at(i, "*/") {
+                        depth -= 1;
+                        i += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else {
+                        i += 1;
+                    }
+                }
+                i
+            }
+            (_, '/') if at(i, "/*") => skip_until(i + 2, "*/"),
+            (_, '"') => skip_quoted(i + 1, '"'),
+            (Family::Rust, '\'') => {
+                // A char literal, or the quote of a lifetime such as 'a
+                if chars.get(i + 1) == Some(&'\\') {
+                    skip_quoted(i + 1, '\'')
+                } else if chars.get(i + 2) == Some(&'\'') {
+                    i + 3
+                } else {
+                    i + 1
+                }
+            }
+            (_, '\'') => skip_quoted(i + 1, '\''),
+            (Family::Go, '`') => skip_until(i + 1, "`"),
+            (Family::JavaScript, '`') => skip_template(&chars, i + 1, &mut templates, braces),
+            (Family::JavaScript, '{') => {
+                braces += 1;
+                i + 1
+            }
+            (Family::JavaScript, '}') if templates.last() == Some(&braces) => {
+                templates.pop();
+                skip_template(&chars, i + 1, &mut templates, braces)
+            }
+            (Family::JavaScript, '}') => {
+                braces = braces.saturating_sub(1);
+                i + 1
+            }
+            _ => i + 1,
+        };
+    }
+    spans
+}
+
+/// Skip the text of a template literal from `i`, up to its closing backtick or
+/// into a `${` substitution, which is lexed as code until its closing brace
+fn skip_template(chars: &[char], mut i: usize, templates: &mut Vec<usize>, braces: usize) -> usize {
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '`' => return i + 1,
+            '$' if chars.get(i + 1) == Some(&'{') => {
+                templates.push(braces);
+                return i + 2;
+            }
+            _ => i += 1,
+        }
+    }
+    chars.len()
+}
+
+/// `content` with every identifier token `old_name` renamed, and the 1-based line
+/// of each occurrence
+fn rename_in(content: &str, family: Family, old_name: &str, new_name: &str) -> (String, Vec<usize>) {
+    let mut renamed = String::with_capacity(content.len());
+    let mut lines = Vec::new();
+    let mut copied = 0;
+    for (start, end) in identifier_spans(content, family) {
+        if &content[start..end] != old_name {
+            continue;
+        }
+        renamed.push_str(&content[copied..start]);
+        renamed.push_str(new_name);
+        copied = end;
+        lines.push(content[..start].matches('\n').count() + 1);
+    }
+    renamed.push_str(&content[copied..]);
+    (renamed, lines)
+}
+
+impl SymbolIndex {
+    /// Plan renaming `old_name` to `new_name` in every indexed file of a language
+    /// that defines it. Nothing is written; see `RenamePlan::apply`.
+    pub async fn rename_symbol_project(&self, old_name: &str, new_name: &str) -> Result<RenamePlan, RenameError> {
+        for name in [old_name, new_name] {
+            if !is_identifier(name) {
+                return Err(RenameError::InvalidName(name.to_string()));
+            }
+        }
+        let definitions = self.definitions(old_name).await;
+        if definitions.is_empty() {
+            return Err(RenameError::SymbolNotFound(old_name.to_string()));
+        }
+        let families: Vec<Family> = definitions.iter().filter_map(|symbol| Family::of(&symbol.file)).collect();
+
+        let mut hasher = DefaultHasher::new();
+        (old_name, new_name).hash(&mut hasher);
+        let mut files = Vec::new();
+        for file in self.file_names().await {
+            let Some(family) = Family::of(&file).filter(|family| families.contains(family)) else {
+                continue;
+            };
+            let Ok(original) = tokio::fs::read_to_string(self.root().join(&file)).await else {
+                continue;
+            };
+            let (renamed, lines) = rename_in(&original, family, old_name, new_name);
+            if lines.is_empty() {
+                continue;
+            }
+            (&file, &original).hash(&mut hasher);
+            files.push(FileRename {
+                diff: unified_diff(&original, &renamed, &format!("a/{}", file), &format!("b/{}", file)),
+                file,
+                lines,
+                original,
+                renamed,
+            });
+        }
+
+        Ok(RenamePlan {
+            old_name: old_name.to_string(),
+            new_name: new_name.to_string(),
+            files,
+            fingerprint: format!("{:016x}", hasher.finish()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rename_function_referenced_in_two_files() {
+        let root = std::env::temp_dir().join(format!("super-ide-rename-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/config.rs"), concat!(
+            "/// Call load_config once at startup\n",
+            "pub fn load_config(path: &str) -> String {\n",
+            "    format!(\"load_config: {}\", path)\n",
+            "}\n",
+        )).unwrap();
+        std::fs::write(root.join("src/main.rs"), concat!(
+            "fn main() {\n",
+            "    let config = config::load_config(\"app.toml\");\n",
+            "    let load_config_count = 1; // not load_config\n",
+            "    println!(\"{}\", config::load_config(r#\"load_config\"#));\n",
+            "}\n",
+        )).unwrap();
+        std::fs::write(root.join("tool.py"), "load_config = None\n").unwrap();
+
+        let index = SymbolIndex::new(root.clone());
+        index.build().await;
+        let plan = index.rename_symbol_project("load_config", "read_settings").await.unwrap();
+        // Python defines no load_config, so its variable of that name is left alone
+        let files: Vec<(&str, &[usize])> = plan.files.iter().map(|file| (file.file.as_str(), file.lines.as_slice())).collect();
+        assert_eq!(files, vec![("src/config.rs", &[2][..]), ("src/main.rs", &[2, 4][..])]);
+        assert_eq!(plan.occurrence_count(), 3);
+        assert!(plan.files[0].diff.contains("+pub fn read_settings(path: &str) -> String {"));
+
+        // A dry run writes nothing
+        assert!(std::fs::read_to_string(root.join("src/main.rs")).unwrap().contains("config::load_config(\"app.toml\")"));
+
+        assert_eq!(plan.apply(&root).await.unwrap(), vec!["src/config.rs", "src/main.rs"]);
+        assert_eq!(std::fs::read_to_string(root.join("src/main.rs")).unwrap(), concat!(
+            "fn main() {\n",
+            "    let config = config::read_settings(\"app.toml\");\n",
+            "    let load_config_count = 1; // not load_config\n",
+            "    println!(\"{}\", config::read_settings(r#\"load_config\"#));\n",
+            "}\n",
+        ));
+        assert!(std::fs::read_to_string(root.join("src/config.rs")).unwrap().contains("/// Call load_config once"));
+        assert_eq!(std::fs::read_to_string(root.join("tool.py")).unwrap(), "load_config = None\n");
+
+        // Applying a plan whose files changed since is refused
+        assert!(matches!(plan.apply(&root).await, Err(RenameError::Stale(files)) if files.len() == 2));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_strings_and_comments_are_skipped_per_language() {
+        let js = "const a = `${run(x)} run`; // run\nrun('run'); /* run */ runner.run();";
+        let names: Vec<&str> = identifier_spans(js, Family::JavaScript).into_iter().map(|(s, e)| &js[s..e]).collect();
+        assert_eq!(names, vec!["const", "a", "run", "x", "run", "runner", "run"]);
+
+        let python = "# run\ndef run():\n    '''run'''\n    return \"run\" + run_all\n";
+        let (renamed, lines) = rename_in(python, Family::Python, "run", "go");
+        assert_eq!(renamed, "# run\ndef go():\n    '''run'''\n    return \"run\" + run_all\n");
+        assert_eq!(lines, vec![2]);
+
+        let rust = "fn f<'a>(c: char) -> bool { c == 'x' || c == '\\'' /* f /* f */ f */ }";
+        let names: Vec<&str> = identifier_spans(rust, Family::Rust).into_iter().map(|(s, e)| &rust[s..e]).collect();
+        assert_eq!(names, vec!["fn", "f", "a", "c", "char", "bool", "c", "c"]);
+    }
+}
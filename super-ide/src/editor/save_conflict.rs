@@ -0,0 +1,80 @@
+//! Saving over external changes
+//!
+//! A document keeps a hash of its file's bytes as of when it was opened or last
+//! saved. Before writing, a save hashes the file again: a different hash, or a file
+//! that has gone, means something else changed it since, and the configured
+//! [`SaveConflictStrategy`] decides whether the save overwrites it, skips it or
+//! reports the conflict. Autosave goes through the same check for every modified
+//! document, so a timer never silently discards what another program wrote.
+
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use super::{Editor, EditorError};
+use crate::config::SaveConflictStrategy;
+
+/// What a save did
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SaveOutcome {
+    Saved,
+    /// Nothing was written: there was no document to save, or the file changed on
+    /// disk and the strategy was [`SaveConflictStrategy::Skip`]
+    Skipped,
+}
+
+/// What autosave did with one modified document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AutosaveEntry {
+    pub document_id: String,
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub status: AutosaveStatus,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum AutosaveStatus {
+    Saved,
+    Skipped,
+    /// The file changed on disk and the strategy is [`SaveConflictStrategy::Prompt`]
+    Conflict,
+    Failed { error: String },
+}
+
+/// Hash of the bytes of the file at `path`, or `None` when it cannot be read
+pub(super) async fn disk_hash(path: &Path) -> Option<u64> {
+    let bytes = tokio::fs::read(path).await.ok()?;
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+impl Editor {
+    /// Save every modified, editable document, settling conflicts with the
+    /// configured strategy. A conflict or failure on one document does not stop
+    /// the others from being saved.
+    pub async fn autosave(&self) -> Vec<AutosaveEntry> {
+        let strategy = self.config.read().await.editor.save_conflict_strategy;
+        let mut entries = Vec::new();
+        for document in self.get_documents().await {
+            let (document_id, path) = {
+                let doc = document.read().await;
+                if !doc.is_modified || doc.mode.is_read_only() || doc.path.as_os_str().is_empty() {
+                    continue;
+                }
+                (doc.id.clone(), doc.path.clone())
+            };
+            let status = match self.save_document(&document, strategy).await {
+                Ok(SaveOutcome::Saved) => AutosaveStatus::Saved,
+                Ok(SaveOutcome::Skipped) => AutosaveStatus::Skipped,
+                Err(EditorError::SaveConflict(_)) => AutosaveStatus::Conflict,
+                Err(e) => AutosaveStatus::Failed { error: e.to_string() },
+            };
+            entries.push(AutosaveEntry { document_id, path, status });
+        }
+        entries
+    }
+}
@@ -0,0 +1,323 @@
+//! Project-wide symbol index for go-to-symbol and go-to-definition
+//!
+//! Symbols are extracted per file with line-oriented patterns for each
+//! supported language. The index is keyed by workspace-relative path, so a
+//! file change only re-extracts that one file.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::sync::RwLock;
+
+use crate::utils::event_bus::FileIndex;
+
+/// Files larger than this are not indexed
+const MAX_INDEXED_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Kind of an indexed symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolKind {
+    Function,
+    Struct,
+    Enum,
+    Trait,
+    Class,
+    Interface,
+    Type,
+    Module,
+    Constant,
+    Macro,
+    /// A function inside a type; only outlines tell methods apart
+    Method,
+    /// A Rust `impl` block, in outlines
+    Impl,
+}
+
+/// A symbol definition and where it lives
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Workspace-relative path
+    pub file: String,
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column of the symbol name
+    pub column: usize,
+}
+
+static RUST_SYMBOL: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:async\s+)?(?:const\s+)?(?:unsafe\s+)?(?:extern\s+"[^"]*"\s+)?(fn|struct|enum|trait|type|mod|const|static|macro_rules!)\s+([A-Za-z_][A-Za-z0-9_]*)"#).unwrap()
+});
+static PYTHON_SYMBOL: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\s*(?:async\s+)?(def|class)\s+([A-Za-z_][A-Za-z0-9_]*)").unwrap()
+});
+static JS_SYMBOL: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\s*(?:export\s+)?(?:default\s+)?(?:async\s+)?(function\*?|class|interface|type|enum)\s+([A-Za-z_$][A-Za-z0-9_$]*)").unwrap()
+});
+static JS_ARROW: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\s*(?:export\s+)?(?:const|let)\s+([A-Za-z_$][A-Za-z0-9_$]*)\s*=\s*(?:async\s*)?(?:\([^)]*\)|[A-Za-z_$][A-Za-z0-9_$]*)\s*=>").unwrap()
+});
+static GO_SYMBOL: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(func)\s+(?:\([^)]*\)\s*)?([A-Za-z_][A-Za-z0-9_]*)|^type\s+([A-Za-z_][A-Za-z0-9_]*)\s+(struct|interface)").unwrap()
+});
+
+fn kind_from_keyword(keyword: &str) -> SymbolKind {
+    match keyword {
+        "fn" | "def" | "func" | "function" | "function*" => SymbolKind::Function,
+        "struct" => SymbolKind::Struct,
+        "enum" => SymbolKind::Enum,
+        "trait" => SymbolKind::Trait,
+        "class" => SymbolKind::Class,
+        "interface" => SymbolKind::Interface,
+        "type" => SymbolKind::Type,
+        "mod" => SymbolKind::Module,
+        "macro_rules!" => SymbolKind::Macro,
+        _ => SymbolKind::Constant,
+    }
+}
+
+/// Extract symbol definitions from a file's content, choosing patterns by extension
+pub fn extract_symbols(file: &str, content: &str) -> Vec<Symbol> {
+    let extension = Path::new(file).extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    let mut symbols = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let found = match extension {
+            "rs" => RUST_SYMBOL.captures(line).map(|caps| (caps.get(2), kind_from_keyword(&caps[1]))),
+            "py" => PYTHON_SYMBOL.captures(line).map(|caps| (caps.get(2), kind_from_keyword(&caps[1]))),
+            "js" | "jsx" | "ts" | "tsx" | "mjs" => JS_SYMBOL.captures(line)
+                .map(|caps| (caps.get(2), kind_from_keyword(&caps[1])))
+                .or_else(|| JS_ARROW.captures(line).map(|caps| (caps.get(1), SymbolKind::Function))),
+            "go" => GO_SYMBOL.captures(line).map(|caps| match caps.get(2) {
+                Some(name) => (Some(name), SymbolKind::Function),
+                None => (caps.get(3), kind_from_keyword(&caps[4])),
+            }),
+            _ => None,
+        };
+
+        if let Some((Some(name), kind)) = found {
+            symbols.push(Symbol {
+                name: name.as_str().to_string(),
+                kind,
+                file: file.to_string(),
+                line: index + 1,
+                column: name.start() + 1,
+            });
+        }
+    }
+
+    symbols
+}
+
+/// Fuzzy match score of `query` against `name`; `None` when the query is not a subsequence
+fn fuzzy_score(query: &str, name: &str) -> Option<i64> {
+    let query = query.to_lowercase();
+    let lower = name.to_lowercase();
+
+    if lower == query {
+        return Some(1000);
+    }
+    if lower.starts_with(&query) {
+        return Some(800 - lower.len() as i64);
+    }
+    if lower.contains(&query) {
+        return Some(600 - lower.len() as i64);
+    }
+
+    // Subsequence match, penalizing gaps between matched characters
+    let mut score = 400;
+    let mut last_match: Option<usize> = None;
+    let mut chars = lower.char_indices();
+    for q in query.chars() {
+        let (position, _) = chars.by_ref().find(|(_, c)| *c == q)?;
+        if let Some(last) = last_match {
+            score -= (position - last - 1) as i64;
+        }
+        last_match = Some(position);
+    }
+    Some(score - lower.len() as i64)
+}
+
+/// Symbols of every supported source file in the workspace
+#[derive(Debug)]
+pub struct SymbolIndex {
+    root: PathBuf,
+    files: RwLock<HashMap<String, Vec<Symbol>>>,
+}
+
+impl SymbolIndex {
+    /// Create an empty index for the workspace at `root`
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            files: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Scan the whole workspace, honoring .gitignore, replacing any existing entries
+    pub async fn build(&self) {
+        let root = self.root.clone();
+        let scanned = tokio::task::spawn_blocking(move || {
+            let mut files = HashMap::new();
+            for entry in ignore::WalkBuilder::new(&root).require_git(false).build().flatten() {
+                let path = entry.path();
+                let Some(relative) = Self::relative_path(&root, path) else {
+                    continue;
+                };
+                if let Some(symbols) = Self::read_symbols(path, &relative) {
+                    files.insert(relative, symbols);
+                }
+            }
+            files
+        }).await.unwrap_or_default();
+
+        *self.files.write().await = scanned;
+    }
+
+    /// Fuzzy search symbols by name, best matches first
+    pub async fn search(&self, query: &str, limit: usize) -> Vec<Symbol> {
+        let files = self.files.read().await;
+        let mut matches: Vec<(i64, &Symbol)> = files.values()
+            .flatten()
+            .filter_map(|symbol| fuzzy_score(query, &symbol.name).map(|score| (score, symbol)))
+            .collect();
+
+        matches.sort_by(|(a_score, a), (b_score, b)| {
+            b_score.cmp(a_score)
+                .then_with(|| a.file.cmp(&b.file))
+                .then_with(|| a.line.cmp(&b.line))
+        });
+        matches.into_iter().take(limit).map(|(_, symbol)| symbol.clone()).collect()
+    }
+
+    /// Every definition with exactly this name
+    pub async fn definitions(&self, name: &str) -> Vec<Symbol> {
+        let files = self.files.read().await;
+        let mut found: Vec<Symbol> = files.values()
+            .flatten()
+            .filter(|symbol| symbol.name == name)
+            .cloned()
+            .collect();
+        found.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+        found
+    }
+
+    /// Number of indexed files
+    pub async fn file_count(&self) -> usize {
+        self.files.read().await.len()
+    }
+
+    /// Workspace-relative paths of the indexed files, sorted
+    pub async fn file_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.files.read().await.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Workspace the index covers
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn relative_path(root: &Path, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(root).ok()?;
+        Some(relative.to_string_lossy().replace('\\', "/"))
+    }
+
+    /// Symbols of a supported source file, or `None` if it is missing, unsupported or too large
+    fn read_symbols(path: &Path, relative: &str) -> Option<Vec<Symbol>> {
+        let extension = path.extension().and_then(|ext| ext.to_str())?;
+        if !matches!(extension, "rs" | "py" | "js" | "jsx" | "ts" | "tsx" | "mjs" | "go") {
+            return None;
+        }
+        let metadata = std::fs::metadata(path).ok()?;
+        if !metadata.is_file() || metadata.len() > MAX_INDEXED_FILE_BYTES {
+            return None;
+        }
+        let content = std::fs::read_to_string(path).ok()?;
+        Some(extract_symbols(relative, &content))
+    }
+}
+
+#[async_trait::async_trait]
+impl FileIndex for SymbolIndex {
+    /// Re-extract the symbols of one file, dropping it if it no longer exists
+    async fn update_file(&self, relative: &str) {
+        let path = self.root.join(relative);
+        let relative_owned = relative.to_string();
+        let symbols = tokio::task::spawn_blocking(move || Self::read_symbols(&path, &relative_owned))
+            .await
+            .ok()
+            .flatten();
+
+        let mut files = self.files.write().await;
+        match symbols {
+            Some(symbols) => {
+                files.insert(relative.to_string(), symbols);
+            }
+            None => {
+                files.remove(relative);
+            }
+        }
+    }
+
+    async fn remove_file(&self, relative: &str) {
+        self.files.write().await.remove(relative);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_index_two_files_and_find_function() {
+        let root = std::env::temp_dir().join(format!("super-ide-symbols-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), "pub struct Parser;\n\npub async fn parse_config(input: &str) {}\n").unwrap();
+        std::fs::write(root.join("app.py"), "class App:\n    def run(self):\n        pass\n").unwrap();
+
+        let index = SymbolIndex::new(root.clone());
+        index.build().await;
+        assert_eq!(index.file_count().await, 2);
+
+        let definitions = index.definitions("parse_config").await;
+        assert_eq!(definitions, vec![Symbol {
+            name: "parse_config".to_string(),
+            kind: SymbolKind::Function,
+            file: "src/lib.rs".to_string(),
+            line: 3,
+            column: 14,
+        }]);
+
+        let results = index.search("pcfg", 10).await;
+        assert_eq!(results[0].name, "parse_config");
+        assert_eq!(index.definitions("App").await[0].file, "app.py");
+
+        // Editing one file only re-reads that file
+        std::fs::write(root.join("app.py"), "def main():\n    pass\n").unwrap();
+        index.update_file("app.py").await;
+        assert!(index.definitions("App").await.is_empty());
+        assert_eq!(index.definitions("main").await.len(), 1);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_extract_symbols_across_languages() {
+        let rust = extract_symbols("a.rs", "pub(crate) const fn size() {}\nconst LIMIT: u32 = 3;\nmacro_rules! log {}");
+        let kinds: Vec<_> = rust.iter().map(|s| (s.name.as_str(), s.kind)).collect();
+        assert_eq!(kinds, vec![
+            ("size", SymbolKind::Function),
+            ("LIMIT", SymbolKind::Constant),
+            ("log", SymbolKind::Macro),
+        ]);
+
+        let js = extract_symbols("a.ts", "export const handler = async (req) => {}\nexport interface Props {}");
+        assert_eq!(js.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), vec!["handler", "Props"]);
+    }
+}
@@ -0,0 +1,171 @@
+//! Background syntax parsing
+//!
+//! Edits schedule a parse of their document instead of running one. Each document
+//! has at most one parse pending: an edit arriving within the debounce window aborts
+//! it and starts the wait over, so a burst of typing costs a single parse of the
+//! final text. Completed parses are stored on the document, where token, outline
+//! and fold reads pick them up, and announced on the event bus's `editor` channel.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+
+use super::folding::{compute_fold_points, keep_folded, FoldStrategy};
+use super::outline::compute_outline;
+use super::{Document, SyntaxHighlighting, SyntaxNode, SyntaxToken, SyntaxTree, TokenRule};
+use crate::utils::event_bus::{EventBus, IdeEvent};
+
+/// Quiet time after an edit before the document is parsed
+pub const PARSE_DEBOUNCE: Duration = Duration::from_millis(150);
+
+/// The most recently scheduled parse of a document
+#[derive(Debug)]
+struct PendingParse {
+    /// Counts the parses scheduled for the document; only the latest may store its result
+    revision: u64,
+    task: JoinHandle<()>,
+}
+
+/// Debounced parser running one background task per document
+#[derive(Debug, Default)]
+pub struct BackgroundParser {
+    pending: Arc<Mutex<HashMap<String, PendingParse>>>,
+    event_bus: Option<Arc<EventBus>>,
+}
+
+impl BackgroundParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Announce completed parses on `event_bus`
+    pub fn with_event_bus(mut self, event_bus: Arc<EventBus>) -> Self {
+        self.event_bus = Some(event_bus);
+        self
+    }
+
+    /// Parse `document` once edits to it pause, superseding any parse still pending
+    pub async fn schedule(
+        &self,
+        document: Arc<RwLock<Document>>,
+        highlighting: Option<SyntaxHighlighting>,
+        fold_strategy: FoldStrategy,
+    ) {
+        let document_id = document.read().await.id.clone();
+        let mut pending = self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let revision = match pending.get(&document_id) {
+            Some(previous) => {
+                previous.task.abort();
+                previous.revision + 1
+            }
+            None => 1,
+        };
+        let task = tokio::spawn(parse_when_idle(
+            self.pending.clone(),
+            self.event_bus.clone(),
+            document,
+            revision,
+            highlighting,
+            fold_strategy,
+        ));
+        pending.insert(document_id, PendingParse { revision, task });
+    }
+
+    /// Drop a closed document, cancelling its pending parse
+    pub fn forget(&self, document_id: &str) {
+        let mut pending = self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(previous) = pending.remove(document_id) {
+            previous.task.abort();
+        }
+    }
+}
+
+async fn parse_when_idle(
+    pending: Arc<Mutex<HashMap<String, PendingParse>>>,
+    event_bus: Option<Arc<EventBus>>,
+    document: Arc<RwLock<Document>>,
+    revision: u64,
+    highlighting: Option<SyntaxHighlighting>,
+    fold_strategy: FoldStrategy,
+) {
+    tokio::time::sleep(PARSE_DEBOUNCE).await;
+
+    let (document_id, path, content) = {
+        let doc = document.read().await;
+        let content = doc.content.read().await.to_string();
+        (doc.id.clone(), doc.path.clone(), content)
+    };
+    // Aborting this task while the parse runs discards its result
+    let parsed = tokio::task::spawn_blocking(move || {
+        let (tree, tokens) = parse(&content, highlighting.as_ref());
+        let fold_points = compute_fold_points(&content, fold_strategy);
+        (tree, tokens, compute_outline(&path, &content), fold_points)
+    }).await;
+    let Ok((tree, tokens, outline, mut fold_points)) = parsed else {
+        return;
+    };
+
+    {
+        let mut doc = document.write().await;
+        let latest = pending.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(&document_id)
+            .map(|parse| parse.revision);
+        if latest != Some(revision) {
+            return;
+        }
+        doc.syntax_tree = Some(tree.clone());
+        doc.syntax_tokens = tokens.clone();
+        doc.outline = Some(outline);
+        keep_folded(&mut fold_points, &doc.fold_points);
+        doc.fold_points = fold_points;
+    }
+
+    if let Some(event_bus) = event_bus {
+        let _ = event_bus.publish("editor", IdeEvent::SyntaxParsed { document_id, revision, tree, tokens });
+    }
+}
+
+/// Syntax tree and highlighting tokens of `content`
+fn parse(content: &str, highlighting: Option<&SyntaxHighlighting>) -> (SyntaxTree, Vec<SyntaxToken>) {
+    // Simple syntax tree generation (would use tree-sitter for real implementation)
+    let nodes = content.lines()
+        .filter(|line| line.starts_with("fn "))
+        .map(|line| SyntaxNode {
+            node_type: "function".to_string(),
+            start_byte: 0,
+            end_byte: 0,
+            children: Vec::new(),
+            text: line.to_string(),
+        })
+        .collect();
+
+    let tokens = match highlighting {
+        Some(highlighting) => content.lines()
+            .enumerate()
+            .flat_map(|(line_num, line)| {
+                tokenize_line(line, highlighting).into_iter().map(move |rule| SyntaxToken {
+                    token_type: rule.token_type,
+                    text: rule.pattern,
+                    line: line_num,
+                    column: 0, // Would need more sophisticated parsing
+                })
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    (SyntaxTree { nodes, root: 0 }, tokens)
+}
+
+/// Tokenize a line for syntax highlighting
+fn tokenize_line(line: &str, highlighting: &SyntaxHighlighting) -> Vec<TokenRule> {
+    // Simple tokenization (would use more sophisticated regex for real implementation)
+    highlighting.keywords.iter()
+        .chain(&highlighting.strings)
+        .filter(|rule| line.contains(&rule.pattern))
+        .cloned()
+        .collect()
+}
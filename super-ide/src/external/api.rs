@@ -2,7 +2,7 @@
 //!
 //! This module provides Rust bindings for the Python MCP (Model Context Protocol) system.
 
-use super::{ExternalConfig, ExternalError, ExternalResult};
+use super::{ExternalConfig, ExternalError, ExternalResult, ExternalService};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -101,7 +101,12 @@ pub struct McpApiClient {
 impl McpApiClient {
     /// Create a new MCP API client
     pub fn new(config: ExternalConfig) -> Self {
-        let client = Client::builder()
+        let client = crate::utils::http::client_builder(&config.proxy)
+            .unwrap_or_else(|e| {
+                log::warn!("Ignoring invalid proxy configuration: {}", e);
+                Client::builder()
+            })
+            .connect_timeout(Duration::from_secs(config.connect_timeout))
             .timeout(Duration::from_secs(config.request_timeout))
             .build()
             .expect("Failed to create HTTP client");
@@ -137,7 +142,7 @@ impl McpApiClient {
             .json(&request_body)
             .send()
             .await
-            .map_err(|e| ExternalError::HttpError(e.to_string()))?;
+            .map_err(|e| ExternalError::from_request(e, &self._config, ExternalService::Mcp))?;
 
         let status = response.status();
         if !status.is_success() {
@@ -316,6 +321,16 @@ impl McpApiClient {
         }
     }
 
+    /// Check that the MCP server is listening; any HTTP answer counts
+    pub async fn check_reachable(&self) -> ExternalResult<()> {
+        self.client
+            .get(&self.base_url)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|e| ExternalError::from_request(e, &self._config, ExternalService::Mcp))
+    }
+
     /// Get available functions from MCP server
     pub async fn get_available_functions(&self) -> ExternalResult<Vec<McpFunctionDescription>> {
         // This would need to be implemented in the Python MCP server
@@ -2,7 +2,7 @@
 //!
 //! This module provides Rust bindings for the Python browser automation system.
 
-use super::{ExternalConfig, ExternalError, ExternalResult};
+use super::{ExternalConfig, ExternalError, ExternalResult, ExternalService};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -10,7 +10,10 @@ use std::time::Duration;
 use log::debug;
 use base64::Engine;
 
-
+/// Actions the browser automation server handles
+pub const BROWSER_ACTIONS: &[&str] = &[
+    "navigate", "screenshot", "execute_script", "click", "type", "wait_for_element", "get_page_info", "get_element_info",
+];
 
 /// Browser automation request types
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -96,7 +99,12 @@ pub struct BrowserClient {
 impl BrowserClient {
     /// Create a new browser client
     pub fn new(config: ExternalConfig) -> Self {
-        let client = Client::builder()
+        let client = crate::utils::http::client_builder(&config.proxy)
+            .unwrap_or_else(|e| {
+                log::warn!("Ignoring invalid proxy configuration: {}", e);
+                Client::builder()
+            })
+            .connect_timeout(Duration::from_secs(config.connect_timeout))
             .timeout(Duration::from_secs(config.request_timeout))
             .build()
             .expect("Failed to create HTTP client");
@@ -120,7 +128,10 @@ impl BrowserClient {
             params.insert("wait_for_load".to_string(), serde_json::Value::Bool(wait_for_load));
         }
 
-        let response = self.call_browser_action("navigate", params).await?;
+        let response = self.call_browser_action("navigate", params).await.map_err(|e| match e {
+            ExternalError::Timeout(_) => ExternalError::NavigationTimeout(request.url.clone()),
+            e => e,
+        })?;
 
         if response.success {
             if let Some(data) = response.data {
@@ -131,7 +142,7 @@ impl BrowserClient {
                 Err(ExternalError::BrowserError("No page info in response".to_string()))
             }
         } else {
-            Err(ExternalError::BrowserError(response.message))
+            Err(action_failed("navigate", &request.url, response.message))
         }
     }
 
@@ -191,7 +202,7 @@ impl BrowserClient {
     /// Click on an element
     pub async fn click(&self, request: BrowserClickRequest) -> ExternalResult<()> {
         let mut params = HashMap::new();
-        params.insert("selector".to_string(), serde_json::Value::String(request.selector));
+        params.insert("selector".to_string(), serde_json::Value::String(request.selector.clone()));
         params.insert("action".to_string(), serde_json::Value::String("click".to_string()));
 
         if let Some(button) = request.button {
@@ -209,7 +220,7 @@ impl BrowserClient {
         if response.success {
             Ok(())
         } else {
-            Err(ExternalError::BrowserError(response.message))
+            Err(action_failed("click", &request.selector, response.message))
         }
     }
 
@@ -219,8 +230,8 @@ impl BrowserClient {
         params.insert("text".to_string(), serde_json::Value::String(request.text));
         params.insert("action".to_string(), serde_json::Value::String("type".to_string()));
 
-        if let Some(selector) = request.selector {
-            params.insert("selector".to_string(), serde_json::Value::String(selector));
+        if let Some(selector) = &request.selector {
+            params.insert("selector".to_string(), serde_json::Value::String(selector.clone()));
         }
 
         if let Some(delay) = request.delay {
@@ -232,14 +243,17 @@ impl BrowserClient {
         if response.success {
             Ok(())
         } else {
-            Err(ExternalError::BrowserError(response.message))
+            Err(match &request.selector {
+                Some(selector) => action_failed("type", selector, response.message),
+                None => ExternalError::BrowserError(response.message),
+            })
         }
     }
 
     /// Wait for element
     pub async fn wait_for_element(&self, request: BrowserWaitRequest) -> ExternalResult<BrowserElementInfo> {
         let mut params = HashMap::new();
-        params.insert("selector".to_string(), serde_json::Value::String(request.selector));
+        params.insert("selector".to_string(), serde_json::Value::String(request.selector.clone()));
         params.insert("action".to_string(), serde_json::Value::String("wait_for_element".to_string()));
 
         if let Some(timeout) = request.timeout {
@@ -261,7 +275,7 @@ impl BrowserClient {
                 Err(ExternalError::BrowserError("No element info in response".to_string()))
             }
         } else {
-            Err(ExternalError::BrowserError(response.message))
+            Err(action_failed("wait_for_element", &request.selector, response.message))
         }
     }
 
@@ -302,7 +316,7 @@ impl BrowserClient {
                 Err(ExternalError::BrowserError("No element info in response".to_string()))
             }
         } else {
-            Err(ExternalError::BrowserError(response.message))
+            Err(action_failed("get_element_info", selector, response.message))
         }
     }
 
@@ -330,7 +344,7 @@ impl BrowserClient {
             .json(&request_body)
             .send()
             .await
-            .map_err(|e| ExternalError::HttpError(e.to_string()))?;
+            .map_err(|e| ExternalError::from_request(e, &self._config, ExternalService::Browser))?;
 
         let status = response.status();
         if !status.is_success() {
@@ -364,16 +378,40 @@ impl BrowserClient {
         })
     }
 
-    /// Check if browser is available
-    pub async fn is_browser_available(&self) -> bool {
-        // Try to connect to the browser debug endpoint
-        match self.client
-            .get(&format!("{}/health", self.debug_url))
+    /// Check that the browser automation server answers its health check
+    pub async fn check_available(&self) -> ExternalResult<()> {
+        let response = self.client
+            .get(format!("{}/health", self.debug_url))
             .send()
             .await
-        {
-            Ok(response) => response.status().is_success(),
-            Err(_) => false,
+            .map_err(|e| ExternalError::from_request(e, &self._config, ExternalService::Browser))?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(ExternalError::BrowserUnavailable(format!("{} (health check returned {})", self.debug_url, response.status())))
+        }
+    }
+
+    /// Check if browser is available
+    pub async fn is_browser_available(&self) -> bool {
+        self.check_available().await.is_ok()
+    }
+}
+
+/// Error for a failure the browser reported while running `action` against
+/// `target`, the URL for navigation and otherwise a selector
+fn action_failed(action: &str, target: &str, message: String) -> ExternalError {
+    let lower = message.to_lowercase();
+    let timed_out = lower.contains("timeout") || lower.contains("timed out");
+    if action == "navigate" {
+        if timed_out {
+            return ExternalError::NavigationTimeout(target.to_string());
         }
+    } else if lower.contains("not a valid selector") || lower.contains("invalid selector") || lower.contains("syntaxerror") {
+        return ExternalError::BadSelector { selector: target.to_string(), reason: message };
+    } else if timed_out || lower.contains("no element") || lower.contains("not found") {
+        return ExternalError::ElementNotFound(target.to_string());
     }
+    ExternalError::BrowserError(message)
 }
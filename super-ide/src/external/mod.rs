@@ -28,6 +28,16 @@ pub struct ExternalConfig {
     pub browser_debug_port: u16,
     /// Request timeout in seconds
     pub request_timeout: u64,
+    /// Connection timeout in seconds
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout: u64,
+    /// Proxy for requests that leave the machine; the local MCP and browser servers are reached directly
+    #[serde(default)]
+    pub proxy: crate::config::ProxySettings,
+}
+
+fn default_connect_timeout() -> u64 {
+    10
 }
 
 impl Default for ExternalConfig {
@@ -39,6 +49,8 @@ impl Default for ExternalConfig {
             mcp_server_port: 12306,
             browser_debug_port: 9222,
             request_timeout: 30,
+            connect_timeout: default_connect_timeout(),
+            proxy: crate::config::ProxySettings::default(),
         }
     }
 }
@@ -55,15 +67,36 @@ pub enum ExternalError {
     #[error("HTTP request failed: {0}")]
     HttpError(String),
 
+    #[error("HTTP request failed, {0}")]
+    ProxyError(String),
+
+    #[error("Request timed out after {0} seconds")]
+    Timeout(u64),
+
     #[error("JSON parsing failed: {0}")]
     JsonError(String),
 
     #[error("Browser operation failed: {0}")]
     BrowserError(String),
 
+    #[error("Browser automation is not running at {0}")]
+    BrowserUnavailable(String),
+
+    #[error("Navigation to {0} timed out")]
+    NavigationTimeout(String),
+
+    #[error("Invalid selector {selector}: {reason}")]
+    BadSelector { selector: String, reason: String },
+
+    #[error("No element matches selector {0}")]
+    ElementNotFound(String),
+
     #[error("MCP operation failed: {0}")]
     McpError(String),
 
+    #[error("MCP server is not running at {0}")]
+    McpUnreachable(String),
+
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 
@@ -71,6 +104,63 @@ pub enum ExternalError {
     ProcessError(String),
 }
 
+/// Local server an external request goes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExternalService {
+    Browser,
+    Mcp,
+}
+
+impl ExternalService {
+    /// Address the service listens on
+    pub fn endpoint(&self, config: &ExternalConfig) -> String {
+        match self {
+            ExternalService::Browser => format!("http://localhost:{}", config.browser_debug_port),
+            ExternalService::Mcp => format!("http://localhost:{}", config.mcp_server_port),
+        }
+    }
+}
+
+impl ExternalError {
+    /// Classify a transport error, keeping timeouts, proxy failures and a server
+    /// that is not listening separate from other HTTP failures
+    pub fn from_request(error: reqwest::Error, config: &ExternalConfig, service: ExternalService) -> Self {
+        if error.is_timeout() {
+            ExternalError::Timeout(config.request_timeout)
+        } else if let Some(message) = crate::utils::http::proxy_failure(&config.proxy, &error) {
+            ExternalError::ProxyError(message)
+        } else if error.is_connect() {
+            match service {
+                ExternalService::Browser => ExternalError::BrowserUnavailable(service.endpoint(config)),
+                ExternalService::Mcp => ExternalError::McpUnreachable(service.endpoint(config)),
+            }
+        } else {
+            ExternalError::HttpError(error.to_string())
+        }
+    }
+}
+
+/// Whether an integration can be used right now and what it offers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapabilityStatus {
+    pub available: bool,
+    pub endpoint: String,
+    /// Operations the integration supports once available
+    pub capabilities: Vec<String>,
+    /// API error code of the failure that made it unavailable
+    pub error_code: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Availability of the browser and MCP integrations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExternalStatus {
+    pub browser: CapabilityStatus,
+    pub mcp: CapabilityStatus,
+    pub external_api_path: String,
+    pub browser_path: String,
+}
+
 /// Manager for external integrations
 pub struct ExternalManager {
     config: ExternalConfig,
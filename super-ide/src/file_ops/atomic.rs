@@ -0,0 +1,140 @@
+//! Atomic file replacement
+//!
+//! Content is written to a temporary file next to the target and renamed over it,
+//! so readers and crashes only ever see the old file or the complete new one.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+
+/// Replace the file at `path` with `content`, keeping its permissions. With `sync`, the
+/// data is flushed to disk before the rename and the directory entry after it.
+pub async fn write_atomic(path: &Path, content: &[u8], sync: bool) -> io::Result<()> {
+    write_atomic_from(path, content, u64::MAX, sync).await.map(|_| ())
+}
+
+/// Replace the file at `path` with what `reader` yields, as [`write_atomic`] does, and
+/// return the bytes written. A reader yielding more than `limit` bytes fails with
+/// [`io::ErrorKind::FileTooLarge`] and leaves the file as it was.
+pub async fn write_atomic_from<R: AsyncRead + Unpin>(path: &Path, reader: R, limit: u64, sync: bool) -> io::Result<u64> {
+    // Replace the file a symlink points at, not the link itself
+    let path = match fs::symlink_metadata(path).await {
+        Ok(metadata) if metadata.file_type().is_symlink() => fs::canonicalize(path).await?,
+        _ => path.to_path_buf(),
+    };
+    let file_name = path.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a file path", path.display())))?;
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let temp_path = dir.join(format!(".{}.{}.tmp", file_name.to_string_lossy(), uuid::Uuid::new_v4().simple()));
+
+    let result = replace_with_temp(&path, &temp_path, reader, limit, sync).await;
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path).await;
+    }
+    let written = result?;
+
+    if sync {
+        sync_dir(&dir).await;
+    }
+    Ok(written)
+}
+
+async fn replace_with_temp<R: AsyncRead + Unpin>(path: &Path, temp_path: &Path, reader: R, limit: u64, sync: bool) -> io::Result<u64> {
+    let mut file = fs::OpenOptions::new().write(true).create_new(true).open(temp_path).await?;
+    let written = tokio::io::copy(&mut reader.take(limit.saturating_add(1)), &mut file).await?;
+    if written > limit {
+        return Err(io::Error::new(io::ErrorKind::FileTooLarge, format!("more than {} bytes", limit)));
+    }
+    file.flush().await?;
+    if let Ok(metadata) = fs::metadata(path).await {
+        file.set_permissions(metadata.permissions()).await?;
+    }
+    if sync {
+        file.sync_all().await?;
+    }
+    drop(file);
+
+    fs::rename(temp_path, path).await?;
+    Ok(written)
+}
+
+/// Persist the rename itself; directories cannot be opened for syncing on Windows
+async fn sync_dir(dir: &Path) {
+    #[cfg(unix)]
+    if let Ok(dir) = fs::File::open(dir).await {
+        if let Err(e) = dir.sync_all().await {
+            log::warn!("Failed to sync directory after save: {}", e);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("super-ide-atomic-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_readers_never_see_partial_content() {
+        let dir = temp_dir();
+        let target = dir.join("data.txt");
+        let versions: Vec<String> = (0..4).map(|i| format!("{}\n", i).repeat(64 * 1024)).collect();
+        std::fs::write(&target, &versions[0]).unwrap();
+
+        let done = Arc::new(AtomicBool::new(false));
+        let reader = {
+            let (done, target, versions) = (done.clone(), target.clone(), versions.clone());
+            std::thread::spawn(move || {
+                let mut reads = 0;
+                while !done.load(Ordering::Relaxed) {
+                    let content = std::fs::read_to_string(&target).unwrap();
+                    assert!(versions.contains(&content), "observed a partial write of {} bytes", content.len());
+                    reads += 1;
+                }
+                reads
+            })
+        };
+
+        for round in 0..50 {
+            write_atomic(&target, versions[round % versions.len()].as_bytes(), round % 10 == 0).await.unwrap();
+        }
+        done.store(true, Ordering::Relaxed);
+        assert!(reader.join().unwrap() > 0);
+
+        let leftovers: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(leftovers.len(), 1, "temporary files were left behind");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_permissions_and_symlinks_are_kept() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir();
+        let target = dir.join("run.sh");
+        std::fs::write(&target, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o750)).unwrap();
+        let link = dir.join("link.sh");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        write_atomic(&link, b"#!/bin/sh\necho hi\n", true).await.unwrap();
+
+        assert!(std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "#!/bin/sh\necho hi\n");
+        assert_eq!(std::fs::metadata(&target).unwrap().permissions().mode() & 0o777, 0o750);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
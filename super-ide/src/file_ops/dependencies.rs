@@ -0,0 +1,453 @@
+//! Project dependency inspection
+//!
+//! Reads the manifests found at the workspace root (`Cargo.toml`, `package.json`,
+//! `requirements.txt`, `pyproject.toml`) and lists their dependencies in one shape,
+//! whatever the ecosystem. Cargo workspaces are followed into their member crates.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Package ecosystem a dependency is resolved from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Ecosystem {
+    Cargo,
+    Npm,
+    Python,
+}
+
+/// How a dependency is used by the project
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyKind {
+    Normal,
+    /// Only needed for tests, examples and tooling
+    Dev,
+    /// Only needed by build scripts
+    Build,
+    /// Installed on request (npm optional dependencies, Python extras)
+    Optional,
+}
+
+/// A single declared dependency
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dependency {
+    pub name: String,
+    /// Version requirement as written, `*` when none is given. Path and git
+    /// dependencies are reported as `path:<path>` and `git:<url>`.
+    pub version_req: String,
+    pub kind: DependencyKind,
+    pub ecosystem: Ecosystem,
+    /// Manifest declaring the dependency, relative to the workspace root
+    pub manifest: PathBuf,
+}
+
+/// Dependency inspection errors
+#[derive(Debug, thiserror::Error)]
+pub enum DependencyError {
+    #[error("Invalid manifest {path}: {message}")]
+    InvalidManifest { path: PathBuf, message: String },
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Lists the dependencies declared by a project
+#[derive(Debug, Clone)]
+pub struct DependencyInspector {
+    root: PathBuf,
+}
+
+impl DependencyInspector {
+    /// Create an inspector for the project at `root`
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Dependencies of every manifest at the project root, plus Cargo workspace members
+    pub async fn inspect(&self) -> Result<Vec<Dependency>, DependencyError> {
+        let mut dependencies = Vec::new();
+
+        if let Some(content) = self.read_manifest("Cargo.toml").await? {
+            let manifest = parse_manifest(Path::new("Cargo.toml"), &content)?;
+            dependencies.extend(cargo_dependencies(Path::new("Cargo.toml"), &manifest, None));
+
+            let workspace = manifest.get("workspace");
+            for member in self.workspace_members(workspace) {
+                let path = member.join("Cargo.toml");
+                let Some(content) = self.read_manifest(&path).await? else {
+                    continue;
+                };
+                let member_manifest = parse_manifest(&path, &content)?;
+                dependencies.extend(cargo_dependencies(&path, &member_manifest, workspace));
+            }
+        }
+
+        if let Some(content) = self.read_manifest("package.json").await? {
+            dependencies.extend(parse_package_json(Path::new("package.json"), &content)?);
+        }
+
+        if let Some(content) = self.read_manifest("requirements.txt").await? {
+            dependencies.extend(parse_requirements_txt(Path::new("requirements.txt"), &content, DependencyKind::Normal));
+        }
+        for dev_file in ["requirements-dev.txt", "dev-requirements.txt"] {
+            if let Some(content) = self.read_manifest(dev_file).await? {
+                dependencies.extend(parse_requirements_txt(Path::new(dev_file), &content, DependencyKind::Dev));
+            }
+        }
+
+        if let Some(content) = self.read_manifest("pyproject.toml").await? {
+            dependencies.extend(parse_pyproject_toml(Path::new("pyproject.toml"), &content)?);
+        }
+
+        Ok(dependencies)
+    }
+
+    async fn read_manifest(&self, path: impl AsRef<Path>) -> Result<Option<String>, DependencyError> {
+        match tokio::fs::read_to_string(self.root.join(path)).await {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Member directories of a Cargo workspace, relative to the root
+    fn workspace_members(&self, workspace: Option<&toml::Value>) -> Vec<PathBuf> {
+        let patterns = |key: &str| -> Vec<String> {
+            workspace
+                .and_then(|workspace| workspace.get(key))
+                .and_then(toml::Value::as_array)
+                .map(|entries| entries.iter().filter_map(|entry| entry.as_str().map(str::to_string)).collect())
+                .unwrap_or_default()
+        };
+        let excluded = patterns("exclude");
+
+        let mut members = Vec::new();
+        for pattern in patterns("members") {
+            let Ok(paths) = glob::glob(&self.root.join(&pattern).to_string_lossy()) else {
+                continue;
+            };
+            for path in paths.flatten() {
+                let Ok(relative) = path.strip_prefix(&self.root) else {
+                    continue;
+                };
+                let relative = relative.to_path_buf();
+                if path.is_dir() && relative != Path::new("") && !excluded.iter().any(|e| relative == Path::new(e)) && !members.contains(&relative) {
+                    members.push(relative);
+                }
+            }
+        }
+        members
+    }
+}
+
+fn parse_manifest(path: &Path, content: &str) -> Result<toml::Value, DependencyError> {
+    content.parse::<toml::Value>().map_err(|e| DependencyError::InvalidManifest {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    })
+}
+
+/// Dependencies of one `Cargo.toml`. `workspace` is the root's `[workspace]` table,
+/// used to resolve `dep = { workspace = true }` entries.
+fn cargo_dependencies(path: &Path, manifest: &toml::Value, workspace: Option<&toml::Value>) -> Vec<Dependency> {
+    const SECTIONS: &[(&str, DependencyKind)] = &[
+        ("dependencies", DependencyKind::Normal),
+        ("dev-dependencies", DependencyKind::Dev),
+        ("build-dependencies", DependencyKind::Build),
+    ];
+
+    // Platform-specific tables such as [target.'cfg(unix)'.dependencies]
+    let mut tables: Vec<&toml::Value> = vec![manifest];
+    if let Some(targets) = manifest.get("target").and_then(toml::Value::as_table) {
+        tables.extend(targets.values());
+    }
+    let workspace_dependencies = workspace.or_else(|| manifest.get("workspace")).and_then(|workspace| workspace.get("dependencies"));
+
+    let mut dependencies = Vec::new();
+    for table in tables {
+        for (section, kind) in SECTIONS {
+            let Some(entries) = table.get(section).and_then(toml::Value::as_table) else {
+                continue;
+            };
+            for (name, spec) in entries {
+                let inherited = spec.get("workspace").and_then(toml::Value::as_bool) == Some(true);
+                let spec = if inherited {
+                    workspace_dependencies.and_then(|deps| deps.get(name)).unwrap_or(spec)
+                } else {
+                    spec
+                };
+                dependencies.push(Dependency {
+                    name: spec.get("package").and_then(toml::Value::as_str).unwrap_or(name).to_string(),
+                    version_req: toml_version_req(spec),
+                    kind: *kind,
+                    ecosystem: Ecosystem::Cargo,
+                    manifest: path.to_path_buf(),
+                });
+            }
+        }
+    }
+    dependencies
+}
+
+/// Version requirement of a `name = "1.0"` or `name = { version = "1.0", ... }` entry
+fn toml_version_req(spec: &toml::Value) -> String {
+    if let Some(version) = spec.as_str() {
+        return version.to_string();
+    }
+    let field = |key: &str| spec.get(key).and_then(toml::Value::as_str);
+    if let Some(version) = field("version") {
+        version.to_string()
+    } else if let Some(path) = field("path") {
+        format!("path:{}", path)
+    } else if let Some(git) = field("git") {
+        format!("git:{}", git)
+    } else {
+        "*".to_string()
+    }
+}
+
+fn parse_package_json(path: &Path, content: &str) -> Result<Vec<Dependency>, DependencyError> {
+    const SECTIONS: &[(&str, DependencyKind)] = &[
+        ("dependencies", DependencyKind::Normal),
+        ("devDependencies", DependencyKind::Dev),
+        ("optionalDependencies", DependencyKind::Optional),
+    ];
+
+    let package: serde_json::Value = serde_json::from_str(content).map_err(|e| DependencyError::InvalidManifest {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    })?;
+
+    let mut dependencies = Vec::new();
+    for (section, kind) in SECTIONS {
+        let Some(entries) = package.get(section).and_then(serde_json::Value::as_object) else {
+            continue;
+        };
+        for (name, version) in entries {
+            dependencies.push(Dependency {
+                name: name.clone(),
+                version_req: version.as_str().filter(|v| !v.is_empty()).unwrap_or("*").to_string(),
+                kind: *kind,
+                ecosystem: Ecosystem::Npm,
+                manifest: path.to_path_buf(),
+            });
+        }
+    }
+    Ok(dependencies)
+}
+
+fn parse_requirements_txt(path: &Path, content: &str, kind: DependencyKind) -> Vec<Dependency> {
+    content.lines()
+        .map(|line| line.split(" #").next().unwrap_or("").trim())
+        // Options such as `-r other.txt`, `-e .` and `--index-url` name no package
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('-'))
+        .filter_map(|line| python_requirement(path, line, kind))
+        .collect()
+}
+
+/// Parse a PEP 508 requirement such as `requests[socks]>=2.31; python_version >= "3.8"`.
+/// A bare URL or local path, such as `git+https://…` or `./vendor/lib`, names no package.
+fn python_requirement(path: &Path, requirement: &str, kind: DependencyKind) -> Option<Dependency> {
+    let requirement = requirement.split(';').next().unwrap_or("").trim();
+    let is_url = requirement.split_once("://")
+        .is_some_and(|(scheme, _)| scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.')));
+    if is_url || !requirement.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    let name_end = requirement
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+        .unwrap_or(requirement.len());
+    let name = &requirement[..name_end];
+
+    let mut rest = requirement[name_end..].trim_start();
+    if rest.starts_with('[') {
+        rest = rest.split_once(']').map_or("", |(_, after)| after).trim_start();
+    }
+    let version_req = match rest.strip_prefix('@') {
+        Some(url) => format!("url:{}", url.trim()),
+        None if rest.is_empty() => "*".to_string(),
+        None => rest.trim_start_matches('(').trim_end_matches(')').trim().to_string(),
+    };
+
+    Some(Dependency {
+        name: name.to_string(),
+        version_req,
+        kind,
+        ecosystem: Ecosystem::Python,
+        manifest: path.to_path_buf(),
+    })
+}
+
+fn parse_pyproject_toml(path: &Path, content: &str) -> Result<Vec<Dependency>, DependencyError> {
+    let manifest = parse_manifest(path, content)?;
+    let mut dependencies = Vec::new();
+
+    // PEP 621 metadata
+    if let Some(project) = manifest.get("project") {
+        let requirements = project.get("dependencies").and_then(toml::Value::as_array).into_iter().flatten();
+        dependencies.extend(requirements
+            .filter_map(toml::Value::as_str)
+            .filter_map(|requirement| python_requirement(path, requirement, DependencyKind::Normal)));
+
+        if let Some(extras) = project.get("optional-dependencies").and_then(toml::Value::as_table) {
+            for (extra, requirements) in extras {
+                let kind = if matches!(extra.as_str(), "dev" | "test" | "tests" | "lint" | "docs") {
+                    DependencyKind::Dev
+                } else {
+                    DependencyKind::Optional
+                };
+                dependencies.extend(requirements.as_array().into_iter().flatten()
+                    .filter_map(toml::Value::as_str)
+                    .filter_map(|requirement| python_requirement(path, requirement, kind)));
+            }
+        }
+    }
+
+    // Poetry keeps its own tables, with dev dependencies either in the legacy
+    // [tool.poetry.dev-dependencies] table or in [tool.poetry.group.<name>.dependencies]
+    if let Some(poetry) = manifest.get("tool").and_then(|tool| tool.get("poetry")) {
+        let mut sections = vec![
+            (poetry.get("dependencies"), DependencyKind::Normal),
+            (poetry.get("dev-dependencies"), DependencyKind::Dev),
+        ];
+        if let Some(groups) = poetry.get("group").and_then(toml::Value::as_table) {
+            sections.extend(groups.values().map(|group| (group.get("dependencies"), DependencyKind::Dev)));
+        }
+
+        for (section, kind) in sections {
+            let Some(entries) = section.and_then(toml::Value::as_table) else {
+                continue;
+            };
+            for (name, spec) in entries.iter().filter(|(name, _)| name.as_str() != "python") {
+                dependencies.push(Dependency {
+                    name: name.clone(),
+                    version_req: toml_version_req(spec),
+                    kind,
+                    ecosystem: Ecosystem::Python,
+                    manifest: path.to_path_buf(),
+                });
+            }
+        }
+    }
+
+    Ok(dependencies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find<'a>(dependencies: &'a [Dependency], name: &str) -> &'a Dependency {
+        dependencies.iter().find(|d| d.name == name).unwrap_or_else(|| panic!("{} not found", name))
+    }
+
+    #[tokio::test]
+    async fn test_cargo_workspace_members() {
+        let root = std::env::temp_dir().join(format!("super-ide-deps-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("crates/core")).unwrap();
+        std::fs::create_dir_all(root.join("crates/skipped")).unwrap();
+        std::fs::write(root.join("Cargo.toml"), r#"
+[workspace]
+members = ["crates/*"]
+exclude = ["crates/skipped"]
+
+[workspace.dependencies]
+serde = { version = "1.0", features = ["derive"] }
+"#).unwrap();
+        std::fs::write(root.join("crates/core/Cargo.toml"), r#"
+[package]
+name = "core"
+
+[dependencies]
+serde = { workspace = true }
+tokio = "1.35"
+local-utils = { path = "../utils" }
+
+[dev-dependencies]
+proptest = "1"
+
+[build-dependencies]
+cc = "1.0"
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+"#).unwrap();
+        std::fs::write(root.join("crates/skipped/Cargo.toml"), "[dependencies]\nignored = \"1\"\n").unwrap();
+
+        let dependencies = DependencyInspector::new(&root).inspect().await.unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(dependencies.len(), 6);
+        assert_eq!(find(&dependencies, "serde").version_req, "1.0");
+        assert_eq!(find(&dependencies, "tokio").manifest, Path::new("crates/core/Cargo.toml"));
+        assert_eq!(find(&dependencies, "local-utils").version_req, "path:../utils");
+        assert_eq!(find(&dependencies, "proptest").kind, DependencyKind::Dev);
+        assert_eq!(find(&dependencies, "cc").kind, DependencyKind::Build);
+        assert_eq!(find(&dependencies, "libc").kind, DependencyKind::Normal);
+    }
+
+    #[test]
+    fn test_package_json() {
+        let content = r#"{
+            "name": "web",
+            "dependencies": { "react": "^18.2.0" },
+            "devDependencies": { "jest": "~29.0.0" },
+            "optionalDependencies": { "fsevents": "" }
+        }"#;
+        let dependencies = parse_package_json(Path::new("package.json"), content).unwrap();
+        assert_eq!(dependencies.len(), 3);
+        assert_eq!(find(&dependencies, "react").version_req, "^18.2.0");
+        assert_eq!(find(&dependencies, "jest").kind, DependencyKind::Dev);
+        assert_eq!(find(&dependencies, "fsevents").version_req, "*");
+        assert_eq!(find(&dependencies, "fsevents").ecosystem, Ecosystem::Npm);
+
+        assert!(matches!(
+            parse_package_json(Path::new("package.json"), "{ not json"),
+            Err(DependencyError::InvalidManifest { .. })
+        ));
+    }
+
+    #[test]
+    fn test_requirements_txt() {
+        let content = "# pinned\nrequests[socks]>=2.31,<3 ; python_version >= \"3.8\"\nflask==3.0.0  # web\nnumpy\n-r base.txt\n-e .\nmylib @ https://example.com/mylib.whl\ngit+https://github.com/psf/black\n./vendor/toolkit\n";
+        let dependencies = parse_requirements_txt(Path::new("requirements.txt"), content, DependencyKind::Normal);
+        assert_eq!(dependencies.len(), 4);
+        assert_eq!(find(&dependencies, "requests").version_req, ">=2.31,<3");
+        assert_eq!(find(&dependencies, "flask").version_req, "==3.0.0");
+        assert_eq!(find(&dependencies, "numpy").version_req, "*");
+        assert_eq!(find(&dependencies, "mylib").version_req, "url:https://example.com/mylib.whl");
+    }
+
+    #[test]
+    fn test_pyproject_toml() {
+        let pep621 = r#"
+[project]
+name = "app"
+dependencies = ["httpx>=0.25", "pydantic (>=2,<3)"]
+
+[project.optional-dependencies]
+test = ["pytest>=7"]
+s3 = ["boto3"]
+"#;
+        let dependencies = parse_pyproject_toml(Path::new("pyproject.toml"), pep621).unwrap();
+        assert_eq!(dependencies.len(), 4);
+        assert_eq!(find(&dependencies, "pydantic").version_req, ">=2,<3");
+        assert_eq!(find(&dependencies, "pytest").kind, DependencyKind::Dev);
+        assert_eq!(find(&dependencies, "boto3").kind, DependencyKind::Optional);
+
+        let poetry = r#"
+[tool.poetry.dependencies]
+python = "^3.11"
+django = "^5.0"
+celery = { version = "^5.3", extras = ["redis"] }
+
+[tool.poetry.group.dev.dependencies]
+black = "^24.1"
+"#;
+        let dependencies = parse_pyproject_toml(Path::new("pyproject.toml"), poetry).unwrap();
+        assert_eq!(dependencies.len(), 3);
+        assert_eq!(find(&dependencies, "celery").version_req, "^5.3");
+        assert_eq!(find(&dependencies, "black").kind, DependencyKind::Dev);
+    }
+}
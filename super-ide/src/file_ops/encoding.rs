@@ -0,0 +1,244 @@
+//! Text encodings of files on disk
+//!
+//! Files are decoded to UTF-8 when read and written back in the encoding they were
+//! read with. Detection looks for a byte order mark first, then accepts valid UTF-8,
+//! then recognises BOM-less UTF-16 by its zero bytes; anything else is decoded with
+//! the configured fallback, `windows-1252` unless the editor settings say otherwise.
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+use serde::{Deserialize, Serialize};
+
+/// Encoding used for files that are neither UTF-8 nor UTF-16
+pub const DEFAULT_FALLBACK_ENCODING: &str = "windows-1252";
+
+/// Bytes from the start of a file read to tell its encoding without decoding it
+pub const DETECT_BYTES: usize = 8 * 1024;
+
+/// How a file's text is stored on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "EncodingInfo", into = "EncodingInfo")]
+pub struct TextEncoding {
+    encoding: &'static Encoding,
+    bom: bool,
+}
+
+/// Serialized form of `TextEncoding`
+#[derive(Serialize, Deserialize)]
+struct EncodingInfo {
+    name: String,
+    #[serde(default)]
+    bom: bool,
+}
+
+impl From<TextEncoding> for EncodingInfo {
+    fn from(encoding: TextEncoding) -> Self {
+        Self {
+            name: encoding.name().to_string(),
+            bom: encoding.bom,
+        }
+    }
+}
+
+impl TryFrom<EncodingInfo> for TextEncoding {
+    type Error = String;
+
+    fn try_from(info: EncodingInfo) -> Result<Self, Self::Error> {
+        let encoding = Self::for_label(&info.name).ok_or_else(|| format!("Unknown encoding: {}", info.name))?;
+        Ok(encoding.with_bom(info.bom))
+    }
+}
+
+impl Default for TextEncoding {
+    fn default() -> Self {
+        Self { encoding: UTF_8, bom: false }
+    }
+}
+
+impl TextEncoding {
+    /// The encoding named by a WHATWG label such as `latin1` or `utf-16le`
+    pub fn for_label(label: &str) -> Option<Self> {
+        Encoding::for_label(label.trim().as_bytes()).map(|encoding| Self { encoding, bom: false })
+    }
+
+    /// The configured fallback, or `windows-1252` when the label is unknown
+    pub fn fallback(label: &str) -> Self {
+        Self::for_label(label).unwrap_or_else(|| {
+            log::warn!("Unknown fallback encoding '{}', using {}", label, DEFAULT_FALLBACK_ENCODING);
+            Self { encoding: WINDOWS_1252, bom: false }
+        })
+    }
+
+    /// Same encoding, with or without a byte order mark on save. Only UTF-8 and
+    /// UTF-16 have one.
+    pub fn with_bom(self, bom: bool) -> Self {
+        let has_bom = [UTF_8, UTF_16LE, UTF_16BE].contains(&self.encoding);
+        Self { bom: bom && has_bom, ..self }
+    }
+
+    /// Canonical name, e.g. `UTF-16LE`
+    pub fn name(&self) -> &'static str {
+        self.encoding.name()
+    }
+
+    pub fn has_bom(&self) -> bool {
+        self.bom
+    }
+
+    /// Detect the encoding of `bytes` and decode them, using `fallback` when the
+    /// content is neither UTF-8 nor UTF-16. Undecodable bytes become U+FFFD.
+    pub fn decode(bytes: &[u8], fallback: TextEncoding) -> (String, TextEncoding) {
+        if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+            let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_length..]);
+            return (text.into_owned(), Self { encoding, bom: true });
+        }
+        if let Ok(text) = std::str::from_utf8(bytes) {
+            return (text.to_string(), Self::default());
+        }
+
+        let encoding = utf16_without_bom(bytes).unwrap_or(fallback.encoding);
+        let (text, _) = encoding.decode_without_bom_handling(bytes);
+        (text.into_owned(), Self { encoding, bom: false })
+    }
+
+    /// The encoding `decode` would detect for a file starting with `prefix`, which
+    /// is the whole file when `whole` is set. A character cut off at the end of a
+    /// partial prefix does not count against UTF-8 or UTF-16.
+    pub fn detect(prefix: &[u8], whole: bool, fallback: TextEncoding) -> TextEncoding {
+        if let Some((encoding, _)) = Encoding::for_bom(prefix) {
+            return Self { encoding, bom: true };
+        }
+        match std::str::from_utf8(prefix) {
+            Ok(_) => return Self::default(),
+            Err(e) if !whole && e.error_len().is_none() => return Self::default(),
+            Err(_) => {}
+        }
+
+        let units = if whole { prefix } else { &prefix[..prefix.len().saturating_sub(2) & !1] };
+        let encoding = utf16_without_bom(units).unwrap_or(fallback.encoding);
+        Self { encoding, bom: false }
+    }
+
+    /// Encode `content` for writing, failing when the encoding cannot represent
+    /// some of its characters rather than saving them as `?` or HTML entities
+    pub fn encode(&self, content: &str) -> std::io::Result<Vec<u8>> {
+        // encoding_rs only decodes UTF-16; its encoder writes UTF-8 for it
+        if self.encoding == UTF_16LE || self.encoding == UTF_16BE {
+            let little_endian = self.encoding == UTF_16LE;
+            let mut bytes = Vec::with_capacity(content.len() * 2 + 2);
+            let units = self.bom.then_some(0xFEFF).into_iter().chain(content.encode_utf16());
+            for unit in units {
+                bytes.extend_from_slice(&if little_endian { unit.to_le_bytes() } else { unit.to_be_bytes() });
+            }
+            return Ok(bytes);
+        }
+
+        let (encoded, _, had_errors) = self.encoding.encode(content);
+        if had_errors {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{} cannot represent some characters in the content", self.name()),
+            ));
+        }
+        let mut bytes = Vec::with_capacity(encoded.len() + 3);
+        if self.bom {
+            bytes.extend_from_slice(b"\xEF\xBB\xBF");
+        }
+        bytes.extend_from_slice(&encoded);
+        Ok(bytes)
+    }
+}
+
+/// Whether `bytes` are UTF-16 text, with or without a byte order mark. Their zero
+/// bytes make them look binary otherwise.
+pub fn is_utf16(bytes: &[u8]) -> bool {
+    match Encoding::for_bom(bytes) {
+        Some((encoding, _)) => encoding == UTF_16LE || encoding == UTF_16BE,
+        None => utf16_without_bom(bytes).is_some(),
+    }
+}
+
+/// UTF-16 without a byte order mark: text that is mostly ASCII has a zero in every
+/// other byte, on the odd positions for little endian and the even ones for big endian
+fn utf16_without_bom(bytes: &[u8]) -> Option<&'static Encoding> {
+    if bytes.len() < 2 || !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let units = bytes.len() / 2;
+    let zeros_at = |offset: usize| bytes.iter().skip(offset).step_by(2).filter(|&&byte| byte == 0).count();
+    let (even, odd) = (zeros_at(0), zeros_at(1));
+
+    let encoding = if odd * 2 >= units && even * 10 < units {
+        UTF_16LE
+    } else if even * 2 >= units && odd * 10 < units {
+        UTF_16BE
+    } else {
+        return None;
+    };
+    let (_, had_errors) = encoding.decode_without_bom_handling(bytes);
+    (!had_errors).then_some(encoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn test_detects_and_round_trips_encodings() {
+        let fallback = TextEncoding::fallback(DEFAULT_FALLBACK_ENCODING);
+        let text = "naïve café\r\n";
+
+        let mut with_bom = vec![0xFF, 0xFE];
+        with_bom.extend(utf16le(text));
+        let (decoded, encoding) = TextEncoding::decode(&with_bom, fallback);
+        assert_eq!(decoded, text);
+        assert_eq!((encoding.name(), encoding.has_bom()), ("UTF-16LE", true));
+        assert_eq!(encoding.encode(&decoded).unwrap(), with_bom);
+
+        let (decoded, encoding) = TextEncoding::decode(&utf16le(text), fallback);
+        assert_eq!((decoded.as_str(), encoding.name(), encoding.has_bom()), (text, "UTF-16LE", false));
+
+        let latin1 = b"na\xEFve caf\xE9\r\n";
+        let (decoded, encoding) = TextEncoding::decode(latin1, fallback);
+        assert_eq!((decoded.as_str(), encoding.name()), (text, "windows-1252"));
+        assert_eq!(encoding.encode(&decoded).unwrap(), latin1);
+        assert_eq!(encoding.encode("日本").unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+
+        let utf8_bom = b"\xEF\xBB\xBFfn main() {}\n";
+        let (decoded, encoding) = TextEncoding::decode(utf8_bom, fallback);
+        assert_eq!((decoded.as_str(), encoding.name(), encoding.has_bom()), ("fn main() {}\n", "UTF-8", true));
+        assert_eq!(encoding.encode(&decoded).unwrap(), utf8_bom);
+        assert_eq!(TextEncoding::decode(b"plain\n", fallback).1, TextEncoding::default());
+    }
+
+    #[test]
+    fn test_prefix_detection_matches_decoding() {
+        let fallback = TextEncoding::fallback(DEFAULT_FALLBACK_ENCODING);
+        let utf8 = "café ".repeat(10).into_bytes();
+        // Cut inside the last `é`
+        let cut = &utf8[..utf8.len() - 2];
+        assert_eq!(TextEncoding::detect(cut, false, fallback), TextEncoding::default());
+        assert_eq!(TextEncoding::detect(cut, true, fallback).name(), "windows-1252");
+
+        let utf16 = utf16le("naïve 😀 café");
+        // Cut between the halves of the emoji's surrogate pair
+        let detected = TextEncoding::detect(&utf16[..14], false, fallback);
+        assert_eq!((detected.name(), detected.has_bom()), ("UTF-16LE", false));
+        for bytes in [&utf16[..], b"na\xEFve caf\xE9\r\n", b"\xEF\xBB\xBFfn main() {}\n"] {
+            assert_eq!(TextEncoding::detect(bytes, true, fallback), TextEncoding::decode(bytes, fallback).1);
+        }
+    }
+
+    #[test]
+    fn test_encoding_serializes_by_name() {
+        let encoding = TextEncoding::for_label("utf-16le").unwrap().with_bom(true);
+        let json = serde_json::to_value(encoding).unwrap();
+        assert_eq!(json, serde_json::json!({ "name": "UTF-16LE", "bom": true }));
+        assert_eq!(serde_json::from_value::<TextEncoding>(json).unwrap(), encoding);
+        assert!(serde_json::from_value::<TextEncoding>(serde_json::json!({ "name": "klingon" })).is_err());
+        assert!(!TextEncoding::for_label("latin1").unwrap().with_bom(true).has_bom());
+    }
+}
@@ -0,0 +1,368 @@
+//! Workspace content search
+//!
+//! Files are read line by line and only the lines kept as matches or context are
+//! held in memory, so a broad pattern over a large project costs at most
+//! `max_matches` results rather than a copy of every file it touches.
+
+use anyhow::Result;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::io::AsyncBufReadExt;
+
+use super::{is_secret_path, FileManager, FileOperationError};
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_max_matches() -> usize {
+    1000
+}
+
+/// How `FileManager::grep` matches lines
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrepOptions {
+    /// Treat the pattern as a regular expression instead of literal text
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default = "default_true")]
+    pub case_sensitive: bool,
+    /// Only match the pattern between word boundaries
+    #[serde(default)]
+    pub whole_word: bool,
+    /// Lines of context before and after each match, like `grep -C`
+    #[serde(default)]
+    pub context: usize,
+    /// Glob the path relative to the workspace must match, e.g. `src/**/*.rs`
+    #[serde(default)]
+    pub include: Option<String>,
+    /// Search stops once this many matching lines were found
+    #[serde(default = "default_max_matches")]
+    pub max_matches: usize,
+}
+
+impl Default for GrepOptions {
+    fn default() -> Self {
+        Self {
+            regex: false,
+            case_sensitive: true,
+            whole_word: false,
+            context: 0,
+            include: None,
+            max_matches: default_max_matches(),
+        }
+    }
+}
+
+/// One occurrence of the pattern within a line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GrepSubmatch {
+    /// Byte range of the match within the line
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+    /// Capture groups of the pattern, `None` for groups that did not participate
+    pub groups: Vec<Option<String>>,
+}
+
+/// A matching line with its context. A line is reported once: context lines
+/// shared by neighbouring matches belong to the earlier match's `after`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GrepMatch {
+    /// 1-based line number
+    pub line_number: usize,
+    pub line: String,
+    pub submatches: Vec<GrepSubmatch>,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+/// Matches within one file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrepFileResult {
+    pub path: PathBuf,
+    pub matches: Vec<GrepMatch>,
+}
+
+/// Search results grouped by file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GrepResults {
+    pub files: Vec<GrepFileResult>,
+    pub total_matches: usize,
+    pub files_searched: usize,
+    /// Whether the search stopped at `max_matches`
+    pub truncated: bool,
+}
+
+fn build_regex(pattern: &str, options: &GrepOptions) -> Result<Regex, FileOperationError> {
+    let pattern = if options.regex { pattern.to_string() } else { regex::escape(pattern) };
+    let pattern = if options.whole_word { format!(r"\b(?:{})\b", pattern) } else { pattern };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .build()
+        .map_err(|e| FileOperationError::InvalidPattern(e.to_string()))
+}
+
+fn submatches(regex: &Regex, line: &str) -> Vec<GrepSubmatch> {
+    regex.captures_iter(line)
+        .filter_map(|captures| {
+            let whole = captures.get(0)?;
+            Some(GrepSubmatch {
+                start: whole.start(),
+                end: whole.end(),
+                text: whole.as_str().to_string(),
+                groups: captures.iter().skip(1).map(|group| group.map(|g| g.as_str().to_string())).collect(),
+            })
+        })
+        .collect()
+}
+
+/// Totals of a streamed search
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GrepSummary {
+    pub total_matches: usize,
+    pub files_with_matches: usize,
+    pub files_searched: usize,
+    /// Whether the search stopped at `max_matches`
+    pub truncated: bool,
+    /// Whether the search was cancelled before the walk finished
+    pub cancelled: bool,
+}
+
+impl FileManager {
+    /// Search file contents across the workspace
+    pub async fn grep(&self, pattern: &str, options: &GrepOptions) -> Result<GrepResults> {
+        let mut files = Vec::new();
+        let summary = self.grep_streaming(pattern, options, &AtomicBool::new(false), |file| {
+            files.push(file);
+            true
+        }).await?;
+
+        Ok(GrepResults {
+            files,
+            total_matches: summary.total_matches,
+            files_searched: summary.files_searched,
+            truncated: summary.truncated,
+        })
+    }
+
+    /// Search file contents, passing each file's matches to `on_file` as soon as the
+    /// file is searched. The walk stops early once `cancelled` is set or `on_file`
+    /// returns `false`.
+    pub async fn grep_streaming(
+        &self,
+        pattern: &str,
+        options: &GrepOptions,
+        cancelled: &AtomicBool,
+        mut on_file: impl FnMut(GrepFileResult) -> bool,
+    ) -> Result<GrepSummary> {
+        let regex = build_regex(pattern, options)?;
+        let include = options.include.as_deref()
+            .map(glob::Pattern::new)
+            .transpose()
+            .map_err(|e| FileOperationError::InvalidPattern(e.to_string()))?;
+
+        let mut summary = GrepSummary::default();
+        // Skips what .gitignore and other ignore files exclude, like the file tree;
+        // build and dependency directories are skipped even without one
+        let files = ignore::WalkBuilder::new(&self.base_path)
+            .require_git(false)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .filter_entry(|e| e.depth() == 0 || (e.file_name() != "target" && e.file_name() != "node_modules"))
+            .build()
+            .flatten()
+            .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()) && !is_secret_path(entry.path()));
+
+        for entry in files {
+            if cancelled.load(Ordering::Relaxed) {
+                summary.cancelled = true;
+                break;
+            }
+            let relative_path = entry.path().strip_prefix(&self.base_path).unwrap_or(entry.path()).to_path_buf();
+            if include.as_ref().is_some_and(|include| !include.matches_path(&relative_path)) {
+                continue;
+            }
+            if entry.metadata().map(|m| m.len() > self.max_open_file_bytes).unwrap_or(true) {
+                continue;
+            }
+
+            let budget = options.max_matches - summary.total_matches;
+            let (matches, truncated) = grep_file(entry.path(), &regex, options.context, budget).await;
+            summary.files_searched += 1;
+            if !matches.is_empty() {
+                summary.total_matches += matches.len();
+                summary.files_with_matches += 1;
+                if !on_file(GrepFileResult { path: relative_path, matches }) {
+                    summary.cancelled = true;
+                    break;
+                }
+            }
+            if truncated {
+                summary.truncated = true;
+                break;
+            }
+        }
+
+        Ok(summary)
+    }
+}
+
+/// Matches in one file, at most `budget` of them, and whether the budget ran out.
+/// Binary and non-UTF-8 files yield no matches.
+async fn grep_file(path: &Path, regex: &Regex, context: usize, budget: usize) -> (Vec<GrepMatch>, bool) {
+    let Ok(file) = tokio::fs::File::open(path).await else {
+        return (Vec::new(), false);
+    };
+    let mut lines = tokio::io::BufReader::new(file).lines();
+
+    let mut matches: Vec<GrepMatch> = Vec::new();
+    let mut before: VecDeque<String> = VecDeque::with_capacity(context);
+    let mut after_remaining = 0;
+    let mut line_number = 0;
+
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(_) => return (Vec::new(), false),
+        };
+        if line.contains('\0') {
+            return (Vec::new(), false);
+        }
+        line_number += 1;
+
+        if regex.is_match(&line) {
+            if matches.len() == budget {
+                return (matches, true);
+            }
+            matches.push(GrepMatch {
+                line_number,
+                submatches: submatches(regex, &line),
+                line,
+                before: before.drain(..).collect(),
+                after: Vec::new(),
+            });
+            after_remaining = context;
+        } else if after_remaining > 0 {
+            after_remaining -= 1;
+            if let Some(last) = matches.last_mut() {
+                last.after.push(line);
+            }
+        } else if context > 0 {
+            if before.len() == context {
+                before.pop_front();
+            }
+            before.push_back(line);
+        }
+    }
+
+    (matches, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_ops::tests::fixture;
+
+    #[tokio::test]
+    async fn test_context_lines_are_not_repeated() {
+        let root = fixture(&[("notes.txt", "one\ntwo\nTODO first\nthree\nfour\nTODO second\nfive\nsix\nseven\n")]);
+        let manager = FileManager::new(root.clone());
+
+        let options = GrepOptions { context: 2, ..GrepOptions::default() };
+        let results = manager.grep("TODO", &options).await.unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(results.total_matches, 2);
+        let matches = &results.files[0].matches;
+        assert_eq!(results.files[0].path, Path::new("notes.txt"));
+        assert_eq!((matches[0].line_number, matches[0].before.clone()), (3, vec!["one".to_string(), "two".to_string()]));
+        assert_eq!(matches[0].after, vec!["three", "four"]);
+        assert_eq!((matches[1].line_number, matches[1].before.len()), (6, 0));
+        assert_eq!(matches[1].after, vec!["five", "six"]);
+    }
+
+    #[tokio::test]
+    async fn test_regex_groups_and_word_matching() {
+        let root = fixture(&[
+            ("src/lib.rs", "pub fn parse_line(input: &str) {}\nfn helper() {}\nlet reparse = 1;\n"),
+            ("src/main.py", "def parse_line(text):\n    pass\n"),
+            ("target/debug/out.rs", "fn parse_line() {}\n"),
+        ]);
+        let manager = FileManager::new(root.clone());
+
+        let options = GrepOptions { regex: true, include: Some("**/*.rs".to_string()), ..GrepOptions::default() };
+        let results = manager.grep(r"fn (\w+)\((\w+)?", &options).await.unwrap();
+        assert_eq!(results.files.len(), 1);
+        let groups: Vec<Vec<Option<String>>> = results.files[0].matches.iter()
+            .map(|m| m.submatches[0].groups.clone())
+            .collect();
+        assert_eq!(groups, vec![
+            vec![Some("parse_line".to_string()), Some("input".to_string())],
+            vec![Some("helper".to_string()), None],
+        ]);
+
+        let options = GrepOptions { whole_word: true, case_sensitive: false, ..GrepOptions::default() };
+        let results = manager.grep("PARSE_LINE", &options).await.unwrap();
+        assert_eq!(results.total_matches, 2);
+        assert_eq!(results.files[0].matches[0].submatches[0].start, 7);
+
+        let options = GrepOptions { max_matches: 1, ..GrepOptions::default() };
+        let results = manager.grep("parse", &options).await.unwrap();
+        assert!(results.truncated);
+        assert_eq!(results.total_matches, 1);
+
+        assert!(manager.grep("(unclosed", &GrepOptions { regex: true, ..GrepOptions::default() }).await.is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_gitignored_files_are_not_searched() {
+        let root = fixture(&[
+            (".gitignore", "dist/\n*.log\n"),
+            ("src/app.js", "// TODO: ship it\n"),
+            ("dist/app.min.js", "// TODO: ship it\n"),
+            ("debug.log", "TODO from a log line\n"),
+        ]);
+        let manager = FileManager::new(root.clone());
+
+        let results = manager.grep("TODO", &GrepOptions::default()).await.unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let paths: Vec<&Path> = results.files.iter().map(|file| file.path.as_path()).collect();
+        assert_eq!(paths, vec![Path::new("src/app.js")]);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_search_stops_the_walk() {
+        let files: Vec<(String, String)> = (0..50)
+            .map(|i| (format!("src/file{:02}.rs", i), "// TODO: tidy up\n".to_string()))
+            .collect();
+        let files: Vec<(&str, &str)> = files.iter().map(|(path, content)| (path.as_str(), content.as_str())).collect();
+        let root = fixture(&files);
+        let manager = FileManager::new(root.clone());
+
+        // The flag is what a client's cancel message sets while results are arriving
+        let cancelled = AtomicBool::new(false);
+        let mut received = Vec::new();
+        let summary = manager.grep_streaming("TODO", &GrepOptions::default(), &cancelled, |file| {
+            received.push(file.path);
+            if received.len() == 3 {
+                cancelled.store(true, Ordering::Relaxed);
+            }
+            true
+        }).await.unwrap();
+
+        assert!(summary.cancelled);
+        assert_eq!(received, vec![Path::new("src/file00.rs"), Path::new("src/file01.rs"), Path::new("src/file02.rs")]);
+        assert_eq!((summary.files_searched, summary.total_matches), (3, 3));
+
+        let complete = manager.grep_streaming("TODO", &GrepOptions::default(), &AtomicBool::new(false), |_| true).await.unwrap();
+        assert!(!complete.cancelled);
+        assert_eq!(complete.files_searched, 50);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -7,9 +7,20 @@
 //! - Project structure analysis
 //! - Integration with Git for version control
 
+pub mod atomic;
+pub mod dependencies;
+pub mod encoding;
+pub mod grep;
+pub mod outdated;
+pub mod scaffold;
+pub mod stats;
+pub mod templates;
+pub mod trash;
+pub mod tree_watch;
+
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::path::{PathBuf, Path};
+use std::path::{Component, PathBuf, Path};
 use tokio::fs;
 use walkdir::WalkDir;
 use notify::{RecommendedWatcher, Watcher, RecursiveMode, Event, Watcher};
@@ -17,6 +28,7 @@ use notify::event::{EventKind, ModifyKind};
 use chrono::{DateTime, Utc, TimeZone};
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use encoding::{TextEncoding, DEFAULT_FALLBACK_ENCODING};
 
 /// File information
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -51,6 +63,42 @@ pub struct ProjectStructure {
     pub total_size: u64,
 }
 
+impl ProjectStructure {
+    /// Files most useful for understanding a project: READMEs, manifests and entry points
+    pub fn key_files(&self) -> Vec<&FileInfo> {
+        const README_PREFIXES: &[&str] = &["readme", "contributing", "architecture"];
+        const MANIFESTS: &[&str] = &[
+            "cargo.toml", "package.json", "pyproject.toml", "setup.py", "requirements.txt",
+            "go.mod", "pom.xml", "build.gradle", "composer.json", "gemfile", "makefile",
+        ];
+        const ENTRY_POINTS: &[&str] = &[
+            "main.rs", "lib.rs", "mod.rs", "main.py", "__main__.py", "app.py", "manage.py",
+            "index.js", "index.ts", "main.js", "main.ts", "app.js", "app.ts", "main.go",
+        ];
+
+        let mut key_files: Vec<(u8, &FileInfo)> = self.files.iter()
+            .filter_map(|file| {
+                let name = file.name.to_lowercase();
+                let depth = file.path.components().count();
+                let rank = if README_PREFIXES.iter().any(|prefix| name.starts_with(prefix)) && depth <= 2 {
+                    0
+                } else if MANIFESTS.contains(&name.as_str()) && depth <= 2 {
+                    1
+                } else if ENTRY_POINTS.contains(&name.as_str()) && (name != "mod.rs" || depth <= 3) {
+                    2
+                } else {
+                    return None;
+                };
+                Some((rank, file))
+            })
+            .collect();
+
+        // Shallow files first within each rank so top-level entry points win over nested ones
+        key_files.sort_by_key(|(rank, file)| (*rank, file.path.components().count(), file.path.clone()));
+        key_files.into_iter().map(|(_, file)| file).collect()
+    }
+}
+
 /// File operation result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileOperationResult {
@@ -77,6 +125,50 @@ pub enum FileChangeType {
     Renamed,
 }
 
+/// A saved snapshot of a file's previous content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Snapshot id, `<timestamp>-<n>` with `n` telling apart snapshots of the same millisecond
+    pub id: String,
+    /// Milliseconds since the Unix epoch
+    pub timestamp: i64,
+    pub created_at: DateTime<Utc>,
+    pub size: u64,
+}
+
+/// Directory, relative to the project root, where file snapshots are kept
+const HISTORY_DIR: &str = ".super-ide/history";
+
+/// Snapshots kept per file before the oldest are pruned
+const MAX_HISTORY_ENTRIES: usize = 20;
+
+/// Files larger than this are not snapshotted
+const MAX_SNAPSHOT_BYTES: u64 = 1024 * 1024;
+
+/// Timestamp and number of a snapshot id; bare timestamps are older snapshots
+fn parse_snapshot_id(id: &str) -> Option<(i64, u32)> {
+    let (timestamp, n) = id.split_once('-').unwrap_or((id, "0"));
+    Some((timestamp.parse().ok()?, n.parse().ok()?))
+}
+
+/// File name patterns that commonly hold credentials and must never be copied
+pub const SECRET_FILE_PATTERNS: &[&str] = &[
+    ".env", ".env.*", "*.pem", "*.key", "*.p12", "*.pfx", "*.keystore", "*.jks",
+    "id_rsa*", "id_ed25519*", "id_ecdsa*", ".npmrc", ".pypirc", ".netrc", "credentials*",
+];
+
+/// Whether a path's file name matches the secret denylist
+pub fn is_secret_path(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    SECRET_FILE_PATTERNS.iter().any(|pattern| {
+        glob::Pattern::new(pattern)
+            .map(|p| p.matches(name))
+            .unwrap_or(false)
+    })
+}
+
 /// File operation error types
 #[derive(Debug, thiserror::Error)]
 pub enum FileOperationError {
@@ -94,6 +186,47 @@ pub enum FileOperationError {
     
     #[error("Watch error: {0}")]
     WatchError(String),
+    
+    #[error("File too large: {size} bytes exceeds the {limit} byte limit")]
+    FileTooLarge { size: u64, limit: u64 },
+    
+    #[error("Invalid search pattern: {0}")]
+    InvalidPattern(String),
+    
+    #[error("Already exists: {0}")]
+    AlreadyExists(PathBuf),
+}
+
+/// Language of a file by its extension, `plaintext` when unknown
+pub fn language_for_path(path: &Path) -> &'static str {
+    match path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+        .as_deref() {
+        Some("rs") => "rust",
+        Some("py") => "python", 
+        Some("js") => "javascript",
+        Some("ts") => "typescript",
+        Some("java") => "java",
+        Some("cpp") | Some("cc") | Some("cxx") => "cpp",
+        Some("c") => "c",
+        Some("cs") => "csharp",
+        Some("go") => "go",
+        Some("php") => "php",
+        Some("rb") => "ruby",
+        Some("swift") => "swift",
+        Some("kt") => "kotlin",
+        Some("scala") => "scala",
+        Some("sh") | Some("bash") => "shell",
+        Some("html") => "html",
+        Some("css") => "css",
+        Some("json") => "json",
+        Some("xml") => "xml",
+        Some("yaml") | Some("yml") => "yaml",
+        Some("md") => "markdown",
+        Some("txt") => "plaintext",
+        _ => "plaintext",
+    }
 }
 
 /// File manager for handling file operations
@@ -102,8 +235,15 @@ pub struct FileManager {
     base_path: PathBuf,
     file_watcher: Option<RecommendedWatcher>,
     change_events: Arc<RwLock<Vec<FileChangeEvent>>>,
+    max_open_file_bytes: u64,
+    fsync_on_save: bool,
+    fallback_encoding: TextEncoding,
+    delete_to_trash: bool,
 }
 
+/// Default read limit, matching the editor setting's default
+const DEFAULT_MAX_OPEN_FILE_BYTES: u64 = 10 * 1024 * 1024;
+
 impl FileManager {
     /// Create a new file manager
     pub fn new(base_path: PathBuf) -> Self {
@@ -111,9 +251,37 @@ impl FileManager {
             base_path,
             file_watcher: None,
             change_events: Arc::new(RwLock::new(Vec::new())),
+            max_open_file_bytes: DEFAULT_MAX_OPEN_FILE_BYTES,
+            fsync_on_save: false,
+            fallback_encoding: TextEncoding::fallback(DEFAULT_FALLBACK_ENCODING),
+            delete_to_trash: true,
         }
     }
 
+    /// Set the largest file `read_file` will load
+    pub fn with_max_open_file_bytes(mut self, limit: u64) -> Self {
+        self.max_open_file_bytes = limit;
+        self
+    }
+
+    /// Flush written files to disk before they replace the previous content
+    pub fn with_fsync_on_save(mut self, fsync_on_save: bool) -> Self {
+        self.fsync_on_save = fsync_on_save;
+        self
+    }
+
+    /// Encoding for files that are neither UTF-8 nor UTF-16, as a label like `latin1`
+    pub fn with_fallback_encoding(mut self, label: &str) -> Self {
+        self.fallback_encoding = TextEncoding::fallback(label);
+        self
+    }
+
+    /// Move deleted files into the project's trash instead of removing them
+    pub fn with_delete_to_trash(mut self, delete_to_trash: bool) -> Self {
+        self.delete_to_trash = delete_to_trash;
+        self
+    }
+
     /// Initialize file monitoring
     pub async fn initialize_monitoring(&mut self) -> Result<()> {
         let path = self.base_path.clone();
@@ -153,8 +321,13 @@ impl FileManager {
         Ok(())
     }
 
-    /// Read file contents
+    /// Read file contents, refusing files over the size limit
     pub async fn read_file(&self, path: &Path) -> Result<String> {
+        self.read_file_with_override(path, false).await
+    }
+
+    /// Read file contents; `open_anyway` skips the size limit
+    pub async fn read_file_with_override(&self, path: &Path, open_anyway: bool) -> Result<String> {
         let full_path = self.base_path.join(path);
         
         if !full_path.exists() {
@@ -165,36 +338,226 @@ impl FileManager {
             return Err(FileOperationError::InvalidPath("Path is a directory".to_string()).into());
         }
 
-        let content = fs::read_to_string(&full_path).await?;
+        let size = fs::metadata(&full_path).await?.len();
+        if !open_anyway && size > self.max_open_file_bytes {
+            return Err(FileOperationError::FileTooLarge { size, limit: self.max_open_file_bytes }.into());
+        }
+
+        let (content, _) = TextEncoding::decode(&fs::read(&full_path).await?, self.fallback_encoding);
         Ok(content)
     }
 
-    /// Write file contents
+    /// Write file contents, replacing any existing file atomically. An existing file
+    /// keeps its encoding; new files are UTF-8.
     pub async fn write_file(&self, path: &Path, content: &str) -> Result<FileOperationResult> {
+        let encoding = self.existing_encoding(&self.base_path.join(path)).await
+            .unwrap_or_default();
+        self.write_file_with_encoding(path, content, encoding).await
+    }
+
+    /// Encoding of the file at `full_path`, told from its first bytes only
+    async fn existing_encoding(&self, full_path: &Path) -> std::io::Result<TextEncoding> {
+        use tokio::io::AsyncReadExt;
+
+        let mut prefix = Vec::with_capacity(encoding::DETECT_BYTES);
+        fs::File::open(full_path).await?
+            .take(encoding::DETECT_BYTES as u64)
+            .read_to_end(&mut prefix)
+            .await?;
+        let whole = prefix.len() < encoding::DETECT_BYTES;
+        Ok(TextEncoding::detect(&prefix, whole, self.fallback_encoding))
+    }
+
+    /// Write file contents in `encoding`, replacing any existing file atomically
+    /// after snapshotting it into the local history
+    pub async fn write_file_with_encoding(&self, path: &Path, content: &str, encoding: TextEncoding) -> Result<FileOperationResult> {
         let full_path = self.base_path.join(path);
         
+        // Keep the previous content recoverable before overwriting it
+        if full_path.is_file() {
+            if let Err(e) = self.snapshot_file(path).await {
+                log::warn!("Failed to snapshot {}: {}", path.display(), e);
+            }
+        }
+        
         // Create parent directories if they don't exist
         if let Some(parent) = full_path.parent() {
             fs::create_dir_all(parent).await?;
         }
 
-        let _bytes_written = fs::write(&full_path, content).await?;
+        let bytes = encoding.encode(content)?;
+        atomic::write_atomic(&full_path, &bytes, self.fsync_on_save).await?;
         
         Ok(FileOperationResult {
             success: true,
             message: "File written successfully".to_string(),
-            bytes_written: Some(content.len() as u64),
+            bytes_written: Some(bytes.len() as u64),
             lines_read: Some(content.lines().count() as u32),
         })
     }
 
+    /// `path` under the project root. Absolute paths and `..` are refused, so a
+    /// request cannot name a file outside the workspace.
+    pub fn resolve(&self, path: &Path) -> Result<PathBuf, FileOperationError> {
+        if path.components().any(|component| !matches!(component, Component::Normal(_) | Component::CurDir)) {
+            return Err(FileOperationError::InvalidPath(format!("{} is outside the workspace", path.display())));
+        }
+        Ok(self.base_path.join(path))
+    }
+
+    /// Open a file to stream its bytes as they are, with its size. Files over the
+    /// size limit are refused.
+    pub async fn open_raw(&self, path: &Path) -> Result<(fs::File, u64)> {
+        let full_path = self.resolve(path)?;
+        if !full_path.exists() {
+            return Err(FileOperationError::FileNotFound(full_path).into());
+        }
+        if full_path.is_dir() {
+            return Err(FileOperationError::InvalidPath("Path is a directory".to_string()).into());
+        }
+
+        let size = fs::metadata(&full_path).await?.len();
+        if size > self.max_open_file_bytes {
+            return Err(FileOperationError::FileTooLarge { size, limit: self.max_open_file_bytes }.into());
+        }
+        Ok((fs::File::open(&full_path).await?, size))
+    }
+
+    /// Write the bytes `reader` yields to a file as they arrive, without decoding
+    /// them, and replace the file once they are all written. More bytes than the
+    /// size limit fail with [`FileOperationError::FileTooLarge`], whose `size` is
+    /// then the bytes received before the write stopped.
+    pub async fn write_raw<R: tokio::io::AsyncRead + Unpin>(&self, path: &Path, reader: R) -> Result<FileOperationResult> {
+        let full_path = self.resolve(path)?;
+        if full_path.is_dir() {
+            return Err(FileOperationError::InvalidPath("Path is a directory".to_string()).into());
+        }
+        if full_path.is_file() {
+            if let Err(e) = self.snapshot_file(path).await {
+                log::warn!("Failed to snapshot {}: {}", path.display(), e);
+            }
+        }
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let limit = self.max_open_file_bytes;
+        let written = match atomic::write_atomic_from(&full_path, reader, limit, self.fsync_on_save).await {
+            Ok(written) => written,
+            Err(e) if e.kind() == std::io::ErrorKind::FileTooLarge => {
+                return Err(FileOperationError::FileTooLarge { size: limit + 1, limit }.into());
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(FileOperationResult {
+            success: true,
+            message: "File written successfully".to_string(),
+            bytes_written: Some(written),
+            lines_read: None,
+        })
+    }
+
+    /// Snapshot the current content of a file into the local history
+    async fn snapshot_file(&self, path: &Path) -> Result<()> {
+        let full_path = self.resolve(path)?;
+
+        if is_secret_path(path) || path.starts_with(".super-ide") {
+            return Ok(());
+        }
+
+        let metadata = fs::metadata(&full_path).await?;
+        if metadata.len() > MAX_SNAPSHOT_BYTES {
+            return Ok(());
+        }
+
+        let content = fs::read(&full_path).await?;
+        if content.iter().take(8192).any(|b| *b == 0) {
+            return Ok(()); // Binary file
+        }
+
+        let history_dir = self.history_dir(path)?;
+        fs::create_dir_all(&history_dir).await?;
+        let timestamp = Utc::now().timestamp_millis();
+        let mut n = 0;
+        let mut snapshot = loop {
+            let name = format!("{}-{}", timestamp, n);
+            match fs::OpenOptions::new().write(true).create_new(true).open(history_dir.join(name)).await {
+                Ok(file) => break file,
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => n += 1,
+                Err(e) => return Err(e.into()),
+            }
+        };
+        tokio::io::AsyncWriteExt::write_all(&mut snapshot, &content).await?;
+
+        // Prune the oldest snapshots beyond the cap
+        let entries = self.list_history(path).await?;
+        for entry in entries.iter().skip(MAX_HISTORY_ENTRIES) {
+            let _ = fs::remove_file(history_dir.join(&entry.id)).await;
+        }
+
+        Ok(())
+    }
+
+    /// List saved snapshots of a file, newest first
+    pub async fn list_history(&self, path: &Path) -> Result<Vec<HistoryEntry>> {
+        let history_dir = self.history_dir(path)?;
+        if !history_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut entries = Vec::new();
+        let mut dir = fs::read_dir(&history_dir).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            let Some(id) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            let Some((timestamp, n)) = parse_snapshot_id(&id) else {
+                continue;
+            };
+            let metadata = entry.metadata().await?;
+            entries.push((n, HistoryEntry {
+                id,
+                timestamp,
+                created_at: Utc.timestamp_millis_opt(timestamp).single().unwrap_or_else(Utc::now),
+                size: metadata.len(),
+            }));
+        }
+
+        entries.sort_by(|(a_n, a), (b_n, b)| (b.timestamp, b_n).cmp(&(a.timestamp, a_n)));
+        Ok(entries.into_iter().map(|(_, entry)| entry).collect())
+    }
+
+    /// Restore a file to a saved snapshot; the current content is snapshotted first
+    pub async fn restore_history(&self, path: &Path, id: &str) -> Result<FileOperationResult> {
+        if parse_snapshot_id(id).is_none() {
+            return Err(FileOperationError::InvalidPath(format!("no snapshot {}", id)).into());
+        }
+        let snapshot_path = self.history_dir(path)?.join(id);
+        if !snapshot_path.is_file() {
+            return Err(FileOperationError::FileNotFound(snapshot_path).into());
+        }
+
+        // Snapshots hold the file's bytes as they were, in whatever encoding it had
+        let (content, encoding) = TextEncoding::decode(&fs::read(&snapshot_path).await?, self.fallback_encoding);
+        let mut result = self.write_file_with_encoding(path, &content, encoding).await?;
+        result.message = format!("Restored snapshot {}", id);
+        Ok(result)
+    }
+
+    /// Directory holding the snapshots of one file; paths outside the workspace have none
+    fn history_dir(&self, path: &Path) -> Result<PathBuf, FileOperationError> {
+        self.resolve(path)?;
+        Ok(self.base_path.join(HISTORY_DIR).join(path))
+    }
+
     /// Create a new file
     pub async fn create_file(&self, path: &Path, content: Option<&str>) -> Result<FileOperationResult> {
         let content = content.unwrap_or("");
         self.write_file(path, content).await
     }
 
-    /// Delete a file
+    /// Delete a file, moving it to the trash unless that is turned off
     pub async fn delete_file(&self, path: &Path) -> Result<FileOperationResult> {
         let full_path = self.base_path.join(path);
         
@@ -206,11 +569,11 @@ impl FileManager {
             return Err(FileOperationError::InvalidPath("Use delete_directory for directories".to_string()).into());
         }
 
-        fs::remove_file(&full_path).await?;
+        let message = self.remove_path(path, false).await?;
         
         Ok(FileOperationResult {
             success: true,
-            message: "File deleted successfully".to_string(),
+            message,
             bytes_written: None,
             lines_read: None,
         })
@@ -230,7 +593,7 @@ impl FileManager {
         })
     }
 
-    /// Delete a directory
+    /// Delete a directory, moving it to the trash unless that is turned off
     pub async fn delete_directory(&self, path: &Path) -> Result<FileOperationResult> {
         let full_path = self.base_path.join(path);
         
@@ -242,11 +605,11 @@ impl FileManager {
             return Err(FileOperationError::InvalidPath("Path is not a directory".to_string()).into());
         }
 
-        fs::remove_dir_all(&full_path).await?;
+        let message = self.remove_path(path, true).await?;
         
         Ok(FileOperationResult {
             success: true,
-            message: "Directory deleted successfully".to_string(),
+            message,
             bytes_written: None,
             lines_read: None,
         })
@@ -377,6 +740,69 @@ impl FileManager {
         })
     }
 
+    /// Get project structure, skipping anything excluded by .gitignore and other ignore files
+    pub async fn scan_project(&self, max_depth: u32) -> Result<ProjectStructure> {
+        let mut files = Vec::new();
+        let mut directories = Vec::new();
+        let mut total_size = 0u64;
+
+        let walker = ignore::WalkBuilder::new(&self.base_path)
+            .max_depth(Some(max_depth as usize))
+            .require_git(false)
+            .build();
+
+        for entry in walker.flatten() {
+            let path = entry.path();
+            if path == self.base_path {
+                continue;
+            }
+
+            let metadata = match entry.metadata() {
+                Ok(m) => m,
+                Err(_) => continue,
+            };
+            let file_name = path.file_name()
+                .and_then(|s| s.to_str())
+                .unwrap_or("");
+            let relative_path = path.strip_prefix(&self.base_path).unwrap_or(path);
+
+            let file_info = FileInfo {
+                path: relative_path.to_path_buf(),
+                name: file_name.to_string(),
+                extension: path.extension()
+                    .and_then(|s| s.to_str())
+                    .map(|s| s.to_string()),
+                size: metadata.len(),
+                is_directory: metadata.is_dir(),
+                created_at: metadata.created()
+                    .map(DateTime::<Utc>::from)
+                    .unwrap_or_else(|_| Utc::now()),
+                modified_at: metadata.modified()
+                    .map(DateTime::<Utc>::from)
+                    .unwrap_or_else(|_| Utc::now()),
+                is_hidden: file_name.starts_with('.'),
+                git_status: None,
+            };
+
+            total_size += file_info.size;
+
+            if file_info.is_directory {
+                directories.push(file_info);
+            } else {
+                files.push(file_info);
+            }
+        }
+
+        let total_files = files.len() + directories.len();
+        Ok(ProjectStructure {
+            root_path: self.base_path.clone(),
+            files,
+            directories,
+            total_files,
+            total_size,
+        })
+    }
+
     /// Search for files by name
     pub async fn search_files(&self, pattern: &str, case_sensitive: bool) -> Result<Vec<FileInfo>> {
         let mut results = Vec::new();
@@ -465,34 +891,7 @@ impl FileManager {
 
     /// Detect language from file extension
     pub fn detect_language(&self, path: &Path) -> String {
-        match path.extension()
-            .and_then(|ext| ext.to_str())
-            .map(|ext| ext.to_lowercase())
-            .as_deref() {
-            Some("rs") => "rust",
-            Some("py") => "python", 
-            Some("js") => "javascript",
-            Some("ts") => "typescript",
-            Some("java") => "java",
-            Some("cpp") | Some("cc") | Some("cxx") => "cpp",
-            Some("c") => "c",
-            Some("cs") => "csharp",
-            Some("go") => "go",
-            Some("php") => "php",
-            Some("rb") => "ruby",
-            Some("swift") => "swift",
-            Some("kt") => "kotlin",
-            Some("scala") => "scala",
-            Some("sh") | Some("bash") => "shell",
-            Some("html") => "html",
-            Some("css") => "css",
-            Some("json") => "json",
-            Some("xml") => "xml",
-            Some("yaml") | Some("yml") => "yaml",
-            Some("md") => "markdown",
-            Some("txt") => "plaintext",
-            _ => "plaintext",
-        }.to_string()
+        language_for_path(path).to_string()
     }
 
     /// Get file info for a specific file
@@ -527,3 +926,122 @@ impl FileManager {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A temporary workspace holding `files`, given as paths and contents
+    pub(super) fn fixture(files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("super-ide-files-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        for (path, content) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        root
+    }
+
+    #[tokio::test]
+    async fn test_write_snapshot_restore_round_trip() {
+        let root = std::env::temp_dir().join(format!("super-ide-history-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let manager = FileManager::new(root.clone());
+        let path = Path::new("src/main.rs");
+
+        manager.write_file(path, "fn main() {}").await.unwrap();
+        assert!(manager.list_history(path).await.unwrap().is_empty());
+
+        manager.write_file(path, "fn main() { broken").await.unwrap();
+        let history = manager.list_history(path).await.unwrap();
+        assert_eq!(history.len(), 1);
+
+        manager.restore_history(path, &history[0].id).await.unwrap();
+        assert_eq!(manager.read_file(path).await.unwrap(), "fn main() {}");
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_back_to_back_saves_keep_every_snapshot() {
+        let root = std::env::temp_dir().join(format!("super-ide-history-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let manager = FileManager::new(root.clone());
+        let path = Path::new("notes.txt");
+
+        for version in 0..4 {
+            manager.write_file(path, &format!("version {}", version)).await.unwrap();
+        }
+        let history = manager.list_history(path).await.unwrap();
+        assert_eq!(history.len(), 3);
+
+        // Newest first, even within one millisecond
+        manager.restore_history(path, &history[0].id).await.unwrap();
+        assert_eq!(manager.read_file(path).await.unwrap(), "version 2");
+        assert!(manager.restore_history(path, "../../notes.txt").await.is_err());
+        assert!(manager.list_history(Path::new("../notes.txt")).await.is_err());
+        assert!(manager.restore_history(Path::new("../../notes.txt"), &history[0].id).await.is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_secret_files_are_not_snapshotted() {
+        let root = std::env::temp_dir().join(format!("super-ide-history-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let manager = FileManager::new(root.clone());
+        let path = Path::new(".env");
+
+        manager.write_file(path, "TOKEN=one").await.unwrap();
+        manager.write_file(path, "TOKEN=two").await.unwrap();
+        assert!(manager.list_history(path).await.unwrap().is_empty());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_file_rejects_file_over_limit() {
+        let root = std::env::temp_dir().join(format!("super-ide-limit-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("big.txt"), "x".repeat(1025)).unwrap();
+        let manager = FileManager::new(root.clone()).with_max_open_file_bytes(1024);
+        let path = Path::new("big.txt");
+
+        let err = manager.read_file(path).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FileOperationError>(),
+            Some(FileOperationError::FileTooLarge { size: 1025, limit: 1024 })
+        ));
+        assert_eq!(manager.read_file_with_override(path, true).await.unwrap().len(), 1025);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_binary_upload_downloads_byte_for_byte() {
+        use tokio::io::AsyncReadExt;
+
+        let root = std::env::temp_dir().join(format!("super-ide-raw-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let manager = FileManager::new(root.clone()).with_max_open_file_bytes(1024);
+        let image: Vec<u8> = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0xff, 0xfe, 0x80].into_iter().cycle().take(300).collect();
+        let path = Path::new("assets/logo.png");
+
+        let result = manager.write_raw(path, image.as_slice()).await.unwrap();
+        assert_eq!(result.bytes_written, Some(300));
+        let (mut file, size) = manager.open_raw(path).await.unwrap();
+        let mut downloaded = Vec::new();
+        file.read_to_end(&mut downloaded).await.unwrap();
+        assert_eq!((size, downloaded), (300, image.clone()));
+
+        let too_large = manager.write_raw(path, vec![0u8; 2048].as_slice()).await.unwrap_err();
+        assert!(matches!(too_large.downcast_ref::<FileOperationError>(), Some(FileOperationError::FileTooLarge { limit: 1024, .. })));
+        assert_eq!(std::fs::read(root.join(path)).unwrap(), image);
+        let escape = manager.write_raw(Path::new("../escape.png"), image.as_slice()).await.unwrap_err();
+        assert!(matches!(escape.downcast_ref::<FileOperationError>(), Some(FileOperationError::InvalidPath(_))));
+        assert!(manager.open_raw(Path::new("/etc/hostname")).await.is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,396 @@
+//! Outdated dependency checks
+//!
+//! Looks up the latest published version of each dependency on crates.io, npm or
+//! PyPI and compares it with the version the manifest asks for. Registry replies
+//! are cached and lookups are spaced out, so refreshing the dependency panel does
+//! not hammer the registries. A lookup that fails leaves the dependency with an
+//! `unknown` status instead of failing the whole check.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::{Mutex, Semaphore};
+use tokio::time::{Duration, Instant};
+
+use super::dependencies::{Dependency, Ecosystem};
+use crate::config::ProxySettings;
+
+/// How long a registry reply is reused
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Most registry requests in flight at once
+const MAX_CONCURRENT_LOOKUPS: usize = 4;
+
+/// Minimum gap between the start of two registry requests
+const MIN_LOOKUP_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Registry endpoints queried for latest versions
+#[derive(Debug, Clone)]
+pub struct RegistryUrls {
+    pub crates_io: String,
+    pub npm: String,
+    pub pypi: String,
+}
+
+impl Default for RegistryUrls {
+    fn default() -> Self {
+        Self {
+            crates_io: "https://crates.io/api/v1/crates".to_string(),
+            npm: "https://registry.npmjs.org".to_string(),
+            pypi: "https://pypi.org/pypi".to_string(),
+        }
+    }
+}
+
+/// Result of comparing a dependency with its registry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutdatedStatus {
+    UpToDate,
+    Outdated,
+    /// The registry could not be reached, or the dependency has no registry version
+    Unknown,
+}
+
+/// A dependency with its latest published version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutdatedReport {
+    #[serde(flatten)]
+    pub dependency: Dependency,
+    /// Version the requirement is based on, e.g. `1.2` for `^1.2`
+    pub current: Option<String>,
+    pub latest: Option<String>,
+    pub status: OutdatedStatus,
+    /// Whether moving to `latest` crosses a semver-incompatible boundary
+    pub major_bump: bool,
+    /// Why the status is unknown
+    pub error: Option<String>,
+}
+
+#[derive(Debug)]
+struct CachedVersion {
+    latest: String,
+    fetched_at: Instant,
+}
+
+/// Checks dependencies against their registries
+#[derive(Debug)]
+pub struct OutdatedChecker {
+    client: reqwest::Client,
+    registries: RegistryUrls,
+    cache: Mutex<HashMap<(Ecosystem, String), CachedVersion>>,
+    lookups: Semaphore,
+    next_lookup: Mutex<Instant>,
+}
+
+impl Default for OutdatedChecker {
+    fn default() -> Self {
+        Self::new(RegistryUrls::default())
+    }
+}
+
+fn build_client(proxy: &ProxySettings) -> reqwest::Client {
+    let builder = crate::utils::http::client_builder(proxy).unwrap_or_else(|e| {
+        log::warn!("Ignoring invalid proxy configuration: {}", e);
+        reqwest::Client::builder()
+    });
+    builder
+        // crates.io rejects requests without a user agent
+        .user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+        .timeout(std::time::Duration::from_secs(10))
+        .build()
+        .unwrap_or_default()
+}
+
+impl OutdatedChecker {
+    /// Create a checker querying `registries`
+    pub fn new(registries: RegistryUrls) -> Self {
+        Self {
+            client: build_client(&ProxySettings::default()),
+            registries,
+            cache: Mutex::new(HashMap::new()),
+            lookups: Semaphore::new(MAX_CONCURRENT_LOOKUPS),
+            next_lookup: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Send registry lookups through `proxy`
+    pub fn with_proxy(mut self, proxy: &ProxySettings) -> Self {
+        self.client = build_client(proxy);
+        self
+    }
+
+    /// Compare each dependency with the latest version on its registry
+    pub async fn check(&self, dependencies: Vec<Dependency>) -> Vec<OutdatedReport> {
+        let mut packages: Vec<(Ecosystem, String)> = Vec::new();
+        for dependency in dependencies.iter().filter(|dependency| unpublished_source(dependency).is_none()) {
+            let package = (dependency.ecosystem, dependency.name.clone());
+            if !packages.contains(&package) {
+                packages.push(package);
+            }
+        }
+
+        let lookups = packages.iter().map(|(ecosystem, name)| async move {
+            ((*ecosystem, name.clone()), self.latest_version(*ecosystem, name).await)
+        });
+        let latest: HashMap<(Ecosystem, String), Result<String, String>> =
+            futures::future::join_all(lookups).await.into_iter().collect();
+
+        dependencies.into_iter()
+            .map(|dependency| {
+                let lookup = match unpublished_source(&dependency) {
+                    Some(source) => Err(format!("{} dependency, not published on a registry", source)),
+                    None => latest.get(&(dependency.ecosystem, dependency.name.clone())).cloned()
+                        .unwrap_or_else(|| Err("not looked up".to_string())),
+                };
+                compare(dependency, lookup)
+            })
+            .collect()
+    }
+
+    /// Latest published version of `name`, from the cache when fresh
+    async fn latest_version(&self, ecosystem: Ecosystem, name: &str) -> Result<String, String> {
+        let key = (ecosystem, name.to_string());
+        if let Some(cached) = self.cache.lock().await.get(&key) {
+            if cached.fetched_at.elapsed() < CACHE_TTL {
+                return Ok(cached.latest.clone());
+            }
+        }
+
+        let latest = self.fetch_latest_version(ecosystem, name).await?;
+        self.cache.lock().await.insert(key, CachedVersion {
+            latest: latest.clone(),
+            fetched_at: Instant::now(),
+        });
+        Ok(latest)
+    }
+
+    async fn fetch_latest_version(&self, ecosystem: Ecosystem, name: &str) -> Result<String, String> {
+        let _permit = self.lookups.acquire().await.map_err(|e| e.to_string())?;
+        self.throttle().await;
+
+        let url = match ecosystem {
+            Ecosystem::Cargo => format!("{}/{}", self.registries.crates_io, name),
+            // Scoped packages keep their `@` but need the slash escaped
+            Ecosystem::Npm => format!("{}/{}/latest", self.registries.npm, name.replace('/', "%2F")),
+            Ecosystem::Python => format!("{}/{}/json", self.registries.pypi, name),
+        };
+        let response = self.client.get(&url).send().await
+            .map_err(|e| format!("Registry request failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("Registry returned {} for {}", response.status(), name));
+        }
+        let body: serde_json::Value = response.json().await
+            .map_err(|e| format!("Invalid registry response: {}", e))?;
+
+        let version = match ecosystem {
+            Ecosystem::Cargo => body.pointer("/crate/max_stable_version")
+                .filter(|version| !version.is_null())
+                .or_else(|| body.pointer("/crate/max_version")),
+            Ecosystem::Npm => body.get("version"),
+            Ecosystem::Python => body.pointer("/info/version"),
+        };
+        version.and_then(serde_json::Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| format!("Registry response for {} has no version", name))
+    }
+
+    /// Wait until the next registry request is allowed to start
+    async fn throttle(&self) {
+        let mut next_lookup = self.next_lookup.lock().await;
+        tokio::time::sleep_until(*next_lookup).await;
+        *next_lookup = Instant::now() + MIN_LOOKUP_INTERVAL;
+    }
+}
+
+/// Source of a dependency taken from outside the registries, such as a `path`
+/// or `git` Cargo dependency or a Python `name @ url` requirement; a package of
+/// the same name on the registry is unrelated
+fn unpublished_source(dependency: &Dependency) -> Option<&'static str> {
+    if dependency.version_req.starts_with("path:") {
+        Some("Path")
+    } else if dependency.version_req.starts_with("git:") {
+        Some("Git")
+    } else if dependency.version_req.starts_with("url:") {
+        Some("URL")
+    } else {
+        None
+    }
+}
+
+/// Version a requirement such as `^1.2`, `>=2.31,<3` or `~=3.0` is based on
+fn base_version(version_req: &str) -> Option<String> {
+    let first = version_req.split(',').next()?.trim();
+    let version = first.trim_start_matches(['^', '~', '=', '>', '<', '!', 'v', ' ']);
+    let version = version.trim_end_matches(".*");
+    if version.is_empty() || !version.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(version.to_string())
+}
+
+/// Numeric major, minor and patch of a version, missing parts as zero
+fn version_parts(version: &str) -> [u64; 3] {
+    let mut parts = [0; 3];
+    for (part, value) in version.split('.').take(3).zip(parts.iter_mut()) {
+        let digits: String = part.chars().take_while(char::is_ascii_digit).collect();
+        *value = digits.parse().unwrap_or(0);
+    }
+    parts
+}
+
+/// Whether going from `current` to `latest` is a breaking change under semver,
+/// where a bump of the first non-zero component counts as major
+fn is_major_bump(current: [u64; 3], latest: [u64; 3]) -> bool {
+    if current[0] != latest[0] {
+        return latest[0] > current[0];
+    }
+    current[0] == 0 && latest[1] > current[1]
+}
+
+fn compare(dependency: Dependency, lookup: Result<String, String>) -> OutdatedReport {
+    let current = base_version(&dependency.version_req);
+    let mut report = OutdatedReport {
+        dependency,
+        current: current.clone(),
+        latest: None,
+        status: OutdatedStatus::Unknown,
+        major_bump: false,
+        error: None,
+    };
+
+    match (current, lookup) {
+        (_, Err(error)) => report.error = Some(error),
+        (None, Ok(latest)) => report.latest = Some(latest),
+        (Some(current), Ok(latest)) => {
+            let (current, latest_parts) = (version_parts(&current), version_parts(&latest));
+            report.status = if latest_parts > current { OutdatedStatus::Outdated } else { OutdatedStatus::UpToDate };
+            report.major_bump = is_major_bump(current, latest_parts);
+            report.latest = Some(latest);
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_ops::dependencies::DependencyKind;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Registry answering by request path, counting the requests it serves
+    async fn mock_registry() -> (String, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut buf = vec![0u8; 4096];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                let path = request.split_whitespace().nth(1).unwrap_or("").to_string();
+
+                let (status, body) = match path.as_str() {
+                    "/crates/serde" => ("200 OK", r#"{"crate":{"max_stable_version":"1.0.200","max_version":"1.0.200"}}"#),
+                    "/crates/rand" => ("200 OK", r#"{"crate":{"max_stable_version":"0.9.1","max_version":"0.9.1"}}"#),
+                    "/npm/react/latest" => ("200 OK", r#"{"name":"react","version":"19.1.0"}"#),
+                    "/pypi/requests/json" => ("200 OK", r#"{"info":{"version":"2.32.3"}}"#),
+                    _ => ("503 Service Unavailable", r#"{"error":"unavailable"}"#),
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        (format!("http://{}", addr), requests)
+    }
+
+    fn dependency(name: &str, version_req: &str, ecosystem: Ecosystem) -> Dependency {
+        Dependency {
+            name: name.to_string(),
+            version_req: version_req.to_string(),
+            kind: DependencyKind::Normal,
+            ecosystem,
+            manifest: PathBuf::from("manifest"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reports_outdated_against_mock_registry() {
+        let (base, requests) = mock_registry().await;
+        let checker = OutdatedChecker::new(RegistryUrls {
+            crates_io: format!("{}/crates", base),
+            npm: format!("{}/npm", base),
+            pypi: format!("{}/pypi", base),
+        });
+        let dependencies = vec![
+            dependency("serde", "1.0", Ecosystem::Cargo),
+            dependency("rand", "0.8", Ecosystem::Cargo),
+            dependency("react", "^18.2.0", Ecosystem::Npm),
+            dependency("requests", ">=2.32.3,<3", Ecosystem::Python),
+            dependency("missing", "1.0", Ecosystem::Cargo),
+        ];
+
+        let reports = checker.check(dependencies.clone()).await;
+        let status: Vec<(OutdatedStatus, bool)> = reports.iter().map(|r| (r.status, r.major_bump)).collect();
+        assert_eq!(status, vec![
+            (OutdatedStatus::Outdated, false),
+            (OutdatedStatus::Outdated, true),
+            (OutdatedStatus::Outdated, true),
+            (OutdatedStatus::UpToDate, false),
+            (OutdatedStatus::Unknown, false),
+        ]);
+        assert_eq!(reports[0].current.as_deref(), Some("1.0"));
+        assert_eq!(reports[0].latest.as_deref(), Some("1.0.200"));
+        assert!(reports[4].error.as_deref().unwrap().contains("503"));
+        assert_eq!(requests.load(Ordering::SeqCst), 5);
+
+        // Successful lookups are cached, failed ones are retried
+        checker.check(dependencies).await;
+        assert_eq!(requests.load(Ordering::SeqCst), 6);
+    }
+
+    #[tokio::test]
+    async fn test_path_and_git_dependencies_are_not_looked_up() {
+        let (base, requests) = mock_registry().await;
+        let checker = OutdatedChecker::new(RegistryUrls {
+            crates_io: format!("{}/crates", base),
+            npm: format!("{}/npm", base),
+            pypi: format!("{}/pypi", base),
+        });
+        let reports = checker.check(vec![
+            dependency("serde", "path:../serde", Ecosystem::Cargo),
+            dependency("rand", "git:https://github.com/rust-random/rand", Ecosystem::Cargo),
+            dependency("mylib", "url:https://example.com/mylib.whl", Ecosystem::Python),
+        ]).await;
+
+        assert_eq!(requests.load(Ordering::SeqCst), 0);
+        assert!(reports.iter().all(|report| report.status == OutdatedStatus::Unknown && report.latest.is_none()));
+        assert!(reports[0].error.as_deref().unwrap().contains("Path"));
+        assert!(reports[1].error.as_deref().unwrap().contains("Git"));
+        assert!(reports[2].error.as_deref().unwrap().contains("URL"));
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_registry_reports_unknown() {
+        let checker = OutdatedChecker::new(RegistryUrls {
+            crates_io: "http://127.0.0.1:1".to_string(),
+            npm: "http://127.0.0.1:1".to_string(),
+            pypi: "http://127.0.0.1:1".to_string(),
+        });
+        let reports = checker.check(vec![dependency("serde", "1.0", Ecosystem::Cargo)]).await;
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].status, OutdatedStatus::Unknown);
+        assert!(reports[0].error.is_some());
+    }
+}
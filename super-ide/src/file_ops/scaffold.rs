@@ -0,0 +1,139 @@
+//! Project scaffolding
+//!
+//! Templates describe the files of a new project as a `ScaffoldPlan`. Nothing is
+//! written until the plan is applied, so it can be shown to the user first, and
+//! applying it leaves existing files alone unless told to overwrite them.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+
+/// A file a scaffold creates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScaffoldFile {
+    /// Path relative to the project root
+    pub path: PathBuf,
+    pub content: String,
+}
+
+/// Files that make up a new project
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScaffoldPlan {
+    pub root: PathBuf,
+    pub files: Vec<ScaffoldFile>,
+}
+
+/// Scaffolding errors
+#[derive(Debug, thiserror::Error)]
+pub enum ScaffoldError {
+    #[error("Refusing to overwrite existing files: {}", list_paths(.0))]
+    Conflicts(Vec<PathBuf>),
+
+    #[error("Invalid scaffold path: {0}")]
+    InvalidPath(PathBuf),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+fn list_paths(paths: &[PathBuf]) -> String {
+    paths.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(", ")
+}
+
+impl ScaffoldPlan {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into(), files: Vec::new() }
+    }
+
+    /// Add a file at `path`, relative to the project root
+    pub fn file(mut self, path: impl Into<PathBuf>, content: impl Into<String>) -> Self {
+        self.files.push(ScaffoldFile { path: path.into(), content: content.into() });
+        self
+    }
+
+    /// Planned files that already exist on disk, relative to the root
+    pub fn conflicts(&self) -> Vec<PathBuf> {
+        self.files.iter()
+            .filter(|file| self.root.join(&file.path).exists())
+            .map(|file| file.path.clone())
+            .collect()
+    }
+
+    /// Write every file, creating directories as needed, and return the paths
+    /// written. Existing files are only replaced when `force` is set; otherwise
+    /// nothing is written and all of them are reported.
+    pub fn apply(&self, force: bool) -> Result<Vec<PathBuf>, ScaffoldError> {
+        if let Some(file) = self.files.iter().find(|file| !is_relative_inside(&file.path)) {
+            return Err(ScaffoldError::InvalidPath(file.path.clone()));
+        }
+        if !force {
+            let conflicts = self.conflicts();
+            if !conflicts.is_empty() {
+                return Err(ScaffoldError::Conflicts(conflicts));
+            }
+        }
+
+        let mut written = Vec::with_capacity(self.files.len());
+        for file in &self.files {
+            let path = self.root.join(&file.path);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, &file.content)?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+}
+
+/// Whether `path` is relative and stays under the directory it is joined to
+fn is_relative_inside(path: &Path) -> bool {
+    path.components().next().is_some() && path.components().all(|component| matches!(component, Component::Normal(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(root: &Path) -> ScaffoldPlan {
+        ScaffoldPlan::new(root)
+            .file("Cargo.toml", "[package]\nname = \"demo\"\n")
+            .file("src/main.rs", "fn main() {}\n")
+    }
+
+    #[test]
+    fn test_preview_writes_nothing_until_applied() {
+        let root = std::env::temp_dir().join(format!("super-ide-scaffold-{}", uuid::Uuid::new_v4()));
+        let plan = plan(&root);
+
+        assert!(plan.conflicts().is_empty());
+        assert!(!root.exists());
+
+        let written = plan.apply(false).unwrap();
+        assert_eq!(written, vec![root.join("Cargo.toml"), root.join("src/main.rs")]);
+        assert_eq!(std::fs::read_to_string(root.join("src/main.rs")).unwrap(), "fn main() {}\n");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_existing_files_are_reported_and_kept_without_force() {
+        let root = std::env::temp_dir().join(format!("super-ide-scaffold-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "// mine\n").unwrap();
+        let plan = plan(&root);
+
+        assert_eq!(plan.conflicts(), vec![PathBuf::from("src/main.rs")]);
+        match plan.apply(false) {
+            Err(ScaffoldError::Conflicts(conflicts)) => assert_eq!(conflicts, vec![PathBuf::from("src/main.rs")]),
+            other => panic!("expected conflicts, got {:?}", other),
+        }
+        assert_eq!(std::fs::read_to_string(root.join("src/main.rs")).unwrap(), "// mine\n");
+        assert!(!root.join("Cargo.toml").exists());
+
+        plan.apply(true).unwrap();
+        assert_eq!(std::fs::read_to_string(root.join("src/main.rs")).unwrap(), "fn main() {}\n");
+
+        let escaping = ScaffoldPlan::new(&root).file("../outside.txt", "");
+        assert!(matches!(escaping.apply(true), Err(ScaffoldError::InvalidPath(_))));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
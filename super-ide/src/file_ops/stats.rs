@@ -0,0 +1,256 @@
+//! Project size and language statistics
+//!
+//! The walk honors `.gitignore` and stops after a fixed number of files. Lines are
+//! counted in fixed-size chunks, so memory use does not grow with file size. A hash
+//! of every path, size and modification time identifies the tree: when it matches the
+//! previous run, the cached statistics are returned without reading any file.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tokio::sync::RwLock;
+
+use super::language_for_path;
+
+/// Files walked before the statistics are marked truncated
+const DEFAULT_MAX_FILES: usize = 100_000;
+
+/// Number of entries in `largest_files`
+const LARGEST_FILES: usize = 10;
+
+/// Files and lines for one language
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LanguageStats {
+    pub language: String,
+    pub files: usize,
+    pub lines: u64,
+    pub bytes: u64,
+}
+
+/// A file and its size
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileSize {
+    pub path: PathBuf,
+    pub bytes: u64,
+}
+
+/// Project statistics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectStats {
+    /// Most lines first
+    pub languages: Vec<LanguageStats>,
+    pub total_files: usize,
+    pub total_lines: u64,
+    pub total_bytes: u64,
+    /// Largest first
+    pub largest_files: Vec<FileSize>,
+    /// Whether the walk stopped at the file limit
+    pub truncated: bool,
+    /// Hash of the paths, sizes and modification times the statistics were computed from
+    pub tree_hash: String,
+}
+
+/// A file found by the walk
+struct WalkedFile {
+    path: PathBuf,
+    bytes: u64,
+    modified: Option<std::time::SystemTime>,
+}
+
+/// Computes project statistics, reusing the last result while the tree is unchanged
+#[derive(Debug)]
+pub struct ProjectStatsCollector {
+    max_files: usize,
+    cache: RwLock<HashMap<PathBuf, ProjectStats>>,
+}
+
+impl Default for ProjectStatsCollector {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_FILES)
+    }
+}
+
+impl ProjectStatsCollector {
+    /// Create a collector walking at most `max_files` files per project
+    pub fn new(max_files: usize) -> Self {
+        Self {
+            max_files,
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Statistics for the project at `root`
+    pub async fn collect(&self, root: &Path) -> Result<ProjectStats> {
+        let root = root.to_path_buf();
+        let max_files = self.max_files;
+        let walk_root = root.clone();
+        let (files, truncated) = tokio::task::spawn_blocking(move || walk(&walk_root, max_files)).await?;
+        let tree_hash = tree_hash(&files);
+
+        if let Some(cached) = self.cache.read().await.get(&root) {
+            if cached.tree_hash == tree_hash {
+                return Ok(cached.clone());
+            }
+        }
+
+        let count_root = root.clone();
+        let mut stats = tokio::task::spawn_blocking(move || summarize(&count_root, &files)).await?;
+        stats.truncated = truncated;
+        stats.tree_hash = tree_hash;
+
+        self.cache.write().await.insert(root, stats.clone());
+        Ok(stats)
+    }
+}
+
+/// Files under `root` not excluded by ignore files, sorted by path, and whether the limit was hit
+fn walk(root: &Path, max_files: usize) -> (Vec<WalkedFile>, bool) {
+    let mut files = Vec::new();
+    let mut truncated = false;
+
+    for entry in ignore::WalkBuilder::new(root).require_git(false).build().flatten() {
+        if !entry.file_type().is_some_and(|file_type| file_type.is_file()) {
+            continue;
+        }
+        if files.len() == max_files {
+            truncated = true;
+            break;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        files.push(WalkedFile {
+            path: entry.path().strip_prefix(root).unwrap_or(entry.path()).to_path_buf(),
+            bytes: metadata.len(),
+            modified: metadata.modified().ok(),
+        });
+    }
+
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    (files, truncated)
+}
+
+fn tree_hash(files: &[WalkedFile]) -> String {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    for file in files {
+        file.path.hash(&mut hasher);
+        file.bytes.hash(&mut hasher);
+        file.modified.hash(&mut hasher);
+    }
+    format!("{:x}", hasher.finish())
+}
+
+fn summarize(root: &Path, files: &[WalkedFile]) -> ProjectStats {
+    let mut languages: HashMap<&'static str, LanguageStats> = HashMap::new();
+    let mut total_lines = 0;
+
+    for file in files {
+        let lines = count_lines(&root.join(&file.path)).unwrap_or(0);
+        let language = language_for_path(&file.path);
+        let stats = languages.entry(language).or_insert_with(|| LanguageStats {
+            language: language.to_string(),
+            files: 0,
+            lines: 0,
+            bytes: 0,
+        });
+        stats.files += 1;
+        stats.lines += lines;
+        stats.bytes += file.bytes;
+        total_lines += lines;
+    }
+
+    let mut languages: Vec<LanguageStats> = languages.into_values().collect();
+    languages.sort_by(|a, b| b.lines.cmp(&a.lines).then(b.bytes.cmp(&a.bytes)).then_with(|| a.language.cmp(&b.language)));
+
+    let mut largest_files: Vec<FileSize> = files.iter()
+        .map(|file| FileSize { path: file.path.clone(), bytes: file.bytes })
+        .collect();
+    largest_files.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.path.cmp(&b.path)));
+    largest_files.truncate(LARGEST_FILES);
+
+    ProjectStats {
+        languages,
+        total_files: files.len(),
+        total_lines,
+        total_bytes: files.iter().map(|file| file.bytes).sum(),
+        largest_files,
+        truncated: false,
+        tree_hash: String::new(),
+    }
+}
+
+/// Number of lines in a text file, counting a last line without a newline; binary files have none
+fn count_lines(path: &Path) -> std::io::Result<u64> {
+    let mut file = std::fs::File::open(path)?;
+    let mut buffer = [0u8; 64 * 1024];
+    let mut lines = 0;
+    let mut last = None;
+    let mut first_chunk = true;
+
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        let chunk = &buffer[..read];
+        if first_chunk && chunk.contains(&0) {
+            return Ok(0);
+        }
+        first_chunk = false;
+        lines += chunk.iter().filter(|&&byte| byte == b'\n').count() as u64;
+        last = chunk.last().copied();
+    }
+
+    if last.is_some_and(|byte| byte != b'\n') {
+        lines += 1;
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_ops::tests::fixture;
+
+    #[tokio::test]
+    async fn test_lines_per_language() {
+        let root = fixture(&[
+            (".gitignore", "target/\n"),
+            ("src/main.rs", "fn main() {\n    run();\n}\n"),
+            ("src/lib.rs", "pub fn run() {}"),
+            ("scripts/build.py", "import os\n\nprint(os.getcwd())\n"),
+            ("scripts/util.py", "x = 1\n"),
+            ("target/debug/generated.rs", "fn ignored() {}\n".repeat(100).as_str()),
+            ("logo.png", "\u{0}PNG data\nmore\n"),
+        ]);
+        let collector = ProjectStatsCollector::default();
+
+        let stats = collector.collect(&root).await.unwrap();
+        let by_language: Vec<(&str, usize, u64)> = stats.languages.iter()
+            .map(|language| (language.language.as_str(), language.files, language.lines))
+            .collect();
+        assert_eq!(by_language, vec![("rust", 2, 4), ("python", 2, 4), ("plaintext", 1, 0)]);
+        assert_eq!(stats.total_files, 5);
+        assert_eq!(stats.total_lines, 8);
+        assert_eq!(stats.largest_files[0].path, Path::new("scripts/build.py"));
+        assert!(!stats.truncated);
+
+        // Unchanged tree hits the cache, an edit is picked up
+        assert_eq!(collector.collect(&root).await.unwrap().tree_hash, stats.tree_hash);
+        std::fs::write(root.join("src/lib.rs"), "pub fn run() {}\npub fn stop() {}\n").unwrap();
+        let updated = collector.collect(&root).await.unwrap();
+        assert_ne!(updated.tree_hash, stats.tree_hash);
+        assert_eq!(updated.languages[0].lines, 5);
+
+        let limited = ProjectStatsCollector::new(2).collect(&root).await.unwrap();
+        assert!(limited.truncated);
+        assert_eq!(limited.total_files, 2);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
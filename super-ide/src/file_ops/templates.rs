@@ -0,0 +1,256 @@
+//! Project templates
+//!
+//! A template is a TOML file naming the files of a new project, whose paths and
+//! contents may use `{{placeholder}}`s. `{{name}}` is always the project name; a
+//! template's `[placeholders]` table gives defaults for any others, which may
+//! themselves use the values the caller passed. A placeholder with no value is an
+//! error rather than being left in the output.
+//!
+//! Templates ship built in, and `.toml` files in a user templates directory are
+//! added to them, replacing a built-in template with the same file stem.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use super::scaffold::ScaffoldPlan;
+
+/// Built-in templates, by id
+const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    ("cpp", include_str!("templates/cpp.toml")),
+    ("go", include_str!("templates/go.toml")),
+    ("javascript", include_str!("templates/javascript.toml")),
+    ("python", include_str!("templates/python.toml")),
+    ("rust", include_str!("templates/rust.toml")),
+    ("typescript", include_str!("templates/typescript.toml")),
+];
+
+static PLACEHOLDER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{\{\s*([A-Za-z_][A-Za-z0-9_]*)\s*\}\}").unwrap());
+
+/// A file a template creates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateFile {
+    /// Path relative to the project root
+    pub path: String,
+    pub content: String,
+}
+
+/// A project template
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectTemplate {
+    /// The template file's stem, e.g. `go`
+    #[serde(default, skip_deserializing)]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Defaults for placeholders other than `name`
+    #[serde(default)]
+    pub placeholders: BTreeMap<String, String>,
+    pub files: Vec<TemplateFile>,
+}
+
+/// A template as listed to users, without its files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateSummary {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    /// Every placeholder the template uses, `name` included
+    pub placeholders: Vec<String>,
+}
+
+/// Template errors
+#[derive(Debug, thiserror::Error)]
+pub enum TemplateError {
+    #[error("Unknown template: {0}")]
+    UnknownTemplate(String),
+
+    #[error("Template {template} uses {{{{{placeholder}}}}}, which has no value")]
+    UndefinedPlaceholder { template: String, placeholder: String },
+
+    #[error("Invalid template {path}: {reason}")]
+    Invalid { path: PathBuf, reason: String },
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// The templates new projects can be created from
+#[derive(Debug, Clone)]
+pub struct TemplateRegistry {
+    templates: BTreeMap<String, ProjectTemplate>,
+}
+
+impl TemplateRegistry {
+    /// Only the built-in templates
+    pub fn builtin() -> Self {
+        let templates = BUILTIN_TEMPLATES.iter()
+            .map(|(id, source)| {
+                let mut template: ProjectTemplate = toml::from_str(source)
+                    .unwrap_or_else(|e| panic!("built-in template {} is invalid: {}", id, e));
+                template.id = id.to_string();
+                (id.to_string(), template)
+            })
+            .collect();
+        Self { templates }
+    }
+
+    /// The built-in templates plus the `.toml` templates in `dir`, which need not exist.
+    /// A template that cannot be read or parsed is skipped with a warning.
+    pub fn load(dir: &Path) -> Result<Self, TemplateError> {
+        let mut registry = Self::builtin();
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(registry),
+            Err(e) => return Err(e.into()),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("toml") {
+                continue;
+            }
+            let Some(id) = path.file_stem().and_then(|stem| stem.to_str()).map(str::to_string) else {
+                continue;
+            };
+            let parsed = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| toml::from_str::<ProjectTemplate>(&text).map_err(|e| e.to_string()));
+            let mut template = match parsed {
+                Ok(template) => template,
+                Err(reason) => {
+                    log::warn!("Skipping {}", TemplateError::Invalid { path, reason });
+                    continue;
+                }
+            };
+            template.id = id.clone();
+            registry.templates.insert(id, template);
+        }
+        Ok(registry)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ProjectTemplate> {
+        self.templates.get(id)
+    }
+
+    /// Every template, sorted by id
+    pub fn list(&self) -> Vec<TemplateSummary> {
+        self.templates.values().map(ProjectTemplate::summary).collect()
+    }
+
+    /// Plan the files of project `name` at `root` from template `id`. `values` fill
+    /// placeholders other than `name`, overriding the template's defaults.
+    pub fn plan(&self, id: &str, root: impl Into<PathBuf>, name: &str, values: &HashMap<String, String>) -> Result<ScaffoldPlan, TemplateError> {
+        let template = self.get(id).ok_or_else(|| TemplateError::UnknownTemplate(id.to_string()))?;
+
+        let mut values = values.clone();
+        values.insert("name".to_string(), name.to_string());
+        let defaults = template.placeholders.iter()
+            .filter(|(placeholder, _)| !values.contains_key(*placeholder))
+            .map(|(placeholder, default)| Ok((placeholder.clone(), template.substitute(default, &values)?)))
+            .collect::<Result<Vec<_>, TemplateError>>()?;
+        values.extend(defaults);
+
+        template.files.iter().try_fold(ScaffoldPlan::new(root), |plan, file| {
+            Ok(plan.file(template.substitute(&file.path, &values)?, template.substitute(&file.content, &values)?))
+        })
+    }
+}
+
+impl ProjectTemplate {
+    fn summary(&self) -> TemplateSummary {
+        let mut placeholders: Vec<String> = self.files.iter()
+            .flat_map(|file| [file.path.as_str(), file.content.as_str()])
+            .chain(self.placeholders.values().map(String::as_str))
+            .flat_map(|text| PLACEHOLDER.captures_iter(text).map(|captures| captures[1].to_string()))
+            .chain(self.placeholders.keys().cloned())
+            .collect();
+        placeholders.sort();
+        placeholders.dedup();
+        TemplateSummary {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            description: self.description.clone(),
+            placeholders,
+        }
+    }
+
+    /// Replace every placeholder in `text`
+    fn substitute(&self, text: &str, values: &HashMap<String, String>) -> Result<String, TemplateError> {
+        if let Some(captures) = PLACEHOLDER.captures_iter(text).find(|captures| !values.contains_key(&captures[1])) {
+            return Err(TemplateError::UndefinedPlaceholder {
+                template: self.id.clone(),
+                placeholder: captures[1].to_string(),
+            });
+        }
+        Ok(PLACEHOLDER.replace_all(text, |captures: &regex::Captures| values[&captures[1]].clone()).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_templates_render() {
+        let registry = TemplateRegistry::builtin();
+        for summary in registry.list() {
+            let plan = registry.plan(&summary.id, "/tmp/demo", "demo", &HashMap::new()).unwrap();
+            assert!(!plan.files.is_empty(), "{} has no files", summary.id);
+            assert!(plan.files.iter().all(|file| !file.content.contains("{{")), "{} left a placeholder", summary.id);
+        }
+
+        let go = registry.plan("go", "/tmp/demo", "demo", &HashMap::new()).unwrap();
+        assert!(go.files[0].content.starts_with("module example.com/demo\n"));
+        let python = registry.plan("python", "/tmp/demo", "demo", &HashMap::new()).unwrap();
+        assert!(python.files.iter().any(|file| file.path == Path::new("demo/main.py")));
+    }
+
+    #[test]
+    fn test_custom_template_scaffolds_a_project() {
+        let dir = std::env::temp_dir().join(format!("super-ide-templates-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("flask.toml"), r#"
+name = "Flask"
+description = "Flask app"
+
+[placeholders]
+port = "5000"
+package = "{{name}}_app"
+
+[[files]]
+path = "{{package}}/__init__.py"
+content = "from flask import Flask\napp = Flask('{{ name }}')\n"
+
+[[files]]
+path = "run.sh"
+content = "flask --app {{package}} run --port {{port}}\n"
+"#).unwrap();
+        std::fs::write(dir.join("broken.toml"), "name = \"Broken\"\nfiles = [{ path = \"x\", content = \"{{missing}}\" }]\n").unwrap();
+        std::fs::write(dir.join("typo.toml"), "name = \"Typo\"\n[[files]\n").unwrap();
+
+        let registry = TemplateRegistry::load(&dir).unwrap();
+        assert!(registry.get("typo").is_none());
+        let flask = registry.list().into_iter().find(|template| template.id == "flask").unwrap();
+        assert_eq!(flask.placeholders, vec!["name", "package", "port"]);
+        assert!(registry.get("rust").is_some());
+
+        let root = dir.join("shop");
+        let values = HashMap::from([("port".to_string(), "8080".to_string())]);
+        registry.plan("flask", &root, "shop", &values).unwrap().apply(false).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(root.join("shop_app/__init__.py")).unwrap(),
+            "from flask import Flask\napp = Flask('shop')\n"
+        );
+        assert_eq!(std::fs::read_to_string(root.join("run.sh")).unwrap(), "flask --app shop_app run --port 8080\n");
+
+        assert!(matches!(
+            registry.plan("broken", &root, "shop", &HashMap::new()),
+            Err(TemplateError::UndefinedPlaceholder { placeholder, .. }) if placeholder == "missing"
+        ));
+        assert!(matches!(registry.plan("cobol", &root, "shop", &HashMap::new()), Err(TemplateError::UnknownTemplate(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,196 @@
+//! Recoverable deletion
+//!
+//! Deleted files and directories are moved into `.super-ide/trash/` under the
+//! project root instead of being removed, and recorded in a manifest with the path
+//! they came from so they can be put back. When the move fails, for example on a
+//! read-only quarantine or a path the trash lives inside, the deletion falls back
+//! to removing the file for good and logs a warning.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::sync::Mutex;
+
+use super::{FileManager, FileOperationError};
+
+/// Directory, relative to the project root, holding deleted files
+const TRASH_DIR: &str = ".super-ide/trash";
+
+/// Manifest of the trash, inside `TRASH_DIR`
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Held while a manifest is read, changed and written back, so concurrent
+/// deletions do not drop each other's entries. File managers are created per
+/// request, so the lock is shared by all of them.
+static MANIFEST_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// A deleted file or directory that can be restored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub id: String,
+    /// Path the entry was deleted from, relative to the project root
+    pub original_path: PathBuf,
+    pub deleted_at: DateTime<Utc>,
+    pub is_dir: bool,
+}
+
+impl FileManager {
+    /// Move `path` into the trash, or remove it permanently when trash is off or
+    /// the move fails. The returned message says which happened.
+    pub(super) async fn remove_path(&self, path: &Path, is_dir: bool) -> Result<String> {
+        let kind = if is_dir { "Directory" } else { "File" };
+        if self.delete_to_trash {
+            match self.move_to_trash(path, is_dir).await {
+                Ok(entry) => return Ok(format!("{} moved to trash ({})", kind, entry.id)),
+                Err(e) => log::warn!("Could not move {} to trash, deleting it permanently: {}", path.display(), e),
+            }
+        }
+
+        let full_path = self.base_path.join(path);
+        if is_dir {
+            fs::remove_dir_all(&full_path).await?;
+        } else {
+            fs::remove_file(&full_path).await?;
+        }
+        Ok(format!("{} deleted permanently", kind))
+    }
+
+    async fn move_to_trash(&self, path: &Path, is_dir: bool) -> Result<TrashEntry> {
+        let trash_dir = self.base_path.join(TRASH_DIR);
+        fs::create_dir_all(&trash_dir).await?;
+
+        let _manifest = MANIFEST_LOCK.lock().await;
+        // An unreadable manifest must fail the move before anything is moved
+        let mut entries = self.read_trash_manifest().await?;
+        let entry = TrashEntry {
+            id: uuid::Uuid::new_v4().simple().to_string(),
+            original_path: path.to_path_buf(),
+            deleted_at: Utc::now(),
+            is_dir,
+        };
+        fs::rename(self.base_path.join(path), trash_dir.join(&entry.id)).await?;
+
+        entries.push(entry.clone());
+        if let Err(e) = self.write_trash_manifest(&entries).await {
+            // Without a manifest entry the file could not be found again
+            let _ = fs::rename(trash_dir.join(&entry.id), self.base_path.join(path)).await;
+            return Err(e);
+        }
+        Ok(entry)
+    }
+
+    /// Deleted entries that can be restored, most recently deleted first
+    pub async fn list_trash(&self) -> Result<Vec<TrashEntry>> {
+        let trash_dir = self.base_path.join(TRASH_DIR);
+        let mut entries: Vec<TrashEntry> = self.read_trash_manifest().await?
+            .into_iter()
+            .filter(|entry| trash_dir.join(&entry.id).exists())
+            .collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.deleted_at));
+        Ok(entries)
+    }
+
+    /// Move a trashed entry back to where it was deleted from and return it. Fails
+    /// rather than overwrite anything created at that path since.
+    pub async fn restore_from_trash(&self, id: &str) -> Result<TrashEntry> {
+        let _manifest = MANIFEST_LOCK.lock().await;
+        let mut entries = self.read_trash_manifest().await?;
+        let Some(index) = entries.iter().position(|entry| entry.id == id) else {
+            return Err(FileOperationError::FileNotFound(PathBuf::from(TRASH_DIR).join(id)).into());
+        };
+        let entry = entries.remove(index);
+
+        let target = self.base_path.join(&entry.original_path);
+        if target.exists() {
+            return Err(FileOperationError::AlreadyExists(entry.original_path).into());
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::rename(self.base_path.join(TRASH_DIR).join(&entry.id), &target).await?;
+        self.write_trash_manifest(&entries).await?;
+        Ok(entry)
+    }
+
+    async fn read_trash_manifest(&self) -> Result<Vec<TrashEntry>> {
+        match fs::read(self.base_path.join(TRASH_DIR).join(MANIFEST_FILE)).await {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn write_trash_manifest(&self, entries: &[TrashEntry]) -> Result<()> {
+        let bytes = serde_json::to_vec_pretty(entries)?;
+        super::atomic::write_atomic(&self.base_path.join(TRASH_DIR).join(MANIFEST_FILE), &bytes, self.fsync_on_save).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_deleted_file_is_recoverable_from_trash() {
+        let root = std::env::temp_dir().join(format!("super-ide-trash-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), "pub fn answer() -> u32 { 42 }\n").unwrap();
+        let manager = FileManager::new(root.clone());
+
+        let result = manager.delete_file(Path::new("src/lib.rs")).await.unwrap();
+        assert!(result.message.contains("moved to trash"));
+        assert!(!root.join("src/lib.rs").exists());
+
+        let trash = manager.list_trash().await.unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!((trash[0].original_path.as_path(), trash[0].is_dir), (Path::new("src/lib.rs"), false));
+
+        // Something new at the old path is never overwritten
+        std::fs::write(root.join("src/lib.rs"), "// new\n").unwrap();
+        let err = manager.restore_from_trash(&trash[0].id).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<FileOperationError>(), Some(FileOperationError::AlreadyExists(_))));
+        std::fs::remove_file(root.join("src/lib.rs")).unwrap();
+
+        manager.restore_from_trash(&trash[0].id).await.unwrap();
+        assert_eq!(std::fs::read_to_string(root.join("src/lib.rs")).unwrap(), "pub fn answer() -> u32 { 42 }\n");
+        assert!(manager.list_trash().await.unwrap().is_empty());
+
+        let permanent = FileManager::new(root.clone()).with_delete_to_trash(false);
+        assert!(permanent.delete_directory(Path::new("src")).await.unwrap().message.contains("permanently"));
+        assert!(manager.list_trash().await.unwrap().is_empty());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_deletions_keep_every_manifest_entry() {
+        let root = std::env::temp_dir().join(format!("super-ide-trash-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        for i in 0..20 {
+            std::fs::write(root.join(format!("file{}.txt", i)), "x").unwrap();
+        }
+        let manager = std::sync::Arc::new(FileManager::new(root.clone()));
+
+        let deletions: Vec<_> = (0..20)
+            .map(|i| {
+                let manager = manager.clone();
+                tokio::spawn(async move { manager.delete_file(Path::new(&format!("file{}.txt", i))).await })
+            })
+            .collect();
+        for deletion in deletions {
+            deletion.await.unwrap().unwrap();
+        }
+        assert_eq!(manager.list_trash().await.unwrap().len(), 20);
+
+        // A corrupt manifest leaves the file where it is rather than orphaning it in the trash
+        std::fs::write(root.join("keep.txt"), "x").unwrap();
+        std::fs::write(root.join(TRASH_DIR).join(MANIFEST_FILE), "not json").unwrap();
+        assert!(manager.move_to_trash(Path::new("keep.txt"), false).await.is_err());
+        assert!(root.join("keep.txt").exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
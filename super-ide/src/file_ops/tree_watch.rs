@@ -0,0 +1,270 @@
+//! Live file tree updates
+//!
+//! A workspace watcher turns filesystem notifications into `IdeEvent::FileChanged`
+//! events on the event bus. Subscribers coalesce those events into batched tree
+//! updates, so a burst such as a `git checkout` reaches the sidebar as one message.
+
+use anyhow::Result;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use notify::event::{ModifyKind, RenameMode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::time::{Duration, Instant};
+
+use super::FileOperationError;
+use crate::utils::event_bus::{EventBus, EventSubscriber, FileEventType, IdeEvent};
+
+/// Directories whose churn never shows up in the file tree
+const IGNORED_DIRS: &[&str] = &[".git", ".super-ide", "target", "node_modules"];
+
+/// Longest time a batch may keep growing while events keep arriving
+const MAX_BATCH_WINDOW: Duration = Duration::from_secs(1);
+
+/// How a tree node changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TreeChangeKind {
+    Created,
+    Removed,
+    Modified,
+}
+
+/// A single added, removed or modified tree node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TreeNodeChange {
+    pub path: String,
+    pub kind: TreeChangeKind,
+    pub is_directory: bool,
+}
+
+/// Batched tree changes pushed to clients
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreeUpdate {
+    pub changes: Vec<TreeNodeChange>,
+}
+
+/// Watch `root` recursively and broadcast its changes on the event bus; keep the
+/// returned watcher alive for as long as events should flow
+pub fn watch_workspace(root: PathBuf, event_bus: Arc<EventBus>) -> Result<RecommendedWatcher> {
+    let watch_root = root.clone();
+    let mut watcher = RecommendedWatcher::new(
+        move |result: Result<Event, notify::Error>| {
+            let Ok(event) = result else {
+                return;
+            };
+            for (path, event_type) in file_events(&watch_root, &event) {
+                let _ = event_bus.broadcast(IdeEvent::FileChanged { path, event_type });
+            }
+        },
+        notify::Config::default(),
+    ).map_err(|e| FileOperationError::WatchError(e.to_string()))?;
+
+    watcher
+        .watch(&root, RecursiveMode::Recursive)
+        .map_err(|e| FileOperationError::WatchError(e.to_string()))?;
+
+    Ok(watcher)
+}
+
+/// Map a notify event to workspace-relative file events
+fn file_events(root: &Path, event: &Event) -> Vec<(String, FileEventType)> {
+    let relative = |path: &PathBuf| -> Option<String> {
+        let relative = path.strip_prefix(root).ok()?;
+        let ignored = relative.components().any(|component| {
+            IGNORED_DIRS.iter().any(|dir| component.as_os_str() == *dir)
+        });
+        (!ignored && !relative.as_os_str().is_empty())
+            .then(|| relative.to_string_lossy().replace('\\', "/"))
+    };
+
+    match event.kind {
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+            match (relative(&event.paths[0]), relative(&event.paths[1])) {
+                (Some(from), Some(to)) => vec![(to.clone(), FileEventType::Renamed { from, to })],
+                (Some(from), None) => vec![(from, FileEventType::Deleted)],
+                (None, Some(to)) => vec![(to, FileEventType::Created)],
+                (None, None) => Vec::new(),
+            }
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => event.paths.iter()
+            .filter_map(relative)
+            .map(|path| (path, FileEventType::Deleted))
+            .collect(),
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => event.paths.iter()
+            .filter_map(relative)
+            .map(|path| (path, FileEventType::Created))
+            .collect(),
+        EventKind::Create(_) => event.paths.iter()
+            .filter_map(relative)
+            .map(|path| (path, FileEventType::Created))
+            .collect(),
+        EventKind::Remove(_) => event.paths.iter()
+            .filter_map(relative)
+            .map(|path| (path, FileEventType::Deleted))
+            .collect(),
+        EventKind::Modify(_) => event.paths.iter()
+            .filter_map(relative)
+            .map(|path| (path, FileEventType::Modified))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Coalesces file events into one tree update, keeping first-seen order
+#[derive(Debug)]
+pub struct TreeUpdateBatcher {
+    root: PathBuf,
+    order: Vec<String>,
+    pending: HashMap<String, TreeChangeKind>,
+}
+
+impl TreeUpdateBatcher {
+    /// Create an empty batcher for the workspace at `root`
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            order: Vec::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Whether no changes are pending
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Record a file event, folding it into any pending change for the same path
+    pub fn push(&mut self, path: &str, event_type: &FileEventType) {
+        match event_type {
+            FileEventType::Created => self.record(path, TreeChangeKind::Created),
+            FileEventType::Modified => self.record(path, TreeChangeKind::Modified),
+            FileEventType::Deleted => self.record(path, TreeChangeKind::Removed),
+            FileEventType::Renamed { from, to } => {
+                self.record(from, TreeChangeKind::Removed);
+                self.record(to, TreeChangeKind::Created);
+            }
+        }
+    }
+
+    fn record(&mut self, path: &str, kind: TreeChangeKind) {
+        let merged = match (self.pending.get(path).copied(), kind) {
+            (None, kind) => Some(kind),
+            // A node created in this batch is still new to the client
+            (Some(TreeChangeKind::Created), TreeChangeKind::Modified) => Some(TreeChangeKind::Created),
+            // Created and removed within one batch: the client never saw it
+            (Some(TreeChangeKind::Created), TreeChangeKind::Removed) => None,
+            // Removed and recreated: the client still has the node
+            (Some(TreeChangeKind::Removed), TreeChangeKind::Created) => Some(TreeChangeKind::Modified),
+            (Some(_), kind) => Some(kind),
+        };
+
+        match merged {
+            Some(kind) => {
+                if self.pending.insert(path.to_string(), kind).is_none() {
+                    self.order.push(path.to_string());
+                }
+            }
+            None => {
+                self.pending.remove(path);
+                self.order.retain(|pending| pending != path);
+            }
+        }
+    }
+
+    /// Drain the pending changes into an update, or `None` when nothing changed
+    pub fn take(&mut self) -> Option<TreeUpdate> {
+        if self.pending.is_empty() {
+            return None;
+        }
+
+        let changes = self.order.drain(..)
+            .filter_map(|path| {
+                let kind = self.pending.remove(&path)?;
+                let is_directory = kind != TreeChangeKind::Removed && self.root.join(&path).is_dir();
+                Some(TreeNodeChange { path, kind, is_directory })
+            })
+            .collect();
+        Some(TreeUpdate { changes })
+    }
+}
+
+/// Wait for the next batch of tree changes: collects events until none arrive for
+/// `quiet`, capped at `MAX_BATCH_WINDOW`. Returns `None` once the event bus closes.
+pub async fn next_tree_update(
+    subscriber: &mut EventSubscriber,
+    batcher: &mut TreeUpdateBatcher,
+    quiet: Duration,
+) -> Option<TreeUpdate> {
+    loop {
+        // Block until the first file event of the batch
+        while batcher.is_empty() {
+            if let IdeEvent::FileChanged { path, event_type } = subscriber.recv().await? {
+                batcher.push(&path, &event_type);
+            }
+        }
+
+        let deadline = Instant::now() + MAX_BATCH_WINDOW;
+        while let Ok(Some(event)) = tokio::time::timeout(
+            quiet.min(deadline.saturating_duration_since(Instant::now())),
+            subscriber.recv(),
+        ).await {
+            if let IdeEvent::FileChanged { path, event_type } = event {
+                batcher.push(&path, &event_type);
+            }
+        }
+
+        // Changes may have cancelled out, in which case keep waiting
+        if let Some(update) = batcher.take() {
+            return Some(update);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batcher_coalesces_bursts() {
+        let mut batcher = TreeUpdateBatcher::new(std::env::temp_dir());
+        batcher.push("a.rs", &FileEventType::Created);
+        batcher.push("a.rs", &FileEventType::Modified);
+        batcher.push("tmp.swp", &FileEventType::Created);
+        batcher.push("tmp.swp", &FileEventType::Deleted);
+        batcher.push("b.rs", &FileEventType::Deleted);
+        batcher.push("b.rs", &FileEventType::Created);
+
+        let update = batcher.take().unwrap();
+        let changes: Vec<_> = update.changes.iter().map(|c| (c.path.as_str(), c.kind)).collect();
+        assert_eq!(changes, vec![("a.rs", TreeChangeKind::Created), ("b.rs", TreeChangeKind::Modified)]);
+        assert!(batcher.take().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_creating_file_emits_created_event() {
+        let root = std::env::temp_dir().join(format!("super-ide-tree-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let root = root.canonicalize().unwrap();
+
+        let event_bus = Arc::new(EventBus::new());
+        let mut subscriber = event_bus.subscribe("files").unwrap();
+        let _watcher = watch_workspace(root.clone(), event_bus.clone()).unwrap();
+        let mut batcher = TreeUpdateBatcher::new(root.clone());
+
+        std::fs::write(root.join("new.rs"), "fn main() {}").unwrap();
+
+        let update = tokio::time::timeout(
+            Duration::from_secs(5),
+            next_tree_update(&mut subscriber, &mut batcher, Duration::from_millis(100)),
+        ).await.unwrap().unwrap();
+        assert!(update.changes.contains(&TreeNodeChange {
+            path: "new.rs".to_string(),
+            kind: TreeChangeKind::Created,
+            is_directory: false,
+        }));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -6,6 +6,8 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 use chrono::{DateTime, Utc};
 
+pub mod snippets;
+
 /// Learning style preferences for personalized education
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum LearningStyle {
@@ -0,0 +1,323 @@
+//! Snippet runner for code execution demos
+//!
+//! Each snippet runs in a temporary directory of its own, removed afterwards:
+//! Rust as a throwaway crate built with `cargo build --offline` and run as the
+//! resulting binary, Python with `python3 -I`, which ignores `PYTHON*` variables
+//! and user site-packages. The snippet gets an environment with nothing but
+//! `PATH`, `HOME`, `TMPDIR` and `LANG`, CPU time and memory limits set with
+//! `ulimit` on Unix, a wall-clock timeout, and a cap on the output kept; one that
+//! runs out of time or writes too much is killed.
+//!
+//! This guards against runaway demos, not hostile code: the snippet still runs as
+//! the IDE's user, which is why running one needs a trusted workspace.
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+use tokio::process::{ChildStderr, ChildStdout, Command};
+
+/// Snippets longer than this are refused
+pub const MAX_SNIPPET_BYTES: usize = 64 * 1024;
+
+/// `PATH` a snippet runs with
+const SNIPPET_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+
+/// Manifest of the crate a Rust snippet is built in; `[workspace]` keeps cargo
+/// from looking for an enclosing workspace
+const RUST_MANIFEST: &str = "[package]\nname = \"snippet\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\n\n[workspace]\n";
+
+/// Languages snippets can be run in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnippetLanguage {
+    Rust,
+    Python,
+}
+
+impl SnippetLanguage {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "rust" | "rs" => Some(Self::Rust),
+            "python" | "py" => Some(Self::Python),
+            _ => None,
+        }
+    }
+}
+
+/// Limits on one snippet run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnippetLimits {
+    /// Wall-clock time the snippet may run, which is also its CPU time limit
+    pub timeout: Duration,
+    /// Wall-clock time building a Rust snippet may take
+    pub compile_timeout: Duration,
+    /// Bytes kept of stdout, and of stderr
+    pub max_output_bytes: usize,
+    /// Virtual memory the snippet may map
+    pub max_memory_bytes: u64,
+}
+
+impl Default for SnippetLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(5),
+            compile_timeout: Duration::from_secs(60),
+            max_output_bytes: 64 * 1024,
+            max_memory_bytes: 512 * 1024 * 1024,
+        }
+    }
+}
+
+/// Step of a run that output comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnippetStage {
+    /// Building a Rust snippet, which failed
+    Compile,
+    Run,
+}
+
+/// What a snippet printed and how it ended
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnippetOutput {
+    pub stage: SnippetStage,
+    pub stdout: String,
+    pub stderr: String,
+    /// `None` when the process was killed, by the runner or by a signal
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    /// Whether output beyond the limit was cut off, which kills the snippet
+    pub truncated: bool,
+    pub duration_ms: u64,
+}
+
+/// Snippet runner errors
+#[derive(Debug, thiserror::Error)]
+pub enum SnippetError {
+    #[error("Running {0} snippets is not supported")]
+    UnsupportedLanguage(String),
+
+    #[error("Snippet is {size} bytes, over the {limit} byte limit")]
+    TooLarge { size: usize, limit: usize },
+
+    #[error("{0} is not installed or not on PATH")]
+    ToolMissing(String),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Run `code` as a `language` snippet within `limits`
+pub async fn run_snippet(language: &str, code: &str, limits: &SnippetLimits) -> Result<SnippetOutput, SnippetError> {
+    let snippet_language = SnippetLanguage::from_name(language)
+        .ok_or_else(|| SnippetError::UnsupportedLanguage(language.to_string()))?;
+    if code.len() > MAX_SNIPPET_BYTES {
+        return Err(SnippetError::TooLarge { size: code.len(), limit: MAX_SNIPPET_BYTES });
+    }
+
+    let dir = std::env::temp_dir().join(format!("super-ide-snippet-{}", uuid::Uuid::new_v4()));
+    tokio::fs::create_dir_all(&dir).await?;
+    let result = match snippet_language {
+        SnippetLanguage::Rust => run_rust(&dir, code, limits).await,
+        SnippetLanguage::Python => run_python(&dir, code, limits).await,
+    };
+    if let Err(e) = tokio::fs::remove_dir_all(&dir).await {
+        log::warn!("Could not remove snippet directory {}: {}", dir.display(), e);
+    }
+    result
+}
+
+async fn run_python(dir: &Path, code: &str, limits: &SnippetLimits) -> Result<SnippetOutput, SnippetError> {
+    let python = find_program("python3").ok_or_else(|| SnippetError::ToolMissing("python3".to_string()))?;
+    let script = dir.join("snippet.py");
+    tokio::fs::write(&script, code).await?;
+    run_limited(&python, &[OsStr::new("-I"), script.as_os_str()], dir, limits).await
+}
+
+/// Build the snippet, wrapped in `main` unless it has one, and run the binary.
+/// A failed build is returned as the output of the compile stage.
+async fn run_rust(dir: &Path, code: &str, limits: &SnippetLimits) -> Result<SnippetOutput, SnippetError> {
+    let cargo = find_program("cargo").ok_or_else(|| SnippetError::ToolMissing("cargo".to_string()))?;
+    let source = if code.contains("fn main") {
+        code.to_string()
+    } else {
+        format!("fn main() {{\n{}\n}}\n", code)
+    };
+    tokio::fs::create_dir_all(dir.join("src")).await?;
+    tokio::fs::write(dir.join("Cargo.toml"), RUST_MANIFEST).await?;
+    tokio::fs::write(dir.join("src").join("main.rs"), source).await?;
+
+    // The toolchain keeps the IDE's environment; only the snippet is restricted
+    let mut build = Command::new(cargo);
+    build.args(["build", "--quiet", "--offline"])
+        .current_dir(dir)
+        .env("CARGO_TARGET_DIR", dir.join("target"));
+    let built = collect(build, SnippetStage::Compile, limits.compile_timeout, limits.max_output_bytes).await?;
+    if built.exit_code != Some(0) {
+        return Ok(built);
+    }
+
+    let binary = dir.join("target").join("debug").join(format!("snippet{}", std::env::consts::EXE_SUFFIX));
+    run_limited(&binary, &[], dir, limits).await
+}
+
+/// Run `program` in `dir` with the snippet environment and limits
+async fn run_limited(program: &Path, args: &[&OsStr], dir: &Path, limits: &SnippetLimits) -> Result<SnippetOutput, SnippetError> {
+    let mut command = if cfg!(unix) {
+        let script = format!(
+            "ulimit -t {} && ulimit -v {} && exec \"$0\" \"$@\"",
+            limits.timeout.as_secs().max(1),
+            limits.max_memory_bytes / 1024
+        );
+        let mut command = Command::new("/bin/sh");
+        command.arg("-c").arg(script).arg(program).args(args);
+        command
+    } else {
+        let mut command = Command::new(program);
+        command.args(args);
+        command
+    };
+    command.current_dir(dir)
+        .env_clear()
+        .env("PATH", SNIPPET_PATH)
+        .env("HOME", dir)
+        .env("TMPDIR", dir)
+        .env("LANG", "C.UTF-8");
+    collect(command, SnippetStage::Run, limits.timeout, limits.max_output_bytes).await
+}
+
+/// Run `command` until it exits, runs past `timeout` or writes more than
+/// `max_output` bytes to stdout or stderr, killing it in the last two cases
+async fn collect(mut command: Command, stage: SnippetStage, timeout: Duration, max_output: usize) -> Result<SnippetOutput, SnippetError> {
+    command.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let started = Instant::now();
+    let mut child = command.spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+
+    let mut out = Vec::new();
+    let mut err = Vec::new();
+    let finished = tokio::time::timeout(timeout, async {
+        let truncated = read_capped(stdout, stderr, max_output, &mut out, &mut err).await;
+        if truncated {
+            let _ = child.start_kill();
+        }
+        child.wait().await.map(|status| (status, truncated))
+    }).await;
+
+    let (exit_code, timed_out, truncated) = match finished {
+        Ok(Ok((status, truncated))) => (if truncated { None } else { status.code() }, false, truncated),
+        Ok(Err(e)) => return Err(e.into()),
+        Err(_) => {
+            let _ = child.start_kill();
+            let _ = child.wait().await;
+            (None, true, out.len() > max_output || err.len() > max_output)
+        }
+    };
+    out.truncate(max_output);
+    err.truncate(max_output);
+
+    Ok(SnippetOutput {
+        stage,
+        stdout: String::from_utf8_lossy(&out).to_string(),
+        stderr: String::from_utf8_lossy(&err).to_string(),
+        exit_code,
+        timed_out,
+        truncated,
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// Read both streams to their end, or until either has given more than
+/// `max_output` bytes; returns whether that happened
+async fn read_capped(
+    mut stdout: ChildStdout,
+    mut stderr: ChildStderr,
+    max_output: usize,
+    out: &mut Vec<u8>,
+    err: &mut Vec<u8>,
+) -> bool {
+    let (mut out_open, mut err_open) = (true, true);
+    let mut out_chunk = [0u8; 8192];
+    let mut err_chunk = [0u8; 8192];
+    while out_open || err_open {
+        tokio::select! {
+            read = stdout.read(&mut out_chunk), if out_open => match read {
+                Ok(0) | Err(_) => out_open = false,
+                Ok(n) => out.extend_from_slice(&out_chunk[..n]),
+            },
+            read = stderr.read(&mut err_chunk), if err_open => match read {
+                Ok(0) | Err(_) => err_open = false,
+                Ok(n) => err.extend_from_slice(&err_chunk[..n]),
+            },
+        }
+        if out.len() > max_output || err.len() > max_output {
+            return true;
+        }
+    }
+    false
+}
+
+/// Full path of the program `name` on the IDE's `PATH`
+fn find_program(name: &str) -> Option<PathBuf> {
+    let file = format!("{}{}", name, std::env::consts::EXE_SUFFIX);
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(&file))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rust_snippet_output_is_captured() {
+        let output = run_snippet("rust", "println!(\"{} apples\", 6 * 7);\neprintln!(\"done\");", &SnippetLimits::default())
+            .await
+            .unwrap();
+        assert_eq!(output.stage, SnippetStage::Run);
+        assert_eq!((output.stdout.as_str(), output.stderr.as_str()), ("42 apples\n", "done\n"));
+        assert_eq!(output.exit_code, Some(0));
+        assert!(!output.timed_out && !output.truncated);
+
+        let broken = run_snippet("rust", "let count: u32 = \"many\";", &SnippetLimits::default()).await.unwrap();
+        assert_eq!(broken.stage, SnippetStage::Compile);
+        assert_ne!(broken.exit_code, Some(0));
+        assert!(broken.stderr.contains("mismatched types"));
+
+        // A snippet that floods its output is cut off and killed
+        let limits = SnippetLimits { max_output_bytes: 1024, ..SnippetLimits::default() };
+        let flood = run_snippet("rust", "loop { println!(\"spam\"); }", &limits).await.unwrap();
+        assert!(flood.truncated && !flood.timed_out);
+        assert_eq!((flood.stdout.len(), flood.exit_code), (1024, None));
+
+        assert!(matches!(
+            run_snippet("ruby", "puts 1", &SnippetLimits::default()).await,
+            Err(SnippetError::UnsupportedLanguage(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_python_snippet_is_stopped_at_the_timeout() {
+        if find_program("python3").is_none() {
+            return;
+        }
+        let limits = SnippetLimits { timeout: Duration::from_secs(1), ..SnippetLimits::default() };
+        let output = run_snippet("python", "import os, time\nprint('CARGO' in os.environ, os.environ['HOME'], flush=True)\ntime.sleep(30)\n", &limits)
+            .await
+            .unwrap();
+        assert!(output.timed_out);
+        assert_eq!(output.exit_code, None);
+        assert!(output.duration_ms < 5000);
+        // Output written before the timeout is kept; the IDE's environment is not passed on
+        assert!(output.stdout.starts_with("False "));
+        assert!(output.stdout.contains("super-ide-snippet-"));
+    }
+}
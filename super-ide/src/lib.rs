@@ -16,6 +16,7 @@ pub mod learning;
 pub mod git;
 pub mod file_ops;
 pub mod collaboration;
+pub mod diagnostics;
 
 // Re-export main components
 pub use core::{SuperIDE, IdeResult, IdeError};
@@ -25,6 +26,7 @@ pub use config::{Configuration};
 pub use terminal::{TerminalManager, TerminalSession, CommandExecutor, TerminalConfig, TerminalError};
 pub use git::{GitManager, GitRepository, GitStatus, GitCommit, GitBranch, GitError};
 pub use file_ops::{FileManager, FileInfo, ProjectStructure, FileOperationResult, FileOperationError};
+pub use diagnostics::{DiagnosticsManager, Diagnostic, DiagnosticSource};
 pub use collaboration::{CollaborationManager, CollaborationSession, CollaborationUser, Operation, UserPresence, CollaborationEvent};
 pub use learning::{
     LearningEngine, 
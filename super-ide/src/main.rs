@@ -1,5 +1,6 @@
 //! Main entry point for Super IDE - AI-Powered Development Environment
 
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 
@@ -8,7 +9,11 @@ use super_ide::{
     ui::WebUI,
     utils::performance::global_performance_monitor,
     utils::file_manager::FileManager,
-    config::AIProvider,
+    utils::logging::IdeLogger,
+    config::{AIProvider, LoggingSettings},
+    file_ops::templates::TemplateRegistry,
+    core::state::{record_opened_workspace, PersistedState},
+    editor::location::FileLocation,
 };
 
 use clap::{Parser, Subcommand, CommandFactory};
@@ -20,6 +25,9 @@ use anyhow::Result;
 #[command(about = "Super IDE - AI-Powered Development Environment")]
 #[command(version = env!("CARGO_PKG_VERSION"))]
 struct Args {
+    /// File to open, optionally at a position: `src/main.rs:42:5`
+    file: Option<String>,
+    
     /// Workspace directory path
     #[arg(short, long)]
     workspace: Option<PathBuf>,
@@ -60,8 +68,17 @@ enum Commands {
     New {
         /// Project name
         name: String,
-        /// Project template
+        /// Project template, e.g. rust, go or typescript; user templates are read from
+        /// the `templates` directory next to the configuration file
         template: Option<String>,
+        
+        /// List the files that would be created, with their contents, without writing them
+        #[arg(long)]
+        dry_run: bool,
+        
+        /// Overwrite files that already exist in the project directory
+        #[arg(long)]
+        force: bool,
     },
     
     /// Import existing project
@@ -71,7 +88,15 @@ enum Commands {
     },
     
     /// Show configuration
-    Config,
+    Config {
+        /// List settings that differ from the defaults
+        #[arg(long)]
+        diff: bool,
+        
+        /// Rewrite the configuration file with the defaults, keeping a `.bak` copy
+        #[arg(long, conflicts_with = "diff")]
+        reset: bool,
+    },
     
     /// Generate completion file for shell
     Completions {
@@ -95,8 +120,14 @@ enum Commands {
 async fn main() -> Result<()> {
     let args = Args::parse();
     
-    // Setup logging
-    setup_logging(args.debug)?;
+    // Log to stderr while the settings load, then as they say; a configuration that
+    // fails to load is reported once it is needed
+    setup_logging(args.debug, &LoggingSettings::default(), std::path::Path::new("."))?;
+    let loaded = load_configuration(&args).await;
+    if let Ok(config) = &loaded {
+        let workspace = config.workspace_dir();
+        setup_logging(args.debug, &config.logging, args.workspace.as_deref().unwrap_or(&workspace))?;
+    }
     
     // Handle subcommands
     if let Some(ref command) = args.command {
@@ -104,7 +135,7 @@ async fn main() -> Result<()> {
     }
     
     // Initialize configuration
-    let mut config = load_configuration(&args).await?;
+    let mut config = loaded?;
     
     // Apply command line overrides
     if let Some(workspace) = args.workspace {
@@ -121,6 +152,15 @@ async fn main() -> Result<()> {
     // Save updated configuration
     config.save().await?;
     
+    // Remember the workspace for the recent projects list
+    if let Some(state_path) = PersistedState::default_path() {
+        match record_opened_workspace(&state_path, &config.workspace_dir()).await {
+            Ok(state) if state.first_run => println!("👋 Welcome to Super IDE!"),
+            Ok(_) => {}
+            Err(e) => log::warn!("Could not record the opened workspace: {}", e),
+        }
+    }
+    
     println!("🚀 Starting Super IDE v{}", env!("CARGO_PKG_VERSION"));
     println!("📁 Workspace: {}", config.ide.workspace_path);
     println!("🤖 AI Provider: {:?}", config.ai.provider);
@@ -129,12 +169,28 @@ async fn main() -> Result<()> {
     // Initialize Super IDE
     let ide = initialize().await?;
     
+    if let Some(spec) = args.file.as_deref() {
+        // A file whose name really ends in `:<number>` opens as named
+        let location = if std::path::Path::new(spec).exists() {
+            FileLocation { path: PathBuf::from(spec), line: None, column: None }
+        } else {
+            FileLocation::parse(spec)
+        };
+        let path = location.path.canonicalize().unwrap_or_else(|_| location.path.clone());
+        match ide.open_file_at(path, location.cursor()).await {
+            Ok(_) => println!("📄 Opened {}", spec),
+            Err(e) => eprintln!("Could not open {}: {}", spec, e),
+        }
+    }
+    
     // Performance monitoring is automatically started with global instance
     let _monitor = global_performance_monitor();
     
     // Initialize file manager
+    let fsync_on_save = ide.config().read().await.editor.fsync_on_save;
     let file_manager = FileManager::new().await
-        .map_err(|e| anyhow::anyhow!("Failed to initialize file manager: {}", e))?;
+        .map_err(|e| anyhow::anyhow!("Failed to initialize file manager: {}", e))?
+        .with_fsync_on_save(fsync_on_save);
     
     // Create API state
     let _api_state = super_ide::api::ApiState {
@@ -151,6 +207,8 @@ async fn main() -> Result<()> {
         return Ok(());
     }
     
+    // `/health` answers from here on; `/ready` once initialization is done
+    web_ui.initialize().await;
     println!("✅ Server running. Press Ctrl+C to stop.");
     
     // Handle graceful shutdown
@@ -168,14 +226,20 @@ async fn main() -> Result<()> {
 /// Handle subcommands
 async fn handle_subcommand(command: Commands, args: Args) -> Result<()> {
     match command {
-        Commands::New { name, template } => {
-            create_new_project(&name, template.as_deref(), &args).await
+        Commands::New { name, template, dry_run, force } => {
+            create_new_project(&name, template.as_deref(), dry_run, force, &args).await
         },
         Commands::Import { path } => {
             import_project(&path, &args).await
         },
-        Commands::Config => {
-            show_configuration(&args).await
+        Commands::Config { diff, reset } => {
+            if reset {
+                reset_configuration(&args)
+            } else if diff {
+                show_configuration_diff(&args).await
+            } else {
+                show_configuration(&args).await
+            }
         },
         Commands::Completions { shell } => {
             generate_completions(&shell)
@@ -186,16 +250,16 @@ async fn handle_subcommand(command: Commands, args: Args) -> Result<()> {
     }
 }
 
-/// Setup logging configuration
-fn setup_logging(debug: bool) -> Result<()> {
-    use env_logger::{Builder, Env};
-    
-    let env = Env::default()
-        .filter_or("SUPER_IDE_LOG", if debug { "debug" } else { "info" });
+/// Setup logging configuration; `SUPER_IDE_LOG`, then `--debug`, override the
+/// configured levels
+fn setup_logging(debug: bool, settings: &LoggingSettings, workspace: &std::path::Path) -> Result<()> {
+    let directives = std::env::var("SUPER_IDE_LOG").ok()
+        .or_else(|| debug.then(|| "debug".to_string()));
     
-    Builder::from_env(env)
-        .format_timestamp_millis()
-        .init();
+    IdeLogger::new(settings, workspace, directives.as_deref())
+        .map_err(|e| anyhow::anyhow!("Failed to open the log file: {}", e))?
+        .install()
+        .map_err(|e| anyhow::anyhow!("Failed to install the logger: {}", e))?;
     
     Ok(())
 }
@@ -239,24 +303,33 @@ fn parse_ai_provider(provider: &str) -> Result<AIProvider> {
 }
 
 /// Create a new project
-async fn create_new_project(name: &str, template: Option<&str>, args: &Args) -> Result<()> {
+async fn create_new_project(name: &str, template: Option<&str>, dry_run: bool, force: bool, args: &Args) -> Result<()> {
     let workspace = args.workspace.as_ref().unwrap_or(&PathBuf::from(".")).clone();
     let project_path = workspace.join(name);
     
-    println!("📁 Creating new project '{}' at {}", name, project_path.display());
-    
-    // Create project directory
-    std::fs::create_dir_all(&project_path)?;
-    
     // Determine template
     let template = template.unwrap_or("rust");
     
-    match template {
-        "rust" => create_rust_project(&project_path, name).await?,
-        "python" => create_python_project(&project_path, name).await?,
-        "javascript" => create_js_project(&project_path, name).await?,
-        _ => anyhow::bail!("Unknown template: {}", template),
+    let registry = TemplateRegistry::load(&Configuration::templates_dir()?)?;
+    if registry.get(template).is_none() {
+        let available: Vec<String> = registry.list().into_iter().map(|template| template.id).collect();
+        anyhow::bail!("Unknown template: {}. Available templates: {}", template, available.join(", "));
     }
+    let plan = registry.plan(template, &project_path, name, &HashMap::new())?;
+    
+    if dry_run {
+        println!("📋 Project '{}' would be created at {} with:", name, project_path.display());
+        let conflicts = plan.conflicts();
+        for file in &plan.files {
+            let note = if conflicts.contains(&file.path) { " (exists, needs --force)" } else { "" };
+            println!("\n── {}{} ──", file.path.display(), note);
+            print!("{}", file.content);
+        }
+        return Ok(());
+    }
+    
+    println!("📁 Creating new project '{}' at {}", name, project_path.display());
+    plan.apply(force)?;
     
     println!("✅ Project '{}' created successfully!", name);
     println!("🚀 Run 'cd {}' and then 'super-ide' to start coding with AI!", name);
@@ -264,295 +337,6 @@ async fn create_new_project(name: &str, template: Option<&str>, args: &Args) ->
     Ok(())
 }
 
-/// Create Rust project template
-async fn create_rust_project(project_path: &PathBuf, name: &str) -> Result<()> {
-    let cargo_toml = format!(r#"[package]
-name = "{}"
-version = "0.1.0"
-edition = "2021"
-
-[dependencies]
-# Add your dependencies here
-
-[dev-dependencies]
-# Add your test dependencies here
-"#, name);
-    
-    let main_rs = format!(r#"//! {}
-
-// This is your main.rs file
-// Super IDE will provide AI assistance for this code
-
-fn main() {{
-    println!("Hello, {{}}!", "{}");
-    
-    // Your code goes here
-    // The AI will help you with:
-    // - Code completion and suggestions
-    // - Bug detection and fixes
-    // - Performance optimization
-    // - Test generation
-    // - Documentation
-}}
-
-/// Calculate the factorial of a number
-/// 
-/// # Examples
-/// 
-/// ```
-/// assert_eq!(factorial(5), 120);
-/// ```
-fn factorial(n: u64) -> u64 {{
-    if n <= 1 {{
-        1
-    }} else {{
-        n * factorial(n - 1)
-    }}
-}}
-
-#[cfg(test)]
-mod tests {{
-    use super::*;
-    
-    #[test]
-    fn test_factorial() {{
-        assert_eq!(factorial(0), 1);
-        assert_eq!(factorial(1), 1);
-        assert_eq!(factorial(5), 120);
-    }}
-}}
-"#, name, name);
-    
-    let readme = format!(r#"# {}
-
-A Rust project created with Super IDE.
-
-## Features
-
-- 🧠 AI-powered code assistance
-- 🔍 Smart debugging and error detection
-- 📝 Automatic documentation generation
-- 🧪 Unit test generation
-- ⚡ Performance optimization suggestions
-
-## Getting Started
-
-1. Install Super IDE: `cargo install super-ide`
-2. Run the project: `cargo run`
-3. Open Super IDE in your browser and start coding!
-
-## AI Features
-
-Super IDE provides:
-- Real-time code analysis
-- Intelligent code completion
-- Bug prediction and prevention
-- Code refactoring suggestions
-- Test case generation
-- Documentation automation
-
-## Usage
-
-```bash
-# Run the application
-cargo run
-
-# Run tests
-cargo test
-
-# Build for release
-cargo build --release
-```
-
-Happy coding with AI assistance! 🚀
-"#, name);
-    
-    std::fs::write(project_path.join("Cargo.toml"), cargo_toml)?;
-    std::fs::write(project_path.join("src").join("main.rs"), main_rs)?;
-    std::fs::write(project_path.join("README.md"), readme)?;
-    
-    // Create .gitignore
-    let gitignore = r#"# Generated by Cargo
-/target/
-
-# IDE files
-.super-ide/
-.vscode/
-.idea/
-
-# OS files
-.DS_Store
-Thumbs.db
-
-# Logs
-*.log
-
-# Environment variables
-.env
-.env.local
-"#;
-    
-    std::fs::write(project_path.join(".gitignore"), gitignore)?;
-    
-    Ok(())
-}
-
-/// Create Python project template
-async fn create_python_project(project_path: &PathBuf, name: &str) -> Result<()> {
-    let setup_py = format!(r#"from setuptools import setup, find_packages
-
-setup(
-    name="{}",
-    version="0.1.0",
-    description="A Python project created with Super IDE",
-    author="",
-    packages=find_packages(),
-    install_requires=[],
-    python_requires=">=3.8",
-)
-"#, name);
-    
-    let main_py = format!(r#"#!/usr/bin/env python3
-\"\"\"
-{}
-
-Super IDE - AI-Powered Python Development
-\"\"\"
-
-def main():
-    print("Hello, {}!")
-    
-    # Your code goes here
-    # Super IDE AI features:
-    # - Code completion and suggestions
-    # - Bug detection and fixes
-    # - Performance optimization
-    # - Test generation
-    # - Documentation
-
-if __name__ == "__main__":
-    main()
-"#, name, name);
-    
-    let readme = format!(r#"# {}
-
-A Python project created with Super IDE.
-
-## Features
-
-- 🧠 AI-powered code assistance
-- 🔍 Smart debugging and error detection
-- 📝 Automatic documentation generation
-- 🧪 Unit test generation
-- ⚡ Performance optimization suggestions
-
-## Installation
-
-1. Install Super IDE
-2. Open this directory in Super IDE
-3. Start coding with AI assistance!
-
-## Usage
-
-```bash
-python {}.py
-```
-
-Happy coding with AI assistance! 🚀
-"#, name, name);
-    
-    std::fs::create_dir_all(project_path.join(name))?;
-    std::fs::write(project_path.join("setup.py"), setup_py)?;
-    std::fs::write(project_path.join("__init__.py"), "")?;
-    std::fs::write(project_path.join(name).join("__init__.py"), "")?;
-    std::fs::write(project_path.join(name).join("main.py"), main_py)?;
-    std::fs::write(project_path.join("README.md"), readme)?;
-    
-    Ok(())
-}
-
-/// Create JavaScript project template
-async fn create_js_project(project_path: &PathBuf, name: &str) -> Result<()> {
-    let package_json = format!(r#"{{
-  "name": "{}",
-  "version": "1.0.0",
-  "description": "A JavaScript project created with Super IDE",
-  "main": "index.js",
-  "scripts": {{
-    "start": "node index.js",
-    "dev": "node index.js",
-    "test": "echo \\"Error: no test specified\\" && exit 1"
-  }},
-  "keywords": ["super-ide", "ai-assisted"],
-  "author": "",
-  "license": "MIT",
-  "dependencies": {{}}
-}}"#, name);
-    
-    let index_js = format!(r#"#!/usr/bin/env node
-
-/**
- * {}
- * 
- * Super IDE - AI-Powered JavaScript Development
- */
-
-function main() {{
-    console.log("Hello, {}!");
-    
-    // Your code goes here
-    // Super IDE AI features:
-    // - Code completion and suggestions
-    // - Bug detection and fixes
-    // - Performance optimization
-    // - Test generation
-    // - Documentation
-}}
-
-// ES6 Module support
-export default main;
-
-// CommonJS support
-if (typeof module !== 'undefined' && module.exports) {{
-    module.exports = main;
-}}
-
-// Run if executed directly
-if (typeof require !== 'undefined' && require.main === module) {{
-    main();
-}}
-"#, name, name);
-    
-    let readme = format!(r#"# {}
-
-A JavaScript project created with Super IDE.
-
-## Features
-
-- 🧠 AI-powered code assistance
-- 🔍 Smart debugging and error detection
-- 📝 Automatic documentation generation
-- 🧪 Unit test generation
-- ⚡ Performance optimization suggestions
-
-## Usage
-
-```bash
-npm start
-# or
-node index.js
-```
-
-Happy coding with AI assistance! 🚀
-"#, name);
-    
-    std::fs::write(project_path.join("package.json"), package_json)?;
-    std::fs::write(project_path.join("index.js"), index_js)?;
-    std::fs::write(project_path.join("README.md"), readme)?;
-    
-    Ok(())
-}
-
 /// Import existing project
 async fn import_project(path: &PathBuf, _args: &Args) -> Result<()> {
     println!("📂 Importing project from {}", path.display());
@@ -610,6 +394,41 @@ async fn show_configuration(args: &Args) -> Result<()> {
     Ok(())
 }
 
+/// Show the settings that differ from the defaults
+async fn show_configuration_diff(args: &Args) -> Result<()> {
+    let config = load_configuration(args).await?;
+    let changes = config.diff_from_default()?;
+
+    if changes.is_empty() {
+        println!("✅ Configuration matches the defaults");
+        return Ok(());
+    }
+
+    println!("📋 Settings changed from the defaults:");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    for change in &changes {
+        println!("{}: {} (default: {})", change.path, change.current, change.default);
+    }
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+
+    Ok(())
+}
+
+/// Reset the configuration file to the defaults
+fn reset_configuration(args: &Args) -> Result<()> {
+    let path = match &args.config {
+        Some(path) => path.clone(),
+        None => Configuration::user_config_file()?,
+    };
+
+    match Configuration::reset_file(&path)? {
+        Some(backup) => println!("✅ Reset {} to defaults (previous settings saved to {})", path.display(), backup.display()),
+        None => println!("✅ Wrote default configuration to {}", path.display()),
+    }
+
+    Ok(())
+}
+
 /// Generate shell completions
 fn generate_completions(shell: &str) -> Result<()> {
     use clap_complete::{generate, shells::*};
@@ -649,8 +468,10 @@ async fn run_server(args: &Args, port: u16, bind: &str) -> Result<()> {
     let ide = initialize().await?;
     
     // Initialize file manager
+    let fsync_on_save = ide.config().read().await.editor.fsync_on_save;
     let file_manager = FileManager::new().await
-        .map_err(|e| anyhow::anyhow!("Failed to initialize file manager: {}", e))?;
+        .map_err(|e| anyhow::anyhow!("Failed to initialize file manager: {}", e))?
+        .with_fsync_on_save(fsync_on_save);
     
     // Create API state
     let _api_state = super_ide::api::ApiState {
@@ -667,10 +488,16 @@ async fn run_server(args: &Args, port: u16, bind: &str) -> Result<()> {
         return Ok(());
     }
     
+    // `/health` answers from here on; `/ready` once initialization is done
+    web_ui.initialize().await;
     println!("✅ Server running. Press Ctrl+C to stop.");
     
     // Keep server running
     tokio::signal::ctrl_c().await?;
     
+    if let Err(e) = web_ui.stop().await {
+        eprintln!("Error stopping web UI: {}", e);
+    }
+    
     Ok(())
 }
@@ -8,16 +8,19 @@
 //! - Integrate with the WebSocket UI for terminal display
 
 pub mod ws_handler;
+pub mod scrollback;
+pub mod tasks;
 
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::process::{Stdio};
 use std::sync::Arc;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{broadcast, mpsc, RwLock};
 use tokio::time::Duration;
 
 use crate::core::IdeResult;
+use scrollback::{Scrollback, ScrollbackBuffer, ScrollbackLine};
 
 /// Terminal session information
 #[derive(Debug, Clone)]
@@ -101,6 +104,9 @@ pub enum TerminalError {
     #[error("Session not found: {0}")]
     SessionNotFound(String),
     
+    #[error("Task not found: {0}")]
+    TaskNotFound(String),
+    
     #[error("Session already exists")]
     SessionExists,
     
@@ -123,6 +129,7 @@ pub struct TerminalManager {
     sessions: Arc<RwLock<HashMap<String, TerminalSession>>>,
     output_senders: Arc<RwLock<HashMap<String, mpsc::UnboundedSender<String>>>>,
     input_senders: Arc<RwLock<HashMap<String, mpsc::UnboundedSender<String>>>>,
+    scrollback: Arc<RwLock<HashMap<String, Arc<std::sync::Mutex<ScrollbackBuffer>>>>>,
     config: TerminalConfig,
 }
 
@@ -133,6 +140,7 @@ impl TerminalManager {
             sessions: Arc::new(RwLock::new(HashMap::new())),
             output_senders: Arc::new(RwLock::new(HashMap::new())),
             input_senders: Arc::new(RwLock::new(HashMap::new())),
+            scrollback: Arc::new(RwLock::new(HashMap::new())),
             config,
         }
     }
@@ -167,6 +175,13 @@ impl TerminalManager {
             output_senders.insert(session_id.clone(), output_tx.clone());
         }
         
+        // Keep recent output so reconnecting clients can repaint
+        let scrollback = Arc::new(std::sync::Mutex::new(ScrollbackBuffer::new(self.config.max_output_lines)));
+        {
+            let mut buffers = self.scrollback.write().await;
+            buffers.insert(session_id.clone(), scrollback.clone());
+        }
+        
         // Create input channel for command processing
         let (input_tx, mut input_rx) = mpsc::unbounded_channel::<String>();
         
@@ -195,6 +210,7 @@ impl TerminalManager {
         
         // Spawn task to handle stdout
         let output_tx_clone = output_tx.clone();
+        let stdout_scrollback = scrollback.clone();
         let _stdout_task = tokio::spawn(async move {
             let mut reader = tokio::io::BufReader::new(stdout);
             let mut buffer = String::new();
@@ -206,6 +222,9 @@ impl TerminalManager {
                 
                 let output = buffer.trim_end().to_string();
                 if !output.is_empty() {
+                    if let Ok(mut lines) = stdout_scrollback.lock() {
+                        lines.push(&output, false);
+                    }
                     let _ = output_tx_clone.send(output);
                 }
                 buffer.clear();
@@ -224,6 +243,9 @@ impl TerminalManager {
                 
                 let output = format!("[stderr] {}", buffer.trim_end());
                 if !output.trim().is_empty() {
+                    if let Ok(mut lines) = scrollback.lock() {
+                        lines.push(buffer.trim_end(), true);
+                    }
                     let _ = output_tx.send(output);
                 }
                 buffer.clear();
@@ -305,6 +327,11 @@ impl TerminalManager {
                 input_senders.remove(session_id);
             }
             
+            {
+                let mut buffers = self.scrollback.write().await;
+                buffers.remove(session_id);
+            }
+            
             Ok(None) // We don't track exit codes in this simple implementation
         } else {
             Err(TerminalError::SessionNotFound(session_id.to_string()).into())
@@ -330,6 +357,21 @@ impl TerminalManager {
         sender.send(input.to_string()).map_err(|_| TerminalError::InvalidSession.into())
     }
     
+    /// Get the last `lines` lines of output for a session
+    pub async fn scrollback(&self, session_id: &str, lines: usize) -> IdeResult<Scrollback> {
+        self.subscribe_output(session_id, lines).await.map(|(scrollback, _)| scrollback)
+    }
+    
+    /// Get recent output together with a live stream that continues exactly where it ends
+    pub async fn subscribe_output(&self, session_id: &str, lines: usize) -> IdeResult<(Scrollback, broadcast::Receiver<ScrollbackLine>)> {
+        let buffers = self.scrollback.read().await;
+        let buffer = buffers.get(session_id)
+            .ok_or_else(|| TerminalError::SessionNotFound(session_id.to_string()))?;
+        let buffer = buffer.lock()
+            .map_err(|_| TerminalError::InvalidSession)?;
+        Ok(buffer.subscribe_with_snapshot(lines))
+    }
+    
     /// Get output receiver for a terminal session
     pub async fn get_output_receiver(&self, session_id: &str) -> Option<mpsc::UnboundedReceiver<String>> {
         let output_senders = self.output_senders.read().await;
@@ -416,6 +458,42 @@ impl CommandExecutor {
             execution_time,
         })
     }
+
+    /// Run a program with arguments, feeding `input` on stdin, and collect its output
+    pub async fn execute_with_input(&mut self, program: &str, args: &[String], input: &str) -> IdeResult<ProcessResult> {
+        let start_time = tokio::time::Instant::now();
+
+        let mut child = tokio::process::Command::new(program)
+            .args(args)
+            .envs(&self.config.environment)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| TerminalError::ProcessExecution(e.to_string()))?;
+
+        let mut stdin = child.stdin.take()
+            .ok_or_else(|| TerminalError::ProcessExecution("Failed to get stdin".to_string()))?;
+
+        // Write stdin concurrently so a large input cannot deadlock against a full stdout pipe
+        let input = input.to_string();
+        let writer = tokio::spawn(async move {
+            let _ = stdin.write_all(input.as_bytes()).await;
+        });
+
+        let output = child.wait_with_output()
+            .await
+            .map_err(|e| TerminalError::ProcessExecution(e.to_string()))?;
+        let _ = writer.await;
+
+        Ok(ProcessResult {
+            exit_code: output.status.code().unwrap_or(-1),
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            execution_time: start_time.elapsed(),
+        })
+    }
 }
 
 #[cfg(test)]
@@ -440,4 +518,13 @@ mod tests {
         assert!(result.exit_code == 0);
         assert!(result.stdout.contains("Hello, World!"));
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_execute_with_input_pipes_stdin() {
+        let mut executor = CommandExecutor::default();
+
+        let result = executor.execute_with_input("cat", &[], "formatted\n").await.unwrap();
+        assert_eq!(result.exit_code, 0);
+        assert_eq!(result.stdout, "formatted\n");
+    }
+}
@@ -0,0 +1,232 @@
+//! Bounded terminal scrollback with gap-free replay
+//!
+//! Every output line gets a sequence number. Lines are appended and broadcast
+//! under the same lock, so a client that takes a snapshot and subscribes in one
+//! step sees each line exactly once: either in the snapshot or on the live stream.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use tokio::sync::broadcast;
+
+/// A styled run of text parsed from ANSI SGR escape sequences
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnsiSpan {
+    pub text: String,
+    pub foreground: Option<String>,
+    pub background: Option<String>,
+    pub bold: bool,
+    pub underline: bool,
+}
+
+/// One line of terminal output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScrollbackLine {
+    pub seq: u64,
+    pub is_error: bool,
+    pub spans: Vec<AnsiSpan>,
+}
+
+/// Recent output returned to a reconnecting client
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Scrollback {
+    pub lines: Vec<ScrollbackLine>,
+    /// Sequence number of the next line the live stream will deliver
+    pub next_seq: u64,
+}
+
+/// Bounded ring of output lines for one terminal session
+#[derive(Debug)]
+pub struct ScrollbackBuffer {
+    lines: VecDeque<ScrollbackLine>,
+    capacity: usize,
+    next_seq: u64,
+    live: broadcast::Sender<ScrollbackLine>,
+}
+
+impl ScrollbackBuffer {
+    /// Create a buffer keeping at most `capacity` lines
+    pub fn new(capacity: usize) -> Self {
+        let (live, _) = broadcast::channel(256);
+        Self {
+            lines: VecDeque::with_capacity(capacity.min(1024)),
+            capacity: capacity.max(1),
+            next_seq: 0,
+            live,
+        }
+    }
+
+    /// Append a raw output line and forward it to live subscribers
+    pub fn push(&mut self, raw: &str, is_error: bool) -> u64 {
+        let line = ScrollbackLine {
+            seq: self.next_seq,
+            is_error,
+            spans: parse_ansi(raw),
+        };
+        self.next_seq += 1;
+
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line.clone());
+        let _ = self.live.send(line);
+
+        self.next_seq - 1
+    }
+
+    /// The most recent `count` lines
+    pub fn snapshot(&self, count: usize) -> Scrollback {
+        let skip = self.lines.len().saturating_sub(count);
+        Scrollback {
+            lines: self.lines.iter().skip(skip).cloned().collect(),
+            next_seq: self.next_seq,
+        }
+    }
+
+    /// Snapshot the last `count` lines and subscribe to everything after them
+    pub fn subscribe_with_snapshot(&self, count: usize) -> (Scrollback, broadcast::Receiver<ScrollbackLine>) {
+        (self.snapshot(count), self.live.subscribe())
+    }
+}
+
+/// Split a line containing ANSI SGR escapes into styled spans; other escapes are dropped
+pub fn parse_ansi(input: &str) -> Vec<AnsiSpan> {
+    let mut spans = Vec::new();
+    let mut style = AnsiSpan {
+        text: String::new(),
+        foreground: None,
+        background: None,
+        bold: false,
+        underline: false,
+    };
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            style.text.push(c);
+            continue;
+        }
+        if chars.peek() != Some(&'[') {
+            continue;
+        }
+        chars.next();
+
+        let mut params = String::new();
+        let mut terminator = None;
+        for next in chars.by_ref() {
+            if next.is_ascii_digit() || next == ';' {
+                params.push(next);
+            } else {
+                terminator = Some(next);
+                break;
+            }
+        }
+        if terminator != Some('m') {
+            continue;
+        }
+
+        if !style.text.is_empty() {
+            spans.push(style.clone());
+            style.text.clear();
+        }
+
+        let codes: Vec<u8> = if params.is_empty() {
+            vec![0]
+        } else {
+            params.split(';').filter_map(|p| p.parse().ok()).collect()
+        };
+        for code in codes {
+            match code {
+                0 => {
+                    style.foreground = None;
+                    style.background = None;
+                    style.bold = false;
+                    style.underline = false;
+                }
+                1 => style.bold = true,
+                4 => style.underline = true,
+                22 => style.bold = false,
+                24 => style.underline = false,
+                30..=37 => style.foreground = Some(ansi_color(code - 30, false)),
+                90..=97 => style.foreground = Some(ansi_color(code - 90, true)),
+                39 => style.foreground = None,
+                40..=47 => style.background = Some(ansi_color(code - 40, false)),
+                100..=107 => style.background = Some(ansi_color(code - 100, true)),
+                49 => style.background = None,
+                _ => {}
+            }
+        }
+    }
+
+    if !style.text.is_empty() || spans.is_empty() {
+        spans.push(style);
+    }
+    spans
+}
+
+/// Name of one of the 16 standard terminal colors
+fn ansi_color(index: u8, bright: bool) -> String {
+    const NAMES: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+    let name = NAMES[index as usize % 8];
+    if bright {
+        format!("bright_{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(line: &ScrollbackLine) -> String {
+        line.spans.iter().map(|span| span.text.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_scrollback_and_live_stream_reconstruct_output() {
+        let mut buffer = ScrollbackBuffer::new(100);
+        for i in 0..5 {
+            buffer.push(&format!("line {}", i), false);
+        }
+
+        let (scrollback, mut live) = buffer.subscribe_with_snapshot(3);
+        for i in 5..8 {
+            buffer.push(&format!("line {}", i), i == 6);
+        }
+
+        let mut received: Vec<String> = scrollback.lines.iter().map(text).collect();
+        let mut expected_seq = scrollback.next_seq;
+        while let Ok(line) = live.try_recv() {
+            assert_eq!(line.seq, expected_seq);
+            expected_seq += 1;
+            received.push(text(&line));
+        }
+
+        let expected: Vec<String> = (2..8).map(|i| format!("line {}", i)).collect();
+        assert_eq!(received, expected);
+    }
+
+    #[test]
+    fn test_buffer_is_bounded() {
+        let mut buffer = ScrollbackBuffer::new(2);
+        buffer.push("a", false);
+        buffer.push("b", false);
+        buffer.push("c", false);
+
+        let snapshot = buffer.snapshot(10);
+        assert_eq!(snapshot.lines.iter().map(text).collect::<Vec<_>>(), vec!["b", "c"]);
+        assert_eq!(snapshot.lines[0].seq, 1);
+        assert_eq!(snapshot.next_seq, 3);
+    }
+
+    #[test]
+    fn test_parse_ansi_colors() {
+        let spans = parse_ansi("\x1b[1;31merror\x1b[0m: done");
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].text, "error");
+        assert_eq!(spans[0].foreground.as_deref(), Some("red"));
+        assert!(spans[0].bold);
+        assert_eq!(spans[1].text, ": done");
+        assert_eq!(spans[1].foreground, None);
+    }
+}
@@ -0,0 +1,275 @@
+//! Project tasks (build, test, run)
+//!
+//! Each task runs one shell command in its own process group with output captured
+//! into a scrollback buffer. Cancelling a task kills the whole group, so compilers
+//! and test binaries started by the command go down with it.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::io::AsyncBufReadExt;
+use tokio::sync::{broadcast, oneshot, RwLock};
+
+use super::scrollback::{Scrollback, ScrollbackBuffer, ScrollbackLine};
+use super::{TerminalConfig, TerminalError};
+use crate::core::IdeResult;
+
+/// Lifecycle state of a task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskStatus {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+/// Snapshot of a task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskInfo {
+    pub id: String,
+    pub name: String,
+    pub command: String,
+    pub status: TaskStatus,
+    pub exit_code: Option<i32>,
+    /// Process id of the task, which also leads its process group
+    pub pid: Option<u32>,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug)]
+struct TaskEntry {
+    info: TaskInfo,
+    /// Released when the task is cancelled, which ends live output streams
+    output: Option<Arc<std::sync::Mutex<ScrollbackBuffer>>>,
+    readers: Vec<tokio::task::JoinHandle<()>>,
+    cancel: Option<oneshot::Sender<()>>,
+}
+
+/// Runs project tasks and tracks their state
+#[derive(Debug)]
+pub struct TaskRunner {
+    tasks: Arc<RwLock<HashMap<String, TaskEntry>>>,
+    config: TerminalConfig,
+}
+
+impl TaskRunner {
+    /// Create a task runner
+    pub fn new(config: TerminalConfig) -> Self {
+        Self {
+            tasks: Arc::new(RwLock::new(HashMap::new())),
+            config,
+        }
+    }
+
+    /// Start `command` in `cwd` and return the task id
+    pub async fn start(&self, name: &str, command: &str, cwd: PathBuf) -> IdeResult<String> {
+        let (shell, flag) = if cfg!(target_os = "windows") { ("cmd", "/C") } else { ("sh", "-c") };
+        let mut process = tokio::process::Command::new(shell);
+        process.arg(flag)
+            .arg(command)
+            .current_dir(&cwd)
+            .envs(&self.config.environment)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        #[cfg(unix)]
+        process.process_group(0);
+
+        let mut child = process.spawn().map_err(|e| TerminalError::ProcessExecution(e.to_string()))?;
+        let stdout = child.stdout.take()
+            .ok_or_else(|| TerminalError::ProcessExecution("Failed to get stdout".to_string()))?;
+        let stderr = child.stderr.take()
+            .ok_or_else(|| TerminalError::ProcessExecution("Failed to get stderr".to_string()))?;
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let output = Arc::new(std::sync::Mutex::new(ScrollbackBuffer::new(self.config.max_output_lines)));
+        let readers = vec![
+            tokio::spawn(capture_lines(stdout, output.clone(), false)),
+            tokio::spawn(capture_lines(stderr, output.clone(), true)),
+        ];
+        let (cancel_tx, cancel_rx) = oneshot::channel();
+
+        let info = TaskInfo {
+            id: id.clone(),
+            name: name.to_string(),
+            command: command.to_string(),
+            status: TaskStatus::Running,
+            exit_code: None,
+            pid: child.id(),
+            started_at: chrono::Utc::now(),
+            finished_at: None,
+        };
+        self.tasks.write().await.insert(id.clone(), TaskEntry {
+            info,
+            output: Some(output),
+            readers,
+            cancel: Some(cancel_tx),
+        });
+
+        let tasks = self.tasks.clone();
+        let task_id = id.clone();
+        tokio::spawn(async move {
+            let status = tokio::select! {
+                status = child.wait() => status.ok(),
+                _ = cancel_rx => {
+                    let _ = child.start_kill();
+                    child.wait().await.ok()
+                }
+            };
+
+            let mut tasks = tasks.write().await;
+            if let Some(entry) = tasks.get_mut(&task_id) {
+                // A cancelled task keeps its status; only natural exits are recorded here
+                if entry.info.status == TaskStatus::Running {
+                    let exit_code = status.and_then(|status| status.code());
+                    entry.info.exit_code = exit_code;
+                    entry.info.status = if exit_code == Some(0) { TaskStatus::Succeeded } else { TaskStatus::Failed };
+                    entry.info.finished_at = Some(chrono::Utc::now());
+                }
+            }
+        });
+
+        Ok(id)
+    }
+
+    /// Kill a running task and everything it started, and stop streaming its output.
+    /// Cancelling a finished task leaves it unchanged.
+    pub async fn cancel(&self, task_id: &str) -> IdeResult<TaskInfo> {
+        let mut tasks = self.tasks.write().await;
+        let entry = tasks.get_mut(task_id)
+            .ok_or_else(|| TerminalError::TaskNotFound(task_id.to_string()))?;
+        if entry.info.status != TaskStatus::Running {
+            return Ok(entry.info.clone());
+        }
+
+        entry.info.status = TaskStatus::Cancelled;
+        entry.info.finished_at = Some(chrono::Utc::now());
+        if let Some(pid) = entry.info.pid {
+            kill_process_tree(pid).await;
+        }
+        if let Some(cancel) = entry.cancel.take() {
+            let _ = cancel.send(());
+        }
+        for reader in entry.readers.drain(..) {
+            reader.abort();
+        }
+        entry.output = None;
+
+        Ok(entry.info.clone())
+    }
+
+    /// All tasks, most recently started first
+    pub async fn list(&self) -> Vec<TaskInfo> {
+        let mut tasks: Vec<TaskInfo> = self.tasks.read().await.values().map(|entry| entry.info.clone()).collect();
+        tasks.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+        tasks
+    }
+
+    /// Current state of a task
+    pub async fn get(&self, task_id: &str) -> Option<TaskInfo> {
+        self.tasks.read().await.get(task_id).map(|entry| entry.info.clone())
+    }
+
+    /// Recent output of a task plus a live stream of what follows
+    pub async fn subscribe_output(&self, task_id: &str, lines: usize) -> IdeResult<(Scrollback, broadcast::Receiver<ScrollbackLine>)> {
+        let tasks = self.tasks.read().await;
+        let output = tasks.get(task_id)
+            .ok_or_else(|| TerminalError::TaskNotFound(task_id.to_string()))?
+            .output
+            .as_ref()
+            .ok_or(TerminalError::InvalidSession)?;
+        let buffer = output.lock().map_err(|_| TerminalError::InvalidSession)?;
+        Ok(buffer.subscribe_with_snapshot(lines))
+    }
+}
+
+async fn capture_lines<R>(stream: R, output: Arc<std::sync::Mutex<ScrollbackBuffer>>, is_error: bool)
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    let mut lines = tokio::io::BufReader::new(stream).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if let Ok(mut buffer) = output.lock() {
+            buffer.push(&line, is_error);
+        }
+    }
+}
+
+/// Kill a task's process group, reaching every descendant that did not detach
+#[cfg(unix)]
+async fn kill_process_tree(pid: u32) {
+    let _ = tokio::process::Command::new("kill")
+        .args(["-KILL", "--", &format!("-{}", pid)])
+        .status()
+        .await;
+}
+
+#[cfg(not(unix))]
+async fn kill_process_tree(pid: u32) {
+    let _ = tokio::process::Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/T", "/F"])
+        .status()
+        .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::{sleep, Duration};
+
+    async fn process_exists(pid: u32) -> bool {
+        tokio::process::Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(Stdio::null())
+            .status()
+            .await
+            .map(|status| status.success())
+            .unwrap_or(false)
+    }
+
+    #[tokio::test]
+    async fn test_cancel_kills_long_running_task() {
+        let runner = TaskRunner::new(TerminalConfig::default());
+        let id = runner.start("build", "sleep 30", std::env::temp_dir()).await.unwrap();
+        let pid = runner.get(&id).await.unwrap().pid.unwrap();
+        assert!(process_exists(pid).await);
+
+        let info = runner.cancel(&id).await.unwrap();
+        assert_eq!(info.status, TaskStatus::Cancelled);
+        assert!(runner.subscribe_output(&id, 10).await.is_err());
+
+        let mut gone = false;
+        for _ in 0..50 {
+            if !process_exists(pid).await {
+                gone = true;
+                break;
+            }
+            sleep(Duration::from_millis(50)).await;
+        }
+        assert!(gone, "task process {} still running after cancel", pid);
+        assert_eq!(runner.get(&id).await.unwrap().status, TaskStatus::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn test_finished_task_records_exit_code() {
+        let runner = TaskRunner::new(TerminalConfig::default());
+        let id = runner.start("test", "echo done; exit 3", std::env::temp_dir()).await.unwrap();
+
+        let mut info = runner.get(&id).await.unwrap();
+        for _ in 0..50 {
+            if info.status != TaskStatus::Running {
+                break;
+            }
+            sleep(Duration::from_millis(50)).await;
+            info = runner.get(&id).await.unwrap();
+        }
+        assert_eq!(info.status, TaskStatus::Failed);
+        assert_eq!(info.exit_code, Some(3));
+        assert_eq!(runner.cancel(&id).await.unwrap().status, TaskStatus::Failed);
+    }
+}
@@ -2,6 +2,13 @@
 //!
 //! This module handles WebSocket connections for terminal sessions,
 //! enabling real-time terminal communication between frontend and backend.
+//!
+//! Attaching to a session replays its scrollback and then streams new output.
+//! The snapshot and the live subscription are taken under the buffer's lock and
+//! every line carries its sequence number, so a client drops any line numbered
+//! below the `next_seq` of the scrollback it last painted and sees no gaps or
+//! duplicates. A connection dropped without a close frame leaves its sessions
+//! running for the client to attach to again.
 
 use axum::{
     extract::{WebSocketUpgrade, State},
@@ -11,13 +18,18 @@ use axum::{
 use futures::{StreamExt, SinkExt};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use tokio::sync::{RwLock, mpsc};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
 use log::{info, warn, error};
 use uuid::Uuid;
 
 
-use crate::core::SuperIDE;
-use crate::terminal::{TerminalManager};
+use crate::core::{IdeResult, SuperIDE};
+use crate::terminal::TerminalManager;
+use crate::terminal::scrollback::ScrollbackLine;
+
+/// Lines replayed when attaching without saying how many
+const DEFAULT_REPLAY_LINES: usize = 500;
 
 // WebSocket message types
 #[derive(Debug, Serialize, Deserialize)]
@@ -49,6 +61,11 @@ pub enum TerminalMessage {
     GetHistory {
         session_id: String,
     },
+    #[serde(rename = "attach")]
+    Attach {
+        session_id: String,
+        lines: Option<usize>,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -66,6 +83,14 @@ pub enum ServerMessage {
         output: String,
         timestamp: String,
         is_error: bool,
+        seq: u64,
+    },
+    #[serde(rename = "scrollback")]
+    Scrollback {
+        session_id: String,
+        lines: Vec<ScrollbackLine>,
+        /// Sequence number of the first line streamed after these
+        next_seq: u64,
     },
     #[serde(rename = "session_closed")]
     SessionClosed {
@@ -95,7 +120,7 @@ pub enum ServerMessage {
 #[derive(Clone)]
 pub struct TerminalWebSocketState {
     pub ide: Arc<SuperIDE>,
-    pub terminal_manager: Arc<RwLock<TerminalManager>>,
+    pub terminal_manager: Arc<TerminalManager>,
 }
 
 // WebSocket handler
@@ -118,9 +143,21 @@ pub async fn terminal_websocket_connection(
     let (mut sender, mut receiver) = socket.split();
     let session_id = Uuid::new_v4().to_string();
     
-    // Store active sessions
+    // Messages to the client go through one writer so forwarders can share it
+    let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<ServerMessage>();
+    let writer = tokio::spawn(async move {
+        while let Some(message) = outgoing_rx.recv().await {
+            let Ok(json) = serde_json::to_string(&message) else { continue };
+            if sender.send(axum::extract::ws::Message::Text(json)).await.is_err() {
+                break;
+            }
+        }
+    });
+    
+    // Output forwarders of the attached sessions
     let mut active_sessions = std::collections::HashMap::new();
     let mut command_history = std::collections::HashMap::new();
+    let mut closed_by_client = false;
     
     // Handle incoming messages
     while let Some(msg) = receiver.next().await {
@@ -130,34 +167,27 @@ pub async fn terminal_websocket_connection(
                     let result = handle_terminal_message(
                         client_message,
                         &state,
+                        &outgoing,
                         &mut active_sessions,
                         &mut command_history,
                         &session_id,
                     ).await;
                     
                     if let Err(e) = result {
-                        let error_message = e.to_string();
-                        error!("Error handling terminal message: {}", error_message);
-                        
-                        // For now, just log the error without sending to WebSocket
-                        // This avoids the complex Send trait issue in WebSocket context
-                        // TODO: Implement proper error reporting in a future update
+                        error!("Error handling terminal message: {}", e);
                     }
                 } else {
                     warn!("Failed to parse terminal message: {}", text);
-                    let error_msg = ServerMessage::Error {
+                    let _ = outgoing.send(ServerMessage::Error {
                         message: "Invalid message format".to_string(),
                         code: "INVALID_MESSAGE".to_string(),
                         session_id: None,
-                    };
-                    
-                    if let Ok(json) = serde_json::to_string(&error_msg) {
-                        let _ = sender.send(axum::extract::ws::Message::Text(json)).await;
-                    }
+                    });
                 }
             }
             Ok(axum::extract::ws::Message::Close(_)) => {
                 info!("Terminal WebSocket connection closed by client");
+                closed_by_client = true;
                 break;
             }
             Err(e) => {
@@ -170,24 +200,55 @@ pub async fn terminal_websocket_connection(
         }
     }
     
-    // Cleanup: Close all active sessions
+    // Cleanup: stop forwarding, and close the sessions only if the client said goodbye
     info!("Cleaning up terminal sessions for connection: {}", session_id);
-    let terminal_manager = state.terminal_manager.write().await;
-    
-    for (session_id, _) in active_sessions {
-        if let Err(e) = terminal_manager.close_session(&session_id).await {
+    for (session_id, forwarder) in active_sessions {
+        forwarder.abort();
+        if !closed_by_client {
+            continue;
+        }
+        if let Err(e) = state.terminal_manager.close_session(&session_id).await {
             error!("Failed to close terminal session {}: {}", session_id, e);
         }
     }
+    writer.abort();
     
     info!("Terminal WebSocket connection closed");
 }
 
+/// Replay the last `lines` lines of a session to the client and stream its output from there
+async fn attach_session(
+    state: &TerminalWebSocketState,
+    session_id: &str,
+    lines: usize,
+    outgoing: &mpsc::UnboundedSender<ServerMessage>,
+    active_sessions: &mut std::collections::HashMap<String, JoinHandle<()>>,
+) -> IdeResult<()> {
+    let (scrollback, live) = state.terminal_manager.subscribe_output(session_id, lines).await?;
+    let _ = outgoing.send(ServerMessage::Scrollback {
+        session_id: session_id.to_string(),
+        lines: scrollback.lines,
+        next_seq: scrollback.next_seq,
+    });
+    
+    let forwarder = tokio::spawn(forward_terminal_output(
+        state.terminal_manager.clone(),
+        live,
+        session_id.to_string(),
+        outgoing.clone(),
+    ));
+    if let Some(previous) = active_sessions.insert(session_id.to_string(), forwarder) {
+        previous.abort();
+    }
+    Ok(())
+}
+
 // Handle terminal messages
 async fn handle_terminal_message(
     message: TerminalMessage,
     state: &TerminalWebSocketState,
-    active_sessions: &mut std::collections::HashMap<String, mpsc::UnboundedReceiver<String>>,
+    outgoing: &mpsc::UnboundedSender<ServerMessage>,
+    active_sessions: &mut std::collections::HashMap<String, JoinHandle<()>>,
     command_history: &mut std::collections::HashMap<String, Vec<String>>,
     _connection_id: &str,
 ) -> Result<(), Box<dyn std::error::Error>> {
@@ -196,27 +257,25 @@ async fn handle_terminal_message(
             let actual_session_id = session_id.unwrap_or_else(|| Uuid::new_v4().to_string());
             info!("Creating terminal session: {}", actual_session_id);
             
-            let terminal_manager = state.terminal_manager.write().await;
+            let terminal_manager = &state.terminal_manager;
             
             match terminal_manager.create_session(None, None, Some(format!("Terminal {}", actual_session_id))).await {
-                Ok(_session_id) => {
-                    // Get output receiver for this session
-                    let receiver = terminal_manager.get_output_receiver(&actual_session_id).await
-                        .unwrap_or_else(|| mpsc::unbounded_channel::<String>().1);
-                    active_sessions.insert(actual_session_id.clone(), receiver);
-                    
-                    // Initialize command history
-                    command_history.entry(actual_session_id.clone()).or_insert_with(Vec::new);
-                    
-                    info!("Successfully created terminal session: {}", actual_session_id);
-                    
+                Ok(created_session_id) => {
+                    // Confirm first, so the client knows the id before its output arrives
                     let response = ServerMessage::SessionCreated {
-                        session_id: actual_session_id,
+                        session_id: created_session_id.clone(),
                         success: true,
                         message: None,
                     };
+                    send_message_to_client(response, outgoing).await?;
+                    
+                    // Stream the session's output, including anything printed before attaching
+                    attach_session(state, &created_session_id, DEFAULT_REPLAY_LINES, outgoing, active_sessions).await?;
                     
-                    return send_message_to_client(response, state).await;
+                    // Initialize command history
+                    command_history.entry(created_session_id.clone()).or_insert_with(Vec::new);
+                    
+                    info!("Successfully created terminal session: {}", created_session_id);
                 }
                 Err(e) => {
                     error!("Failed to create terminal session: {}", e);
@@ -227,7 +286,7 @@ async fn handle_terminal_message(
                         message: Some(e.to_string()),
                     };
                     
-                    return send_message_to_client(response, state).await;
+                    return send_message_to_client(response, outgoing).await;
                 }
             }
         }
@@ -235,7 +294,7 @@ async fn handle_terminal_message(
         TerminalMessage::ExecuteCommand { session_id, command, cwd } => {
             info!("Executing command '{}' in session {}", command, session_id);
             
-            let terminal_manager = state.terminal_manager.write().await;
+            let terminal_manager = &state.terminal_manager;
             
             match terminal_manager.execute_command(&session_id, &command, cwd.as_deref()).await {
                 Ok(_) => {
@@ -255,7 +314,7 @@ async fn handle_terminal_message(
                         session_id: Some(session_id),
                     };
                     
-                    return send_message_to_client(error_msg, state).await;
+                    return send_message_to_client(error_msg, outgoing).await;
                 }
             }
         }
@@ -263,7 +322,7 @@ async fn handle_terminal_message(
         TerminalMessage::Resize { session_id, width, height } => {
             info!("Resizing terminal session {} to {}x{}", session_id, width, height);
             
-            let terminal_manager = state.terminal_manager.write().await;
+            let terminal_manager = &state.terminal_manager;
             
             if let Err(e) = terminal_manager.resize_session(&session_id, width, height).await {
                 error!("Failed to resize terminal session {}: {}", session_id, e);
@@ -274,19 +333,21 @@ async fn handle_terminal_message(
                     session_id: Some(session_id),
                 };
                 
-                return send_message_to_client(error_msg, state).await;
+                return send_message_to_client(error_msg, outgoing).await;
             }
         }
         
         TerminalMessage::CloseSession { session_id } => {
             info!("Closing terminal session: {}", session_id);
             
-            let terminal_manager = state.terminal_manager.write().await;
+            let terminal_manager = &state.terminal_manager;
             
             match terminal_manager.close_session(&session_id).await {
                 Ok(exit_code) => {
                     // Remove from active sessions
-                    active_sessions.remove(&session_id);
+                    if let Some(forwarder) = active_sessions.remove(&session_id) {
+                        forwarder.abort();
+                    }
                     command_history.remove(&session_id);
                     
                     info!("Successfully closed terminal session: {} (exit code: {:?})", session_id, exit_code);
@@ -296,7 +357,7 @@ async fn handle_terminal_message(
                         exit_code,
                     };
                     
-                    return send_message_to_client(response, state).await;
+                    return send_message_to_client(response, outgoing).await;
                 }
                 Err(e) => {
                     error!("Failed to close terminal session {}: {}", session_id, e);
@@ -307,7 +368,7 @@ async fn handle_terminal_message(
                         session_id: Some(session_id),
                     };
                     
-                    return send_message_to_client(error_msg, state).await;
+                    return send_message_to_client(error_msg, outgoing).await;
                 }
             }
         }
@@ -324,7 +385,23 @@ async fn handle_terminal_message(
                 commands: history,
             };
             
-            return send_message_to_client(response, state).await;
+            return send_message_to_client(response, outgoing).await;
+        }
+        
+        TerminalMessage::Attach { session_id, lines } => {
+            info!("Attaching to terminal session: {}", session_id);
+            
+            if let Err(e) = attach_session(state, &session_id, lines.unwrap_or(DEFAULT_REPLAY_LINES), outgoing, active_sessions).await {
+                warn!("Cannot attach to terminal session {}: {}", session_id, e);
+                
+                let error_msg = ServerMessage::Error {
+                    message: format!("Failed to attach to session: {}", e),
+                    code: "SESSION_NOT_FOUND".to_string(),
+                    session_id: Some(session_id),
+                };
+                
+                return send_message_to_client(error_msg, outgoing).await;
+            }
         }
     }
     
@@ -334,31 +411,49 @@ async fn handle_terminal_message(
 // Helper function to send messages to the client
 async fn send_message_to_client(
     message: ServerMessage,
-    _state: &TerminalWebSocketState,
+    outgoing: &mpsc::UnboundedSender<ServerMessage>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // This is a placeholder - in a real implementation, you'd need access to the WebSocket sender
-    // For now, we'll just log the message
-    info!("Would send to client: {:?}", message);
+    outgoing.send(message)?;
     Ok(())
 }
 
 // Background task to forward terminal output to WebSocket clients
 pub async fn forward_terminal_output(
-    _terminal_manager: Arc<RwLock<TerminalManager>>,
-    mut output_receiver: mpsc::UnboundedReceiver<(String, String, bool)>,
+    terminal_manager: Arc<TerminalManager>,
+    mut live: broadcast::Receiver<ScrollbackLine>,
     session_id: String,
+    outgoing: mpsc::UnboundedSender<ServerMessage>,
 ) {
-    while let Some((output, timestamp, is_error)) = output_receiver.recv().await {
-        let message = ServerMessage::CommandOutput {
-            session_id: session_id.clone(),
-            output,
-            timestamp,
-            is_error,
+    loop {
+        let message = match live.recv().await {
+            Ok(line) => ServerMessage::CommandOutput {
+                session_id: session_id.clone(),
+                output: line.spans.iter().map(|span| span.text.as_str()).collect(),
+                timestamp: chrono::Utc::now().to_rfc3339(),
+                is_error: line.is_error,
+                seq: line.seq,
+            },
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                // Repaint from the whole buffer rather than leave a hole; the
+                // client drops the lines it already has by their sequence numbers
+                warn!("Terminal output for session {} fell {} lines behind", session_id, skipped);
+                match terminal_manager.subscribe_output(&session_id, usize::MAX).await {
+                    Ok((scrollback, resubscribed)) => {
+                        live = resubscribed;
+                        ServerMessage::Scrollback {
+                            session_id: session_id.clone(),
+                            lines: scrollback.lines,
+                            next_seq: scrollback.next_seq,
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
         };
         
-        if let Ok(json) = serde_json::to_string(&message) {
-            // In a real implementation, you would send this to the WebSocket client
-            info!("Terminal output for session {}: {}", session_id, json);
+        if outgoing.send(message).is_err() {
+            break;
         }
     }
 }
@@ -8,7 +8,7 @@ use axum::{
     Router,
     Json,
 };
-use axum::extract::Path;
+use axum::extract::{Path, Query};
 use tokio::sync::broadcast;
 use serde::{Deserialize, Serialize};
 use tower_http::cors::{CorsLayer, Any};
@@ -26,6 +26,11 @@ use crate::utils::file_manager::FileManager;
 use crate::utils::event_bus::EventBus;
 
 use crate::editor::{CompletionContext, CompletionItem};
+use crate::editor::location::FileLocation;
+use crate::editor::preview::OpenMode;
+use crate::editor::save_conflict::SaveOutcome;
+use crate::editor::EditorError;
+use crate::config::SaveConflictStrategy;
 
 // WebSocket message types
 #[derive(Debug, Serialize, Deserialize)]
@@ -139,25 +144,37 @@ impl WebUI {
         use crate::api::{load_file, save_file, create_file, delete_file, get_file_tree, search_files};
         use crate::api::{ai_chat, get_completions, analyze_code};
         use crate::api::{git_status, git_branches, git_commit};
-        use crate::api::{project_info, get_config, health_check};
+        use crate::api::{project_info, get_config, health_check, readiness_check};
+        use crate::api::logging::{log_requests, RequestLogging};
+        use crate::api::limits::{limit_body, BodyLimits};
         
-        let app = Router::new()
-            // Static file serving for frontend
-            .route("/", get(serve_frontend))
-            .route("/health", get(health_check))
-            
+        let (logging, limits) = {
+            let config = self.app_state.ide.config().read().await;
+            (RequestLogging { enabled: config.ide.log_requests }, BodyLimits::new(&config.api))
+        };
+        let files = Router::new()
             // File operations
             .route("/api/files/:path", get(load_file))
             .route("/api/files/:path", put(save_file))
             .route("/api/files/create", post(create_file))
             .route("/api/files/:path", delete(delete_file))
             .route("/api/files/tree", get(get_file_tree))
-            .route("/api/files/search", get(search_files))
-            
+            .route("/api/files/search", get(search_files));
+        
+        let ai = Router::new()
             // AI endpoints
             .route("/api/ai/chat", post(ai_chat))
             .route("/api/ai/completions", post(get_completions))
             .route("/api/ai/analyze", post(analyze_code))
+            // Legacy AI routes
+            .route("/api/analyze", post(analyze_code))
+            .route("/api/ai/suggest", post(get_ai_suggestion));
+        
+        let other = Router::new()
+            // Static file serving for frontend
+            .route("/", get(serve_frontend))
+            .route("/health", get(health_check))
+            .route("/ready", get(readiness_check))
             
             // Git operations
             .route("/api/git/status", get(git_status))
@@ -176,11 +193,15 @@ impl WebUI {
             .route("/api/files", get(list_files))
             .route("/api/open/:path", get(open_file))
             .route("/api/save/:document_id", get(save_document))
-            .route("/api/complete", get(get_completion))
-            .route("/api/analyze", post(analyze_code))
-            .route("/api/ai/suggest", post(get_ai_suggestion))
-            
-            .layer(CorsLayer::new().allow_origin(Any))
+            .route("/api/complete", get(get_completion));
+        
+        let app = limit_body(other, limits.default)
+            .merge(limit_body(files, limits.files))
+            .merge(limit_body(ai, limits.ai))
+            .layer(axum::middleware::from_fn_with_state(logging, log_requests))
+            .layer(CorsLayer::new()
+                .allow_origin(Any)
+                .expose_headers([axum::http::HeaderName::from_static(crate::api::logging::REQUEST_ID_HEADER)]))
             .with_state(self.app_state.clone());
             
         let addr = SocketAddr::from(([0, 0, 0, 0], port));
@@ -200,11 +221,20 @@ impl WebUI {
         Ok(())
     }
     
-    /// Stop the web server
+    /// Initialize the IDE's subsystems, after which `/ready` reports it ready
+    pub async fn initialize(&self) {
+        self.app_state.ide.initialize(&self.app_state.git_manager).await;
+    }
+    
+    /// Stop the web server and save collaborative edits made since the last
+    /// periodic snapshot
     pub async fn stop(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         if let Some(task) = self.server_task.take() {
             task.abort();
         }
+        if let Err(e) = self.app_state.ide.collaboration_manager().snapshot().await {
+            log::warn!("Could not save collaborative documents: {}", e);
+        }
         Ok(())
     }
 }
@@ -223,17 +253,18 @@ async fn serve_frontend() -> impl IntoResponse {
 async fn terminal_websocket_handler(
     ws: WebSocketUpgrade,
     State(state): State<AppState>,
-) -> impl IntoResponse {
+) -> axum::response::Response {
+    if let Err(e) = state.ide.workspace_trust().ensure_trusted() {
+        return crate::api::ApiResponse::<()>::ide_error("Terminal refused", e).into_response();
+    }
     let terminal_state = TerminalWebSocketState {
         ide: state.ide.clone(),
-        terminal_manager: Arc::new(tokio::sync::RwLock::new(
-            crate::terminal::TerminalManager::new(
-                crate::terminal::TerminalConfig::default()
-            )
-        )),
+        // Shared with the scrollback endpoint, and outliving the connection for reattaching
+        terminal_manager: state.ide.terminal_manager().clone(),
     };
     
     crate::terminal::ws_handler::terminal_websocket_handler(ws, axum::extract::State(terminal_state)).await
+        .into_response()
 }
 
 
@@ -276,19 +307,42 @@ async fn list_files(State(state): State<AppState>) -> impl IntoResponse {
     }
 }
 
-/// Open a file
+/// Open a file, with the cursor at `?line=&column=` when given. `?mode=preview` opens
+/// it read-only and `?mode=hex` as a hex dump, whose rows are returned; binary files
+/// always open as hex.
 async fn open_file(
     State(state): State<AppState>,
     Path(path): Path<String>,
+    Query(position): Query<OpenFileQuery>,
 ) -> impl IntoResponse {
     let path_clone = path.clone();
-    let path_buf = std::path::PathBuf::from(path);
+    let location = FileLocation {
+        path: std::path::PathBuf::from(path),
+        line: position.line,
+        column: position.column,
+    };
     
     let editor = state.ide.editor();
     let editor_lock = editor.lock().await;
     
-    match editor_lock.open_file(path_buf).await {
-        Ok(document_id) => {
+    let opened = match editor_lock.open_file_as(location.path.clone(), position.mode).await {
+        Ok(document_id) => match location.cursor() {
+            Some((line, column)) => editor_lock.set_cursor(&document_id, line, column).await.map(|()| document_id),
+            None => Ok(document_id),
+        },
+        Err(e) => Err(e),
+    };
+    let opened = match opened {
+        Ok(document_id) => match editor_lock.document_mode(&document_id).await {
+            Ok(OpenMode::Hex) => editor_lock.hex_rows(&document_id).await.map(|rows| (document_id, OpenMode::Hex, Some(rows))),
+            Ok(mode) => Ok((document_id, mode, None)),
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e),
+    };
+    
+    match opened {
+        Ok((document_id, mode, rows)) => {
             // Notify via WebSocket
             let _ = state.event_sender.send(UiEvent::FileOpened {
                 document_id: document_id.clone(),
@@ -298,6 +352,8 @@ async fn open_file(
             Json(serde_json::json!({
                 "success": true,
                 "document_id": document_id,
+                "mode": mode,
+                "rows": rows,
                 "message": "File opened successfully"
             }))
         }
@@ -310,16 +366,22 @@ async fn open_file(
     }
 }
 
-/// Save document
+/// Save document. A file changed on disk since it was opened is handled as the
+/// `on_conflict` query says, or as configured; `prompt` reports it with `"conflict": true`.
 async fn save_document(
     State(state): State<AppState>,
     Path(document_id): Path<String>,
+    Query(query): Query<SaveQuery>,
 ) -> impl IntoResponse {
     let editor = state.ide.editor();
     let editor_lock = editor.lock().await;
     
-    match editor_lock.save_active_document().await {
-        Ok(_) => {
+    let saved = match query.on_conflict {
+        Some(strategy) => editor_lock.save_active_document_with(strategy).await,
+        None => editor_lock.save_active_document().await,
+    };
+    match saved {
+        Ok(SaveOutcome::Saved) => {
             let _ = state.event_sender.send(UiEvent::FileSaved {
                 document_id: document_id.clone(),
             });
@@ -329,9 +391,17 @@ async fn save_document(
                 "message": "Document saved successfully"
             }))
         }
+        Ok(SaveOutcome::Skipped) => {
+            Json(serde_json::json!({
+                "success": true,
+                "skipped": true,
+                "message": "Document not saved: the file changed on disk"
+            }))
+        }
         Err(e) => {
             Json(serde_json::json!({
                 "success": false,
+                "conflict": matches!(e, EditorError::SaveConflict(_)),
                 "error": e.to_string()
             }))
         }
@@ -370,6 +440,7 @@ async fn get_ai_suggestion(
         position: None,
         cursor_position: None,
         text_before_cursor: String::new(),
+        document: None,
     };
     
     match ai_engine.generate_completion(request).await {
@@ -489,6 +560,21 @@ pub struct FileInfo {
     pub is_file: bool,
 }
 
+/// Where to put the cursor in an opened file, 1-based, and whether to open it for editing
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct OpenFileQuery {
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    #[serde(default)]
+    pub mode: OpenMode,
+}
+
+/// How to save a file that changed on disk, overriding the configured strategy
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SaveQuery {
+    pub on_conflict: Option<SaveConflictStrategy>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CodeAnalysisRequest {
     pub code: String,
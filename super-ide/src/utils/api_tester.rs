@@ -0,0 +1,374 @@
+//! HTTP request tester and saved request collections
+//!
+//! Requests are sent as given, through the configured proxy, and the response is
+//! returned with its timing. Collections group named requests, Postman style, and
+//! are saved one JSON file each under `.super-ide/api-collections/` in the project.
+//! A collection's variables fill `{{name}}` placeholders in the URL, headers and
+//! body of its requests when they run.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::config::ProxySettings;
+
+/// Directory, relative to the project root, holding saved collections
+const COLLECTIONS_DIR: &str = ".super-ide/api-collections";
+
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// Response bodies are cut to this many bytes
+const MAX_RESPONSE_BYTES: usize = 1024 * 1024;
+
+/// `{{name}}` placeholders, allowing spaces inside the braces
+static VARIABLE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{\{\s*([A-Za-z0-9_.-]+)\s*\}\}").unwrap());
+
+/// API tester errors
+#[derive(Debug, thiserror::Error)]
+pub enum ApiTestError {
+    #[error("Collection not found: {0}")]
+    CollectionNotFound(String),
+
+    #[error("Request {request} not found in collection {collection}")]
+    RequestNotFound { collection: String, request: String },
+
+    #[error("Variable {{{{{0}}}}} is not defined")]
+    UndefinedVariable(String),
+
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+
+    #[error("Request timed out after {0} seconds")]
+    Timeout(u64),
+
+    #[error("Request failed: {0}")]
+    Request(String),
+
+    #[error("Invalid collection file: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// An HTTP request to send
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HttpRequestSpec {
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub body: Option<String>,
+    /// Seconds before giving up, 30 when unset
+    #[serde(default)]
+    pub timeout: Option<u64>,
+}
+
+/// Response to a sent request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpResponseSummary {
+    pub status: u16,
+    pub headers: HashMap<String, String>,
+    /// Body as text, lossily decoded and cut to 1 MiB
+    pub body: String,
+    pub truncated: bool,
+    pub duration_ms: u64,
+}
+
+/// A named request saved in a collection
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedRequest {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub request: HttpRequestSpec,
+}
+
+/// Named group of saved requests sharing a set of variables
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RequestCollection {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    /// Values for `{{name}}` placeholders, such as `base_url`
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+    #[serde(default)]
+    pub requests: Vec<SavedRequest>,
+}
+
+impl HttpRequestSpec {
+    /// This request with every `{{name}}` placeholder replaced from `variables`
+    pub fn resolve(&self, variables: &HashMap<String, String>) -> Result<Self, ApiTestError> {
+        let substitute = |text: &str| -> Result<String, ApiTestError> {
+            let mut resolved = String::with_capacity(text.len());
+            let mut last = 0;
+            for placeholder in VARIABLE.captures_iter(text) {
+                let whole = placeholder.get(0).unwrap();
+                let name = &placeholder[1];
+                let value = variables.get(name).ok_or_else(|| ApiTestError::UndefinedVariable(name.to_string()))?;
+                resolved.push_str(&text[last..whole.start()]);
+                resolved.push_str(value);
+                last = whole.end();
+            }
+            resolved.push_str(&text[last..]);
+            Ok(resolved)
+        };
+
+        Ok(Self {
+            method: self.method.clone(),
+            url: substitute(&self.url)?,
+            headers: self.headers.iter()
+                .map(|(name, value)| Ok((name.clone(), substitute(value)?)))
+                .collect::<Result<_, ApiTestError>>()?,
+            body: self.body.as_deref().map(substitute).transpose()?,
+            timeout: self.timeout,
+        })
+    }
+
+    /// Send the request and read its response
+    pub async fn send(&self, proxy: &ProxySettings) -> Result<HttpResponseSummary, ApiTestError> {
+        let method = reqwest::Method::from_bytes(self.method.trim().to_uppercase().as_bytes())
+            .map_err(|_| ApiTestError::InvalidRequest(format!("unknown method {}", self.method)))?;
+        let url = reqwest::Url::parse(&self.url)
+            .map_err(|e| ApiTestError::InvalidRequest(format!("invalid URL {}: {}", self.url, e)))?;
+        let timeout = self.timeout.unwrap_or(DEFAULT_TIMEOUT_SECS);
+
+        let client = super::http::client_builder(proxy)
+            .and_then(|builder| builder.timeout(Duration::from_secs(timeout)).build())
+            .map_err(|e| ApiTestError::InvalidRequest(e.to_string()))?;
+        let mut request = client.request(method, url);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        if let Some(body) = &self.body {
+            request = request.body(body.clone());
+        }
+
+        let started = Instant::now();
+        let failed = |e: reqwest::Error| {
+            if e.is_timeout() {
+                ApiTestError::Timeout(timeout)
+            } else if let Some(message) = super::http::proxy_failure(proxy, &e) {
+                ApiTestError::Request(message)
+            } else {
+                ApiTestError::Request(e.to_string())
+            }
+        };
+        let mut response = request.send().await.map_err(failed)?;
+
+        let status = response.status().as_u16();
+        let headers = response.headers().iter()
+            .map(|(name, value)| (name.to_string(), String::from_utf8_lossy(value.as_bytes()).to_string()))
+            .collect();
+        // Stop reading at the limit rather than buffer an endless or huge body
+        let mut bytes = Vec::new();
+        let mut truncated = false;
+        while let Some(chunk) = response.chunk().await.map_err(failed)? {
+            let room = MAX_RESPONSE_BYTES - bytes.len();
+            if chunk.len() > room {
+                bytes.extend_from_slice(&chunk[..room]);
+                truncated = true;
+                break;
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        let body = String::from_utf8_lossy(&bytes).to_string();
+
+        Ok(HttpResponseSummary {
+            status,
+            headers,
+            body,
+            truncated,
+            duration_ms: started.elapsed().as_millis() as u64,
+        })
+    }
+}
+
+/// Saved collections of one project
+pub struct CollectionStore {
+    dir: PathBuf,
+}
+
+impl CollectionStore {
+    pub fn new(project_root: &Path) -> Self {
+        Self { dir: project_root.join(COLLECTIONS_DIR) }
+    }
+
+    /// All collections, by name
+    pub async fn list(&self) -> Result<Vec<RequestCollection>, ApiTestError> {
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut collections = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.path().extension().is_some_and(|extension| extension == "json") {
+                collections.push(serde_json::from_slice(&tokio::fs::read(entry.path()).await?)?);
+            }
+        }
+        collections.sort_by(|a: &RequestCollection, b| a.name.cmp(&b.name));
+        Ok(collections)
+    }
+
+    pub async fn get(&self, id: &str) -> Result<RequestCollection, ApiTestError> {
+        let path = self.path(id)?;
+        match tokio::fs::read(&path).await {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(ApiTestError::CollectionNotFound(id.to_string())),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Save `collection`, giving it and its requests ids when they have none, and
+    /// return it as saved
+    pub async fn save(&self, mut collection: RequestCollection) -> Result<RequestCollection, ApiTestError> {
+        if collection.id.is_empty() {
+            collection.id = uuid::Uuid::new_v4().simple().to_string();
+        }
+        for request in collection.requests.iter_mut().filter(|request| request.id.is_empty()) {
+            request.id = uuid::Uuid::new_v4().simple().to_string();
+        }
+
+        let path = self.path(&collection.id)?;
+        tokio::fs::create_dir_all(&self.dir).await?;
+        crate::file_ops::atomic::write_atomic(&path, &serde_json::to_vec_pretty(&collection)?, false).await?;
+        Ok(collection)
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<(), ApiTestError> {
+        match tokio::fs::remove_file(self.path(id)?).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(ApiTestError::CollectionNotFound(id.to_string())),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Send a saved request with the collection's variables, `overrides` taking precedence
+    pub async fn run(
+        &self,
+        collection_id: &str,
+        request_id: &str,
+        overrides: &HashMap<String, String>,
+        proxy: &ProxySettings,
+    ) -> Result<HttpResponseSummary, ApiTestError> {
+        let collection = self.get(collection_id).await?;
+        let saved = collection.requests.iter()
+            .find(|request| request.id == request_id)
+            .ok_or_else(|| ApiTestError::RequestNotFound {
+                collection: collection_id.to_string(),
+                request: request_id.to_string(),
+            })?;
+
+        let mut variables = collection.variables.clone();
+        variables.extend(overrides.iter().map(|(name, value)| (name.clone(), value.clone())));
+        saved.request.resolve(&variables)?.send(proxy).await
+    }
+
+    /// File of collection `id`; ids are generated, so anything but a plain name is rejected
+    fn path(&self, id: &str) -> Result<PathBuf, ApiTestError> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(ApiTestError::CollectionNotFound(id.to_string()));
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_saved_request_resolves_variables_and_runs() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            while !request.ends_with(b"\r\n\r\n") {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            let body = r#"{"status":"ok"}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&request).to_string()
+        });
+
+        let root = std::env::temp_dir().join(format!("super-ide-collections-{}", uuid::Uuid::new_v4()));
+        let store = CollectionStore::new(&root);
+        let collection = store.save(RequestCollection {
+            id: String::new(),
+            name: "Health".to_string(),
+            variables: HashMap::from([("base_url".to_string(), "http://127.0.0.1:1".to_string())]),
+            requests: vec![SavedRequest {
+                id: String::new(),
+                name: "Check".to_string(),
+                request: HttpRequestSpec {
+                    method: "get".to_string(),
+                    url: "{{base_url}}/health".to_string(),
+                    headers: HashMap::from([("x-token".to_string(), "{{ token }}".to_string())]),
+                    ..Default::default()
+                },
+            }],
+        }).await.unwrap();
+        assert_eq!(store.list().await.unwrap(), vec![collection.clone()]);
+
+        // Without a value for `token` nothing is sent
+        let request_id = &collection.requests[0].id;
+        let err = store.run(&collection.id, request_id, &HashMap::new(), &ProxySettings::default()).await.unwrap_err();
+        assert!(matches!(err, ApiTestError::UndefinedVariable(ref name) if name == "token"));
+
+        let overrides = HashMap::from([
+            ("base_url".to_string(), format!("http://{}", addr)),
+            ("token".to_string(), "secret-1".to_string()),
+        ]);
+        let response = store.run(&collection.id, request_id, &overrides, &ProxySettings::default()).await.unwrap();
+        assert_eq!((response.status, response.body.as_str()), (200, r#"{"status":"ok"}"#));
+
+        let received = server.await.unwrap();
+        assert!(received.starts_with("GET /health HTTP/1.1"), "{}", received);
+        assert!(received.contains("x-token: secret-1"), "{}", received);
+
+        store.delete(&collection.id).await.unwrap();
+        assert!(matches!(store.get(&collection.id).await, Err(ApiTestError::CollectionNotFound(_))));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_endless_response_is_cut_at_the_limit() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 4096];
+            let _ = socket.read(&mut buf).await;
+            socket.write_all(b"HTTP/1.1 200 OK\r\ncontent-type: text/plain\r\ntransfer-encoding: chunked\r\n\r\n").await.unwrap();
+            // Keeps streaming until the client hangs up
+            let chunk = format!("{:x}\r\n{}\r\n", 64 * 1024, "a".repeat(64 * 1024));
+            while socket.write_all(chunk.as_bytes()).await.is_ok() {}
+        });
+
+        let request = HttpRequestSpec {
+            method: "GET".to_string(),
+            url: format!("http://{}/stream", addr),
+            ..Default::default()
+        };
+        let response = request.send(&ProxySettings::default()).await.unwrap();
+        assert!(response.truncated);
+        assert_eq!(response.body.len(), MAX_RESPONSE_BYTES);
+    }
+}
@@ -0,0 +1,221 @@
+//! Unified diffs between two versions of a text
+//!
+//! Lines are matched with a longest-common-subsequence table over the part between
+//! the common prefix and suffix. When that middle part is too large for the table,
+//! it is reported as replaced wholesale, which is still a correct diff.
+
+/// Unchanged lines shown around each change
+const CONTEXT_LINES: usize = 3;
+
+/// Largest middle section, in old × new lines, matched line by line
+const MAX_TABLE_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Unified diff turning `old` into `new`, with `---`/`+++` headers naming them
+/// `old_label` and `new_label`. Empty when the texts are equal.
+pub fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str) -> String {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let ops = diff_ops(&old_lines, &new_lines);
+    if ops.iter().all(|op| matches!(op, Op::Equal(..))) {
+        return String::new();
+    }
+
+    // Lines of each side consumed before every op, for hunk headers
+    let mut positions = Vec::with_capacity(ops.len() + 1);
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for op in &ops {
+        positions.push((old_pos, new_pos));
+        match op {
+            Op::Equal(..) => {
+                old_pos += 1;
+                new_pos += 1;
+            }
+            Op::Delete(_) => old_pos += 1,
+            Op::Insert(_) => new_pos += 1,
+        }
+    }
+
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for (i, _) in ops.iter().enumerate().filter(|(_, op)| !matches!(op, Op::Equal(..))) {
+        let start = i.saturating_sub(CONTEXT_LINES);
+        let end = (i + 1 + CONTEXT_LINES).min(ops.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut diff = format!("--- {}\n+++ {}\n", old_label, new_label);
+    for (start, end) in hunks {
+        let hunk = &ops[start..end];
+        let old_count = hunk.iter().filter(|op| !matches!(op, Op::Insert(_))).count();
+        let new_count = hunk.iter().filter(|op| !matches!(op, Op::Delete(_))).count();
+        let (old_start, new_start) = positions[start];
+        diff.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, old_count),
+            hunk_range(new_start, new_count)
+        ));
+        for op in hunk {
+            let (prefix, line) = match *op {
+                Op::Equal(o, _) => (' ', old_lines[o]),
+                Op::Delete(o) => ('-', old_lines[o]),
+                Op::Insert(n) => ('+', new_lines[n]),
+            };
+            diff.push(prefix);
+            diff.push_str(line);
+            if !line.ends_with('\n') {
+                diff.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+    diff
+}
+
+/// `start,count` as in a hunk header; an empty range names the line before it
+fn hunk_range(lines_before: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", lines_before),
+        1 => format!("{}", lines_before + 1),
+        _ => format!("{},{}", lines_before + 1, count),
+    }
+}
+
+fn diff_ops(old: &[&str], new: &[&str]) -> Vec<Op> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_end, new_end) = (old.len() - suffix, new.len() - suffix);
+
+    let mut ops: Vec<Op> = (0..prefix).map(|i| Op::Equal(i, i)).collect();
+    let (middle_old, middle_new) = (old_end - prefix, new_end - prefix);
+    if middle_old.saturating_mul(middle_new) <= MAX_TABLE_CELLS {
+        ops.extend(lcs_ops(&old[prefix..old_end], &new[prefix..new_end], prefix, prefix));
+    } else {
+        ops.extend((prefix..old_end).map(Op::Delete));
+        ops.extend((prefix..new_end).map(Op::Insert));
+    }
+    ops.extend((0..suffix).map(|i| Op::Equal(old_end + i, new_end + i)));
+    ops
+}
+
+/// Edit script for `old` → `new` keeping their longest common subsequence, with
+/// indices offset by where the slices start. Deletions come before insertions.
+fn lcs_ops(old: &[&str], new: &[&str], old_offset: usize, new_offset: usize) -> Vec<Op> {
+    let width = new.len() + 1;
+    // Length of the common subsequence of old[i..] and new[j..]
+    let mut table = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            table[i * width + j] = if old[i] == new[j] {
+                table[(i + 1) * width + j + 1] + 1
+            } else {
+                table[(i + 1) * width + j].max(table[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::with_capacity(old.len() + new.len());
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ops.push(Op::Equal(old_offset + i, new_offset + j));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || table[(i + 1) * width + j] >= table[i * width + j + 1]) {
+            ops.push(Op::Delete(old_offset + i));
+            i += 1;
+        } else {
+            ops.push(Op::Insert(new_offset + j));
+            j += 1;
+        }
+    }
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Apply `diff` to `old`, checking that every context and removed line matches
+    fn apply(old: &str, diff: &str) -> String {
+        let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+        let mut result = String::new();
+        let mut next_old = 0;
+        let mut last: Option<char> = None;
+
+        for line in diff.split_inclusive('\n').skip(2) {
+            if let Some(header) = line.strip_prefix("@@ -") {
+                let range = header.split(' ').next().unwrap();
+                let (start, count) = range.split_once(',').unwrap_or((range, "1"));
+                let start: usize = start.parse().unwrap();
+                let first = if count == "0" { start } else { start - 1 };
+                for kept in &old_lines[next_old..first] {
+                    result.push_str(kept);
+                }
+                next_old = first;
+            } else if line.starts_with('\\') {
+                // The previous line had no newline
+                if last != Some('-') {
+                    result.pop();
+                }
+            } else {
+                let (prefix, text) = line.split_at(1);
+                last = prefix.chars().next();
+                match prefix {
+                    " " | "-" => {
+                        assert_eq!(old_lines[next_old].trim_end_matches('\n'), text.trim_end_matches('\n'));
+                        next_old += 1;
+                        if prefix == " " {
+                            result.push_str(text);
+                        }
+                    }
+                    _ => result.push_str(text),
+                }
+            }
+        }
+        for kept in &old_lines[next_old..] {
+            result.push_str(kept);
+        }
+        result
+    }
+
+    #[test]
+    fn test_diff_reproduces_new_content() {
+        let old: String = (1..=20).map(|i| format!("line {}\n", i)).collect();
+        let cases = [
+            old.replace("line 2\n", "line two\n").replace("line 15\n", ""),
+            format!("header\n{}", old),
+            format!("{}footer", old),
+            old.replace("line 20\n", "line 20"),
+            old.replace("line 10\n", "line 10\nline 10.5\n"),
+            String::new(),
+        ];
+
+        for new in &cases {
+            let diff = unified_diff(&old, new, "a/notes.txt", "b/notes.txt");
+            assert_eq!(&apply(&old, &diff), new, "{}", diff);
+        }
+        assert_eq!(apply("", &unified_diff("", &old, "a", "b")), old);
+        assert_eq!(unified_diff(&old, &old, "a", "b"), "");
+    }
+
+    #[test]
+    fn test_hunk_headers_and_context() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\nn\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\n";
+        let diff = unified_diff(old, new, "a/x", "b/x");
+
+        assert_eq!(diff, "--- a/x\n+++ b/x\n@@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n@@ -11,4 +11,3 @@\n k\n l\n m\n-n\n");
+        assert_eq!(unified_diff("x", "y", "a", "b"), "--- a\n+++ b\n@@ -1 +1 @@\n-x\n\\ No newline at end of file\n+y\n\\ No newline at end of file\n");
+    }
+}
@@ -30,6 +30,13 @@ pub enum IdeEvent {
         content: String,
         position: (usize, usize),
     },
+    /// A background parse finished; `revision` grows with each parse of the document
+    SyntaxParsed {
+        document_id: String,
+        revision: u64,
+        tree: crate::editor::SyntaxTree,
+        tokens: Vec<crate::editor::SyntaxToken>,
+    },
     
     /// AI events
     AiSuggestion {
@@ -41,6 +48,19 @@ pub enum IdeEvent {
         request_id: String,
         result: AiResult,
     },
+    AiRequestStarted {
+        id: String,
+        op: String,
+    },
+    AiRequestFinished {
+        id: String,
+        tokens: u32,
+        ms: u64,
+    },
+    AiRequestFailed {
+        id: String,
+        error: String,
+    },
     
     /// Collaboration events
     UserJoined {
@@ -390,6 +410,17 @@ pub enum EventResponse {
 
 /// Event subscriber extension for common event types
 impl EventSubscriber {
+    /// Wait for the next event, skipping over any dropped by a lagging receiver
+    pub async fn recv(&mut self) -> Option<IdeEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) => return Some(event),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
     /// Wait for the next event of a specific type
     pub async fn next_of_type<T>(&mut self) -> Option<T>
     where
@@ -430,6 +461,39 @@ impl EventSubscriber {
     }
 }
 
+/// An index of workspace files kept current from `FileChanged` events, whose
+/// paths are workspace-relative
+#[async_trait::async_trait]
+pub trait FileIndex: Send + Sync {
+    /// Re-read one file, dropping it if it no longer exists or is not indexed
+    async fn update_file(&self, relative: &str);
+
+    /// Drop one file
+    async fn remove_file(&self, relative: &str);
+
+    /// Apply a file change event to the index
+    async fn apply_event(&self, event: &IdeEvent) {
+        let IdeEvent::FileChanged { path, event_type } = event else {
+            return;
+        };
+        match event_type {
+            FileEventType::Deleted => self.remove_file(path).await,
+            FileEventType::Renamed { from, to } => {
+                self.remove_file(from).await;
+                self.update_file(to).await;
+            }
+            FileEventType::Created | FileEventType::Modified => self.update_file(path).await,
+        }
+    }
+
+    /// Keep the index current from file change events until the event bus closes
+    async fn follow(&self, mut subscriber: EventSubscriber) {
+        while let Some(event) = subscriber.recv().await {
+            self.apply_event(&event).await;
+        }
+    }
+}
+
 // Global event bus instance
 static GLOBAL_EVENT_BUS: Lazy<EventBus> = Lazy::new(|| EventBus::new());
 
@@ -6,6 +6,7 @@ use anyhow::Result;
 use thiserror::Error;
 use notify::{RecommendedWatcher, Watcher, RecursiveMode, Event, EventKind};
 use tokio::sync::mpsc;
+use crate::file_ops::encoding::{TextEncoding, DEFAULT_FALLBACK_ENCODING};
 
 /// File management errors
 #[derive(thiserror::Error, Debug)]
@@ -101,6 +102,10 @@ impl FileWatcher {
 pub struct FileManager {
     watchers: Vec<FileWatcher>,
     event_sender: mpsc::UnboundedSender<FileEvent>,
+    fsync_on_save: bool,
+    fallback_encoding: TextEncoding,
+    /// Workspace whose files are snapshotted into its local history when overwritten
+    history_root: Option<PathBuf>,
 }
 
 impl FileManager {
@@ -131,22 +136,79 @@ impl FileManager {
         Ok(Self {
             watchers: Vec::new(),
             event_sender,
+            fsync_on_save: false,
+            fallback_encoding: TextEncoding::fallback(DEFAULT_FALLBACK_ENCODING),
+            history_root: None,
         })
     }
     
+    /// Flush written files to disk before they replace the previous content
+    pub fn with_fsync_on_save(mut self, fsync_on_save: bool) -> Self {
+        self.fsync_on_save = fsync_on_save;
+        self
+    }
+
+    /// Encoding for files that are neither UTF-8 nor UTF-16, as a label like `latin1`
+    pub fn with_fallback_encoding(mut self, label: &str) -> Self {
+        self.fallback_encoding = TextEncoding::fallback(label);
+        self
+    }
+
+    /// Write files under `root` through the workspace file manager, which keeps
+    /// their previous content in the local history
+    pub fn with_local_history(mut self, root: PathBuf) -> Self {
+        self.history_root = Some(root);
+        self
+    }
+    
     /// Read file content
     pub async fn read_file(&self, path: &Path) -> Result<String, FileManagerError> {
-        fs::read_to_string(path).map_err(|e| FileManagerError::Io(e))
+        self.read_file_with_encoding(path).await.map(|(content, _)| content)
+    }
+
+    /// Read file content decoded to UTF-8, along with the encoding it was stored in
+    pub async fn read_file_with_encoding(&self, path: &Path) -> Result<(String, TextEncoding), FileManagerError> {
+        let bytes = fs::read(path)?;
+        Ok(self.decode(&bytes))
+    }
+
+    /// Decode file content read some other way, as `read_file_with_encoding` would
+    pub fn decode(&self, bytes: &[u8]) -> (String, TextEncoding) {
+        TextEncoding::decode(bytes, self.fallback_encoding)
     }
     
-    /// Write file content
+    /// Write file content through a temporary file, so the target is never half-written
     pub async fn write_file(&self, path: &Path, content: &str) -> Result<(), FileManagerError> {
+        self.write_file_with_encoding(path, content, TextEncoding::default()).await
+    }
+
+    /// Write file content in `encoding`
+    pub async fn write_file_with_encoding(&self, path: &Path, content: &str, encoding: TextEncoding) -> Result<(), FileManagerError> {
+        if let Some((root, relative)) = self.history_location(path) {
+            return crate::file_ops::FileManager::new(root)
+                .with_fsync_on_save(self.fsync_on_save)
+                .write_file_with_encoding(&relative, content, encoding)
+                .await
+                .map(|_| ())
+                .map_err(|e| FileManagerError::Io(std::io::Error::other(e)));
+        }
+
         // Create parent directories if they don't exist
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)?;
         }
         
-        fs::write(path, content).map_err(|e| FileManagerError::Io(e))
+        let bytes = encoding.encode(content)?;
+        crate::file_ops::atomic::write_atomic(path, &bytes, self.fsync_on_save)
+            .await
+            .map_err(FileManagerError::Io)
+    }
+    
+    /// The history root and `path` relative to it, when `path` is inside it
+    fn history_location(&self, path: &Path) -> Option<(PathBuf, PathBuf)> {
+        let root = std::path::absolute(self.history_root.as_ref()?).ok()?;
+        let relative = std::path::absolute(path).ok()?.strip_prefix(&root).ok()?.to_path_buf();
+        Some((root, relative))
     }
     
     /// Create a new file
@@ -367,6 +429,9 @@ impl Default for FileManager {
         Self {
             watchers: vec![],
             event_sender,
+            fsync_on_save: false,
+            fallback_encoding: TextEncoding::fallback(DEFAULT_FALLBACK_ENCODING),
+            history_root: None,
         }
     }
 }
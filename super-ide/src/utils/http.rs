@@ -0,0 +1,161 @@
+//! Outbound HTTP clients
+//!
+//! Clients that talk to other machines start from `client_builder`, so the configured
+//! proxy, or `HTTPS_PROXY` from the environment, applies to all of them. Requests to
+//! loopback addresses always go out directly.
+
+use reqwest::{ClientBuilder, NoProxy, Proxy, StatusCode, Url};
+
+use crate::config::ProxySettings;
+
+const LOOPBACK: &[&str] = &["localhost", "127.0.0.1", "::1"];
+
+/// A client builder routed through `proxy`, or reqwest's defaults when none is set
+pub fn client_builder(proxy: &ProxySettings) -> reqwest::Result<ClientBuilder> {
+    let builder = reqwest::Client::builder();
+    let Some(url) = proxy.url() else {
+        return Ok(builder);
+    };
+
+    let mut route = Proxy::all(url.as_str())?.no_proxy(NoProxy::from_string(&bypassed_hosts(proxy).join(",")));
+    if let Some(username) = proxy.username.as_deref() {
+        route = route.basic_auth(username, proxy.password.as_deref().unwrap_or(""));
+    }
+    Ok(builder.proxy(route))
+}
+
+/// Explanation for a request that failed at the proxy rather than at the server;
+/// `None` for other failures and requests that do not use a proxy
+pub fn proxy_failure(proxy: &ProxySettings, error: &reqwest::Error) -> Option<String> {
+    let url = proxy.url()?;
+    if !error.is_connect() || error.url().is_some_and(|target| is_bypassed(proxy, target)) {
+        return None;
+    }
+
+    let mut cause: &dyn std::error::Error = error;
+    while let Some(source) = cause.source() {
+        cause = source;
+    }
+    let cause = cause.to_string();
+    if cause.contains("proxy authentication required") {
+        Some(authentication_required(&url))
+    } else {
+        Some(format!("could not connect through proxy {}: {}", without_credentials(&url), cause))
+    }
+}
+
+/// Explanation for a response the proxy sent instead of the server, as it does for
+/// plain HTTP requests it refuses to forward
+pub fn proxy_status_failure(proxy: &ProxySettings, status: StatusCode) -> Option<String> {
+    if status != StatusCode::PROXY_AUTHENTICATION_REQUIRED {
+        return None;
+    }
+    proxy.url().map(|url| authentication_required(&url))
+}
+
+fn authentication_required(url: &str) -> String {
+    format!("proxy {} rejected the connection: authentication required, check the proxy username and password", without_credentials(url))
+}
+
+/// Hosts requests go to directly: loopback, the configured list and `NO_PROXY`
+fn bypassed_hosts(proxy: &ProxySettings) -> Vec<String> {
+    let from_env = std::env::var("NO_PROXY").or_else(|_| std::env::var("no_proxy")).unwrap_or_default();
+    LOOPBACK.iter().map(|host| host.to_string())
+        .chain(proxy.no_proxy.iter().cloned())
+        .chain(from_env.split(',').map(str::to_string))
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+        .collect()
+}
+
+fn is_bypassed(proxy: &ProxySettings, target: &Url) -> bool {
+    let Some(host) = target.host_str() else {
+        return false;
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    bypassed_hosts(proxy).iter().any(|entry| {
+        let domain = entry.trim_start_matches('.');
+        entry == "*" || host == domain || host.strip_suffix(domain).is_some_and(|rest| rest.ends_with('.'))
+    })
+}
+
+/// `url` with any user name and password removed, for error messages and settings shown to users
+pub fn without_credentials(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut parsed) => {
+            let _ = parsed.set_username("");
+            let _ = parsed.set_password(None);
+            parsed.to_string()
+        }
+        Err(_) => url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// A proxy answering the first request with `response` and returning what it received
+    async fn mock_proxy(response: &'static str) -> (std::net::SocketAddr, tokio::task::JoinHandle<String>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut received = Vec::new();
+            let mut buffer = [0u8; 4096];
+            while !received.windows(4).any(|window| window == b"\r\n\r\n") {
+                let read = socket.read(&mut buffer).await.unwrap();
+                if read == 0 {
+                    break;
+                }
+                received.extend_from_slice(&buffer[..read]);
+            }
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&received).to_string()
+        });
+        (addr, handle)
+    }
+
+    #[tokio::test]
+    async fn test_configured_proxy_is_used_with_credentials() {
+        let (addr, proxy_server) = mock_proxy("HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok").await;
+        let proxy = ProxySettings {
+            url: Some(format!("http://{}", addr)),
+            username: Some("build".to_string()),
+            password: Some("s3cret".to_string()),
+            no_proxy: vec![".internal.example".to_string()],
+        };
+        let client = client_builder(&proxy).unwrap().build().unwrap();
+
+        let body = client.get("http://registry.example.test/index").send().await.unwrap().text().await.unwrap();
+        assert_eq!(body, "ok");
+        let request = proxy_server.await.unwrap();
+        assert!(request.starts_with("GET http://registry.example.test/index HTTP/1.1"), "{}", request);
+        // base64 of "build:s3cret"
+        assert!(request.to_lowercase().contains("proxy-authorization: basic ynvpbgq6cznjcmv0"), "{}", request);
+
+        assert!(is_bypassed(&proxy, &Url::parse("http://localhost:12306/execute").unwrap()));
+        assert!(is_bypassed(&proxy, &Url::parse("https://pkg.internal.example/").unwrap()));
+        assert!(!is_bypassed(&proxy, &Url::parse("https://notinternal.example/").unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_tunnel_names_the_proxy() {
+        let (addr, proxy_server) = mock_proxy("HTTP/1.1 407 Proxy Authentication Required\r\ncontent-length: 0\r\n\r\n").await;
+        let proxy = ProxySettings {
+            url: Some(format!("http://user:wrong@{}", addr)),
+            ..ProxySettings::default()
+        };
+        let client = client_builder(&proxy).unwrap().build().unwrap();
+
+        let error = client.get("https://api.example.test/v1/models").send().await.unwrap_err();
+        assert!(proxy_server.await.unwrap().starts_with("CONNECT api.example.test:443"));
+        let message = proxy_failure(&proxy, &error).unwrap();
+        assert!(message.contains("authentication required"), "{}", message);
+        assert!(message.contains(&addr.to_string()) && !message.contains("wrong"), "{}", message);
+
+        assert!(proxy_status_failure(&proxy, StatusCode::PROXY_AUTHENTICATION_REQUIRED).is_some());
+        assert!(proxy_status_failure(&proxy, StatusCode::FORBIDDEN).is_none());
+    }
+}
@@ -280,4 +280,37 @@ impl Default for LanguageTools {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+/// Tree-sitter grammar of `language`, a language name or file extension
+pub fn grammar(language: &str) -> Option<tree_sitter::Language> {
+    Some(match language.to_lowercase().as_str() {
+        "rust" | "rs" => tree_sitter_rust::language(),
+        "python" | "py" => tree_sitter_python::language(),
+        "javascript" | "js" | "jsx" | "mjs" => tree_sitter_javascript::language(),
+        "typescript" | "ts" => tree_sitter_typescript::language_typescript(),
+        "tsx" => tree_sitter_typescript::language_tsx(),
+        _ => return None,
+    })
+}
+
+/// Number of syntax errors tree-sitter finds in `code`, or `None` when no parser
+/// is available for `language`
+pub fn syntax_error_count(code: &str, language: &str) -> Option<usize> {
+    let grammar = grammar(language)?;
+    let mut parser = Parser::new();
+    parser.set_language(grammar).ok()?;
+    let tree = parser.parse(code, None)?;
+
+    let mut errors = 0;
+    let mut stack = vec![tree.root_node()];
+    while let Some(node) = stack.pop() {
+        if node.is_error() || node.is_missing() {
+            errors += 1;
+        } else if node.has_error() {
+            let mut cursor = node.walk();
+            stack.extend(node.children(&mut cursor));
+        }
+    }
+    Some(errors)
+}
@@ -0,0 +1,263 @@
+//! Log output
+//!
+//! Records go to stderr, to log files under the workspace, or both, as text lines
+//! or as JSON objects for log collectors. Files live in `.super-ide/logs/`; once
+//! `super-ide.log` reaches its size limit it is renamed to `super-ide.log.1`, the
+//! older ones move up by one, and the oldest beyond the kept count is removed.
+//! Levels are set per module path, the same way `SUPER_IDE_LOG` sets them.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock, PoisonError, RwLock};
+
+use chrono::SecondsFormat;
+use env_logger::filter::{Builder as FilterBuilder, Filter};
+use log::{Log, Metadata, Record, SetLoggerError};
+
+use crate::config::{LogFormat, LoggingSettings};
+
+/// Directory of the log files, relative to the workspace
+pub const LOG_DIR: &str = ".super-ide/logs";
+
+/// Name of the file currently written to
+const LOG_FILE: &str = "super-ide.log";
+
+/// A log file that is rotated once it reaches a size
+#[derive(Debug)]
+struct RotatingFile {
+    dir: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+    len: u64,
+}
+
+impl RotatingFile {
+    fn open(dir: &Path, max_bytes: u64, max_files: usize) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let file = OpenOptions::new().create(true).append(true).open(dir.join(LOG_FILE))?;
+        let len = file.metadata()?.len();
+        Ok(Self { dir: dir.to_path_buf(), max_bytes, max_files, file, len })
+    }
+
+    /// Path of the current file for 0, of the `index`th most recent rotated one otherwise
+    fn path(&self, index: usize) -> PathBuf {
+        match index {
+            0 => self.dir.join(LOG_FILE),
+            _ => self.dir.join(format!("{}.{}", LOG_FILE, index)),
+        }
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files > 0 {
+            let _ = fs::remove_file(self.path(self.max_files));
+            for index in (0..self.max_files).rev() {
+                let from = self.path(index);
+                if from.exists() {
+                    fs::rename(&from, self.path(index + 1))?;
+                }
+            }
+        }
+        self.file = File::create(self.path(0))?;
+        self.len = 0;
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let bytes = line.len() as u64 + 1;
+        if self.len > 0 && self.len + bytes > self.max_bytes {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.len += bytes;
+        Ok(())
+    }
+}
+
+/// Logger writing to the destinations of [`LoggingSettings`]
+#[derive(Debug)]
+pub struct IdeLogger {
+    filter: Filter,
+    format: LogFormat,
+    stderr: bool,
+    file: Option<Mutex<RotatingFile>>,
+}
+
+impl IdeLogger {
+    /// Logger for `settings`, with its files under `workspace`. `directives` in
+    /// `SUPER_IDE_LOG` syntax, e.g. `debug,tower_http=warn`, override the
+    /// configured levels.
+    pub fn new(settings: &LoggingSettings, workspace: &Path, directives: Option<&str>) -> io::Result<Self> {
+        let mut filter = FilterBuilder::new();
+        filter.parse(&settings.level);
+        for (module, level) in &settings.modules {
+            filter.parse(&format!("{}={}", module, level));
+        }
+        if let Some(directives) = directives {
+            filter.parse(directives);
+        }
+
+        let file = if settings.file {
+            let dir = workspace.join(LOG_DIR);
+            Some(Mutex::new(RotatingFile::open(&dir, settings.max_file_bytes, settings.max_files)?))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            filter: filter.build(),
+            format: settings.format,
+            stderr: settings.stderr,
+            file,
+        })
+    }
+
+    /// Make this the logger of the `log` macros, replacing one installed before,
+    /// as when the settings are loaded after logging to stderr
+    pub fn install(self) -> Result<(), SetLoggerError> {
+        log::set_max_level(self.filter.filter());
+        let mut logger = Some(self);
+        let installed = INSTALLED.get_or_init(|| InstalledLogger(RwLock::new(logger.take().unwrap())));
+        match logger {
+            Some(logger) => {
+                *installed.0.write().unwrap_or_else(PoisonError::into_inner) = logger;
+                Ok(())
+            }
+            None => log::set_logger(installed),
+        }
+    }
+}
+
+/// What the `log` macros write to; `install` swaps the logger inside
+struct InstalledLogger(RwLock<IdeLogger>);
+
+static INSTALLED: OnceLock<InstalledLogger> = OnceLock::new();
+
+impl Log for InstalledLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.0.read().unwrap_or_else(PoisonError::into_inner).enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        self.0.read().unwrap_or_else(PoisonError::into_inner).log(record)
+    }
+
+    fn flush(&self) {
+        self.0.read().unwrap_or_else(PoisonError::into_inner).flush()
+    }
+}
+
+fn format_record(format: LogFormat, record: &Record) -> String {
+    let timestamp = chrono::Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+    match format {
+        LogFormat::Text => format!("[{} {:<5} {}] {}", timestamp, record.level(), record.target(), record.args()),
+        LogFormat::Json => serde_json::json!({
+            "timestamp": timestamp,
+            "level": record.level().as_str(),
+            "target": record.target(),
+            "message": record.args().to_string(),
+        })
+        .to_string(),
+    }
+}
+
+impl Log for IdeLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.filter.matches(record) {
+            return;
+        }
+        let line = format_record(self.format, record);
+        if self.stderr {
+            eprintln!("{}", line);
+        }
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                // Nowhere left to report a failing log file
+                let _ = file.write_line(&line);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.file.flush();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_file_destination_gets_the_entries() {
+        let workspace = std::env::temp_dir().join(format!("super-ide-logging-{}", uuid::Uuid::new_v4()));
+        let settings = LoggingSettings {
+            level: "info".to_string(),
+            modules: BTreeMap::from([("tower_http".to_string(), "warn".to_string())]),
+            format: LogFormat::Json,
+            stderr: false,
+            file: true,
+            max_file_bytes: 1024,
+            max_files: 1,
+        };
+        let logger = IdeLogger::new(&settings, &workspace, None).unwrap();
+
+        logger.log(&Record::builder().level(Level::Info).target("super_ide::ai").args(format_args!("Model {} ready", "gpt-4")).build());
+        logger.log(&Record::builder().level(Level::Debug).target("super_ide::ai").args(format_args!("Prompt sent")).build());
+        logger.log(&Record::builder().level(Level::Info).target("tower_http::trace").args(format_args!("GET /health")).build());
+        logger.flush();
+
+        let log_file = workspace.join(LOG_DIR).join("super-ide.log");
+        let entries: Vec<serde_json::Value> = fs::read_to_string(&log_file).unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["level"], "INFO");
+        assert_eq!(entries[0]["target"], "super_ide::ai");
+        assert_eq!(entries[0]["message"], "Model gpt-4 ready");
+
+        // `--debug` style directives override the configured levels
+        let verbose = IdeLogger::new(&settings, &workspace, Some("debug")).unwrap();
+        for index in 0..40 {
+            verbose.log(&Record::builder().level(Level::Debug).target("super_ide::ai").args(format_args!("Prompt {} sent", index)).build());
+        }
+        verbose.flush();
+        let rotated = fs::read_to_string(workspace.join(LOG_DIR).join("super-ide.log.1")).unwrap();
+        assert!(rotated.contains("Prompt"));
+        assert!(!workspace.join(LOG_DIR).join("super-ide.log.2").exists());
+        assert!(fs::metadata(&log_file).unwrap().len() <= 1024);
+
+        fs::remove_dir_all(&workspace).unwrap();
+    }
+
+    #[test]
+    fn test_installing_again_replaces_the_logger() {
+        let workspace = std::env::temp_dir().join(format!("super-ide-logging-{}", uuid::Uuid::new_v4()));
+        let settings = |file| LoggingSettings { stderr: false, file, ..LoggingSettings::default() };
+        let log_file = workspace.join(LOG_DIR).join("super-ide.log");
+
+        IdeLogger::new(&settings(false), &workspace, None).unwrap().install().unwrap();
+        log::warn!("before the settings loaded");
+        IdeLogger::new(&settings(true), &workspace, None).unwrap().install().unwrap();
+        log::warn!("after the settings loaded");
+        log::logger().flush();
+
+        let logged = fs::read_to_string(&log_file).unwrap();
+        assert!(!logged.contains("before the settings loaded"));
+        assert!(logged.contains("after the settings loaded"));
+
+        fs::remove_dir_all(&workspace).unwrap();
+    }
+}
@@ -1,6 +1,11 @@
 //! Utility modules for Super IDE
 
+pub mod api_tester;
+pub mod diff;
 pub mod file_manager;
 pub mod event_bus;
+pub mod http;
 pub mod language_tools;
-pub mod performance;
\ No newline at end of file
+pub mod logging;
+pub mod performance;
+pub mod secrets;
\ No newline at end of file